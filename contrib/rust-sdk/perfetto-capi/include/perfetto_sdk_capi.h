@@ -0,0 +1,79 @@
+/*
+ * Copyright (C) 2025 Rivos Inc.
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#ifndef PERFETTO_SDK_CAPI_H_
+#define PERFETTO_SDK_CAPI_H_
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// Backends of PerfettoRsProducerInit().
+#define PERFETTO_RS_BACKEND_IN_PROCESS (1u << 0)
+#define PERFETTO_RS_BACKEND_SYSTEM (1u << 1)
+
+// Data source type registered with PerfettoRsDataSourceRegister().
+struct PerfettoRsDataSource;
+
+// Called when an instance `inst_id` of the data source is set up with the
+// serialized DataSourceConfig in `config`.
+typedef void (*PerfettoRsOnSetupCb)(void* user_arg,
+                                    uint32_t inst_id,
+                                    const uint8_t* config,
+                                    size_t config_size);
+
+// Called when an instance `inst_id` of the data source starts or stops.
+typedef void (*PerfettoRsOnInstanceCb)(void* user_arg, uint32_t inst_id);
+
+// Callbacks of a data source type. Callbacks can be null, and may be called
+// on any thread.
+struct PerfettoRsDataSourceCallbacks {
+  PerfettoRsOnSetupCb on_setup;
+  PerfettoRsOnInstanceCb on_start;
+  PerfettoRsOnInstanceCb on_stop;
+  // Passed to the callbacks.
+  void* user_arg;
+};
+
+// Initializes the producer with the PERFETTO_RS_BACKEND_* flags in
+// `backends`. A `shmem_size_hint_kb` of 0 uses the default size.
+void PerfettoRsProducerInit(uint32_t backends, uint32_t shmem_size_hint_kb);
+
+// Registers a data source type named `name`, calling `callbacks` (which can
+// be null) for its instances. Returns null on failure. Data source types
+// stay registered for the lifetime of the process.
+struct PerfettoRsDataSource* PerfettoRsDataSourceRegister(
+    const char* name,
+    const struct PerfettoRsDataSourceCallbacks* callbacks);
+
+// Returns true if an instance of the data source is active.
+bool PerfettoRsDataSourceIsEnabled(const struct PerfettoRsDataSource*);
+
+// Writes the serialized TracePacket fields in `packet` to all the active
+// instances of the data source, and returns the number of instances written.
+uint32_t PerfettoRsDataSourceEmitPacket(const struct PerfettoRsDataSource*,
+                                        const uint8_t* packet,
+                                        size_t size);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif  // PERFETTO_SDK_CAPI_H_
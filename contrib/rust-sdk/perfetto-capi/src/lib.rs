@@ -0,0 +1,290 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![doc = include_str!("../README.md")]
+#![deny(missing_docs)]
+// The exported symbols follow the naming of the Perfetto C API.
+#![allow(non_snake_case)]
+
+use perfetto_sdk::{
+    data_source::{DataSource, DataSourceArgsBuilder},
+    producer::{Backends, Producer, ProducerInitArgsBuilder},
+    protos::trace::trace_packet::TracePacket,
+};
+use std::{
+    ffi::{CStr, c_char, c_void},
+    ptr, slice,
+};
+
+/// Backend flag of [`PerfettoRsProducerInit`] for the in-process backend.
+pub const PERFETTO_RS_BACKEND_IN_PROCESS: u32 = 1 << 0;
+
+/// Backend flag of [`PerfettoRsProducerInit`] for the system backend.
+pub const PERFETTO_RS_BACKEND_SYSTEM: u32 = 1 << 1;
+
+/// Called when an instance of a data source is set up with a serialized
+/// `DataSourceConfig`.
+pub type PerfettoRsOnSetupCb = Option<
+    unsafe extern "C" fn(
+        user_arg: *mut c_void,
+        inst_id: u32,
+        config: *const u8,
+        config_size: usize,
+    ),
+>;
+
+/// Called when an instance of a data source starts or stops.
+pub type PerfettoRsOnInstanceCb = Option<unsafe extern "C" fn(user_arg: *mut c_void, inst_id: u32)>;
+
+/// Callbacks of a data source type registered with
+/// [`PerfettoRsDataSourceRegister`].
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PerfettoRsDataSourceCallbacks {
+    /// Called when an instance is set up.
+    pub on_setup: PerfettoRsOnSetupCb,
+    /// Called when an instance starts.
+    pub on_start: PerfettoRsOnInstanceCb,
+    /// Called when an instance stops.
+    pub on_stop: PerfettoRsOnInstanceCb,
+    /// Passed to the callbacks.
+    pub user_arg: *mut c_void,
+}
+
+// Callbacks shared with the data source runtime.
+#[derive(Clone, Copy)]
+struct SharedCallbacks(PerfettoRsDataSourceCallbacks);
+
+// SAFETY: `PerfettoRsDataSourceRegister` requires the callbacks and their
+// `user_arg` to be usable from any thread.
+unsafe impl Send for SharedCallbacks {}
+
+// SAFETY: See above.
+unsafe impl Sync for SharedCallbacks {}
+
+impl SharedCallbacks {
+    // Closures calling this capture the whole struct, rather than only the
+    // `user_arg` pointer.
+    fn user_arg(&self) -> *mut c_void {
+        self.0.user_arg
+    }
+}
+
+/// Data source type registered with [`PerfettoRsDataSourceRegister`].
+pub struct PerfettoRsDataSource {
+    data_source: DataSource<'static>,
+}
+
+/// Initializes the producer with the `PERFETTO_RS_BACKEND_*` flags in
+/// `backends`. A `shmem_size_hint_kb` of 0 uses the default size.
+#[unsafe(no_mangle)]
+pub extern "C" fn PerfettoRsProducerInit(backends: u32, shmem_size_hint_kb: u32) {
+    let mut args = ProducerInitArgsBuilder::new().backends(Backends::from_bits_truncate(backends));
+    if shmem_size_hint_kb != 0 {
+        args = args.shmem_size_hint_kb(shmem_size_hint_kb);
+    }
+    Producer::init(args.build());
+}
+
+/// Registers a data source type named `name`, calling `callbacks` for its
+/// instances. Returns null on failure.
+///
+/// Data source types stay registered for the lifetime of the process.
+///
+/// # Safety
+///
+/// - `name` must be a valid NUL-terminated string.
+/// - `callbacks` must be null or point to a valid
+///   [`PerfettoRsDataSourceCallbacks`], whose callbacks are safe to call with
+///   its `user_arg` on any thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn PerfettoRsDataSourceRegister(
+    name: *const c_char,
+    callbacks: *const PerfettoRsDataSourceCallbacks,
+) -> *mut PerfettoRsDataSource {
+    // SAFETY: `name` must be a valid NUL-terminated string.
+    let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() else {
+        return ptr::null_mut();
+    };
+    let mut args = DataSourceArgsBuilder::new();
+    // SAFETY: `callbacks` must be null or valid.
+    if let Some(callbacks) = unsafe { callbacks.as_ref() } {
+        let callbacks = SharedCallbacks(*callbacks);
+        if let Some(on_setup) = callbacks.0.on_setup {
+            args = args.on_setup(move |inst_id, config, _args| {
+                // SAFETY: `on_setup` must be safe to call on any thread.
+                unsafe { on_setup(callbacks.user_arg(), inst_id, config.as_ptr(), config.len()) };
+            });
+        }
+        if let Some(on_start) = callbacks.0.on_start {
+            args = args.on_start(move |inst_id, _args| {
+                // SAFETY: `on_start` must be safe to call on any thread.
+                unsafe { on_start(callbacks.user_arg(), inst_id) };
+            });
+        }
+        if let Some(on_stop) = callbacks.0.on_stop {
+            args = args.on_stop(move |inst_id, _args| {
+                // SAFETY: `on_stop` must be safe to call on any thread.
+                unsafe { on_stop(callbacks.user_arg(), inst_id) };
+            });
+        }
+    }
+    let mut data_source = DataSource::new();
+    if data_source.register(name, args.build()).is_err() {
+        return ptr::null_mut();
+    }
+    Box::into_raw(Box::new(PerfettoRsDataSource { data_source }))
+}
+
+/// Returns true if an instance of the data source is active.
+///
+/// # Safety
+///
+/// `data_source` must have been returned by [`PerfettoRsDataSourceRegister`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn PerfettoRsDataSourceIsEnabled(
+    data_source: *const PerfettoRsDataSource,
+) -> bool {
+    // SAFETY: `data_source` must be a registered data source, which is never
+    // freed.
+    unsafe { &*data_source }.data_source.is_enabled()
+}
+
+/// Writes the serialized `TracePacket` fields in `packet` to all the active
+/// instances of the data source, and returns the number of instances written.
+///
+/// # Safety
+///
+/// - `data_source` must have been returned by
+///   [`PerfettoRsDataSourceRegister`].
+/// - `packet` must point to `size` readable bytes, or be null if `size` is 0.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn PerfettoRsDataSourceEmitPacket(
+    data_source: *const PerfettoRsDataSource,
+    packet: *const u8,
+    size: usize,
+) -> u32 {
+    // SAFETY: See `PerfettoRsDataSourceIsEnabled`.
+    let data_source = &unsafe { &*data_source }.data_source;
+    let bytes = if size == 0 {
+        &[][..]
+    } else {
+        // SAFETY: `packet` must point to `size` readable bytes.
+        unsafe { slice::from_raw_parts(packet, size) }
+    };
+    data_source
+        .trace_fanout(
+            |packet: &mut TracePacket| packet.msg.append_bytes(bytes),
+            |_, _| {},
+        )
+        .instances
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use perfetto_sdk::{
+        heap_buffer::HeapBuffer,
+        pb_decoder::{PbDecoder, PbDecoderField},
+        pb_msg::{PbMsg, PbMsgWriter},
+        protos::trace::{
+            test_event::{TestEvent, TestEventFieldNumber},
+            trace_packet::TracePacketFieldNumber,
+        },
+        trace_config::TraceConfigBuilder,
+        trace_reader::TraceReader,
+        tracing_session::TracingSession,
+    };
+    use std::{
+        error::Error,
+        sync::{
+            Arc, Mutex,
+            atomic::{AtomicU32, Ordering},
+        },
+    };
+
+    static STARTED: AtomicU32 = AtomicU32::new(0);
+
+    unsafe extern "C" fn on_start(user_arg: *mut c_void, _inst_id: u32) {
+        // SAFETY: `user_arg` points to `STARTED`.
+        unsafe { &*(user_arg as *const AtomicU32) }.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn emit_packet() -> Result<(), Box<dyn Error>> {
+        const NAME: &CStr = c"com.example.capi_data_source";
+        PerfettoRsProducerInit(PERFETTO_RS_BACKEND_IN_PROCESS, 0);
+        let callbacks = PerfettoRsDataSourceCallbacks {
+            on_setup: None,
+            on_start: Some(on_start),
+            on_stop: None,
+            user_arg: &STARTED as *const AtomicU32 as *mut c_void,
+        };
+        // SAFETY: `NAME` and `callbacks` are valid.
+        let data_source = unsafe { PerfettoRsDataSourceRegister(NAME.as_ptr(), &callbacks) };
+        assert!(!data_source.is_null());
+        // The fields of a packet with a `for_testing` message, as a native
+        // component would encode them.
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(writer.stream_writer());
+        let mut msg = PbMsg::new(&writer)?;
+        TracePacket { msg: &mut msg }.set_for_testing(|for_testing: &mut TestEvent| {
+            for_testing.set_str("capi");
+        });
+        msg.finalize();
+        let mut packet = vec![0u8; writer.stream_writer().get_written_size()];
+        hb.copy_into(&mut packet);
+        // SAFETY: `data_source` is registered.
+        assert!(!unsafe { PerfettoRsDataSourceIsEnabled(data_source) });
+
+        let mut session = TracingSession::in_process()?;
+        session.setup(
+            &TraceConfigBuilder::ring_buffer(1024)
+                .data_source(NAME.to_str()?)
+                .build()?,
+        );
+        session.start_blocking();
+        assert_eq!(STARTED.load(Ordering::Relaxed), 1);
+        // SAFETY: `data_source` is registered and `packet` is readable.
+        let instances =
+            unsafe { PerfettoRsDataSourceEmitPacket(data_source, packet.as_ptr(), packet.len()) };
+        assert_eq!(instances, 1);
+        session.stop_blocking();
+
+        let data = Arc::new(Mutex::new(vec![]));
+        let data_for_read = Arc::clone(&data);
+        session.read_trace_blocking(move |chunk, _end| {
+            data_for_read.lock().unwrap().extend_from_slice(chunk);
+        });
+        let data = data.lock().unwrap();
+        let mut strs = vec![];
+        for packet in TraceReader::new(&data) {
+            for field in PbDecoder::new(&packet?) {
+                if let (id, PbDecoderField::Delimited(data)) = field?
+                    && id == TracePacketFieldNumber::ForTesting as u32
+                {
+                    for field in PbDecoder::new(data) {
+                        if let (id, PbDecoderField::Delimited(value)) = field?
+                            && id == TestEventFieldNumber::Str as u32
+                        {
+                            strs.push(value.to_vec());
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(strs, vec![b"capi".to_vec()]);
+        Ok(())
+    }
+}
@@ -14,7 +14,10 @@
 
 use {
     quote::quote,
-    syn::{Error, Expr, ExprLit, ItemFn, Lit, Token, parse_macro_input, punctuated::Punctuated},
+    syn::{
+        Data, DeriveInput, Error, Expr, ExprLit, Fields, ItemFn, Lit, LitInt, Token, Type,
+        parse_macro_input, punctuated::Punctuated,
+    },
 };
 
 #[derive(Debug, Default)]
@@ -195,3 +198,248 @@ pub fn tracefn(
     };
     result.into()
 }
+
+// Returns the `FieldType` of the config field type `ty`, the pattern of the
+// decoded field and the expression of the decoded value.
+fn config_field_type(
+    ty: &Type,
+) -> Result<
+    (
+        proc_macro2::TokenStream,
+        proc_macro2::TokenStream,
+        proc_macro2::TokenStream,
+    ),
+    Error,
+> {
+    let name = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.get_ident().map(|id| id.to_string()),
+        _ => None,
+    };
+    let decoded = match name.as_deref() {
+        Some("bool") => (
+            quote!(Bool),
+            quote!(PbDecoderField::Varint(value)),
+            quote!(value != 0),
+        ),
+        Some("i32") => (
+            quote!(I32),
+            quote!(PbDecoderField::Varint(value)),
+            quote!(value as i32),
+        ),
+        Some("i64") => (
+            quote!(I64),
+            quote!(PbDecoderField::Varint(value)),
+            quote!(value as i64),
+        ),
+        Some("u32") => (
+            quote!(U32),
+            quote!(PbDecoderField::Varint(value)),
+            quote!(value as u32),
+        ),
+        Some("u64") => (
+            quote!(U64),
+            quote!(PbDecoderField::Varint(value)),
+            quote!(value),
+        ),
+        Some("f32") => (
+            quote!(F32),
+            quote!(PbDecoderField::Fixed32(value)),
+            quote!(f32::from_bits(value)),
+        ),
+        Some("f64") => (
+            quote!(F64),
+            quote!(PbDecoderField::Fixed64(value)),
+            quote!(f64::from_bits(value)),
+        ),
+        Some("String") => (
+            quote!(String),
+            quote!(field @ PbDecoderField::Delimited(_)),
+            quote!(field.as_str()?.to_string()),
+        ),
+        _ => {
+            return Err(Error::new_spanned(
+                ty,
+                "unsupported config field type; expected bool, i32, i64, u32, u64, f32, f64 or String",
+            ));
+        }
+    };
+    Ok(decoded)
+}
+
+/// This provides a derive macro that implements
+/// `perfetto_sdk::config_schema::DataSourceConfig` for a struct, to decode it
+/// from the `DataSourceConfig` of data source instances and to publish its
+/// schema when registering the data source.
+///
+/// The `DataSourceConfig` field that carries the config is set with
+/// `#[config(field_id = N)]` on the struct. Fields are numbered in order from
+/// 1, unless set with `#[config(number = N)]`, and must be of type `bool`,
+/// `i32`, `i64`, `u32`, `u64`, `f32`, `f64` or `String`. The defaults of the
+/// schema are the values of the `Default` implementation of the struct.
+///
+/// Example:
+///
+///  ```
+/// use perfetto_sdk::{config_schema::DataSourceConfig, data_source::*};
+/// use perfetto_sdk_derive::DataSourceConfig;
+///
+/// #[derive(Debug, PartialEq, DataSourceConfig)]
+/// #[config(field_id = 1500)]
+/// struct SamplerConfig {
+///     period_ms: u32,
+///     exact: bool,
+///     #[config(number = 5)]
+///     label: String,
+/// }
+///
+/// impl Default for SamplerConfig {
+///     fn default() -> Self {
+///         Self {
+///             period_ms: 100,
+///             exact: false,
+///             label: "main".to_string(),
+///         }
+///     }
+/// }
+///
+/// let schema = SamplerConfig::config_schema();
+/// assert_eq!(schema.field_id(), 1500);
+/// assert_eq!(schema.default_of(1), Some("100"));
+/// assert_eq!(schema.descriptor().field(5).unwrap().name, "label");
+///
+/// // An encoded `SamplerConfig` with `period_ms` set to 10.
+/// assert_eq!(
+///     SamplerConfig::decode(&[0x08, 10]).unwrap(),
+///     SamplerConfig {
+///         period_ms: 10,
+///         ..Default::default()
+///     }
+/// );
+///
+/// let data_source_args = DataSourceArgsBuilder::new()
+///     .config_schema(SamplerConfig::config_schema())
+///     .on_setup(|_, config, _| {
+///         let config = SamplerConfig::from_data_source_config(config).unwrap();
+///         println!("sampling every {}ms", config.period_ms);
+///     });
+/// ```
+#[proc_macro_derive(DataSourceConfig, attributes(config))]
+pub fn derive_data_source_config(item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    match data_source_config(&input) {
+        Ok(result) => result.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn data_source_config(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let mut field_id: Option<u32> = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("config"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("field_id") {
+                field_id = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("invalid attribute argument"))
+            }
+        })?;
+    }
+    let Some(field_id) = field_id else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "missing required `#[config(field_id = N)]` attribute",
+        ));
+    };
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "DataSourceConfig can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "DataSourceConfig can only be derived for structs with named fields",
+        ));
+    };
+    let mut descriptors = Vec::new();
+    let mut defaults = Vec::new();
+    let mut decoders = Vec::new();
+    let mut next_number = 1u32;
+    for field in &fields.named {
+        let mut number = next_number;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("config"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("number") {
+                    number = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                    Ok(())
+                } else {
+                    Err(meta.error("invalid attribute argument"))
+                }
+            })?;
+        }
+        next_number = number + 1;
+        let ident = field.ident.as_ref().unwrap();
+        let name = ident.to_string();
+        let (field_type, pattern, value) = config_field_type(&field.ty)?;
+        descriptors.push(quote! {
+            FieldDescriptor {
+                name: #name,
+                number: #number,
+                field_type: FieldType::#field_type,
+            }
+        });
+        defaults.push(quote! {
+            .default_value(#number, &defaults.#ident)
+        });
+        decoders.push(quote! {
+            (#number, #pattern) => config.#ident = #value,
+        });
+    }
+    let struct_name = &input.ident;
+    let message_name = struct_name.to_string();
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics perfetto_sdk::config_schema::DataSourceConfig
+            for #struct_name #ty_generics #where_clause
+        {
+            const FIELD_ID: u32 = #field_id;
+
+            fn config_schema() -> perfetto_sdk::config_schema::ConfigSchema {
+                use perfetto_sdk::pb_descriptor::*;
+                static DESCRIPTOR: MessageDescriptor = MessageDescriptor {
+                    name: #message_name,
+                    fields: &[#(#descriptors),*],
+                };
+                let defaults = <Self as Default>::default();
+                perfetto_sdk::config_schema::ConfigSchema::new(#field_id, &DESCRIPTOR)
+                    #(#defaults)*
+            }
+
+            fn decode(data: &[u8]) -> Result<Self, perfetto_sdk::pb_decoder::PbDecoderError> {
+                use perfetto_sdk::pb_decoder::*;
+                let mut config = <Self as Default>::default();
+                for item in PbDecoder::new(data) {
+                    match item? {
+                        #(#decoders)*
+                        (id, _) => perfetto_sdk::sdk_log!(
+                            Warn,
+                            "Ignored unknown field {} of data source config {}",
+                            id,
+                            #message_name,
+                        ),
+                    }
+                }
+                Ok(config)
+            }
+        }
+    })
+}
@@ -0,0 +1,54 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use perfetto_sdk::{data_source::DataSourceTimestamp, producer::*};
+
+use perfetto_sdk_protos_etw::etw_exporter::*;
+
+use std::{error::Error, time::Duration};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let producer_args = ProducerInitArgsBuilder::new().backends(Backends::SYSTEM);
+    Producer::init(producer_args.build());
+    let exporter = EtwExporterBuilder::new()
+        .name("etw.export.example")
+        .register()?;
+    // A real application converts the records of its ETW session in the
+    // consumer callback. This example exports synthetic context switches
+    // between two threads of CPU 0.
+    let (first, second) = (1000, 1001);
+    loop {
+        std::thread::sleep(Duration::from_millis(10));
+        if !exporter.is_enabled() {
+            continue;
+        }
+        let timestamp = DataSourceTimestamp::now().timestamp();
+        let events: Vec<_> = [(first, second), (second, first)]
+            .into_iter()
+            .enumerate()
+            .map(|(index, (old_thread_id, new_thread_id))| EtwEvent {
+                timestamp: timestamp + index as u64 * 1_000_000,
+                cpu: 0,
+                process_id: std::process::id(),
+                thread_id: old_thread_id,
+                kind: EtwEventKind::CSwitch(CSwitchEvent {
+                    new_thread_id,
+                    old_thread_id,
+                    ..Default::default()
+                }),
+            })
+            .collect();
+        exporter.export(&events);
+    }
+}
@@ -0,0 +1,621 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::protos::trace::{
+    etw::{etw::*, etw_event::*, etw_event_bundle::*},
+    trace_packet::prelude::*,
+};
+use perfetto_sdk::{
+    data_source::{Clear, DataSource, DataSourceArgsBuilder, DataSourceError, TraceContext},
+    protos::trace::{
+        trace_packet::TracePacket,
+        track_event::{
+            debug_annotation::DebugAnnotation,
+            thread_descriptor::ThreadDescriptor,
+            track_descriptor::TrackDescriptor,
+            track_event::{TrackEvent, TrackEventType},
+        },
+    },
+    track_registry::TrackRegistry,
+};
+use std::collections::HashSet;
+
+/// Name of the data source registered by default by
+/// [`EtwExporterBuilder::register`].
+pub const ETW_DATA_SOURCE_NAME: &str = "perfetto.sdk.etw";
+
+// Maximum number of events per `EtwTraceEventBundle` packet.
+const MAX_BUNDLE_EVENTS: usize = 256;
+
+/// Converts an ETW timestamp in QueryPerformanceCounter ticks, e.g. of a
+/// session with a QPC `ClientContext`, to nanoseconds. On Windows, the SDK
+/// timestamps use the same clock, so converted ETW events line up with the
+/// events emitted by the SDK.
+pub fn qpc_to_ns(ticks: u64, frequency: u64) -> u64 {
+    (ticks as u128 * 1_000_000_000 / frequency.max(1) as u128) as u64
+}
+
+/// Context switch, from the `CSwitch` event of the kernel thread provider.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CSwitchEvent {
+    /// Id of the thread switched to.
+    pub new_thread_id: u32,
+    /// Id of the thread switched from.
+    pub old_thread_id: u32,
+    /// Priority of the thread switched to.
+    pub new_thread_priority: i32,
+    /// Priority of the thread switched from.
+    pub old_thread_priority: i32,
+    /// Index of the C-state last used by the processor.
+    pub previous_c_state: u32,
+    /// `OldThreadWaitReason` of the event.
+    pub old_thread_wait_reason: i32,
+    /// `OldThreadWaitMode` of the event.
+    pub old_thread_wait_mode: i32,
+    /// `OldThreadState` of the event.
+    pub old_thread_state: i32,
+    /// Ideal processor of the thread switched from.
+    pub old_thread_wait_ideal_processor: i32,
+    /// Time the thread switched to waited for, in clock ticks.
+    pub new_thread_wait_time: u32,
+}
+
+/// Thread readied for execution, from the `ReadyThread` event of the kernel
+/// thread provider.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadyThreadEvent {
+    /// Id of the thread readied.
+    pub thread_id: u32,
+    /// `AdjustReason` of the event.
+    pub adjust_reason: i32,
+    /// Value the priority is adjusted by.
+    pub adjust_increment: i32,
+    /// `Flag` of the event.
+    pub flag: i32,
+}
+
+/// Disk I/O, from the events of the kernel disk I/O provider.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiskIoEvent {
+    /// Opcode of the event, e.g. 10 for reads and 11 for writes.
+    pub opcode: u32,
+    /// Number of the disk.
+    pub disk_number: u32,
+    /// `IrpFlags` of the event.
+    pub irp_flags: u32,
+    /// Size of the transfer, in bytes.
+    pub transfer_size: u32,
+    /// Offset of the transfer on the disk, in bytes.
+    pub byte_offset: i64,
+    /// `FileObject` of the event.
+    pub file_object: u64,
+    /// `Irp` of the event.
+    pub irp_ptr: u64,
+    /// Duration of the I/O, in clock ticks.
+    pub response_time: i64,
+    /// Id of the thread that issued the I/O.
+    pub issuing_thread_id: u32,
+}
+
+/// Event of a user-mode provider, exported as an instant track event on the
+/// track of its thread.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UserEvent {
+    /// Name of the provider, used as the category of the track event.
+    pub provider: String,
+    /// Name of the event, e.g. its task and opcode names.
+    pub name: String,
+    /// Properties of the event, exported as debug annotations.
+    pub properties: Vec<(String, String)>,
+}
+
+/// Payload of an [`EtwEvent`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EtwEventKind {
+    /// Context switch.
+    CSwitch(CSwitchEvent),
+    /// Thread readied for execution.
+    ReadyThread(ReadyThreadEvent),
+    /// Disk I/O.
+    DiskIo(DiskIoEvent),
+    /// Event of a user-mode provider.
+    User(UserEvent),
+}
+
+/// ETW event, decoded from an event record by the ETW consumer.
+///
+/// The exporter doesn't depend on a particular ETW crate: the consumer
+/// callback of the application converts the records it receives, e.g. the
+/// `EventRecord`s of `ferrisetw`, into events, using the header of the
+/// record for the common fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EtwEvent {
+    /// Timestamp of the event in nanoseconds, in the clock of the trace, see
+    /// [`qpc_to_ns`].
+    pub timestamp: u64,
+    /// Processor the event was logged on.
+    pub cpu: u32,
+    /// Id of the process that logged the event.
+    pub process_id: u32,
+    /// Id of the thread that logged the event.
+    pub thread_id: u32,
+    /// Payload of the event.
+    pub kind: EtwEventKind,
+}
+
+// The bindings write `sint32` fields like `int32` ones, so their values are
+// zigzag-encoded here.
+fn zigzag(value: i32) -> i32 {
+    (value << 1) ^ (value >> 31)
+}
+
+fn write_kernel_event(event: &EtwEvent, etw_event: &mut EtwTraceEvent) {
+    etw_event
+        .set_timestamp(event.timestamp)
+        .set_thread_id(event.thread_id);
+    match &event.kind {
+        EtwEventKind::CSwitch(c_switch) => {
+            etw_event.set_c_switch(|msg: &mut CSwitchEtwEvent| {
+                msg.set_new_thread_id(c_switch.new_thread_id)
+                    .set_old_thread_id(c_switch.old_thread_id)
+                    .set_new_thread_priority(zigzag(c_switch.new_thread_priority))
+                    .set_old_thread_priority(zigzag(c_switch.old_thread_priority))
+                    .set_previous_c_state(c_switch.previous_c_state)
+                    .set_old_thread_wait_reason_int(c_switch.old_thread_wait_reason)
+                    .set_old_thread_wait_mode_int(c_switch.old_thread_wait_mode)
+                    .set_old_thread_state_int(zigzag(c_switch.old_thread_state))
+                    .set_old_thread_wait_ideal_processor(zigzag(
+                        c_switch.old_thread_wait_ideal_processor,
+                    ))
+                    .set_new_thread_wait_time(c_switch.new_thread_wait_time);
+            });
+        }
+        EtwEventKind::ReadyThread(ready_thread) => {
+            etw_event.set_ready_thread(|msg: &mut ReadyThreadEtwEvent| {
+                msg.set_t_thread_id(ready_thread.thread_id)
+                    .set_adjust_reason_int(ready_thread.adjust_reason)
+                    .set_adjust_increment(zigzag(ready_thread.adjust_increment))
+                    .set_flag_int(ready_thread.flag);
+            });
+        }
+        EtwEventKind::DiskIo(disk_io) => {
+            etw_event.set_disk_io(|msg: &mut DiskIoEtwEvent| {
+                msg.set_opcode(disk_io.opcode)
+                    .set_disk_number(disk_io.disk_number)
+                    .set_irp_flags(disk_io.irp_flags)
+                    .set_transfer_size(disk_io.transfer_size)
+                    .set_byte_offset(disk_io.byte_offset)
+                    .set_file_object(disk_io.file_object)
+                    .set_irp_ptr(disk_io.irp_ptr)
+                    .set_response_time(disk_io.response_time)
+                    .set_issuing_thread_id(disk_io.issuing_thread_id);
+            });
+        }
+        EtwEventKind::User(_) => {}
+    }
+}
+
+/// Incremental state of the exporter: the threads whose track descriptor
+/// has been written.
+#[derive(Default)]
+pub struct EtwExporterState {
+    threads: HashSet<(u32, u32)>,
+}
+
+impl Clear for EtwExporterState {
+    fn clear(&mut self) {
+        self.threads.clear();
+    }
+}
+
+fn write_user_event(
+    ctx: &mut TraceContext<'_, EtwExporterState>,
+    event: &EtwEvent,
+    user: &UserEvent,
+) {
+    let track_uuid =
+        TrackRegistry::global().thread_track_uuid(event.process_id as i32, event.thread_id as i32);
+    ctx.with_incremental_state(|ctx, state| {
+        if state.threads.insert((event.process_id, event.thread_id)) {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+                    desc.set_uuid(track_uuid);
+                    desc.set_thread(|thread: &mut ThreadDescriptor| {
+                        thread
+                            .set_pid(event.process_id as i32)
+                            .set_tid(event.thread_id as i64);
+                    });
+                });
+            });
+        }
+    });
+    ctx.add_packet(|packet: &mut TracePacket| {
+        packet.set_timestamp(event.timestamp);
+        packet.set_track_event(|track_event: &mut TrackEvent| {
+            track_event
+                .set_type(TrackEventType::TypeInstant)
+                .set_track_uuid(track_uuid)
+                .set_name(&user.name)
+                .set_categories(&user.provider);
+            for (name, value) in &user.properties {
+                track_event.set_debug_annotations(|annotation: &mut DebugAnnotation| {
+                    annotation.set_name(name).set_string_value(value);
+                });
+            }
+        });
+    });
+}
+
+/// ETW exporter builder.
+#[must_use = "This is a builder; remember to call `.register()` (or keep chaining)."]
+pub struct EtwExporterBuilder {
+    name: String,
+}
+
+impl Default for EtwExporterBuilder {
+    fn default() -> Self {
+        Self {
+            name: ETW_DATA_SOURCE_NAME.to_string(),
+        }
+    }
+}
+
+impl EtwExporterBuilder {
+    /// Create new ETW exporter builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name of the data source. Defaults to
+    /// [`ETW_DATA_SOURCE_NAME`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Registers the data source.
+    pub fn register(self) -> Result<EtwExporter, DataSourceError> {
+        let args = DataSourceArgsBuilder::new().handles_incremental_state_clear(true);
+        let data_source: &'static mut DataSource<'static, EtwExporterState> =
+            Box::leak(Box::new(DataSource::new_with_incremental_state_type()));
+        data_source.register(&self.name, args.build())?;
+        Ok(EtwExporter { data_source })
+    }
+}
+
+/// A data source that converts the events of an ETW session into trace
+/// packets as they are consumed, so that ETW data is merged with the events
+/// emitted by the SDK into a single trace.
+///
+/// Kernel events are written as `EtwTraceEventBundle` packets, which trace
+/// processor imports like the ETW traces of Chrome: context switches and
+/// readied threads as scheduling data and disk I/O as slices. Events of
+/// user-mode providers are written as instant track events on the tracks of
+/// their threads.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk_protos_etw::etw_exporter::*;
+///
+/// let exporter = EtwExporterBuilder::new().register().unwrap();
+/// // From the callback of the ETW consumer.
+/// exporter.export(&[EtwEvent {
+///     timestamp: qpc_to_ns(123_456_789, 10_000_000),
+///     cpu: 0,
+///     process_id: 4,
+///     thread_id: 8,
+///     kind: EtwEventKind::CSwitch(CSwitchEvent {
+///         new_thread_id: 8,
+///         old_thread_id: 0,
+///         ..Default::default()
+///     }),
+/// }]);
+/// ```
+pub struct EtwExporter {
+    data_source: &'static DataSource<'static, EtwExporterState>,
+}
+
+impl EtwExporter {
+    /// Returns whether an instance of the data source is started, e.g. to
+    /// only enable the providers of the ETW session while tracing.
+    pub fn is_enabled(&self) -> bool {
+        self.data_source.is_enabled()
+    }
+
+    /// Writes `events` to all the instances of the data source. Consecutive
+    /// kernel events of the same processor are written as a single bundle, so
+    /// consumers delivering events one at a time should batch them.
+    pub fn export(&self, events: &[EtwEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        self.data_source
+            .trace(|ctx: &mut TraceContext<'_, EtwExporterState>| {
+                let runs = events.chunk_by(|a, b| {
+                    a.cpu == b.cpu
+                        && !matches!(a.kind, EtwEventKind::User(_))
+                        && !matches!(b.kind, EtwEventKind::User(_))
+                });
+                for run in runs {
+                    if let EtwEventKind::User(user) = &run[0].kind {
+                        write_user_event(ctx, &run[0], user);
+                        continue;
+                    }
+                    for bundle in run.chunks(MAX_BUNDLE_EVENTS) {
+                        ctx.add_packet(|packet: &mut TracePacket| {
+                            packet.set_etw_events(|msg: &mut EtwTraceEventBundle| {
+                                msg.set_cpu(bundle[0].cpu);
+                                for event in bundle {
+                                    msg.set_event(|etw_event: &mut EtwTraceEvent| {
+                                        write_kernel_event(event, etw_event);
+                                    });
+                                }
+                            });
+                        });
+                    }
+                }
+            });
+    }
+
+    /// Returns the underlying data source.
+    pub fn data_source(&self) -> &'static DataSource<'static, EtwExporterState> {
+        self.data_source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::trace::trace_packet::TracePacketExtFieldNumber;
+    use perfetto_sdk::protos::trace::{
+        trace_packet::TracePacketFieldNumber,
+        track_event::{
+            debug_annotation::DebugAnnotationFieldNumber,
+            thread_descriptor::ThreadDescriptorFieldNumber,
+            track_descriptor::TrackDescriptorFieldNumber, track_event::TrackEventFieldNumber,
+        },
+    };
+    use perfetto_sdk::test_util::{acquire_test_environment, messages, record_packets, varint};
+    use std::sync::OnceLock;
+
+    fn exporter() -> &'static EtwExporter {
+        static EXPORTER: OnceLock<EtwExporter> = OnceLock::new();
+        EXPORTER.get_or_init(|| {
+            EtwExporterBuilder::new()
+                .name("com.example.etw_exporter")
+                .register()
+                .unwrap()
+        })
+    }
+
+    fn event(timestamp: u64, cpu: u32, kind: EtwEventKind) -> EtwEvent {
+        EtwEvent {
+            timestamp,
+            cpu,
+            process_id: 4,
+            thread_id: 8,
+            kind,
+        }
+    }
+
+    #[test]
+    fn qpc_conversion() {
+        assert_eq!(qpc_to_ns(30, 10_000_000), 3000);
+        assert_eq!(qpc_to_ns(u64::MAX, 1_000_000_000), u64::MAX);
+        // A zero frequency doesn't divide by zero.
+        assert_eq!(qpc_to_ns(5, 0), 5_000_000_000);
+    }
+
+    #[test]
+    fn kernel_events() {
+        let _lock = acquire_test_environment();
+        let exporter = exporter();
+        let packets = record_packets(
+            "com.example.etw_exporter",
+            |_| {},
+            || {
+                assert!(exporter.is_enabled());
+                exporter.export(&[
+                    event(
+                        100,
+                        1,
+                        EtwEventKind::CSwitch(CSwitchEvent {
+                            new_thread_id: 8,
+                            old_thread_id: 9,
+                            new_thread_priority: -2,
+                            old_thread_state: 5,
+                            ..Default::default()
+                        }),
+                    ),
+                    event(
+                        200,
+                        1,
+                        EtwEventKind::ReadyThread(ReadyThreadEvent {
+                            thread_id: 9,
+                            adjust_increment: -1,
+                            ..Default::default()
+                        }),
+                    ),
+                    event(
+                        300,
+                        2,
+                        EtwEventKind::DiskIo(DiskIoEvent {
+                            opcode: 11,
+                            transfer_size: 4096,
+                            byte_offset: 8192,
+                            ..Default::default()
+                        }),
+                    ),
+                ]);
+            },
+        );
+        let bundles: Vec<_> = packets
+            .iter()
+            .flat_map(|packet| messages(packet, TracePacketExtFieldNumber::EtwEvents as u32))
+            .collect();
+        // Events of different processors are written in different bundles.
+        assert_eq!(bundles.len(), 2);
+        assert_eq!(
+            varint(bundles[0], EtwTraceEventBundleFieldNumber::Cpu as u32),
+            Some(1)
+        );
+        assert_eq!(
+            varint(bundles[1], EtwTraceEventBundleFieldNumber::Cpu as u32),
+            Some(2)
+        );
+
+        let events = messages(bundles[0], EtwTraceEventBundleFieldNumber::Event as u32);
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            varint(events[0], EtwTraceEventFieldNumber::Timestamp as u32),
+            Some(100)
+        );
+        assert_eq!(
+            varint(events[0], EtwTraceEventFieldNumber::ThreadId as u32),
+            Some(8)
+        );
+        let c_switch = messages(events[0], EtwTraceEventFieldNumber::CSwitch as u32)[0];
+        assert_eq!(
+            varint(c_switch, CswitchEtwEventFieldNumber::NewThreadId as u32),
+            Some(8)
+        );
+        assert_eq!(
+            varint(c_switch, CswitchEtwEventFieldNumber::OldThreadId as u32),
+            Some(9)
+        );
+        // `sint32` fields are zigzag-encoded.
+        assert_eq!(
+            varint(
+                c_switch,
+                CswitchEtwEventFieldNumber::NewThreadPriority as u32
+            ),
+            Some(3)
+        );
+        assert_eq!(
+            varint(
+                c_switch,
+                CswitchEtwEventFieldNumber::OldThreadStateInt as u32
+            ),
+            Some(10)
+        );
+        let ready_thread = messages(events[1], EtwTraceEventFieldNumber::ReadyThread as u32)[0];
+        assert_eq!(
+            varint(
+                ready_thread,
+                ReadyThreadEtwEventFieldNumber::TThreadId as u32
+            ),
+            Some(9)
+        );
+        assert_eq!(
+            varint(
+                ready_thread,
+                ReadyThreadEtwEventFieldNumber::AdjustIncrement as u32
+            ),
+            Some(1)
+        );
+
+        let events = messages(bundles[1], EtwTraceEventBundleFieldNumber::Event as u32);
+        assert_eq!(events.len(), 1);
+        let disk_io = messages(events[0], EtwTraceEventFieldNumber::DiskIo as u32)[0];
+        assert_eq!(
+            varint(disk_io, DiskIoEtwEventFieldNumber::Opcode as u32),
+            Some(11)
+        );
+        assert_eq!(
+            varint(disk_io, DiskIoEtwEventFieldNumber::TransferSize as u32),
+            Some(4096)
+        );
+        assert_eq!(
+            varint(disk_io, DiskIoEtwEventFieldNumber::ByteOffset as u32),
+            Some(8192)
+        );
+    }
+
+    #[test]
+    fn user_events() {
+        let _lock = acquire_test_environment();
+        let exporter = exporter();
+        let user = EtwEventKind::User(UserEvent {
+            provider: "Example-Provider".to_string(),
+            name: "Load/Start".to_string(),
+            properties: vec![("path".to_string(), "a.dll".to_string())],
+        });
+        let packets = record_packets(
+            "com.example.etw_exporter",
+            |_| {},
+            || {
+                exporter.export(&[event(100, 0, user.clone())]);
+                exporter.export(&[event(200, 0, user.clone())]);
+            },
+        );
+        // The track of the thread is only described once.
+        let descriptors: Vec<_> = packets
+            .iter()
+            .flat_map(|packet| messages(packet, TracePacketFieldNumber::TrackDescriptor as u32))
+            .collect();
+        assert_eq!(descriptors.len(), 1);
+        let track_uuid = varint(descriptors[0], TrackDescriptorFieldNumber::Uuid as u32);
+        let thread = messages(descriptors[0], TrackDescriptorFieldNumber::Thread as u32)[0];
+        assert_eq!(
+            varint(thread, ThreadDescriptorFieldNumber::Pid as u32),
+            Some(4)
+        );
+        assert_eq!(
+            varint(thread, ThreadDescriptorFieldNumber::Tid as u32),
+            Some(8)
+        );
+
+        let events: Vec<_> = packets
+            .iter()
+            .filter_map(|packet| {
+                let track_event = messages(packet, TracePacketFieldNumber::TrackEvent as u32);
+                let timestamp = varint(packet, TracePacketFieldNumber::Timestamp as u32);
+                track_event
+                    .first()
+                    .map(|track_event| (timestamp, *track_event))
+            })
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].0, Some(100));
+        assert_eq!(events[1].0, Some(200));
+        let track_event = events[0].1;
+        assert_eq!(
+            varint(track_event, TrackEventFieldNumber::Type as u32),
+            Some(TrackEventType::TypeInstant as u64)
+        );
+        assert_eq!(
+            varint(track_event, TrackEventFieldNumber::TrackUuid as u32),
+            track_uuid
+        );
+        assert_eq!(
+            messages(track_event, TrackEventFieldNumber::Name as u32),
+            vec![b"Load/Start"]
+        );
+        assert_eq!(
+            messages(track_event, TrackEventFieldNumber::Categories as u32),
+            vec![b"Example-Provider"]
+        );
+        let annotation = messages(track_event, TrackEventFieldNumber::DebugAnnotations as u32)[0];
+        assert_eq!(
+            messages(annotation, DebugAnnotationFieldNumber::Name as u32),
+            vec![b"path"]
+        );
+        assert_eq!(
+            messages(annotation, DebugAnnotationFieldNumber::StringValue as u32),
+            vec![b"a.dll"]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Autogenerated by the ProtoZero Rust compiler plugin.
+// Invoked by contrib/rust-sdk/tools/gen_rust_protos
+// DO NOT EDIT.
+
+use crate::pb_enum;
+use crate::pb_msg;
+
+pb_enum!(ReadyThreadEtwEventAdjustReason {
+    IGNORE_THE_INCREMENT: 0,
+    APPLY_INCREMENT: 1,
+    APPLY_INCREMENT_BOOST: 2,
+});
+
+pb_enum!(ReadyThreadEtwEventTraceFlag {
+    TRACE_FLAG_UNSPECIFIED: 0,
+    THREAD_READIED: 1,
+    KERNEL_STACK_SWAPPED_OUT: 2,
+    PROCESS_ADDRESS_SWAPPED_OUT: 4,
+});
+
+pb_enum!(CSwitchEtwEventOldThreadWaitReason {
+    EXECUTIVE: 0,
+    FREE_PAGE: 1,
+    PAGE_IN: 2,
+    POOL_ALLOCATION: 3,
+    DELAY_EXECUTION: 4,
+    SUSPEND: 5,
+    USER_REQUEST: 6,
+    WR_EXECUTIVE: 7,
+    WR_FREE_PAGE: 8,
+    WR_PAGE_IN: 9,
+    WR_POOL_ALLOCATION: 10,
+    WR_DELAY_EXECUTION: 11,
+    WR_SUSPENDED: 12,
+    WR_USER_REQUEST: 13,
+    WR_EVENT_PAIR: 14,
+    WR_QUEUE: 15,
+    WR_LPC_RECEIVER: 16,
+    WR_LPC_REPLY: 17,
+    WR_VIRTUAL_MEMORY: 18,
+    WR_PAGE_OUT: 19,
+    WR_RENDEZ_VOUS: 20,
+    WR_KEYED_EVENT: 21,
+    WR_TERMINATED: 22,
+    WR_PROCESS_IN_SWAP: 23,
+    WR_CPU_RATE_CONTROL: 24,
+    WR_CALLOUT_STACK: 25,
+    WR_KERNEL: 26,
+    WR_RESOURCE: 27,
+    WR_PUSH_LOCK: 28,
+    WR_MUTEX: 29,
+    WR_QUANTUM_END: 30,
+    WR_DISPATCH_INT: 31,
+    WR_PREEMPTED: 32,
+    WR_YIELD_EXECUTION: 33,
+    WR_FAST_MUTEX: 34,
+    WR_GUARD_MUTEX: 35,
+    WR_RUNDOWN: 36,
+    MAXIMUM_WAIT_REASON: 37,
+});
+
+pb_enum!(CSwitchEtwEventOldThreadWaitMode {
+    KERNEL_MODE: 0,
+    USER_MODE: 1,
+});
+
+pb_enum!(CSwitchEtwEventOldThreadState {
+    INITIALIZED: 0,
+    READY: 1,
+    RUNNING: 2,
+    STANDBY: 3,
+    TERMINATED: 4,
+    WAITING: 5,
+    TRANSITION: 6,
+    DEFERRED_READY: 7,
+});
+
+pb_msg!(DiskIoEtwEvent {
+    disk_number: u32, primitive, 1,
+    irp_flags: u32, primitive, 2,
+    transfer_size: u32, primitive, 3,
+    byte_offset: i64, primitive, 4,
+    file_object: u64, primitive, 5,
+    irp_ptr: u64, primitive, 6,
+    response_time: i64, primitive, 7,
+    issuing_thread_id: u32, primitive, 8,
+    opcode: u32, primitive, 9,
+});
+
+pb_msg!(StackWalkEtwEvent {
+    trigger: String, primitive, 1,
+    callstack_iid: u64, primitive, 2,
+});
+
+pb_msg!(FileIoPathOperationEtwEvent {
+    irp_ptr: u64, primitive, 1,
+    file_object: u64, primitive, 2,
+    file_key: u64, primitive, 3,
+    extra_info: u64, primitive, 4,
+    ttid: u32, primitive, 5,
+    info_class: u32, primitive, 6,
+    file_name: String, primitive, 7,
+    opcode: u32, primitive, 8,
+});
+
+pb_msg!(FileIoOpEndEtwEvent {
+    irp_ptr: u64, primitive, 1,
+    extra_info: u64, primitive, 2,
+    nt_status: u32, primitive, 3,
+});
+
+pb_msg!(FileIoSimpleOpEtwEvent {
+    irp_ptr: u64, primitive, 1,
+    file_object: u64, primitive, 2,
+    file_key: u64, primitive, 3,
+    ttid: u32, primitive, 4,
+    opcode: u32, primitive, 5,
+});
+
+pb_msg!(FileIoReadWriteEtwEvent {
+    offset: u64, primitive, 1,
+    irp_ptr: u64, primitive, 2,
+    file_object: u64, primitive, 3,
+    file_key: u64, primitive, 4,
+    ttid: u32, primitive, 5,
+    io_size: u32, primitive, 6,
+    io_flags: u32, primitive, 7,
+    opcode: u32, primitive, 8,
+});
+
+pb_msg!(FileIoInfoEtwEvent {
+    irp_ptr: u64, primitive, 1,
+    file_object: u64, primitive, 2,
+    file_key: u64, primitive, 3,
+    extra_info: u64, primitive, 4,
+    ttid: u32, primitive, 5,
+    info_class: u32, primitive, 6,
+    opcode: u32, primitive, 7,
+});
+
+pb_msg!(FileIoDirEnumEtwEvent {
+    irp_ptr: u64, primitive, 1,
+    file_object: u64, primitive, 2,
+    file_key: u64, primitive, 3,
+    ttid: u32, primitive, 4,
+    length: u32, primitive, 5,
+    info_class: u32, primitive, 6,
+    file_index: u32, primitive, 7,
+    file_name: String, primitive, 8,
+    opcode: u32, primitive, 9,
+});
+
+pb_msg!(FileIoCreateEtwEvent {
+    irp_ptr: u64, primitive, 1,
+    file_object: u64, primitive, 2,
+    ttid: u32, primitive, 3,
+    create_options: u32, primitive, 4,
+    file_attributes: u32, primitive, 5,
+    share_access: u32, primitive, 6,
+    open_path: String, primitive, 7,
+});
+
+pb_msg!(MemInfoEtwEvent {
+    priority_levels: u32, primitive, 1,
+    zero_page_count: u64, primitive, 2,
+    free_page_count: u64, primitive, 3,
+    modified_page_count: u64, primitive, 4,
+    modified_no_write_page_count: u64, primitive, 5,
+    bad_page_count: u64, primitive, 6,
+    standby_page_counts: [primitive, u64], repeated, 7,
+    repurposed_page_counts: [primitive, u64], repeated, 8,
+    modified_page_count_page_file: u64, primitive, 9,
+    paged_pool_page_count: u64, primitive, 10,
+    non_paged_pool_page_count: u64, primitive, 11,
+    mdl_page_count: u64, primitive, 12,
+    commit_page_count: u64, primitive, 13,
+});
+
+pb_msg!(ReadyThreadEtwEvent {
+    t_thread_id: u32, primitive, 1,
+    adjust_reason: ReadyThreadEtwEventAdjustReason, enum, 2,
+    adjust_reason_int: i32, primitive, 5,
+    adjust_increment: i32, primitive, 3,
+    flag: ReadyThreadEtwEventTraceFlag, enum, 4,
+    flag_int: i32, primitive, 6,
+}
+oneof adjust_reason_enum_or_int {
+    adjust_reason,
+    adjust_reason_int,
+}
+oneof flag_enum_or_int {
+    flag,
+    flag_int,
+});
+
+pb_msg!(CSwitchEtwEvent {
+    new_thread_id: u32, primitive, 1,
+    old_thread_id: u32, primitive, 2,
+    new_thread_priority: i32, primitive, 3,
+    old_thread_priority: i32, primitive, 4,
+    previous_c_state: u32, primitive, 5,
+    old_thread_wait_reason: CSwitchEtwEventOldThreadWaitReason, enum, 6,
+    old_thread_wait_reason_int: i32, primitive, 11,
+    old_thread_wait_mode: CSwitchEtwEventOldThreadWaitMode, enum, 7,
+    old_thread_wait_mode_int: i32, primitive, 12,
+    old_thread_state: CSwitchEtwEventOldThreadState, enum, 8,
+    old_thread_state_int: i32, primitive, 13,
+    old_thread_wait_ideal_processor: i32, primitive, 9,
+    new_thread_wait_time: u32, primitive, 10,
+}
+oneof old_thread_wait_reason_enum_or_int {
+    old_thread_wait_reason,
+    old_thread_wait_reason_int,
+}
+oneof old_thread_wait_mode_enum_or_int {
+    old_thread_wait_mode,
+    old_thread_wait_mode_int,
+}
+oneof old_thread_state_enum_or_int {
+    old_thread_state,
+    old_thread_state_int,
+});
@@ -0,0 +1,52 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Autogenerated by the ProtoZero Rust compiler plugin.
+// Invoked by contrib/rust-sdk/tools/gen_rust_protos
+// DO NOT EDIT.
+
+use crate::pb_msg;
+use crate::protos::trace::etw::etw::*;
+
+pb_msg!(EtwTraceEvent {
+    timestamp: u64, primitive, 1,
+    cpu: u32, primitive, 4,
+    thread_id: u32, primitive, 5,
+    c_switch: CSwitchEtwEvent, msg, 2,
+    ready_thread: ReadyThreadEtwEvent, msg, 3,
+    mem_info: MemInfoEtwEvent, msg, 6,
+    file_io_create: FileIoCreateEtwEvent, msg, 7,
+    file_io_dir_enum: FileIoDirEnumEtwEvent, msg, 8,
+    file_io_info: FileIoInfoEtwEvent, msg, 9,
+    file_io_read_write: FileIoReadWriteEtwEvent, msg, 10,
+    file_io_simple_op: FileIoSimpleOpEtwEvent, msg, 11,
+    file_io_op_end: FileIoOpEndEtwEvent, msg, 12,
+    stack_walk: StackWalkEtwEvent, msg, 13,
+    file_io_path_operation: FileIoPathOperationEtwEvent, msg, 14,
+    disk_io: DiskIoEtwEvent, msg, 15,
+}
+oneof event {
+    c_switch,
+    ready_thread,
+    mem_info,
+    file_io_create,
+    file_io_dir_enum,
+    file_io_info,
+    file_io_read_write,
+    file_io_simple_op,
+    file_io_op_end,
+    stack_walk,
+    file_io_path_operation,
+    disk_io,
+});
@@ -0,0 +1,28 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Autogenerated by the gen_rust_protos script.
+// DO NOT EDIT.
+
+/// `etw` protos.
+#[path = "etw.pz.rs"]
+pub mod etw;
+
+/// `etw_event` protos.
+#[path = "etw_event.pz.rs"]
+pub mod etw_event;
+
+/// `etw_event_bundle` protos.
+#[path = "etw_event_bundle.pz.rs"]
+pub mod etw_event_bundle;
@@ -0,0 +1,796 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::protos::{
+    common::gpu_counter_descriptor::*,
+    config::{
+        data_source_config::DataSourceConfigExtFieldNumber,
+        gpu::gpu_counter_config::GpuCounterConfigFieldNumber,
+    },
+    trace::{gpu::gpu_counter_event::*, trace_packet::prelude::*},
+};
+use perfetto_sdk::{
+    counter_value::CounterValue,
+    data_source::{DataSourceArgsBuilder, DataSourceError, DataSourceTimestamp, TraceContext},
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField},
+    polling::{DEFAULT_POLLING_PERIOD, PollingDataSource, PollingDataSourceBuilder},
+    protos::trace::trace_packet::TracePacket,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Name of the data source registered by default by
+/// [`GpuCounterDataSourceBuilder::register`].
+pub const GPU_COUNTER_DATA_SOURCE_NAME: &str = "gpu.counters";
+
+/// Name of the counter created by [`GpuCounterSpec::gpu_frequency`].
+pub const GPU_FREQUENCY_COUNTER_NAME: &str = "GPU Frequency";
+
+/// Name of the counter created by [`GpuCounterSpec::memory_total`].
+pub const GPU_MEMORY_TOTAL_COUNTER_NAME: &str = "GPU Memory Total";
+
+/// Name of the counter created by [`GpuCounterSpec::memory_used`].
+pub const GPU_MEMORY_USED_COUNTER_NAME: &str = "GPU Memory Used";
+
+/// A counter supported by the GPU, advertised in the counter descriptor.
+///
+/// Besides counters of their own, drivers can expose the standard counters
+/// created by [`GpuCounterSpec::gpu_frequency`], [`GpuCounterSpec::memory_total`]
+/// and [`GpuCounterSpec::memory_used`], which come with the name, unit and
+/// counter group that the Perfetto UI uses to show them as GPU frequency and
+/// memory tracks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuCounterSpec {
+    /// Id of the counter, used in the samples.
+    pub counter_id: u32,
+    /// Name of the counter.
+    pub name: String,
+    /// Description of the counter, if not empty.
+    pub description: String,
+    /// Unit of the values of the counter, if known.
+    pub unit: Option<GpuCounterDescriptorMeasureUnit>,
+    /// Groups the counter belongs to.
+    pub groups: Vec<GpuCounterDescriptorGpuCounterGroup>,
+    /// Whether the counter is sampled when the config doesn't select any
+    /// counter. If no counter is selected by default, all counters are.
+    pub select_by_default: bool,
+}
+
+impl GpuCounterSpec {
+    /// Creates the spec of a counter with an id and a name.
+    pub fn new(counter_id: u32, name: impl Into<String>) -> Self {
+        Self {
+            counter_id,
+            name: name.into(),
+            description: String::new(),
+            unit: None,
+            groups: Vec::new(),
+            select_by_default: false,
+        }
+    }
+
+    /// Creates the spec of the standard GPU frequency counter, in hertz, in
+    /// the system group.
+    pub fn gpu_frequency(counter_id: u32) -> Self {
+        Self::standard(
+            counter_id,
+            GPU_FREQUENCY_COUNTER_NAME,
+            "Frequency of the GPU clock.",
+            GpuCounterDescriptorMeasureUnit::Hertz,
+            GpuCounterDescriptorGpuCounterGroup::System,
+        )
+    }
+
+    /// Creates the spec of the standard counter of the total GPU memory, in
+    /// bytes, in the memory group.
+    pub fn memory_total(counter_id: u32) -> Self {
+        Self::standard(
+            counter_id,
+            GPU_MEMORY_TOTAL_COUNTER_NAME,
+            "Total memory available to the GPU.",
+            GpuCounterDescriptorMeasureUnit::Byte,
+            GpuCounterDescriptorGpuCounterGroup::Memory,
+        )
+    }
+
+    /// Creates the spec of the standard counter of the used GPU memory, in
+    /// bytes, in the memory group.
+    pub fn memory_used(counter_id: u32) -> Self {
+        Self::standard(
+            counter_id,
+            GPU_MEMORY_USED_COUNTER_NAME,
+            "Memory currently allocated by the GPU.",
+            GpuCounterDescriptorMeasureUnit::Byte,
+            GpuCounterDescriptorGpuCounterGroup::Memory,
+        )
+    }
+
+    fn standard(
+        counter_id: u32,
+        name: &str,
+        description: &str,
+        unit: GpuCounterDescriptorMeasureUnit,
+        group: GpuCounterDescriptorGpuCounterGroup,
+    ) -> Self {
+        Self {
+            description: description.to_string(),
+            unit: Some(unit),
+            groups: vec![group],
+            ..Self::new(counter_id, name)
+        }
+    }
+}
+
+/// Fields of the `GpuCounterConfig` of a data source instance, decoded from
+/// the config passed to the `on_setup` callback.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GpuCounterInstanceConfig {
+    /// Sampling period, if set.
+    pub counter_period: Option<Duration>,
+    /// Ids of the counters to sample.
+    pub counter_ids: Vec<u32>,
+    /// Names of the counters to sample.
+    pub counter_names: Vec<String>,
+    /// Whether counters are sampled at the sample points of the command
+    /// stream instead of at a fixed period.
+    pub instrumented_sampling: bool,
+    /// Whether the GPU clock should be fixed while sampling.
+    pub fix_gpu_clock: bool,
+}
+
+impl GpuCounterInstanceConfig {
+    /// Decodes the `gpu_counter_config` field of the encoded
+    /// `DataSourceConfig` in `config`. Unknown fields are ignored.
+    pub fn decode(config: &[u8]) -> Result<Self, PbDecoderError> {
+        use GpuCounterConfigFieldNumber::*;
+        const GPU_COUNTER_CONFIG_ID: u32 = DataSourceConfigExtFieldNumber::GpuCounterConfig as u32;
+        let mut decoded = Self::default();
+        for item in PbDecoder::new(config) {
+            let (id, field) = item?;
+            if id != GPU_COUNTER_CONFIG_ID {
+                continue;
+            }
+            for item in field.as_decoder()? {
+                match item? {
+                    (id, PbDecoderField::Varint(value)) if id == CounterPeriodNs as u32 => {
+                        decoded.counter_period = Some(Duration::from_nanos(value));
+                    }
+                    (id, PbDecoderField::Varint(value)) if id == CounterIds as u32 => {
+                        decoded.counter_ids.push(value as u32);
+                    }
+                    (id, field) if id == CounterNames as u32 => {
+                        decoded.counter_names.push(field.as_str()?.to_string());
+                    }
+                    (id, PbDecoderField::Varint(value)) if id == InstrumentedSampling as u32 => {
+                        decoded.instrumented_sampling = value != 0;
+                    }
+                    (id, PbDecoderField::Varint(value)) if id == FixGpuClock as u32 => {
+                        decoded.fix_gpu_clock = value != 0;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(decoded)
+    }
+
+    // Returns the ids of the `counters` selected by the config.
+    fn select(&self, counters: &[GpuCounterSpec]) -> Vec<u32> {
+        let selected = |filter: &dyn Fn(&GpuCounterSpec) -> bool| {
+            counters
+                .iter()
+                .filter(|spec| filter(spec))
+                .map(|spec| spec.counter_id)
+                .collect()
+        };
+        if !self.counter_ids.is_empty() || !self.counter_names.is_empty() {
+            selected(&|spec| {
+                self.counter_ids.contains(&spec.counter_id)
+                    || self.counter_names.contains(&spec.name)
+            })
+        } else if counters.iter().any(|spec| spec.select_by_default) {
+            selected(&|spec| spec.select_by_default)
+        } else {
+            selected(&|_| true)
+        }
+    }
+}
+
+/// Values sampled by the sample callback of a [`GpuCounterDataSource`].
+#[derive(Debug, Default)]
+pub struct GpuCounterSamples {
+    values: Vec<(u32, CounterValue)>,
+}
+
+impl GpuCounterSamples {
+    /// Adds the value of the counter `counter_id`.
+    pub fn push(&mut self, counter_id: u32, value: impl Into<CounterValue>) -> &mut Self {
+        self.values.push((counter_id, value.into()));
+        self
+    }
+
+    /// Returns the values added so far.
+    pub fn values(&self) -> &[(u32, CounterValue)] {
+        &self.values
+    }
+}
+
+type SampleCallback = Box<dyn FnMut(&[u32], &mut GpuCounterSamples) + Send + 'static>;
+
+struct InstanceState {
+    counter_ids: Vec<u32>,
+    need_descriptor: bool,
+}
+
+struct Shared {
+    counters: Vec<GpuCounterSpec>,
+    gpu_id: Option<i32>,
+    instrumented_sampling: bool,
+    instances: Mutex<HashMap<u32, InstanceState>>,
+    sample: Mutex<(SampleCallback, GpuCounterSamples)>,
+}
+
+impl Shared {
+    fn write(&self, ctx: &mut TraceContext, timestamp: u64) {
+        let inst_id = ctx.instance_index();
+        let (counter_ids, need_descriptor) = {
+            let mut instances = self.instances.lock().unwrap();
+            let Some(state) = instances.get_mut(&inst_id) else {
+                return;
+            };
+            (
+                state.counter_ids.clone(),
+                std::mem::replace(&mut state.need_descriptor, false),
+            )
+        };
+        let mut sample = self.sample.lock().unwrap();
+        let (sample, samples) = &mut *sample;
+        samples.values.clear();
+        sample(&counter_ids, samples);
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet
+                .set_timestamp(timestamp)
+                .set_timestamp_clock_id(DataSourceTimestamp::now().clock_id());
+            packet.set_gpu_counter_event(|event: &mut GpuCounterEvent| {
+                if let Some(gpu_id) = self.gpu_id {
+                    event.set_gpu_id(gpu_id);
+                }
+                if need_descriptor {
+                    event.set_counter_descriptor(|desc: &mut GpuCounterDescriptor| {
+                        self.write_descriptor(desc, &counter_ids);
+                    });
+                }
+                event.add_counters_from(
+                    samples.values(),
+                    |counter: &mut GpuCounterEventGpuCounter, (counter_id, value)| {
+                        counter.set_counter_id(*counter_id).set_value(*value);
+                    },
+                );
+            });
+        });
+    }
+
+    fn write_descriptor(&self, desc: &mut GpuCounterDescriptor, counter_ids: &[u32]) {
+        desc.set_supports_counter_names(true);
+        if self.instrumented_sampling {
+            desc.set_supports_instrumented_sampling(true);
+        }
+        for spec in &self.counters {
+            if !counter_ids.contains(&spec.counter_id) {
+                continue;
+            }
+            desc.set_specs(|desc: &mut GpuCounterDescriptorGpuCounterSpec| {
+                desc.set_counter_id(spec.counter_id).set_name(&spec.name);
+                if !spec.description.is_empty() {
+                    desc.set_description(&spec.description);
+                }
+                if let Some(unit) = spec.unit {
+                    desc.set_numerator_units(unit);
+                }
+                for group in &spec.groups {
+                    desc.set_groups(*group);
+                }
+                if spec.select_by_default {
+                    desc.set_select_by_default(true);
+                }
+            });
+        }
+    }
+}
+
+/// GPU counter data source builder.
+#[must_use = "This is a builder; remember to call `.register()` (or keep chaining)."]
+pub struct GpuCounterDataSourceBuilder {
+    name: String,
+    default_period: Duration,
+    gpu_id: Option<i32>,
+    instrumented_sampling: bool,
+}
+
+impl Default for GpuCounterDataSourceBuilder {
+    fn default() -> Self {
+        Self {
+            name: GPU_COUNTER_DATA_SOURCE_NAME.to_string(),
+            default_period: DEFAULT_POLLING_PERIOD,
+            gpu_id: None,
+            instrumented_sampling: false,
+        }
+    }
+}
+
+impl GpuCounterDataSourceBuilder {
+    /// Create new GPU counter data source builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name of the data source. Defaults to
+    /// [`GPU_COUNTER_DATA_SOURCE_NAME`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Set the sampling period of instances whose `counter_period_ns` isn't
+    /// set.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn default_period(mut self, period: Duration) -> Self {
+        self.default_period = period;
+        self
+    }
+
+    /// Set the id of the GPU the counters belong to, for systems with more
+    /// than one GPU.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn gpu_id(mut self, gpu_id: i32) -> Self {
+        self.gpu_id = Some(gpu_id);
+        self
+    }
+
+    /// Enables instrumented sampling: instances whose config sets
+    /// `instrumented_sampling` are sampled at the points reported with
+    /// [`GpuCounterDataSource::sample_point`] instead of at a fixed period.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn instrumented_sampling(mut self, enabled: bool) -> Self {
+        self.instrumented_sampling = enabled;
+        self
+    }
+
+    /// Registers the data source and starts sampling it.
+    ///
+    /// `counters` are the counters supported by the GPU. When an instance
+    /// starts, the counters selected by its config are advertised in a
+    /// counter descriptor. `sample` is then called with the ids of the
+    /// selected counters each time the instance is sampled, and adds their
+    /// values to the samples.
+    ///
+    /// Panics if the polling thread can't be created.
+    pub fn register<F>(
+        self,
+        counters: Vec<GpuCounterSpec>,
+        sample: F,
+    ) -> Result<GpuCounterDataSource, DataSourceError>
+    where
+        F: FnMut(&[u32], &mut GpuCounterSamples) + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            counters,
+            gpu_id: self.gpu_id,
+            instrumented_sampling: self.instrumented_sampling,
+            instances: Mutex::new(HashMap::new()),
+            sample: Mutex::new((Box::new(sample), GpuCounterSamples::default())),
+        });
+        let setup_shared = Arc::clone(&shared);
+        let start_shared = Arc::clone(&shared);
+        let stop_shared = Arc::clone(&shared);
+        let args = DataSourceArgsBuilder::new()
+            .on_setup(move |inst_id, config, _| {
+                let config = GpuCounterInstanceConfig::decode(config).unwrap_or_default();
+                let state = InstanceState {
+                    counter_ids: config.select(&setup_shared.counters),
+                    need_descriptor: false,
+                };
+                setup_shared
+                    .instances
+                    .lock()
+                    .unwrap()
+                    .insert(inst_id, state);
+            })
+            .on_start(move |inst_id, _| {
+                let mut instances = start_shared.instances.lock().unwrap();
+                if let Some(state) = instances.get_mut(&inst_id) {
+                    state.need_descriptor = true;
+                }
+            })
+            .on_stop(move |inst_id, _| {
+                stop_shared.instances.lock().unwrap().remove(&inst_id);
+            });
+        let timer_shared = Arc::clone(&shared);
+        let sampler_shared = Arc::clone(&shared);
+        let instrumented_sampling = self.instrumented_sampling;
+        let polling = PollingDataSourceBuilder::new()
+            .data_source_args(args)
+            .default_period(self.default_period)
+            .period_from_config(|config| {
+                GpuCounterInstanceConfig::decode(config)
+                    .ok()
+                    .and_then(|config| config.counter_period)
+            })
+            .instrumented_from_config(move |config| {
+                instrumented_sampling
+                    && GpuCounterInstanceConfig::decode(config)
+                        .is_ok_and(|config| config.instrumented_sampling)
+            })
+            .instrumented_sampler(move |ctx: &mut TraceContext, _queue: u32, timestamp: u64| {
+                sampler_shared.write(ctx, timestamp);
+            })
+            .register(&self.name, move |ctx: &mut TraceContext| {
+                timer_shared.write(ctx, DataSourceTimestamp::now().timestamp());
+            })?;
+        Ok(GpuCounterDataSource { polling })
+    }
+}
+
+/// A data source that writes GPU counter events, for GPU vendors that only
+/// supply the counters of the GPU and a callback that samples them.
+///
+/// The data source decodes the `GpuCounterConfig` of each instance, writes a
+/// counter descriptor with the selected counters when the instance starts,
+/// and then samples them at the `counter_period_ns` of the config, or at the
+/// sample points of the command stream with instrumented sampling.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk_protos_gpu::{
+///     counter_data_source::{GpuCounterDataSourceBuilder, GpuCounterSamples, GpuCounterSpec},
+///     protos::common::gpu_counter_descriptor::GpuCounterDescriptorMeasureUnit,
+/// };
+///
+/// let mut busy = GpuCounterSpec::new(1, "busy");
+/// busy.unit = Some(GpuCounterDescriptorMeasureUnit::Percent);
+/// let counters = vec![
+///     busy,
+///     GpuCounterSpec::new(2, "bytes_read"),
+///     GpuCounterSpec::gpu_frequency(3),
+///     GpuCounterSpec::memory_used(4),
+/// ];
+/// let _data_source = GpuCounterDataSourceBuilder::new()
+///     .register(counters, |counter_ids: &[u32], samples: &mut GpuCounterSamples| {
+///         for counter_id in counter_ids {
+///             match counter_id {
+///                 1 => samples.push(*counter_id, 42.5),
+///                 _ => samples.push(*counter_id, 4096u64),
+///             };
+///         }
+///     })
+///     .unwrap();
+/// ```
+pub struct GpuCounterDataSource {
+    polling: PollingDataSource,
+}
+
+impl GpuCounterDataSource {
+    /// Reports a sample point of `queue` at `timestamp`, sampling the
+    /// instances that use instrumented sampling.
+    pub fn sample_point(&self, queue: u32, timestamp: u64) {
+        self.polling.sample_point(queue, timestamp);
+    }
+
+    /// Returns the underlying polling data source.
+    pub fn polling_data_source(&self) -> &PollingDataSource {
+        &self.polling
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::{
+        config::{data_source_config::prelude::*, gpu::gpu_counter_config::GpuCounterConfig},
+        trace::trace_packet::TracePacketExtFieldNumber,
+    };
+    use perfetto_sdk::protos::{
+        config::data_source_config::DataSourceConfig, trace::trace_packet::TracePacketFieldNumber,
+    };
+    use perfetto_sdk::test_util::{
+        acquire_test_environment, fields, messages, record_packets, varint, varints,
+    };
+    use std::{
+        sync::{
+            OnceLock,
+            atomic::{AtomicU32, Ordering},
+        },
+        time::Instant,
+    };
+
+    const DATA_SOURCE_NAME: &str = "com.example.gpu_counters";
+
+    static SAMPLES: AtomicU32 = AtomicU32::new(0);
+
+    fn data_source() -> &'static GpuCounterDataSource {
+        static DATA_SOURCE: OnceLock<GpuCounterDataSource> = OnceLock::new();
+        DATA_SOURCE.get_or_init(|| {
+            let mut busy = GpuCounterSpec::new(1, "busy");
+            busy.unit = Some(GpuCounterDescriptorMeasureUnit::Percent);
+            let counters = vec![
+                busy,
+                GpuCounterSpec::new(2, "bytes_read"),
+                GpuCounterSpec::gpu_frequency(3),
+            ];
+            GpuCounterDataSourceBuilder::new()
+                .name(DATA_SOURCE_NAME)
+                .gpu_id(7)
+                .register(
+                    counters,
+                    |counter_ids: &[u32], samples: &mut GpuCounterSamples| {
+                        for counter_id in counter_ids {
+                            match counter_id {
+                                1 => samples.push(*counter_id, 42.5),
+                                _ => samples.push(*counter_id, 1_000_000u64),
+                            };
+                        }
+                        SAMPLES.fetch_add(1, Ordering::Relaxed);
+                    },
+                )
+                .unwrap()
+        })
+    }
+
+    const MEMORY_DATA_SOURCE_NAME: &str = "com.example.gpu_memory_counters";
+    const MEMORY_TOTAL: u64 = 8 << 30;
+    const MEMORY_USED: u64 = 1 << 30;
+
+    static MEMORY_SAMPLES: AtomicU32 = AtomicU32::new(0);
+
+    fn memory_data_source() -> &'static GpuCounterDataSource {
+        static DATA_SOURCE: OnceLock<GpuCounterDataSource> = OnceLock::new();
+        DATA_SOURCE.get_or_init(|| {
+            let counters = vec![
+                GpuCounterSpec::memory_total(1),
+                GpuCounterSpec::memory_used(2),
+            ];
+            GpuCounterDataSourceBuilder::new()
+                .name(MEMORY_DATA_SOURCE_NAME)
+                .register(
+                    counters,
+                    |counter_ids: &[u32], samples: &mut GpuCounterSamples| {
+                        for counter_id in counter_ids {
+                            match counter_id {
+                                1 => samples.push(*counter_id, MEMORY_TOTAL),
+                                _ => samples.push(*counter_id, MEMORY_USED),
+                            };
+                        }
+                        MEMORY_SAMPLES.fetch_add(1, Ordering::Relaxed);
+                    },
+                )
+                .unwrap()
+        })
+    }
+
+    #[test]
+    fn standard_counters() {
+        let frequency = GpuCounterSpec::gpu_frequency(3);
+        assert_eq!(frequency.counter_id, 3);
+        assert_eq!(frequency.name, GPU_FREQUENCY_COUNTER_NAME);
+        assert_eq!(frequency.unit, Some(GpuCounterDescriptorMeasureUnit::Hertz));
+        assert_eq!(
+            frequency.groups,
+            vec![GpuCounterDescriptorGpuCounterGroup::System]
+        );
+        assert!(!frequency.select_by_default);
+        for (spec, name) in [
+            (
+                GpuCounterSpec::memory_total(4),
+                GPU_MEMORY_TOTAL_COUNTER_NAME,
+            ),
+            (GpuCounterSpec::memory_used(5), GPU_MEMORY_USED_COUNTER_NAME),
+        ] {
+            assert_eq!(spec.name, name);
+            assert!(!spec.description.is_empty());
+            assert_eq!(spec.unit, Some(GpuCounterDescriptorMeasureUnit::Byte));
+            assert_eq!(
+                spec.groups,
+                vec![GpuCounterDescriptorGpuCounterGroup::Memory]
+            );
+        }
+    }
+
+    #[test]
+    fn decode_config() {
+        let mut busy = GpuCounterSpec::new(1, "busy");
+        busy.select_by_default = true;
+        let counters = vec![busy, GpuCounterSpec::new(2, "bytes_read")];
+        let config = GpuCounterInstanceConfig {
+            counter_names: vec!["bytes_read".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(config.select(&counters), vec![2]);
+        assert_eq!(
+            GpuCounterInstanceConfig::default().select(&counters),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn counter_events() {
+        let _lock = acquire_test_environment();
+        let _data_source = data_source();
+        SAMPLES.store(0, Ordering::Relaxed);
+        let packets = record_packets(
+            DATA_SOURCE_NAME,
+            |ds_cfg: &mut DataSourceConfig| {
+                ds_cfg.set_gpu_counter_config(|cfg: &mut GpuCounterConfig| {
+                    cfg.set_counter_period_ns(1_000_000)
+                        .set_counter_ids(1)
+                        .set_counter_ids(3);
+                });
+            },
+            || {
+                let deadline = Instant::now() + Duration::from_secs(10);
+                while SAMPLES.load(Ordering::Relaxed) < 3 && Instant::now() < deadline {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            },
+        );
+        let events: Vec<_> = packets
+            .iter()
+            .filter_map(|packet| {
+                let event = messages(packet, TracePacketExtFieldNumber::GpuCounterEvent as u32);
+                let timestamp = varint(packet, TracePacketFieldNumber::Timestamp as u32);
+                event.first().map(|event| (timestamp, *event))
+            })
+            .collect();
+        assert!(events.len() >= 3);
+
+        // The descriptor of the selected counters is only written once.
+        let descriptors: Vec<_> = events
+            .iter()
+            .map(|(_, event)| messages(event, GpuCounterEventFieldNumber::CounterDescriptor as u32))
+            .collect();
+        assert_eq!(descriptors[0].len(), 1);
+        assert!(
+            descriptors[1..]
+                .iter()
+                .all(|descriptor| descriptor.is_empty())
+        );
+        let descriptor = descriptors[0][0];
+        assert_eq!(
+            varint(
+                descriptor,
+                GpuCounterDescriptorFieldNumber::SupportsCounterNames as u32
+            ),
+            Some(1)
+        );
+        let specs = messages(descriptor, GpuCounterDescriptorFieldNumber::Specs as u32);
+        assert_eq!(specs.len(), 2);
+        use GpuCounterDescriptorGpuCounterSpecFieldNumber as Spec;
+        assert_eq!(varint(specs[0], Spec::CounterId as u32), Some(1));
+        assert_eq!(messages(specs[0], Spec::Name as u32), vec![b"busy"]);
+        assert_eq!(
+            varints(specs[0], Spec::NumeratorUnits as u32),
+            vec![GpuCounterDescriptorMeasureUnit::Percent as u64]
+        );
+        assert_eq!(varint(specs[1], Spec::CounterId as u32), Some(3));
+        assert_eq!(
+            messages(specs[1], Spec::Name as u32),
+            vec![GPU_FREQUENCY_COUNTER_NAME.as_bytes()]
+        );
+        assert_eq!(
+            varints(specs[1], Spec::NumeratorUnits as u32),
+            vec![GpuCounterDescriptorMeasureUnit::Hertz as u64]
+        );
+        assert_eq!(
+            varints(specs[1], Spec::Groups as u32),
+            vec![GpuCounterDescriptorGpuCounterGroup::System as u64]
+        );
+
+        // Each sample has the values of the selected counters.
+        for (timestamp, event) in &events {
+            assert!(timestamp.is_some());
+            assert_eq!(
+                varint(event, GpuCounterEventFieldNumber::GpuId as u32),
+                Some(7)
+            );
+            let counters = messages(event, GpuCounterEventFieldNumber::Counters as u32);
+            assert_eq!(counters.len(), 2);
+            use GpuCounterEventGpuCounterFieldNumber as Counter;
+            assert_eq!(varint(counters[0], Counter::CounterId as u32), Some(1));
+            assert_eq!(
+                fields(counters[0], Counter::DoubleValue as u32),
+                vec![PbDecoderField::Fixed64(42.5f64.to_bits())]
+            );
+            assert_eq!(varint(counters[1], Counter::CounterId as u32), Some(3));
+            assert_eq!(
+                varint(counters[1], Counter::IntValue as u32),
+                Some(1_000_000)
+            );
+        }
+    }
+
+    #[test]
+    fn memory_counter_events() {
+        use GpuCounterDescriptorGpuCounterSpecFieldNumber as Spec;
+        use GpuCounterEventGpuCounterFieldNumber as Counter;
+        let _lock = acquire_test_environment();
+        let _data_source = memory_data_source();
+        MEMORY_SAMPLES.store(0, Ordering::Relaxed);
+        let packets = record_packets(
+            MEMORY_DATA_SOURCE_NAME,
+            |ds_cfg: &mut DataSourceConfig| {
+                ds_cfg.set_gpu_counter_config(|cfg: &mut GpuCounterConfig| {
+                    cfg.set_counter_period_ns(1_000_000)
+                        .set_counter_ids(1)
+                        .set_counter_ids(2);
+                });
+            },
+            || {
+                let deadline = Instant::now() + Duration::from_secs(10);
+                while MEMORY_SAMPLES.load(Ordering::Relaxed) < 2 && Instant::now() < deadline {
+                    std::thread::sleep(Duration::from_millis(1));
+                }
+            },
+        );
+        let events: Vec<_> = packets
+            .iter()
+            .flat_map(|packet| messages(packet, TracePacketExtFieldNumber::GpuCounterEvent as u32))
+            .collect();
+        assert!(events.len() >= 2);
+
+        // The standard counters are advertised with their unit and group, so
+        // that the UI shows them as memory tracks.
+        let descriptor = messages(
+            events[0],
+            GpuCounterEventFieldNumber::CounterDescriptor as u32,
+        );
+        let specs = messages(descriptor[0], GpuCounterDescriptorFieldNumber::Specs as u32);
+        assert_eq!(specs.len(), 2);
+        for (spec, (counter_id, name)) in specs.iter().zip([
+            (1, GPU_MEMORY_TOTAL_COUNTER_NAME),
+            (2, GPU_MEMORY_USED_COUNTER_NAME),
+        ]) {
+            assert_eq!(varint(spec, Spec::CounterId as u32), Some(counter_id));
+            assert_eq!(messages(spec, Spec::Name as u32), vec![name.as_bytes()]);
+            assert_eq!(messages(spec, Spec::Description as u32).len(), 1);
+            assert_eq!(
+                varints(spec, Spec::NumeratorUnits as u32),
+                vec![GpuCounterDescriptorMeasureUnit::Byte as u64]
+            );
+            assert_eq!(
+                varints(spec, Spec::Groups as u32),
+                vec![GpuCounterDescriptorGpuCounterGroup::Memory as u64]
+            );
+        }
+
+        // Sizes in bytes are written as integers.
+        for event in &events {
+            let counters = messages(event, GpuCounterEventFieldNumber::Counters as u32);
+            let values: Vec<_> = counters
+                .iter()
+                .map(|counter| {
+                    assert!(fields(counter, Counter::DoubleValue as u32).is_empty());
+                    (
+                        varint(counter, Counter::CounterId as u32).unwrap(),
+                        varint(counter, Counter::IntValue as u32).unwrap(),
+                    )
+                })
+                .collect();
+            assert_eq!(values, [(1, MEMORY_TOTAL), (2, MEMORY_USED)]);
+        }
+    }
+}
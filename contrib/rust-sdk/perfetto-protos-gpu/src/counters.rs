@@ -0,0 +1,71 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::protos::trace::gpu::gpu_counter_event::GpuCounterEventGpuCounter;
+use perfetto_sdk::counter_value::CounterValue;
+
+impl GpuCounterEventGpuCounter<'_, '_> {
+    /// Set the `int_value` or `double_value` field, depending on the type of
+    /// `value`.
+    pub fn set_value(&mut self, value: impl Into<CounterValue>) -> &mut Self {
+        match value.into() {
+            CounterValue::Int(value) => self.set_int_value(value),
+            CounterValue::Double(value) => self.set_double_value(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::trace::gpu::gpu_counter_event::GpuCounterEventGpuCounterFieldNumber as Counter;
+    use perfetto_sdk::{
+        heap_buffer::HeapBuffer,
+        pb_decoder::PbDecoderField,
+        pb_msg::{PbMsg, PbMsgWriter},
+        test_util::{fields, varint},
+    };
+
+    fn encode_value(value: impl Into<CounterValue>) -> Vec<u8> {
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(writer.stream_writer());
+        let mut msg = PbMsg::new(&writer).unwrap();
+        GpuCounterEventGpuCounter { msg: &mut msg }.set_value(value);
+        msg.finalize();
+        let mut data = vec![0u8; writer.stream_writer().get_written_size()];
+        hb.copy_into(&mut data);
+        data
+    }
+
+    #[test]
+    fn set_value() {
+        let data = encode_value(-3i32);
+        assert_eq!(varint(&data, Counter::IntValue as u32), Some(-3i64 as u64));
+        assert!(fields(&data, Counter::DoubleValue as u32).is_empty());
+
+        let data = encode_value(0.25f64);
+        assert_eq!(
+            fields(&data, Counter::DoubleValue as u32),
+            vec![PbDecoderField::Fixed64(0.25f64.to_bits())]
+        );
+        assert!(fields(&data, Counter::IntValue as u32).is_empty());
+
+        // Values that don't fit in an int64 are written as doubles.
+        let data = encode_value(u64::MAX);
+        assert_eq!(
+            fields(&data, Counter::DoubleValue as u32),
+            vec![PbDecoderField::Fixed64((u64::MAX as f64).to_bits())]
+        );
+    }
+}
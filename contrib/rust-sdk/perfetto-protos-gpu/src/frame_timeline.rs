@@ -0,0 +1,435 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::protos::trace::{android::frame_timeline_event::*, trace_packet::prelude::*};
+use perfetto_sdk::{
+    data_source::{DataSourceTimestamp, TraceContextBase},
+    protos::trace::trace_packet::TracePacket,
+};
+use std::{
+    ops::Range,
+    sync::atomic::{AtomicI64, Ordering},
+};
+
+/// Name of the data source that the Perfetto UI expects frame timeline
+/// events from.
+pub const FRAME_TIMELINE_DATA_SOURCE_NAME: &str = "android.surfaceflinger.frametimeline";
+
+// Cookies connect the start and end events of a frame, and must be unique in
+// a trace.
+static NEXT_COOKIE: AtomicI64 = AtomicI64::new(1);
+
+/// Jank classification of an actual frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameJank {
+    /// When the frame was presented, relative to its expected timeline.
+    pub present_type: FrameTimelineEventPresentType,
+    /// Bitmask of the `FrameTimelineEventJankType` reasons of the jank.
+    pub jank_type: i32,
+    /// Severity of the jank, if known.
+    pub jank_severity_type: Option<FrameTimelineEventJankSeverityType>,
+    /// Whether the expected timeline of the frame was still valid.
+    pub prediction_type: FrameTimelineEventPredictionType,
+    /// Whether the frame finished before its deadline.
+    pub on_time_finish: bool,
+    /// Whether the frame was composited by the GPU.
+    pub gpu_composition: bool,
+}
+
+impl Default for FrameJank {
+    fn default() -> Self {
+        Self {
+            present_type: FrameTimelineEventPresentType::PresentOnTime,
+            jank_type: FrameTimelineEventJankType::JankNone as i32,
+            jank_severity_type: None,
+            prediction_type: FrameTimelineEventPredictionType::PredictionValid,
+            on_time_finish: true,
+            gpu_composition: false,
+        }
+    }
+}
+
+impl FrameJank {
+    /// Classification of a frame presented on time, without jank.
+    pub fn on_time() -> Self {
+        Self::default()
+    }
+
+    /// Classification of a frame presented late because of `jank_type`.
+    pub fn late(jank_type: FrameTimelineEventJankType) -> Self {
+        Self {
+            present_type: FrameTimelineEventPresentType::PresentLate,
+            jank_type: jank_type as i32,
+            on_time_finish: false,
+            ..Self::default()
+        }
+    }
+
+    /// Adds `jank_type` to the reasons of the jank.
+    #[must_use]
+    pub fn with_jank_type(mut self, jank_type: FrameTimelineEventJankType) -> Self {
+        if self.jank_type == FrameTimelineEventJankType::JankNone as i32 {
+            self.jank_type = 0;
+        }
+        self.jank_type |= jank_type as i32;
+        self
+    }
+
+    /// Sets the severity of the jank.
+    #[must_use]
+    pub fn with_severity(mut self, severity: FrameTimelineEventJankSeverityType) -> Self {
+        self.jank_severity_type = Some(severity);
+        self
+    }
+}
+
+/// Surface frame of a layer, produced by the application for a display frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SurfaceFrame<'a> {
+    /// Token of the frame, which connects its expected and actual timelines.
+    pub token: i64,
+    /// Token of the display frame that the surface frame is presented in.
+    pub display_frame_token: i64,
+    /// Name of the layer.
+    pub layer_name: &'a str,
+}
+
+/// Writes the expected and actual timelines of frames as frame timeline
+/// events, which the frame timeline view of the Perfetto UI shows as the
+/// "Expected Timeline" and "Actual Timeline" tracks of the process.
+///
+/// Frames are keyed by their token: the expected and actual timelines of a
+/// frame are written with the same token. Timestamps are in the clock of
+/// [`DataSourceTimestamp::now`]. The events are written from the trace
+/// callback of a data source, usually named
+/// [`FRAME_TIMELINE_DATA_SOURCE_NAME`].
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::data_source::{DataSource, DataSourceArgsBuilder, DataSourceTimestamp};
+/// use perfetto_sdk_protos_gpu::frame_timeline::*;
+///
+/// let mut data_source = DataSource::new();
+/// data_source
+///     .register(
+///         FRAME_TIMELINE_DATA_SOURCE_NAME,
+///         DataSourceArgsBuilder::new().build(),
+///     )
+///     .unwrap();
+/// let timeline = FrameTimeline::new();
+/// let start = DataSourceTimestamp::now().timestamp();
+/// data_source.trace(|ctx| {
+///     timeline.expected_display_frame(ctx, 1, start..start + 16_000_000);
+///     timeline.actual_display_frame(ctx, 1, start..start + 20_000_000, &FrameJank::default());
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameTimeline {
+    pid: i32,
+}
+
+impl Default for FrameTimeline {
+    fn default() -> Self {
+        Self::with_pid(std::process::id() as i32)
+    }
+}
+
+impl FrameTimeline {
+    /// Creates a frame timeline for the frames of the current process.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a frame timeline for the frames of the process `pid`.
+    pub fn with_pid(pid: i32) -> Self {
+        Self { pid }
+    }
+
+    /// Writes the expected timeline of the display frame `token`.
+    pub fn expected_display_frame(&self, ctx: &mut TraceContextBase, token: i64, time: Range<u64>) {
+        self.write_frame(ctx, time, |event, cookie| {
+            event.set_expected_display_frame_start(
+                |start: &mut FrameTimelineEventExpectedDisplayFrameStart| {
+                    start.set_cookie(cookie).set_token(token).set_pid(self.pid);
+                },
+            );
+        });
+    }
+
+    /// Writes the actual timeline of the display frame `token`.
+    pub fn actual_display_frame(
+        &self,
+        ctx: &mut TraceContextBase,
+        token: i64,
+        time: Range<u64>,
+        jank: &FrameJank,
+    ) {
+        self.write_frame(ctx, time, |event, cookie| {
+            event.set_actual_display_frame_start(
+                |start: &mut FrameTimelineEventActualDisplayFrameStart| {
+                    start
+                        .set_cookie(cookie)
+                        .set_token(token)
+                        .set_pid(self.pid)
+                        .set_present_type(jank.present_type)
+                        .set_on_time_finish(jank.on_time_finish)
+                        .set_gpu_composition(jank.gpu_composition)
+                        .set_jank_type(jank.jank_type)
+                        .set_prediction_type(jank.prediction_type);
+                    if let Some(severity) = jank.jank_severity_type {
+                        start.set_jank_severity_type(severity);
+                    }
+                },
+            );
+        });
+    }
+
+    /// Writes the expected timeline of the surface frame `frame`.
+    pub fn expected_surface_frame(
+        &self,
+        ctx: &mut TraceContextBase,
+        frame: &SurfaceFrame,
+        time: Range<u64>,
+    ) {
+        self.write_frame(ctx, time, |event, cookie| {
+            event.set_expected_surface_frame_start(
+                |start: &mut FrameTimelineEventExpectedSurfaceFrameStart| {
+                    start
+                        .set_cookie(cookie)
+                        .set_token(frame.token)
+                        .set_display_frame_token(frame.display_frame_token)
+                        .set_pid(self.pid)
+                        .set_layer_name(frame.layer_name);
+                },
+            );
+        });
+    }
+
+    /// Writes the actual timeline of the surface frame `frame`.
+    pub fn actual_surface_frame(
+        &self,
+        ctx: &mut TraceContextBase,
+        frame: &SurfaceFrame,
+        time: Range<u64>,
+        jank: &FrameJank,
+    ) {
+        self.write_frame(ctx, time, |event, cookie| {
+            event.set_actual_surface_frame_start(
+                |start: &mut FrameTimelineEventActualSurfaceFrameStart| {
+                    start
+                        .set_cookie(cookie)
+                        .set_token(frame.token)
+                        .set_display_frame_token(frame.display_frame_token)
+                        .set_pid(self.pid)
+                        .set_layer_name(frame.layer_name)
+                        .set_present_type(jank.present_type)
+                        .set_on_time_finish(jank.on_time_finish)
+                        .set_gpu_composition(jank.gpu_composition)
+                        .set_jank_type(jank.jank_type)
+                        .set_prediction_type(jank.prediction_type);
+                    if let Some(severity) = jank.jank_severity_type {
+                        start.set_jank_severity_type(severity);
+                    }
+                },
+            );
+        });
+    }
+
+    // Writes the start event of a frame with `cb`, and its end event.
+    fn write_frame<F>(&self, ctx: &mut TraceContextBase, time: Range<u64>, cb: F)
+    where
+        F: Fn(&mut FrameTimelineEvent, i64),
+    {
+        let cookie = NEXT_COOKIE.fetch_add(1, Ordering::Relaxed);
+        let clock_id = DataSourceTimestamp::now().clock_id();
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet
+                .set_timestamp(time.start)
+                .set_timestamp_clock_id(clock_id)
+                .set_frame_timeline_event(|event: &mut FrameTimelineEvent| cb(event, cookie));
+        });
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet
+                .set_timestamp(time.end)
+                .set_timestamp_clock_id(clock_id)
+                .set_frame_timeline_event(|event: &mut FrameTimelineEvent| {
+                    event.set_frame_end(|end: &mut FrameTimelineEventFrameEnd| {
+                        end.set_cookie(cookie);
+                    });
+                });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::trace::trace_packet::TracePacketExtFieldNumber;
+    use perfetto_sdk::{
+        protos::{
+            config::data_source_config::DataSourceConfig,
+            trace::trace_packet::TracePacketFieldNumber,
+        },
+        test_util::{
+            DATA_SOURCE_NAME, acquire_test_environment, data_source, messages, record_packets,
+            varint,
+        },
+    };
+
+    #[test]
+    fn jank_types() {
+        let jank = FrameJank::late(FrameTimelineEventJankType::JankAppDeadlineMissed)
+            .with_jank_type(FrameTimelineEventJankType::JankBufferStuffing);
+        assert_eq!(jank.jank_type, 64 | 128);
+        assert!(!jank.on_time_finish);
+        // Adding a reason to a frame without jank replaces `JANK_NONE`.
+        let jank = FrameJank::on_time().with_jank_type(FrameTimelineEventJankType::JankDropped);
+        assert_eq!(jank.jank_type, 1024);
+    }
+
+    #[test]
+    fn frame_events() {
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let timeline = FrameTimeline::with_pid(42);
+        let frame = SurfaceFrame {
+            token: 2,
+            display_frame_token: 1,
+            layer_name: "app#0",
+        };
+        let jank = FrameJank::late(FrameTimelineEventJankType::JankAppDeadlineMissed)
+            .with_severity(FrameTimelineEventJankSeverityType::SeverityFull);
+        let packets = record_packets(
+            DATA_SOURCE_NAME,
+            |_: &mut DataSourceConfig| {},
+            || {
+                data_source.trace(|ctx| {
+                    timeline.expected_display_frame(ctx, 1, 100..200);
+                    timeline.actual_display_frame(ctx, 1, 100..250, &FrameJank::on_time());
+                    timeline.expected_surface_frame(ctx, &frame, 110..190);
+                    timeline.actual_surface_frame(ctx, &frame, 110..240, &jank);
+                });
+            },
+        );
+        let events: Vec<_> = packets
+            .iter()
+            .filter_map(|packet| {
+                let event = messages(packet, TracePacketExtFieldNumber::FrameTimelineEvent as u32);
+                let timestamp = varint(packet, TracePacketFieldNumber::Timestamp as u32);
+                let (case, field) = FrameTimelineEventEventCase::decode(event.first()?).unwrap()?;
+                Some((timestamp.unwrap(), case, field.as_bytes().unwrap()))
+            })
+            .collect();
+        use FrameTimelineEventEventCase::*;
+        let cases: Vec<_> = events.iter().map(|(_, case, _)| *case).collect();
+        assert_eq!(
+            cases,
+            vec![
+                ExpectedDisplayFrameStart,
+                FrameEnd,
+                ActualDisplayFrameStart,
+                FrameEnd,
+                ExpectedSurfaceFrameStart,
+                FrameEnd,
+                ActualSurfaceFrameStart,
+                FrameEnd,
+            ]
+        );
+        let timestamps: Vec<_> = events.iter().map(|(timestamp, _, _)| *timestamp).collect();
+        assert_eq!(timestamps, vec![100, 200, 100, 250, 110, 190, 110, 240]);
+
+        // The end event of each frame has the cookie of its start event, and
+        // cookies are unique.
+        let mut cookies = Vec::new();
+        for pair in events.chunks(2) {
+            // The cookie is field 1 of all the start events.
+            let cookie = varint(pair[0].2, 1);
+            assert!(cookie.is_some());
+            assert_eq!(
+                varint(
+                    pair[1].2,
+                    FrameTimelineEventFrameEndFieldNumber::Cookie as u32
+                ),
+                cookie
+            );
+            assert!(!cookies.contains(&cookie));
+            cookies.push(cookie);
+        }
+
+        // The expected and actual timelines of a frame have the same token.
+        use FrameTimelineEventActualDisplayFrameStartFieldNumber as ActualDisplay;
+        use FrameTimelineEventActualSurfaceFrameStartFieldNumber as ActualSurface;
+        use FrameTimelineEventExpectedDisplayFrameStartFieldNumber as ExpectedDisplay;
+        use FrameTimelineEventExpectedSurfaceFrameStartFieldNumber as ExpectedSurface;
+        let expected_display = events[0].2;
+        assert_eq!(
+            varint(expected_display, ExpectedDisplay::Token as u32),
+            Some(1)
+        );
+        assert_eq!(
+            varint(expected_display, ExpectedDisplay::Pid as u32),
+            Some(42)
+        );
+        let actual_display = events[2].2;
+        assert_eq!(varint(actual_display, ActualDisplay::Token as u32), Some(1));
+        assert_eq!(
+            varint(actual_display, ActualDisplay::PresentType as u32),
+            Some(FrameTimelineEventPresentType::PresentOnTime as u64)
+        );
+        assert_eq!(
+            varint(actual_display, ActualDisplay::JankType as u32),
+            Some(FrameTimelineEventJankType::JankNone as u64)
+        );
+        assert_eq!(
+            varint(actual_display, ActualDisplay::JankSeverityType as u32),
+            None
+        );
+        let expected_surface = events[4].2;
+        assert_eq!(
+            varint(expected_surface, ExpectedSurface::Token as u32),
+            Some(2)
+        );
+        assert_eq!(
+            varint(expected_surface, ExpectedSurface::DisplayFrameToken as u32),
+            Some(1)
+        );
+        assert_eq!(
+            messages(expected_surface, ExpectedSurface::LayerName as u32),
+            vec![b"app#0"]
+        );
+        let actual_surface = events[6].2;
+        assert_eq!(varint(actual_surface, ActualSurface::Token as u32), Some(2));
+        assert_eq!(
+            messages(actual_surface, ActualSurface::LayerName as u32),
+            vec![b"app#0"]
+        );
+        assert_eq!(
+            varint(actual_surface, ActualSurface::PresentType as u32),
+            Some(FrameTimelineEventPresentType::PresentLate as u64)
+        );
+        assert_eq!(
+            varint(actual_surface, ActualSurface::JankType as u32),
+            Some(FrameTimelineEventJankType::JankAppDeadlineMissed as u64)
+        );
+        assert_eq!(
+            varint(actual_surface, ActualSurface::JankSeverityType as u32),
+            Some(FrameTimelineEventJankSeverityType::SeverityFull as u64)
+        );
+        assert_eq!(
+            varint(actual_surface, ActualSurface::OnTimeFinish as u32),
+            Some(0)
+        );
+    }
+}
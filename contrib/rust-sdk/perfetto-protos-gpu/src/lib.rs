@@ -23,5 +23,17 @@ pub use perfetto_sdk::pb_msg_ext;
 /// Re-export pb_enum macro from this crate.
 pub use perfetto_sdk::pb_enum;
 
+/// GPU counter data source module.
+#[cfg(feature = "counters")]
+pub mod counter_data_source;
+
+/// Frame timeline module.
+#[cfg(feature = "frame_timeline")]
+pub mod frame_timeline;
+
 /// Protobuf bindings module.
 pub mod protos;
+
+// Helpers for the GPU counter protos.
+#[cfg(feature = "counters")]
+mod counters;
@@ -81,9 +81,9 @@ pb_enum!(GpuCounterSpecValueDirection {
 });
 
 pb_msg!(GpuCounterDescriptor {
-    specs: GpuCounterDescriptorGpuCounterSpec, msg, 1,
-    blocks: GpuCounterDescriptorGpuCounterBlock, msg, 2,
-    counter_groups: GpuCounterDescriptorGpuCounterGroupSpec, msg, 6,
+    specs: [msg, GpuCounterDescriptorGpuCounterSpec], repeated, 1,
+    blocks: [msg, GpuCounterDescriptorGpuCounterBlock], repeated, 2,
+    counter_groups: [msg, GpuCounterDescriptorGpuCounterGroupSpec], repeated, 6,
     min_sampling_period_ns: u64, primitive, 3,
     max_sampling_period_ns: u64, primitive, 4,
     supports_instrumented_sampling: bool, primitive, 5,
@@ -95,7 +95,7 @@ pb_msg!(GpuCounterDescriptorGpuCounterGroupSpec {
     group_id: u32, primitive, 1,
     name: String, primitive, 2,
     description: String, primitive, 3,
-    counter_ids: u32, primitive, 4,
+    counter_ids: [primitive, u32], repeated, 4,
 });
 
 pb_msg!(GpuCounterDescriptorGpuCounterBlock {
@@ -103,7 +103,7 @@ pb_msg!(GpuCounterDescriptorGpuCounterBlock {
     block_capacity: u32, primitive, 2,
     name: String, primitive, 3,
     description: String, primitive, 4,
-    counter_ids: u32, primitive, 5,
+    counter_ids: [primitive, u32], repeated, 5,
 });
 
 pb_msg!(GpuCounterDescriptorGpuCounterSpec {
@@ -112,9 +112,13 @@ pb_msg!(GpuCounterDescriptorGpuCounterSpec {
     description: String, primitive, 3,
     int_peak_value: i64, primitive, 5,
     double_peak_value: f64, primitive, 6,
-    numerator_units: GpuCounterDescriptorMeasureUnit, enum, 7,
-    denominator_units: GpuCounterDescriptorMeasureUnit, enum, 8,
+    numerator_units: [enum, GpuCounterDescriptorMeasureUnit], repeated, 7,
+    denominator_units: [enum, GpuCounterDescriptorMeasureUnit], repeated, 8,
     select_by_default: bool, primitive, 9,
-    groups: GpuCounterDescriptorGpuCounterGroup, enum, 10,
+    groups: [enum, GpuCounterDescriptorGpuCounterGroup], repeated, 10,
     value_direction: GpuCounterSpecValueDirection, enum, 11,
+}
+oneof peak_value {
+    int_peak_value,
+    double_peak_value,
 });
@@ -16,9 +16,11 @@
 // DO NOT EDIT.
 
 /// `data_source_descriptor` protos.
+#[cfg(feature = "counters")]
 #[path = "data_source_descriptor.pz.rs"]
 pub mod data_source_descriptor;
 
 /// `gpu_counter_descriptor` protos.
+#[cfg(feature = "counters")]
 #[path = "gpu_counter_descriptor.pz.rs"]
 pub mod gpu_counter_descriptor;
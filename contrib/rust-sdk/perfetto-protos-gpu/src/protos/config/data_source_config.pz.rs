@@ -17,15 +17,21 @@
 
 use crate::pb_msg;
 use crate::pb_msg_ext;
+#[cfg(feature = "counters")]
 use crate::protos::config::gpu::gpu_counter_config::*;
+#[cfg(feature = "render_stages")]
 use crate::protos::config::gpu::gpu_renderstages_config::*;
+#[cfg(feature = "vulkan")]
 use crate::protos::config::gpu::vulkan_memory_config::*;
 
 use perfetto_sdk::protos::config::data_source_config::DataSourceConfig;
 
 pb_msg_ext!(DataSourceConfig {
+    #[cfg(feature = "counters")]
     gpu_counter_config: GpuCounterConfig, msg, 108,
+    #[cfg(feature = "vulkan")]
     vulkan_memory_config: VulkanMemoryConfig, msg, 112,
+    #[cfg(feature = "render_stages")]
     gpu_renderstages_config: GpuRenderStagesConfig, msg, 133,
 });
 
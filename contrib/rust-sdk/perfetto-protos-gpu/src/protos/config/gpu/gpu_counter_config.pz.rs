@@ -27,18 +27,22 @@ pb_enum!(ActivityNameFilterNameBase {
 
 pb_msg!(GpuCounterConfig {
     counter_period_ns: u64, primitive, 1,
-    counter_ids: u32, primitive, 2,
-    counter_names: String, primitive, 6,
+    counter_ids: [primitive, u32], repeated, 2,
+    counter_names: [primitive, String], repeated, 6,
     instrumented_sampling: bool, primitive, 3,
     instrumented_sampling_config: GpuCounterConfigInstrumentedSamplingConfig, msg, 5,
     fix_gpu_clock: bool, primitive, 4,
+}
+oneof instrumented_sampling_mode {
+    instrumented_sampling,
+    instrumented_sampling_config,
 });
 
 pb_msg!(GpuCounterConfigInstrumentedSamplingConfig {
-    activity_name_filters: GpuCounterConfigInstrumentedSamplingConfigActivityNameFilter, msg, 3,
-    activity_tx_include_globs: String, primitive, 6,
-    activity_tx_exclude_globs: String, primitive, 7,
-    activity_ranges: GpuCounterConfigInstrumentedSamplingConfigActivityRange, msg, 5,
+    activity_name_filters: [msg, GpuCounterConfigInstrumentedSamplingConfigActivityNameFilter], repeated, 3,
+    activity_tx_include_globs: [primitive, String], repeated, 6,
+    activity_tx_exclude_globs: [primitive, String], repeated, 7,
+    activity_ranges: [msg, GpuCounterConfigInstrumentedSamplingConfigActivityRange], repeated, 5,
 });
 
 pb_msg!(GpuCounterConfigInstrumentedSamplingConfigActivityRange {
@@ -21,5 +21,5 @@ use crate::pb_msg;
 pb_msg!(GpuRenderStagesConfig {
     full_loadstore: bool, primitive, 1,
     low_overhead: bool, primitive, 2,
-    trace_metrics: String, primitive, 3,
+    trace_metrics: [primitive, String], repeated, 3,
 });
@@ -16,13 +16,16 @@
 // DO NOT EDIT.
 
 /// `gpu_counter_config` protos.
+#[cfg(feature = "counters")]
 #[path = "gpu_counter_config.pz.rs"]
 pub mod gpu_counter_config;
 
 /// `gpu_renderstages_config` protos.
+#[cfg(feature = "render_stages")]
 #[path = "gpu_renderstages_config.pz.rs"]
 pub mod gpu_renderstages_config;
 
 /// `vulkan_memory_config` protos.
+#[cfg(feature = "vulkan")]
 #[path = "vulkan_memory_config.pz.rs"]
 pub mod vulkan_memory_config;
@@ -16,6 +16,7 @@
 // DO NOT EDIT.
 
 /// `data_source_config` protos.
+#[cfg(any(feature = "counters", feature = "render_stages", feature = "vulkan"))]
 #[path = "data_source_config.pz.rs"]
 pub mod data_source_config;
 
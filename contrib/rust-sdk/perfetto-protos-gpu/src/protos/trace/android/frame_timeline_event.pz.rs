@@ -0,0 +1,144 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Manually generated with bindings for the FrameTimelineEvent message of
+// protos/third_party/android/frameworks/native/tracing/frameworks_native_trace_packet.proto.
+
+use crate::pb_enum;
+use crate::pb_msg;
+
+pb_enum!(FrameTimelineEventJankType {
+    JANK_UNSPECIFIED: 0,
+    JANK_NONE: 1,
+    JANK_SF_SCHEDULING: 2,
+    JANK_PREDICTION_ERROR: 4,
+    JANK_DISPLAY_HAL: 8,
+    JANK_SF_CPU_DEADLINE_MISSED: 16,
+    JANK_SF_GPU_DEADLINE_MISSED: 32,
+    JANK_APP_DEADLINE_MISSED: 64,
+    JANK_BUFFER_STUFFING: 128,
+    JANK_UNKNOWN: 256,
+    JANK_SF_STUFFING: 512,
+    JANK_DROPPED: 1024,
+    JANK_NON_ANIMATING: 2048,
+    JANK_APP_RESYNCED_JITTER: 4096,
+    JANK_DISPLAY_NOT_ON: 8192,
+    JANK_DISPLAY_MODE_CHANGE_IN_PROGRESS: 16384,
+    JANK_DISPLAY_POWER_MODE_CHANGE_IN_PROGRESS: 32768,
+});
+
+pb_enum!(FrameTimelineEventJankSeverityType {
+    SEVERITY_UNKNOWN: 0,
+    SEVERITY_NONE: 1,
+    SEVERITY_PARTIAL: 2,
+    SEVERITY_FULL: 3,
+});
+
+pb_enum!(FrameTimelineEventPresentType {
+    PRESENT_UNSPECIFIED: 0,
+    PRESENT_ON_TIME: 1,
+    PRESENT_LATE: 2,
+    PRESENT_EARLY: 3,
+    PRESENT_DROPPED: 4,
+    PRESENT_UNKNOWN: 5,
+});
+
+pb_enum!(FrameTimelineEventPredictionType {
+    PREDICTION_UNSPECIFIED: 0,
+    PREDICTION_VALID: 1,
+    PREDICTION_EXPIRED: 2,
+    PREDICTION_UNKNOWN: 3,
+});
+
+pb_enum!(ActualSurfaceFrameStartLatchedFenceState {
+    LATCHED_UNKNOWN: 0,
+    LATCHED_SIGNALED: 1,
+    LATCHED_UNSIGNALED: 2,
+    LATCHED_DELAYED_LATCH_UNSIGNALED: 3,
+});
+
+pb_msg!(FrameTimelineEvent {
+    expected_display_frame_start: FrameTimelineEventExpectedDisplayFrameStart, msg, 1,
+    actual_display_frame_start: FrameTimelineEventActualDisplayFrameStart, msg, 2,
+    expected_surface_frame_start: FrameTimelineEventExpectedSurfaceFrameStart, msg, 3,
+    actual_surface_frame_start: FrameTimelineEventActualSurfaceFrameStart, msg, 4,
+    frame_end: FrameTimelineEventFrameEnd, msg, 5,
+}
+oneof event {
+    expected_display_frame_start,
+    actual_display_frame_start,
+    expected_surface_frame_start,
+    actual_surface_frame_start,
+    frame_end,
+});
+
+pb_msg!(FrameTimelineEventFrameEnd {
+    cookie: i64, primitive, 1,
+});
+
+pb_msg!(FrameTimelineEventActualDisplayFrameStart {
+    cookie: i64, primitive, 1,
+    token: i64, primitive, 2,
+    pid: i32, primitive, 3,
+    present_type: FrameTimelineEventPresentType, enum, 4,
+    on_time_finish: bool, primitive, 5,
+    gpu_composition: bool, primitive, 6,
+    jank_type: i32, primitive, 7,
+    prediction_type: FrameTimelineEventPredictionType, enum, 8,
+    jank_severity_type: FrameTimelineEventJankSeverityType, enum, 9,
+    present_delay_millis: f32, primitive, 10,
+    jank_severity_score: f32, primitive, 11,
+    jank_type_experimental: i32, primitive, 12,
+    present_type_experimental: FrameTimelineEventPresentType, enum, 13,
+    jank_debug_metadata: f32, primitive, 14,
+    latched_unsignaled_count: i64, primitive, 15,
+    addressable_unsignaled_latch_count: i64, primitive, 16,
+});
+
+pb_msg!(FrameTimelineEventExpectedDisplayFrameStart {
+    cookie: i64, primitive, 1,
+    token: i64, primitive, 2,
+    pid: i32, primitive, 3,
+});
+
+pb_msg!(FrameTimelineEventActualSurfaceFrameStart {
+    cookie: i64, primitive, 1,
+    token: i64, primitive, 2,
+    display_frame_token: i64, primitive, 3,
+    pid: i32, primitive, 4,
+    layer_name: String, primitive, 5,
+    present_type: FrameTimelineEventPresentType, enum, 6,
+    on_time_finish: bool, primitive, 7,
+    gpu_composition: bool, primitive, 8,
+    jank_type: i32, primitive, 9,
+    prediction_type: FrameTimelineEventPredictionType, enum, 10,
+    is_buffer: bool, primitive, 11,
+    jank_severity_type: FrameTimelineEventJankSeverityType, enum, 12,
+    present_delay_millis: f32, primitive, 13,
+    vsync_resynced_jitter_millis: f32, primitive, 14,
+    jank_severity_score: f32, primitive, 15,
+    jank_type_experimental: i32, primitive, 16,
+    present_type_experimental: FrameTimelineEventPresentType, enum, 17,
+    jank_debug_metadata: f32, primitive, 18,
+    latched_fence_state: ActualSurfaceFrameStartLatchedFenceState, enum, 19,
+    animation_time_millis: f32, primitive, 20,
+});
+
+pb_msg!(FrameTimelineEventExpectedSurfaceFrameStart {
+    cookie: i64, primitive, 1,
+    token: i64, primitive, 2,
+    display_frame_token: i64, primitive, 3,
+    pid: i32, primitive, 4,
+    layer_name: String, primitive, 5,
+});
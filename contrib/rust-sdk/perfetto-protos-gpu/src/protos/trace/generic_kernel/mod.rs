@@ -16,5 +16,6 @@
 // DO NOT EDIT.
 
 /// `generic_gpu_frequency` protos.
+#[cfg(feature = "frequency")]
 #[path = "generic_gpu_frequency.pz.rs"]
 pub mod generic_gpu_frequency;
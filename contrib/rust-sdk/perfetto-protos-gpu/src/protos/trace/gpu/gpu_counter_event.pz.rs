@@ -28,12 +28,20 @@ pb_msg!(InternedGpuCounterDescriptor {
 pb_msg!(GpuCounterEvent {
     counter_descriptor: GpuCounterDescriptor, msg, 1,
     counter_descriptor_iid: u64, primitive, 4,
-    counters: GpuCounterEventGpuCounter, msg, 2,
+    counters: [msg, GpuCounterEventGpuCounter], repeated, 2,
     gpu_id: i32, primitive, 3,
+}
+oneof desc {
+    counter_descriptor,
+    counter_descriptor_iid,
 });
 
 pb_msg!(GpuCounterEventGpuCounter {
     counter_id: u32, primitive, 1,
     int_value: i64, primitive, 2,
     double_value: f64, primitive, 3,
+}
+oneof value {
+    int_value,
+    double_value,
 });
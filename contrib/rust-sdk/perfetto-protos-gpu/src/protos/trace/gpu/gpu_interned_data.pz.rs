@@ -22,6 +22,6 @@ use crate::protos::trace::gpu::gpu_render_stage_event::*;
 use perfetto_sdk::protos::trace::interned_data::interned_data::*;
 
 pb_msg_ext!(InternedData {
-    compute_kernels: InternedComputeKernel, msg, 1000,
-    compute_arg_names: InternedComputeArgName, msg, 1001,
+    compute_kernels: [msg, InternedComputeKernel], repeated, 1000,
+    compute_arg_names: [msg, InternedComputeArgName], repeated, 1001,
 });
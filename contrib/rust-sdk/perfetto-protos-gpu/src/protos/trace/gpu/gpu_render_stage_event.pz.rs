@@ -44,7 +44,7 @@ pb_msg!(InternedComputeKernel {
     name: String, primitive, 2,
     demangled_name: String, primitive, 3,
     arch: String, primitive, 4,
-    args: GpuRenderStageEventExtraComputeArg, msg, 5,
+    args: [msg, GpuRenderStageEventExtraComputeArg], repeated, 5,
 });
 
 pb_msg!(InternedGpuRenderStageSpecification {
@@ -69,25 +69,29 @@ pb_msg!(GpuRenderStageEvent {
     context: u64, primitive, 5,
     render_target_handle: u64, primitive, 8,
     submission_id: u32, primitive, 10,
-    extra_data: GpuRenderStageEventExtraData, msg, 6,
+    extra_data: [msg, GpuRenderStageEventExtraData], repeated, 6,
     render_pass_handle: u64, primitive, 9,
     render_pass_instance_id: u64, primitive, 16,
-    render_subpass_index_mask: u64, primitive, 15,
+    render_subpass_index_mask: [primitive, u64], repeated, 15,
     command_buffer_handle: u64, primitive, 12,
     name: String, primitive, 17,
     name_iid: u64, primitive, 20,
-    event_wait_ids: u64, primitive, 18,
+    event_wait_ids: [primitive, u64], repeated, 18,
     kernel_iid: u64, primitive, 19,
     launch: GpuRenderStageEventComputeKernelLaunch, msg, 21,
     specifications: GpuRenderStageEventSpecifications, msg, 7,
     hw_queue_id: i32, primitive, 3,
     stage_id: i32, primitive, 4,
+}
+oneof name_field {
+    name,
+    name_iid,
 });
 
 pb_msg!(GpuRenderStageEventSpecifications {
     context_spec: GpuRenderStageEventSpecificationsContextSpec, msg, 1,
-    hw_queue: GpuRenderStageEventSpecificationsDescription, msg, 2,
-    stage: GpuRenderStageEventSpecificationsDescription, msg, 3,
+    hw_queue: [msg, GpuRenderStageEventSpecificationsDescription], repeated, 2,
+    stage: [msg, GpuRenderStageEventSpecificationsDescription], repeated, 3,
 });
 
 pb_msg!(GpuRenderStageEventSpecificationsDescription {
@@ -103,7 +107,7 @@ pb_msg!(GpuRenderStageEventSpecificationsContextSpec {
 pb_msg!(GpuRenderStageEventComputeKernelLaunch {
     grid_size: GpuRenderStageEventDim3, msg, 1,
     workgroup_size: GpuRenderStageEventDim3, msg, 2,
-    args: GpuRenderStageEventExtraComputeArg, msg, 3,
+    args: [msg, GpuRenderStageEventExtraComputeArg], repeated, 3,
 });
 
 pb_msg!(GpuRenderStageEventExtraComputeArg {
@@ -114,6 +118,17 @@ pb_msg!(GpuRenderStageEventExtraComputeArg {
     double_value: f64, primitive, 5,
     string_value: String, primitive, 6,
     string_value_iid: u64, primitive, 7,
+}
+oneof name_field {
+    name_iid,
+    name,
+}
+oneof value {
+    int_value,
+    uint_value,
+    double_value,
+    string_value,
+    string_value_iid,
 });
 
 pb_msg!(GpuRenderStageEventDim3 {
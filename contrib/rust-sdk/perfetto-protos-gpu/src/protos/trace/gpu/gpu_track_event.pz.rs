@@ -31,8 +31,8 @@ pb_enum!(GpuApi {
 });
 
 pb_msg!(GpuCorrelation {
-    render_stage_submission_event_ids: u64, primitive, 1,
-    render_stage_wait_event_ids: u64, primitive, 2,
+    render_stage_submission_event_ids: [primitive, u64], repeated, 1,
+    render_stage_wait_event_ids: [primitive, u64], repeated, 2,
 });
 
 pb_msg_ext!(TrackEvent {
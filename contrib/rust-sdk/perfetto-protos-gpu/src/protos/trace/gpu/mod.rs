@@ -16,33 +16,41 @@
 // DO NOT EDIT.
 
 /// `gpu_counter_event` protos.
+#[cfg(feature = "counters")]
 #[path = "gpu_counter_event.pz.rs"]
 pub mod gpu_counter_event;
 
 /// `gpu_interned_data` protos.
+#[cfg(feature = "render_stages")]
 #[path = "gpu_interned_data.pz.rs"]
 pub mod gpu_interned_data;
 
 /// `gpu_log` protos.
+#[cfg(feature = "log")]
 #[path = "gpu_log.pz.rs"]
 pub mod gpu_log;
 
 /// `gpu_mem_event` protos.
+#[cfg(feature = "memory")]
 #[path = "gpu_mem_event.pz.rs"]
 pub mod gpu_mem_event;
 
 /// `gpu_render_stage_event` protos.
+#[cfg(feature = "render_stages")]
 #[path = "gpu_render_stage_event.pz.rs"]
 pub mod gpu_render_stage_event;
 
 /// `gpu_track_event` protos.
+#[cfg(feature = "track_event")]
 #[path = "gpu_track_event.pz.rs"]
 pub mod gpu_track_event;
 
 /// `vulkan_api_event` protos.
+#[cfg(feature = "vulkan")]
 #[path = "vulkan_api_event.pz.rs"]
 pub mod vulkan_api_event;
 
 /// `vulkan_memory_event` protos.
+#[cfg(feature = "vulkan")]
 #[path = "vulkan_memory_event.pz.rs"]
 pub mod vulkan_memory_event;
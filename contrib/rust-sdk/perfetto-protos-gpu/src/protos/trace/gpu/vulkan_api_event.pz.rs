@@ -21,6 +21,10 @@ use crate::pb_msg;
 pb_msg!(VulkanApiEvent {
     vk_debug_utils_object_name: VulkanApiEventVkDebugUtilsObjectName, msg, 1,
     vk_queue_submit: VulkanApiEventVkQueueSubmit, msg, 2,
+}
+oneof event {
+    vk_debug_utils_object_name,
+    vk_queue_submit,
 });
 
 pb_msg!(VulkanApiEventVkQueueSubmit {
@@ -28,7 +32,7 @@ pb_msg!(VulkanApiEventVkQueueSubmit {
     pid: u32, primitive, 2,
     tid: u32, primitive, 3,
     vk_queue: u64, primitive, 4,
-    vk_command_buffers: u64, primitive, 5,
+    vk_command_buffers: [primitive, u64], repeated, 5,
     submission_id: u32, primitive, 6,
 });
 
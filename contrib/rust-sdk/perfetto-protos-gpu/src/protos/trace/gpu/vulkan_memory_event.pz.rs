@@ -55,7 +55,7 @@ pb_msg!(VulkanMemoryEvent {
     memory_size: u64, primitive, 6,
     caller_iid: u64, primitive, 7,
     allocation_scope: VulkanMemoryEventAllocationScope, enum, 8,
-    annotations: VulkanMemoryEventAnnotation, msg, 9,
+    annotations: [msg, VulkanMemoryEventAnnotation], repeated, 9,
     device: u64, primitive, 16,
     device_memory: u64, primitive, 17,
     memory_type: u32, primitive, 18,
@@ -68,4 +68,9 @@ pb_msg!(VulkanMemoryEventAnnotation {
     int_value: i64, primitive, 2,
     double_value: f64, primitive, 3,
     string_iid: u64, primitive, 4,
+}
+oneof value {
+    int_value,
+    double_value,
+    string_iid,
 });
@@ -16,17 +16,24 @@
 
 use crate::pb_msg;
 use crate::pb_msg_ext;
+#[cfg(feature = "counters")]
 use crate::protos::trace::gpu::gpu_counter_event::*;
+#[cfg(feature = "render_stages")]
 use crate::protos::trace::gpu::gpu_render_stage_event::*;
 
 use perfetto_sdk::protos::trace::interned_data::interned_data::InternedData;
+#[cfg(feature = "vulkan")]
 use perfetto_sdk::protos::trace::profiling::profile_common::InternedString;
 
 pb_msg_ext!(InternedData {
-    vulkan_memory_keys: InternedString, msg, 22,
-    graphics_contexts: InternedGraphicsContext, msg, 23,
-    gpu_specifications: InternedGpuRenderStageSpecification, msg, 24,
-    gpu_counter_descriptors: InternedGpuCounterDescriptor, msg, 47,
+    #[cfg(feature = "vulkan")]
+    vulkan_memory_keys: [msg, InternedString], repeated, 22,
+    #[cfg(feature = "render_stages")]
+    graphics_contexts: [msg, InternedGraphicsContext], repeated, 23,
+    #[cfg(feature = "render_stages")]
+    gpu_specifications: [msg, InternedGpuRenderStageSpecification], repeated, 24,
+    #[cfg(feature = "counters")]
+    gpu_counter_descriptors: [msg, InternedGpuCounterDescriptor], repeated, 47,
 });
 
 /// Import this to use the extra `InternedData` fields.
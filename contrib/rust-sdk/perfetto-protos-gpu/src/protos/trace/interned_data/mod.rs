@@ -16,5 +16,6 @@
 // DO NOT EDIT.
 
 /// `interned_data` protos.
+#[cfg(any(feature = "counters", feature = "render_stages", feature = "vulkan"))]
 #[path = "interned_data.pz.rs"]
 pub mod interned_data;
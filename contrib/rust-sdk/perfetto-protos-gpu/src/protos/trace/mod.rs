@@ -15,6 +15,9 @@
 // Autogenerated by the gen_rust_protos script.
 // DO NOT EDIT.
 
+/// `android` protos.
+pub mod android;
+
 /// `generic_kernel` protos.
 pub mod generic_kernel;
 
@@ -28,5 +31,15 @@ pub mod interned_data;
 pub mod system_info;
 
 /// `trace_packet` protos.
+#[cfg(any(
+    feature = "counters",
+    feature = "frame_timeline",
+    feature = "frequency",
+    feature = "info",
+    feature = "log",
+    feature = "memory",
+    feature = "render_stages",
+    feature = "vulkan"
+))]
 #[path = "trace_packet.pz.rs"]
 pub mod trace_packet;
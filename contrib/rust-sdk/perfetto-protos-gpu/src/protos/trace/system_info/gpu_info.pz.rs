@@ -19,7 +19,7 @@
 use crate::pb_msg;
 
 pb_msg!(GpuInfo {
-    gpus: GpuInfoGpu, msg, 1,
+    gpus: [msg, GpuInfoGpu], repeated, 1,
 });
 
 pb_msg!(GpuInfoGpu {
@@ -27,9 +27,9 @@ pb_msg!(GpuInfoGpu {
     vendor: String, primitive, 2,
     model: String, primitive, 3,
     architecture: String, primitive, 4,
-    uuid: String, primitive, 5,
+    uuid: Bytes, primitive, 5,
     pci_bdf: String, primitive, 7,
-    extra_info: GpuInfoGpuKeyValue, msg, 6,
+    extra_info: [msg, GpuInfoGpuKeyValue], repeated, 6,
 });
 
 pb_msg!(GpuInfoGpuKeyValue {
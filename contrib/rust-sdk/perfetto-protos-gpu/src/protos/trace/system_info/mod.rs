@@ -16,5 +16,6 @@
 // DO NOT EDIT.
 
 /// `gpu_info` protos.
+#[cfg(feature = "info")]
 #[path = "gpu_info.pz.rs"]
 pub mod gpu_info;
@@ -16,25 +16,45 @@
 
 use crate::pb_msg;
 use crate::pb_msg_ext;
+#[cfg(feature = "frame_timeline")]
+use crate::protos::trace::android::frame_timeline_event::*;
+#[cfg(feature = "frequency")]
 use crate::protos::trace::generic_kernel::generic_gpu_frequency::*;
+#[cfg(feature = "counters")]
 use crate::protos::trace::gpu::gpu_counter_event::*;
+#[cfg(feature = "log")]
 use crate::protos::trace::gpu::gpu_log::*;
+#[cfg(feature = "memory")]
 use crate::protos::trace::gpu::gpu_mem_event::*;
+#[cfg(feature = "render_stages")]
 use crate::protos::trace::gpu::gpu_render_stage_event::*;
+#[cfg(feature = "vulkan")]
 use crate::protos::trace::gpu::vulkan_api_event::*;
+#[cfg(feature = "vulkan")]
 use crate::protos::trace::gpu::vulkan_memory_event::*;
+#[cfg(feature = "info")]
 use crate::protos::trace::system_info::gpu_info::*;
 
 use perfetto_sdk::protos::trace::trace_packet::TracePacket;
 
 pb_msg_ext!(TracePacket {
+    #[cfg(feature = "counters")]
     gpu_counter_event: GpuCounterEvent, msg, 52,
+    #[cfg(feature = "render_stages")]
     gpu_render_stage_event: GpuRenderStageEvent, msg, 53,
+    #[cfg(feature = "vulkan")]
     vulkan_memory_event: VulkanMemoryEvent, msg, 62,
+    #[cfg(feature = "log")]
     gpu_log: GpuLog, msg, 63,
+    #[cfg(feature = "vulkan")]
     vulkan_api_event: VulkanApiEvent, msg, 65,
+    #[cfg(feature = "memory")]
     gpu_mem_total_event: GpuMemTotalEvent, msg, 71,
+    #[cfg(feature = "frame_timeline")]
+    frame_timeline_event: FrameTimelineEvent, msg, 76,
+    #[cfg(feature = "info")]
     gpu_info: GpuInfo, msg, 128,
+    #[cfg(feature = "frequency")]
     generic_gpu_frequency_event: GenericGpuFrequencyEvent, msg, 129,
 });
 
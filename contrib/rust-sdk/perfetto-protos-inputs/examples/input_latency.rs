@@ -0,0 +1,56 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use perfetto_sdk::{async_task_category, producer::*, track_event::TrackEvent};
+
+use perfetto_sdk_protos_inputs::input_latency::InputLatency;
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+perfetto_sdk::track_event_categories! {
+    pub mod example_categories {
+        ("input", "Input latency", []),
+    }
+}
+use example_categories as perfetto_te_ns;
+
+fn main() {
+    let producer_args = ProducerInitArgsBuilder::new().backends(Backends::SYSTEM);
+    Producer::init(producer_args.build());
+    TrackEvent::init();
+    example_categories::register().expect("failed to register categories");
+    let latency = InputLatency::new(async_task_category!("input"));
+    // A real compositor gets the kernel timestamps of the events from evdev or
+    // libinput, and the presentation times from the presentation feedback of
+    // its frames. This example simulates a touch event every 100ms, delivered
+    // 2ms after it happened and presented at the next 60Hz vsync.
+    let start = Instant::now();
+    let now_nanos = || start.elapsed().as_nanos() as i64;
+    for event_id in 1u32.. {
+        thread::sleep(Duration::from_millis(100));
+        let event_time = now_nanos() - 2_000_000;
+        latency.received(event_id, event_time);
+        latency.handle(event_id, "dispatch", || {
+            thread::sleep(Duration::from_millis(1));
+        });
+        let vsync = 16_666_667;
+        let present_time = (now_nanos() / vsync + 1) * vsync;
+        if let Some(touch_to_photon) = latency.presented(event_id, present_time) {
+            println!("event {event_id}: {touch_to_photon:?}");
+        }
+    }
+}
@@ -0,0 +1,428 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use perfetto_sdk::{
+    async_task::TaskCategory,
+    fnv1a,
+    track_event::{
+        EventContext, ScopeGuard, TrackEventDebugArg, TrackEventFlow, TrackEventTimestamp,
+        TrackEventTrack, TrackEventType,
+    },
+};
+use std::{
+    collections::VecDeque,
+    ffi::{CStr, CString},
+    sync::Mutex,
+    time::Duration,
+};
+
+// Name of the tracks of the input events.
+const INPUT_TRACK_NAME: &str = "Input events";
+
+// Name of the slices from the timestamp of an input event to its delivery.
+const INPUT_SLICE_NAME: &CStr = c"input";
+
+// Name of the instants emitted when the frame of an input event is presented.
+const PRESENTED_EVENT_NAME: &CStr = c"presented";
+
+// Maximum number of input events waiting for their frame to be presented.
+// Events that never get presented, e.g. because they were dropped, are
+// forgotten once newer events have taken their place.
+const MAX_PENDING_EVENTS: usize = 256;
+
+const INPUT_FLOW_MAGIC: u64 = fnv1a(b"perfetto.input_event");
+
+/// Returns the id of the global flow of the input event `event_id`, e.g. the
+/// `event_id` of an `AndroidMotionEvent`.
+///
+/// The flow doesn't depend on the process, so that a compositor and the
+/// clients it forwards the event to can extend the same flow.
+pub fn input_flow_id(event_id: u32) -> u64 {
+    INPUT_FLOW_MAGIC ^ u64::from(event_id)
+}
+
+fn monotonic(nanos: i64) -> TrackEventTimestamp {
+    TrackEventTimestamp::Monotonic(Duration::from_nanos(nanos.max(0) as u64))
+}
+
+#[derive(Debug)]
+struct PendingEvent {
+    event_id: u32,
+    event_time_nanos: i64,
+    track: Option<TrackEventTrack>,
+}
+
+/// Tracker of the latency of input events, from the `CLOCK_MONOTONIC`
+/// timestamp of the kernel to the presentation of the frame that handled
+/// them, i.e. the touch-to-photon latency.
+///
+/// Each event is keyed by its `event_id`, the one of the `AndroidInputEvent`
+/// messages. Its delivery is a slice on a track of the event, from the
+/// timestamp of the event to [`InputLatency::received`], and the slices of
+/// [`InputLatency::handle`] and the presentation of
+/// [`InputLatency::presented`] are connected to it by the flow of the event.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::async_task_category;
+/// use perfetto_sdk_protos_inputs::input_latency::InputLatency;
+///
+/// perfetto_sdk::track_event_categories! {
+///     pub mod my_categories {
+///         ("input", "Input latency", []),
+///     }
+/// }
+/// use my_categories as perfetto_te_ns;
+///
+/// let latency = InputLatency::new(async_task_category!("input"));
+/// // From the event loop, with the kernel timestamp of the event.
+/// latency.received(0x1234, 1_000_000_000);
+/// latency.handle(0x1234, "hit_test", || {
+///     // Find the surface under the pointer.
+/// });
+/// // From the presentation feedback of the frame.
+/// let touch_to_photon = latency.presented(0x1234, 1_016_000_000);
+/// ```
+#[derive(Debug)]
+pub struct InputLatency {
+    category: TaskCategory,
+    pending: Mutex<VecDeque<PendingEvent>>,
+}
+
+impl InputLatency {
+    /// Creates a tracker of input events, whose events are emitted in
+    /// `category`.
+    pub fn new(category: TaskCategory) -> Self {
+        Self {
+            category,
+            pending: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn emit<F>(&self, variant: TrackEventType, cb: F)
+    where
+        F: FnOnce(&mut EventContext),
+    {
+        let mut ctx = EventContext::default();
+        cb(&mut ctx);
+        self.category.emit(variant, &mut ctx);
+    }
+
+    /// Emits the delivery of the input event `event_id`, whose kernel
+    /// timestamp is `event_time_nanos`, to the calling thread, which begins
+    /// the flow of the event.
+    pub fn received(&self, event_id: u32, event_time_nanos: i64) {
+        let track = self.category.is_enabled().then(|| {
+            let track = TrackEventTrack::register_named_track(
+                INPUT_TRACK_NAME,
+                u64::from(event_id),
+                TrackEventTrack::process_track_uuid(),
+            )
+            .expect("failed to register input event track");
+            let flow = TrackEventFlow::global_flow(input_flow_id(event_id));
+            let received = TrackEventTimestamp::now();
+            self.emit(
+                TrackEventType::SliceBegin(INPUT_SLICE_NAME.as_ptr()),
+                |ctx| {
+                    ctx.set_track(&track)
+                        .set_timestamp(monotonic(event_time_nanos))
+                        .set_flow(&flow)
+                        .add_debug_arg("event_id", TrackEventDebugArg::Uint64(event_id.into()));
+                },
+            );
+            self.emit(TrackEventType::SliceEnd, |ctx| {
+                ctx.set_track(&track).set_timestamp(received);
+            });
+            track
+        });
+        let mut pending = self.pending.lock().unwrap();
+        if pending.len() == MAX_PENDING_EVENTS {
+            pending.pop_front();
+        }
+        pending.push_back(PendingEvent {
+            event_id,
+            event_time_nanos,
+            track,
+        });
+    }
+
+    /// Runs `f`, which handles the input event `event_id`, and emits its
+    /// execution as a slice named `name` on the calling thread, connected to
+    /// the flow of the event.
+    pub fn handle<F, R>(&self, event_id: u32, name: &str, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        if !self.category.is_enabled() {
+            return f();
+        }
+        let name = CString::new(name).expect("slice name contains a NUL byte");
+        let flow = TrackEventFlow::global_flow(input_flow_id(event_id));
+        self.emit(TrackEventType::SliceBegin(name.as_ptr()), |ctx| {
+            ctx.set_flow(&flow);
+        });
+        // The slice is ended if `f` panics, so that the slices of the thread
+        // stay balanced.
+        let _end = ScopeGuard::new(|| self.emit(TrackEventType::SliceEnd, |_| {}));
+        f()
+    }
+
+    /// Emits the presentation, at `present_time_nanos` on `CLOCK_MONOTONIC`,
+    /// of the frame that handled the input event `event_id`, which ends the
+    /// flow of the event. Returns the latency from the kernel timestamp of the
+    /// event, or `None` if the event wasn't received.
+    pub fn presented(&self, event_id: u32, present_time_nanos: i64) -> Option<Duration> {
+        let event = {
+            let mut pending = self.pending.lock().unwrap();
+            let index = pending
+                .iter()
+                .position(|event| event.event_id == event_id)?;
+            pending.remove(index)?
+        };
+        let latency = present_time_nanos.saturating_sub(event.event_time_nanos);
+        if let Some(track) = event.track.as_ref()
+            && self.category.is_enabled()
+        {
+            let flow = TrackEventFlow::global_flow(input_flow_id(event_id));
+            self.emit(
+                TrackEventType::Instant(PRESENTED_EVENT_NAME.as_ptr()),
+                |ctx| {
+                    ctx.set_track(track)
+                        .set_timestamp(monotonic(present_time_nanos))
+                        .set_terminating_flow(&flow)
+                        .add_debug_arg("latency_ns", TrackEventDebugArg::Int64(latency));
+                },
+            );
+        }
+        Some(Duration::from_nanos(latency.max(0) as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use perfetto_sdk::{
+        async_task_category,
+        pb_decoder::PbDecoderField,
+        protos::{
+            config::{
+                data_source_config::DataSourceConfig,
+                track_event::track_event_config::TrackEventConfig,
+            },
+            trace::{
+                interned_data::interned_data::InternedDataFieldNumber,
+                trace_packet::TracePacketFieldNumber,
+                track_event::{
+                    debug_annotation::DebugAnnotationFieldNumber,
+                    track_descriptor::TrackDescriptorFieldNumber,
+                    track_event::{
+                        EventNameFieldNumber, TrackEventFieldNumber, TrackEventType as EventType,
+                    },
+                },
+            },
+        },
+        test_util::{acquire_test_environment, fields, messages, record_packets, varint},
+        track_event::TrackEvent,
+    };
+    use std::{collections::HashMap, sync::Once};
+
+    perfetto_sdk::track_event_categories! {
+        pub mod input_test_te_ns {
+            ( "input_test", "Test input latency", [] ),
+        }
+    }
+    use input_test_te_ns as perfetto_te_ns;
+
+    fn register_categories() {
+        static REGISTER: Once = Once::new();
+        REGISTER.call_once(|| {
+            TrackEvent::init();
+            perfetto_te_ns::register().unwrap();
+        });
+    }
+
+    // Track event of a packet, with its timestamp and name.
+    struct Event<'a> {
+        timestamp: u64,
+        name: Option<String>,
+        data: &'a [u8],
+    }
+
+    fn track_events(packets: &[Vec<u8>]) -> Vec<Event<'_>> {
+        let mut names = HashMap::new();
+        let mut events = Vec::new();
+        for packet in packets {
+            for interned in messages(packet, TracePacketFieldNumber::InternedData as u32) {
+                for name in messages(interned, InternedDataFieldNumber::EventNames as u32) {
+                    names.insert(
+                        varint(name, EventNameFieldNumber::Iid as u32).unwrap(),
+                        String::from_utf8(
+                            messages(name, EventNameFieldNumber::Name as u32)[0].to_vec(),
+                        )
+                        .unwrap(),
+                    );
+                }
+            }
+            for data in messages(packet, TracePacketFieldNumber::TrackEvent as u32) {
+                events.push(Event {
+                    timestamp: varint(packet, TracePacketFieldNumber::Timestamp as u32).unwrap(),
+                    name: varint(data, TrackEventFieldNumber::NameIid as u32)
+                        .map(|iid| names[&iid].clone()),
+                    data,
+                });
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn latency() {
+        let _lock = acquire_test_environment();
+        register_categories();
+        let latency = InputLatency::new(async_task_category!("input_test"));
+        latency.received(1, 1_000);
+        latency.received(2, 10_000);
+        assert_eq!(latency.handle(1, "dispatch", || 5), 5);
+        assert_eq!(
+            latency.presented(1, 5_000),
+            Some(Duration::from_nanos(4_000))
+        );
+        // Events are only presented once.
+        assert_eq!(latency.presented(1, 6_000), None);
+        assert_eq!(latency.presented(3, 6_000), None);
+        // Frames presented before the timestamp of the event have no latency.
+        assert_eq!(latency.presented(2, 5_000), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn forgets_old_events() {
+        let _lock = acquire_test_environment();
+        register_categories();
+        let latency = InputLatency::new(async_task_category!("input_test"));
+        for event_id in 0..=MAX_PENDING_EVENTS as u32 {
+            latency.received(event_id, 0);
+        }
+        assert_eq!(latency.presented(0, 1), None);
+        assert_eq!(latency.presented(1, 1), Some(Duration::from_nanos(1)));
+        assert_eq!(
+            latency.presented(MAX_PENDING_EVENTS as u32, 1),
+            Some(Duration::from_nanos(1))
+        );
+    }
+
+    #[test]
+    fn emitted_events() {
+        let _lock = acquire_test_environment();
+        register_categories();
+        let latency = InputLatency::new(async_task_category!("input_test"));
+        let packets = record_packets(
+            "track_event",
+            |ds_cfg: &mut DataSourceConfig| {
+                ds_cfg.set_track_event_config(|te_cfg: &mut TrackEventConfig| {
+                    te_cfg.set_enabled_categories("input_test");
+                });
+            },
+            || {
+                latency.received(7, 1_000_000);
+                latency.handle(7, "hit_test", || {});
+                assert_eq!(
+                    latency.presented(7, 1_016_000),
+                    Some(Duration::from_nanos(16_000))
+                );
+            },
+        );
+        let track_uuid = TrackEventTrack::register_named_track(
+            INPUT_TRACK_NAME,
+            7,
+            TrackEventTrack::process_track_uuid(),
+        )
+        .unwrap()
+        .uuid();
+        let flow_id = input_flow_id(7);
+
+        // The track of the event is named after the input events.
+        let descriptor = packets
+            .iter()
+            .flat_map(|packet| messages(packet, TracePacketFieldNumber::TrackDescriptor as u32))
+            .find(|descriptor| {
+                varint(descriptor, TrackDescriptorFieldNumber::Uuid as u32) == Some(track_uuid)
+            })
+            .unwrap();
+        assert_eq!(
+            messages(descriptor, TrackDescriptorFieldNumber::StaticName as u32),
+            vec![INPUT_TRACK_NAME.as_bytes()]
+        );
+
+        let events = track_events(&packets);
+        assert_eq!(events.len(), 5);
+        let (received, delivered, handle, handled, presented) =
+            (&events[0], &events[1], &events[2], &events[3], &events[4]);
+        use TrackEventFieldNumber as Field;
+
+        // The delivery begins the flow on the track of the event.
+        assert_eq!(received.name.as_deref(), Some("input"));
+        assert_eq!(received.timestamp, 1_000_000);
+        assert_eq!(
+            varint(received.data, Field::TrackUuid as u32),
+            Some(track_uuid)
+        );
+        assert_eq!(
+            fields(received.data, Field::FlowIds as u32),
+            vec![PbDecoderField::Fixed64(flow_id)]
+        );
+        let event_id = messages(received.data, Field::DebugAnnotations as u32)[0];
+        assert_eq!(
+            varint(event_id, DebugAnnotationFieldNumber::UintValue as u32),
+            Some(7)
+        );
+        assert_eq!(
+            varint(delivered.data, Field::Type as u32),
+            Some(EventType::TypeSliceEnd as u64)
+        );
+        assert_eq!(
+            varint(delivered.data, Field::TrackUuid as u32),
+            Some(track_uuid)
+        );
+
+        // Handling the event continues the flow on the calling thread.
+        assert_eq!(handle.name.as_deref(), Some("hit_test"));
+        assert_eq!(
+            fields(handle.data, Field::FlowIds as u32),
+            vec![PbDecoderField::Fixed64(flow_id)]
+        );
+        assert_eq!(varint(handle.data, Field::TrackUuid as u32), None);
+        assert_eq!(
+            varint(handled.data, Field::Type as u32),
+            Some(EventType::TypeSliceEnd as u64)
+        );
+
+        // The presentation ends the flow with the latency of the event.
+        assert_eq!(presented.name.as_deref(), Some("presented"));
+        assert_eq!(presented.timestamp, 1_016_000);
+        assert_eq!(
+            varint(presented.data, Field::TrackUuid as u32),
+            Some(track_uuid)
+        );
+        assert_eq!(
+            fields(presented.data, Field::TerminatingFlowIds as u32),
+            vec![PbDecoderField::Fixed64(flow_id)]
+        );
+        let latency_ns = messages(presented.data, Field::DebugAnnotations as u32)[0];
+        assert_eq!(
+            varint(latency_ns, DebugAnnotationFieldNumber::IntValue as u32),
+            Some(16_000)
+        );
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Autogenerated by the ProtoZero Rust compiler plugin.
+// Invoked by contrib/rust-sdk/tools/gen_rust_protos
+// DO NOT EDIT.
+
+use crate::pb_msg;
+
+pb_msg!(AndroidInputEvent {
+    dispatcher_motion_event: AndroidMotionEvent, msg, 1,
+    dispatcher_motion_event_redacted: AndroidMotionEvent, msg, 2,
+    dispatcher_key_event: AndroidKeyEvent, msg, 3,
+    dispatcher_key_event_redacted: AndroidKeyEvent, msg, 4,
+    dispatcher_window_dispatch_event: AndroidWindowInputDispatchEvent, msg, 5,
+    dispatcher_window_dispatch_event_redacted: AndroidWindowInputDispatchEvent, msg, 6,
+}
+oneof event {
+    dispatcher_motion_event,
+    dispatcher_motion_event_redacted,
+    dispatcher_key_event,
+    dispatcher_key_event_redacted,
+    dispatcher_window_dispatch_event,
+    dispatcher_window_dispatch_event_redacted,
+});
+
+pb_msg!(AndroidWindowInputDispatchEvent {
+    event_id: u32, primitive, 1,
+    vsync_id: i64, primitive, 2,
+    window_id: i32, primitive, 3,
+    dispatched_pointer: [msg, AndroidWindowInputDispatchEventDispatchedPointer], repeated, 4,
+    resolved_flags: u32, primitive, 5,
+});
+
+pb_msg!(AndroidWindowInputDispatchEventDispatchedPointer {
+    pointer_id: i32, primitive, 1,
+    x_in_display: f32, primitive, 2,
+    y_in_display: f32, primitive, 3,
+    axis_value_in_window: [msg, AndroidMotionEventPointerAxisValue], repeated, 4,
+});
+
+pb_msg!(AndroidKeyEvent {
+    event_id: u32, primitive, 1,
+    event_time_nanos: i64, primitive, 2,
+    down_time_nanos: i64, primitive, 3,
+    source: u32, primitive, 4,
+    action: i32, primitive, 5,
+    device_id: i32, primitive, 6,
+    display_id: i32, primitive, 7,
+    key_code: i32, primitive, 8,
+    scan_code: u32, primitive, 9,
+    meta_state: u32, primitive, 10,
+    repeat_count: i32, primitive, 11,
+    flags: u32, primitive, 12,
+    policy_flags: u32, primitive, 13,
+});
+
+pb_msg!(AndroidMotionEvent {
+    event_id: u32, primitive, 1,
+    event_time_nanos: i64, primitive, 2,
+    source: u32, primitive, 3,
+    action: i32, primitive, 4,
+    device_id: i32, primitive, 5,
+    display_id: i32, primitive, 6,
+    classification: i32, primitive, 7,
+    flags: u32, primitive, 8,
+    pointer: [msg, AndroidMotionEventPointer], repeated, 9,
+    original_event_id: [primitive, u32], packed, 16,
+    down_time_nanos: i64, primitive, 17,
+    cursor_position_x: f32, primitive, 18,
+    cursor_position_y: f32, primitive, 19,
+    action_button: i32, primitive, 20,
+    button_state: u32, primitive, 21,
+    meta_state: u32, primitive, 22,
+    policy_flags: u32, primitive, 23,
+    precision_x: f32, primitive, 24,
+    precision_y: f32, primitive, 25,
+});
+
+pb_msg!(AndroidMotionEventPointer {
+    axis_value: [msg, AndroidMotionEventPointerAxisValue], repeated, 1,
+    pointer_id: i32, primitive, 2,
+    tool_type: i32, primitive, 3,
+});
+
+pb_msg!(AndroidMotionEventPointerAxisValue {
+    axis: i32, primitive, 1,
+    value: f32, primitive, 2,
+});
@@ -0,0 +1,37 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use perfetto_sdk::producer::*;
+
+use perfetto_sdk_protos_memory::memory_dump::MemoryDumpDataSourceBuilder;
+
+use std::{error::Error, time::Duration};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let producer_args = ProducerInitArgsBuilder::new().backends(Backends::SYSTEM);
+    Producer::init(producer_args.build());
+    // Each flush of an instance, e.g. with `flush_period_ms` in the trace
+    // config, writes a memory dump of this process to it.
+    let data_source = MemoryDumpDataSourceBuilder::new()
+        .name("memory.dump.example")
+        .register()?;
+    let mut allocations = Vec::new();
+    loop {
+        allocations.push(vec![1u8; 1 << 20]);
+        std::thread::sleep(Duration::from_secs(1));
+        if allocations.len() % 10 == 0 {
+            data_source.dump();
+        }
+    }
+}
@@ -0,0 +1,688 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::protos::trace::{memory_graph::*, profiling::smaps::*, trace_packet::prelude::*};
+use perfetto_sdk::{
+    data_source::{
+        DataSource, DataSourceArgsBuilder, DataSourceError, DataSourceTimestamp, TraceContext,
+    },
+    protos::trace::trace_packet::TracePacket,
+};
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, OnceLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+/// Name of the data source registered by default by
+/// [`MemoryDumpDataSourceBuilder::register`], the same as the one of Chrome's
+/// memory-infra.
+pub const MEMORY_DUMP_DATA_SOURCE_NAME: &str = "org.chromium.memory_instrumentation";
+
+// Protection flags of `SmapsEntry`, the same as the `PROT_*` constants.
+const PROT_READ: u32 = 1;
+const PROT_WRITE: u32 = 2;
+const PROT_EXEC: u32 = 4;
+
+/// Memory mapping of a process, parsed from its `smaps` file. Sizes are in kB.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmapsRegion {
+    /// Start address of the mapping.
+    pub start_address: u64,
+    /// `PROT_*` flags of the mapping.
+    pub protection_flags: u32,
+    /// Path of the mapped file, or pseudo-path like `[heap]`. Empty for
+    /// anonymous mappings.
+    pub path: String,
+    /// Virtual size.
+    pub size_kb: u64,
+    /// Resident size.
+    pub rss_kb: u64,
+    /// Proportional resident size.
+    pub pss_kb: u64,
+    /// Resident pages shared with other processes and not modified.
+    pub shared_clean_kb: u64,
+    /// Resident pages shared with other processes and modified.
+    pub shared_dirty_kb: u64,
+    /// Resident pages private to the process and not modified.
+    pub private_clean_kb: u64,
+    /// Resident pages private to the process and modified.
+    pub private_dirty_kb: u64,
+    /// Swapped out size.
+    pub swap_kb: u64,
+    /// Locked size.
+    pub locked_kb: u64,
+}
+
+impl SmapsRegion {
+    // Returns the name of the node of the region in a `MemoryTrackerSnapshot`.
+    fn node_name(&self) -> &'static str {
+        match self.path.as_str() {
+            "" => "mmaps/anonymous",
+            "[heap]" => "mmaps/heap",
+            path if path.starts_with("[stack") => "mmaps/stack",
+            path if path.starts_with('/') => "mmaps/file",
+            _ => "mmaps/other",
+        }
+    }
+}
+
+// Parses the header line of a mapping, e.g.
+// "7f0000000000-7f0000001000 r-xp 00000000 08:02 1234 /usr/lib/libc.so.6".
+fn parse_header(line: &str) -> Option<SmapsRegion> {
+    let mut words = line.splitn(6, ' ');
+    let (start, _end) = words.next()?.split_once('-')?;
+    let start_address = u64::from_str_radix(start, 16).ok()?;
+    let perms = words.next()?.as_bytes();
+    if perms.len() < 3 {
+        return None;
+    }
+    let protection_flags = [(b'r', PROT_READ), (b'w', PROT_WRITE), (b'x', PROT_EXEC)]
+        .iter()
+        .zip(perms)
+        .filter(|((flag, _), perm)| *flag == **perm)
+        .fold(0, |flags, ((_, bit), _)| flags | bit);
+    // Skip the offset, device and inode.
+    let path = words.nth(3).unwrap_or("").trim().to_string();
+    Some(SmapsRegion {
+        start_address,
+        protection_flags,
+        path,
+        ..Default::default()
+    })
+}
+
+/// Parses the contents of a `/proc/<pid>/smaps` file.
+pub fn parse_smaps(data: &str) -> Vec<SmapsRegion> {
+    let mut regions: Vec<SmapsRegion> = Vec::new();
+    for line in data.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().trim_end_matches(" kB").parse::<u64>();
+        let Some(region) = regions.last_mut().filter(|_| !key.contains(' ')) else {
+            regions.extend(parse_header(line));
+            continue;
+        };
+        let Ok(value) = value else {
+            // E.g. "VmFlags: rd ex".
+            continue;
+        };
+        match key {
+            "Size" => region.size_kb = value,
+            "Rss" => region.rss_kb = value,
+            "Pss" => region.pss_kb = value,
+            "Shared_Clean" => region.shared_clean_kb = value,
+            "Shared_Dirty" => region.shared_dirty_kb = value,
+            "Private_Clean" => region.private_clean_kb = value,
+            "Private_Dirty" => region.private_dirty_kb = value,
+            "Swap" => region.swap_kb = value,
+            "Locked" => region.locked_kb = value,
+            _ => {}
+        }
+    }
+    regions
+}
+
+/// On-demand memory dump of a process.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryDump {
+    /// Id of the process.
+    pub pid: u32,
+    /// Memory mappings of the process.
+    pub regions: Vec<SmapsRegion>,
+}
+
+impl MemoryDump {
+    /// Captures a memory dump of the current process from `/proc/self/smaps`.
+    pub fn capture() -> io::Result<Self> {
+        Self::capture_from(Path::new("/proc/self/smaps"), std::process::id())
+    }
+
+    /// Captures a memory dump of the process `pid` from its `smaps` file at
+    /// `path`.
+    pub fn capture_from(path: &Path, pid: u32) -> io::Result<Self> {
+        Ok(Self {
+            pid,
+            regions: parse_smaps(&fs::read_to_string(path)?),
+        })
+    }
+
+    /// Writes the mappings of the dump as a `SmapsPacket`.
+    pub fn write_smaps_packet(&self, packet: &mut TracePacket) {
+        packet.set_smaps_packet(|smaps: &mut SmapsPacket| {
+            smaps
+                .set_pid(self.pid)
+                .set_recording_type(SmapsPacketRecordingType::RecordingTypeStandalone);
+            for region in &self.regions {
+                smaps.set_entries(|entry: &mut SmapsEntry| {
+                    entry
+                        .set_path(&region.path)
+                        .set_start_address(region.start_address)
+                        .set_protection_flags(region.protection_flags)
+                        .set_size_kb(region.size_kb)
+                        .set_proportional_resident_kb(region.pss_kb)
+                        .set_shared_clean_resident_kb(region.shared_clean_kb)
+                        .set_shared_dirty_resident_kb(region.shared_dirty_kb)
+                        .set_private_clean_resident_kb(region.private_clean_kb)
+                        .set_private_dirty_kb(region.private_dirty_kb)
+                        .set_swap_kb(region.swap_kb)
+                        .set_locked_kb(region.locked_kb);
+                    if let Some(file_name) = Path::new(&region.path).file_name() {
+                        entry.set_file_name(file_name.to_string_lossy());
+                    }
+                });
+            }
+        });
+    }
+
+    /// Writes the dump as a `MemoryTrackerSnapshot` with the id
+    /// `global_dump_id`, with a node per kind of mapping, e.g. `mmaps/heap`.
+    pub fn write_snapshot(
+        &self,
+        packet: &mut TracePacket,
+        global_dump_id: u64,
+        level_of_detail: MemoryTrackerSnapshotLevelOfDetail,
+    ) {
+        let mut nodes: BTreeMap<&str, SmapsRegion> = BTreeMap::new();
+        for region in &self.regions {
+            let node = nodes.entry(region.node_name()).or_default();
+            node.size_kb += region.size_kb;
+            node.rss_kb += region.rss_kb;
+            node.private_dirty_kb += region.private_dirty_kb;
+            node.swap_kb += region.swap_kb;
+        }
+        packet.set_memory_tracker_snapshot(|snapshot: &mut MemoryTrackerSnapshot| {
+            snapshot
+                .set_global_dump_id(global_dump_id)
+                .set_level_of_detail(level_of_detail);
+            snapshot.set_process_memory_dumps(
+                |process: &mut MemoryTrackerSnapshotProcessSnapshot| {
+                    process.set_pid(self.pid as i32);
+                    for (id, (name, node)) in nodes.iter().enumerate() {
+                        process.set_allocator_dumps(
+                            |dump: &mut MemoryTrackerSnapshotProcessSnapshotMemoryNode| {
+                                dump.set_id(id as u64 + 1)
+                                    .set_absolute_name(name)
+                                    .set_size_bytes(node.rss_kb * 1024);
+                                for (name, kb) in [
+                                    ("size", node.rss_kb),
+                                    ("virtual_size", node.size_kb),
+                                    ("private_dirty", node.private_dirty_kb),
+                                    ("swap", node.swap_kb),
+                                ] {
+                                    dump.set_entries(
+                                        |entry: &mut MemoryTrackerSnapshotProcessSnapshotMemoryNodeMemoryNodeEntry| {
+                                            entry
+                                                .set_name(name)
+                                                .set_units(MemoryNodeEntryUnits::Bytes)
+                                                .set_value_uint64(kb * 1024);
+                                        },
+                                    );
+                                }
+                            },
+                        );
+                    }
+                },
+            );
+        });
+    }
+}
+
+struct Shared {
+    smaps_path: PathBuf,
+    level_of_detail: MemoryTrackerSnapshotLevelOfDetail,
+    data_source: OnceLock<&'static DataSource<'static>>,
+    next_dump_id: AtomicU64,
+    // Serializes dumps, which read the whole smaps file.
+    dumping: Mutex<()>,
+}
+
+impl Shared {
+    // Captures a dump and writes it to the instance `inst_id`, or to all
+    // instances if `None`. `on_written` is called with the context of each
+    // instance once the dump is written. Returns the id of the dump.
+    fn dump(&self, inst_id: Option<u32>, mut on_written: impl FnMut(&mut TraceContext)) -> u64 {
+        let _dumping = self.dumping.lock().unwrap();
+        let dump_id = self.next_dump_id.fetch_add(1, Ordering::Relaxed);
+        let (Some(data_source), Ok(dump)) = (
+            self.data_source.get(),
+            MemoryDump::capture_from(&self.smaps_path, std::process::id()),
+        ) else {
+            return dump_id;
+        };
+        data_source.trace(|ctx: &mut TraceContext| {
+            if inst_id.is_some_and(|inst_id| inst_id != ctx.instance_index()) {
+                return;
+            }
+            let timestamp = DataSourceTimestamp::now();
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet
+                    .set_timestamp(timestamp.timestamp())
+                    .set_timestamp_clock_id(timestamp.clock_id());
+                dump.write_snapshot(packet, dump_id, self.level_of_detail);
+            });
+            // Light dumps don't include the mappings, like in Chrome.
+            if self.level_of_detail == MemoryTrackerSnapshotLevelOfDetail::DetailFull {
+                ctx.add_packet(|packet: &mut TracePacket| {
+                    packet
+                        .set_timestamp(timestamp.timestamp())
+                        .set_timestamp_clock_id(timestamp.clock_id());
+                    dump.write_smaps_packet(packet);
+                });
+            }
+            on_written(ctx);
+        });
+        dump_id
+    }
+}
+
+/// Memory dump data source builder.
+#[must_use = "This is a builder; remember to call `.register()` (or keep chaining)."]
+pub struct MemoryDumpDataSourceBuilder {
+    name: String,
+    smaps_path: PathBuf,
+    level_of_detail: MemoryTrackerSnapshotLevelOfDetail,
+}
+
+impl Default for MemoryDumpDataSourceBuilder {
+    fn default() -> Self {
+        Self {
+            name: MEMORY_DUMP_DATA_SOURCE_NAME.to_string(),
+            smaps_path: PathBuf::from("/proc/self/smaps"),
+            level_of_detail: MemoryTrackerSnapshotLevelOfDetail::DetailFull,
+        }
+    }
+}
+
+impl MemoryDumpDataSourceBuilder {
+    /// Create new memory dump data source builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name of the data source. Defaults to
+    /// [`MEMORY_DUMP_DATA_SOURCE_NAME`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Set the `smaps` file that dumps are captured from. Defaults to
+    /// `/proc/self/smaps`.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn smaps_path(mut self, smaps_path: impl Into<PathBuf>) -> Self {
+        self.smaps_path = smaps_path.into();
+        self
+    }
+
+    /// Set the level of detail of the dumps. Defaults to `DetailFull`, which
+    /// also writes the mappings of the process as a `SmapsPacket`.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn level_of_detail(mut self, level_of_detail: MemoryTrackerSnapshotLevelOfDetail) -> Self {
+        self.level_of_detail = level_of_detail;
+        self
+    }
+
+    /// Registers the data source.
+    pub fn register(self) -> Result<MemoryDumpDataSource, DataSourceError> {
+        let shared = Arc::new(Shared {
+            smaps_path: self.smaps_path,
+            level_of_detail: self.level_of_detail,
+            data_source: OnceLock::new(),
+            next_dump_id: AtomicU64::new(1),
+            dumping: Mutex::new(()),
+        });
+        let flush_shared = Arc::clone(&shared);
+        let args = DataSourceArgsBuilder::new().on_flush(move |inst_id, args| {
+            // Acknowledge the flush once the dump is committed.
+            let mut guard = Some(args.postpone());
+            flush_shared.dump(Some(inst_id), |ctx| {
+                let mut guard = guard.take();
+                ctx.flush(move || drop(guard.take()));
+            });
+        });
+        let data_source: &'static mut DataSource<'static> = Box::leak(Box::new(DataSource::new()));
+        data_source.register(&self.name, args.build())?;
+        let _ = shared.data_source.set(data_source);
+        Ok(MemoryDumpDataSource { shared })
+    }
+}
+
+/// A data source that writes on-demand memory dumps of the current process,
+/// like Chrome's memory-infra: a `MemoryTrackerSnapshot` with a node per kind
+/// of mapping, followed by the mappings of the process as a `SmapsPacket` for
+/// detailed dumps.
+///
+/// A dump is taken when an instance is flushed, and written to that
+/// instance, so that `perfetto --flush` or a periodic flush of the trace
+/// config captures the memory of the process. The C API doesn't expose the
+/// flags of flush requests, so every flush takes a dump. Dumps can also be
+/// taken at any time with [`dump`](Self::dump).
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk_protos_memory::memory_dump::MemoryDumpDataSourceBuilder;
+///
+/// let data_source = MemoryDumpDataSourceBuilder::new().register().unwrap();
+/// data_source.dump();
+/// ```
+pub struct MemoryDumpDataSource {
+    shared: Arc<Shared>,
+}
+
+impl MemoryDumpDataSource {
+    /// Captures a memory dump and writes it to all the instances of the data
+    /// source. Returns the id of the dump.
+    pub fn dump(&self) -> u64 {
+        self.shared.dump(None, |_| {})
+    }
+
+    /// Returns the underlying data source.
+    pub fn data_source(&self) -> &'static DataSource<'static> {
+        self.shared.data_source.get().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::trace::trace_packet::TracePacketExtFieldNumber;
+    use perfetto_sdk::test_util::{acquire_test_environment, messages, record_packets, varint};
+
+    const DATA_SOURCE_NAME: &str = "com.example.memory_dump";
+    const LIGHT_DATA_SOURCE_NAME: &str = "com.example.memory_dump_light";
+
+    const SMAPS: &str = "\
+5581a0000000-5581a0021000 rw-p 00000000 00:00 0                          [heap]
+Size:                132 kB
+Rss:                  64 kB
+Pss:                  64 kB
+Shared_Clean:          0 kB
+Shared_Dirty:          0 kB
+Private_Clean:         0 kB
+Private_Dirty:        64 kB
+Swap:                  8 kB
+Locked:                0 kB
+VmFlags: rd wr mr mw me ac
+7f0000000000-7f0000001000 r-xp 00000000 08:02 1234                       /usr/lib/libc.so.6
+Size:                  4 kB
+Rss:                   4 kB
+Pss:                   2 kB
+Shared_Clean:          4 kB
+Shared_Dirty:          0 kB
+Private_Clean:         0 kB
+Private_Dirty:         0 kB
+Swap:                  0 kB
+Locked:                4 kB
+VmFlags: rd ex mr mw me
+7f0000002000-7f0000003000 ---p 00000000 00:00 0
+Size:                  4 kB
+Rss:                   0 kB
+";
+
+    fn regions() -> Vec<SmapsRegion> {
+        vec![
+            SmapsRegion {
+                start_address: 0x5581a0000000,
+                protection_flags: PROT_READ | PROT_WRITE,
+                path: "[heap]".to_string(),
+                size_kb: 132,
+                rss_kb: 64,
+                pss_kb: 64,
+                private_dirty_kb: 64,
+                swap_kb: 8,
+                ..Default::default()
+            },
+            SmapsRegion {
+                start_address: 0x7f0000000000,
+                protection_flags: PROT_READ | PROT_EXEC,
+                path: "/usr/lib/libc.so.6".to_string(),
+                size_kb: 4,
+                rss_kb: 4,
+                pss_kb: 2,
+                shared_clean_kb: 4,
+                locked_kb: 4,
+                ..Default::default()
+            },
+            SmapsRegion {
+                start_address: 0x7f0000002000,
+                size_kb: 4,
+                ..Default::default()
+            },
+        ]
+    }
+
+    // Registers the data sources once, with dumps read from a copy of `SMAPS`.
+    fn data_sources() -> &'static (MemoryDumpDataSource, MemoryDumpDataSource) {
+        static DATA_SOURCES: OnceLock<(MemoryDumpDataSource, MemoryDumpDataSource)> =
+            OnceLock::new();
+        DATA_SOURCES.get_or_init(|| {
+            let smaps_path = std::env::temp_dir()
+                .join(format!("perfetto_memory_dump_test_{}", std::process::id()));
+            fs::write(&smaps_path, SMAPS).unwrap();
+            let full = MemoryDumpDataSourceBuilder::new()
+                .name(DATA_SOURCE_NAME)
+                .smaps_path(&smaps_path)
+                .register()
+                .unwrap();
+            let light = MemoryDumpDataSourceBuilder::new()
+                .name(LIGHT_DATA_SOURCE_NAME)
+                .smaps_path(&smaps_path)
+                .level_of_detail(MemoryTrackerSnapshotLevelOfDetail::DetailLight)
+                .register()
+                .unwrap();
+            (full, light)
+        })
+    }
+
+    // Returns the snapshots and smaps packets of `packets`, in order.
+    fn dumps(packets: &[Vec<u8>]) -> (Vec<&[u8]>, Vec<&[u8]>) {
+        let extension = |id: u32| {
+            packets
+                .iter()
+                .flat_map(|packet| messages(packet, id))
+                .collect()
+        };
+        (
+            extension(TracePacketExtFieldNumber::MemoryTrackerSnapshot as u32),
+            extension(TracePacketExtFieldNumber::SmapsPacket as u32),
+        )
+    }
+
+    #[test]
+    fn parse() {
+        let regions = parse_smaps(SMAPS);
+        assert_eq!(regions, self::regions());
+        let node_names: Vec<_> = regions.iter().map(SmapsRegion::node_name).collect();
+        assert_eq!(node_names, ["mmaps/heap", "mmaps/file", "mmaps/anonymous"]);
+        assert!(parse_smaps("").is_empty());
+        assert!(parse_smaps("not a mapping\nSize: 4 kB\n").is_empty());
+    }
+
+    #[test]
+    fn full_dump() {
+        use MemoryTrackerSnapshotProcessSnapshotMemoryNodeFieldNumber as Node;
+        use MemoryTrackerSnapshotProcessSnapshotMemoryNodeMemoryNodeEntryFieldNumber as Entry;
+        let _lock = acquire_test_environment();
+        let (data_source, _) = data_sources();
+        let mut dump_id = 0;
+        let packets = record_packets(DATA_SOURCE_NAME, |_| {}, || dump_id = data_source.dump());
+        let (snapshots, smaps) = dumps(&packets);
+
+        // Flushes when the session stops may take more dumps.
+        let position = snapshots
+            .iter()
+            .position(|snapshot| {
+                varint(
+                    snapshot,
+                    MemoryTrackerSnapshotFieldNumber::GlobalDumpId as u32,
+                ) == Some(dump_id)
+            })
+            .unwrap();
+        let snapshot = snapshots[position];
+        assert_eq!(
+            varint(
+                snapshot,
+                MemoryTrackerSnapshotFieldNumber::LevelOfDetail as u32
+            ),
+            Some(MemoryTrackerSnapshotLevelOfDetail::DetailFull as u64)
+        );
+        let processes = messages(
+            snapshot,
+            MemoryTrackerSnapshotFieldNumber::ProcessMemoryDumps as u32,
+        );
+        assert_eq!(processes.len(), 1);
+        assert_eq!(
+            varint(
+                processes[0],
+                MemoryTrackerSnapshotProcessSnapshotFieldNumber::Pid as u32
+            ),
+            Some(std::process::id() as u64)
+        );
+        let nodes = messages(
+            processes[0],
+            MemoryTrackerSnapshotProcessSnapshotFieldNumber::AllocatorDumps as u32,
+        );
+        let summary: Vec<_> = nodes
+            .iter()
+            .map(|node| {
+                (
+                    varint(node, Node::Id as u32).unwrap(),
+                    messages(node, Node::AbsoluteName as u32)[0],
+                    varint(node, Node::SizeBytes as u32).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (1, b"mmaps/anonymous".as_slice(), 0),
+                (2, b"mmaps/file".as_slice(), 4 * 1024),
+                (3, b"mmaps/heap".as_slice(), 64 * 1024),
+            ]
+        );
+        let entries: Vec<_> = messages(nodes[2], Node::Entries as u32)
+            .iter()
+            .map(|entry| {
+                assert_eq!(
+                    varint(entry, Entry::Units as u32),
+                    Some(MemoryNodeEntryUnits::Bytes as u64)
+                );
+                (
+                    messages(entry, Entry::Name as u32)[0],
+                    varint(entry, Entry::ValueUint64 as u32).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            [
+                (b"size".as_slice(), 64 * 1024),
+                (b"virtual_size".as_slice(), 132 * 1024),
+                (b"private_dirty".as_slice(), 64 * 1024),
+                (b"swap".as_slice(), 8 * 1024),
+            ]
+        );
+
+        // Each snapshot is followed by the mappings of the process.
+        assert_eq!(smaps.len(), snapshots.len());
+        let smaps = smaps[position];
+        assert_eq!(
+            varint(smaps, SmapsPacketFieldNumber::Pid as u32),
+            Some(std::process::id() as u64)
+        );
+        assert_eq!(
+            varint(smaps, SmapsPacketFieldNumber::RecordingType as u32),
+            Some(SmapsPacketRecordingType::RecordingTypeStandalone as u64)
+        );
+        let entries = messages(smaps, SmapsPacketFieldNumber::Entries as u32);
+        assert_eq!(entries.len(), regions().len());
+        for (entry, region) in entries.iter().zip(regions()) {
+            let path = messages(entry, SmapsEntryFieldNumber::Path as u32);
+            assert_eq!(path, [region.path.as_bytes()]);
+            assert_eq!(
+                varint(entry, SmapsEntryFieldNumber::StartAddress as u32),
+                Some(region.start_address)
+            );
+            assert_eq!(
+                varint(entry, SmapsEntryFieldNumber::ProtectionFlags as u32),
+                Some(region.protection_flags as u64)
+            );
+            assert_eq!(
+                varint(entry, SmapsEntryFieldNumber::SizeKb as u32),
+                Some(region.size_kb)
+            );
+            assert_eq!(
+                varint(entry, SmapsEntryFieldNumber::ProportionalResidentKb as u32),
+                Some(region.pss_kb)
+            );
+            assert_eq!(
+                varint(entry, SmapsEntryFieldNumber::SharedCleanResidentKb as u32),
+                Some(region.shared_clean_kb)
+            );
+            assert_eq!(
+                varint(entry, SmapsEntryFieldNumber::PrivateDirtyKb as u32),
+                Some(region.private_dirty_kb)
+            );
+            assert_eq!(
+                varint(entry, SmapsEntryFieldNumber::LockedKb as u32),
+                Some(region.locked_kb)
+            );
+        }
+        let file_names: Vec<_> = entries
+            .iter()
+            .map(|entry| messages(entry, SmapsEntryFieldNumber::FileName as u32))
+            .collect();
+        assert_eq!(
+            file_names,
+            [vec![b"[heap]".as_slice()], vec![b"libc.so.6"], vec![]]
+        );
+    }
+
+    #[test]
+    fn light_dump() {
+        let _lock = acquire_test_environment();
+        let (_, data_source) = data_sources();
+        let mut dump_id = 0;
+        let packets = record_packets(
+            LIGHT_DATA_SOURCE_NAME,
+            |_| {},
+            || dump_id = data_source.dump(),
+        );
+        let (snapshots, smaps) = dumps(&packets);
+        assert!(snapshots.iter().any(|snapshot| {
+            varint(
+                snapshot,
+                MemoryTrackerSnapshotFieldNumber::GlobalDumpId as u32,
+            ) == Some(dump_id)
+        }));
+        for snapshot in snapshots {
+            assert_eq!(
+                varint(
+                    snapshot,
+                    MemoryTrackerSnapshotFieldNumber::LevelOfDetail as u32
+                ),
+                Some(MemoryTrackerSnapshotLevelOfDetail::DetailLight as u64)
+            );
+        }
+        assert!(smaps.is_empty());
+    }
+}
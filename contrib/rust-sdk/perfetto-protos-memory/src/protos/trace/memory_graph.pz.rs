@@ -0,0 +1,66 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Autogenerated by the ProtoZero Rust compiler plugin.
+// Invoked by contrib/rust-sdk/tools/gen_rust_protos
+// DO NOT EDIT.
+
+use crate::pb_enum;
+use crate::pb_msg;
+
+pb_enum!(MemoryTrackerSnapshotLevelOfDetail {
+    DETAIL_FULL: 0,
+    DETAIL_LIGHT: 1,
+    DETAIL_BACKGROUND: 2,
+});
+
+pb_enum!(MemoryNodeEntryUnits {
+    UNSPECIFIED: 0,
+    BYTES: 1,
+    COUNT: 2,
+});
+
+pb_msg!(MemoryTrackerSnapshot {
+    global_dump_id: u64, primitive, 1,
+    level_of_detail: MemoryTrackerSnapshotLevelOfDetail, enum, 2,
+    process_memory_dumps: [msg, MemoryTrackerSnapshotProcessSnapshot], repeated, 3,
+});
+
+pb_msg!(MemoryTrackerSnapshotProcessSnapshot {
+    pid: i32, primitive, 1,
+    allocator_dumps: [msg, MemoryTrackerSnapshotProcessSnapshotMemoryNode], repeated, 2,
+    memory_edges: [msg, MemoryTrackerSnapshotProcessSnapshotMemoryEdge], repeated, 3,
+});
+
+pb_msg!(MemoryTrackerSnapshotProcessSnapshotMemoryEdge {
+    source_id: u64, primitive, 1,
+    target_id: u64, primitive, 2,
+    importance: u32, primitive, 3,
+    overridable: bool, primitive, 4,
+});
+
+pb_msg!(MemoryTrackerSnapshotProcessSnapshotMemoryNode {
+    id: u64, primitive, 1,
+    absolute_name: String, primitive, 2,
+    weak: bool, primitive, 3,
+    size_bytes: u64, primitive, 4,
+    entries: [msg, MemoryTrackerSnapshotProcessSnapshotMemoryNodeMemoryNodeEntry], repeated, 5,
+});
+
+pb_msg!(MemoryTrackerSnapshotProcessSnapshotMemoryNodeMemoryNodeEntry {
+    name: String, primitive, 1,
+    units: MemoryNodeEntryUnits, enum, 2,
+    value_uint64: u64, primitive, 3,
+    value_string: String, primitive, 4,
+});
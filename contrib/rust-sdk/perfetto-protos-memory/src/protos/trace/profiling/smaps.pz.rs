@@ -0,0 +1,70 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Autogenerated by the ProtoZero Rust compiler plugin.
+// Invoked by contrib/rust-sdk/tools/gen_rust_protos
+// DO NOT EDIT.
+
+use crate::pb_enum;
+use crate::pb_msg;
+
+pb_enum!(SmapsPacketRecordingType {
+    RECORDING_TYPE_UNKNOWN: 0,
+    RECORDING_TYPE_STANDALONE: 1,
+    RECORDING_TYPE_ART_HPROF_POSTFORK: 2,
+    RECORDING_TYPE_ART_HPROF_PREFORK: 3,
+});
+
+pb_msg!(SmapsPacket {
+    pid: u32, primitive, 1,
+    entries: [msg, SmapsEntry], repeated, 2,
+    packed_entries: PackedSmaps, msg, 3,
+    recording_type: SmapsPacketRecordingType, enum, 4,
+});
+
+pb_msg!(PackedSmaps {
+    string_table: [primitive, String], repeated, 1,
+    name_id: [primitive, u32], packed, 2,
+    aggregate_count: [primitive, u32], packed, 3,
+    size_kb: [primitive, u64], packed, 4,
+    rss_kb: [primitive, u64], packed, 5,
+    anonymous_kb: [primitive, u64], packed, 6,
+    swap_kb: [primitive, u64], packed, 7,
+    shared_clean_kb: [primitive, u64], packed, 8,
+    shared_dirty_kb: [primitive, u64], packed, 9,
+    private_clean_kb: [primitive, u64], packed, 10,
+    private_dirty_kb: [primitive, u64], packed, 11,
+    locked_kb: [primitive, u64], packed, 12,
+    pss_kb: [primitive, u64], packed, 13,
+    pss_dirty_kb: [primitive, u64], packed, 14,
+    swap_pss_kb: [primitive, u64], packed, 15,
+});
+
+pb_msg!(SmapsEntry {
+    path: String, primitive, 1,
+    size_kb: u64, primitive, 2,
+    private_dirty_kb: u64, primitive, 3,
+    swap_kb: u64, primitive, 4,
+    file_name: String, primitive, 5,
+    start_address: u64, primitive, 6,
+    module_timestamp: u64, primitive, 7,
+    module_debugid: String, primitive, 8,
+    module_debug_path: String, primitive, 9,
+    protection_flags: u32, primitive, 10,
+    private_clean_resident_kb: u64, primitive, 11,
+    shared_dirty_resident_kb: u64, primitive, 12,
+    shared_clean_resident_kb: u64, primitive, 13,
+    locked_kb: u64, primitive, 14,
+    proportional_resident_kb: u64, primitive, 15,
+});
@@ -0,0 +1,32 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use perfetto_sdk::producer::*;
+
+use perfetto_sdk_protos_sys_stats::sys_stats_data_source::SysStatsDataSourceBuilder;
+
+use std::{error::Error, time::Duration};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let producer_args = ProducerInitArgsBuilder::new().backends(Backends::SYSTEM);
+    Producer::init(producer_args.build());
+    // Each instance reads the /proc files at the periods of its
+    // `SysStatsConfig`, on the polling thread of the data source.
+    let _data_source = SysStatsDataSourceBuilder::new()
+        .name("linux.sys_stats.example")
+        .register()?;
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
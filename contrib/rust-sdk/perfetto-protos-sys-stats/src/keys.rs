@@ -0,0 +1,499 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::protos::common::sys_stats_counters::{MeminfoCounters, VmstatCounters};
+
+// Names of the /proc/meminfo counters, as in
+// include/perfetto/ext/traced/sys_stats_counters.h.
+pub(crate) const MEMINFO_KEYS: &[(&str, MeminfoCounters)] = &[
+    ("MemTotal", MeminfoCounters::MeminfoMemTotal),
+    ("MemFree", MeminfoCounters::MeminfoMemFree),
+    ("MemAvailable", MeminfoCounters::MeminfoMemAvailable),
+    ("Buffers", MeminfoCounters::MeminfoBuffers),
+    ("Cached", MeminfoCounters::MeminfoCached),
+    ("SwapCached", MeminfoCounters::MeminfoSwapCached),
+    ("Active", MeminfoCounters::MeminfoActive),
+    ("Inactive", MeminfoCounters::MeminfoInactive),
+    ("Active(anon)", MeminfoCounters::MeminfoActiveAnon),
+    ("Inactive(anon)", MeminfoCounters::MeminfoInactiveAnon),
+    ("Active(file)", MeminfoCounters::MeminfoActiveFile),
+    ("Inactive(file)", MeminfoCounters::MeminfoInactiveFile),
+    ("Unevictable", MeminfoCounters::MeminfoUnevictable),
+    ("Mlocked", MeminfoCounters::MeminfoMlocked),
+    ("SwapTotal", MeminfoCounters::MeminfoSwapTotal),
+    ("SwapFree", MeminfoCounters::MeminfoSwapFree),
+    ("Dirty", MeminfoCounters::MeminfoDirty),
+    ("Writeback", MeminfoCounters::MeminfoWriteback),
+    ("AnonPages", MeminfoCounters::MeminfoAnonPages),
+    ("Mapped", MeminfoCounters::MeminfoMapped),
+    ("Shmem", MeminfoCounters::MeminfoShmem),
+    ("Slab", MeminfoCounters::MeminfoSlab),
+    ("SReclaimable", MeminfoCounters::MeminfoSlabReclaimable),
+    ("SUnreclaim", MeminfoCounters::MeminfoSlabUnreclaimable),
+    ("KernelStack", MeminfoCounters::MeminfoKernelStack),
+    ("PageTables", MeminfoCounters::MeminfoPageTables),
+    ("CommitLimit", MeminfoCounters::MeminfoCommitLimit),
+    ("Committed_AS", MeminfoCounters::MeminfoCommitedAs),
+    ("VmallocTotal", MeminfoCounters::MeminfoVmallocTotal),
+    ("VmallocUsed", MeminfoCounters::MeminfoVmallocUsed),
+    ("VmallocChunk", MeminfoCounters::MeminfoVmallocChunk),
+    ("CmaTotal", MeminfoCounters::MeminfoCmaTotal),
+    ("CmaFree", MeminfoCounters::MeminfoCmaFree),
+    ("Gpu", MeminfoCounters::MeminfoGpu),
+    ("Zram", MeminfoCounters::MeminfoZram),
+    ("Misc", MeminfoCounters::MeminfoMisc),
+    ("ION_heap", MeminfoCounters::MeminfoIonHeap),
+    ("ION_heap_pool", MeminfoCounters::MeminfoIonHeapPool),
+];
+
+// Names of the /proc/vmstat counters, as in
+// include/perfetto/ext/traced/sys_stats_counters.h.
+pub(crate) const VMSTAT_KEYS: &[(&str, VmstatCounters)] = &[
+    ("nr_free_pages", VmstatCounters::VmstatNrFreePages),
+    ("nr_alloc_batch", VmstatCounters::VmstatNrAllocBatch),
+    ("nr_inactive_anon", VmstatCounters::VmstatNrInactiveAnon),
+    ("nr_active_anon", VmstatCounters::VmstatNrActiveAnon),
+    ("nr_inactive_file", VmstatCounters::VmstatNrInactiveFile),
+    ("nr_active_file", VmstatCounters::VmstatNrActiveFile),
+    ("nr_unevictable", VmstatCounters::VmstatNrUnevictable),
+    ("nr_mlock", VmstatCounters::VmstatNrMlock),
+    ("nr_anon_pages", VmstatCounters::VmstatNrAnonPages),
+    ("nr_mapped", VmstatCounters::VmstatNrMapped),
+    ("nr_file_pages", VmstatCounters::VmstatNrFilePages),
+    ("nr_dirty", VmstatCounters::VmstatNrDirty),
+    ("nr_writeback", VmstatCounters::VmstatNrWriteback),
+    (
+        "nr_slab_reclaimable",
+        VmstatCounters::VmstatNrSlabReclaimable,
+    ),
+    (
+        "nr_slab_unreclaimable",
+        VmstatCounters::VmstatNrSlabUnreclaimable,
+    ),
+    (
+        "nr_page_table_pages",
+        VmstatCounters::VmstatNrPageTablePages,
+    ),
+    ("nr_kernel_stack", VmstatCounters::VmstatNrKernelStack),
+    ("nr_overhead", VmstatCounters::VmstatNrOverhead),
+    ("nr_unstable", VmstatCounters::VmstatNrUnstable),
+    ("nr_bounce", VmstatCounters::VmstatNrBounce),
+    ("nr_vmscan_write", VmstatCounters::VmstatNrVmscanWrite),
+    (
+        "nr_vmscan_immediate_reclaim",
+        VmstatCounters::VmstatNrVmscanImmediateReclaim,
+    ),
+    ("nr_writeback_temp", VmstatCounters::VmstatNrWritebackTemp),
+    ("nr_isolated_anon", VmstatCounters::VmstatNrIsolatedAnon),
+    ("nr_isolated_file", VmstatCounters::VmstatNrIsolatedFile),
+    ("nr_shmem", VmstatCounters::VmstatNrShmem),
+    ("nr_dirtied", VmstatCounters::VmstatNrDirtied),
+    ("nr_written", VmstatCounters::VmstatNrWritten),
+    ("nr_pages_scanned", VmstatCounters::VmstatNrPagesScanned),
+    (
+        "workingset_refault",
+        VmstatCounters::VmstatWorkingsetRefault,
+    ),
+    (
+        "workingset_activate",
+        VmstatCounters::VmstatWorkingsetActivate,
+    ),
+    (
+        "workingset_nodereclaim",
+        VmstatCounters::VmstatWorkingsetNodereclaim,
+    ),
+    (
+        "nr_anon_transparent_hugepages",
+        VmstatCounters::VmstatNrAnonTransparentHugepages,
+    ),
+    ("nr_free_cma", VmstatCounters::VmstatNrFreeCma),
+    ("nr_swapcache", VmstatCounters::VmstatNrSwapcache),
+    ("nr_dirty_threshold", VmstatCounters::VmstatNrDirtyThreshold),
+    (
+        "nr_dirty_background_threshold",
+        VmstatCounters::VmstatNrDirtyBackgroundThreshold,
+    ),
+    ("pgpgin", VmstatCounters::VmstatPgpgin),
+    ("pgpgout", VmstatCounters::VmstatPgpgout),
+    ("pgpgoutclean", VmstatCounters::VmstatPgpgoutclean),
+    ("pswpin", VmstatCounters::VmstatPswpin),
+    ("pswpout", VmstatCounters::VmstatPswpout),
+    ("pgalloc_dma", VmstatCounters::VmstatPgallocDma),
+    ("pgalloc_normal", VmstatCounters::VmstatPgallocNormal),
+    ("pgalloc_movable", VmstatCounters::VmstatPgallocMovable),
+    ("pgfree", VmstatCounters::VmstatPgfree),
+    ("pgactivate", VmstatCounters::VmstatPgactivate),
+    ("pgdeactivate", VmstatCounters::VmstatPgdeactivate),
+    ("pgfault", VmstatCounters::VmstatPgfault),
+    ("pgmajfault", VmstatCounters::VmstatPgmajfault),
+    ("pgrefill_dma", VmstatCounters::VmstatPgrefillDma),
+    ("pgrefill_normal", VmstatCounters::VmstatPgrefillNormal),
+    ("pgrefill_movable", VmstatCounters::VmstatPgrefillMovable),
+    ("pgsteal_kswapd_dma", VmstatCounters::VmstatPgstealKswapdDma),
+    (
+        "pgsteal_kswapd_normal",
+        VmstatCounters::VmstatPgstealKswapdNormal,
+    ),
+    (
+        "pgsteal_kswapd_movable",
+        VmstatCounters::VmstatPgstealKswapdMovable,
+    ),
+    ("pgsteal_direct_dma", VmstatCounters::VmstatPgstealDirectDma),
+    (
+        "pgsteal_direct_normal",
+        VmstatCounters::VmstatPgstealDirectNormal,
+    ),
+    (
+        "pgsteal_direct_movable",
+        VmstatCounters::VmstatPgstealDirectMovable,
+    ),
+    ("pgscan_kswapd_dma", VmstatCounters::VmstatPgscanKswapdDma),
+    (
+        "pgscan_kswapd_normal",
+        VmstatCounters::VmstatPgscanKswapdNormal,
+    ),
+    (
+        "pgscan_kswapd_movable",
+        VmstatCounters::VmstatPgscanKswapdMovable,
+    ),
+    ("pgscan_direct_dma", VmstatCounters::VmstatPgscanDirectDma),
+    (
+        "pgscan_direct_normal",
+        VmstatCounters::VmstatPgscanDirectNormal,
+    ),
+    (
+        "pgscan_direct_movable",
+        VmstatCounters::VmstatPgscanDirectMovable,
+    ),
+    (
+        "pgscan_direct_throttle",
+        VmstatCounters::VmstatPgscanDirectThrottle,
+    ),
+    ("pginodesteal", VmstatCounters::VmstatPginodesteal),
+    ("slabs_scanned", VmstatCounters::VmstatSlabsScanned),
+    ("kswapd_inodesteal", VmstatCounters::VmstatKswapdInodesteal),
+    (
+        "kswapd_low_wmark_hit_quickly",
+        VmstatCounters::VmstatKswapdLowWmarkHitQuickly,
+    ),
+    (
+        "kswapd_high_wmark_hit_quickly",
+        VmstatCounters::VmstatKswapdHighWmarkHitQuickly,
+    ),
+    ("pageoutrun", VmstatCounters::VmstatPageoutrun),
+    ("allocstall", VmstatCounters::VmstatAllocstall),
+    ("pgrotated", VmstatCounters::VmstatPgrotated),
+    ("drop_pagecache", VmstatCounters::VmstatDropPagecache),
+    ("drop_slab", VmstatCounters::VmstatDropSlab),
+    ("pgmigrate_success", VmstatCounters::VmstatPgmigrateSuccess),
+    ("pgmigrate_fail", VmstatCounters::VmstatPgmigrateFail),
+    (
+        "compact_migrate_scanned",
+        VmstatCounters::VmstatCompactMigrateScanned,
+    ),
+    (
+        "compact_free_scanned",
+        VmstatCounters::VmstatCompactFreeScanned,
+    ),
+    ("compact_isolated", VmstatCounters::VmstatCompactIsolated),
+    ("compact_stall", VmstatCounters::VmstatCompactStall),
+    ("compact_fail", VmstatCounters::VmstatCompactFail),
+    ("compact_success", VmstatCounters::VmstatCompactSuccess),
+    (
+        "compact_daemon_wake",
+        VmstatCounters::VmstatCompactDaemonWake,
+    ),
+    (
+        "unevictable_pgs_culled",
+        VmstatCounters::VmstatUnevictablePgsCulled,
+    ),
+    (
+        "unevictable_pgs_scanned",
+        VmstatCounters::VmstatUnevictablePgsScanned,
+    ),
+    (
+        "unevictable_pgs_rescued",
+        VmstatCounters::VmstatUnevictablePgsRescued,
+    ),
+    (
+        "unevictable_pgs_mlocked",
+        VmstatCounters::VmstatUnevictablePgsMlocked,
+    ),
+    (
+        "unevictable_pgs_munlocked",
+        VmstatCounters::VmstatUnevictablePgsMunlocked,
+    ),
+    (
+        "unevictable_pgs_cleared",
+        VmstatCounters::VmstatUnevictablePgsCleared,
+    ),
+    (
+        "unevictable_pgs_stranded",
+        VmstatCounters::VmstatUnevictablePgsStranded,
+    ),
+    ("nr_zspages", VmstatCounters::VmstatNrZspages),
+    ("nr_ion_heap", VmstatCounters::VmstatNrIonHeap),
+    ("nr_gpu_heap", VmstatCounters::VmstatNrGpuHeap),
+    ("allocstall_dma", VmstatCounters::VmstatAllocstallDma),
+    (
+        "allocstall_movable",
+        VmstatCounters::VmstatAllocstallMovable,
+    ),
+    ("allocstall_normal", VmstatCounters::VmstatAllocstallNormal),
+    (
+        "compact_daemon_free_scanned",
+        VmstatCounters::VmstatCompactDaemonFreeScanned,
+    ),
+    (
+        "compact_daemon_migrate_scanned",
+        VmstatCounters::VmstatCompactDaemonMigrateScanned,
+    ),
+    ("nr_fastrpc", VmstatCounters::VmstatNrFastrpc),
+    (
+        "nr_indirectly_reclaimable",
+        VmstatCounters::VmstatNrIndirectlyReclaimable,
+    ),
+    ("nr_ion_heap_pool", VmstatCounters::VmstatNrIonHeapPool),
+    (
+        "nr_kernel_misc_reclaimable",
+        VmstatCounters::VmstatNrKernelMiscReclaimable,
+    ),
+    (
+        "nr_shadow_call_stack_bytes",
+        VmstatCounters::VmstatNrShadowCallStackBytes,
+    ),
+    ("nr_shmem_hugepages", VmstatCounters::VmstatNrShmemHugepages),
+    ("nr_shmem_pmdmapped", VmstatCounters::VmstatNrShmemPmdmapped),
+    (
+        "nr_unreclaimable_pages",
+        VmstatCounters::VmstatNrUnreclaimablePages,
+    ),
+    (
+        "nr_zone_active_anon",
+        VmstatCounters::VmstatNrZoneActiveAnon,
+    ),
+    (
+        "nr_zone_active_file",
+        VmstatCounters::VmstatNrZoneActiveFile,
+    ),
+    (
+        "nr_zone_inactive_anon",
+        VmstatCounters::VmstatNrZoneInactiveAnon,
+    ),
+    (
+        "nr_zone_inactive_file",
+        VmstatCounters::VmstatNrZoneInactiveFile,
+    ),
+    (
+        "nr_zone_unevictable",
+        VmstatCounters::VmstatNrZoneUnevictable,
+    ),
+    (
+        "nr_zone_write_pending",
+        VmstatCounters::VmstatNrZoneWritePending,
+    ),
+    ("oom_kill", VmstatCounters::VmstatOomKill),
+    ("pglazyfree", VmstatCounters::VmstatPglazyfree),
+    ("pglazyfreed", VmstatCounters::VmstatPglazyfreed),
+    ("pgrefill", VmstatCounters::VmstatPgrefill),
+    ("pgscan_direct", VmstatCounters::VmstatPgscanDirect),
+    ("pgscan_kswapd", VmstatCounters::VmstatPgscanKswapd),
+    ("pgskip_dma", VmstatCounters::VmstatPgskipDma),
+    ("pgskip_movable", VmstatCounters::VmstatPgskipMovable),
+    ("pgskip_normal", VmstatCounters::VmstatPgskipNormal),
+    ("pgsteal_direct", VmstatCounters::VmstatPgstealDirect),
+    ("pgsteal_kswapd", VmstatCounters::VmstatPgstealKswapd),
+    ("swap_ra_hit", VmstatCounters::VmstatSwapRaHit),
+    ("swap_ra", VmstatCounters::VmstatSwapRa),
+    (
+        "workingset_restore",
+        VmstatCounters::VmstatWorkingsetRestore,
+    ),
+    ("allocstall_device", VmstatCounters::VmstatAllocstallDevice),
+    ("allocstall_dma32", VmstatCounters::VmstatAllocstallDma32),
+    ("balloon_deflate", VmstatCounters::VmstatBalloonDeflate),
+    ("balloon_inflate", VmstatCounters::VmstatBalloonInflate),
+    ("balloon_migrate", VmstatCounters::VmstatBalloonMigrate),
+    ("cma_alloc_fail", VmstatCounters::VmstatCmaAllocFail),
+    ("cma_alloc_success", VmstatCounters::VmstatCmaAllocSuccess),
+    ("nr_file_hugepages", VmstatCounters::VmstatNrFileHugepages),
+    ("nr_file_pmdmapped", VmstatCounters::VmstatNrFilePmdmapped),
+    (
+        "nr_foll_pin_acquired",
+        VmstatCounters::VmstatNrFollPinAcquired,
+    ),
+    (
+        "nr_foll_pin_released",
+        VmstatCounters::VmstatNrFollPinReleased,
+    ),
+    (
+        "nr_sec_page_table_pages",
+        VmstatCounters::VmstatNrSecPageTablePages,
+    ),
+    (
+        "nr_shadow_call_stack",
+        VmstatCounters::VmstatNrShadowCallStack,
+    ),
+    ("nr_swapcached", VmstatCounters::VmstatNrSwapcached),
+    (
+        "nr_throttled_written",
+        VmstatCounters::VmstatNrThrottledWritten,
+    ),
+    ("pgalloc_device", VmstatCounters::VmstatPgallocDevice),
+    ("pgalloc_dma32", VmstatCounters::VmstatPgallocDma32),
+    ("pgdemote_direct", VmstatCounters::VmstatPgdemoteDirect),
+    ("pgdemote_kswapd", VmstatCounters::VmstatPgdemoteKswapd),
+    ("pgreuse", VmstatCounters::VmstatPgreuse),
+    ("pgscan_anon", VmstatCounters::VmstatPgscanAnon),
+    ("pgscan_file", VmstatCounters::VmstatPgscanFile),
+    ("pgskip_device", VmstatCounters::VmstatPgskipDevice),
+    ("pgskip_dma32", VmstatCounters::VmstatPgskipDma32),
+    ("pgsteal_anon", VmstatCounters::VmstatPgstealAnon),
+    ("pgsteal_file", VmstatCounters::VmstatPgstealFile),
+    ("thp_collapse_alloc", VmstatCounters::VmstatThpCollapseAlloc),
+    (
+        "thp_collapse_alloc_failed",
+        VmstatCounters::VmstatThpCollapseAllocFailed,
+    ),
+    (
+        "thp_deferred_split_page",
+        VmstatCounters::VmstatThpDeferredSplitPage,
+    ),
+    ("thp_fault_alloc", VmstatCounters::VmstatThpFaultAlloc),
+    ("thp_fault_fallback", VmstatCounters::VmstatThpFaultFallback),
+    (
+        "thp_fault_fallback_charge",
+        VmstatCounters::VmstatThpFaultFallbackCharge,
+    ),
+    ("thp_file_alloc", VmstatCounters::VmstatThpFileAlloc),
+    ("thp_file_fallback", VmstatCounters::VmstatThpFileFallback),
+    (
+        "thp_file_fallback_charge",
+        VmstatCounters::VmstatThpFileFallbackCharge,
+    ),
+    ("thp_file_mapped", VmstatCounters::VmstatThpFileMapped),
+    ("thp_migration_fail", VmstatCounters::VmstatThpMigrationFail),
+    (
+        "thp_migration_split",
+        VmstatCounters::VmstatThpMigrationSplit,
+    ),
+    (
+        "thp_migration_success",
+        VmstatCounters::VmstatThpMigrationSuccess,
+    ),
+    (
+        "thp_scan_exceed_none_pte",
+        VmstatCounters::VmstatThpScanExceedNonePte,
+    ),
+    (
+        "thp_scan_exceed_share_pte",
+        VmstatCounters::VmstatThpScanExceedSharePte,
+    ),
+    (
+        "thp_scan_exceed_swap_pte",
+        VmstatCounters::VmstatThpScanExceedSwapPte,
+    ),
+    ("thp_split_page", VmstatCounters::VmstatThpSplitPage),
+    (
+        "thp_split_page_failed",
+        VmstatCounters::VmstatThpSplitPageFailed,
+    ),
+    ("thp_split_pmd", VmstatCounters::VmstatThpSplitPmd),
+    ("thp_swpout", VmstatCounters::VmstatThpSwpout),
+    (
+        "thp_swpout_fallback",
+        VmstatCounters::VmstatThpSwpoutFallback,
+    ),
+    (
+        "thp_zero_page_alloc",
+        VmstatCounters::VmstatThpZeroPageAlloc,
+    ),
+    (
+        "thp_zero_page_alloc_failed",
+        VmstatCounters::VmstatThpZeroPageAllocFailed,
+    ),
+    ("vma_lock_abort", VmstatCounters::VmstatVmaLockAbort),
+    ("vma_lock_miss", VmstatCounters::VmstatVmaLockMiss),
+    ("vma_lock_retry", VmstatCounters::VmstatVmaLockRetry),
+    ("vma_lock_success", VmstatCounters::VmstatVmaLockSuccess),
+    (
+        "workingset_activate_anon",
+        VmstatCounters::VmstatWorkingsetActivateAnon,
+    ),
+    (
+        "workingset_activate_file",
+        VmstatCounters::VmstatWorkingsetActivateFile,
+    ),
+    ("workingset_nodes", VmstatCounters::VmstatWorkingsetNodes),
+    (
+        "workingset_refault_anon",
+        VmstatCounters::VmstatWorkingsetRefaultAnon,
+    ),
+    (
+        "workingset_refault_file",
+        VmstatCounters::VmstatWorkingsetRefaultFile,
+    ),
+    (
+        "workingset_restore_anon",
+        VmstatCounters::VmstatWorkingsetRestoreAnon,
+    ),
+    (
+        "workingset_restore_file",
+        VmstatCounters::VmstatWorkingsetRestoreFile,
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    // Checks that each name of `keys` is unique and that every counter in
+    // `counters` has a single name.
+    fn check_keys(keys: &[(&str, u32)], counters: impl Iterator<Item = u32>) {
+        let names: HashSet<_> = keys.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names.len(), keys.len());
+        let mapped: HashSet<_> = keys.iter().map(|(_, key)| *key).collect();
+        assert_eq!(mapped.len(), keys.len());
+        assert_eq!(mapped, counters.collect());
+    }
+
+    #[test]
+    fn meminfo_keys() {
+        let keys: Vec<_> = MEMINFO_KEYS
+            .iter()
+            .map(|(name, key)| (*name, *key as u32))
+            .collect();
+        // All the counters but the unspecified one.
+        check_keys(
+            &keys,
+            (1..256).filter(|value| MeminfoCounters::try_from(*value).is_ok()),
+        );
+        assert!(MEMINFO_KEYS.contains(&("Committed_AS", MeminfoCounters::MeminfoCommitedAs)));
+        assert!(MEMINFO_KEYS.contains(&("SReclaimable", MeminfoCounters::MeminfoSlabReclaimable)));
+    }
+
+    #[test]
+    fn vmstat_keys() {
+        let keys: Vec<_> = VMSTAT_KEYS
+            .iter()
+            .map(|(name, key)| (*name, *key as u32))
+            .collect();
+        check_keys(
+            &keys,
+            (1..256).filter(|value| VmstatCounters::try_from(*value).is_ok()),
+        );
+        assert!(VMSTAT_KEYS.contains(&("nr_free_pages", VmstatCounters::VmstatNrFreePages)));
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Autogenerated by the ProtoZero Rust compiler plugin.
+// Invoked by contrib/rust-sdk/tools/gen_rust_protos
+// DO NOT EDIT.
+
+use crate::pb_enum;
+use crate::pb_msg;
+use crate::protos::common::sys_stats_counters::*;
+
+pb_enum!(SysStatsConfigStatCounters {
+    STAT_UNSPECIFIED: 0,
+    STAT_CPU_TIMES: 1,
+    STAT_IRQ_COUNTS: 2,
+    STAT_SOFTIRQ_COUNTS: 3,
+    STAT_FORK_COUNT: 4,
+});
+
+pb_msg!(SysStatsConfig {
+    meminfo_period_ms: u32, primitive, 1,
+    meminfo_counters: [enum, MeminfoCounters], repeated, 2,
+    vmstat_period_ms: u32, primitive, 3,
+    vmstat_counters: [enum, VmstatCounters], repeated, 4,
+    stat_period_ms: u32, primitive, 5,
+    stat_counters: [enum, SysStatsConfigStatCounters], repeated, 6,
+    devfreq_period_ms: u32, primitive, 7,
+    cpufreq_period_ms: u32, primitive, 8,
+    buddyinfo_period_ms: u32, primitive, 9,
+    diskstat_period_ms: u32, primitive, 10,
+    psi_period_ms: u32, primitive, 11,
+    thermal_period_ms: u32, primitive, 12,
+    cpuidle_period_ms: u32, primitive, 13,
+    gpufreq_period_ms: u32, primitive, 14,
+    slab_period_ms: u32, primitive, 15,
+});
@@ -0,0 +1,129 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Autogenerated by the ProtoZero Rust compiler plugin.
+// Invoked by contrib/rust-sdk/tools/gen_rust_protos
+// DO NOT EDIT.
+
+use crate::pb_enum;
+use crate::pb_msg;
+use crate::protos::common::sys_stats_counters::*;
+
+pb_enum!(PsiSamplePsiResource {
+    PSI_RESOURCE_UNSPECIFIED: 0,
+    PSI_RESOURCE_CPU_SOME: 1,
+    PSI_RESOURCE_CPU_FULL: 2,
+    PSI_RESOURCE_IO_SOME: 3,
+    PSI_RESOURCE_IO_FULL: 4,
+    PSI_RESOURCE_MEMORY_SOME: 5,
+    PSI_RESOURCE_MEMORY_FULL: 6,
+});
+
+pb_msg!(SysStats {
+    meminfo: [msg, SysStatsMeminfoValue], repeated, 1,
+    vmstat: [msg, SysStatsVmstatValue], repeated, 2,
+    cpu_stat: [msg, SysStatsCpuTimes], repeated, 3,
+    num_forks: u64, primitive, 4,
+    num_irq_total: u64, primitive, 5,
+    num_irq: [msg, SysStatsInterruptCount], repeated, 6,
+    num_softirq_total: u64, primitive, 7,
+    num_softirq: [msg, SysStatsInterruptCount], repeated, 8,
+    collection_end_timestamp: u64, primitive, 9,
+    devfreq: [msg, SysStatsDevfreqValue], repeated, 10,
+    cpufreq_khz: [primitive, u32], repeated, 11,
+    buddy_info: [msg, SysStatsBuddyInfo], repeated, 12,
+    disk_stat: [msg, SysStatsDiskStat], repeated, 13,
+    psi: SysStatsPsiSample, msg, 14,
+    thermal_zone: SysStatsThermalZone, msg, 15,
+    cpuidle_state: [msg, SysStatsCpuIdleState], repeated, 16,
+    gpufreq_mhz: [primitive, u64], repeated, 17,
+    slab_info: [msg, SysStatsSlabInfo], repeated, 18,
+});
+
+pb_msg!(SysStatsSlabInfo {
+    name: String, primitive, 1,
+    pages_per_slab: u32, primitive, 2,
+    num_slabs: u32, primitive, 3,
+});
+
+pb_msg!(SysStatsCpuIdleState {
+    cpu_id: u32, primitive, 1,
+    cpuidle_state_entry: [msg, SysStatsCpuIdleStateEntry], repeated, 2,
+});
+
+pb_msg!(SysStatsCpuIdleStateEntry {
+    state: String, primitive, 1,
+    duration_us: u64, primitive, 2,
+});
+
+pb_msg!(SysStatsThermalZone {
+    name: String, primitive, 1,
+    temp: u64, primitive, 2,
+    type: String, primitive, 3,
+});
+
+pb_msg!(SysStatsPsiSample {
+    resource: PsiSamplePsiResource, enum, 1,
+    total_ns: u64, primitive, 2,
+});
+
+pb_msg!(SysStatsDiskStat {
+    device_name: String, primitive, 1,
+    read_sectors: u64, primitive, 2,
+    read_time_ms: u64, primitive, 3,
+    write_sectors: u64, primitive, 4,
+    write_time_ms: u64, primitive, 5,
+    discard_sectors: u64, primitive, 6,
+    discard_time_ms: u64, primitive, 7,
+    flush_count: u64, primitive, 8,
+    flush_time_ms: u64, primitive, 9,
+});
+
+pb_msg!(SysStatsBuddyInfo {
+    node: String, primitive, 1,
+    zone: String, primitive, 2,
+    order_pages: [primitive, u32], repeated, 3,
+});
+
+pb_msg!(SysStatsDevfreqValue {
+    key: String, primitive, 1,
+    value: u64, primitive, 2,
+});
+
+pb_msg!(SysStatsInterruptCount {
+    irq: i32, primitive, 1,
+    count: u64, primitive, 2,
+});
+
+pb_msg!(SysStatsCpuTimes {
+    cpu_id: u32, primitive, 1,
+    user_ns: u64, primitive, 2,
+    user_nice_ns: u64, primitive, 3,
+    system_mode_ns: u64, primitive, 4,
+    idle_ns: u64, primitive, 5,
+    io_wait_ns: u64, primitive, 6,
+    irq_ns: u64, primitive, 7,
+    softirq_ns: u64, primitive, 8,
+    steal_ns: u64, primitive, 9,
+});
+
+pb_msg!(SysStatsVmstatValue {
+    key: VmstatCounters, enum, 1,
+    value: u64, primitive, 2,
+});
+
+pb_msg!(SysStatsMeminfoValue {
+    key: MeminfoCounters, enum, 1,
+    value: u64, primitive, 2,
+});
@@ -0,0 +1,705 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    keys::{MEMINFO_KEYS, VMSTAT_KEYS},
+    protos::{
+        common::sys_stats_counters::{MeminfoCounters, VmstatCounters},
+        config::{
+            data_source_config::DataSourceConfigExtFieldNumber,
+            sys_stats::sys_stats_config::{SysStatsConfigFieldNumber, SysStatsConfigStatCounters},
+        },
+        trace::{sys_stats::sys_stats::*, trace_packet::prelude::*},
+    },
+};
+use perfetto_sdk::{
+    data_source::{DataSourceArgsBuilder, DataSourceError, DataSourceTimestamp, TraceContext},
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField},
+    polling::{PollingDataSource, PollingDataSourceBuilder},
+    protos::trace::trace_packet::TracePacket,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Name of the data source registered by default by
+/// [`SysStatsDataSourceBuilder::register`], the same as the one of
+/// `traced_probes`.
+pub const SYS_STATS_DATA_SOURCE_NAME: &str = "linux.sys_stats";
+
+// Shortest period of the counters, the same as the one of `traced_probes`.
+const MIN_PERIOD: Duration = Duration::from_millis(10);
+
+// Duration of a clock tick of /proc/stat. USER_HZ is 100 on all the
+// architectures supported by the SDK.
+const NS_PER_USER_HZ: u64 = 10_000_000;
+
+/// Fields of the `SysStatsConfig` of a data source instance, decoded from the
+/// config passed to the `on_setup` callback.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SysStatsInstanceConfig {
+    /// Period at which /proc/meminfo is read, if it is.
+    pub meminfo_period: Option<Duration>,
+    /// The /proc/meminfo counters to write, or all of them if empty.
+    pub meminfo_counters: Vec<MeminfoCounters>,
+    /// Period at which /proc/vmstat is read, if it is.
+    pub vmstat_period: Option<Duration>,
+    /// The /proc/vmstat counters to write, or all of them if empty.
+    pub vmstat_counters: Vec<VmstatCounters>,
+    /// Period at which /proc/stat is read, if it is.
+    pub stat_period: Option<Duration>,
+    /// The /proc/stat counters to write, or all of them if empty.
+    pub stat_counters: Vec<SysStatsConfigStatCounters>,
+}
+
+impl SysStatsInstanceConfig {
+    /// Decodes the `sys_stats_config` field of the encoded `DataSourceConfig`
+    /// in `config`. Periods are clamped to 10ms, like in `traced_probes`.
+    /// Unknown fields and counters are ignored.
+    pub fn decode(config: &[u8]) -> Result<Self, PbDecoderError> {
+        use SysStatsConfigFieldNumber as Field;
+        const SYS_STATS_CONFIG_ID: u32 = DataSourceConfigExtFieldNumber::SysStatsConfig as u32;
+        let period =
+            |value: u64| (value != 0).then(|| Duration::from_millis(value).max(MIN_PERIOD));
+        let mut decoded = Self::default();
+        for item in PbDecoder::new(config) {
+            let (id, field) = item?;
+            if id != SYS_STATS_CONFIG_ID {
+                continue;
+            }
+            for item in field.as_decoder()? {
+                let (id, PbDecoderField::Varint(value)) = item? else {
+                    continue;
+                };
+                let value32 = value as u32;
+                match id {
+                    id if id == Field::MeminfoPeriodMs as u32 => {
+                        decoded.meminfo_period = period(value)
+                    }
+                    id if id == Field::MeminfoCounters as u32 => {
+                        decoded
+                            .meminfo_counters
+                            .extend(MeminfoCounters::try_from(value32).ok());
+                    }
+                    id if id == Field::VmstatPeriodMs as u32 => {
+                        decoded.vmstat_period = period(value)
+                    }
+                    id if id == Field::VmstatCounters as u32 => {
+                        decoded
+                            .vmstat_counters
+                            .extend(VmstatCounters::try_from(value32).ok());
+                    }
+                    id if id == Field::StatPeriodMs as u32 => decoded.stat_period = period(value),
+                    id if id == Field::StatCounters as u32 => {
+                        decoded
+                            .stat_counters
+                            .extend(SysStatsConfigStatCounters::try_from(value32).ok());
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(decoded)
+    }
+
+    /// Returns the shortest period of the files that are read, at which the
+    /// instance is polled, or `None` if no file is read.
+    pub fn poll_period(&self) -> Option<Duration> {
+        [self.meminfo_period, self.vmstat_period, self.stat_period]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+}
+
+// Counters of a /proc file that are written by an instance.
+struct ProcFile<K> {
+    // Number of polls between two reads of the file, or 0 if it isn't read.
+    ticks: u64,
+    keys: HashMap<&'static str, K>,
+}
+
+impl<K: Copy + PartialEq> ProcFile<K> {
+    fn new(
+        period: Option<Duration>,
+        poll_period: Duration,
+        all: &[(&'static str, K)],
+        selected: &[K],
+    ) -> Self {
+        Self {
+            ticks: period.map_or(0, |period| {
+                (period.as_nanos() / poll_period.as_nanos()).max(1) as u64
+            }),
+            keys: all
+                .iter()
+                .filter(|(_, key)| selected.is_empty() || selected.contains(key))
+                .copied()
+                .collect(),
+        }
+    }
+}
+
+impl<K> ProcFile<K> {
+    fn is_due(&self, polls: u64) -> bool {
+        self.ticks != 0 && polls.is_multiple_of(self.ticks)
+    }
+}
+
+struct InstanceState {
+    meminfo: ProcFile<MeminfoCounters>,
+    vmstat: ProcFile<VmstatCounters>,
+    stat: ProcFile<()>,
+    // Bit mask of the enabled `SysStatsConfigStatCounters`.
+    stat_fields: u32,
+    polls: u64,
+}
+
+impl InstanceState {
+    fn new(config: &SysStatsInstanceConfig) -> Self {
+        let poll_period = config.poll_period().unwrap_or(Duration::MAX);
+        let stat_fields = if config.stat_counters.is_empty() {
+            !0
+        } else {
+            config
+                .stat_counters
+                .iter()
+                .fold(0, |fields, counter| fields | 1 << *counter as u32)
+        };
+        Self {
+            meminfo: ProcFile::new(
+                config.meminfo_period,
+                poll_period,
+                MEMINFO_KEYS,
+                &config.meminfo_counters,
+            ),
+            vmstat: ProcFile::new(
+                config.vmstat_period,
+                poll_period,
+                VMSTAT_KEYS,
+                &config.vmstat_counters,
+            ),
+            stat: ProcFile::new(config.stat_period, poll_period, &[], &[]),
+            stat_fields,
+            polls: 0,
+        }
+    }
+}
+
+// Returns the name and the value of each line of `data` that starts with
+// them, e.g. "nr_free_pages 1234".
+fn key_values(data: &str) -> impl Iterator<Item = (&str, u64)> {
+    data.lines().filter_map(|line| {
+        let mut words = line.split_ascii_whitespace();
+        Some((words.next()?, words.next()?.parse().ok()?))
+    })
+}
+
+// Writes the counters of /proc/meminfo in `keys`. Values are in kB.
+fn write_meminfo(stats: &mut SysStats, data: &str, keys: &HashMap<&str, MeminfoCounters>) {
+    for (name, value) in key_values(data) {
+        let Some(key) = keys.get(name.trim_end_matches(':')) else {
+            continue;
+        };
+        stats.set_meminfo(|meminfo: &mut SysStatsMeminfoValue| {
+            meminfo.set_key(*key).set_value(value);
+        });
+    }
+}
+
+// Writes the counters of /proc/vmstat in `keys`.
+fn write_vmstat(stats: &mut SysStats, data: &str, keys: &HashMap<&str, VmstatCounters>) {
+    for (name, value) in key_values(data) {
+        let Some(key) = keys.get(name) else {
+            continue;
+        };
+        stats.set_vmstat(|vmstat: &mut SysStatsVmstatValue| {
+            vmstat.set_key(*key).set_value(value);
+        });
+    }
+}
+
+// Writes the counters of /proc/stat enabled in `fields`.
+fn write_stat(stats: &mut SysStats, data: &str, fields: u32) {
+    use SysStatsConfigStatCounters::*;
+    let enabled = |counter: SysStatsConfigStatCounters| fields & (1 << counter as u32) != 0;
+    for line in data.lines() {
+        let mut words = line.split_ascii_whitespace();
+        let Some(name) = words.next() else {
+            continue;
+        };
+        let mut values = words.map(|word| word.parse::<u64>().unwrap_or(0));
+        if let Some(cpu_id) = name.strip_prefix("cpu") {
+            // The "cpu" line sums the times of all CPUs.
+            let Ok(cpu_id) = cpu_id.parse::<u32>() else {
+                continue;
+            };
+            if !enabled(StatCpuTimes) {
+                continue;
+            }
+            let mut times = [0; 8];
+            for (time, value) in times.iter_mut().zip(values) {
+                *time = value * NS_PER_USER_HZ;
+            }
+            stats.set_cpu_stat(|cpu: &mut SysStatsCpuTimes| {
+                cpu.set_cpu_id(cpu_id)
+                    .set_user_ns(times[0])
+                    .set_user_nice_ns(times[1])
+                    .set_system_mode_ns(times[2])
+                    .set_idle_ns(times[3])
+                    .set_io_wait_ns(times[4])
+                    .set_irq_ns(times[5])
+                    .set_softirq_ns(times[6])
+                    .set_steal_ns(times[7]);
+            });
+        } else if name == "intr" && enabled(StatIrqCounts) {
+            stats.set_num_irq_total(values.next().unwrap_or(0));
+            // Most interrupts never fired, only write the others.
+            for (irq, count) in values.enumerate().filter(|(_, count)| *count > 0) {
+                stats.set_num_irq(|interrupt: &mut SysStatsInterruptCount| {
+                    interrupt.set_irq(irq as i32).set_count(count);
+                });
+            }
+        } else if name == "softirq" && enabled(StatSoftirqCounts) {
+            stats.set_num_softirq_total(values.next().unwrap_or(0));
+            for (irq, count) in values.enumerate() {
+                stats.set_num_softirq(|interrupt: &mut SysStatsInterruptCount| {
+                    interrupt.set_irq(irq as i32).set_count(count);
+                });
+            }
+        } else if name == "processes"
+            && enabled(StatForkCount)
+            && let Some(forks) = values.next()
+        {
+            stats.set_num_forks(forks);
+        }
+    }
+}
+
+struct Shared {
+    proc_dir: PathBuf,
+    instances: Mutex<HashMap<u32, InstanceState>>,
+}
+
+impl Shared {
+    // Returns the contents of the file `name` of the proc filesystem, if it
+    // is due and can be read.
+    fn read<K>(&self, file: &ProcFile<K>, polls: u64, name: &str) -> Option<String> {
+        if !file.is_due(polls) {
+            return None;
+        }
+        fs::read_to_string(self.proc_dir.join(name)).ok()
+    }
+
+    fn write(&self, ctx: &mut TraceContext) {
+        let inst_id = ctx.instance_index();
+        let mut instances = self.instances.lock().unwrap();
+        let Some(state) = instances.get_mut(&inst_id) else {
+            return;
+        };
+        let timestamp = DataSourceTimestamp::now();
+        let meminfo = self.read(&state.meminfo, state.polls, "meminfo");
+        let vmstat = self.read(&state.vmstat, state.polls, "vmstat");
+        let stat = self.read(&state.stat, state.polls, "stat");
+        state.polls += 1;
+        if meminfo.is_none() && vmstat.is_none() && stat.is_none() {
+            return;
+        }
+        let collection_end = DataSourceTimestamp::now().timestamp();
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet
+                .set_timestamp(timestamp.timestamp())
+                .set_timestamp_clock_id(timestamp.clock_id());
+            packet.set_sys_stats(|stats: &mut SysStats| {
+                if let Some(data) = &meminfo {
+                    write_meminfo(stats, data, &state.meminfo.keys);
+                }
+                if let Some(data) = &vmstat {
+                    write_vmstat(stats, data, &state.vmstat.keys);
+                }
+                if let Some(data) = &stat {
+                    write_stat(stats, data, state.stat_fields);
+                }
+                stats.set_collection_end_timestamp(collection_end);
+            });
+        });
+    }
+}
+
+/// System stats data source builder.
+#[must_use = "This is a builder; remember to call `.register()` (or keep chaining)."]
+pub struct SysStatsDataSourceBuilder {
+    name: String,
+    proc_dir: PathBuf,
+}
+
+impl Default for SysStatsDataSourceBuilder {
+    fn default() -> Self {
+        Self {
+            name: SYS_STATS_DATA_SOURCE_NAME.to_string(),
+            proc_dir: PathBuf::from("/proc"),
+        }
+    }
+}
+
+impl SysStatsDataSourceBuilder {
+    /// Create new system stats data source builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the name of the data source. Defaults to
+    /// [`SYS_STATS_DATA_SOURCE_NAME`], which clashes with `traced_probes` if
+    /// both run on the same system.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    /// Set the directory the proc filesystem is mounted on. Defaults to
+    /// `/proc`.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn proc_dir(mut self, proc_dir: impl Into<PathBuf>) -> Self {
+        self.proc_dir = proc_dir.into();
+        self
+    }
+
+    /// Registers the data source and starts polling it.
+    ///
+    /// Panics if the polling thread can't be created.
+    pub fn register(self) -> Result<SysStatsDataSource, DataSourceError> {
+        let shared = Arc::new(Shared {
+            proc_dir: self.proc_dir,
+            instances: Mutex::new(HashMap::new()),
+        });
+        let setup_shared = Arc::clone(&shared);
+        let stop_shared = Arc::clone(&shared);
+        let args = DataSourceArgsBuilder::new()
+            .on_setup(move |inst_id, config, _| {
+                let config = SysStatsInstanceConfig::decode(config).unwrap_or_default();
+                setup_shared
+                    .instances
+                    .lock()
+                    .unwrap()
+                    .insert(inst_id, InstanceState::new(&config));
+            })
+            .on_stop(move |inst_id, _| {
+                stop_shared.instances.lock().unwrap().remove(&inst_id);
+            });
+        let polling = PollingDataSourceBuilder::new()
+            .data_source_args(args)
+            .period_from_config(|config| {
+                SysStatsInstanceConfig::decode(config)
+                    .ok()
+                    .and_then(|config| config.poll_period())
+            })
+            .register(&self.name, move |ctx: &mut TraceContext| {
+                shared.write(ctx);
+            })?;
+        Ok(SysStatsDataSource { polling })
+    }
+}
+
+/// A data source that writes the counters of /proc/meminfo, /proc/vmstat and
+/// /proc/stat, for Linux systems where `traced_probes` isn't available.
+///
+/// The data source decodes the `SysStatsConfig` of each instance and writes
+/// `SysStats` packets with the same contents as the ones of `traced_probes`:
+/// each file is read at its own `*_period_ms`, and only the counters selected
+/// by the config are written. Instances that don't set any period of these
+/// files don't write anything.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk_protos_sys_stats::sys_stats_data_source::SysStatsDataSourceBuilder;
+///
+/// let _data_source = SysStatsDataSourceBuilder::new().register().unwrap();
+/// ```
+pub struct SysStatsDataSource {
+    polling: PollingDataSource,
+}
+
+impl SysStatsDataSource {
+    /// Returns the underlying polling data source.
+    pub fn polling_data_source(&self) -> &PollingDataSource {
+        &self.polling
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::{
+        config::{data_source_config::prelude::*, sys_stats::sys_stats_config::SysStatsConfig},
+        trace::trace_packet::TracePacketExtFieldNumber,
+    };
+    use perfetto_sdk::{
+        heap_buffer::HeapBuffer,
+        pb_msg::{PbMsg, PbMsgWriter},
+        protos::{
+            config::data_source_config::DataSourceConfig,
+            trace::trace_packet::TracePacketFieldNumber,
+        },
+        test_util::{acquire_test_environment, messages, record_packets, varint, varints},
+    };
+    use std::sync::OnceLock;
+
+    const DATA_SOURCE_NAME: &str = "com.example.sys_stats";
+
+    const MEMINFO: &str = "\
+MemTotal:       16318412 kB
+MemFree:         1234567 kB
+MemAvailable:    8000000 kB
+Active(anon):     100000 kB
+Unknown:              42 kB
+";
+
+    const VMSTAT: &str = "\
+nr_free_pages 308641
+nr_dirty 12
+unknown_counter 7
+";
+
+    const STAT: &str = "\
+cpu  10 20 30 40 50 60 70 80 0 0
+cpu0 1 2 3 4 5 6 7 8 0 0
+cpu1 9 10 11 12 13 14 15 16 0 0
+intr 100 0 40 0 60
+ctxt 12345
+btime 1700000000
+processes 777
+procs_running 2
+softirq 50 10 0 40
+";
+
+    // Registers the data source once, reading a fake proc filesystem.
+    fn data_source() -> &'static SysStatsDataSource {
+        static DATA_SOURCE: OnceLock<SysStatsDataSource> = OnceLock::new();
+        DATA_SOURCE.get_or_init(|| {
+            let proc_dir = std::env::temp_dir()
+                .join(format!("perfetto_sys_stats_test_{}", std::process::id()));
+            fs::create_dir_all(&proc_dir).unwrap();
+            for (name, data) in [("meminfo", MEMINFO), ("vmstat", VMSTAT), ("stat", STAT)] {
+                fs::write(proc_dir.join(name), data).unwrap();
+            }
+            SysStatsDataSourceBuilder::new()
+                .name(DATA_SOURCE_NAME)
+                .proc_dir(proc_dir)
+                .register()
+                .unwrap()
+        })
+    }
+
+    fn encode_config(config: impl Fn(&mut SysStatsConfig)) -> Vec<u8> {
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(writer.stream_writer());
+        let mut msg = PbMsg::new(&writer).unwrap();
+        {
+            let mut ds_cfg = DataSourceConfig { msg: &mut msg };
+            ds_cfg.set_sys_stats_config(|cfg: &mut SysStatsConfig| config(cfg));
+        }
+        msg.finalize();
+        let mut data = vec![0u8; writer.stream_writer().get_written_size()];
+        hb.copy_into(&mut data);
+        data
+    }
+
+    // Returns the `SysStats` of the packets recorded while polling the data
+    // source with `config` for a while.
+    fn record_sys_stats(config: impl Fn(&mut SysStatsConfig)) -> Vec<Vec<u8>> {
+        let _data_source = data_source();
+        let packets = record_packets(
+            DATA_SOURCE_NAME,
+            |ds_cfg: &mut DataSourceConfig| {
+                ds_cfg.set_sys_stats_config(|cfg: &mut SysStatsConfig| config(cfg));
+            },
+            || std::thread::sleep(Duration::from_millis(100)),
+        );
+        packets
+            .iter()
+            .filter_map(|packet| {
+                let timestamp = varint(packet, TracePacketFieldNumber::Timestamp as u32)?;
+                let stats = messages(packet, TracePacketExtFieldNumber::SysStats as u32);
+                let stats = stats.first()?;
+                assert!(
+                    varint(stats, SysStatsFieldNumber::CollectionEndTimestamp as u32).unwrap()
+                        >= timestamp
+                );
+                Some(stats.to_vec())
+            })
+            .collect()
+    }
+
+    // Returns the keys and values of the repeated fields `id` of `stats`.
+    fn key_values(stats: &[u8], id: SysStatsFieldNumber) -> Vec<(u64, u64)> {
+        messages(stats, id as u32)
+            .iter()
+            .map(|value| (varint(value, 1).unwrap(), varint(value, 2).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn decode_config() {
+        let config = encode_config(|cfg: &mut SysStatsConfig| {
+            cfg.set_meminfo_period_ms(1)
+                .set_meminfo_counters(MeminfoCounters::MeminfoMemTotal)
+                .set_vmstat_period_ms(500)
+                .set_stat_counters(SysStatsConfigStatCounters::StatForkCount);
+        });
+        assert_eq!(
+            SysStatsInstanceConfig::decode(&config).unwrap(),
+            SysStatsInstanceConfig {
+                meminfo_period: Some(MIN_PERIOD),
+                meminfo_counters: vec![MeminfoCounters::MeminfoMemTotal],
+                vmstat_period: Some(Duration::from_millis(500)),
+                stat_counters: vec![SysStatsConfigStatCounters::StatForkCount],
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            SysStatsInstanceConfig::decode(&encode_config(|_| {})).unwrap(),
+            SysStatsInstanceConfig::default()
+        );
+        assert_eq!(SysStatsInstanceConfig::default().poll_period(), None);
+    }
+
+    #[test]
+    fn schedule() {
+        let config = SysStatsInstanceConfig {
+            meminfo_period: Some(Duration::from_millis(100)),
+            vmstat_period: Some(Duration::from_millis(350)),
+            ..Default::default()
+        };
+        assert_eq!(config.poll_period(), Some(Duration::from_millis(100)));
+        let state = InstanceState::new(&config);
+        let due = |is_due: &dyn Fn(u64) -> bool| -> Vec<u64> {
+            (0..7).filter(|polls| is_due(*polls)).collect()
+        };
+        assert_eq!(
+            due(&|polls| state.meminfo.is_due(polls)),
+            [0, 1, 2, 3, 4, 5, 6]
+        );
+        assert_eq!(due(&|polls| state.vmstat.is_due(polls)), [0, 3, 6]);
+        assert!(due(&|polls| state.stat.is_due(polls)).is_empty());
+        assert_eq!(state.meminfo.keys.len(), MEMINFO_KEYS.len());
+        assert_eq!(state.stat_fields, !0);
+    }
+
+    #[test]
+    fn counters() {
+        use SysStatsCpuTimesFieldNumber as CpuTimes;
+        let _lock = acquire_test_environment();
+        let stats = record_sys_stats(|cfg: &mut SysStatsConfig| {
+            cfg.set_meminfo_period_ms(10)
+                .set_meminfo_counters(MeminfoCounters::MeminfoMemTotal)
+                .set_meminfo_counters(MeminfoCounters::MeminfoMemFree)
+                .set_vmstat_period_ms(10)
+                .set_stat_period_ms(10)
+                .set_stat_counters(SysStatsConfigStatCounters::StatCpuTimes)
+                .set_stat_counters(SysStatsConfigStatCounters::StatForkCount);
+        });
+        assert!(!stats.is_empty());
+        for stats in &stats {
+            assert_eq!(
+                key_values(stats, SysStatsFieldNumber::Meminfo),
+                [
+                    (MeminfoCounters::MeminfoMemTotal as u64, 16318412),
+                    (MeminfoCounters::MeminfoMemFree as u64, 1234567),
+                ]
+            );
+            assert_eq!(
+                key_values(stats, SysStatsFieldNumber::Vmstat),
+                [
+                    (VmstatCounters::VmstatNrFreePages as u64, 308641),
+                    (VmstatCounters::VmstatNrDirty as u64, 12),
+                ]
+            );
+            assert_eq!(varints(stats, SysStatsFieldNumber::NumForks as u32), [777]);
+            // The total of all CPUs isn't written.
+            let cpus = messages(stats, SysStatsFieldNumber::CpuStat as u32);
+            assert_eq!(cpus.len(), 2);
+            for (cpu_id, cpu) in cpus.iter().enumerate() {
+                let first = 8 * cpu_id as u64 + 1;
+                let times: Vec<_> = [
+                    CpuTimes::UserNs,
+                    CpuTimes::UserNiceNs,
+                    CpuTimes::SystemModeNs,
+                    CpuTimes::IdleNs,
+                    CpuTimes::IoWaitNs,
+                    CpuTimes::IrqNs,
+                    CpuTimes::SoftirqNs,
+                    CpuTimes::StealNs,
+                ]
+                .into_iter()
+                .map(|field| varint(cpu, field as u32).unwrap())
+                .collect();
+                let expected: Vec<_> = (first..first + 8)
+                    .map(|ticks| ticks * NS_PER_USER_HZ)
+                    .collect();
+                assert_eq!(varint(cpu, CpuTimes::CpuId as u32), Some(cpu_id as u64));
+                assert_eq!(times, expected);
+            }
+            assert!(varint(stats, SysStatsFieldNumber::NumIrqTotal as u32).is_none());
+            assert!(varint(stats, SysStatsFieldNumber::NumSoftirqTotal as u32).is_none());
+        }
+    }
+
+    #[test]
+    fn interrupts() {
+        let _lock = acquire_test_environment();
+        let stats = record_sys_stats(|cfg: &mut SysStatsConfig| {
+            cfg.set_stat_period_ms(10)
+                .set_stat_counters(SysStatsConfigStatCounters::StatIrqCounts)
+                .set_stat_counters(SysStatsConfigStatCounters::StatSoftirqCounts);
+        });
+        assert!(!stats.is_empty());
+        for stats in &stats {
+            assert!(messages(stats, SysStatsFieldNumber::Meminfo as u32).is_empty());
+            assert!(messages(stats, SysStatsFieldNumber::Vmstat as u32).is_empty());
+            assert!(messages(stats, SysStatsFieldNumber::CpuStat as u32).is_empty());
+            assert!(varint(stats, SysStatsFieldNumber::NumForks as u32).is_none());
+            assert_eq!(
+                varint(stats, SysStatsFieldNumber::NumIrqTotal as u32),
+                Some(100)
+            );
+            // Interrupts that never fired are skipped.
+            assert_eq!(
+                key_values(stats, SysStatsFieldNumber::NumIrq),
+                [(1, 40), (3, 60)]
+            );
+            assert_eq!(
+                varint(stats, SysStatsFieldNumber::NumSoftirqTotal as u32),
+                Some(50)
+            );
+            assert_eq!(
+                key_values(stats, SysStatsFieldNumber::NumSoftirq),
+                [(0, 10), (1, 0), (2, 40)]
+            );
+        }
+    }
+
+    #[test]
+    fn no_periods() {
+        let _lock = acquire_test_environment();
+        let stats = record_sys_stats(|cfg: &mut SysStatsConfig| {
+            cfg.set_meminfo_counters(MeminfoCounters::MeminfoMemTotal);
+        });
+        assert!(stats.is_empty());
+    }
+}
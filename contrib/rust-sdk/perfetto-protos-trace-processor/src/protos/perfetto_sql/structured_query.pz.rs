@@ -67,7 +67,7 @@ pb_enum!(ExperimentalTimeRangeMode {
 
 pb_msg!(PerfettoSqlStructuredQuery {
     id: String, primitive, 1,
-    referenced_modules: String, primitive, 11,
+    referenced_modules: [primitive, String], repeated, 11,
     table: PerfettoSqlStructuredQueryTable, msg, 2,
     sql: PerfettoSqlStructuredQuerySql, msg, 3,
     simple_slices: PerfettoSqlStructuredQuerySimpleSlices, msg, 4,
@@ -82,24 +82,40 @@ pb_msg!(PerfettoSqlStructuredQuery {
     experimental_filter_to_intervals: PerfettoSqlStructuredQueryExperimentalFilterToIntervals, msg, 106,
     experimental_counter_intervals: PerfettoSqlStructuredQueryExperimentalCounterIntervals, msg, 107,
     experimental_filter_in: PerfettoSqlStructuredQueryExperimentalFilterIn, msg, 108,
-    filters: PerfettoSqlStructuredQueryFilter, msg, 8,
+    filters: [msg, PerfettoSqlStructuredQueryFilter], repeated, 8,
     group_by: PerfettoSqlStructuredQueryGroupBy, msg, 9,
-    select_columns: PerfettoSqlStructuredQuerySelectColumn, msg, 10,
+    select_columns: [msg, PerfettoSqlStructuredQuerySelectColumn], repeated, 10,
     order_by: PerfettoSqlStructuredQueryOrderBy, msg, 14,
     limit: i64, primitive, 12,
     offset: i64, primitive, 13,
     experimental_filter_group: PerfettoSqlStructuredQueryExperimentalFilterGroup, msg, 103,
+}
+oneof source {
+    table,
+    sql,
+    simple_slices,
+    inner_query,
+    inner_query_id,
+    interval_intersect,
+    experimental_join,
+    experimental_union,
+    experimental_add_columns,
+    experimental_create_slices,
+    experimental_time_range,
+    experimental_filter_to_intervals,
+    experimental_counter_intervals,
+    experimental_filter_in,
 });
 
 pb_msg!(PerfettoSqlStructuredQueryExperimentalFilterGroup {
     op: ExperimentalFilterGroupOperator, enum, 1,
-    filters: PerfettoSqlStructuredQueryFilter, msg, 2,
-    groups: PerfettoSqlStructuredQueryExperimentalFilterGroup, msg, 3,
-    sql_expressions: String, primitive, 4,
+    filters: [msg, PerfettoSqlStructuredQueryFilter], repeated, 2,
+    groups: [msg, PerfettoSqlStructuredQueryExperimentalFilterGroup], repeated, 3,
+    sql_expressions: [primitive, String], repeated, 4,
 });
 
 pb_msg!(PerfettoSqlStructuredQueryOrderBy {
-    ordering_specs: PerfettoSqlStructuredQueryOrderByOrderingSpec, msg, 1,
+    ordering_specs: [msg, PerfettoSqlStructuredQueryOrderByOrderingSpec], repeated, 1,
 });
 
 pb_msg!(PerfettoSqlStructuredQueryOrderByOrderingSpec {
@@ -114,8 +130,8 @@ pb_msg!(PerfettoSqlStructuredQuerySelectColumn {
 });
 
 pb_msg!(PerfettoSqlStructuredQueryGroupBy {
-    column_names: String, primitive, 1,
-    aggregates: PerfettoSqlStructuredQueryGroupByAggregate, msg, 2,
+    column_names: [primitive, String], repeated, 1,
+    aggregates: [msg, PerfettoSqlStructuredQueryGroupByAggregate], repeated, 2,
 });
 
 pb_msg!(PerfettoSqlStructuredQueryGroupByAggregate {
@@ -129,9 +145,9 @@ pb_msg!(PerfettoSqlStructuredQueryGroupByAggregate {
 pb_msg!(PerfettoSqlStructuredQueryFilter {
     column_name: String, primitive, 1,
     op: FilterOperator, enum, 2,
-    string_rhs: String, primitive, 3,
-    double_rhs: f64, primitive, 4,
-    int64_rhs: i64, primitive, 5,
+    string_rhs: [primitive, String], repeated, 3,
+    double_rhs: [primitive, f64], repeated, 4,
+    int64_rhs: [primitive, i64], repeated, 5,
 });
 
 pb_msg!(PerfettoSqlStructuredQueryExperimentalFilterIn {
@@ -155,13 +171,17 @@ pb_msg!(PerfettoSqlStructuredQueryExperimentalCreateSlices {
 pb_msg!(PerfettoSqlStructuredQueryExperimentalAddColumns {
     core_query: PerfettoSqlStructuredQuery, msg, 1,
     input_query: PerfettoSqlStructuredQuery, msg, 2,
-    input_columns: PerfettoSqlStructuredQuerySelectColumn, msg, 3,
+    input_columns: [msg, PerfettoSqlStructuredQuerySelectColumn], repeated, 3,
     equality_columns: PerfettoSqlStructuredQueryExperimentalJoinEqualityColumns, msg, 4,
     freeform_condition: PerfettoSqlStructuredQueryExperimentalJoinFreeformCondition, msg, 5,
+}
+oneof condition {
+    equality_columns,
+    freeform_condition,
 });
 
 pb_msg!(PerfettoSqlStructuredQueryExperimentalUnion {
-    queries: PerfettoSqlStructuredQuery, msg, 1,
+    queries: [msg, PerfettoSqlStructuredQuery], repeated, 1,
     use_union_all: bool, primitive, 2,
 });
 
@@ -171,6 +191,10 @@ pb_msg!(PerfettoSqlStructuredQueryExperimentalJoin {
     right_query: PerfettoSqlStructuredQuery, msg, 3,
     equality_columns: PerfettoSqlStructuredQueryExperimentalJoinEqualityColumns, msg, 4,
     freeform_condition: PerfettoSqlStructuredQueryExperimentalJoinFreeformCondition, msg, 5,
+}
+oneof condition {
+    equality_columns,
+    freeform_condition,
 });
 
 pb_msg!(PerfettoSqlStructuredQueryExperimentalJoinFreeformCondition {
@@ -193,21 +217,21 @@ pb_msg!(PerfettoSqlStructuredQueryExperimentalTimeRange {
 pb_msg!(PerfettoSqlStructuredQueryExperimentalFilterToIntervals {
     base: PerfettoSqlStructuredQuery, msg, 1,
     intervals: PerfettoSqlStructuredQuery, msg, 2,
-    partition_columns: String, primitive, 3,
+    partition_columns: [primitive, String], repeated, 3,
     clip_to_intervals: bool, primitive, 4,
-    select_columns: String, primitive, 5,
+    select_columns: [primitive, String], repeated, 5,
 });
 
 pb_msg!(PerfettoSqlStructuredQueryIntervalIntersect {
     base: PerfettoSqlStructuredQuery, msg, 1,
-    interval_intersect: PerfettoSqlStructuredQuery, msg, 2,
-    partition_columns: String, primitive, 3,
+    interval_intersect: [msg, PerfettoSqlStructuredQuery], repeated, 2,
+    partition_columns: [primitive, String], repeated, 3,
 });
 
 pb_msg!(PerfettoSqlStructuredQuerySql {
     sql: String, primitive, 1,
-    column_names: String, primitive, 2,
-    dependencies: PerfettoSqlStructuredQuerySqlDependency, msg, 4,
+    column_names: [primitive, String], repeated, 2,
+    dependencies: [msg, PerfettoSqlStructuredQuerySqlDependency], repeated, 4,
     preamble: String, primitive, 3,
 });
 
@@ -225,6 +249,6 @@ pb_msg!(PerfettoSqlStructuredQuerySimpleSlices {
 
 pb_msg!(PerfettoSqlStructuredQueryTable {
     table_name: String, primitive, 1,
-    column_names: String, primitive, 3,
+    column_names: [primitive, String], repeated, 3,
     module_name: String, primitive, 2,
 });
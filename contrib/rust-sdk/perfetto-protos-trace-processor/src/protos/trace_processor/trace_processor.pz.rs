@@ -94,7 +94,7 @@ pb_msg!(QuerySummarizerResult {
     exists: bool, primitive, 1,
     table_name: String, primitive, 2,
     row_count: i64, primitive, 3,
-    columns: String, primitive, 4,
+    columns: [primitive, String], repeated, 4,
     duration_ms: f64, primitive, 5,
     error: String, primitive, 6,
     sql: String, primitive, 7,
@@ -108,7 +108,7 @@ pb_msg!(QuerySummarizerArgs {
 });
 
 pb_msg!(UpdateSummarizerSpecResult {
-    queries: SummarizerQuerySyncInfo, msg, 1,
+    queries: [msg, SummarizerQuerySyncInfo], repeated, 1,
     error: String, primitive, 2,
 });
 
@@ -134,20 +134,24 @@ pb_msg!(CreateSummarizerArgs {
 });
 
 pb_msg!(TraceSummaryResult {
-    proto_summary: String, primitive, 1,
+    proto_summary: Bytes, primitive, 1,
     textproto_summary: String, primitive, 2,
     error: String, primitive, 3,
+}
+oneof summary {
+    proto_summary,
+    textproto_summary,
 });
 
 pb_msg!(TraceSummaryArgs {
-    proto_specs: TraceSummarySpec, msg, 1,
-    textproto_specs: String, primitive, 2,
+    proto_specs: [msg, TraceSummarySpec], repeated, 1,
+    textproto_specs: [primitive, String], repeated, 2,
     computation_spec: TraceSummaryArgsComputationSpec, msg, 3,
     output_format: TraceSummaryArgsFormat, enum, 4,
 });
 
 pb_msg!(TraceSummaryArgsComputationSpec {
-    metric_ids: String, primitive, 1,
+    metric_ids: [primitive, String], repeated, 1,
     run_all_metrics: bool, primitive, 3,
     metadata_query_id: String, primitive, 2,
 });
@@ -162,7 +166,7 @@ pb_msg!(RegisterSqlPackageResult {
 
 pb_msg!(RegisterSqlPackageArgs {
     package_name: String, primitive, 1,
-    modules: RegisterSqlPackageArgsModule, msg, 2,
+    modules: [msg, RegisterSqlPackageArgsModule], repeated, 2,
     allow_override: bool, primitive, 3,
 });
 
@@ -178,15 +182,15 @@ pb_msg!(ResetTraceProcessorArgs {
     ftrace_drop_until_all_cpus_valid: bool, primitive, 4,
     parsing_mode: ResetTraceProcessorArgsParsingMode, enum, 5,
     sorting_mode: ResetTraceProcessorArgsSortingMode, enum, 6,
-    extra_parsing_descriptors: String, primitive, 7,
+    extra_parsing_descriptors: [primitive, Bytes], repeated, 7,
 });
 
 pb_msg!(DescriptorSet {
-    descriptors: DescriptorProto, msg, 1,
+    descriptors: [msg, DescriptorProto], repeated, 1,
 });
 
 pb_msg!(DisableAndReadMetatraceResult {
-    metatrace: String, primitive, 1,
+    metatrace: Bytes, primitive, 1,
     error: String, primitive, 2,
 });
 
@@ -199,14 +203,19 @@ pb_msg!(EnableMetatraceArgs {
 });
 
 pb_msg!(ComputeMetricResult {
-    metrics: String, primitive, 1,
+    metrics: Bytes, primitive, 1,
     metrics_as_prototext: String, primitive, 3,
     metrics_as_json: String, primitive, 4,
     error: String, primitive, 2,
+}
+oneof result {
+    metrics,
+    metrics_as_prototext,
+    metrics_as_json,
 });
 
 pb_msg!(ComputeMetricArgs {
-    metric_names: String, primitive, 1,
+    metric_names: [primitive, String], repeated, 1,
     format: ComputeMetricArgsResultFormat, enum, 2,
 });
 
@@ -220,9 +229,9 @@ pb_msg!(StatusResult {
 pb_msg!(StatusArgs {});
 
 pb_msg!(QueryResult {
-    column_names: String, primitive, 1,
+    column_names: [primitive, String], repeated, 1,
     error: String, primitive, 2,
-    batch: QueryResultCellsBatch, msg, 3,
+    batch: [msg, QueryResultCellsBatch], repeated, 3,
     statement_count: u32, primitive, 4,
     statement_with_output_count: u32, primitive, 5,
     last_statement_sql: String, primitive, 6,
@@ -230,10 +239,10 @@ pb_msg!(QueryResult {
 });
 
 pb_msg!(QueryResultCellsBatch {
-    cells: CellsBatchCellType, enum, 1,
-    varint_cells: i64, primitive, 2,
-    float64_cells: f64, primitive, 3,
-    blob_cells: String, primitive, 4,
+    cells: [enum, CellsBatchCellType], packed, 1,
+    varint_cells: [primitive, i64], packed, 2,
+    float64_cells: [primitive, f64], packed, 3,
+    blob_cells: [primitive, Bytes], repeated, 4,
     string_cells: String, primitive, 5,
     is_last_batch: bool, primitive, 6,
 });
@@ -254,7 +263,7 @@ pb_msg!(TraceProcessorRpc {
     request: TraceProcessorRpcTraceProcessorMethod, enum, 2,
     response: TraceProcessorRpcTraceProcessorMethod, enum, 3,
     invalid_request: TraceProcessorRpcTraceProcessorMethod, enum, 4,
-    append_trace_data: String, primitive, 101,
+    append_trace_data: Bytes, primitive, 101,
     query_args: QueryArgs, msg, 103,
     compute_metric_args: ComputeMetricArgs, msg, 105,
     enable_metatrace_args: EnableMetatraceArgs, msg, 106,
@@ -278,8 +287,39 @@ pb_msg!(TraceProcessorRpc {
     update_summarizer_spec_result: UpdateSummarizerSpecResult, msg, 216,
     query_summarizer_result: QuerySummarizerResult, msg, 217,
     destroy_summarizer_result: DestroySummarizerResult, msg, 218,
+}
+oneof type {
+    request,
+    response,
+    invalid_request,
+}
+oneof args {
+    append_trace_data,
+    query_args,
+    compute_metric_args,
+    enable_metatrace_args,
+    reset_trace_processor_args,
+    register_sql_package_args,
+    trace_summary_args,
+    create_summarizer_args,
+    update_summarizer_spec_args,
+    query_summarizer_args,
+    destroy_summarizer_args,
+    append_result,
+    query_result,
+    metric_result,
+    metric_descriptors,
+    metatrace,
+    status,
+    register_sql_package_result,
+    finalize_data_result,
+    trace_summary_result,
+    create_summarizer_result,
+    update_summarizer_spec_result,
+    query_summarizer_result,
+    destroy_summarizer_result,
 });
 
 pb_msg!(TraceProcessorRpcStream {
-    msg: TraceProcessorRpc, msg, 1,
+    msg: [msg, TraceProcessorRpc], repeated, 1,
 });
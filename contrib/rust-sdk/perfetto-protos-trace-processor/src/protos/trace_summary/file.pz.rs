@@ -21,8 +21,8 @@ use crate::protos::perfetto_sql::structured_query::*;
 use crate::protos::trace_summary::v2_metric::*;
 
 pb_msg!(TraceSummary {
-    metric_bundles: TraceMetricV2Bundle, msg, 3,
-    metadata: TraceSummaryMetadata, msg, 2,
+    metric_bundles: [msg, TraceMetricV2Bundle], repeated, 3,
+    metadata: [msg, TraceSummaryMetadata], repeated, 2,
 });
 
 pb_msg!(TraceSummaryMetadata {
@@ -31,7 +31,7 @@ pb_msg!(TraceSummaryMetadata {
 });
 
 pb_msg!(TraceSummarySpec {
-    metric_spec: TraceMetricV2Spec, msg, 1,
-    query: PerfettoSqlStructuredQuery, msg, 2,
-    metric_template_spec: TraceMetricV2TemplateSpec, msg, 3,
+    metric_spec: [msg, TraceMetricV2Spec], repeated, 1,
+    query: [msg, PerfettoSqlStructuredQuery], repeated, 2,
+    metric_template_spec: [msg, TraceMetricV2TemplateSpec], repeated, 3,
 });
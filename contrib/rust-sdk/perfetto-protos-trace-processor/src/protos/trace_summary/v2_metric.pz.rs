@@ -79,23 +79,23 @@ pb_enum!(TraceMetricV2SpecMetricPolarity {
 
 pb_msg!(TraceMetricV2Bundle {
     bundle_id: String, primitive, 1,
-    row: TraceMetricV2BundleRow, msg, 2,
-    specs: TraceMetricV2Spec, msg, 3,
-    interned_dimension_bundles: TraceMetricV2BundleInternedDimensionBundle, msg, 4,
+    row: [msg, TraceMetricV2BundleRow], repeated, 2,
+    specs: [msg, TraceMetricV2Spec], repeated, 3,
+    interned_dimension_bundles: [msg, TraceMetricV2BundleInternedDimensionBundle], repeated, 4,
 });
 
 pb_msg!(TraceMetricV2BundleInternedDimensionBundle {
-    interned_dimension_rows: TraceMetricV2BundleInternedDimensionBundleInternedDimensionRow, msg, 2,
+    interned_dimension_rows: [msg, TraceMetricV2BundleInternedDimensionBundleInternedDimensionRow], repeated, 2,
 });
 
 pb_msg!(TraceMetricV2BundleInternedDimensionBundleInternedDimensionRow {
     key_dimension_value: TraceMetricV2BundleRowDimension, msg, 1,
-    interned_dimension_values: TraceMetricV2BundleRowDimension, msg, 2,
+    interned_dimension_values: [msg, TraceMetricV2BundleRowDimension], repeated, 2,
 });
 
 pb_msg!(TraceMetricV2BundleRow {
-    values: TraceMetricV2BundleRowValue, msg, 1,
-    dimension: TraceMetricV2BundleRowDimension, msg, 2,
+    values: [msg, TraceMetricV2BundleRowValue], repeated, 1,
+    dimension: [msg, TraceMetricV2BundleRowDimension], repeated, 2,
 });
 
 pb_msg!(TraceMetricV2BundleRowDimension {
@@ -104,6 +104,13 @@ pb_msg!(TraceMetricV2BundleRowDimension {
     double_value: f64, primitive, 3,
     null_value: TraceMetricV2BundleRowDimensionNull, msg, 4,
     bool_value: bool, primitive, 5,
+}
+oneof value_oneof {
+    string_value,
+    int64_value,
+    double_value,
+    null_value,
+    bool_value,
 });
 
 pb_msg!(TraceMetricV2BundleRowDimensionNull {});
@@ -111,17 +118,21 @@ pb_msg!(TraceMetricV2BundleRowDimensionNull {});
 pb_msg!(TraceMetricV2BundleRowValue {
     null_value: TraceMetricV2BundleRowValueNull, msg, 1,
     double_value: f64, primitive, 2,
+}
+oneof value_oneof {
+    null_value,
+    double_value,
 });
 
 pb_msg!(TraceMetricV2BundleRowValueNull {});
 
 pb_msg!(TraceMetricV2TemplateSpec {
     id_prefix: String, primitive, 1,
-    dimensions_specs: TraceMetricV2SpecDimensionSpec, msg, 5,
-    dimensions: String, primitive, 2,
-    value_columns: String, primitive, 3,
-    value_column_specs: TraceMetricV2TemplateSpecValueColumnSpec, msg, 8,
-    interned_dimension_specs: TraceMetricV2SpecInternedDimensionSpec, msg, 9,
+    dimensions_specs: [msg, TraceMetricV2SpecDimensionSpec], repeated, 5,
+    dimensions: [primitive, String], repeated, 2,
+    value_columns: [primitive, String], repeated, 3,
+    value_column_specs: [msg, TraceMetricV2TemplateSpecValueColumnSpec], repeated, 8,
+    interned_dimension_specs: [msg, TraceMetricV2SpecInternedDimensionSpec], repeated, 9,
     query: PerfettoSqlStructuredQuery, msg, 4,
     dimension_uniqueness: TraceMetricV2SpecDimensionUniqueness, enum, 6,
     disable_auto_bundling: bool, primitive, 7,
@@ -135,12 +146,16 @@ pb_msg!(TraceMetricV2TemplateSpecValueColumnSpec {
     display_name: String, primitive, 5,
     display_help: String, primitive, 6,
     doc_link: String, primitive, 7,
+}
+oneof unit_oneof {
+    unit,
+    custom_unit,
 });
 
 pb_msg!(TraceMetricV2Spec {
     id: String, primitive, 1,
-    dimensions_specs: TraceMetricV2SpecDimensionSpec, msg, 5,
-    dimensions: String, primitive, 2,
+    dimensions_specs: [msg, TraceMetricV2SpecDimensionSpec], repeated, 5,
+    dimensions: [primitive, String], repeated, 2,
     value: String, primitive, 3,
     query: PerfettoSqlStructuredQuery, msg, 4,
     dimension_uniqueness: TraceMetricV2SpecDimensionUniqueness, enum, 6,
@@ -148,12 +163,16 @@ pb_msg!(TraceMetricV2Spec {
     custom_unit: String, primitive, 9,
     polarity: TraceMetricV2SpecMetricPolarity, enum, 10,
     bundle_id: String, primitive, 7,
-    interned_dimension_specs: TraceMetricV2SpecInternedDimensionSpec, msg, 11,
+    interned_dimension_specs: [msg, TraceMetricV2SpecInternedDimensionSpec], repeated, 11,
+}
+oneof unit_oneof {
+    unit,
+    custom_unit,
 });
 
 pb_msg!(TraceMetricV2SpecInternedDimensionSpec {
     key_column_spec: TraceMetricV2SpecInternedDimensionSpecColumnSpec, msg, 1,
-    data_column_specs: TraceMetricV2SpecInternedDimensionSpecColumnSpec, msg, 2,
+    data_column_specs: [msg, TraceMetricV2SpecInternedDimensionSpecColumnSpec], repeated, 2,
     query: PerfettoSqlStructuredQuery, msg, 3,
 });
 
@@ -68,6 +68,12 @@ unsafe extern "C" {
 pub struct PerfettoDsOnSetupArgs {
     _unused: [u8; 0],
 }
+unsafe extern "C" {
+    pub fn PerfettoDsOnSetupArgsSetError(
+        arg1: *mut PerfettoDsOnSetupArgs,
+        error: *const ::std::os::raw::c_char,
+    );
+}
 pub type PerfettoDsOnSetupCb = ::std::option::Option<
     unsafe extern "C" fn(
         arg1: *mut PerfettoDsImpl,
@@ -141,6 +147,9 @@ unsafe extern "C" {
         arg1: *mut PerfettoDsOnFlushArgs,
     ) -> *mut PerfettoDsAsyncFlusher;
 }
+unsafe extern "C" {
+    pub fn PerfettoDsOnFlushArgsIsClone(arg1: *mut PerfettoDsOnFlushArgs) -> bool;
+}
 unsafe extern "C" {
     pub fn PerfettoDsFlushDone(arg1: *mut PerfettoDsAsyncFlusher);
 }
@@ -300,6 +309,9 @@ unsafe extern "C" {
         writer: *mut PerfettoStreamWriter,
     );
 }
+unsafe extern "C" {
+    pub fn PerfettoDsTracerImplIsDropping(tracer: *mut PerfettoDsTracerImpl) -> bool;
+}
 pub type PerfettoDsTracerOnFlushCb =
     ::std::option::Option<unsafe extern "C" fn(user_arg: *mut ::std::os::raw::c_void)>;
 pub const PerfettoDsClockId_PERFETTO_DS_CLOCK_MONOTONIC: PerfettoDsClockId = 3;
@@ -399,12 +411,62 @@ unsafe extern "C" {
         size: u32,
     );
 }
+unsafe extern "C" {
+    pub fn PerfettoProducerBackendInitArgsSetShmemPageSizeHintKb(
+        arg1: *mut PerfettoProducerBackendInitArgs,
+        size: u32,
+    );
+}
+unsafe extern "C" {
+    pub fn PerfettoProducerBackendInitArgsSetShmemBatchCommitsDurationMs(
+        arg1: *mut PerfettoProducerBackendInitArgs,
+        duration_ms: u32,
+    );
+}
+unsafe extern "C" {
+    pub fn PerfettoProducerBackendInitArgsSetShmemDirectPatchingEnabled(
+        arg1: *mut PerfettoProducerBackendInitArgs,
+        enabled: bool,
+    );
+}
 unsafe extern "C" {
     pub fn PerfettoProducerBackendInitArgsSetMachineId(
         arg1: *mut PerfettoProducerBackendInitArgs,
         machine_id: u32,
     );
 }
+unsafe extern "C" {
+    pub fn PerfettoProducerBackendInitArgsSetProducerSocketName(
+        arg1: *mut PerfettoProducerBackendInitArgs,
+        name: *const ::std::os::raw::c_char,
+    );
+}
+unsafe extern "C" {
+    pub fn PerfettoProducerBackendInitArgsSetShmemEmulationEnabled(
+        arg1: *mut PerfettoProducerBackendInitArgs,
+        enabled: bool,
+    );
+}
+pub type PerfettoProducerCreateSocketCb = ::std::option::Option<
+    unsafe extern "C" fn(
+        socket_name: *const ::std::os::raw::c_char,
+        user_arg: *mut ::std::os::raw::c_void,
+        done_arg: *mut ::std::os::raw::c_void,
+    ),
+>;
+unsafe extern "C" {
+    pub fn PerfettoProducerBackendInitArgsSetCreateSocketCb(
+        arg1: *mut PerfettoProducerBackendInitArgs,
+        cb: PerfettoProducerCreateSocketCb,
+        user_arg: *mut ::std::os::raw::c_void,
+    );
+}
+unsafe extern "C" {
+    pub fn PerfettoProducerCreateSocketDone(
+        done_arg: *mut ::std::os::raw::c_void,
+        fd: ::std::os::raw::c_int,
+    );
+}
 unsafe extern "C" {
     pub fn PerfettoProducerBackendInitArgsDestroy(arg1: *mut PerfettoProducerBackendInitArgs);
 }
@@ -420,6 +482,41 @@ unsafe extern "C" {
         ttl_ms: u32,
     );
 }
+unsafe extern "C" {
+    pub fn PerfettoProducerGetStalledChunkRequests() -> u64;
+}
+unsafe extern "C" {
+    pub fn PerfettoProducerGetDroppedChunkRequests() -> u64;
+}
+unsafe extern "C" {
+    pub fn PerfettoProducerGetThreadStalledChunkRequests() -> u64;
+}
+unsafe extern "C" {
+    pub fn PerfettoProducerGetThreadDroppedChunkRequests() -> u64;
+}
+unsafe extern "C" {
+    pub fn PerfettoProducerSetThreadChunkRequestStatsEnabled(enabled: bool);
+}
+unsafe extern "C" {
+    pub fn PerfettoProducerSetThreadStallTimeout(timeout_ns: u64);
+}
+unsafe extern "C" {
+    pub fn PerfettoProducerClearThreadStallTimeout();
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct PerfettoService {
+    _unused: [u8; 0],
+}
+unsafe extern "C" {
+    pub fn PerfettoServiceStart(
+        producer_socket: *const ::std::os::raw::c_char,
+        consumer_socket: *const ::std::os::raw::c_char,
+    ) -> *mut PerfettoService;
+}
+unsafe extern "C" {
+    pub fn PerfettoServiceDestroy(service: *mut PerfettoService);
+}
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct PerfettoTracingSessionImpl {
@@ -438,6 +535,14 @@ unsafe extern "C" {
         cfg_len: usize,
     );
 }
+unsafe extern "C" {
+    pub fn PerfettoTracingSessionSetupWithFd(
+        arg1: *mut PerfettoTracingSessionImpl,
+        cfg_begin: *mut ::std::os::raw::c_void,
+        cfg_len: usize,
+        fd: ::std::os::raw::c_int,
+    );
+}
 pub type PerfettoTracingSessionStopCb = ::std::option::Option<
     unsafe extern "C" fn(
         arg1: *mut PerfettoTracingSessionImpl,
@@ -484,6 +589,39 @@ unsafe extern "C" {
         timeout_ms: u32,
     ) -> bool;
 }
+unsafe extern "C" {
+    pub fn PerfettoTracingSessionDetachBlocking(
+        arg1: *mut PerfettoTracingSessionImpl,
+        key: *const ::std::os::raw::c_char,
+    ) -> bool;
+}
+unsafe extern "C" {
+    pub fn PerfettoTracingSessionAttachBlocking(
+        arg1: *mut PerfettoTracingSessionImpl,
+        key: *const ::std::os::raw::c_char,
+    ) -> bool;
+}
+unsafe extern "C" {
+    pub fn PerfettoTracingSessionCloneBlocking(
+        arg1: *mut PerfettoTracingSessionImpl,
+        unique_session_name: *const ::std::os::raw::c_char,
+    ) -> bool;
+}
+pub type PerfettoTracingSessionQueryCb = ::std::option::Option<
+    unsafe extern "C" fn(
+        arg1: *mut PerfettoTracingSessionImpl,
+        data: *const ::std::os::raw::c_void,
+        size: usize,
+        user_arg: *mut ::std::os::raw::c_void,
+    ),
+>;
+unsafe extern "C" {
+    pub fn PerfettoTracingSessionGetTraceStatsBlocking(
+        arg1: *mut PerfettoTracingSessionImpl,
+        cb: PerfettoTracingSessionQueryCb,
+        user_arg: *mut ::std::os::raw::c_void,
+    ) -> bool;
+}
 pub type PerfettoTracingSessionReadCb = ::std::option::Option<
     unsafe extern "C" fn(
         arg1: *mut PerfettoTracingSessionImpl,
@@ -506,6 +644,14 @@ unsafe extern "C" {
 unsafe extern "C" {
     pub fn PerfettoTeInit();
 }
+pub const PerfettoTeCategoryFiltering_PERFETTO_TE_CATEGORY_FILTERING_C:
+    PerfettoTeCategoryFiltering = 0;
+pub const PerfettoTeCategoryFiltering_PERFETTO_TE_CATEGORY_FILTERING_CPP:
+    PerfettoTeCategoryFiltering = 1;
+pub type PerfettoTeCategoryFiltering = ::std::os::raw::c_uint;
+unsafe extern "C" {
+    pub fn PerfettoTeSetCategoryFiltering(arg1: PerfettoTeCategoryFiltering);
+}
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct PerfettoTeCategoryDescriptor {
@@ -715,6 +861,8 @@ pub const PerfettoTeHlExtraType_PERFETTO_TE_HL_EXTRA_TYPE_CORRELATION_ID: Perfet
     20;
 pub const PerfettoTeHlExtraType_PERFETTO_TE_HL_EXTRA_TYPE_CORRELATION_ID_STR:
     PerfettoTeHlExtraType = 21;
+pub const PerfettoTeHlExtraType_PERFETTO_TE_HL_EXTRA_TYPE_SOURCE_LOCATION: PerfettoTeHlExtraType =
+    22;
 pub type PerfettoTeHlExtraType = ::std::os::raw::c_uint;
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
@@ -844,6 +992,14 @@ pub union PerfettoTeHlExtraCorrelationIdUnion {
 }
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
+pub struct PerfettoTeHlExtraSourceLocation {
+    pub header: PerfettoTeHlExtra,
+    pub file_name: *const ::std::os::raw::c_char,
+    pub function_name: *const ::std::os::raw::c_char,
+    pub line_number: u32,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
 pub struct PerfettoTeHlExtraProtoFields {
     pub header: PerfettoTeHlExtra,
     pub fields: *const *mut PerfettoTeHlProtoField,
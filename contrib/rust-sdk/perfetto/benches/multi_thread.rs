@@ -0,0 +1,149 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Throughput of threads emitting trace data concurrently.
+//!
+//! Each benchmark runs `N` threads that emit events at the same time into an
+//! in-process tracing session, and reports the number of events emitted per
+//! second by all the threads. Run with `cargo bench --bench multi_thread`.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use perfetto_sdk::{
+    data_source::TraceContext,
+    producer::{Backends, Producer, ProducerInitArgsBuilder},
+    protos::trace::{test_event::TestEvent, trace_packet::TracePacket},
+    test_util::{DATA_SOURCE_NAME, data_source},
+    trace_config::TraceConfigBuilder,
+    tracing_session::TracingSession,
+    track_event::{EventContext, TrackEvent, TrackEventCounter, TrackEventTrack},
+    track_event_begin, track_event_categories, track_event_counter, track_event_end,
+};
+use std::{
+    hint::black_box,
+    sync::Barrier,
+    thread,
+    time::{Duration, Instant},
+};
+
+track_event_categories! {
+    pub mod bench_te_ns {
+        ( "bench", "Benchmark events", [] ),
+    }
+}
+
+use bench_te_ns as perfetto_te_ns;
+
+// Events emitted by each thread per iteration.
+const EVENTS_PER_ITERATION: u64 = 100;
+
+const THREAD_COUNTS: &[usize] = &[1, 2, 4, 8];
+
+// Sets up an in-process session with the track event and the benchmark data
+// sources, which runs until the process exits.
+fn start_session() -> TracingSession {
+    let producer_args = ProducerInitArgsBuilder::new().backends(Backends::IN_PROCESS);
+    Producer::init(producer_args.build());
+    TrackEvent::init();
+    perfetto_te_ns::register().expect("failed to register categories");
+    data_source();
+    let config = TraceConfigBuilder::ring_buffer(64 * 1024)
+        .track_event(&["bench"])
+        .data_source(DATA_SOURCE_NAME)
+        .build()
+        .expect("invalid trace config");
+    let mut session = TracingSession::in_process().expect("failed to create session");
+    session.setup(&config);
+    session.start_blocking();
+    session
+}
+
+// Runs `emit` on `threads` threads at once for `iters` iterations each, and
+// returns the time until all the threads are done.
+fn run_threads<F>(threads: usize, iters: u64, emit: F) -> Duration
+where
+    F: Fn(u64) + Sync,
+{
+    let barrier = Barrier::new(threads + 1);
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| {
+                barrier.wait();
+                for iter in 0..iters {
+                    emit(iter);
+                }
+            });
+        }
+        barrier.wait();
+        let start = Instant::now();
+        // Joins the threads at the end of the scope.
+        start
+    })
+    .elapsed()
+}
+
+fn bench_emit<F>(c: &mut Criterion, name: &str, emit: F)
+where
+    F: Fn(u64) + Sync,
+{
+    let mut group = c.benchmark_group(name);
+    for &threads in THREAD_COUNTS {
+        group.throughput(Throughput::Elements(threads as u64 * EVENTS_PER_ITERATION));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| run_threads(threads, iters, &emit));
+            },
+        );
+    }
+    group.finish();
+}
+
+fn multi_thread(c: &mut Criterion) {
+    let _session = start_session();
+    let counter_track = TrackEventTrack::register_counter_track(
+        "bench_counter",
+        TrackEventTrack::process_track_uuid(),
+    )
+    .expect("failed to register counter track");
+
+    bench_emit(c, "track_event_slices", |_| {
+        for _ in 0..EVENTS_PER_ITERATION {
+            track_event_begin!("bench", "slice");
+            track_event_end!("bench");
+        }
+    });
+    bench_emit(c, "track_event_counters", |iter| {
+        for value in 0..EVENTS_PER_ITERATION {
+            track_event_counter!("bench", |ctx: &mut EventContext| {
+                ctx.set_track(&counter_track);
+                ctx.set_counter(TrackEventCounter::Int64(black_box((iter + value) as i64)));
+            });
+        }
+    });
+    bench_emit(c, "data_source_packets", |iter| {
+        data_source().trace(|ctx: &mut TraceContext| {
+            for value in 0..EVENTS_PER_ITERATION {
+                ctx.add_packet(|packet: &mut TracePacket| {
+                    packet.set_for_testing(|for_testing: &mut TestEvent| {
+                        for_testing.set_counter(black_box(iter + value));
+                    });
+                });
+            }
+        });
+    });
+}
+
+criterion_group!(benches, multi_thread);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{env, process::Command};
+
+fn main() {
+    // Plugins must be built with the same compiler as the host, as their entry
+    // point and the types that they share with the host use the Rust ABI.
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(&rustc)
+        .arg("--version")
+        .output()
+        .unwrap_or_else(|err| panic!("failed to run {rustc}: {err}"));
+    let version = String::from_utf8(output.stdout).expect("invalid rustc version");
+    println!(
+        "cargo:rustc-env=PERFETTO_SDK_RUSTC_VERSION={}",
+        version.trim()
+    );
+    println!("cargo:rerun-if-changed=build.rs");
+}
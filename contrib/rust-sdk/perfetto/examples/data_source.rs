@@ -15,6 +15,7 @@
 use perfetto_sdk::{
     data_source::*,
     pb_decoder::*,
+    pb_format::format_config,
     producer::*,
     protos::{
         config::{data_source_config::*, test_config::*},
@@ -85,7 +86,7 @@ impl DummyFields {
                     self.field_string = Some(String::from_utf8(v.to_vec()).unwrap())
                 }
                 (BYTES_ID, Delimited(v)) => self.field_bytes = v.to_vec(),
-                _ => println!("WARNING: unknown DummyFields field: {:?}", item),
+                _ => perfetto_sdk::sdk_log!(Warn, "Unknown DummyFields field: {:?}", item),
             }
         }
         self
@@ -127,7 +128,7 @@ impl TestConfig {
                     dummy_fields.decode(v);
                     self.dummy_fields = Some(dummy_fields);
                 }
-                _ => println!("WARNING: unknown TestConfig field: {:?}", item),
+                _ => perfetto_sdk::sdk_log!(Warn, "Unknown TestConfig field: {:?}", item),
             }
         }
         self
@@ -159,6 +160,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             }
             test_configs[inst_id as usize] = Some(test_config);
             println!("OnSetup id: {} data: {}", inst_id, setup_data);
+            print!("{}", format_config(config));
         })
         .on_start(move |inst_id, _| {
             println!(
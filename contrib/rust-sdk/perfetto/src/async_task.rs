@@ -0,0 +1,397 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::track_event::{
+    EventContext, TrackEventDebugArg, TrackEventFlow, TrackEventTrack, TrackEventType,
+};
+use std::{
+    ffi::CStr,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+
+// Name of the slices emitted for the polls of a task.
+const POLL_SLICE_NAME: &CStr = c"poll";
+
+// Task ids are allocated per task, as the ids of executors are reused.
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Track event category that the events of async tasks are emitted in.
+///
+/// Created with [`async_task_category!`](crate::async_task_category) in the
+/// scope of the categories defined with
+/// [`track_event_categories!`](crate::track_event_categories).
+#[derive(Debug, Clone, Copy)]
+pub struct TaskCategory {
+    is_enabled: fn() -> bool,
+    emit: fn(TrackEventType, &mut EventContext),
+}
+
+impl TaskCategory {
+    #[doc(hidden)]
+    pub const fn __new(
+        is_enabled: fn() -> bool,
+        emit: fn(TrackEventType, &mut EventContext),
+    ) -> Self {
+        Self { is_enabled, emit }
+    }
+
+    /// Returns true if the category is enabled.
+    pub fn is_enabled(&self) -> bool {
+        (self.is_enabled)()
+    }
+
+    /// Emits an event of type `variant` in the category.
+    pub fn emit(&self, variant: TrackEventType, ctx: &mut EventContext) {
+        (self.emit)(variant, ctx)
+    }
+}
+
+/// Returns the [`TaskCategory`] of `category`, which must be defined in the
+/// `perfetto_te_ns` categories in scope.
+#[macro_export]
+macro_rules! async_task_category {
+    ($category:literal) => {{
+        const CATEGORY_INDEX: usize = perfetto_te_ns::category_index($category);
+        $crate::async_task::TaskCategory::__new(
+            || perfetto_te_ns::is_category_enabled(CATEGORY_INDEX),
+            |variant, ctx| perfetto_te_ns::emit(CATEGORY_INDEX, variant, ctx),
+        )
+    }};
+}
+
+/// Track of an async task, nested under the process track, that the polls of
+/// the task are emitted on as slices.
+///
+/// The polls of a task are connected by a flow, so that the time a task
+/// waited between two polls is visible. Executors that don't poll futures
+/// through [`TracedTask`] call [`AsyncTask::begin_poll`] and
+/// [`AsyncTask::end_poll`] around each poll.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{async_task::AsyncTask, async_task_category};
+///
+/// perfetto_sdk::track_event_categories! {
+///     pub mod my_categories {
+///         ("executor", "Async task scheduling", []),
+///     }
+/// }
+/// use my_categories as perfetto_te_ns;
+///
+/// let mut task = AsyncTask::new(async_task_category!("executor"), "fetch");
+/// task.begin_poll();
+/// // Poll the future of the task.
+/// task.end_poll(false);
+/// ```
+#[derive(Debug)]
+pub struct AsyncTask {
+    category: TaskCategory,
+    id: u64,
+    track: TrackEventTrack,
+    polls: u64,
+    // Whether the begin event of the current poll was emitted.
+    in_poll: bool,
+}
+
+impl AsyncTask {
+    /// Creates the track of a new task named `name`.
+    pub fn new(category: TaskCategory, name: &str) -> Self {
+        let id = NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed);
+        let track = TrackEventTrack::register_named_track_with_dynamic_name(
+            name,
+            id,
+            TrackEventTrack::process_track_uuid(),
+        )
+        .expect("failed to register task track");
+        Self {
+            category,
+            id,
+            track,
+            polls: 0,
+            in_poll: false,
+        }
+    }
+
+    /// Returns the id of the task, unique in the process.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the UUID of the track of the task.
+    pub fn uuid(&self) -> u64 {
+        self.track.uuid()
+    }
+
+    /// Returns the number of times the task was polled.
+    pub fn polls(&self) -> u64 {
+        self.polls
+    }
+
+    fn emit<F>(&self, variant: TrackEventType, cb: F) -> bool
+    where
+        F: FnOnce(&mut EventContext),
+    {
+        if !self.category.is_enabled() {
+            return false;
+        }
+        let mut ctx = EventContext::default();
+        ctx.set_track(&self.track);
+        cb(&mut ctx);
+        self.category.emit(variant, &mut ctx);
+        true
+    }
+
+    /// Emits the begin event of a poll of the task, connected to the previous
+    /// poll by the flow of the task.
+    pub fn begin_poll(&mut self) {
+        self.polls += 1;
+        let flow = TrackEventFlow::process_scoped_flow(self.uuid());
+        let polls = self.polls;
+        self.in_poll = self.emit(
+            TrackEventType::SliceBegin(POLL_SLICE_NAME.as_ptr()),
+            |ctx| {
+                ctx.set_flow(&flow)
+                    .add_debug_arg("poll", TrackEventDebugArg::Uint64(polls));
+            },
+        );
+    }
+
+    /// Emits the end event of the current poll of the task. `ready` is true if
+    /// the poll completed the task.
+    pub fn end_poll(&mut self, ready: bool) {
+        if !std::mem::take(&mut self.in_poll) {
+            return;
+        }
+        self.emit(TrackEventType::SliceEnd, |ctx| {
+            ctx.add_debug_arg("ready", TrackEventDebugArg::Bool(ready));
+        });
+    }
+}
+
+/// Future that emits the polls of the future it wraps on the track of an
+/// [`AsyncTask`], see [`TraceTaskExt::trace_task`].
+#[must_use = "Futures do nothing unless polled."]
+pub struct TracedTask<F> {
+    future: Pin<Box<F>>,
+    task: AsyncTask,
+}
+
+impl<F> TracedTask<F> {
+    /// Returns the task that the polls are emitted for.
+    pub fn task(&self) -> &AsyncTask {
+        &self.task
+    }
+}
+
+impl<F: Future> Future for TracedTask<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.task.begin_poll();
+        let result = this.future.as_mut().poll(cx);
+        this.task.end_poll(result.is_ready());
+        result
+    }
+}
+
+/// Extension trait that traces the polls of futures.
+pub trait TraceTaskExt: Future + Sized {
+    /// Wraps the future in a [`TracedTask`] named `name`, that emits each
+    /// poll of the future as a slice on a track of its own.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use perfetto_sdk::{async_task::TraceTaskExt, async_task_category};
+    ///
+    /// perfetto_sdk::track_event_categories! {
+    ///     pub mod my_categories {
+    ///         ("executor", "Async task scheduling", []),
+    ///     }
+    /// }
+    /// use my_categories as perfetto_te_ns;
+    ///
+    /// async fn fetch() {}
+    ///
+    /// let task = fetch().trace_task(async_task_category!("executor"), "fetch");
+    /// // Spawn `task` on any executor.
+    /// ```
+    fn trace_task(self, category: TaskCategory, name: &str) -> TracedTask<Self> {
+        TracedTask {
+            future: Box::pin(self),
+            task: AsyncTask::new(category, name),
+        }
+    }
+}
+
+impl<F: Future> TraceTaskExt for F {}
+
+/// Spawns `future` on the current tokio runtime as a task named `name`, whose
+/// polls are traced, see [`TraceTaskExt::trace_task`].
+///
+/// tokio's runtime task hooks are only available with `tokio_unstable`, so
+/// tasks are traced by wrapping their future instead.
+///
+/// Panics if called outside of a tokio runtime.
+#[cfg(feature = "tokio")]
+pub fn spawn<F>(category: TaskCategory, name: &str, future: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    tokio::spawn(future.trace_task(category, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_decoder::{PbDecoder, PbDecoderField},
+        protos::trace::{
+            trace::TraceFieldNumber,
+            trace_packet::TracePacketFieldNumber,
+            track_event::track_event::{TrackEventFieldNumber, TrackEventType as EventType},
+        },
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        tracing_session::TracingSession,
+        track_event::TrackEvent,
+    };
+    use std::{error::Error, task::Waker};
+
+    crate::track_event_categories! {
+        pub mod test_te_ns {
+            ( "tasks", "Test async tasks", [] ),
+        }
+    }
+    use test_te_ns as perfetto_te_ns;
+
+    // Future that is pending until it was polled `polls` times.
+    struct Yield {
+        polls: u32,
+    }
+
+    impl Future for Yield {
+        type Output = u32;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+            self.polls -= 1;
+            if self.polls == 0 {
+                return Poll::Ready(42);
+            }
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[derive(Default)]
+    struct Event {
+        r#type: Option<EventType>,
+        track_uuid: Option<u64>,
+        flow_ids: Vec<u64>,
+    }
+
+    impl Event {
+        fn decode(data: &[u8]) -> Self {
+            use PbDecoderField::*;
+            let mut event = Event::default();
+            const TYPE_ID: u32 = TrackEventFieldNumber::Type as u32;
+            const TRACK_UUID_ID: u32 = TrackEventFieldNumber::TrackUuid as u32;
+            const FLOW_IDS_ID: u32 = TrackEventFieldNumber::FlowIds as u32;
+            for field in PbDecoder::new(data) {
+                match field.unwrap() {
+                    (TYPE_ID, Varint(v)) => event.r#type = EventType::try_from(v as u32).ok(),
+                    (TRACK_UUID_ID, Varint(v)) => event.track_uuid = Some(v),
+                    (FLOW_IDS_ID, Fixed64(v)) => event.flow_ids.push(v),
+                    _ => {}
+                }
+            }
+            event
+        }
+    }
+
+    fn read_trace_events(session: &mut TracingSession) -> Vec<Event> {
+        const PACKET_ID: u32 = TraceFieldNumber::Packet as u32;
+        const TRACK_EVENT_ID: u32 = TracePacketFieldNumber::TrackEvent as u32;
+        let data = read_trace_data(session);
+        let mut events = vec![];
+        for trace_field in PbDecoder::new(&data) {
+            if let (PACKET_ID, PbDecoderField::Delimited(packet)) = trace_field.unwrap() {
+                for packet_field in PbDecoder::new(packet) {
+                    if let (TRACK_EVENT_ID, PbDecoderField::Delimited(v)) = packet_field.unwrap() {
+                        events.push(Event::decode(v));
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn traced_task() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        TrackEvent::init();
+        test_te_ns::register()?;
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name("track_event")
+            .add_enabled_category("tasks")
+            .add_disabled_category("*")
+            .build()?;
+        session.start_blocking();
+        let mut task = Yield { polls: 2 }.trace_task(async_task_category!("tasks"), "yield");
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(Pin::new(&mut task).poll(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut task).poll(&mut cx), Poll::Ready(42));
+        assert_eq!(task.task().polls(), 2);
+        let uuid = task.task().uuid();
+        session.stop_blocking();
+        test_te_ns::unregister()?;
+
+        let events = read_trace_events(&mut session);
+        let types: Vec<_> = events.iter().map(|event| event.r#type).collect();
+        assert_eq!(
+            types,
+            vec![
+                Some(EventType::TypeSliceBegin),
+                Some(EventType::TypeSliceEnd),
+                Some(EventType::TypeSliceBegin),
+                Some(EventType::TypeSliceEnd),
+            ]
+        );
+        assert!(events.iter().all(|event| event.track_uuid == Some(uuid)));
+        // Both polls are connected by the same flow.
+        assert_eq!(events[0].flow_ids.len(), 1);
+        assert_eq!(events[0].flow_ids, events[2].flow_ids);
+        Ok(())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn spawn_task() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        TrackEvent::init();
+        test_te_ns::register()?;
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        let output = runtime.block_on(async {
+            spawn(async_task_category!("tasks"), "yield", Yield { polls: 3 }).await
+        })?;
+        assert_eq!(output, 42);
+        test_te_ns::unregister()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::trace_reader::{TraceReaderError, TraceStreamReader};
+use std::io;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+// Size of the reads from the underlying reader.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Async trace reader errors.
+#[derive(Error, Debug)]
+pub enum AsyncTraceReaderError {
+    /// Reading from the underlying reader failed.
+    #[error("Failed to read trace: {0}")]
+    Io(#[from] io::Error),
+    /// The trace is malformed or ends in the middle of a packet.
+    #[error("Invalid trace: {0}")]
+    Trace(#[from] TraceReaderError),
+}
+
+/// Reader that yields the `TracePacket`s of a serialized `Trace` as they are
+/// received from an [`AsyncRead`], e.g. a socket that a live session is
+/// streamed to, without waiting for the end of the trace.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::async_trace_reader::AsyncTraceReader;
+///
+/// async fn monitor(stream: tokio::net::TcpStream) {
+///     let mut reader = AsyncTraceReader::new(stream);
+///     while let Some(packet) = reader.next_packet().await {
+///         println!("{} byte packet", packet.unwrap().len());
+///     }
+/// }
+/// ```
+pub struct AsyncTraceReader<R> {
+    reader: R,
+    stream: TraceStreamReader,
+    chunk: Box<[u8]>,
+    done: bool,
+}
+
+impl<R: AsyncRead + Unpin> AsyncTraceReader<R> {
+    /// Creates a reader over the serialized trace read from `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            stream: TraceStreamReader::new(),
+            chunk: vec![0u8; READ_CHUNK_SIZE].into_boxed_slice(),
+            done: false,
+        }
+    }
+
+    /// Returns the next packet, waiting until it has been received completely.
+    /// Returns `None` at the end of the trace, or after an error.
+    ///
+    /// Cancelling the returned future doesn't lose data.
+    pub async fn next_packet(&mut self) -> Option<Result<Vec<u8>, AsyncTraceReaderError>> {
+        loop {
+            if let Some(packet) = self.stream.next_packet() {
+                if packet.is_err() {
+                    self.done = true;
+                }
+                return Some(packet.map_err(AsyncTraceReaderError::from));
+            }
+            if self.done {
+                return None;
+            }
+            match self.reader.read(&mut self.chunk).await {
+                Ok(0) => {
+                    self.done = true;
+                    return self.stream.finish().err().map(|err| Err(err.into()));
+                }
+                Ok(size) => self.stream.push(&self.chunk[..size]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err.into()));
+                }
+            }
+        }
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn streamed_packets() {
+        let (mut writer, reader) = tokio::io::duplex(4);
+        let mut reader = AsyncTraceReader::new(reader);
+        let trace = b"\x0a\x02\x08\x01\x10\x05\x0a\x03\x08\x02\x10";
+        let write = tokio::spawn(async move {
+            for byte in trace {
+                writer.write_all(&[*byte]).await.unwrap();
+            }
+            // Ends the stream in the middle of a packet.
+            writer.write_all(b"\x0a\x02").await.unwrap();
+        });
+        assert_eq!(reader.next_packet().await.unwrap().unwrap(), b"\x08\x01");
+        assert_eq!(
+            reader.next_packet().await.unwrap().unwrap(),
+            b"\x08\x02\x10"
+        );
+        write.await.unwrap();
+        assert!(matches!(
+            reader.next_packet().await,
+            Some(Err(AsyncTraceReaderError::Trace(
+                TraceReaderError::Truncated(11)
+            )))
+        ));
+        assert!(reader.next_packet().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn split_reads() {
+        let (mut writer, reader) = tokio::io::duplex(1024);
+        let mut reader = AsyncTraceReader::new(reader);
+        // A packet larger than a read, received in pieces smaller than a read.
+        let payload = vec![0x42u8; READ_CHUNK_SIZE + 1];
+        let mut trace = vec![0x0a];
+        let mut len = [0u8; 10];
+        let len_size = crate::pb_utils::pb_write_varint(payload.len() as u64, &mut len);
+        trace.extend_from_slice(&len[..len_size]);
+        trace.extend_from_slice(&payload);
+        trace.extend_from_slice(b"\x0a\x02\x08\x01");
+        let write = tokio::spawn(async move {
+            for piece in trace.chunks(1000) {
+                writer.write_all(piece).await.unwrap();
+            }
+        });
+        assert_eq!(reader.next_packet().await.unwrap().unwrap(), payload);
+        assert_eq!(reader.next_packet().await.unwrap().unwrap(), b"\x08\x01");
+        write.await.unwrap();
+        assert!(reader.next_packet().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn truncated_length() {
+        // The stream ends in the middle of the length of the second packet.
+        let trace: &[u8] = b"\x0a\x02\x08\x01\x0a\x80";
+        let mut reader = AsyncTraceReader::new(trace);
+        assert_eq!(reader.next_packet().await.unwrap().unwrap(), b"\x08\x01");
+        assert!(matches!(
+            reader.next_packet().await,
+            Some(Err(AsyncTraceReaderError::Trace(
+                TraceReaderError::Truncated(4)
+            )))
+        ));
+        assert!(reader.next_packet().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn oversized_packet() {
+        // The packet declares a length of 1 TiB.
+        let trace: &[u8] = b"\x0a\x80\x80\x80\x80\x80\x20";
+        let mut reader = AsyncTraceReader::new(trace);
+        assert!(matches!(
+            reader.next_packet().await,
+            Some(Err(AsyncTraceReaderError::Trace(
+                TraceReaderError::FieldTooLarge(0)
+            )))
+        ));
+        assert!(reader.next_packet().await.is_none());
+    }
+}
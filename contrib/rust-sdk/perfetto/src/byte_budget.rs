@@ -0,0 +1,210 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::pb_decoder::{PbDecoder, PbDecoderField};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+/// Action taken on the packets of an instance once it has exhausted its
+/// [`ByteBudget`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BudgetAction {
+    /// Drop all further packets.
+    #[default]
+    Drop,
+    /// Keep one packet out of every `n` further packets, and drop the others.
+    Downsample(u32),
+}
+
+/// Number of bytes each instance of a data source can write, see
+/// [`DataSourceArgsBuilder::byte_budget`](crate::data_source::DataSourceArgsBuilder::byte_budget).
+///
+/// A data source that writes a lot can fill a small ring buffer by itself and
+/// overwrite the data of all the other data sources of the session. The bytes
+/// written by each instance are tracked across trace calls, and once they
+/// exceed the budget a marker packet with `previous_packet_dropped` is written
+/// and the action is taken on the further packets of the instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteBudget {
+    /// Number of bytes each instance can write, or zero for no limit.
+    pub limit: u64,
+    /// Action taken once the budget is exhausted.
+    pub action: BudgetAction,
+    /// Number of a varint field of the `DataSourceConfig` of the instances
+    /// that overrides `limit`, with zero disabling the budget.
+    pub config_field: Option<u32>,
+}
+
+impl ByteBudget {
+    /// Budget of `limit` bytes, after which further packets are dropped.
+    pub fn drop(limit: u64) -> Self {
+        Self {
+            limit,
+            action: BudgetAction::Drop,
+            config_field: None,
+        }
+    }
+
+    /// Budget of `limit` bytes, after which one packet out of every `n` is
+    /// kept.
+    pub fn downsample(limit: u64, n: u32) -> Self {
+        Self {
+            limit,
+            action: BudgetAction::Downsample(n),
+            config_field: None,
+        }
+    }
+
+    /// Reads the limit of each instance from the varint field `field_id` of
+    /// its `DataSourceConfig`, if set, e.g. from a field of the config
+    /// extension of the data source.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn config_field(mut self, field_id: u32) -> Self {
+        self.config_field = Some(field_id);
+        self
+    }
+
+    // Returns the limit of the instance set up with the encoded
+    // `DataSourceConfig` `config`.
+    pub(crate) fn limit_of(&self, config: &[u8]) -> u64 {
+        let Some(field_id) = self.config_field else {
+            return self.limit;
+        };
+        PbDecoder::new(config)
+            .filter_map(|item| match item {
+                Ok((id, PbDecoderField::Varint(value))) if id == field_id => Some(value),
+                _ => None,
+            })
+            .last()
+            .unwrap_or(self.limit)
+    }
+}
+
+// Whether the next packet of an instance is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Admission {
+    Write,
+    // The budget was just exhausted: a marker is written instead of the packet.
+    Exhausted,
+    Throttle,
+}
+
+// Bytes written by an instance, reset when the instance is set up.
+#[derive(Debug, Default)]
+pub(crate) struct BudgetState {
+    // Limit of the instance, or zero if unlimited.
+    limit: AtomicU64,
+    // Zero to drop the packets once the budget is exhausted, or keep one
+    // packet out of `keep_one_in`.
+    keep_one_in: AtomicU32,
+    used: AtomicU64,
+    exhausted: AtomicBool,
+    // Number of packets offered since the budget was exhausted.
+    offered: AtomicU64,
+}
+
+impl BudgetState {
+    pub(crate) fn reset(&self, budget: &ByteBudget, config: &[u8]) {
+        self.limit.store(budget.limit_of(config), Ordering::Relaxed);
+        let keep_one_in = match budget.action {
+            BudgetAction::Drop => 0,
+            BudgetAction::Downsample(n) => n.max(1),
+        };
+        self.keep_one_in.store(keep_one_in, Ordering::Relaxed);
+        self.used.store(0, Ordering::Relaxed);
+        self.exhausted.store(false, Ordering::Relaxed);
+        self.offered.store(0, Ordering::Relaxed);
+    }
+
+    pub(crate) fn admit(&self) -> Admission {
+        let limit = self.limit.load(Ordering::Relaxed);
+        if limit == 0 || self.used.load(Ordering::Relaxed) < limit {
+            return Admission::Write;
+        }
+        // Only the first thread to see the exhausted budget writes the marker.
+        if !self.exhausted.swap(true, Ordering::Relaxed) {
+            return Admission::Exhausted;
+        }
+        let keep_one_in = u64::from(self.keep_one_in.load(Ordering::Relaxed));
+        let offered = self.offered.fetch_add(1, Ordering::Relaxed) + 1;
+        if keep_one_in != 0 && offered.is_multiple_of(keep_one_in) {
+            return Admission::Write;
+        }
+        Admission::Throttle
+    }
+
+    pub(crate) fn charge(&self, size: usize) {
+        self.used.fetch_add(size as u64, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        heap_buffer::HeapBuffer,
+        pb_msg::{PbMsg, PbMsgWriter},
+        protos::config::data_source_config::DataSourceConfig,
+    };
+
+    #[test]
+    fn limit_from_config() {
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        {
+            let mut config = DataSourceConfig { msg: &mut msg };
+            config.set_name("budgeted");
+        }
+        msg.append_type0_field(1001, 4096);
+        msg.finalize();
+        let mut data = vec![0u8; writer.writer.get_written_size()];
+        hb.copy_into(&mut data);
+
+        assert_eq!(ByteBudget::drop(100).limit_of(&data), 100);
+        assert_eq!(
+            ByteBudget::drop(100).config_field(1001).limit_of(&data),
+            4096
+        );
+        assert_eq!(
+            ByteBudget::drop(100).config_field(1002).limit_of(&data),
+            100
+        );
+    }
+
+    #[test]
+    fn admit() {
+        let state = BudgetState::default();
+        state.reset(&ByteBudget::drop(10), &[]);
+        assert_eq!(state.admit(), Admission::Write);
+        state.charge(10);
+        assert_eq!(state.admit(), Admission::Exhausted);
+        assert_eq!(state.admit(), Admission::Throttle);
+
+        state.reset(&ByteBudget::downsample(10, 3), &[]);
+        state.charge(20);
+        let admissions: Vec<_> = (0..7).map(|_| state.admit()).collect();
+        use Admission::*;
+        assert_eq!(
+            admissions,
+            vec![
+                Exhausted, Throttle, Throttle, Write, Throttle, Throttle, Write
+            ]
+        );
+
+        // A zero limit disables the budget.
+        state.reset(&ByteBudget::drop(0), &[]);
+        state.charge(1000);
+        assert_eq!(state.admit(), Admission::Write);
+    }
+}
@@ -0,0 +1,983 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    fnv1a,
+    heap_buffer::HeapBuffer,
+    pb_msg::{PbMsg, PbMsgWriter},
+    protos::trace::{
+        trace::Trace,
+        trace_packet::{TracePacket, TracePacketSequenceFlags},
+        track_event::{
+            counter_descriptor::CounterDescriptor,
+            debug_annotation::DebugAnnotation,
+            process_descriptor::ProcessDescriptor,
+            thread_descriptor::ThreadDescriptor,
+            track_descriptor::TrackDescriptor,
+            track_event::{TrackEvent, TrackEventType},
+        },
+    },
+    track_event::TrackEventTrack,
+};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Chrome JSON import errors.
+#[derive(Error, Debug, PartialEq)]
+pub enum ChromeJsonError {
+    /// The input is not well-formed JSON.
+    #[error("Invalid JSON at byte offset {0}.")]
+    InvalidJson(usize),
+    /// The input is JSON but not in the Chrome Trace Event Format.
+    #[error("Expected an array of trace events or an object with a `traceEvents` array.")]
+    NotATrace,
+    /// The input nests arrays and objects deeper than [`MAX_JSON_DEPTH`].
+    #[error("JSON nested deeper than {MAX_JSON_DEPTH} levels at byte offset {0}.")]
+    TooDeep(usize),
+}
+
+/// Maximum nesting depth of the arrays and objects of an imported trace.
+pub const MAX_JSON_DEPTH: usize = 128;
+
+/// Parsed JSON value.
+#[derive(Debug, Clone, PartialEq)]
+enum JsonValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl JsonValue {
+    fn get(&self, key: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_f64(&self) -> Option<f64> {
+        match self {
+            JsonValue::Int(v) => Some(*v as f64),
+            JsonValue::Double(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    // Chrome traces use both numbers and (possibly hex) strings for ids.
+    fn as_id(&self) -> Option<u64> {
+        match self {
+            JsonValue::Int(v) => Some(*v as u64),
+            JsonValue::Double(v) => Some(*v as u64),
+            JsonValue::String(s) => {
+                let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+                    Some(hex) => u64::from_str_radix(hex, 16).ok(),
+                    None => s.parse::<i64>().ok().map(|v| v as u64),
+                };
+                Some(parsed.unwrap_or_else(|| fnv1a(s.as_bytes())))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Minimal recursive descent JSON parser.
+struct JsonParser<'a> {
+    data: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn new(data: &'a str) -> Self {
+        Self {
+            data: data.as_bytes(),
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn error(&self) -> ChromeJsonError {
+        ChromeJsonError::InvalidJson(self.pos)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.data.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.data.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), ChromeJsonError> {
+        if self.peek() != Some(byte) {
+            return Err(self.error());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn expect_literal(&mut self, literal: &[u8]) -> Result<(), ChromeJsonError> {
+        if !self.data[self.pos..].starts_with(literal) {
+            return Err(self.error());
+        }
+        self.pos += literal.len();
+        Ok(())
+    }
+
+    /// Parses a complete document. The top-level array is allowed to be
+    /// unterminated, which is common for traces written by crashing or
+    /// streaming producers.
+    fn parse_document(mut self) -> Result<JsonValue, ChromeJsonError> {
+        let value = if self.peek() == Some(b'[') {
+            self.parse_nested(|parser| parser.parse_array(true))?
+        } else {
+            self.parse_value()?
+        };
+        if self.peek().is_some() {
+            return Err(self.error());
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<JsonValue, ChromeJsonError> {
+        match self.peek() {
+            Some(b'{') => self.parse_nested(Self::parse_object),
+            Some(b'[') => self.parse_nested(|parser| parser.parse_array(false)),
+            Some(b'"') => Ok(JsonValue::String(self.parse_string()?)),
+            Some(b't') => self.expect_literal(b"true").map(|_| JsonValue::Bool(true)),
+            Some(b'f') => self
+                .expect_literal(b"false")
+                .map(|_| JsonValue::Bool(false)),
+            Some(b'n') => self.expect_literal(b"null").map(|_| JsonValue::Null),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            _ => Err(self.error()),
+        }
+    }
+
+    // Parses an array or object with `parse`, one level deeper.
+    fn parse_nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<JsonValue, ChromeJsonError>,
+    ) -> Result<JsonValue, ChromeJsonError> {
+        if self.depth == MAX_JSON_DEPTH {
+            return Err(ChromeJsonError::TooDeep(self.pos));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn parse_object(&mut self) -> Result<JsonValue, ChromeJsonError> {
+        self.expect(b'{')?;
+        let mut entries = Vec::new();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(JsonValue::Object(entries));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.error());
+            }
+            let key = self.parse_string()?;
+            self.expect(b':')?;
+            entries.push((key, self.parse_value()?));
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(entries));
+                }
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    fn parse_array(&mut self, allow_unterminated: bool) -> Result<JsonValue, ChromeJsonError> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(values));
+                }
+                None if allow_unterminated => return Ok(JsonValue::Array(values)),
+                _ => {}
+            }
+            values.push(self.parse_value()?);
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {}
+                None if allow_unterminated => {}
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<JsonValue, ChromeJsonError> {
+        let start = self.pos;
+        let mut is_double = false;
+        while let Some(&b) = self.data.get(self.pos) {
+            match b {
+                b'0'..=b'9' | b'-' | b'+' => {}
+                b'.' | b'e' | b'E' => is_double = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        // Only ASCII bytes were consumed so this is valid UTF-8.
+        let text = std::str::from_utf8(&self.data[start..self.pos]).map_err(|_| self.error())?;
+        if !is_double && let Ok(v) = text.parse::<i64>() {
+            return Ok(JsonValue::Int(v));
+        }
+        text.parse::<f64>()
+            .map(JsonValue::Double)
+            .map_err(|_| ChromeJsonError::InvalidJson(start))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, ChromeJsonError> {
+        let digits = self.data.get(self.pos..self.pos + 4).ok_or(self.error())?;
+        let digits = std::str::from_utf8(digits).map_err(|_| self.error())?;
+        let value = u32::from_str_radix(digits, 16).map_err(|_| self.error())?;
+        self.pos += 4;
+        Ok(value)
+    }
+
+    fn parse_string(&mut self) -> Result<String, ChromeJsonError> {
+        self.expect(b'"')?;
+        let mut out = Vec::new();
+        loop {
+            let b = *self.data.get(self.pos).ok_or(self.error())?;
+            self.pos += 1;
+            match b {
+                b'"' => break,
+                b'\\' => {
+                    let escape = *self.data.get(self.pos).ok_or(self.error())?;
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let mut code = self.parse_hex4()?;
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.data[self.pos..].starts_with(b"\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.parse_hex4()?;
+                                code =
+                                    0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00));
+                            }
+                            char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)
+                        }
+                        _ => return Err(self.error()),
+                    };
+                    let mut buf = [0u8; 4];
+                    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                _ => out.push(b),
+            }
+        }
+        // The input is a `&str` and escapes produce valid characters.
+        String::from_utf8(out).map_err(|_| self.error())
+    }
+}
+
+// Magic values used to derive track UUIDs that are unlikely to collide with
+// the UUIDs of tracks emitted by the SDK itself.
+const PROCESS_MAGIC: u64 = 0x6a3f1c2e9b7d4058;
+const THREAD_MAGIC: u64 = 0x1d8e5b3a7c046f92;
+const GLOBAL_TRACK_UUID: u64 = 0x4c17a9e2b05d3f86;
+
+// Packet sequence id used for all imported packets.
+const IMPORT_SEQUENCE_ID: u32 = 1;
+
+fn process_uuid(pid: i64) -> u64 {
+    PROCESS_MAGIC ^ fnv1a(&pid.to_le_bytes())
+}
+
+fn thread_uuid(pid: i64, tid: i64) -> u64 {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&pid.to_le_bytes());
+    bytes[8..].copy_from_slice(&tid.to_le_bytes());
+    THREAD_MAGIC ^ fnv1a(&bytes)
+}
+
+// Converts a microsecond JSON timestamp to nanoseconds.
+fn us_to_ns(us: f64) -> u64 {
+    (us * 1000.0).round() as u64
+}
+
+fn json_pid(value: Option<&JsonValue>) -> i64 {
+    value.and_then(JsonValue::as_id).unwrap_or(0) as i64
+}
+
+/// Tracks referenced by imported events, in order of first use.
+#[derive(Default)]
+struct TrackTable {
+    processes: Vec<i64>,
+    process_names: HashMap<i64, String>,
+    threads: Vec<(i64, i64)>,
+    thread_names: HashMap<(i64, i64), String>,
+    // (uuid, parent uuid, name, is counter)
+    named: Vec<(u64, u64, String, bool)>,
+    needs_global: bool,
+}
+
+impl TrackTable {
+    fn add_process(&mut self, pid: i64) -> u64 {
+        if !self.processes.contains(&pid) {
+            self.processes.push(pid);
+        }
+        process_uuid(pid)
+    }
+
+    fn add_thread(&mut self, pid: i64, tid: i64) -> u64 {
+        self.add_process(pid);
+        if !self.threads.contains(&(pid, tid)) {
+            self.threads.push((pid, tid));
+        }
+        thread_uuid(pid, tid)
+    }
+
+    fn add_named(&mut self, uuid: u64, parent_uuid: u64, name: &str, is_counter: bool) -> u64 {
+        if !self.named.iter().any(|(u, ..)| *u == uuid) {
+            self.named
+                .push((uuid, parent_uuid, name.to_string(), is_counter));
+        }
+        uuid
+    }
+}
+
+/// A single track event to emit.
+struct ImportedEvent<'a> {
+    ts: u64,
+    // Duration of the slice, if its begin and end are both known.
+    dur: Option<u64>,
+    track_uuid: u64,
+    event_type: TrackEventType,
+    name: Option<&'a str>,
+    category: Option<&'a str>,
+    args: Option<&'a JsonValue>,
+    counter_value: Option<&'a JsonValue>,
+    flow_id: Option<u64>,
+    terminating_flow_id: Option<u64>,
+}
+
+impl<'a> ImportedEvent<'a> {
+    fn new(ts: u64, track_uuid: u64, event_type: TrackEventType) -> Self {
+        Self {
+            ts,
+            dur: None,
+            track_uuid,
+            event_type,
+            name: None,
+            category: None,
+            args: None,
+            counter_value: None,
+            flow_id: None,
+            terminating_flow_id: None,
+        }
+    }
+
+    // Orders the events by timestamp. At the same timestamp, slices that began
+    // earlier end first, shortest first. Then other slices begin, longest first,
+    // so that they enclose the shorter ones. Slices without a known duration
+    // are assumed to be the longest. Everything else, including the ends of
+    // zero duration slices, keeps its input order as the sort is stable.
+    fn sort_key(&self) -> (u64, u8, u64) {
+        match (self.event_type, self.dur) {
+            (TrackEventType::TypeSliceEnd, Some(0)) => (self.ts, 1, u64::MAX),
+            (TrackEventType::TypeSliceEnd, dur) => (self.ts, 0, dur.unwrap_or(u64::MAX)),
+            (TrackEventType::TypeSliceBegin, dur) => {
+                (self.ts, 1, dur.map_or(0, |dur| u64::MAX - dur))
+            }
+            _ => (self.ts, 1, u64::MAX),
+        }
+    }
+}
+
+// Sets the durations of `B`/`E` and async slices by matching their begins and
+// ends in input order, so that they are sorted like `X` events.
+fn match_slices(imported: &mut [ImportedEvent]) {
+    let mut open: HashMap<u64, Vec<usize>> = HashMap::new();
+    for i in 0..imported.len() {
+        let (ts, track_uuid) = (imported[i].ts, imported[i].track_uuid);
+        match (imported[i].event_type, imported[i].dur) {
+            (TrackEventType::TypeSliceBegin, None) => open.entry(track_uuid).or_default().push(i),
+            (TrackEventType::TypeSliceEnd, None) => {
+                if let Some(begin) = open.get_mut(&track_uuid).and_then(Vec::pop) {
+                    let dur = ts.saturating_sub(imported[begin].ts);
+                    imported[begin].dur = Some(dur);
+                    imported[i].dur = Some(dur);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn write_annotation_value(annotation: &mut DebugAnnotation, value: &JsonValue) {
+    match value {
+        JsonValue::Null => {
+            annotation.set_legacy_json_value("null");
+        }
+        JsonValue::Bool(v) => {
+            annotation.set_bool_value(*v);
+        }
+        JsonValue::Int(v) => {
+            annotation.set_int_value(*v);
+        }
+        JsonValue::Double(v) => {
+            annotation.set_double_value(*v);
+        }
+        JsonValue::String(v) => {
+            annotation.set_string_value(v.as_str());
+        }
+        JsonValue::Array(values) => {
+            for v in values {
+                annotation.set_array_values(|item: &mut DebugAnnotation| {
+                    write_annotation_value(item, v);
+                });
+            }
+        }
+        JsonValue::Object(entries) => {
+            for (k, v) in entries {
+                annotation.set_dict_entries(|entry: &mut DebugAnnotation| {
+                    entry.set_name(k.as_str());
+                    write_annotation_value(entry, v);
+                });
+            }
+        }
+    }
+}
+
+fn write_track_event(event: &mut TrackEvent, imported: &ImportedEvent) {
+    event.set_type(imported.event_type);
+    event.set_track_uuid(imported.track_uuid);
+    if let Some(category) = imported.category {
+        for category in category.split(',').filter(|c| !c.is_empty()) {
+            event.set_categories(category);
+        }
+    }
+    if let Some(name) = imported.name {
+        event.set_name(name);
+    }
+    match imported.counter_value {
+        Some(JsonValue::Int(v)) => {
+            event.set_counter_value(*v);
+        }
+        Some(v) => {
+            event.set_double_counter_value(v.as_f64().unwrap_or_default());
+        }
+        None => {}
+    }
+    if let Some(id) = imported.flow_id {
+        event.set_flow_ids(id);
+    }
+    if let Some(id) = imported.terminating_flow_id {
+        event.set_terminating_flow_ids(id);
+    }
+    if let Some(JsonValue::Object(entries)) = imported.args {
+        for (k, v) in entries {
+            event.set_debug_annotations(|annotation: &mut DebugAnnotation| {
+                annotation.set_name(k.as_str());
+                write_annotation_value(annotation, v);
+            });
+        }
+    }
+}
+
+/// Converts a trace in the Chrome Trace Event Format (JSON) to a serialized
+/// Perfetto `Trace` protobuf, i.e. the contents of a `.perfetto-trace` file.
+///
+/// Both the JSON array format and the JSON object format (with a
+/// `traceEvents` array) are accepted, as is an unterminated top-level array.
+///
+/// Duration (`B`, `E`, `X`), instant (`i`, `I`), counter (`C`), async (`b`,
+/// `e`, `n`, `S`, `F`) and metadata (`M`) events are converted to track
+/// events on process, thread, counter and async tracks. Flow v2 bindings
+/// (`bind_id`, `flow_in` and `flow_out`) are converted to flow ids. Other
+/// event phases are ignored.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::chrome_json::import_chrome_json;
+///
+/// let json = r#"[
+///   {"name": "DrawFrame", "cat": "gfx", "ph": "X", "ts": 10, "dur": 5, "pid": 1, "tid": 2}
+/// ]"#;
+/// let trace = import_chrome_json(json).unwrap();
+/// std::fs::write(std::env::temp_dir().join("imported.perfetto-trace"), trace).unwrap();
+/// ```
+pub fn import_chrome_json(json: &str) -> Result<Vec<u8>, ChromeJsonError> {
+    let document = JsonParser::new(json).parse_document()?;
+    let events = match &document {
+        JsonValue::Array(events) => events,
+        JsonValue::Object(_) => match document.get("traceEvents") {
+            Some(JsonValue::Array(events)) => events,
+            _ => return Err(ChromeJsonError::NotATrace),
+        },
+        _ => return Err(ChromeJsonError::NotATrace),
+    };
+
+    let mut tracks = TrackTable::default();
+    let mut imported = Vec::new();
+    for event in events {
+        let Some(ph) = event.get("ph").and_then(JsonValue::as_str) else {
+            continue;
+        };
+        let name = event.get("name").and_then(JsonValue::as_str);
+        let category = event.get("cat").and_then(JsonValue::as_str);
+        let args = event.get("args");
+        let pid = json_pid(event.get("pid"));
+        let tid = event.get("tid").map_or(pid, |v| json_pid(Some(v)));
+        let ts = us_to_ns(event.get("ts").and_then(JsonValue::as_f64).unwrap_or(0.0));
+
+        let bind_id = event.get("bind_id").and_then(JsonValue::as_id);
+        let flow_out = event.get("flow_out") == Some(&JsonValue::Bool(true));
+        let flow_in = event.get("flow_in") == Some(&JsonValue::Bool(true));
+        let (flow_id, terminating_flow_id) = match bind_id {
+            Some(id) if flow_out => (Some(id), None),
+            Some(id) if flow_in => (None, Some(id)),
+            _ => (None, None),
+        };
+
+        let mut push = |ts, dur, track_uuid, event_type, with_name: bool| {
+            let mut e = ImportedEvent::new(ts, track_uuid, event_type);
+            e.dur = dur;
+            if with_name {
+                e.name = name;
+                e.category = category;
+                e.args = args;
+                e.flow_id = flow_id;
+                e.terminating_flow_id = terminating_flow_id;
+            }
+            imported.push(e);
+        };
+
+        match ph {
+            "B" => {
+                let uuid = tracks.add_thread(pid, tid);
+                push(ts, None, uuid, TrackEventType::TypeSliceBegin, true);
+            }
+            "E" => {
+                let uuid = tracks.add_thread(pid, tid);
+                push(ts, None, uuid, TrackEventType::TypeSliceEnd, false);
+            }
+            "X" => {
+                let uuid = tracks.add_thread(pid, tid);
+                let dur = us_to_ns(event.get("dur").and_then(JsonValue::as_f64).unwrap_or(0.0));
+                push(ts, Some(dur), uuid, TrackEventType::TypeSliceBegin, true);
+                // Slices past the end of time are clamped.
+                let end = ts.saturating_add(dur);
+                push(end, Some(dur), uuid, TrackEventType::TypeSliceEnd, false);
+            }
+            "i" | "I" => {
+                let uuid = match event.get("s").and_then(JsonValue::as_str) {
+                    Some("g") => {
+                        tracks.needs_global = true;
+                        GLOBAL_TRACK_UUID
+                    }
+                    Some("p") => tracks.add_process(pid),
+                    _ => tracks.add_thread(pid, tid),
+                };
+                push(ts, None, uuid, TrackEventType::TypeInstant, true);
+            }
+            "C" => {
+                let parent_uuid = tracks.add_process(pid);
+                let Some(JsonValue::Object(entries)) = args else {
+                    continue;
+                };
+                let prefix = match event.get("id").and_then(JsonValue::as_id) {
+                    Some(id) => format!("{} {}", name.unwrap_or_default(), id),
+                    None => name.unwrap_or_default().to_string(),
+                };
+                for (key, value) in entries {
+                    if value.as_f64().is_none() {
+                        continue;
+                    }
+                    let counter_name = format!("{prefix} {key}");
+                    let uuid = TrackEventTrack::counter_track_uuid(&counter_name, parent_uuid);
+                    tracks.add_named(uuid, parent_uuid, &counter_name, true);
+                    let mut e = ImportedEvent::new(ts, uuid, TrackEventType::TypeCounter);
+                    e.counter_value = Some(value);
+                    imported.push(e);
+                }
+            }
+            "b" | "e" | "n" | "S" | "F" => {
+                let id = event.get("id").and_then(JsonValue::as_id).or_else(|| {
+                    let id2 = event.get("id2")?;
+                    id2.get("global").or_else(|| id2.get("local"))?.as_id()
+                });
+                let Some(id) = id else {
+                    continue;
+                };
+                let parent_uuid = tracks.add_process(pid);
+                let track_name = name.unwrap_or_default();
+                let scoped_id = id ^ fnv1a(category.unwrap_or_default().as_bytes());
+                let uuid = TrackEventTrack::named_track_uuid(track_name, scoped_id, parent_uuid);
+                tracks.add_named(uuid, parent_uuid, track_name, false);
+                match ph {
+                    "b" | "S" => push(ts, None, uuid, TrackEventType::TypeSliceBegin, true),
+                    "e" | "F" => push(ts, None, uuid, TrackEventType::TypeSliceEnd, false),
+                    _ => push(ts, None, uuid, TrackEventType::TypeInstant, true),
+                }
+            }
+            "M" => {
+                let value = args
+                    .and_then(|a| a.get("name"))
+                    .and_then(JsonValue::as_str)
+                    .map(str::to_string);
+                match (name, value) {
+                    (Some("process_name"), Some(value)) => {
+                        tracks.add_process(pid);
+                        tracks.process_names.insert(pid, value);
+                    }
+                    (Some("thread_name"), Some(value)) => {
+                        tracks.add_thread(pid, tid);
+                        tracks.thread_names.insert((pid, tid), value);
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Packets of a sequence must be sorted by timestamp, but the ends of `X`
+    // events are only known once their begins are added.
+    match_slices(&mut imported);
+    imported.sort_by_key(ImportedEvent::sort_key);
+    Ok(write_trace(&tracks, &imported))
+}
+
+fn write_trace(tracks: &TrackTable, events: &[ImportedEvent]) -> Vec<u8> {
+    let writer = PbMsgWriter::new();
+    let hb = HeapBuffer::new(&writer.writer);
+    let mut msg = PbMsg::new(&writer).unwrap();
+    {
+        let mut trace = Trace { msg: &mut msg };
+        let mut first = true;
+        let mut add_packet = |cb: &dyn Fn(&mut TracePacket)| {
+            let is_first = first;
+            first = false;
+            trace.set_packet(|packet: &mut TracePacket| {
+                packet.set_trusted_packet_sequence_id(IMPORT_SEQUENCE_ID);
+                if is_first {
+                    packet.set_first_packet_on_sequence(true);
+                    packet.set_sequence_flags(
+                        TracePacketSequenceFlags::SeqIncrementalStateCleared.into(),
+                    );
+                }
+                cb(packet);
+            });
+        };
+
+        for &pid in &tracks.processes {
+            add_packet(&|packet| {
+                packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+                    desc.set_uuid(process_uuid(pid));
+                    desc.set_process(|process: &mut ProcessDescriptor| {
+                        process.set_pid(pid as i32);
+                        if let Some(name) = tracks.process_names.get(&pid) {
+                            process.set_process_name(name.as_str());
+                        }
+                    });
+                });
+            });
+        }
+        for &(pid, tid) in &tracks.threads {
+            add_packet(&|packet| {
+                packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+                    desc.set_uuid(thread_uuid(pid, tid));
+                    desc.set_parent_uuid(process_uuid(pid));
+                    desc.set_thread(|thread: &mut ThreadDescriptor| {
+                        thread.set_pid(pid as i32);
+                        thread.set_tid(tid);
+                        if let Some(name) = tracks.thread_names.get(&(pid, tid)) {
+                            thread.set_thread_name(name.as_str());
+                        }
+                    });
+                });
+            });
+        }
+        for (uuid, parent_uuid, name, is_counter) in &tracks.named {
+            add_packet(&|packet| {
+                packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+                    desc.set_uuid(*uuid);
+                    desc.set_parent_uuid(*parent_uuid);
+                    desc.set_name(name.as_str());
+                    if *is_counter {
+                        desc.set_counter(|_: &mut CounterDescriptor| {});
+                    }
+                });
+            });
+        }
+        if tracks.needs_global {
+            add_packet(&|packet| {
+                packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+                    desc.set_uuid(GLOBAL_TRACK_UUID);
+                    desc.set_name("Global");
+                });
+            });
+        }
+        for event in events {
+            add_packet(&|packet| {
+                packet.set_timestamp(event.ts);
+                packet.set_track_event(|track_event: &mut TrackEvent| {
+                    write_track_event(track_event, event);
+                });
+            });
+        }
+    }
+    msg.finalize();
+    let size = writer.writer.get_written_size();
+    let mut buffer = vec![0u8; size];
+    hb.copy_into(&mut buffer);
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb_decoder::{PbDecoder, PbDecoderField};
+    use crate::protos::trace::{
+        trace::TraceFieldNumber, trace_packet::TracePacketFieldNumber,
+        track_event::track_event::TrackEventFieldNumber,
+    };
+    use crate::test_util::field;
+
+    fn packets(trace: &[u8]) -> Vec<&[u8]> {
+        PbDecoder::new(trace)
+            .map(|item| match item.unwrap() {
+                (id, PbDecoderField::Delimited(data)) if id == TraceFieldNumber::Packet as u32 => {
+                    data
+                }
+                other => panic!("unexpected field: {:?}", other),
+            })
+            .collect()
+    }
+
+    fn track_events(trace: &[u8]) -> Vec<(u64, u64)> {
+        packets(trace)
+            .into_iter()
+            .filter_map(|packet| {
+                let Some(PbDecoderField::Delimited(event)) =
+                    field(packet, TracePacketFieldNumber::TrackEvent as u32)
+                else {
+                    return None;
+                };
+                let Some(PbDecoderField::Varint(ts)) =
+                    field(packet, TracePacketFieldNumber::Timestamp as u32)
+                else {
+                    panic!("missing timestamp");
+                };
+                let Some(PbDecoderField::Varint(ty)) =
+                    field(event, TrackEventFieldNumber::Type as u32)
+                else {
+                    panic!("missing type");
+                };
+                Some((ts, ty))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_json() {
+        let value = JsonParser::new(r#"{"a": [1, -2.5, "x\né"], "b": true, "c": null}"#)
+            .parse_document()
+            .unwrap();
+        assert_eq!(
+            value.get("a"),
+            Some(&JsonValue::Array(vec![
+                JsonValue::Int(1),
+                JsonValue::Double(-2.5),
+                JsonValue::String("x\né".to_string()),
+            ]))
+        );
+        assert_eq!(value.get("b"), Some(&JsonValue::Bool(true)));
+        assert_eq!(value.get("c"), Some(&JsonValue::Null));
+    }
+
+    #[test]
+    fn invalid_json() {
+        assert_eq!(
+            import_chrome_json(r#"{"traceEvents": [}"#),
+            Err(ChromeJsonError::InvalidJson(17))
+        );
+        assert_eq!(
+            import_chrome_json(r#"{"foo": 1}"#),
+            Err(ChromeJsonError::NotATrace)
+        );
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(
+            JsonParser::new(&nested(MAX_JSON_DEPTH))
+                .parse_document()
+                .is_ok()
+        );
+        assert_eq!(
+            import_chrome_json(&nested(MAX_JSON_DEPTH + 1)),
+            Err(ChromeJsonError::TooDeep(MAX_JSON_DEPTH))
+        );
+    }
+
+    #[test]
+    fn sorted_slices() {
+        let trace = import_chrome_json(
+            r#"[{"ph": "X", "name": "a", "ts": 1, "dur": 1, "pid": 1, "tid": 1},
+                {"ph": "X", "name": "b", "ts": 2, "dur": 0, "pid": 1, "tid": 1},
+                {"ph": "X", "name": "c", "ts": 2, "dur": 1, "pid": 1, "tid": 1},
+                {"ph": "X", "name": "d", "ts": 2, "dur": 2, "pid": 1, "tid": 1},
+                {"ph": "X", "name": "e", "ts": 1e16, "dur": 1e16, "pid": 1, "tid": 1}]"#,
+        )
+        .unwrap();
+        let begin = TrackEventType::TypeSliceBegin as u64;
+        let end = TrackEventType::TypeSliceEnd as u64;
+        assert_eq!(
+            track_events(&trace),
+            vec![
+                (1000, begin),
+                (2000, end),
+                (2000, begin),
+                (2000, begin),
+                (2000, begin),
+                (2000, end),
+                (3000, end),
+                (4000, end),
+                (10_000_000_000_000_000_000, begin),
+                (u64::MAX, end),
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_duration_slices() {
+        let trace = import_chrome_json(
+            r#"[{"ph": "X", "name": "a", "ts": 0, "dur": 2, "pid": 1, "tid": 1},
+                {"ph": "B", "name": "b", "ts": 1, "pid": 1, "tid": 1},
+                {"ph": "E", "ts": 2, "pid": 1, "tid": 1},
+                {"ph": "B", "name": "c", "ts": 2, "pid": 1, "tid": 1},
+                {"ph": "E", "ts": 2, "pid": 1, "tid": 1},
+                {"ph": "b", "name": "d", "id": 1, "ts": 3, "pid": 1},
+                {"ph": "e", "name": "d", "id": 1, "ts": 3, "pid": 1},
+                {"ph": "S", "name": "e", "id": 2, "ts": 4, "pid": 1},
+                {"ph": "F", "name": "e", "id": 2, "ts": 4, "pid": 1}]"#,
+        )
+        .unwrap();
+        let begin = TrackEventType::TypeSliceBegin as u64;
+        let end = TrackEventType::TypeSliceEnd as u64;
+        assert_eq!(
+            track_events(&trace),
+            vec![
+                (0, begin),
+                (1000, begin),
+                (2000, end),
+                (2000, end),
+                (2000, begin),
+                (2000, end),
+                (3000, begin),
+                (3000, end),
+                (4000, begin),
+                (4000, end),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_array() {
+        let trace = import_chrome_json(
+            r#"[{"ph": "B", "name": "a", "ts": 1, "pid": 1, "tid": 1},
+                {"ph": "E", "ts": 2, "pid": 1, "tid": 1},"#,
+        )
+        .unwrap();
+        assert_eq!(
+            track_events(&trace),
+            vec![
+                (1000, TrackEventType::TypeSliceBegin as u64),
+                (2000, TrackEventType::TypeSliceEnd as u64),
+            ]
+        );
+    }
+
+    #[test]
+    fn import_events() {
+        let trace = import_chrome_json(
+            r#"{"traceEvents": [
+                {"ph": "M", "name": "process_name", "pid": 1, "args": {"name": "app"}},
+                {"ph": "X", "name": "a", "cat": "c", "ts": 1.5, "dur": 2, "pid": 1, "tid": 2,
+                 "args": {"n": 1, "o": {"k": [true]}}},
+                {"ph": "i", "name": "b", "s": "g", "ts": 4, "pid": 1, "tid": 2},
+                {"ph": "C", "name": "mem", "ts": 5, "pid": 1, "args": {"rss": 10, "pss": 2.5}},
+                {"ph": "b", "name": "req", "cat": "c", "id": "0x10", "ts": 6, "pid": 1},
+                {"ph": "e", "name": "req", "cat": "c", "id": "0x10", "ts": 7, "pid": 1},
+                {"ph": "s", "name": "ignored", "id": 1, "ts": 8, "pid": 1}
+            ], "displayTimeUnit": "ns"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            track_events(&trace),
+            vec![
+                (1500, TrackEventType::TypeSliceBegin as u64),
+                (3500, TrackEventType::TypeSliceEnd as u64),
+                (4000, TrackEventType::TypeInstant as u64),
+                (5000, TrackEventType::TypeCounter as u64),
+                (5000, TrackEventType::TypeCounter as u64),
+                (6000, TrackEventType::TypeSliceBegin as u64),
+                (7000, TrackEventType::TypeSliceEnd as u64),
+            ]
+        );
+
+        // Process, thread, two counters, one async and the global track.
+        let descriptors = packets(&trace)
+            .into_iter()
+            .filter(|p| field(p, TracePacketFieldNumber::TrackDescriptor as u32).is_some())
+            .count();
+        assert_eq!(descriptors, 6);
+
+        let first = packets(&trace)[0];
+        assert_eq!(
+            field(first, TracePacketFieldNumber::SequenceFlags as u32),
+            Some(PbDecoderField::Varint(
+                TracePacketSequenceFlags::SeqIncrementalStateCleared as u64
+            ))
+        );
+    }
+}
@@ -0,0 +1,292 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{DataSourceTimestamp, TraceContextBase},
+    protos::trace::{
+        clock_snapshot::{ClockSnapshot, ClockSnapshotClock},
+        trace_packet::TracePacket,
+    },
+};
+use std::{collections::VecDeque, time::Duration};
+
+/// Default period of [`ClockSync::sample_if_due`].
+pub const DEFAULT_SYNC_PERIOD: Duration = Duration::from_secs(1);
+
+/// Default number of samples used to estimate the drift of a device clock.
+pub const DEFAULT_SYNC_WINDOW: usize = 8;
+
+/// Device clock timestamp and trace clock timestamp taken at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+    /// Device clock timestamp, in nanoseconds.
+    pub device: u64,
+    /// Trace clock timestamp, in nanoseconds.
+    pub trace: u64,
+}
+
+/// Synchronizes a device clock, e.g. the timestamp counter of a GPU, with the
+/// trace clock (`CLOCK_BOOTTIME` on Linux).
+///
+/// Each sample reads the device clock between two reads of the trace clock.
+/// The recent samples are used to estimate the drift of the device clock,
+/// which [`to_trace_time`](Self::to_trace_time) takes into account when
+/// converting device timestamps. [`write_snapshot`](Self::write_snapshot)
+/// emits the latest sample as a `ClockSnapshot` so that packets can also use
+/// the device clock directly as their `timestamp_clock_id`; trace processor
+/// interpolates between snapshots.
+///
+/// `clock_id` must be a sequence-scoped clock id (64-127) if the snapshots
+/// are only written to the sequences that use the clock, or a global clock id
+/// (128 and up) otherwise.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     clock_sync::ClockSync, data_source::TraceContext, polling::PollingDataSourceBuilder,
+/// };
+/// use std::time::Duration;
+///
+/// fn read_gpu_clock() -> u64 {
+///     0
+/// }
+///
+/// let mut sync = ClockSync::new(128, read_gpu_clock).with_period(Duration::from_millis(500));
+/// let _data_source = PollingDataSourceBuilder::new()
+///     .register("com.example.gpu_clock", move |ctx: &mut TraceContext| {
+///         if sync.sample_if_due().is_some() {
+///             sync.write_snapshot(ctx);
+///         }
+///         let _trace_ts = sync.to_trace_time(read_gpu_clock());
+///     })
+///     .unwrap();
+/// ```
+pub struct ClockSync {
+    clock_id: u32,
+    device_clock: Box<dyn FnMut() -> u64 + Send>,
+    trace_clock_id: u32,
+    period: Duration,
+    window: usize,
+    samples: VecDeque<ClockSample>,
+}
+
+impl ClockSync {
+    /// Creates a helper for clock `clock_id`, read by `device_clock` in
+    /// nanoseconds.
+    pub fn new<F>(clock_id: u32, device_clock: F) -> Self
+    where
+        F: FnMut() -> u64 + Send + 'static,
+    {
+        Self {
+            clock_id,
+            device_clock: Box::new(device_clock),
+            trace_clock_id: DataSourceTimestamp::now().clock_id(),
+            period: DEFAULT_SYNC_PERIOD,
+            window: DEFAULT_SYNC_WINDOW,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Sets the period of [`sample_if_due`](Self::sample_if_due).
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn with_period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+
+    /// Sets the number of recent samples used to estimate the drift.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window.max(2);
+        self
+    }
+
+    /// Returns the clock id of the device clock.
+    pub fn clock_id(&self) -> u32 {
+        self.clock_id
+    }
+
+    /// Samples the device clock against the trace clock.
+    pub fn sample(&mut self) -> ClockSample {
+        let before = DataSourceTimestamp::now().timestamp();
+        let device = (self.device_clock)();
+        let after = DataSourceTimestamp::now().timestamp();
+        let sample = ClockSample {
+            device,
+            trace: before + (after.saturating_sub(before)) / 2,
+        };
+        // Start over if the device clock was reset.
+        if self.samples.back().is_some_and(|last| device < last.device) {
+            self.samples.clear();
+        }
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        sample
+    }
+
+    /// Samples the device clock if no sample was taken in the last period.
+    pub fn sample_if_due(&mut self) -> Option<ClockSample> {
+        if let Some(last) = self.samples.back() {
+            let elapsed = DataSourceTimestamp::now()
+                .timestamp()
+                .saturating_sub(last.trace);
+            if elapsed < self.period.as_nanos() as u64 {
+                return None;
+            }
+        }
+        Some(self.sample())
+    }
+
+    /// Returns the latest sample.
+    pub fn last_sample(&self) -> Option<ClockSample> {
+        self.samples.back().copied()
+    }
+
+    /// Returns the estimated rate of the trace clock relative to the device
+    /// clock, i.e. trace clock nanoseconds per device clock nanosecond.
+    pub fn rate(&self) -> f64 {
+        let (Some(first), Some(last)) = (self.samples.front(), self.samples.back()) else {
+            return 1.0;
+        };
+        if last.device == first.device {
+            return 1.0;
+        }
+        (last.trace - first.trace) as f64 / (last.device - first.device) as f64
+    }
+
+    /// Returns the estimated drift of the device clock, in parts per million.
+    /// Positive when the device clock runs slower than the trace clock.
+    pub fn drift_ppm(&self) -> f64 {
+        (self.rate() - 1.0) * 1e6
+    }
+
+    /// Converts the device timestamp `device_ts` to the trace clock, or
+    /// returns `None` if no sample was taken yet.
+    pub fn to_trace_time(&self, device_ts: u64) -> Option<u64> {
+        let last = self.samples.back()?;
+        let delta = (device_ts as i128 - last.device as i128) as f64 * self.rate();
+        let trace = last.trace as i128 + delta.round() as i128;
+        Some(trace.clamp(0, u64::MAX as i128) as u64)
+    }
+
+    /// Writes the latest sample as a `ClockSnapshot` packet. Does nothing if
+    /// no sample was taken yet.
+    pub fn write_snapshot(&self, ctx: &mut TraceContextBase) {
+        let Some(sample) = self.last_sample() else {
+            return;
+        };
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet.set_timestamp(sample.trace);
+            packet.set_clock_snapshot(|snapshot: &mut ClockSnapshot| {
+                snapshot.set_clocks(|clock: &mut ClockSnapshotClock| {
+                    clock.set_clock_id(self.clock_id);
+                    clock.set_timestamp(sample.device);
+                });
+                snapshot.set_clocks(|clock: &mut ClockSnapshotClock| {
+                    clock.set_clock_id(self.trace_clock_id);
+                    clock.set_timestamp(sample.trace);
+                });
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_source::TraceContext,
+        pb_decoder::PbDecoderField,
+        protos::trace::{
+            clock_snapshot::{ClockSnapshotClockFieldNumber, ClockSnapshotFieldNumber},
+            trace_packet::TracePacketFieldNumber,
+        },
+        test_util::fields,
+        test_util::{DATA_SOURCE_NAME, data_source},
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        trace_reader::TraceReader,
+    };
+    use std::error::Error;
+
+    #[test]
+    fn conversion() {
+        let mut sync = ClockSync::new(128, || 0);
+        assert_eq!(sync.to_trace_time(1000), None);
+        sync.samples.push_back(ClockSample {
+            device: 1000,
+            trace: 10_000,
+        });
+        assert_eq!(sync.rate(), 1.0);
+        assert_eq!(sync.to_trace_time(1500), Some(10_500));
+        sync.samples.push_back(ClockSample {
+            device: 2000,
+            trace: 12_000,
+        });
+        assert_eq!(sync.rate(), 2.0);
+        assert_eq!(sync.drift_ppm(), 1e6);
+        assert_eq!(sync.to_trace_time(3000), Some(14_000));
+        assert_eq!(sync.to_trace_time(1500), Some(11_000));
+        assert_eq!(sync.to_trace_time(0), Some(8_000));
+
+        // A device clock reset drops the previous samples.
+        sync.sample();
+        assert_eq!(sync.samples.len(), 1);
+        assert!(sync.sample_if_due().is_none());
+    }
+
+    #[test]
+    fn snapshot() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let mut sync = ClockSync::new(128, || 42);
+        let sample = sync.sample();
+        data_source.trace(|ctx: &mut TraceContext| sync.write_snapshot(ctx));
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        let mut clocks = vec![];
+        let packets = TraceReader::new(&data).collect::<Result<Vec<_>, _>>()?;
+        for packet in &packets {
+            for snapshot in fields(packet, TracePacketFieldNumber::ClockSnapshot as u32) {
+                let PbDecoderField::Delimited(snapshot) = snapshot else {
+                    panic!("unexpected snapshot {:?}", snapshot);
+                };
+                for clock in fields(snapshot, ClockSnapshotFieldNumber::Clocks as u32) {
+                    let PbDecoderField::Delimited(clock) = clock else {
+                        panic!("unexpected clock {:?}", clock);
+                    };
+                    let varint = |id| match fields(clock, id).first() {
+                        Some(PbDecoderField::Varint(value)) => *value,
+                        field => panic!("unexpected field {:?}", field),
+                    };
+                    clocks.push((
+                        varint(ClockSnapshotClockFieldNumber::ClockId as u32),
+                        varint(ClockSnapshotClockFieldNumber::Timestamp as u32),
+                    ));
+                }
+            }
+        }
+        // The service also writes snapshots of the builtin clocks.
+        assert!(clocks.contains(&(128, 42)));
+        assert!(clocks.contains(&(sync.trace_clock_id as u64, sample.trace)));
+        Ok(())
+    }
+}
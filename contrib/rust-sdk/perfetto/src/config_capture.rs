@@ -0,0 +1,308 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    instance_config::InstanceConfig,
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField, append_field},
+    protos::config::{
+        data_source_config::DataSourceConfigFieldNumber,
+        trace_config::{TraceConfigDataSourceFieldNumber, TraceConfigFieldNumber},
+    },
+    trace_config::TraceConfigBuilder,
+    tracing_session::{TracingSession, TracingSessionError},
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+
+/// Config capture and replay errors.
+#[derive(Error, Debug)]
+pub enum ConfigCaptureError {
+    /// The capture file couldn't be read.
+    #[error("Failed to read captured configs: {0}")]
+    Io(#[from] io::Error),
+    /// The capture file isn't an encoded `TraceConfig`.
+    #[error("Invalid captured configs: {0}")]
+    Decode(#[from] PbDecoderError),
+    /// The replay session couldn't be created.
+    #[error("Failed to create replay session: {0}")]
+    Session(#[from] TracingSessionError),
+    /// There is no captured config with this index.
+    #[error("No captured config {0}.")]
+    NoSuchConfig(usize),
+}
+
+// Appends `config` to `buffer` as the config of an entry of the
+// `data_sources` of a `TraceConfig`.
+fn append_data_source(buffer: &mut Vec<u8>, config: &[u8]) {
+    let mut data_source = Vec::with_capacity(config.len() + 8);
+    append_field(
+        &mut data_source,
+        TraceConfigDataSourceFieldNumber::Config as u32,
+        &PbDecoderField::Delimited(config),
+    );
+    append_field(
+        buffer,
+        TraceConfigFieldNumber::DataSources as u32,
+        &PbDecoderField::Delimited(&data_source),
+    );
+}
+
+/// Captures the configs delivered to the setup callback of a data source into
+/// a file, see
+/// [`DataSourceArgsBuilder::capture_configs`](crate::data_source::DataSourceArgsBuilder::capture_configs).
+///
+/// The file is an encoded `TraceConfig` whose `data_sources` are the captured
+/// configs, in the order they were delivered, so it can be inspected with
+/// `protoc --decode=perfetto.protos.TraceConfig`, and is replayed with
+/// [`CapturedConfigs`]. Configs are written as they are delivered, including
+/// the ones the setup callback rejects.
+#[derive(Clone)]
+pub struct ConfigCapture {
+    file: Arc<Mutex<BufWriter<File>>>,
+}
+
+impl ConfigCapture {
+    /// Creates the capture file `path`, truncating it if it exists.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            file: Arc::new(Mutex::new(BufWriter::new(File::create(path)?))),
+        })
+    }
+
+    // Appends the encoded `DataSourceConfig` `config` to the file. The file is
+    // flushed so that configs that crash the process are captured.
+    pub(crate) fn capture(&self, config: &[u8]) {
+        let mut record = Vec::new();
+        append_data_source(&mut record, config);
+        let mut file = self.file.lock().unwrap();
+        if let Err(err) = file.write_all(&record).and_then(|_| file.flush()) {
+            crate::sdk_log!(Warn, "Failed to capture data source config: {}", err);
+        }
+    }
+}
+
+/// Data source configs captured by a [`ConfigCapture`], to replay them
+/// against a data source in tests.
+///
+/// Each config is replayed in a session of its own with the in-process
+/// backend, so the data source registered under the name of the config goes
+/// through setup, start and stop with the exact config that was captured.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::config_capture::CapturedConfigs;
+///
+/// let configs = CapturedConfigs::load("configs.pb").unwrap();
+/// for index in 0..configs.configs().len() {
+///     let trace = configs.replay(index, || {
+///         // Trace from the data source while the instance is started.
+///     });
+///     assert!(trace.is_ok());
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CapturedConfigs {
+    configs: Vec<InstanceConfig>,
+}
+
+impl CapturedConfigs {
+    /// Loads the configs captured into the file `path`.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigCaptureError> {
+        Ok(Self::decode(&std::fs::read(path)?)?)
+    }
+
+    /// Decodes captured configs from the content of a capture file, or from
+    /// the `data_sources` of any encoded `TraceConfig`.
+    pub fn decode(data: &[u8]) -> Result<Self, PbDecoderError> {
+        let mut configs = Vec::new();
+        for item in PbDecoder::new(data) {
+            let (id, field) = item?;
+            let PbDecoderField::Delimited(data_source) = field else {
+                continue;
+            };
+            if id != TraceConfigFieldNumber::DataSources as u32 {
+                continue;
+            }
+            for item in PbDecoder::new(data_source) {
+                if let (id, PbDecoderField::Delimited(config)) = item?
+                    && id == TraceConfigDataSourceFieldNumber::Config as u32
+                {
+                    configs.push(InstanceConfig::decode(config)?);
+                }
+            }
+        }
+        Ok(Self { configs })
+    }
+
+    /// Returns the captured configs, in the order they were delivered.
+    pub fn configs(&self) -> &[InstanceConfig] {
+        &self.configs
+    }
+
+    /// Returns the encoded `TraceConfig` of a session with a single `size_kb`
+    /// buffer and the data source config `index`.
+    pub fn trace_config(&self, index: usize, size_kb: u32) -> Result<Vec<u8>, ConfigCaptureError> {
+        let captured = self
+            .configs
+            .get(index)
+            .ok_or(ConfigCaptureError::NoSuchConfig(index))?;
+        let mut config = captured.raw.clone();
+        // The captured config may target another buffer. The last value of a
+        // field wins, so the instance writes to the only buffer of the session.
+        append_field(
+            &mut config,
+            DataSourceConfigFieldNumber::TargetBuffer as u32,
+            &PbDecoderField::Varint(0),
+        );
+        let mut trace_config = TraceConfigBuilder::ring_buffer(size_kb)
+            .build()
+            .expect("config without triggers");
+        append_data_source(&mut trace_config, &config);
+        Ok(trace_config)
+    }
+
+    /// Replays the config `index` in an in-process session: sets up and
+    /// starts the session, calls `during` while the instance is started, and
+    /// returns the trace once the session is stopped.
+    ///
+    /// The data source must be registered under the name of the config.
+    pub fn replay<F>(&self, index: usize, during: F) -> Result<Vec<u8>, ConfigCaptureError>
+    where
+        F: FnOnce(),
+    {
+        let config = self.trace_config(index, 1024)?;
+        let mut session = TracingSession::in_process()?;
+        session.setup(&config);
+        session.start_blocking();
+        during();
+        session.stop_blocking();
+        let trace = Arc::new(Mutex::new(Vec::new()));
+        let trace_data = Arc::clone(&trace);
+        session.read_trace_blocking(move |data, _has_more| {
+            trace_data.lock().unwrap().extend_from_slice(data);
+        });
+        Ok(std::mem::take(&mut *trace.lock().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_source::{DataSource, DataSourceArgsBuilder, TraceContext},
+        protos::{
+            config::{
+                data_source_config::DataSourceConfig,
+                test_config::{TestConfig, TestConfigFieldNumber},
+            },
+            trace::{test_event::TestEvent, trace_packet::TracePacket},
+        },
+        tests::{TracingSessionBuilder, acquire_test_environment},
+        trace_reader::TraceReader,
+    };
+    use std::{error::Error, fs};
+
+    const DATA_SOURCE_NAME: &str = "com.example.captured_data_source";
+
+    // Returns the `seed` of the `TestConfig` of the encoded `DataSourceConfig`
+    // `config`, or zero.
+    fn seed(config: &[u8]) -> u64 {
+        PbDecoder::new(config)
+            .map(|item| item.unwrap())
+            .filter_map(|(id, field)| match field {
+                PbDecoderField::Delimited(test_config)
+                    if id == DataSourceConfigFieldNumber::ForTesting as u32 =>
+                {
+                    PbDecoder::new(test_config)
+                        .map(|item| item.unwrap())
+                        .find_map(|(id, field)| match field {
+                            PbDecoderField::Varint(seed)
+                                if id == TestConfigFieldNumber::Seed as u32 =>
+                            {
+                                Some(seed)
+                            }
+                            _ => None,
+                        })
+                }
+                _ => None,
+            })
+            .next()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn capture_and_replay() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let path = std::env::temp_dir().join(format!("config_capture_{}.pb", std::process::id()));
+        let capture = ConfigCapture::create(&path)?;
+        let setups = Arc::new(Mutex::new(Vec::new()));
+        let setup_configs = Arc::clone(&setups);
+        let mut data_source = DataSource::new();
+        data_source.register(
+            DATA_SOURCE_NAME,
+            DataSourceArgsBuilder::new()
+                .capture_configs(capture)
+                .on_setup(move |_inst_id, config, _args| {
+                    setup_configs.lock().unwrap().push(seed(config));
+                })
+                .build(),
+        )?;
+
+        let config = TraceConfigBuilder::ring_buffer(1024)
+            .data_source_with(DATA_SOURCE_NAME, |config: &mut DataSourceConfig| {
+                config.set_for_testing(|test_config: &mut TestConfig| {
+                    test_config.set_seed(1234);
+                });
+            })
+            .build()?;
+        let mut session = TracingSession::in_process()?;
+        session.setup(&config);
+        session.start_blocking();
+        session.stop_blocking();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        session.stop_blocking();
+
+        let captured = CapturedConfigs::load(&path)?;
+        assert_eq!(captured.configs().len(), 2);
+        assert_eq!(captured.configs()[0].name, DATA_SOURCE_NAME);
+        assert_eq!(seed(&captured.configs()[0].raw), 1234);
+        let trace = captured.replay(0, || {
+            data_source.trace(|ctx: &mut TraceContext| {
+                ctx.add_packet(|packet: &mut TracePacket| {
+                    packet.set_for_testing(|for_testing: &mut TestEvent| {
+                        for_testing.set_counter(1);
+                    });
+                });
+            });
+        })?;
+        assert!(TraceReader::new(&trace).count() > 0);
+        // The replayed config is delivered as it was captured.
+        assert_eq!(*setups.lock().unwrap(), vec![1234, 0, 1234]);
+        assert!(matches!(
+            captured.replay(2, || {}),
+            Err(ConfigCaptureError::NoSuchConfig(2))
+        ));
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
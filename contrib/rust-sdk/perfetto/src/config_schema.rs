@@ -0,0 +1,318 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField},
+    pb_descriptor::{FieldType, MessageDescriptor, PbMessage},
+    protos::common::{
+        data_source_descriptor::DataSourceConfigSchema,
+        descriptor::{
+            DescriptorProto, EnumDescriptorProto, EnumValueDescriptorProto, FieldDescriptorProto,
+            FieldDescriptorProtoLabel, FieldDescriptorProtoType,
+        },
+    },
+};
+use std::{cell::RefCell, collections::HashSet};
+
+/// Machine-readable description of the config that a data source
+/// understands: the `DataSourceConfig` field that carries it, and the names,
+/// types and defaults of its fields.
+///
+/// The schema is published in the `DataSourceDescriptor` of the data source
+/// when registered with
+/// [`DataSourceArgsBuilder::config_schema`](crate::data_source::DataSourceArgsBuilder::config_schema),
+/// so that recording tools can render a config form for it. The config
+/// message is described as a `DescriptorProto`, in which nested messages and
+/// enums are declared as nested types and defaults are set in the
+/// `default_value` of the fields.
+#[derive(Debug, Clone)]
+pub struct ConfigSchema {
+    field_id: u32,
+    descriptor: &'static MessageDescriptor,
+    defaults: Vec<(u32, String)>,
+}
+
+impl ConfigSchema {
+    /// Creates the schema of the config message described by `descriptor`,
+    /// carried by the `DataSourceConfig` field `field_id`.
+    pub fn new(field_id: u32, descriptor: &'static MessageDescriptor) -> Self {
+        Self {
+            field_id,
+            descriptor,
+            defaults: Vec::new(),
+        }
+    }
+
+    /// Creates the schema of the config message `T`, defined with
+    /// [`pb_msg!`](crate::pb_msg), carried by the `DataSourceConfig` field
+    /// `field_id`.
+    pub fn of<T: PbMessage>(field_id: u32) -> Self {
+        Self::new(field_id, T::descriptor())
+    }
+
+    /// Sets the default value of field `number`, in the text format of the
+    /// `default_value` of a `FieldDescriptorProto`, e.g. `true` or `100`.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn default_value(mut self, number: u32, value: impl ToString) -> Self {
+        self.defaults.retain(|(field, _)| *field != number);
+        self.defaults.push((number, value.to_string()));
+        self
+    }
+
+    /// Returns the number of the `DataSourceConfig` field that carries the
+    /// config.
+    pub fn field_id(&self) -> u32 {
+        self.field_id
+    }
+
+    /// Returns the descriptor of the config message.
+    pub fn descriptor(&self) -> &'static MessageDescriptor {
+        self.descriptor
+    }
+
+    /// Returns the default value of field `number`, if set.
+    pub fn default_of(&self, number: u32) -> Option<&str> {
+        self.defaults
+            .iter()
+            .find(|(field, _)| *field == number)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the config carried by the encoded `DataSourceConfig`
+    /// `config`, if any.
+    pub fn extract<'a>(&self, config: &'a [u8]) -> Result<Option<&'a [u8]>, PbDecoderError> {
+        for item in PbDecoder::new(config) {
+            if let (id, PbDecoderField::Delimited(value)) = item?
+                && id == self.field_id
+            {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    pub(crate) fn write(&self, schema: &mut DataSourceConfigSchema) {
+        schema.set_field_id(self.field_id);
+        schema.set_message(|message: &mut DescriptorProto| {
+            let declared = RefCell::new(HashSet::from([self.descriptor.name]));
+            write_message(message, self.descriptor, Some(self), &declared);
+        });
+    }
+}
+
+fn write_message(
+    message: &mut DescriptorProto,
+    descriptor: &'static MessageDescriptor,
+    schema: Option<&ConfigSchema>,
+    declared: &RefCell<HashSet<&'static str>>,
+) {
+    message.set_name(descriptor.name);
+    for field in descriptor.fields {
+        let (field_type, type_name) = match field.field_type {
+            FieldType::Bool => (FieldDescriptorProtoType::TypeBool, None),
+            FieldType::I32 => (FieldDescriptorProtoType::TypeInt32, None),
+            FieldType::I64 => (FieldDescriptorProtoType::TypeInt64, None),
+            FieldType::U32 => (FieldDescriptorProtoType::TypeUint32, None),
+            FieldType::U64 => (FieldDescriptorProtoType::TypeUint64, None),
+            FieldType::F32 => (FieldDescriptorProtoType::TypeFloat, None),
+            FieldType::F64 => (FieldDescriptorProtoType::TypeDouble, None),
+            FieldType::String => (FieldDescriptorProtoType::TypeString, None),
+            FieldType::Bytes => (FieldDescriptorProtoType::TypeBytes, None),
+            FieldType::Enum(descriptor) => {
+                (FieldDescriptorProtoType::TypeEnum, Some(descriptor().name))
+            }
+            FieldType::Message(descriptor) | FieldType::Map(descriptor) => (
+                FieldDescriptorProtoType::TypeMessage,
+                Some(descriptor().name),
+            ),
+        };
+        message.set_field(|proto: &mut FieldDescriptorProto| {
+            proto
+                .set_name(field.name)
+                .set_number(field.number as i32)
+                .set_label(FieldDescriptorProtoLabel::LabelOptional)
+                .set_type(field_type);
+            if let Some(type_name) = type_name {
+                proto.set_type_name(type_name);
+            }
+            if let Some(value) = schema.and_then(|schema| schema.default_of(field.number)) {
+                proto.set_default_value(value);
+            }
+        });
+    }
+    // Nested messages and enums are declared once, in the message that uses
+    // them first, as the types are referred to by name.
+    for field in descriptor.fields {
+        match field.field_type {
+            FieldType::Enum(descriptor) if declared.borrow_mut().insert(descriptor().name) => {
+                let descriptor = descriptor();
+                message.set_enum_type(|proto: &mut EnumDescriptorProto| {
+                    proto.set_name(descriptor.name);
+                    for (name, number) in descriptor.values {
+                        proto.set_value(|value: &mut EnumValueDescriptorProto| {
+                            value.set_name(name).set_number(*number as i32);
+                        });
+                    }
+                });
+            }
+            FieldType::Message(nested) | FieldType::Map(nested)
+                if declared.borrow_mut().insert(nested().name) =>
+            {
+                message.set_nested_type(|proto: &mut DescriptorProto| {
+                    write_message(proto, nested(), None, declared);
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Config of a data source, decoded from a field of the `DataSourceConfig`
+/// of its instances, with a [`ConfigSchema`] that describes it.
+///
+/// Usually implemented with `#[derive(DataSourceConfig)]` from the
+/// `perfetto-sdk-derive` crate, on a struct whose fields are primitives or
+/// strings, which also generates the defaults of the schema from the
+/// `Default` implementation of the struct.
+pub trait DataSourceConfig: Default + Sized {
+    /// Number of the `DataSourceConfig` field that carries the config.
+    const FIELD_ID: u32;
+
+    /// Returns the schema of the config.
+    fn config_schema() -> ConfigSchema;
+
+    /// Decodes the encoded config message `data`. Fields that aren't set keep
+    /// their default value.
+    fn decode(data: &[u8]) -> Result<Self, PbDecoderError>;
+
+    /// Decodes the config carried by the encoded `DataSourceConfig` `config`,
+    /// e.g. the config passed to the `on_setup` callback, or returns the
+    /// default config if the field isn't set.
+    fn from_data_source_config(config: &[u8]) -> Result<Self, PbDecoderError> {
+        for item in PbDecoder::new(config) {
+            if let (id, PbDecoderField::Delimited(value)) = item?
+                && id == Self::FIELD_ID
+            {
+                return Self::decode(value);
+            }
+        }
+        Ok(Self::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        heap_buffer::HeapBuffer,
+        pb_msg::{PbMsg, PbMsgWriter},
+        protos::common::descriptor::{DescriptorProtoFieldNumber, FieldDescriptorProtoFieldNumber},
+    };
+
+    #[allow(dead_code)]
+    mod sampler {
+        use crate::{pb_enum, pb_msg};
+
+        pb_enum!(SampleMode {
+            SAMPLE_MODE_FAST: 1,
+            SAMPLE_MODE_EXACT: 2,
+        });
+
+        pb_msg!(SamplerConfig {
+            period_ms: u32, primitive, 1,
+            mode: SampleMode, enum, 2,
+            label: String, primitive, 3,
+        });
+    }
+    use sampler::SamplerConfig;
+
+    // Returns the fields of the encoded `DescriptorProto` `data` as (name,
+    // type, type name, default value) tuples.
+    fn fields(data: &[u8]) -> Vec<(String, u64, String, String)> {
+        PbDecoder::new(data)
+            .map(|item| item.unwrap())
+            .filter(|(id, _)| *id == DescriptorProtoFieldNumber::Field as u32)
+            .map(|(_, field)| {
+                let mut decoded = (String::new(), 0, String::new(), String::new());
+                for item in PbDecoder::new(field.as_bytes().unwrap()) {
+                    let (id, value) = item.unwrap();
+                    let text = || String::from_utf8(value.as_bytes().unwrap().to_vec()).unwrap();
+                    match id {
+                        id if id == FieldDescriptorProtoFieldNumber::Name as u32 => {
+                            decoded.0 = text()
+                        }
+                        id if id == FieldDescriptorProtoFieldNumber::Type as u32 => {
+                            let PbDecoderField::Varint(field_type) = value else {
+                                panic!("unexpected type {:?}", value);
+                            };
+                            decoded.1 = field_type;
+                        }
+                        id if id == FieldDescriptorProtoFieldNumber::TypeName as u32 => {
+                            decoded.2 = text()
+                        }
+                        id if id == FieldDescriptorProtoFieldNumber::DefaultValue as u32 => {
+                            decoded.3 = text()
+                        }
+                        _ => {}
+                    }
+                }
+                decoded
+            })
+            .collect()
+    }
+
+    #[test]
+    fn write_schema() {
+        let schema = ConfigSchema::of::<SamplerConfig<'_, '_>>(1500)
+            .default_value(1, 100)
+            .default_value(3, "main");
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        schema.write(&mut DataSourceConfigSchema { msg: &mut msg });
+        msg.finalize();
+        let mut data = vec![0u8; writer.writer.get_written_size()];
+        hb.copy_into(&mut data);
+
+        let items: Vec<_> = PbDecoder::new(&data).map(|item| item.unwrap()).collect();
+        assert!(matches!(items[0], (1, PbDecoderField::Varint(1500))));
+        let (2, PbDecoderField::Delimited(message)) = items[1] else {
+            panic!("unexpected field {:?}", items[1]);
+        };
+        assert_eq!(
+            fields(message),
+            vec![
+                (
+                    "period_ms".to_string(),
+                    13,
+                    String::new(),
+                    "100".to_string()
+                ),
+                (
+                    "mode".to_string(),
+                    14,
+                    "SampleMode".to_string(),
+                    String::new()
+                ),
+                ("label".to_string(), 9, String::new(), "main".to_string()),
+            ]
+        );
+        // The enum is declared in the config message.
+        assert!(
+            PbDecoder::new(message)
+                .map(|item| item.unwrap())
+                .any(|(id, _)| { id == DescriptorProtoFieldNumber::EnumType as u32 })
+        );
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{protos::trace::track_event::track_event::TrackEvent, track_event::TrackEventCounter};
+
+/// Value of a counter, written as an integer or a double depending on the
+/// type it was converted from.
+///
+/// Integers are kept as integers, so that large values don't silently lose
+/// precision by being written as doubles. Only `u64` and `usize` values above
+/// `i64::MAX`, which don't fit the integer field, are written as doubles.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::counter_value::CounterValue;
+///
+/// assert_eq!(CounterValue::from(42u32), CounterValue::Int(42));
+/// assert_eq!(CounterValue::from(0.5f32), CounterValue::Double(0.5));
+/// assert_eq!(CounterValue::from(u64::MAX), CounterValue::Double(u64::MAX as f64));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CounterValue {
+    /// Integer counter value.
+    Int(i64),
+    /// Double counter value.
+    Double(f64),
+}
+
+macro_rules! impl_from_int {
+    ($($tp:ty),*) => {
+        $(
+            impl From<$tp> for CounterValue {
+                fn from(value: $tp) -> Self {
+                    Self::Int(value.into())
+                }
+            }
+        )*
+    };
+}
+
+impl_from_int!(i8, i16, i32, i64, u8, u16, u32);
+
+macro_rules! impl_try_from_int {
+    ($($tp:ty),*) => {
+        $(
+            impl From<$tp> for CounterValue {
+                fn from(value: $tp) -> Self {
+                    match i64::try_from(value) {
+                        Ok(value) => Self::Int(value),
+                        Err(_) => Self::Double(value as f64),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_int!(u64, usize, isize);
+
+impl From<f32> for CounterValue {
+    fn from(value: f32) -> Self {
+        Self::Double(value.into())
+    }
+}
+
+impl From<f64> for CounterValue {
+    fn from(value: f64) -> Self {
+        Self::Double(value)
+    }
+}
+
+impl From<TrackEventCounter> for CounterValue {
+    fn from(value: TrackEventCounter) -> Self {
+        match value {
+            TrackEventCounter::Int64(value) => Self::Int(value),
+            TrackEventCounter::Double(value) => Self::Double(value),
+        }
+    }
+}
+
+impl From<CounterValue> for TrackEventCounter {
+    fn from(value: CounterValue) -> Self {
+        match value {
+            CounterValue::Int(value) => Self::Int64(value),
+            CounterValue::Double(value) => Self::Double(value),
+        }
+    }
+}
+
+impl TrackEvent<'_, '_> {
+    /// Set the `counter_value` or `double_counter_value` field, depending on
+    /// the type of `value`.
+    pub fn set_counter(&mut self, value: impl Into<CounterValue>) -> &mut Self {
+        match value.into() {
+            CounterValue::Int(value) => self.set_counter_value(value),
+            CounterValue::Double(value) => self.set_double_counter_value(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        heap_buffer::HeapBuffer,
+        pb_decoder::{PbDecoder, PbDecoderField},
+        pb_msg::{PbMsg, PbMsgWriter},
+        protos::trace::track_event::track_event::TrackEventFieldNumber,
+    };
+
+    #[test]
+    fn from() {
+        assert_eq!(CounterValue::from(-1i8), CounterValue::Int(-1));
+        assert_eq!(CounterValue::from(i64::MIN), CounterValue::Int(i64::MIN));
+        assert_eq!(
+            CounterValue::from(u32::MAX),
+            CounterValue::Int(u32::MAX.into())
+        );
+        assert_eq!(CounterValue::from(1u64 << 62), CounterValue::Int(1 << 62));
+        assert_eq!(
+            CounterValue::from(i64::MAX as u64),
+            CounterValue::Int(i64::MAX)
+        );
+        assert_eq!(
+            CounterValue::from(i64::MAX as u64 + 1),
+            CounterValue::Double(9223372036854775808.0)
+        );
+        assert_eq!(CounterValue::from(-3isize), CounterValue::Int(-3));
+        assert_eq!(CounterValue::from(1.5f64), CounterValue::Double(1.5));
+        assert_eq!(
+            CounterValue::from(TrackEventCounter::Int64(7)),
+            CounterValue::Int(7)
+        );
+    }
+
+    #[test]
+    fn set_counter() {
+        // Larger than 2^53, so not representable as a double.
+        const VALUE: u64 = (1 << 60) + 1;
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        {
+            let mut event = TrackEvent { msg: &mut msg };
+            event.set_counter(VALUE).set_counter(0.25f64);
+        }
+        msg.finalize();
+        let mut data = vec![0u8; writer.writer.get_written_size()];
+        hb.copy_into(&mut data);
+        let fields: Vec<_> = PbDecoder::new(&data).map(|f| f.unwrap()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                (
+                    TrackEventFieldNumber::CounterValue as u32,
+                    PbDecoderField::Varint(VALUE)
+                ),
+                (
+                    TrackEventFieldNumber::DoubleCounterValue as u32,
+                    PbDecoderField::Fixed64(0.25f64.to_bits())
+                ),
+            ]
+        );
+    }
+}
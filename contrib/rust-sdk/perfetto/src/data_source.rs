@@ -13,26 +13,37 @@
 // limitations under the License.
 
 use crate::{
+    byte_budget::{Admission, BudgetState, ByteBudget},
+    config_capture::ConfigCapture,
+    config_schema::ConfigSchema,
     heap_buffer::HeapBuffer,
+    instance_config::InstanceConfig,
     pb_msg::{PbMsg, PbMsgWriter},
     protos::{
-        common::data_source_descriptor::DataSourceDescriptor, trace::trace_packet::TracePacket,
+        common::data_source_descriptor::{DataSourceConfigSchema, DataSourceDescriptor},
+        trace::{
+            trace_packet::{TracePacket, TracePacketDataLossReason},
+            track_event::{thread_descriptor::ThreadDescriptor, track_descriptor::TrackDescriptor},
+        },
     },
     stream_writer::StreamWriter,
+    track_event::TrackEventTrack,
+    watchdog::{Watchdog, WatchdogGuard},
 };
 use perfetto_sdk_sys::*;
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::HashMap,
     default::Default,
+    ffi::CString,
     marker::PhantomData,
     os::raw::c_void,
     ptr,
     sync::{
-        Mutex, OnceLock,
-        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock, PoisonError, RwLock, RwLockReadGuard, TryLockError,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 use thiserror::Error;
 
@@ -45,6 +56,22 @@ pub enum DataSourceError {
     /// Unknown error occured when trying to register data source.
     #[error("Failed to register data source.")]
     RegisterError,
+    /// A plugin has already registered a data source type with this name,
+    /// see [`PluginRegistrar::register`](crate::plugin::PluginRegistrar::register).
+    #[cfg(feature = "plugin")]
+    #[error("Data source {0} has already been registered by a plugin.")]
+    PluginNameTakenError(String),
+}
+
+/// Errors returned by the setup callback to reject a data source instance.
+#[derive(Error, Debug, PartialEq)]
+pub enum SetupError {
+    /// The config of the instance is malformed.
+    #[error("Invalid data source config: {0}.")]
+    InvalidConfig(String),
+    /// The config of the instance requests something that is not supported.
+    #[error("Unsupported data source config: {0}.")]
+    Unsupported(String),
 }
 
 /// Opaque handle used to perform operations from the OnSetup callback. Unused
@@ -53,7 +80,21 @@ pub struct OnSetupArgs {
     _args: *mut PerfettoDsOnSetupArgs,
 }
 
-type OnSetupCallback = Box<dyn FnMut(u32, &[u8], &mut OnSetupArgs) + Send + Sync + 'static>;
+impl OnSetupArgs {
+    // Reports to the tracing service that the instance failed to set up.
+    fn set_error(&mut self, error: &str) {
+        if self._args.is_null() {
+            return;
+        }
+        let error = CString::new(error.replace('\0', " ")).unwrap();
+        // SAFETY: `_args` is the non-null handle passed to the setup callback,
+        // which is still running, and `error` is a null-terminated string.
+        unsafe { PerfettoDsOnSetupArgsSetError(self._args, error.as_ptr()) };
+    }
+}
+
+type OnSetupCallback =
+    Box<dyn FnMut(u32, &[u8], &mut OnSetupArgs) -> Result<(), SetupError> + Send + Sync + 'static>;
 
 /// Opaque handle used to perform operations from the OnSetup callback. Unused
 /// for now.
@@ -68,10 +109,22 @@ type OnStartCallback = Box<dyn FnMut(u32, &mut OnStartArgs) + Send + Sync + 'sta
 #[must_use = "dropping StopGuard immediately defeats its purpose"]
 pub struct StopGuard {
     async_stopper: *mut PerfettoDsAsyncStopper,
+    deadline: StopDeadline,
+}
+
+impl StopGuard {
+    /// Returns the time left to signal the stop before the deadline, see
+    /// [`OnStopArgs::deadline`].
+    pub fn remaining(&self) -> Duration {
+        self.deadline
+            .instant
+            .saturating_duration_since(Instant::now())
+    }
 }
 
 impl Drop for StopGuard {
     fn drop(&mut self) {
+        self.deadline.check();
         // SAFETY: `self.async_stopper` must have been created using
         // `PerfettoDsOnStopArgsPostpone`.
         unsafe {
@@ -86,9 +139,42 @@ unsafe impl Send for StopGuard {}
 // SAFETY: The underlying PerfettoDsAsyncStopper is thread-safe.
 unsafe impl Sync for StopGuard {}
 
+/// Time the tracing service waits for an instance to stop when the trace
+/// config doesn't set `data_source_stop_timeout_ms`.
+pub const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Deadline of the stop of an instance, after which a warning is logged and the
+// stop is counted as slow.
+#[derive(Clone)]
+struct StopDeadline {
+    inst_id: u32,
+    start: Instant,
+    instant: Instant,
+    stats: Arc<DsStatsCounters>,
+}
+
+impl StopDeadline {
+    fn check(&self) {
+        let now = Instant::now();
+        if now > self.instant {
+            self.stats.slow_stops.fetch_add(1, Ordering::Relaxed);
+            crate::sdk_log!(
+                Warn,
+                "Data source instance {} took {:?} to stop, more than its stop timeout of {:?}; \
+                 packets written after the timeout may be lost",
+                self.inst_id,
+                now - self.start,
+                self.instant - self.start,
+            );
+        }
+    }
+}
+
 /// Opaque handle used to perform operations from the OnStop callback.
 pub struct OnStopArgs {
     args: *mut PerfettoDsOnStopArgs,
+    deadline: StopDeadline,
+    postponed: bool,
 }
 
 impl OnStopArgs {
@@ -100,12 +186,43 @@ impl OnStopArgs {
         assert!(!self.args.is_null());
         // SAFETY: `self.args` must be pointing to a valid PerfettoDsOnStopArgs handle.
         let async_stopper = unsafe { PerfettoDsOnStopArgsPostpone(self.args) };
-        StopGuard { async_stopper }
+        self.postponed = true;
+        StopGuard {
+            async_stopper,
+            deadline: self.deadline.clone(),
+        }
+    }
+
+    /// Returns the time by which the instance must be stopped, including a
+    /// postponed stop.
+    ///
+    /// This is the stop timeout declared with
+    /// [`DataSourceArgsBuilder::stop_timeout`], or else the stop timeout of
+    /// the tracing session, from the start of the stop. Stopping later logs a
+    /// warning and is counted in [`DataSourceStats::slow_stops`].
+    pub fn deadline(&self) -> Instant {
+        self.deadline.instant
     }
 }
 
 type OnStopCallback = Box<dyn FnMut(u32, &mut OnStopArgs) + Send + Sync + 'static>;
 
+/// Lifecycle state of a data source instance, see
+/// [`DataSourceArgsBuilder::on_state_change`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceState {
+    /// The instance is set up but not started, so `DataSource::trace()` skips
+    /// it. The instances of a session started by a `START_TRACING` trigger stay
+    /// in this state until the trigger is activated.
+    SetUp,
+    /// The instance is started and `DataSource::trace()` writes to it.
+    Started,
+    /// The instance is stopping.
+    Stopped,
+}
+
+type OnStateChangeCallback = Box<dyn FnMut(u32, InstanceState) + Send + Sync + 'static>;
+
 /// A scope-based guard to signal that the data source flush operation is
 /// complete when dropped.
 #[must_use = "dropping FlushGuard immediately defeats its purpose"]
@@ -145,15 +262,35 @@ impl OnFlushArgs {
         let async_flusher = unsafe { PerfettoDsOnFlushArgsPostpone(self.args) };
         FlushGuard { async_flusher }
     }
+
+    /// Returns true if the tracing service requested the flush because it
+    /// started cloning the tracing session of the instance, see
+    /// [`DataSourceArgsBuilder::on_clone_started`].
+    pub fn is_clone(&mut self) -> bool {
+        assert!(!self.args.is_null());
+        // SAFETY: `self.args` must be pointing to a valid PerfettoDsOnFlushArgs handle.
+        unsafe { PerfettoDsOnFlushArgsIsClone(self.args) }
+    }
 }
 
 type OnFlushCallback = Box<dyn FnMut(u32, &mut OnFlushArgs) + Send + Sync + 'static>;
 
-/// Data source buffer exhausted policy.
+type OnCloneStartedCallback = Box<dyn FnMut(u32, &mut OnFlushArgs) + Send + Sync + 'static>;
+
+type OnClearIncrementalStateCallback = Box<dyn Fn(u32) + Send + Sync + 'static>;
+
+/// Data source buffer exhausted policy, i.e. what trace calls do when the
+/// shared memory buffer is full.
+///
+/// The outcome of each trace call is returned by [`DataSource::trace`], so that
+/// dropped data can be counted. [`DataSource::trace_with_timeout`] overrides
+/// the policy for a single call, to block up to a timeout chosen by the caller
+/// and then drop data.
 #[derive(Default, PartialEq)]
 pub enum DataSourceBufferExhaustedPolicy {
     /// If the data source runs out of space when trying to acquire a new chunk,
-    /// it will drop data.
+    /// it will drop data. Trace calls never block, which is required by
+    /// real-time threads.
     #[default]
     Drop,
     /// If the data source runs out of space when trying to acquire a new chunk,
@@ -185,12 +322,231 @@ impl ToDsBufferExhaustedPolicy for DataSourceBufferExhaustedPolicy {
     }
 }
 
+// Callbacks of the instances, called on the service thread.
 #[derive(Default)]
-struct DsCallbacks {
+struct InstanceCallbacks {
     on_setup: Option<OnSetupCallback>,
     on_start: Option<OnStartCallback>,
     on_stop: Option<OnStopCallback>,
     on_flush: Option<OnFlushCallback>,
+    on_clone_started: Option<OnCloneStartedCallback>,
+    on_state_change: Option<OnStateChangeCallback>,
+}
+
+// Shared with the trampolines, which only take shared references to it: the
+// incremental state of a writer is cleared on the tracing thread that calls
+// `trace()`, concurrently with the callbacks of the instances.
+#[derive(Default)]
+struct DsCallbacks {
+    instance: Mutex<InstanceCallbacks>,
+    on_clear_incremental_state: RwLock<Option<OnClearIncrementalStateCallback>>,
+    // Bitmask of the instances rejected by `on_setup`.
+    rejected_instances: Arc<AtomicU32>,
+    // Bitmask of the started instances.
+    started_instances: Arc<AtomicU32>,
+    stats: Arc<DsStatsCounters>,
+    sessions: Arc<InstanceSessions>,
+    // Time the data source declared that it needs to stop.
+    stop_timeout: Option<Duration>,
+    // Set once the data source is retired. Held for reading while callbacks
+    // run, so that retiring waits for them to return.
+    retired: Arc<RwLock<bool>>,
+    watchdog: Option<Watchdog>,
+    config_capture: Option<ConfigCapture>,
+    byte_budget: Option<ByteBudget>,
+    // Name of the data source type, set when registered.
+    name: String,
+}
+
+impl DsCallbacks {
+    // Watches the `callback` of the instance `inst_id` while the returned
+    // guard is alive, if the data source has a watchdog.
+    fn watch(&self, callback: &str, inst_id: u32) -> Option<WatchdogGuard> {
+        let watchdog = self.watchdog?;
+        let stats = Arc::clone(&self.stats);
+        Some(watchdog.arm(
+            format!(
+                "The {} callback of instance {} of data source {}",
+                callback, inst_id, self.name
+            ),
+            move || {
+                stats.slow_callbacks.fetch_add(1, Ordering::Relaxed);
+            },
+        ))
+    }
+
+    // Returns the stop timeout of the tracing session of `inst_id`.
+    fn session_stop_timeout(&self, inst_id: u32) -> Duration {
+        match self.sessions.config(inst_id) {
+            Some(config) if config.stop_timeout_ms > 0 => {
+                Duration::from_millis(config.stop_timeout_ms.into())
+            }
+            _ => DEFAULT_STOP_TIMEOUT,
+        }
+    }
+
+    fn is_rejected(&self, inst_id: u32) -> bool {
+        is_instance_rejected(&self.rejected_instances, inst_id)
+    }
+
+    fn set_rejected(&self, inst_id: u32, rejected: bool) {
+        let Some(bit) = 1u32.checked_shl(inst_id) else {
+            return;
+        };
+        if rejected {
+            self.rejected_instances.fetch_or(bit, Ordering::Relaxed);
+        } else {
+            self.rejected_instances.fetch_and(!bit, Ordering::Relaxed);
+        }
+    }
+
+    fn set_state(&self, instance: &mut InstanceCallbacks, inst_id: u32, state: InstanceState) {
+        if let Some(bit) = 1u32.checked_shl(inst_id) {
+            if state == InstanceState::Started {
+                self.started_instances.fetch_or(bit, Ordering::Relaxed);
+            } else {
+                self.started_instances.fetch_and(!bit, Ordering::Relaxed);
+            }
+        }
+        if let Some(f) = &mut instance.on_state_change {
+            f(inst_id, state);
+        }
+    }
+}
+
+fn is_instance_rejected(rejected_instances: &AtomicU32, inst_id: u32) -> bool {
+    1u32.checked_shl(inst_id)
+        .is_some_and(|bit| rejected_instances.load(Ordering::Relaxed) & bit != 0)
+}
+
+thread_local! {
+    // Addresses of the `retired` locks of the data source types whose
+    // callbacks are running on the thread, see `RetiredGuard`.
+    static RUNNING_CALLBACKS: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+// Holds the `retired` lock of a data source type for reading while one of its
+// callbacks runs on the thread, and records it so that retiring the type from
+// the callback panics instead of deadlocking.
+struct RetiredGuard<'a> {
+    retired: RwLockReadGuard<'a, bool>,
+    key: usize,
+}
+
+impl<'a> RetiredGuard<'a> {
+    fn new(retired: &'a Arc<RwLock<bool>>) -> Self {
+        let key = Arc::as_ptr(retired) as usize;
+        RUNNING_CALLBACKS.with(|running| running.borrow_mut().push(key));
+        Self {
+            retired: retired.read().unwrap(),
+            key,
+        }
+    }
+
+    fn is_retired(&self) -> bool {
+        *self.retired
+    }
+}
+
+impl Drop for RetiredGuard<'_> {
+    fn drop(&mut self) {
+        RUNNING_CALLBACKS.with(|running| {
+            let mut running = running.borrow_mut();
+            if let Some(pos) = running.iter().rposition(|key| *key == self.key) {
+                running.remove(pos);
+            }
+        });
+    }
+}
+
+// Returns true if one of the callbacks of the data source type that owns
+// `retired` is running on the thread.
+fn is_running_callback(retired: &Arc<RwLock<bool>>) -> bool {
+    let key = Arc::as_ptr(retired) as usize;
+    RUNNING_CALLBACKS.with(|running| running.borrow().contains(&key))
+}
+
+/// Tracing session of a data source instance, from the config of the instance.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SessionInfo {
+    /// Unique id of the tracing session, assigned by the tracing service.
+    pub tracing_session_id: u64,
+    /// Index of the trace buffer of the session that the instance writes to.
+    pub target_buffer: u32,
+}
+
+// Maximum number of concurrent instances of a data source type.
+const MAX_DATA_SOURCE_INSTANCES: usize = 8;
+
+// Sessions and configs of the instances of a data source type, set up by
+// `on_setup` and cleared when the instance is destroyed.
+#[derive(Default)]
+struct InstanceSessions {
+    tracing_session_ids: [AtomicU64; MAX_DATA_SOURCE_INSTANCES],
+    target_buffers: [AtomicU32; MAX_DATA_SOURCE_INSTANCES],
+    budgets: [BudgetState; MAX_DATA_SOURCE_INSTANCES],
+    configs: [RwLock<Option<Arc<InstanceConfig>>>; MAX_DATA_SOURCE_INSTANCES],
+}
+
+impl InstanceSessions {
+    fn set(&self, inst_id: u32, config: &[u8]) -> Option<Arc<InstanceConfig>> {
+        let index = inst_id as usize;
+        if index >= MAX_DATA_SOURCE_INSTANCES {
+            return None;
+        }
+        // The config is produced by the tracing service, so decoding can only
+        // fail for fields that aren't used here.
+        let config = Arc::new(InstanceConfig::decode(config).ok()?);
+        self.tracing_session_ids[index].store(config.tracing_session_id, Ordering::Relaxed);
+        self.target_buffers[index].store(config.target_buffer, Ordering::Relaxed);
+        *self.configs[index].write().unwrap() = Some(Arc::clone(&config));
+        Some(config)
+    }
+
+    // Clears the session of `inst_id` if it's still the one set up with
+    // `config`, and not the one of a newer instance with the same index.
+    fn clear(&self, inst_id: u32, config: &Arc<InstanceConfig>) {
+        let Some(slot) = self.configs.get(inst_id as usize) else {
+            return;
+        };
+        let mut slot = slot.write().unwrap();
+        if slot
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, config))
+        {
+            *slot = None;
+            self.tracing_session_ids[inst_id as usize].store(0, Ordering::Relaxed);
+            self.target_buffers[inst_id as usize].store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn config(&self, inst_id: u32) -> Option<Arc<InstanceConfig>> {
+        self.configs.get(inst_id as usize)?.read().unwrap().clone()
+    }
+
+    fn reset_budget(&self, inst_id: u32, budget: &ByteBudget, config: &[u8]) {
+        if let Some(state) = self.budgets.get(inst_id as usize) {
+            state.reset(budget, config);
+        }
+    }
+
+    // Returns the budget state of `inst_id`, or null if out of range.
+    fn budget(&self, inst_id: u32) -> *const BudgetState {
+        self.budgets
+            .get(inst_id as usize)
+            .map_or(ptr::null(), |state| state as *const _)
+    }
+
+    fn get(&self, inst_id: u32) -> SessionInfo {
+        let index = inst_id as usize;
+        if index >= MAX_DATA_SOURCE_INSTANCES {
+            return SessionInfo::default();
+        }
+        SessionInfo {
+            tracing_session_id: self.tracing_session_ids[index].load(Ordering::Relaxed),
+            target_buffer: self.target_buffers[index].load(Ordering::Relaxed),
+        }
+    }
 }
 
 /// Data source arguments struct.
@@ -201,6 +557,10 @@ pub struct DataSourceArgs {
     buffer_exhausted_policy_configurable: bool,
     will_notify_on_stop: bool,
     handles_incremental_state_clear: bool,
+    thread_descriptors: bool,
+    chunk_request_stats: bool,
+    config_schema: Option<ConfigSchema>,
+    byte_budget: Option<ByteBudget>,
 }
 
 /// Data source arguments builder.
@@ -243,6 +603,45 @@ impl DataSourceArgsBuilder {
         self
     }
 
+    /// Set how long the data source needs to stop, e.g. to tear down
+    /// hardware and write its last packets from the stop callback or a
+    /// postponed stop.
+    ///
+    /// The tracing service takes the stop timeout from the
+    /// `data_source_stop_timeout_ms` of the trace config, see
+    /// [`TraceConfigBuilder::data_source_stop_timeout`](crate::trace_config::TraceConfigBuilder::data_source_stop_timeout),
+    /// and gives up on instances that take longer, losing the packets they
+    /// write afterwards. A warning is logged at setup when the session allows
+    /// less than `stop_timeout`, and when a stop takes longer than
+    /// `stop_timeout`, see [`OnStopArgs::deadline`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn stop_timeout(mut self, stop_timeout: Duration) -> Self {
+        self.args.callbacks.stop_timeout = Some(stop_timeout);
+        self
+    }
+
+    /// Set a watchdog for the setup, start, stop and flush callbacks, which
+    /// logs a warning or aborts the process when one of them runs for longer
+    /// than the deadline of the watchdog, see [`Watchdog`]. Slow callbacks are
+    /// counted in [`DataSourceStats::slow_callbacks`].
+    ///
+    /// Only the callbacks themselves are watched: postponed stops and flushes
+    /// run for as long as their guard is alive, see [`stop_timeout`](Self::stop_timeout).
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn watchdog(mut self, watchdog: Watchdog) -> Self {
+        self.args.callbacks.watchdog = Some(watchdog);
+        self
+    }
+
+    /// Set a capture that the configs delivered to the setup callback are
+    /// written into, before the callback is called, to replay them later with
+    /// [`CapturedConfigs`](crate::config_capture::CapturedConfigs).
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn capture_configs(mut self, capture: ConfigCapture) -> Self {
+        self.args.callbacks.config_capture = Some(capture);
+        self
+    }
+
     /// Set whether this data source wants to receive incremental state clear notifications.
     ///
     /// This controls the **policy** of *whether* the tracing service should send clear
@@ -278,23 +677,98 @@ impl DataSourceArgsBuilder {
         self
     }
 
+    /// Set whether a thread descriptor is written the first time each thread
+    /// traces to an instance, so that trace processor labels the packets of the
+    /// thread with the name of the `std::thread` and its OS thread id.
+    ///
+    /// The descriptor uses the same track as the thread track of track events,
+    /// and is written again after the incremental state is cleared. Thread ids
+    /// are only available on Linux, Android and macOS.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn thread_descriptors(mut self, thread_descriptors: bool) -> Self {
+        self.args.thread_descriptors = thread_descriptors;
+        self
+    }
+
+    /// Set whether the chunk requests that stalled or failed while writing
+    /// the packets of the data source are counted in
+    /// [`DataSourceStats::stalled_chunk_requests`] and
+    /// [`DataSourceStats::dropped_chunk_requests`].
+    ///
+    /// This is off by default, as it makes each trace call read the counters
+    /// of the calling thread, and makes the shared memory arbiter count the
+    /// chunk requests of each thread from then on.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn chunk_request_stats(mut self, chunk_request_stats: bool) -> Self {
+        self.args.chunk_request_stats = chunk_request_stats;
+        self
+    }
+
+    /// Set the schema of the config that the data source understands, which
+    /// is published in its descriptor so that recording tools can render a
+    /// config form for it, see [`ConfigSchema`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn config_schema(mut self, config_schema: ConfigSchema) -> Self {
+        self.args.config_schema = Some(config_schema);
+        self
+    }
+
+    /// Set the number of bytes each instance can write, after which its
+    /// further packets are dropped or downsampled, see [`ByteBudget`].
+    ///
+    /// Packets are throttled in [`TraceContextBase::add_packet`] and
+    /// [`TraceContextBase::add_packets`], and are counted in
+    /// [`DataSourceStats::packets_throttled`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn byte_budget(mut self, byte_budget: ByteBudget) -> Self {
+        self.args.byte_budget = Some(byte_budget);
+        self
+    }
+
     /// Set setup callback.
     #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
-    pub fn on_setup<F>(mut self, cb: F) -> Self
+    pub fn on_setup<F>(mut self, mut cb: F) -> Self
     where
         F: FnMut(u32, &[u8], &mut OnSetupArgs) + Send + Sync + 'static,
     {
-        self.args.callbacks.on_setup = Some(Box::new(cb));
+        self.instance_callbacks().on_setup = Some(Box::new(move |inst_id, config, args| {
+            cb(inst_id, config, args);
+            Ok(())
+        }));
+        self
+    }
+
+    /// Set a setup callback that can reject the config of an instance.
+    ///
+    /// When `cb` returns an error, the error is logged and the instance is
+    /// disabled: the start, stop and flush callbacks are not called for it and
+    /// `DataSource::trace()` skips it until it is set up again.
+    ///
+    /// The error is reported to the tracing service, which counts it in the
+    /// `data_source_setup_failures` of the
+    /// [`TraceStats`](crate::trace_stats::TraceStats) of the session. It is
+    /// also counted in [`DataSourceStats::rejected_setups`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn try_on_setup<F>(mut self, cb: F) -> Self
+    where
+        F: FnMut(u32, &[u8], &mut OnSetupArgs) -> Result<(), SetupError> + Send + Sync + 'static,
+    {
+        self.instance_callbacks().on_setup = Some(Box::new(cb));
         self
     }
 
     /// Set start callback.
+    ///
+    /// Instances are started when their tracing session starts, which for a
+    /// session with a `START_TRACING` trigger is when the trigger is activated,
+    /// possibly long after setup. Expensive resources like hardware counters
+    /// should be enabled here rather than in the setup callback.
     #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
     pub fn on_start<F>(mut self, cb: F) -> Self
     where
         F: FnMut(u32, &mut OnStartArgs) + Send + Sync + 'static,
     {
-        self.args.callbacks.on_start = Some(Box::new(cb));
+        self.instance_callbacks().on_start = Some(Box::new(cb));
         self
     }
 
@@ -304,7 +778,7 @@ impl DataSourceArgsBuilder {
     where
         F: FnMut(u32, &mut OnStopArgs) + Send + Sync + 'static,
     {
-        self.args.callbacks.on_stop = Some(Box::new(cb));
+        self.instance_callbacks().on_stop = Some(Box::new(cb));
         self
     }
 
@@ -314,10 +788,108 @@ impl DataSourceArgsBuilder {
     where
         F: FnMut(u32, &mut OnFlushArgs) + Send + Sync + 'static,
     {
-        self.args.callbacks.on_flush = Some(Box::new(cb));
+        self.instance_callbacks().on_flush = Some(Box::new(cb));
+        self
+    }
+
+    /// Set clone started callback.
+    ///
+    /// `cb` is called when the tracing service starts cloning the tracing
+    /// session of an instance, e.g. for `perfetto --clone-by-name`, before the
+    /// flush callback of the clone. Data written before the flush is
+    /// acknowledged ends up in the clone, so a data source can e.g. write a
+    /// snapshot of its state, or postpone the flush with
+    /// [`OnFlushArgs::postpone`] while it does so on another thread.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn on_clone_started<F>(mut self, cb: F) -> Self
+    where
+        F: FnMut(u32, &mut OnFlushArgs) + Send + Sync + 'static,
+    {
+        self.instance_callbacks().on_clone_started = Some(Box::new(cb));
+        self
+    }
+
+    /// Set incremental state clear callback.
+    ///
+    /// `cb` is called with the index of the instance after the incremental
+    /// state of one of its trace writers has been cleared using
+    /// [`Clear::clear()`], so a stateful data source can e.g. schedule
+    /// descriptors to be emitted again for that instance. The state is cleared lazily,
+    /// so `cb` runs on the tracing thread that calls
+    /// [`DataSource::trace()`], possibly on several threads at once. Requires
+    /// [`handles_incremental_state_clear`](Self::handles_incremental_state_clear)
+    /// to be set.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn on_clear_incremental_state<F>(mut self, cb: F) -> Self
+    where
+        F: Fn(u32) + Send + Sync + 'static,
+    {
+        *self
+            .args
+            .callbacks
+            .on_clear_incremental_state
+            .get_mut()
+            .unwrap() = Some(Box::new(cb));
         self
     }
 
+    /// Set a callback called after the setup, start and stop callbacks with the
+    /// new state of the instance, to tell instances that are only set up from
+    /// started ones. Not called for instances rejected by
+    /// [`try_on_setup`](Self::try_on_setup).
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn on_state_change<F>(mut self, cb: F) -> Self
+    where
+        F: FnMut(u32, InstanceState) + Send + Sync + 'static,
+    {
+        self.instance_callbacks().on_state_change = Some(Box::new(cb));
+        self
+    }
+
+    // Calls `setup`, `start` and `stop` after the corresponding callbacks set by the
+    // user, for helpers that track the lifecycle of the instances. `setup` is not
+    // called for instances rejected by the user's setup callback.
+    pub(crate) fn observe_instances<S, T, P>(
+        mut self,
+        mut setup: S,
+        mut start: T,
+        mut stop: P,
+    ) -> Self
+    where
+        S: FnMut(u32, &[u8]) + Send + Sync + 'static,
+        T: FnMut(u32) + Send + Sync + 'static,
+        P: FnMut(u32) + Send + Sync + 'static,
+    {
+        let callbacks = self.instance_callbacks();
+        let mut on_setup = callbacks.on_setup.take();
+        callbacks.on_setup = Some(Box::new(move |inst_id, config, args| {
+            if let Some(cb) = &mut on_setup {
+                cb(inst_id, config, args)?;
+            }
+            setup(inst_id, config);
+            Ok(())
+        }));
+        let mut on_start = callbacks.on_start.take();
+        callbacks.on_start = Some(Box::new(move |inst_id, args| {
+            if let Some(cb) = &mut on_start {
+                cb(inst_id, args);
+            }
+            start(inst_id);
+        }));
+        let mut on_stop = callbacks.on_stop.take();
+        callbacks.on_stop = Some(Box::new(move |inst_id, args| {
+            if let Some(cb) = &mut on_stop {
+                cb(inst_id, args);
+            }
+            stop(inst_id);
+        }));
+        self
+    }
+
+    fn instance_callbacks(&mut self) -> &mut InstanceCallbacks {
+        self.args.callbacks.instance.get_mut().unwrap()
+    }
+
     /// Returns data source arguments struct.
     pub fn build(self) -> DataSourceArgs {
         self.args
@@ -359,19 +931,238 @@ unsafe extern "C" fn flush_callback_trampoline(user_arg: *mut c_void) {
         }
     });
     if let Err(err) = result {
-        eprintln!("Fatal panic: {:?}", err);
-        std::process::abort();
+        crate::__sdk_fatal!("Fatal panic: {:?}", err);
+    }
+}
+
+/// Statistics of the data written by a data source type, see [`DataSource::stats`].
+///
+/// The packet and chunk request counters are updated once at the end of each
+/// trace call, not for every packet, to keep the cost of writing packets low.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DataSourceStats {
+    /// Number of trace packets written.
+    pub packets_written: u64,
+    /// Number of bytes written in trace packets.
+    pub bytes_written: u64,
+    /// Number of instance configs rejected by the setup callback.
+    pub rejected_setups: u64,
+    /// Number of instances set up after the data source type was retired,
+    /// see [`DataSource::retire`].
+    pub retired_setups: u64,
+    /// Number of instances that took longer than their stop timeout to stop.
+    pub slow_stops: u64,
+    /// Number of trace packets dropped because the shared memory buffer was
+    /// full. Included in `packets_written`.
+    pub packets_dropped: u64,
+    /// Number of callbacks that ran for longer than the deadline of the
+    /// watchdog, see [`DataSourceArgsBuilder::watchdog`].
+    pub slow_callbacks: u64,
+    /// Number of trace packets not written because the instance exhausted
+    /// its byte budget, see [`DataSourceArgsBuilder::byte_budget`]. Not
+    /// included in `packets_written`.
+    pub packets_throttled: u64,
+    /// Number of times writing a trace packet stalled to wait for a free chunk
+    /// because the shared memory buffer was full. Only counted if enabled with
+    /// [`DataSourceArgsBuilder::chunk_request_stats`].
+    pub stalled_chunk_requests: u64,
+    /// Number of times writing a trace packet failed to acquire a chunk
+    /// because the shared memory buffer was full, and started dropping data.
+    /// Only counted if enabled with [`DataSourceArgsBuilder::chunk_request_stats`].
+    pub dropped_chunk_requests: u64,
+}
+
+/// Outcome of a trace call, see [`DataSource::trace`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TraceOutcome {
+    /// Number of instances traced.
+    pub instances: u32,
+    /// Number of trace packets written, including the dropped packets.
+    pub packets_written: u64,
+    /// Number of trace packets dropped because the shared memory buffer was
+    /// full.
+    pub packets_dropped: u64,
+}
+
+impl TraceOutcome {
+    /// Returns true if any of the packets was dropped.
+    pub fn dropped(&self) -> bool {
+        self.packets_dropped > 0
+    }
+}
+
+// Number of shards of a `ShardedCounter`.
+const COUNTER_SHARDS: usize = 16;
+
+// Shard of the counters updated by the current thread. Threads are assigned
+// shards round robin, so that concurrent writers mostly update different
+// cache lines.
+fn counter_shard() -> usize {
+    static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static SHARD: usize = NEXT_SHARD.fetch_add(1, Ordering::Relaxed) % COUNTER_SHARDS;
+    }
+    SHARD.with(|shard| *shard)
+}
+
+#[derive(Default)]
+#[repr(align(64))]
+struct CounterShard(AtomicU64);
+
+// Counter updated from the trace path of many threads at once. Each thread
+// adds to its own shard, and reads sum the shards.
+#[derive(Default)]
+pub(crate) struct ShardedCounter {
+    shards: [CounterShard; COUNTER_SHARDS],
+}
+
+impl ShardedCounter {
+    pub(crate) const fn new() -> Self {
+        Self {
+            shards: [const { CounterShard(AtomicU64::new(0)) }; COUNTER_SHARDS],
+        }
+    }
+
+    pub(crate) fn add(&self, value: u64) {
+        self.shards[counter_shard()]
+            .0
+            .fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub(crate) fn get(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|shard| shard.0.load(Ordering::Relaxed))
+            .fold(0, u64::wrapping_add)
+    }
+}
+
+// Chunk requests of the calling thread that stalled or were dropped because
+// the shared memory buffer was full, see `SharedMemoryArbiterImpl`.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct ChunkRequests {
+    stalled: u64,
+    dropped: u64,
+}
+
+impl ChunkRequests {
+    pub(crate) fn of_thread() -> Self {
+        // SAFETY: Both functions only read counters of the calling thread.
+        unsafe {
+            Self {
+                stalled: PerfettoProducerGetThreadStalledChunkRequests(),
+                dropped: PerfettoProducerGetThreadDroppedChunkRequests(),
+            }
+        }
+    }
+
+    // Requests made on the calling thread between `earlier` and `self`.
+    fn since(self, earlier: Self) -> Self {
+        Self {
+            stalled: self.stalled.wrapping_sub(earlier.stalled),
+            dropped: self.dropped.wrapping_sub(earlier.dropped),
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct DsStatsCounters {
+    packets_written: ShardedCounter,
+    bytes_written: ShardedCounter,
+    rejected_setups: AtomicU64,
+    retired_setups: AtomicU64,
+    slow_stops: AtomicU64,
+    packets_dropped: AtomicU64,
+    slow_callbacks: AtomicU64,
+    packets_throttled: AtomicU64,
+    stalled_chunk_requests: AtomicU64,
+    dropped_chunk_requests: AtomicU64,
+}
+
+impl DsStatsCounters {
+    fn record_packets(
+        &self,
+        packets: u64,
+        bytes: u64,
+        dropped: u64,
+        chunk_requests: ChunkRequests,
+    ) {
+        self.packets_written.add(packets);
+        self.bytes_written.add(bytes);
+        if dropped != 0 {
+            self.packets_dropped.fetch_add(dropped, Ordering::Relaxed);
+        }
+        if chunk_requests.stalled != 0 {
+            self.stalled_chunk_requests
+                .fetch_add(chunk_requests.stalled, Ordering::Relaxed);
+        }
+        if chunk_requests.dropped != 0 {
+            self.dropped_chunk_requests
+                .fetch_add(chunk_requests.dropped, Ordering::Relaxed);
+        }
+    }
+
+    fn snapshot(&self) -> DataSourceStats {
+        DataSourceStats {
+            packets_written: self.packets_written.get(),
+            bytes_written: self.bytes_written.get(),
+            rejected_setups: self.rejected_setups.load(Ordering::Relaxed),
+            retired_setups: self.retired_setups.load(Ordering::Relaxed),
+            slow_stops: self.slow_stops.load(Ordering::Relaxed),
+            packets_dropped: self.packets_dropped.load(Ordering::Relaxed),
+            slow_callbacks: self.slow_callbacks.load(Ordering::Relaxed),
+            packets_throttled: self.packets_throttled.load(Ordering::Relaxed),
+            stalled_chunk_requests: self.stalled_chunk_requests.load(Ordering::Relaxed),
+            dropped_chunk_requests: self.dropped_chunk_requests.load(Ordering::Relaxed),
+        }
     }
 }
 
 /// Trace context base struct with passed to data source and track event trace callbacks.
 pub struct TraceContextBase {
     pub(crate) iterator: PerfettoDsImplTracerIterator,
+    // Counters of the data source type, or null if not tracked.
+    pub(crate) stats: *const DsStatsCounters,
+    // Outcome of the trace call so far.
+    pub(crate) outcome: TraceOutcome,
+    // Bytes of the packets written so far, added to `stats` with the rest of
+    // the outcome at the end of the trace call.
+    pub(crate) bytes_written: u64,
+    // Byte budget of the current instance, or null if unlimited.
+    pub(crate) budget: *const BudgetState,
+    // Chunk space left after the last packet, and reservation for the next one.
+    pub(crate) chunk: ChunkState,
+}
+
+// Space left in the chunk of a tracer, see `TraceContextBase::remaining_chunk_bytes`.
+#[derive(Default)]
+pub(crate) struct ChunkState {
+    // Tracer of the last packet written, and bytes left in its chunk.
+    remaining: Option<(*mut PerfettoDsTracerImpl, usize)>,
+    // Bytes to reserve at the start of the next packet.
+    reserved: usize,
 }
 
 impl TraceContextBase {
     /// Creates new trace packets and calls `cb` to write data to each of the packets.
-    pub fn add_packet<F>(&mut self, mut cb: F)
+    ///
+    /// Packets are committed to the service a chunk at a time, not one by one,
+    /// so a loop adding many small packets doesn't cause an IPC per packet. See
+    /// [`ProducerInitArgsBuilder::shmem_batch_commits_duration_ms`](crate::producer::ProducerInitArgsBuilder::shmem_batch_commits_duration_ms)
+    /// to also batch the commits of several chunks.
+    ///
+    /// The packet is skipped if the instance exhausted its byte budget, see
+    /// [`DataSourceArgsBuilder::byte_budget`].
+    pub fn add_packet<F>(&mut self, cb: F)
+    where
+        F: FnMut(&mut TracePacket),
+    {
+        if self.admit_packet() {
+            self.write_packet(cb);
+        }
+    }
+
+    fn write_packet<F>(&mut self, mut cb: F)
     where
         F: FnMut(&mut TracePacket),
     {
@@ -388,13 +1179,21 @@ impl TraceContextBase {
                 }),
             },
         };
+        let start_size = writer.writer.get_written_size();
         let mut msg = PbMsg::new(&writer).unwrap();
+        msg.reserve(std::mem::take(&mut self.chunk.reserved));
         let mut packet = TracePacket { msg: &mut msg };
 
         cb(&mut packet);
 
         packet.msg.finalize();
+        let size = writer.writer.get_written_size() - start_size;
+        self.chunk.remaining = Some((self.iterator.tracer, writer.writer.available_bytes()));
 
+        // SAFETY: `self.iterator.tracer` must be a pointer provided by a call to
+        // PerfettoDsImplTraceIterateBegin/Next.
+        let dropping = unsafe { PerfettoDsTracerImplIsDropping(self.iterator.tracer) };
+        self.record_packet(size, dropping);
         let mut inner_writer = writer.writer.writer.borrow_mut();
         // SAFETY:
         //
@@ -404,6 +1203,64 @@ impl TraceContextBase {
         }
     }
 
+    /// Creates `count` new trace packets and calls `cb` with the index of each
+    /// packet to write data to it.
+    ///
+    /// This is equivalent to calling [`add_packet`](Self::add_packet) `count`
+    /// times, but the packets share a packet writer, which makes it cheaper for
+    /// sampling loops that emit many small packets at a time. Packets are
+    /// committed to the service a chunk at a time either way.
+    ///
+    /// Packets past the byte budget of the instance are skipped, see
+    /// [`DataSourceArgsBuilder::byte_budget`].
+    pub fn add_packets<F>(&mut self, count: usize, mut cb: F)
+    where
+        F: FnMut(usize, &mut TracePacket),
+    {
+        let mut packet_writer: Option<PbMsgWriter> = None;
+        for index in 0..count {
+            if !self.admit_packet() {
+                continue;
+            }
+            // Returns a writer that must be freed using `PerfettoDsTracerImplPacketEnd`.
+            //
+            // SAFETY: See `write_packet`.
+            let stream_writer = unsafe { PerfettoDsTracerImplPacketBegin(self.iterator.tracer) };
+            let writer = match &mut packet_writer {
+                Some(writer) => {
+                    *writer.writer.writer.borrow_mut() = stream_writer;
+                    writer
+                }
+                None => packet_writer.insert(PbMsgWriter {
+                    writer: StreamWriter {
+                        writer: RefCell::new(stream_writer),
+                    },
+                }),
+            };
+            let start_size = writer.writer.get_written_size();
+            let mut msg = PbMsg::new(writer).unwrap();
+            msg.reserve(std::mem::take(&mut self.chunk.reserved));
+            let mut packet = TracePacket { msg: &mut msg };
+
+            cb(index, &mut packet);
+
+            packet.msg.finalize();
+            let size = writer.writer.get_written_size() - start_size;
+            self.chunk.remaining = Some((self.iterator.tracer, writer.writer.available_bytes()));
+
+            // SAFETY: See `write_packet`.
+            let dropping = unsafe { PerfettoDsTracerImplIsDropping(self.iterator.tracer) };
+            self.record_packet(size, dropping);
+            let mut inner_writer = writer.writer.writer.borrow_mut();
+            // SAFETY:
+            //
+            // Free writer created above using `PerfettoDsTracerImplPacketBegin`.
+            unsafe {
+                PerfettoDsTracerImplPacketEnd(self.iterator.tracer, &mut *inner_writer as *mut _);
+            }
+        }
+    }
+
     /// Forces a commit of the thread-local tracing data written so far to the
     /// service.
     ///
@@ -429,10 +1286,105 @@ impl TraceContextBase {
         };
     }
 
-    /// Returns the index of the current instance.
-    pub fn instance_index(&self) -> u32 {
+    /// Returns an estimate of the number of bytes left in the chunk that the
+    /// next packet of the current instance is written to, or `None` if no
+    /// packet was written to the instance yet by this context.
+    ///
+    /// A packet that doesn't fit is split across chunks, so producers writing
+    /// bursts of data, e.g. a descriptor of hundreds of counters, can use it
+    /// to decide how much to write to each packet. Within a packet, see
+    /// [`PbMsg::remaining_chunk_bytes`].
+    pub fn remaining_chunk_bytes(&self) -> Option<usize> {
+        self.chunk
+            .remaining
+            .filter(|(tracer, _)| *tracer == self.iterator.tracer)
+            .map(|(_, remaining)| remaining)
+    }
+
+    /// Hints that the next packet added to the current instance is about
+    /// `size` bytes: its fields start in a new chunk if fewer bytes are left in
+    /// the current one, instead of switching chunks in the middle of them.
+    ///
+    /// The hint only applies to the next packet. Packets larger than a chunk
+    /// are split across chunks regardless. Within a packet, see
+    /// [`PbMsg::reserve`].
+    pub fn reserve(&mut self, size: usize) {
+        self.chunk.reserved = size;
+    }
+
+    /// Returns the index of the current instance.
+    pub fn instance_index(&self) -> u32 {
         self.iterator.inst_id
     }
+
+    // Adds the packets written by the trace call, and the chunk requests made by
+    // the calling thread since `chunk_requests` if they are counted, to the
+    // data source stats. Chunks are acquired on the writing thread, so these
+    // requests are the ones of this data source.
+    pub(crate) fn record_stats(&self, chunk_requests: Option<ChunkRequests>) {
+        // SAFETY: `self.stats` must be null or point to the counters of the data
+        // source being traced, which outlive its trace contexts.
+        if let Some(stats) = unsafe { self.stats.as_ref() } {
+            stats.record_packets(
+                self.outcome.packets_written,
+                self.bytes_written,
+                self.outcome.packets_dropped,
+                chunk_requests.map_or_else(ChunkRequests::default, |chunk_requests| {
+                    ChunkRequests::of_thread().since(chunk_requests)
+                }),
+            );
+        }
+    }
+
+    fn record_packet(&mut self, size: usize, dropped: bool) {
+        self.outcome.packets_written += 1;
+        self.outcome.packets_dropped += u64::from(dropped);
+        self.bytes_written += size as u64;
+        if dropped {
+            crate::sdk_log!(
+                Debug,
+                "Dropped packet of data source instance {}: the shared memory buffer is full",
+                self.iterator.inst_id,
+            );
+        }
+        // SAFETY: `self.budget` must be null or point to the budget state of
+        // the instance, which outlives the trace contexts of its data source.
+        if let Some(budget) = unsafe { self.budget.as_ref() } {
+            budget.charge(size);
+        }
+    }
+
+    // Returns true if the next packet of the instance is within its byte
+    // budget. Writes the marker packet when the budget is exhausted.
+    fn admit_packet(&mut self) -> bool {
+        // SAFETY: See `record_packet`.
+        let Some(budget) = (unsafe { self.budget.as_ref() }) else {
+            return true;
+        };
+        let admission = budget.admit();
+        if admission == Admission::Write {
+            return true;
+        }
+        // SAFETY: See `record_stats`.
+        if let Some(stats) = unsafe { self.stats.as_ref() } {
+            stats.packets_throttled.fetch_add(1, Ordering::Relaxed);
+        }
+        if admission == Admission::Exhausted {
+            crate::sdk_log!(
+                Info,
+                "Data source instance {} exhausted its byte budget; packets are dropped",
+                self.iterator.inst_id,
+            );
+            let timestamp = DataSourceTimestamp::now();
+            self.write_packet(|packet: &mut TracePacket| {
+                packet.set_timestamp(timestamp.timestamp());
+                packet.set_timestamp_clock_id(timestamp.clock_id());
+                packet
+                    .set_previous_packet_dropped(TracePacketDataLossReason::DataLossPresent as u32);
+            });
+        }
+        false
+    }
 }
 
 /// Data source timestamp types.
@@ -458,6 +1410,9 @@ impl DataSourceTimestamp {
     /// `TracePacket::set_timestamp` and `set_timestamp_clock_id`.
     #[allow(non_upper_case_globals)]
     pub fn now() -> Self {
+        if let Some(timestamp) = crate::platform::now() {
+            return timestamp;
+        }
         // SAFETY: FFI call with no outstanding preconditions.
         let ds_timestamp = unsafe { PerfettoDsGetTimestamp() };
         if ds_timestamp.clock_id == PerfettoDsClockId_PERFETTO_DS_CLOCK_MONOTONIC {
@@ -570,15 +1525,56 @@ impl Clear for IncrementalState {}
 pub struct TraceContext<'a, IncrT: Default + Clear = IncrementalState> {
     base: TraceContextBase,
     pub(crate) impl_: *mut PerfettoDsImpl,
+    sessions: &'a InstanceSessions,
     pub(crate) _marker: PhantomData<&'a IncrT>,
 }
 
 impl<IncrT: Default + Clear> TraceContext<'_, IncrT> {
+    /// Returns the tracing session of the current instance, e.g. to partition the
+    /// output of a producer serving several concurrent sessions.
+    ///
+    /// The unique session name of the trace config is not passed on to producers
+    /// by the tracing service.
+    pub fn session(&self) -> SessionInfo {
+        self.sessions.get(self.base.iterator.inst_id)
+    }
+
+    /// Returns the config the current instance was set up with, e.g. to only
+    /// write the data selected by the config. Fields that aren't decoded by
+    /// [`InstanceConfig`] can be decoded from [`InstanceConfig::raw`].
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use perfetto_sdk::data_source::{DataSource, TraceContext};
+    ///
+    /// let data_source: DataSource = DataSource::new();
+    /// data_source.trace(|ctx: &mut TraceContext| {
+    ///     let Some(config) = ctx.config() else {
+    ///         return;
+    ///     };
+    ///     if config.enable_extra_guardrails {
+    ///         // Skip expensive data.
+    ///     }
+    /// });
+    /// ```
+    pub fn config(&self) -> Option<Arc<InstanceConfig>> {
+        self.sessions.config(self.base.iterator.inst_id)
+    }
+
     /// Calls `cb` with the incremental state for the instance.
     pub fn with_incremental_state<F>(&mut self, mut cb: F)
     where
         F: FnMut(&mut Self, &mut IncrT),
     {
+        // SAFETY: The writer state is only accessed by the thread that owns the
+        // trace writer, during the trace call.
+        let state = unsafe { &mut (*self.writer_state()).state };
+        cb(self, state);
+    }
+
+    // Returns the incremental state of the trace writer of the instance.
+    fn writer_state(&mut self) -> *mut WriterState<IncrT> {
         assert!(!self.impl_.is_null());
         // SAFETY:
         //
@@ -597,15 +1593,74 @@ impl<IncrT: Default + Clear> TraceContext<'_, IncrT> {
         if ptr.is_null() {
             panic!("missing incremental state");
         }
-        // SAFETY:
-        //
-        // - `buf` must be non-null.
-        // - `IncrT` must match the generic type used for on_create_incr_trampoline.
-        let state: &mut IncrT = unsafe { &mut *(ptr as *mut IncrT) };
-        cb(self, state);
+        // `WriterState<IncrT>` must match the type created by on_create_incr_trampoline.
+        ptr as *mut WriterState<IncrT>
+    }
+
+    // Writes a descriptor of the current thread unless already written since the
+    // incremental state was last cleared.
+    fn write_thread_descriptor_once(&mut self) {
+        // SAFETY: See `with_incremental_state`.
+        let state = unsafe { &mut *self.writer_state() };
+        if state.thread_descriptor_written {
+            return;
+        }
+        state.thread_descriptor_written = true;
+        let Some(tid) = current_thread_id() else {
+            return;
+        };
+        let process_uuid = TrackEventTrack::process_track_uuid();
+        let thread = std::thread::current();
+        let timestamp = DataSourceTimestamp::now();
+        self.add_packet(|packet: &mut TracePacket| {
+            packet.set_timestamp(timestamp.timestamp());
+            packet.set_timestamp_clock_id(timestamp.clock_id());
+            packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+                desc.set_uuid(process_uuid ^ tid as u64);
+                if process_uuid != 0 {
+                    desc.set_parent_uuid(process_uuid);
+                }
+                desc.set_thread(|td: &mut ThreadDescriptor| {
+                    td.set_pid(std::process::id() as i32);
+                    td.set_tid(tid);
+                    if let Some(name) = thread.name() {
+                        td.set_thread_name(name);
+                    }
+                });
+            });
+        });
     }
 }
 
+// Incremental state of a trace writer: the state of the data source and the
+// state kept by the SDK for the writer.
+struct WriterState<IncrT> {
+    state: IncrT,
+    thread_descriptor_written: bool,
+    // Index of the instance the writer writes to.
+    inst_id: u32,
+}
+
+// Returns the OS id of the calling thread, if supported on the platform.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn current_thread_id() -> Option<i64> {
+    // SAFETY: FFI call with no outstanding preconditions.
+    Some(unsafe { libc::gettid() }.into())
+}
+
+#[cfg(target_os = "macos")]
+fn current_thread_id() -> Option<i64> {
+    let mut tid = 0u64;
+    // SAFETY: `tid` is valid for writes and a null thread means the calling thread.
+    let result = unsafe { libc::pthread_threadid_np(0, &mut tid) };
+    (result == 0).then_some(tid as i64)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+fn current_thread_id() -> Option<i64> {
+    None
+}
+
 impl<IncrT: Default + Clear> std::ops::Deref for TraceContext<'_, IncrT> {
     type Target = TraceContextBase;
     fn deref(&self) -> &Self::Target {
@@ -624,6 +1679,14 @@ pub struct DataSource<'a: 'static, IncrT: Default + Clear = IncrementalState> {
     enabled: *mut bool,
     impl_: *mut PerfettoDsImpl,
     callbacks: Mutex<Option<Box<DsCallbacks>>>,
+    rejected_instances: Arc<AtomicU32>,
+    started_instances: Arc<AtomicU32>,
+    stats: Arc<DsStatsCounters>,
+    sessions: Arc<InstanceSessions>,
+    retired: Arc<RwLock<bool>>,
+    thread_descriptors: bool,
+    chunk_request_stats: bool,
+    byte_budget: bool,
     _marker: PhantomData<&'a IncrT>,
 }
 
@@ -637,24 +1700,104 @@ unsafe extern "C" fn on_setup_callback_trampoline(
 ) -> *mut c_void {
     let result = std::panic::catch_unwind(|| {
         // SAFETY: `user_arg` must be a pointer to a boxed DsCallbacks struct.
-        let callbacks: &mut DsCallbacks = unsafe { &mut *(user_arg as *mut _) };
-        if let Some(f) = &mut callbacks.on_setup {
-            // SAFETY:
-            // - `ds_config` must be non-null.
-            // - `ds_config_size` bytes starting at `ptr` must be valid for **reads**.
-            let config =
-                unsafe { std::slice::from_raw_parts(ds_config as *const u8, ds_config_size) };
+        let callbacks: &DsCallbacks = unsafe { &*(user_arg as *const _) };
+        let retired = RetiredGuard::new(&callbacks.retired);
+        if retired.is_retired() {
+            callbacks
+                .stats
+                .retired_setups
+                .fetch_add(1, Ordering::Relaxed);
+            callbacks.set_rejected(inst_id, true);
+            OnSetupArgs { _args: args }.set_error("The data source has been retired.");
+            return None;
+        }
+        // SAFETY:
+        // - `ds_config` must be non-null.
+        // - `ds_config_size` bytes starting at `ptr` must be valid for **reads**.
+        let config = unsafe { std::slice::from_raw_parts(ds_config as *const u8, ds_config_size) };
+        let instance_ctx = callbacks
+            .sessions
+            .set(inst_id, config)
+            .map(|config| InstanceContext { inst_id, config });
+        if let Some(budget) = &callbacks.byte_budget {
+            callbacks.sessions.reset_budget(inst_id, budget, config);
+        }
+        if let Some(capture) = &callbacks.config_capture {
+            capture.capture(config);
+        }
+        if let Some(stop_timeout) = callbacks.stop_timeout {
+            let session_stop_timeout = callbacks.session_stop_timeout(inst_id);
+            if session_stop_timeout < stop_timeout {
+                crate::sdk_log!(
+                    Warn,
+                    "Data source instance {} needs {:?} to stop but the session only allows {:?}; \
+                     increase data_source_stop_timeout_ms in the trace config",
+                    inst_id,
+                    stop_timeout,
+                    session_stop_timeout,
+                );
+            }
+        }
+        let watched = callbacks.watch("setup", inst_id);
+        let mut instance = callbacks.instance.lock().unwrap();
+        if let Some(f) = &mut instance.on_setup {
             let mut on_setup_args = OnSetupArgs { _args: args };
-            f(inst_id, config, &mut on_setup_args);
+            let result = f(inst_id, config, &mut on_setup_args);
+            if let Err(err) = &result {
+                crate::sdk_log!(Warn, "Rejected data source instance {}: {}", inst_id, err);
+                callbacks
+                    .stats
+                    .rejected_setups
+                    .fetch_add(1, Ordering::Relaxed);
+                on_setup_args.set_error(&err.to_string());
+            }
+            callbacks.set_rejected(inst_id, result.is_err());
+        }
+        drop(watched);
+        if !callbacks.is_rejected(inst_id) {
+            callbacks.set_state(&mut instance, inst_id, InstanceState::SetUp);
+        }
+        instance_ctx
+    });
+    match result {
+        // The instance context is only used by the SDK, to clear the session of
+        // the instance when it's destroyed.
+        Ok(Some(instance_ctx)) => Box::into_raw(Box::new(instance_ctx)) as *mut c_void,
+        Ok(None) => ptr::null_mut(),
+        Err(err) => {
+            crate::__sdk_fatal!("Fatal panic: {:?}", err);
         }
+    }
+}
+
+// Context of an instance, passed by the tracing service to the callbacks of
+// the instance.
+struct InstanceContext {
+    inst_id: u32,
+    config: Arc<InstanceConfig>,
+}
+
+unsafe extern "C" fn on_destroy_callback_trampoline(
+    _ds: *mut PerfettoDsImpl,
+    user_arg: *mut c_void,
+    inst_ctx: *mut c_void,
+) {
+    if inst_ctx.is_null() {
+        return;
+    }
+    // SAFETY: A non-null `inst_ctx` must be a pointer to a boxed InstanceContext
+    // struct created by on_setup_callback_trampoline.
+    let instance_ctx = unsafe { Box::from_raw(inst_ctx as *mut InstanceContext) };
+    let result = std::panic::catch_unwind(|| {
+        // SAFETY: `user_arg` must be a pointer to a boxed DsCallbacks struct.
+        let callbacks: &DsCallbacks = unsafe { &*(user_arg as *const _) };
+        callbacks
+            .sessions
+            .clear(instance_ctx.inst_id, &instance_ctx.config);
     });
     if let Err(err) = result {
-        eprintln!("Fatal panic: {:?}", err);
-        std::process::abort();
+        crate::__sdk_fatal!("Fatal panic: {:?}", err);
     }
-    // Instance contexts are not supported as preferably handled by the
-    // client in Rust code.
-    ptr::null_mut()
 }
 
 unsafe extern "C" fn on_start_callback_trampoline(
@@ -666,15 +1809,23 @@ unsafe extern "C" fn on_start_callback_trampoline(
 ) {
     let result = std::panic::catch_unwind(|| {
         // SAFETY: `user_arg` must be a pointer to a boxed DsCallbacks struct.
-        let callbacks: &mut DsCallbacks = unsafe { &mut *(user_arg as *mut _) };
-        if let Some(f) = &mut callbacks.on_start {
+        let callbacks: &DsCallbacks = unsafe { &*(user_arg as *const _) };
+        // Retired data sources reject all their instances.
+        let _retired = RetiredGuard::new(&callbacks.retired);
+        if callbacks.is_rejected(inst_id) {
+            return;
+        }
+        let watched = callbacks.watch("start", inst_id);
+        let mut instance = callbacks.instance.lock().unwrap();
+        if let Some(f) = &mut instance.on_start {
             let mut on_start_args = OnStartArgs { _args: args };
             f(inst_id, &mut on_start_args);
         }
+        drop(watched);
+        callbacks.set_state(&mut instance, inst_id, InstanceState::Started);
     });
     if let Err(err) = result {
-        eprintln!("Fatal panic: {:?}", err);
-        std::process::abort();
+        crate::__sdk_fatal!("Fatal panic: {:?}", err);
     }
 }
 
@@ -687,15 +1838,41 @@ unsafe extern "C" fn on_stop_callback_trampoline(
 ) {
     let result = std::panic::catch_unwind(|| {
         // SAFETY: `user_arg` must be a pointer to a boxed DsCallbacks struct.
-        let callbacks: &mut DsCallbacks = unsafe { &mut *(user_arg as *mut _) };
-        if let Some(f) = &mut callbacks.on_stop {
-            let mut on_stop_args = OnStopArgs { args };
+        let callbacks: &DsCallbacks = unsafe { &*(user_arg as *const _) };
+        // Retired data sources reject all their instances.
+        let _retired = RetiredGuard::new(&callbacks.retired);
+        if callbacks.is_rejected(inst_id) {
+            callbacks.set_rejected(inst_id, false);
+            return;
+        }
+        let stop_timeout = callbacks
+            .stop_timeout
+            .unwrap_or_else(|| callbacks.session_stop_timeout(inst_id));
+        let watched = callbacks.watch("stop", inst_id);
+        let mut instance = callbacks.instance.lock().unwrap();
+        if let Some(f) = &mut instance.on_stop {
+            let start = Instant::now();
+            let mut on_stop_args = OnStopArgs {
+                args,
+                deadline: StopDeadline {
+                    inst_id,
+                    start,
+                    instant: start + stop_timeout,
+                    stats: Arc::clone(&callbacks.stats),
+                },
+                postponed: false,
+            };
             f(inst_id, &mut on_stop_args);
+            // Postponed stops are checked when their guard is dropped.
+            if !on_stop_args.postponed {
+                on_stop_args.deadline.check();
+            }
         }
+        drop(watched);
+        callbacks.set_state(&mut instance, inst_id, InstanceState::Stopped);
     });
     if let Err(err) = result {
-        eprintln!("Fatal panic: {:?}", err);
-        std::process::abort();
+        crate::__sdk_fatal!("Fatal panic: {:?}", err);
     }
 }
 
@@ -708,50 +1885,270 @@ unsafe extern "C" fn on_flush_callback_trampoline(
 ) {
     let result = std::panic::catch_unwind(|| {
         // SAFETY: `user_arg` must be a pointer to a boxed DsCallbacks struct.
-        let callbacks: &mut DsCallbacks = unsafe { &mut *(user_arg as *mut _) };
-        if let Some(f) = &mut callbacks.on_flush {
-            let mut on_flush_args = OnFlushArgs { args };
+        let callbacks: &DsCallbacks = unsafe { &*(user_arg as *const _) };
+        // Retired data sources reject all their instances.
+        let _retired = RetiredGuard::new(&callbacks.retired);
+        if callbacks.is_rejected(inst_id) {
+            return;
+        }
+        let _watched = callbacks.watch("flush", inst_id);
+        let mut instance_callbacks = callbacks.instance.lock().unwrap();
+        let mut on_flush_args = OnFlushArgs { args };
+        if let Some(f) = &mut instance_callbacks.on_clone_started
+            && on_flush_args.is_clone()
+        {
+            f(inst_id, &mut on_flush_args);
+        }
+        if let Some(f) = &mut instance_callbacks.on_flush {
             f(inst_id, &mut on_flush_args);
         }
     });
     if let Err(err) = result {
-        eprintln!("Fatal panic: {:?}", err);
-        std::process::abort();
+        crate::__sdk_fatal!("Fatal panic: {:?}", err);
+    }
+}
+
+// Data source types registered with `DataSource::register`, independently of
+// their incremental state type, for tracing to all of them at once.
+struct RegisteredDataSource {
+    impl_: *mut PerfettoDsImpl,
+    rejected_instances: Arc<AtomicU32>,
+    stats: Arc<DsStatsCounters>,
+    chunk_request_stats: bool,
+}
+
+// SAFETY: Registered data source types are never destroyed and tracing to them
+// is thread-safe.
+unsafe impl Send for RegisteredDataSource {}
+
+static REGISTERED_DATA_SOURCES: OnceLock<Mutex<Vec<RegisteredDataSource>>> = OnceLock::new();
+
+fn registered_data_sources() -> &'static Mutex<Vec<RegisteredDataSource>> {
+    REGISTERED_DATA_SOURCES.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+// Returns the sum of the statistics of all the registered data source types.
+pub(crate) fn registered_stats() -> DataSourceStats {
+    registered_data_sources().lock().unwrap().iter().fold(
+        DataSourceStats::default(),
+        |total, data_source| {
+            let stats = data_source.stats.snapshot();
+            DataSourceStats {
+                packets_written: total.packets_written + stats.packets_written,
+                bytes_written: total.bytes_written + stats.bytes_written,
+                rejected_setups: total.rejected_setups + stats.rejected_setups,
+                retired_setups: total.retired_setups + stats.retired_setups,
+                slow_stops: total.slow_stops + stats.slow_stops,
+                packets_dropped: total.packets_dropped + stats.packets_dropped,
+                slow_callbacks: total.slow_callbacks + stats.slow_callbacks,
+                packets_throttled: total.packets_throttled + stats.packets_throttled,
+                stalled_chunk_requests: total.stalled_chunk_requests + stats.stalled_chunk_requests,
+                dropped_chunk_requests: total.dropped_chunk_requests + stats.dropped_chunk_requests,
+            }
+        },
+    )
+}
+
+// Serializes the fields of the packet written by `cb`.
+fn encode_packet<F>(cb: F) -> Vec<u8>
+where
+    F: FnOnce(&mut TracePacket),
+{
+    let writer = PbMsgWriter::new();
+    let hb = HeapBuffer::new(&writer.writer);
+    let mut msg = PbMsg::new(&writer).unwrap();
+    cb(&mut TracePacket { msg: &mut msg });
+    msg.finalize();
+    let size = writer.writer.get_written_size();
+    let mut buffer = vec![0u8; size];
+    hb.copy_into(&mut buffer);
+    buffer
+}
+
+thread_local! {
+    // Set while the thread runs the callback of a trace call, when a packet
+    // may be open on the trace writers of the thread.
+    static IN_TRACE: Cell<bool> = const { Cell::new(false) };
+}
+
+// Marks the thread as running a trace callback until dropped, including when
+// the callback unwinds.
+pub(crate) struct InTraceScope {
+    previous: bool,
+}
+
+impl InTraceScope {
+    pub(crate) fn enter() -> Self {
+        Self {
+            previous: IN_TRACE.replace(true),
+        }
+    }
+}
+
+impl Drop for InTraceScope {
+    fn drop(&mut self) {
+        IN_TRACE.set(self.previous);
+    }
+}
+
+// Returns true if the calling thread runs the callback of a trace call, e.g.
+// in a panic hook called from inside the callback.
+pub(crate) fn is_in_trace() -> bool {
+    IN_TRACE.get()
+}
+
+thread_local! {
+    // Deadline of the innermost `trace_with_timeout()` call of the thread.
+    static STALL_DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+// Makes the trace writers of the thread wait for a free chunk when the shared
+// memory buffer is full, until `timeout` has passed, and then drop data. A
+// nested scope can't extend the deadline of the outer one. Restores the outer
+// deadline, or the buffer exhausted policy, when dropped.
+struct StallTimeoutScope {
+    previous: Option<Instant>,
+}
+
+impl StallTimeoutScope {
+    fn enter(timeout: Duration) -> Self {
+        let now = Instant::now();
+        // Saturate instead of overflowing for very long timeouts.
+        let deadline = now
+            .checked_add(timeout)
+            .unwrap_or_else(|| now + Duration::from_secs(u32::MAX.into()));
+        let previous = STALL_DEADLINE.get();
+        let deadline = previous.map_or(deadline, |previous| previous.min(deadline));
+        STALL_DEADLINE.set(Some(deadline));
+        set_thread_stall_timeout(deadline.saturating_duration_since(now));
+        Self { previous }
+    }
+}
+
+impl Drop for StallTimeoutScope {
+    fn drop(&mut self) {
+        STALL_DEADLINE.set(self.previous);
+        match self.previous {
+            Some(deadline) => {
+                set_thread_stall_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+            // SAFETY: Only resets state of the calling thread.
+            None => unsafe { PerfettoProducerClearThreadStallTimeout() },
+        }
+    }
+}
+
+fn set_thread_stall_timeout(timeout: Duration) {
+    let timeout_ns = u64::try_from(timeout.as_nanos()).unwrap_or(u64::MAX);
+    // SAFETY: Only sets state of the calling thread.
+    unsafe { PerfettoProducerSetThreadStallTimeout(timeout_ns) };
+}
+
+// Calls `cb` for all the active instances (on this thread) of all the registered
+// data source types.
+pub(crate) fn trace_all_data_sources<F>(cb: F)
+where
+    F: FnMut(&mut TraceContextBase),
+{
+    let data_sources = registered_data_sources()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner);
+    trace_data_sources(&data_sources, cb);
+}
+
+// Like `trace_all_data_sources`, but returns false without calling `cb` if the
+// registry is locked, e.g. by the calling thread when it panicked while
+// registering a data source type.
+pub(crate) fn try_trace_all_data_sources<F>(cb: F) -> bool
+where
+    F: FnMut(&mut TraceContextBase),
+{
+    let data_sources = match registered_data_sources().try_lock() {
+        Ok(data_sources) => data_sources,
+        Err(TryLockError::Poisoned(err)) => err.into_inner(),
+        Err(TryLockError::WouldBlock) => return false,
+    };
+    trace_data_sources(&data_sources, cb);
+    true
+}
+
+fn trace_data_sources<F>(data_sources: &[RegisteredDataSource], mut cb: F)
+where
+    F: FnMut(&mut TraceContextBase),
+{
+    let _in_trace = InTraceScope::enter();
+    for data_source in data_sources {
+        let chunk_requests = data_source
+            .chunk_request_stats
+            .then(ChunkRequests::of_thread);
+        let mut ctx = TraceContextBase {
+            // SAFETY: `data_source.impl_` must be a pointer to a registered data
+            // source, which is the case for all the entries of the registry.
+            iterator: unsafe { PerfettoDsImplTraceIterateBegin(data_source.impl_) },
+            stats: Arc::as_ptr(&data_source.stats),
+            outcome: TraceOutcome::default(),
+            bytes_written: 0,
+            budget: ptr::null(),
+            chunk: ChunkState::default(),
+        };
+        while !ctx.iterator.tracer.is_null() {
+            if !is_instance_rejected(&data_source.rejected_instances, ctx.iterator.inst_id) {
+                cb(&mut ctx);
+            }
+            // SAFETY: See above.
+            unsafe { PerfettoDsImplTraceIterateNext(data_source.impl_, &raw mut ctx.iterator) };
+        }
+        ctx.record_stats(chunk_requests);
     }
 }
 
 unsafe extern "C" fn on_create_incr_trampoline<IncrT: Default + Clear>(
     _ds: *mut PerfettoDsImpl,
-    _inst_id: PerfettoDsInstanceIndex,
+    inst_id: PerfettoDsInstanceIndex,
     _tracer: *mut PerfettoDsTracerImpl,
     _user_arg: *mut c_void,
 ) -> *mut c_void {
-    let boxed = Box::new(IncrT::default());
+    let boxed = Box::new(WriterState {
+        state: IncrT::default(),
+        thread_descriptor_written: false,
+        inst_id,
+    });
     Box::into_raw(boxed) as *mut c_void
 }
 
 unsafe extern "C" fn on_delete_incr_trampoline<IncrT: Default + Clear>(data: *mut c_void) {
     // Reclaims the Box and calls drop.
     //
-    // SAFETY: `data` must be a pointer to a boxed IncrT struct.
-    unsafe { drop(Box::from_raw(data as *mut IncrT)) };
+    // SAFETY: `data` must be a pointer to a boxed WriterState<IncrT> struct.
+    unsafe { drop(Box::from_raw(data as *mut WriterState<IncrT>)) };
 }
 
 unsafe extern "C" fn on_clear_incr_trampoline<IncrT: Default + Clear>(
     incremental_state: *mut c_void,
-    _user_arg: *mut c_void,
+    user_arg: *mut c_void,
 ) -> bool {
     let result = std::panic::catch_unwind(|| {
-        // SAFETY: `incremental_state` must be a pointer to a valid IncrT instance.
-        let state: &mut IncrT = unsafe { &mut *(incremental_state as *mut IncrT) };
-        state.clear();
+        // SAFETY: `incremental_state` must be a pointer to a valid WriterState<IncrT>
+        // instance.
+        let state: &mut WriterState<IncrT> =
+            unsafe { &mut *(incremental_state as *mut WriterState<IncrT>) };
+        state.state.clear();
+        state.thread_descriptor_written = false;
+        // SAFETY: `user_arg` must be a pointer to a boxed DsCallbacks struct.
+        let callbacks: &DsCallbacks = unsafe { &*(user_arg as *const _) };
+        let retired = RetiredGuard::new(&callbacks.retired);
+        if retired.is_retired() {
+            return true;
+        }
+        if let Some(f) = &*callbacks.on_clear_incremental_state.read().unwrap() {
+            f(state.inst_id);
+        }
         true
     });
     match result {
         Ok(success) => success,
         Err(err) => {
-            eprintln!("Fatal panic: {:?}", err);
-            std::process::abort();
+            crate::__sdk_fatal!("Fatal panic: {:?}", err);
         }
     }
 }
@@ -770,6 +2167,20 @@ impl<'a: 'static, IncrT: Default + Clear> DataSource<'a, IncrT> {
             return Err(AlreadyRegisteredError);
         }
         let mut boxed_callbacks = Box::new(args.callbacks);
+        boxed_callbacks.name = name.to_string();
+        self.retired = Arc::clone(&boxed_callbacks.retired);
+        self.rejected_instances = Arc::clone(&boxed_callbacks.rejected_instances);
+        self.started_instances = Arc::clone(&boxed_callbacks.started_instances);
+        self.stats = Arc::clone(&boxed_callbacks.stats);
+        self.sessions = Arc::clone(&boxed_callbacks.sessions);
+        self.thread_descriptors = args.thread_descriptors;
+        self.chunk_request_stats = args.chunk_request_stats;
+        if args.chunk_request_stats {
+            // SAFETY: FFI call with no outstanding preconditions.
+            unsafe { PerfettoProducerSetThreadChunkRequestStatsEnabled(true) };
+        }
+        self.byte_budget = args.byte_budget.is_some();
+        boxed_callbacks.byte_budget = args.byte_budget;
         let user_arg = crate::__box_as_mut_ptr(&mut boxed_callbacks) as *mut c_void;
 
         let writer = PbMsgWriter::new();
@@ -780,6 +2191,11 @@ impl<'a: 'static, IncrT: Default + Clear> DataSource<'a, IncrT> {
             desc.set_name(name);
             desc.set_will_notify_on_stop(args.will_notify_on_stop);
             desc.set_handles_incremental_state_clear(args.handles_incremental_state_clear);
+            if let Some(config_schema) = &args.config_schema {
+                desc.set_config_schema(|schema: &mut DataSourceConfigSchema| {
+                    config_schema.write(schema);
+                });
+            }
         }
         msg.finalize();
         let desc_size = writer.writer.get_written_size();
@@ -794,6 +2210,7 @@ impl<'a: 'static, IncrT: Default + Clear> DataSource<'a, IncrT> {
             PerfettoDsSetOnSetupCallback(ds_impl, Some(on_setup_callback_trampoline));
             PerfettoDsSetOnStartCallback(ds_impl, Some(on_start_callback_trampoline));
             PerfettoDsSetOnStopCallback(ds_impl, Some(on_stop_callback_trampoline));
+            PerfettoDsSetOnDestroyCallback(ds_impl, Some(on_destroy_callback_trampoline));
             PerfettoDsSetOnFlushCallback(ds_impl, Some(on_flush_callback_trampoline));
             PerfettoDsSetOnCreateIncr(ds_impl, Some(on_create_incr_trampoline::<IncrT>));
             PerfettoDsSetOnDeleteIncr(ds_impl, Some(on_delete_incr_trampoline::<IncrT>));
@@ -820,10 +2237,22 @@ impl<'a: 'static, IncrT: Default + Clear> DataSource<'a, IncrT> {
         };
         self.impl_ = ds_impl;
         callbacks.replace(boxed_callbacks);
+        registered_data_sources()
+            .lock()
+            .unwrap()
+            .push(RegisteredDataSource {
+                impl_: ds_impl,
+                rejected_instances: Arc::clone(&self.rejected_instances),
+                stats: Arc::clone(&self.stats),
+                chunk_request_stats: self.chunk_request_stats,
+            });
         Ok(())
     }
 
     /// Returns true if any active instance exists of data source type.
+    ///
+    /// Instances are active as soon as they are set up, see
+    /// [`is_started`](Self::is_started).
     pub fn is_enabled(&self) -> bool {
         // SAFETY: `self.enabled` must be a pointer to a primitive with layout that
         // matches C11 atomic_bool.
@@ -833,8 +2262,34 @@ impl<'a: 'static, IncrT: Default + Clear> DataSource<'a, IncrT> {
         }
     }
 
+    /// Returns true if any instance of the data source type is started, i.e.
+    /// isn't waiting for its tracing session to start.
+    pub fn is_started(&self) -> bool {
+        self.started_instances.load(Ordering::Relaxed) != 0
+    }
+
+    /// Returns statistics of the data written by all instances of the data source
+    /// type since it was registered.
+    ///
+    /// Packets written while the trace writer drops data because the shared
+    /// memory buffer is full are counted in
+    /// [`packets_dropped`](DataSourceStats::packets_dropped); the tracing service
+    /// also accounts the loss in the `TraceStats` of the session. The chunk
+    /// requests that stalled or failed while writing the packets are counted in
+    /// [`stalled_chunk_requests`](DataSourceStats::stalled_chunk_requests) and
+    /// [`dropped_chunk_requests`](DataSourceStats::dropped_chunk_requests) if
+    /// enabled with [`DataSourceArgsBuilder::chunk_request_stats`].
+    pub fn stats(&self) -> DataSourceStats {
+        self.stats.snapshot()
+    }
+
     /// Call `cb` for all the active instances (on this thread) of a data source type.
-    pub fn trace<F>(&self, mut cb: F)
+    ///
+    /// Returns the outcome of the call, e.g. to count the packets dropped
+    /// because the shared memory buffer was full. Whether the call drops or
+    /// blocks in that case depends on the [`DataSourceBufferExhaustedPolicy`] of
+    /// the data source type.
+    pub fn trace<F>(&self, mut cb: F) -> TraceOutcome
     where
         F: FnMut(&mut TraceContext<'_, IncrT>),
     {
@@ -842,6 +2297,8 @@ impl<'a: 'static, IncrT: Default + Clear> DataSource<'a, IncrT> {
         // will return false in that case.
         if crate::__unlikely!(self.is_enabled()) {
             assert!(!self.impl_.is_null());
+            let _in_trace = InTraceScope::enter();
+            let chunk_requests = self.chunk_request_stats.then(ChunkRequests::of_thread);
             let mut ctx = TraceContext::<'_, IncrT> {
                 base: TraceContextBase {
                     // SAFETY: `self.impl_` must be a pointer to a registered data source. Ie.
@@ -849,8 +2306,14 @@ impl<'a: 'static, IncrT: Default + Clear> DataSource<'a, IncrT> {
                     // to be the case as is_enabled() will always return false otherwise and this
                     // cannot be reached.
                     iterator: unsafe { PerfettoDsImplTraceIterateBegin(self.impl_) },
+                    stats: Arc::as_ptr(&self.stats),
+                    outcome: TraceOutcome::default(),
+                    bytes_written: 0,
+                    budget: ptr::null(),
+                    chunk: ChunkState::default(),
                 },
                 impl_: self.impl_,
+                sessions: &self.sessions,
                 _marker: PhantomData,
             };
             loop {
@@ -858,14 +2321,167 @@ impl<'a: 'static, IncrT: Default + Clear> DataSource<'a, IncrT> {
                     break;
                 }
 
-                cb(&mut ctx);
+                if !is_instance_rejected(&self.rejected_instances, ctx.base.iterator.inst_id) {
+                    if self.byte_budget {
+                        ctx.base.budget = self.sessions.budget(ctx.base.iterator.inst_id);
+                    }
+                    if self.thread_descriptors {
+                        ctx.write_thread_descriptor_once();
+                    }
+                    ctx.base.outcome.instances += 1;
+                    crate::self_profiling::measure(|| cb(&mut ctx));
+                }
 
                 // SAFETY: `self.impl_` must be a pointer to a registered data source. Guaranteed
                 // to be the case as is_enabled() will always return false otherwise and this
                 // cannot be reached.
                 unsafe { PerfettoDsImplTraceIterateNext(self.impl_, &raw mut ctx.base.iterator) };
             }
+            ctx.base.record_stats(chunk_requests);
+            return ctx.base.outcome;
+        }
+        TraceOutcome::default()
+    }
+
+    /// Same as [`trace`](Self::trace), but waits up to `timeout` for free space
+    /// when the shared memory buffer is full, regardless of the
+    /// [`DataSourceBufferExhaustedPolicy`] of the data source type. Once
+    /// `timeout` has passed, the rest of the call drops data instead, which is
+    /// reported in the returned outcome. The timeout covers the whole call
+    /// rather than each packet, so that the call waits for `timeout` at most in
+    /// total.
+    pub fn trace_with_timeout<F>(&self, timeout: Duration, cb: F) -> TraceOutcome
+    where
+        F: FnMut(&mut TraceContext<'_, IncrT>),
+    {
+        if !self.is_enabled() {
+            return TraceOutcome::default();
+        }
+        let _stall_timeout = StallTimeoutScope::enter(timeout);
+        self.trace(cb)
+    }
+
+    /// Call `cb` for the active instances (on this thread) of a data source type
+    /// whose config, see [`TraceContext::config`], matches `predicate`.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use perfetto_sdk::data_source::{DataSource, TraceContext};
+    ///
+    /// let data_source: DataSource = DataSource::new();
+    /// // Skip expensive data for sessions that asked for extra guardrails.
+    /// data_source.trace_matching(
+    ///     |config| !config.enable_extra_guardrails,
+    ///     |ctx: &mut TraceContext| {
+    ///         // Write expensive data.
+    ///     },
+    /// );
+    /// ```
+    pub fn trace_matching<P, F>(&self, mut predicate: P, mut cb: F) -> TraceOutcome
+    where
+        P: FnMut(&InstanceConfig) -> bool,
+        F: FnMut(&mut TraceContext<'_, IncrT>),
+    {
+        let mut instances = 0;
+        let mut outcome = self.trace(|ctx: &mut TraceContext<'_, IncrT>| {
+            if ctx.config().is_some_and(|config| predicate(&config)) {
+                instances += 1;
+                cb(ctx);
+            }
+        });
+        outcome.instances = instances;
+        outcome
+    }
+
+    /// Serializes a packet once with `packet_cb` and writes it to all the active
+    /// instances (on this thread) of a data source type, e.g. when several
+    /// concurrent sessions record the same GPU counters.
+    ///
+    /// `fixup_cb` is called for each instance with its incremental state, to add
+    /// the fields that depend on the packet sequence of the instance, such as
+    /// `sequence_flags` or interned ids. These fields are written after the
+    /// shared ones, so they take precedence for non-repeated fields.
+    /// `packet_cb` isn't called if no instance is active.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use perfetto_sdk::{
+    ///     data_source::{DataSource, IncrementalState},
+    ///     protos::trace::trace_packet::{TracePacket, TracePacketSequenceFlags},
+    /// };
+    ///
+    /// let data_source: DataSource = DataSource::new();
+    /// data_source.trace_fanout(
+    ///     |packet: &mut TracePacket| {
+    ///         packet.set_timestamp(1234);
+    ///     },
+    ///     |state: &mut IncrementalState, packet: &mut TracePacket| {
+    ///         if state.was_cleared {
+    ///             packet.set_sequence_flags(
+    ///                 TracePacketSequenceFlags::SeqIncrementalStateCleared as u32,
+    ///             );
+    ///             state.was_cleared = false;
+    ///         }
+    ///     },
+    /// );
+    /// ```
+    pub fn trace_fanout<P, F>(&self, packet_cb: P, mut fixup_cb: F) -> TraceOutcome
+    where
+        P: FnOnce(&mut TracePacket),
+        F: FnMut(&mut IncrT, &mut TracePacket),
+    {
+        let mut packet_cb = Some(packet_cb);
+        let mut shared: Option<Vec<u8>> = None;
+        self.trace(|ctx: &mut TraceContext<'_, IncrT>| {
+            let shared = shared.get_or_insert_with(|| encode_packet(packet_cb.take().unwrap()));
+            ctx.with_incremental_state(|ctx, state| {
+                ctx.add_packet(|packet: &mut TracePacket| {
+                    packet.msg.append_bytes(shared);
+                    fixup_cb(state, packet);
+                });
+            });
+        })
+    }
+
+    /// Retires the data source type, e.g. before unloading the code of its
+    /// callbacks.
+    ///
+    /// Registrations can't be undone with the tracing service, so the data
+    /// source type stays registered, but all its current and future instances
+    /// are rejected and the callbacks passed to [`register`](Self::register) are
+    /// dropped. Waits for the callbacks that are running to return.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from one of the callbacks of the data source type,
+    /// which would otherwise wait for itself to return.
+    pub fn retire(&self) {
+        assert!(
+            !is_running_callback(&self.retired),
+            "A data source type can't be retired from its own callbacks."
+        );
+        let callbacks = self.callbacks.lock().unwrap();
+        // Shared with the trampolines, see `DsCallbacks`.
+        let Some(callbacks) = callbacks.as_ref() else {
+            return;
+        };
+        let mut retired = self.retired.write().unwrap();
+        if *retired {
+            return;
         }
+        *retired = true;
+        self.rejected_instances.store(u32::MAX, Ordering::Relaxed);
+        self.started_instances.store(0, Ordering::Relaxed);
+        *callbacks.instance.lock().unwrap() = InstanceCallbacks::default();
+        *callbacks.on_clear_incremental_state.write().unwrap() = None;
+    }
+
+    /// Returns true if the data source type has been retired, see
+    /// [`retire`](Self::retire).
+    pub fn is_retired(&self) -> bool {
+        *self.retired.read().unwrap()
     }
 }
 
@@ -885,6 +2501,14 @@ impl<'a: 'static, IncrT: Default + Clear> Default for DataSource<'a, IncrT> {
             enabled: &raw mut perfetto_atomic_false,
             impl_: ptr::null_mut(),
             callbacks: Mutex::new(None),
+            rejected_instances: Arc::default(),
+            started_instances: Arc::default(),
+            stats: Arc::default(),
+            sessions: Arc::default(),
+            retired: Arc::default(),
+            thread_descriptors: false,
+            chunk_request_stats: false,
+            byte_budget: false,
             _marker: PhantomData,
         }
     }
@@ -899,30 +2523,16 @@ unsafe impl<'a: 'static, IncrT: Default + Clear> Sync for DataSource<'a, IncrT>
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tests::{
-        PRODUCER_SHMEM_SIZE_HINT_KB, TracingSessionBuilder, acquire_test_environment,
+    use crate::{
+        test_util::{DATA_SOURCE_NAME, data_source},
+        tests::{PRODUCER_SHMEM_SIZE_HINT_KB, TracingSessionBuilder, acquire_test_environment},
     };
     use std::{error::Error, sync::OnceLock};
 
-    const DATA_SOURCE_NAME: &str = "com.example.custom_data_source";
-    static DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
-
-    fn get_data_source() -> &'static DataSource<'static> {
-        DATA_SOURCE.get_or_init(|| {
-            let data_source_args = DataSourceArgsBuilder::new()
-                .buffer_exhausted_policy(DataSourceBufferExhaustedPolicy::StallAndAbort);
-            let mut data_source = DataSource::new();
-            data_source
-                .register(DATA_SOURCE_NAME, data_source_args.build())
-                .expect("failed to register data source");
-            data_source
-        })
-    }
-
     #[test]
     fn is_enabled() -> Result<(), Box<dyn Error>> {
         let _lock = acquire_test_environment();
-        let data_source = get_data_source();
+        let data_source = data_source();
         assert!(!data_source.is_enabled());
         let mut session = TracingSessionBuilder::new()
             .set_data_source_name(DATA_SOURCE_NAME)
@@ -939,7 +2549,7 @@ mod tests {
         use crate::protos::trace::{test_event::*, trace::*, trace_packet::*};
         use std::sync::{Arc, Mutex};
         let _lock = acquire_test_environment();
-        let data_source = get_data_source();
+        let data_source = data_source();
         let mut session = TracingSessionBuilder::new()
             .set_data_source_name(DATA_SOURCE_NAME)
             .build()?;
@@ -990,7 +2600,7 @@ mod tests {
         use crate::protos::trace::{test_event::*, trace::*, trace_packet::*};
         use std::sync::{Arc, Mutex};
         let _lock = acquire_test_environment();
-        let data_source = get_data_source();
+        let data_source = data_source();
         let mut session = TracingSessionBuilder::new()
             .set_data_source_name(DATA_SOURCE_NAME)
             .build()?;
@@ -1038,6 +2648,884 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn trace_matching() -> Result<(), Box<dyn Error>> {
+        use crate::protos::trace::trace_packet::TracePacket;
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let mut matched = 0;
+        data_source.trace_matching(
+            |config| config.name == DATA_SOURCE_NAME,
+            |ctx: &mut TraceContext| {
+                matched += 1;
+                ctx.add_packet(|_packet: &mut TracePacket| {});
+            },
+        );
+        data_source.trace_matching(
+            |config| config.enable_extra_guardrails,
+            |_ctx: &mut TraceContext| {
+                matched += 1;
+            },
+        );
+        session.stop_blocking();
+        assert_eq!(matched, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn session_info() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let mut sessions = Vec::new();
+        let mut instance_index = 0;
+        data_source.trace(|ctx: &mut TraceContext| {
+            instance_index = ctx.instance_index();
+            sessions.push((ctx.session(), ctx.config().unwrap()));
+        });
+        session.stop_blocking();
+        assert_eq!(sessions.len(), 1);
+        let (info, config) = &sessions[0];
+        assert_eq!(config.name, DATA_SOURCE_NAME);
+        assert_ne!(info.tracing_session_id, 0);
+        assert_eq!(
+            *info,
+            SessionInfo {
+                tracing_session_id: config.tracing_session_id,
+                target_buffer: config.target_buffer,
+            }
+        );
+        // The session of the instance is cleared once it's destroyed.
+        drop(session);
+        let sessions = &data_source.sessions;
+        for _ in 0..200 {
+            if sessions.config(instance_index).is_none() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(sessions.config(instance_index), None);
+        assert_eq!(sessions.get(instance_index), SessionInfo::default());
+        Ok(())
+    }
+
+    #[test]
+    fn rejected_setup() -> Result<(), Box<dyn Error>> {
+        const REJECTING_DATA_SOURCE_NAME: &str = "com.example.rejecting_data_source";
+        static REJECTING_DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+        static STARTED: AtomicBool = AtomicBool::new(false);
+        let _lock = acquire_test_environment();
+        let data_source = REJECTING_DATA_SOURCE.get_or_init(|| {
+            let data_source_args = DataSourceArgsBuilder::new()
+                .try_on_setup(|_inst_id, _config, _args| {
+                    Err(SetupError::Unsupported("counter id 42".to_string()))
+                })
+                .on_start(|_inst_id, _args| STARTED.store(true, Ordering::Relaxed));
+            let mut data_source = DataSource::new();
+            data_source
+                .register(REJECTING_DATA_SOURCE_NAME, data_source_args.build())
+                .expect("failed to register data source");
+            data_source
+        });
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(REJECTING_DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let mut traced = false;
+        data_source.trace(|_ctx: &mut TraceContext| traced = true);
+        session.stop_blocking();
+        assert!(!traced);
+        assert!(!STARTED.load(Ordering::Relaxed));
+        assert_eq!(data_source.stats().rejected_setups, 1);
+        assert_eq!(session.get_trace_stats()?.data_source_setup_failures, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn sharded_counter() {
+        let counter = ShardedCounter::new();
+        std::thread::scope(|scope| {
+            for _ in 0..COUNTER_SHARDS + 1 {
+                scope.spawn(|| {
+                    for _ in 0..100 {
+                        counter.add(2);
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.get(), (COUNTER_SHARDS as u64 + 1) * 200);
+    }
+
+    #[test]
+    fn stop_timeout() -> Result<(), Box<dyn Error>> {
+        const SLOW_STOP_DATA_SOURCE_NAME: &str = "com.example.slow_stop_data_source";
+        static SLOW_STOP_DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+        static SESSION_STOP_TIMEOUT_MS: AtomicU32 = AtomicU32::new(0);
+        let _lock = acquire_test_environment();
+        let data_source = SLOW_STOP_DATA_SOURCE.get_or_init(|| {
+            let data_source_args = DataSourceArgsBuilder::new()
+                .will_notify_on_stop(true)
+                .stop_timeout(Duration::from_millis(1))
+                .on_setup(|_inst_id, config, _args| {
+                    let config = InstanceConfig::decode(config).unwrap();
+                    SESSION_STOP_TIMEOUT_MS.store(config.stop_timeout_ms, Ordering::Relaxed);
+                })
+                .on_stop(|_inst_id, args| {
+                    let guard = args.postpone();
+                    assert!(guard.remaining() <= Duration::from_millis(1));
+                    std::thread::spawn(move || {
+                        std::thread::sleep(Duration::from_millis(20));
+                        drop(guard);
+                    });
+                });
+            let mut data_source = DataSource::new();
+            data_source
+                .register(SLOW_STOP_DATA_SOURCE_NAME, data_source_args.build())
+                .expect("failed to register data source");
+            data_source
+        });
+        let config = crate::trace_config::TraceConfigBuilder::ring_buffer(1024)
+            .data_source(SLOW_STOP_DATA_SOURCE_NAME)
+            .data_source_stop_timeout(Duration::from_secs(2))
+            .build()?;
+        let mut session = crate::tracing_session::TracingSession::in_process()?;
+        session.setup(&config);
+        session.start_blocking();
+        assert_eq!(SESSION_STOP_TIMEOUT_MS.load(Ordering::Relaxed), 2000);
+        session.stop_blocking();
+        assert_eq!(data_source.stats().slow_stops, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn watchdog() -> Result<(), Box<dyn Error>> {
+        const WATCHDOG_DATA_SOURCE_NAME: &str = "com.example.watchdog_data_source";
+        static WATCHDOG_DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+        let _lock = acquire_test_environment();
+        let data_source = WATCHDOG_DATA_SOURCE.get_or_init(|| {
+            let data_source_args = DataSourceArgsBuilder::new()
+                .watchdog(Watchdog::log(Duration::from_millis(10)))
+                .on_start(|_inst_id, _args| std::thread::sleep(Duration::from_millis(100)));
+            let mut data_source = DataSource::new();
+            data_source
+                .register(WATCHDOG_DATA_SOURCE_NAME, data_source_args.build())
+                .expect("failed to register data source");
+            data_source
+        });
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(WATCHDOG_DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        session.stop_blocking();
+        assert_eq!(data_source.stats().slow_callbacks, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn deferred_start() -> Result<(), Box<dyn Error>> {
+        const DEFERRED_DATA_SOURCE_NAME: &str = "com.example.deferred_data_source";
+        const START_TRIGGER: &str = "com.example.deferred_start";
+        static DEFERRED_DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+        static STATES: Mutex<Vec<InstanceState>> = Mutex::new(Vec::new());
+        let _lock = acquire_test_environment();
+        let data_source = DEFERRED_DATA_SOURCE.get_or_init(|| {
+            let data_source_args = DataSourceArgsBuilder::new()
+                .on_state_change(|_inst_id, state| STATES.lock().unwrap().push(state));
+            let mut data_source = DataSource::new();
+            data_source
+                .register(DEFERRED_DATA_SOURCE_NAME, data_source_args.build())
+                .expect("failed to register data source");
+            data_source
+        });
+        let wait_for_state = |state| {
+            for _ in 0..500 {
+                if STATES.lock().unwrap().last() == Some(&state) {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            panic!("timed out waiting for {:?}", state);
+        };
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DEFERRED_DATA_SOURCE_NAME)
+            .set_start_trigger(START_TRIGGER)
+            .build()?;
+        session.start_async();
+        wait_for_state(InstanceState::SetUp);
+        assert!(data_source.is_enabled());
+        assert!(!data_source.is_started());
+        let mut traced = false;
+        data_source.trace(|_ctx: &mut TraceContext| traced = true);
+        assert!(!traced);
+
+        crate::producer::Producer::activate_trigger(START_TRIGGER, Duration::from_secs(10))?;
+        wait_for_state(InstanceState::Started);
+        assert!(data_source.is_started());
+        data_source.trace(|_ctx: &mut TraceContext| traced = true);
+        assert!(traced);
+        session.stop_blocking();
+        assert!(!data_source.is_started());
+        assert_eq!(
+            *STATES.lock().unwrap(),
+            [
+                InstanceState::SetUp,
+                InstanceState::Started,
+                InstanceState::Stopped
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn clear_incremental_state() -> Result<(), Box<dyn Error>> {
+        const CLEARING_DATA_SOURCE_NAME: &str = "com.example.clearing_data_source";
+        static CLEARING_DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+        static CLEARED: AtomicU32 = AtomicU32::new(u32::MAX);
+        let _lock = acquire_test_environment();
+        let data_source = CLEARING_DATA_SOURCE.get_or_init(|| {
+            let data_source_args = DataSourceArgsBuilder::new()
+                .handles_incremental_state_clear(true)
+                .on_clear_incremental_state(|inst_id| CLEARED.store(inst_id, Ordering::Relaxed));
+            let mut data_source = DataSource::new();
+            data_source
+                .register(CLEARING_DATA_SOURCE_NAME, data_source_args.build())
+                .expect("failed to register data source");
+            data_source
+        });
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(CLEARING_DATA_SOURCE_NAME)
+            .set_incremental_state_clear_period_ms(10)
+            .build()?;
+        session.start_blocking();
+        let mut instance_index = None;
+        for _ in 0..200 {
+            // Incremental state is created on first use by a trace writer.
+            data_source.trace(|ctx: &mut TraceContext| {
+                instance_index = Some(ctx.instance_index());
+                ctx.with_incremental_state(|_ctx, state| state.was_cleared = false);
+            });
+            if CLEARED.load(Ordering::Relaxed) != u32::MAX {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        session.stop_blocking();
+        assert_eq!(Some(CLEARED.load(Ordering::Relaxed)), instance_index);
+        Ok(())
+    }
+
+    #[test]
+    fn clone_started() -> Result<(), Box<dyn Error>> {
+        use crate::protos::trace::test_event::TestEvent;
+        use crate::{
+            tests::read_trace_data, trace_reader::TraceReader, tracing_session::TracingSession,
+        };
+        const CLONED_DATA_SOURCE_NAME: &str = "com.example.cloned_data_source";
+        static CLONED_DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+        static FLUSHES: Mutex<Vec<(u32, bool)>> = Mutex::new(Vec::new());
+        let _lock = acquire_test_environment();
+        let data_source = CLONED_DATA_SOURCE.get_or_init(|| {
+            let data_source_args = DataSourceArgsBuilder::new()
+                .on_clone_started(|inst_id, _args| {
+                    FLUSHES.lock().unwrap().push((inst_id, true));
+                    // Written to the clone.
+                    CLONED_DATA_SOURCE
+                        .get()
+                        .unwrap()
+                        .trace(|ctx: &mut TraceContext| {
+                            ctx.add_packet(|packet: &mut TracePacket| {
+                                packet.set_for_testing(|for_testing: &mut TestEvent| {
+                                    for_testing.set_str("Clone snapshot");
+                                });
+                            });
+                        });
+                })
+                .on_flush(|inst_id, args| {
+                    FLUSHES.lock().unwrap().push((inst_id, args.is_clone()));
+                });
+            let mut data_source = DataSource::new();
+            data_source
+                .register(CLONED_DATA_SOURCE_NAME, data_source_args.build())
+                .expect("failed to register data source");
+            data_source
+        });
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(CLONED_DATA_SOURCE_NAME)
+            .set_unique_session_name("clone-started-test")
+            .build()?;
+        session.start_blocking();
+        let mut instance_index = 0;
+        data_source.trace(|ctx: &mut TraceContext| instance_index = ctx.instance_index());
+        // A plain flush isn't a clone.
+        session.flush_blocking(Duration::from_secs(10));
+        assert_eq!(*FLUSHES.lock().unwrap(), vec![(instance_index, false)]);
+        FLUSHES.lock().unwrap().clear();
+
+        let mut clone = TracingSession::in_process()?;
+        clone.clone_session("clone-started-test")?;
+        assert_eq!(
+            *FLUSHES.lock().unwrap(),
+            vec![(instance_index, true), (instance_index, true)]
+        );
+        let data = read_trace_data(&mut clone);
+        let packets = TraceReader::new(&data).collect::<Result<Vec<_>, _>>()?;
+        assert!(
+            packets
+                .iter()
+                .any(|packet| packet.windows(14).any(|w| w == b"Clone snapshot"))
+        );
+        session.stop_blocking();
+        Ok(())
+    }
+
+    #[test]
+    fn write_stats() -> Result<(), Box<dyn Error>> {
+        use crate::pb_decoder::{PbDecoder, PbDecoderField};
+        use crate::protos::trace::{test_event::*, trace_packet::*};
+        use crate::{tests::read_trace_data, trace_reader::TraceReader};
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let stats = data_source.stats();
+        data_source.trace(|ctx: &mut TraceContext| {
+            for index in 0..3 {
+                ctx.add_packet(|packet: &mut TracePacket| {
+                    packet.set_for_testing(|for_testing: &mut TestEvent| {
+                        for_testing.set_counter(index);
+                    });
+                });
+            }
+        });
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+        let mut counters = vec![];
+        for packet in TraceReader::new(&data) {
+            for field in PbDecoder::new(&packet?) {
+                const FOR_TESTING_ID: u32 = TracePacketFieldNumber::ForTesting as u32;
+                if let (FOR_TESTING_ID, PbDecoderField::Delimited(data)) = field? {
+                    for field in PbDecoder::new(data) {
+                        const COUNTER_ID: u32 = TestEventFieldNumber::Counter as u32;
+                        if let (COUNTER_ID, PbDecoderField::Varint(value)) = field? {
+                            counters.push(value);
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(counters, vec![0, 1, 2]);
+        let new_stats = data_source.stats();
+        assert_eq!(new_stats.packets_written, stats.packets_written + 3);
+        // Each packet contains at least a `for_testing` tag and size and a two
+        // byte `counter` field.
+        assert!(new_stats.bytes_written >= stats.bytes_written + 3 * 4);
+        Ok(())
+    }
+
+    #[test]
+    fn add_packets() -> Result<(), Box<dyn Error>> {
+        use crate::pb_decoder::{PbDecoder, PbDecoderField};
+        use crate::protos::trace::{test_event::*, trace_packet::*};
+        use crate::{tests::read_trace_data, trace_reader::TraceReader};
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let stats = data_source.stats();
+        let outcome = data_source.trace(|ctx: &mut TraceContext| {
+            ctx.add_packets(3, |index, packet: &mut TracePacket| {
+                packet.set_for_testing(|for_testing: &mut TestEvent| {
+                    for_testing.set_counter(index as u64);
+                });
+            });
+            ctx.add_packets(0, |_, _packet: &mut TracePacket| unreachable!());
+        });
+        assert_eq!(outcome.packets_written, 3);
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+        let mut counters = vec![];
+        for packet in TraceReader::new(&data) {
+            for field in PbDecoder::new(&packet?) {
+                const FOR_TESTING_ID: u32 = TracePacketFieldNumber::ForTesting as u32;
+                if let (FOR_TESTING_ID, PbDecoderField::Delimited(data)) = field? {
+                    for field in PbDecoder::new(data) {
+                        const COUNTER_ID: u32 = TestEventFieldNumber::Counter as u32;
+                        if let (COUNTER_ID, PbDecoderField::Varint(value)) = field? {
+                            counters.push(value);
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(counters, vec![0, 1, 2]);
+        let new_stats = data_source.stats();
+        assert_eq!(new_stats.packets_written, stats.packets_written + 3);
+        assert!(new_stats.bytes_written >= stats.bytes_written + 3 * 4);
+        Ok(())
+    }
+
+    #[test]
+    fn reserve() -> Result<(), Box<dyn Error>> {
+        use crate::pb_decoder::{PbDecoder, PbDecoderField};
+        use crate::protos::trace::{test_event::*, trace_packet::*};
+        use crate::{tests::read_trace_data, trace_reader::TraceReader};
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        data_source.trace(|ctx: &mut TraceContext| {
+            assert_eq!(ctx.remaining_chunk_bytes(), None);
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_for_testing(|for_testing: &mut TestEvent| {
+                    for_testing.set_str("first");
+                });
+            });
+            let remaining = ctx.remaining_chunk_bytes().unwrap();
+            ctx.reserve(remaining + 1);
+            ctx.add_packet(|packet: &mut TracePacket| {
+                // The fields of the packet start in a new chunk.
+                assert!(packet.msg.remaining_chunk_bytes() > remaining);
+                packet.set_for_testing(|for_testing: &mut TestEvent| {
+                    for_testing.set_str("second");
+                });
+            });
+        });
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+        let mut strs = vec![];
+        for packet in TraceReader::new(&data) {
+            for field in PbDecoder::new(&packet?) {
+                const FOR_TESTING_ID: u32 = TracePacketFieldNumber::ForTesting as u32;
+                if let (FOR_TESTING_ID, PbDecoderField::Delimited(data)) = field? {
+                    for field in PbDecoder::new(data) {
+                        const STR_ID: u32 = TestEventFieldNumber::Str as u32;
+                        if let (STR_ID, PbDecoderField::Delimited(value)) = field? {
+                            strs.push(String::from_utf8(value.to_vec())?);
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(strs, vec!["first", "second"]);
+        Ok(())
+    }
+
+    #[test]
+    fn trace_fanout() -> Result<(), Box<dyn Error>> {
+        use crate::pb_decoder::{PbDecoder, PbDecoderField};
+        use crate::protos::trace::{test_event::*, trace_packet::*};
+        use crate::{tests::read_trace_data, trace_reader::TraceReader};
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut sessions = [
+            TracingSessionBuilder::new()
+                .set_data_source_name(DATA_SOURCE_NAME)
+                .build()?,
+            TracingSessionBuilder::new()
+                .set_data_source_name(DATA_SOURCE_NAME)
+                .build()?,
+        ];
+        for session in &mut sessions {
+            session.start_blocking();
+        }
+        let mut encoded = 0;
+        let outcome = data_source.trace_fanout(
+            |packet: &mut TracePacket| {
+                encoded += 1;
+                packet.set_for_testing(|for_testing: &mut TestEvent| {
+                    for_testing.set_str("shared");
+                });
+            },
+            |state: &mut IncrementalState, packet: &mut TracePacket| {
+                if state.was_cleared {
+                    packet.set_sequence_flags(
+                        TracePacketSequenceFlags::SeqIncrementalStateCleared as u32,
+                    );
+                    state.was_cleared = false;
+                }
+            },
+        );
+        assert_eq!(encoded, 1);
+        assert_eq!(outcome.instances, 2);
+        assert_eq!(outcome.packets_written, 2);
+        for session in &mut sessions {
+            session.stop_blocking();
+            let data = read_trace_data(session);
+            let mut packets = vec![];
+            for packet in TraceReader::new(&data) {
+                let mut str = None;
+                let mut flags = None;
+                for field in PbDecoder::new(&packet?) {
+                    match field? {
+                        (id, PbDecoderField::Delimited(data))
+                            if id == TracePacketFieldNumber::ForTesting as u32 =>
+                        {
+                            for field in PbDecoder::new(data) {
+                                const STR_ID: u32 = TestEventFieldNumber::Str as u32;
+                                if let (STR_ID, PbDecoderField::Delimited(value)) = field? {
+                                    str = Some(String::from_utf8(value.to_vec())?);
+                                }
+                            }
+                        }
+                        (id, PbDecoderField::Varint(value))
+                            if id == TracePacketFieldNumber::SequenceFlags as u32 =>
+                        {
+                            flags = Some(value);
+                        }
+                        _ => {}
+                    }
+                }
+                if str.is_some() {
+                    packets.push((str, flags));
+                }
+            }
+            assert_eq!(
+                packets,
+                vec![(
+                    Some("shared".to_string()),
+                    Some(TracePacketSequenceFlags::SeqIncrementalStateCleared as u64)
+                )]
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn byte_budget() -> Result<(), Box<dyn Error>> {
+        use crate::pb_decoder::{PbDecoder, PbDecoderField};
+        use crate::protos::trace::{test_event::*, trace_packet::*};
+        use crate::{tests::read_trace_data, trace_reader::TraceReader};
+        const BUDGET_DATA_SOURCE_NAME: &str = "com.example.budget_data_source";
+        const BUDGET_FIELD_ID: u32 = 5000;
+        static BUDGET_DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+        let _lock = acquire_test_environment();
+        let data_source = BUDGET_DATA_SOURCE.get_or_init(|| {
+            let data_source_args = DataSourceArgsBuilder::new()
+                .byte_budget(ByteBudget::drop(0).config_field(BUDGET_FIELD_ID));
+            let mut data_source = DataSource::new();
+            data_source
+                .register(BUDGET_DATA_SOURCE_NAME, data_source_args.build())
+                .expect("failed to register data source");
+            data_source
+        });
+        let stats = data_source.stats();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(BUDGET_DATA_SOURCE_NAME)
+            .add_data_source_config_field(BUDGET_FIELD_ID, 16)
+            .build()?;
+        session.start_blocking();
+        for index in 0..10 {
+            data_source.trace(|ctx: &mut TraceContext| {
+                ctx.add_packet(|packet: &mut TracePacket| {
+                    packet.set_for_testing(|for_testing: &mut TestEvent| {
+                        for_testing.set_counter(index);
+                    });
+                });
+            });
+        }
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+        let mut counters = vec![];
+        let mut markers = 0;
+        for packet in TraceReader::new(&data) {
+            let mut counter = None;
+            let mut dropped = false;
+            for field in PbDecoder::new(&packet?) {
+                const FOR_TESTING_ID: u32 = TracePacketFieldNumber::ForTesting as u32;
+                const DROPPED_ID: u32 = TracePacketFieldNumber::PreviousPacketDropped as u32;
+                match field? {
+                    (FOR_TESTING_ID, PbDecoderField::Delimited(data)) => {
+                        for field in PbDecoder::new(data) {
+                            const COUNTER_ID: u32 = TestEventFieldNumber::Counter as u32;
+                            if let (COUNTER_ID, PbDecoderField::Varint(value)) = field? {
+                                counter = Some(value);
+                            }
+                        }
+                    }
+                    (DROPPED_ID, PbDecoderField::Varint(value)) => dropped = value != 0,
+                    _ => {}
+                }
+            }
+            match counter {
+                Some(counter) => counters.push(counter),
+                None if dropped => markers += 1,
+                None => {}
+            }
+        }
+        // The packets are written until the budget is exhausted, then a marker
+        // is written once and the other packets are throttled.
+        assert!(!counters.is_empty() && counters.len() < 10);
+        assert_eq!(counters, (0..counters.len() as u64).collect::<Vec<_>>());
+        assert_eq!(markers, 1);
+        assert_eq!(
+            data_source.stats().packets_throttled - stats.packets_throttled,
+            10 - counters.len() as u64
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+    fn thread_descriptors() -> Result<(), Box<dyn Error>> {
+        use crate::pb_decoder::{PbDecoder, PbDecoderField};
+        use crate::protos::trace::{
+            trace_packet::TracePacketFieldNumber,
+            track_event::{
+                thread_descriptor::ThreadDescriptorFieldNumber,
+                track_descriptor::TrackDescriptorFieldNumber,
+            },
+        };
+        use crate::{tests::read_trace_data, trace_reader::TraceReader};
+        const THREAD_DATA_SOURCE_NAME: &str = "com.example.thread_data_source";
+        const THREAD_NAME: &str = "thread_descriptors_worker";
+        static THREAD_DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+        let _lock = acquire_test_environment();
+        let data_source = THREAD_DATA_SOURCE.get_or_init(|| {
+            let data_source_args = DataSourceArgsBuilder::new().thread_descriptors(true);
+            let mut data_source = DataSource::new();
+            data_source
+                .register(THREAD_DATA_SOURCE_NAME, data_source_args.build())
+                .expect("failed to register data source");
+            data_source
+        });
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(THREAD_DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        std::thread::Builder::new()
+            .name(THREAD_NAME.to_string())
+            .spawn(|| {
+                for _ in 0..2 {
+                    data_source.trace(|ctx: &mut TraceContext| {
+                        ctx.add_packet(|_packet: &mut TracePacket| {});
+                    });
+                }
+            })?
+            .join()
+            .unwrap();
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+        let mut threads = vec![];
+        for packet in TraceReader::new(&data) {
+            for field in PbDecoder::new(&packet?) {
+                const TRACK_DESCRIPTOR_ID: u32 = TracePacketFieldNumber::TrackDescriptor as u32;
+                const THREAD_ID: u32 = TrackDescriptorFieldNumber::Thread as u32;
+                const TID_ID: u32 = ThreadDescriptorFieldNumber::Tid as u32;
+                const THREAD_NAME_ID: u32 = ThreadDescriptorFieldNumber::ThreadName as u32;
+                let (TRACK_DESCRIPTOR_ID, PbDecoderField::Delimited(desc)) = field? else {
+                    continue;
+                };
+                for field in PbDecoder::new(desc) {
+                    let (THREAD_ID, PbDecoderField::Delimited(thread)) = field? else {
+                        continue;
+                    };
+                    let mut tid = None;
+                    let mut name = None;
+                    for field in PbDecoder::new(thread) {
+                        match field? {
+                            (TID_ID, PbDecoderField::Varint(value)) => tid = Some(value),
+                            (THREAD_NAME_ID, PbDecoderField::Delimited(value)) => {
+                                name = Some(String::from_utf8_lossy(value).into_owned())
+                            }
+                            _ => {}
+                        }
+                    }
+                    threads.push((tid.is_some_and(|tid| tid != 0), name));
+                }
+            }
+        }
+        // The descriptor is only written by the first trace call of the thread.
+        assert_eq!(threads, vec![(true, Some(THREAD_NAME.to_string()))]);
+        Ok(())
+    }
+
+    const OUTCOME_DATA_SOURCE_NAME: &str = "com.example.outcome_data_source";
+
+    // Data source that drops packets when the shared memory buffer is full.
+    fn get_outcome_data_source() -> &'static DataSource<'static> {
+        static OUTCOME_DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+        OUTCOME_DATA_SOURCE.get_or_init(|| {
+            let data_source_args = DataSourceArgsBuilder::new()
+                .buffer_exhausted_policy(DataSourceBufferExhaustedPolicy::Drop)
+                .chunk_request_stats(true);
+            let mut data_source = DataSource::new();
+            data_source
+                .register(OUTCOME_DATA_SOURCE_NAME, data_source_args.build())
+                .expect("failed to register data source");
+            data_source
+        })
+    }
+
+    #[test]
+    fn trace_outcome() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = get_outcome_data_source();
+        let outcome = data_source.trace(|ctx: &mut TraceContext| {
+            ctx.add_packet(|_packet: &mut TracePacket| {});
+        });
+        assert_eq!(outcome, TraceOutcome::default());
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(OUTCOME_DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let outcome = data_source.trace(|ctx: &mut TraceContext| {
+            for _ in 0..3 {
+                ctx.add_packet(|_packet: &mut TracePacket| {});
+            }
+        });
+        assert_eq!(
+            outcome,
+            TraceOutcome {
+                instances: 1,
+                packets_written: 3,
+                packets_dropped: 0,
+            }
+        );
+        session.stop_blocking();
+        Ok(())
+    }
+
+    #[test]
+    fn trace_outcome_dropped() -> Result<(), Box<dyn Error>> {
+        use crate::protos::trace::test_event::TestEvent;
+        let _lock = acquire_test_environment();
+        let data_source = get_outcome_data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(OUTCOME_DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        // Write many times the size of the shared memory buffer in a single
+        // trace call, faster than the service can drain it.
+        let payload = "x".repeat(1024);
+        let packet_count = PRODUCER_SHMEM_SIZE_HINT_KB as usize * 64;
+        let before = data_source.stats();
+        let outcome = data_source.trace(|ctx: &mut TraceContext| {
+            for _ in 0..packet_count {
+                ctx.add_packet(|packet: &mut TracePacket| {
+                    packet.set_for_testing(|for_testing: &mut TestEvent| {
+                        for_testing.set_str(&payload);
+                    });
+                });
+            }
+        });
+        session.stop_blocking();
+        assert_eq!(outcome.instances, 1);
+        assert_eq!(outcome.packets_written, packet_count as u64);
+        assert!(outcome.dropped());
+        assert!(outcome.packets_dropped < outcome.packets_written);
+        let stats = data_source.stats();
+        assert!(stats.packets_dropped - before.packets_dropped >= outcome.packets_dropped);
+        // Chunk requests fail instead of stalling with the drop policy.
+        assert!(stats.dropped_chunk_requests > before.dropped_chunk_requests);
+        assert_eq!(stats.stalled_chunk_requests, before.stalled_chunk_requests);
+        Ok(())
+    }
+
+    #[test]
+    fn trace_with_timeout() -> Result<(), Box<dyn Error>> {
+        use crate::protos::trace::test_event::TestEvent;
+        let _lock = acquire_test_environment();
+        let data_source = get_outcome_data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(OUTCOME_DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let payload = "x".repeat(1024);
+        let packet_count = PRODUCER_SHMEM_SIZE_HINT_KB as usize * 64;
+        let trace = |timeout| {
+            data_source.trace_with_timeout(timeout, |ctx: &mut TraceContext| {
+                for _ in 0..packet_count {
+                    ctx.add_packet(|packet: &mut TracePacket| {
+                        packet.set_for_testing(|for_testing: &mut TestEvent| {
+                            for_testing.set_str(&payload);
+                        });
+                    });
+                }
+            })
+        };
+        // Chunk requests stall instead of failing right away with the drop
+        // policy, until the service frees chunks.
+        let before = data_source.stats();
+        let outcome = trace(Duration::from_secs(60));
+        assert_eq!(outcome.packets_written, packet_count as u64);
+        let stats = data_source.stats();
+        assert!(stats.stalled_chunk_requests > before.stalled_chunk_requests);
+        // Chunk requests fail once the timeout has passed.
+        let before = data_source.stats();
+        let outcome = trace(Duration::ZERO);
+        assert!(outcome.dropped());
+        let stats = data_source.stats();
+        assert!(stats.dropped_chunk_requests > before.dropped_chunk_requests);
+        session.stop_blocking();
+        // The policy applies again after the call.
+        assert_eq!(STALL_DEADLINE.get(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn retire() -> Result<(), Box<dyn Error>> {
+        const RETIRED_DATA_SOURCE_NAME: &str = "com.example.retired_data_source";
+        static RETIRED_DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+        static SETUPS: AtomicU32 = AtomicU32::new(0);
+        let _lock = acquire_test_environment();
+        let data_source = RETIRED_DATA_SOURCE.get_or_init(|| {
+            let data_source_args = DataSourceArgsBuilder::new().on_setup(|_, _, _| {
+                SETUPS.fetch_add(1, Ordering::Relaxed);
+            });
+            let mut data_source = DataSource::new();
+            data_source
+                .register(RETIRED_DATA_SOURCE_NAME, data_source_args.build())
+                .expect("failed to register data source");
+            data_source
+        });
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(RETIRED_DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        assert!(data_source.is_started());
+        data_source.retire();
+        assert!(data_source.is_retired());
+        assert!(!data_source.is_started());
+        let mut traced = false;
+        data_source.trace(|_ctx: &mut TraceContext| traced = true);
+        assert!(!traced);
+        session.stop_blocking();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(RETIRED_DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        data_source.trace(|_ctx: &mut TraceContext| traced = true);
+        assert!(!traced);
+        assert_eq!(session.get_trace_stats()?.data_source_setup_failures, 1);
+        session.stop_blocking();
+        assert_eq!(SETUPS.load(Ordering::Relaxed), 1);
+        assert_eq!(data_source.stats().retired_setups, 1);
+        assert_eq!(data_source.stats().rejected_setups, 0);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be retired from its own callbacks")]
+    fn retire_from_callback() {
+        let data_source = DataSource::new();
+        // Held by the trampolines while the callbacks run.
+        let _retired = RetiredGuard::new(&data_source.retired);
+        data_source.retire();
+    }
+
     #[test]
     fn timestamp() {
         let _lock = acquire_test_environment();
@@ -0,0 +1,265 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{Clear, DataSourceTimestamp, TraceContextBase},
+    protos::trace::{
+        clock_snapshot::{ClockSnapshot, ClockSnapshotClock},
+        trace_packet::{TracePacket, TracePacketDefaults, TracePacketSequenceFlags},
+        track_event::{
+            counter_descriptor::CounterDescriptor,
+            track_descriptor::TrackDescriptor,
+            track_event::{TrackEvent, TrackEventType},
+        },
+    },
+    track_event::TrackEventTrack,
+};
+use std::collections::HashMap;
+
+/// Sequence-scoped clock id used for delta encoded timestamps. Clock ids
+/// 64-127 are reserved for clocks defined by a packet sequence.
+pub const DELTA_COUNTER_CLOCK_ID: u32 = 64;
+
+/// Counter track written by a [`DeltaCounterWriter`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeltaCounterTrack {
+    uuid: u64,
+    parent_uuid: u64,
+    name: String,
+}
+
+impl DeltaCounterTrack {
+    /// Creates a counter track named `name`. The track is nested under
+    /// `parent_uuid` unless it is zero.
+    pub fn new(name: impl Into<String>, parent_uuid: u64) -> Self {
+        let name = name.into();
+        Self {
+            uuid: TrackEventTrack::counter_track_uuid(&name, parent_uuid),
+            parent_uuid,
+            name,
+        }
+    }
+
+    /// Returns the track UUID.
+    pub fn uuid(&self) -> u64 {
+        self.uuid
+    }
+}
+
+/// Writes counter values with both values and timestamps encoded as deltas
+/// against the previous sample on the same packet sequence.
+///
+/// The writer keeps per-sequence state and is meant to be used as (or be
+/// part of) the incremental state of a data source. It emits the clock
+/// snapshot, packet defaults and incremental counter track descriptors that
+/// the deltas depend on whenever that state is (re)created.
+///
+/// Packet timestamps on a sequence written to by a `DeltaCounterWriter`
+/// default to the incremental clock. Other packets written to the same
+/// sequence must set `timestamp_clock_id` explicitly.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     data_source::{DataSource, DataSourceArgsBuilder, DataSourceTimestamp, TraceContext},
+///     delta_counter::{DeltaCounterTrack, DeltaCounterWriter},
+/// };
+///
+/// let mut data_source = DataSource::<DeltaCounterWriter>::new_with_incremental_state_type();
+/// data_source
+///     .register("com.example.counters", DataSourceArgsBuilder::new().build())
+///     .unwrap();
+/// let track = DeltaCounterTrack::new("gpu.busy_cycles", 0);
+/// data_source.trace(|ctx: &mut TraceContext<DeltaCounterWriter>| {
+///     ctx.with_incremental_state(|ctx, writer| {
+///         writer.write_counter(ctx, &track, DataSourceTimestamp::now(), 1234);
+///     });
+/// });
+/// ```
+#[derive(Debug, Default)]
+pub struct DeltaCounterWriter {
+    initialized: bool,
+    clock_id: u32,
+    last_timestamp: u64,
+    last_values: HashMap<u64, i64>,
+}
+
+impl Clear for DeltaCounterWriter {
+    fn clear(&mut self) {
+        self.initialized = false;
+        self.last_values.clear();
+    }
+}
+
+impl DeltaCounterWriter {
+    /// Creates a new writer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes `value` at `timestamp` to the counter `track`.
+    pub fn write_counter(
+        &mut self,
+        ctx: &mut TraceContextBase,
+        track: &DeltaCounterTrack,
+        timestamp: DataSourceTimestamp,
+        value: i64,
+    ) {
+        let ts = timestamp.timestamp();
+        let clock_id = timestamp.clock_id();
+        // Timestamps can only be encoded as unsigned deltas on one clock, so
+        // start over if the clock changes or goes backwards.
+        if !self.initialized || clock_id != self.clock_id || ts < self.last_timestamp {
+            self.reset(ctx, clock_id, ts);
+        }
+
+        if !self.last_values.contains_key(&track.uuid) {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet
+                    .set_sequence_flags(TracePacketSequenceFlags::SeqNeedsIncrementalState.into());
+                packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+                    desc.set_uuid(track.uuid);
+                    if track.parent_uuid != 0 {
+                        desc.set_parent_uuid(track.parent_uuid);
+                    }
+                    desc.set_name(track.name.as_str());
+                    desc.set_counter(|counter: &mut CounterDescriptor| {
+                        counter.set_is_incremental(true);
+                    });
+                });
+            });
+        }
+        let last_value = self.last_values.entry(track.uuid).or_insert(0);
+        let delta_value = value.wrapping_sub(*last_value);
+        *last_value = value;
+        let delta_ts = ts - self.last_timestamp;
+        self.last_timestamp = ts;
+
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet.set_timestamp(delta_ts);
+            packet.set_sequence_flags(TracePacketSequenceFlags::SeqNeedsIncrementalState.into());
+            packet.set_track_event(|event: &mut TrackEvent| {
+                event.set_type(TrackEventType::TypeCounter);
+                event.set_track_uuid(track.uuid);
+                event.set_counter_value(delta_value);
+            });
+        });
+    }
+
+    // Clears the incremental state of the sequence and defines the
+    // incremental clock relative to `clock_id` at `ts`.
+    fn reset(&mut self, ctx: &mut TraceContextBase, clock_id: u32, ts: u64) {
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet.set_sequence_flags(TracePacketSequenceFlags::SeqIncrementalStateCleared.into());
+            packet.set_clock_snapshot(|snapshot: &mut ClockSnapshot| {
+                snapshot.set_clocks(|clock: &mut ClockSnapshotClock| {
+                    clock.set_clock_id(DELTA_COUNTER_CLOCK_ID);
+                    clock.set_timestamp(ts);
+                    clock.set_is_incremental(true);
+                });
+                snapshot.set_clocks(|clock: &mut ClockSnapshotClock| {
+                    clock.set_clock_id(clock_id);
+                    clock.set_timestamp(ts);
+                });
+            });
+            packet.set_trace_packet_defaults(|defaults: &mut TracePacketDefaults| {
+                defaults.set_timestamp_clock_id(DELTA_COUNTER_CLOCK_ID);
+            });
+        });
+        self.initialized = true;
+        self.clock_id = clock_id;
+        self.last_timestamp = ts;
+        self.last_values.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_source::TraceContext,
+        pb_decoder::PbDecoderField,
+        protos::trace::{
+            trace_packet::TracePacketFieldNumber,
+            track_event::{
+                counter_descriptor::CounterDescriptorFieldNumber,
+                track_descriptor::TrackDescriptorFieldNumber, track_event::TrackEventFieldNumber,
+            },
+        },
+        test_util::field,
+        test_util::{data_source_name, data_source_with_state},
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        trace_reader::TraceReader,
+    };
+    use std::{error::Error, time::Duration};
+
+    #[test]
+    fn delta_encoding() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source_with_state::<DeltaCounterWriter>();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(data_source_name::<DeltaCounterWriter>())
+            .build()?;
+        session.start_blocking();
+        let track = DeltaCounterTrack::new("counter", 0);
+        for (ts, value) in [(1000, 10), (1500, 15), (1600, 5)] {
+            data_source.trace(|ctx: &mut TraceContext<DeltaCounterWriter>| {
+                ctx.with_incremental_state(|ctx, writer| {
+                    let timestamp = DataSourceTimestamp::Boot(Duration::from_nanos(ts));
+                    writer.write_counter(ctx, &track, timestamp, value);
+                });
+            });
+        }
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        let mut has_defaults = false;
+        let mut is_incremental = false;
+        let mut samples = vec![];
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            if field(&packet, TracePacketFieldNumber::TracePacketDefaults as u32).is_some() {
+                has_defaults = true;
+            }
+            if let Some(PbDecoderField::Delimited(desc)) =
+                field(&packet, TracePacketFieldNumber::TrackDescriptor as u32)
+                && let Some(PbDecoderField::Delimited(counter)) =
+                    field(desc, TrackDescriptorFieldNumber::Counter as u32)
+            {
+                is_incremental = field(counter, CounterDescriptorFieldNumber::IsIncremental as u32)
+                    == Some(PbDecoderField::Varint(1));
+            }
+            if let Some(PbDecoderField::Delimited(event)) =
+                field(&packet, TracePacketFieldNumber::TrackEvent as u32)
+            {
+                let Some(PbDecoderField::Varint(ts)) =
+                    field(&packet, TracePacketFieldNumber::Timestamp as u32)
+                else {
+                    panic!("missing timestamp");
+                };
+                let Some(PbDecoderField::Varint(value)) =
+                    field(event, TrackEventFieldNumber::CounterValue as u32)
+                else {
+                    panic!("missing counter value");
+                };
+                samples.push((ts, value as i64));
+            }
+        }
+        assert!(has_defaults);
+        assert!(is_incremental);
+        assert_eq!(samples, vec![(0, 10), (500, 5), (100, -10)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,457 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::DataSourceTimestamp,
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField, append_field},
+    platform::Platform,
+    protos::trace::{
+        trace::TraceFieldNumber,
+        trace_packet::{TracePacketDefaultsFieldNumber, TracePacketFieldNumber},
+        track_event::{
+            process_descriptor::ProcessDescriptorFieldNumber,
+            thread_descriptor::ThreadDescriptorFieldNumber,
+            track_descriptor::TrackDescriptorFieldNumber,
+            track_event::{TrackEventDefaultsFieldNumber, TrackEventFieldNumber},
+        },
+    },
+    trace_reader::{TraceReader, TraceReaderError},
+};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+use thiserror::Error;
+
+// Sequence of the packets written by the tracing service, see
+// `kServicePacketSequenceID`.
+const SERVICE_SEQUENCE_ID: u64 = 1;
+
+/// Trace normalization errors.
+#[derive(Error, Debug, PartialEq)]
+pub enum NormalizeError {
+    /// The trace could not be read.
+    #[error("Failed to read trace: {0}")]
+    Trace(#[from] TraceReaderError),
+    /// A packet could not be decoded.
+    #[error("Failed to decode packet: {0}")]
+    Decode(#[from] PbDecoderError),
+}
+
+/// Clock that advances by a fixed step each time it is read, for traces whose
+/// timestamps only depend on the order of the events.
+///
+/// Installed as the [`Platform`] of the producer, it is the clock of the
+/// track events and data source packets emitted without an explicit
+/// timestamp. Timestamps are on the boot clock.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     deterministic::CounterClock,
+///     producer::{Backends, Producer, ProducerInitArgsBuilder},
+/// };
+/// use std::{sync::Arc, time::Duration};
+///
+/// Producer::init(
+///     ProducerInitArgsBuilder::new()
+///         .backends(Backends::IN_PROCESS)
+///         .platform(Arc::new(CounterClock::new(
+///             Duration::from_secs(1),
+///             Duration::from_micros(10),
+///         )))
+///         .build(),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct CounterClock {
+    next: AtomicU64,
+    step: u64,
+}
+
+impl CounterClock {
+    /// Creates a clock that reads `start` first, then advances by `step`.
+    pub fn new(start: Duration, step: Duration) -> Self {
+        Self {
+            next: AtomicU64::new(start.as_nanos() as u64),
+            step: step.as_nanos() as u64,
+        }
+    }
+}
+
+impl Platform for CounterClock {
+    fn now(&self) -> Option<DataSourceTimestamp> {
+        let value = self.next.fetch_add(self.step, Ordering::Relaxed);
+        Some(DataSourceTimestamp::Boot(Duration::from_nanos(value)))
+    }
+}
+
+// Values of a kind of id, renumbered from 1 in order of first appearance.
+#[derive(Debug, Default)]
+struct Renumbering {
+    ids: HashMap<u64, u64>,
+}
+
+impl Renumbering {
+    fn map(&mut self, id: u64) -> u64 {
+        // Zero is the "unset" value of ids, e.g. of the parent of a root track.
+        if id == 0 {
+            return 0;
+        }
+        let next = self.ids.len() as u64 + 1;
+        *self.ids.entry(id).or_insert(next)
+    }
+}
+
+// Kind of the ids carried by a field.
+#[derive(Debug, Clone, Copy)]
+enum Id {
+    Sequence,
+    Track,
+    Flow,
+    // Process and thread ids share a numbering, as the id of the main thread
+    // is the id of its process.
+    Os,
+}
+
+/// Normalizes the parts of a trace that differ between runs of the same
+/// instrumentation, so that traces can be compared with golden files.
+///
+/// Combined with a [`CounterClock`], two runs that emit the same events in
+/// the same order produce byte-identical normalized traces:
+///
+/// - The packets of the tracing service, e.g. clock snapshots, stats and the
+///   trace config, are dropped.
+/// - The `trusted_uid` and `trusted_pid` of the packets are dropped.
+/// - Sequence ids, track uuids, flow ids and process and thread ids are
+///   renumbered from 1, each in order of first appearance in the trace, so
+///   that e.g. uuids derived from the process id don't depend on it.
+///
+/// The normalizer keeps the renumberings across calls, so the packets of a
+/// live stream can be normalized one at a time.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::deterministic::TraceNormalizer;
+///
+/// // Two runs that only differ by the sequence id of their packet.
+/// let run1 = b"\x0a\x02\x50\x07";
+/// let run2 = b"\x0a\x02\x50\x09";
+/// assert_eq!(
+///     TraceNormalizer::new().normalize_trace(run1).unwrap(),
+///     TraceNormalizer::new().normalize_trace(run2).unwrap(),
+/// );
+/// ```
+#[derive(Debug, Default)]
+pub struct TraceNormalizer {
+    sequences: Renumbering,
+    tracks: Renumbering,
+    flows: Renumbering,
+    os_ids: Renumbering,
+}
+
+impl TraceNormalizer {
+    /// Creates a normalizer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the normalized encoded `TracePacket` `packet`, or `None` if the
+    /// packet is dropped.
+    pub fn normalize_packet(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, PbDecoderError> {
+        use TracePacketFieldNumber as Packet;
+        let mut out = Vec::with_capacity(packet.len());
+        for item in PbDecoder::new(packet) {
+            let (field_id, field) = item?;
+            match (field_id, field) {
+                (id, PbDecoderField::Varint(SERVICE_SEQUENCE_ID))
+                    if id == Packet::TrustedPacketSequenceId as u32 =>
+                {
+                    return Ok(None);
+                }
+                (id, _) if id == Packet::TrustedUid as u32 || id == Packet::TrustedPid as u32 => {}
+                (id, field) if id == Packet::TrustedPacketSequenceId as u32 => {
+                    self.append_id(&mut out, id, field, Id::Sequence);
+                }
+                (id, PbDecoderField::Delimited(data)) if id == Packet::TrackDescriptor as u32 => {
+                    let data = self.normalize_track_descriptor(data)?;
+                    append_field(&mut out, id, &PbDecoderField::Delimited(&data));
+                }
+                (id, PbDecoderField::Delimited(data)) if id == Packet::TrackEvent as u32 => {
+                    let data = self.normalize_track_event(data)?;
+                    append_field(&mut out, id, &PbDecoderField::Delimited(&data));
+                }
+                (id, PbDecoderField::Delimited(data))
+                    if id == Packet::TracePacketDefaults as u32 =>
+                {
+                    let data = self.normalize_packet_defaults(data)?;
+                    append_field(&mut out, id, &PbDecoderField::Delimited(&data));
+                }
+                (id, field) => append_field(&mut out, id, &field),
+            }
+        }
+        Ok(Some(out))
+    }
+
+    /// Returns the normalized serialized `Trace` `trace`. Compressed packets
+    /// are decompressed.
+    pub fn normalize_trace(&mut self, trace: &[u8]) -> Result<Vec<u8>, NormalizeError> {
+        let mut out = Vec::with_capacity(trace.len());
+        for packet in TraceReader::new(trace) {
+            if let Some(packet) = self.normalize_packet(&packet?)? {
+                append_field(
+                    &mut out,
+                    TraceFieldNumber::Packet as u32,
+                    &PbDecoderField::Delimited(&packet),
+                );
+            }
+        }
+        Ok(out)
+    }
+
+    // Appends the scalar `field` with its id renumbered.
+    fn append_id(&mut self, out: &mut Vec<u8>, field_id: u32, field: PbDecoderField, kind: Id) {
+        let renumbering = match kind {
+            Id::Sequence => &mut self.sequences,
+            Id::Track => &mut self.tracks,
+            Id::Flow => &mut self.flows,
+            Id::Os => &mut self.os_ids,
+        };
+        let field = match field {
+            PbDecoderField::Varint(id) => PbDecoderField::Varint(renumbering.map(id)),
+            PbDecoderField::Fixed64(id) => PbDecoderField::Fixed64(renumbering.map(id)),
+            field => field,
+        };
+        append_field(out, field_id, &field);
+    }
+
+    // Renumbers the fields of the encoded `message` whose kind is returned by
+    // `kind_of`. Nested messages are normalized by `nested`.
+    fn normalize_message<K, N>(
+        &mut self,
+        message: &[u8],
+        kind_of: K,
+        mut nested: N,
+    ) -> Result<Vec<u8>, PbDecoderError>
+    where
+        K: Fn(u32) -> Option<Id>,
+        N: FnMut(&mut Self, u32, &[u8]) -> Option<Result<Vec<u8>, PbDecoderError>>,
+    {
+        let mut out = Vec::with_capacity(message.len());
+        for item in PbDecoder::new(message) {
+            let (field_id, field) = item?;
+            match field {
+                PbDecoderField::Delimited(data) => match nested(self, field_id, data) {
+                    Some(data) => {
+                        append_field(&mut out, field_id, &PbDecoderField::Delimited(&data?))
+                    }
+                    None => append_field(&mut out, field_id, &field),
+                },
+                field => match kind_of(field_id) {
+                    Some(kind) => self.append_id(&mut out, field_id, field, kind),
+                    None => append_field(&mut out, field_id, &field),
+                },
+            }
+        }
+        Ok(out)
+    }
+
+    fn normalize_track_descriptor(&mut self, data: &[u8]) -> Result<Vec<u8>, PbDecoderError> {
+        use TrackDescriptorFieldNumber as Track;
+        self.normalize_message(
+            data,
+            |id| (id == Track::Uuid as u32 || id == Track::ParentUuid as u32).then_some(Id::Track),
+            |normalizer, id, data| {
+                if id == Track::Process as u32 {
+                    Some(
+                        normalizer.normalize_os_ids(data, ProcessDescriptorFieldNumber::Pid as u32),
+                    )
+                } else if id == Track::Thread as u32 {
+                    Some(normalizer.normalize_os_ids(data, ThreadDescriptorFieldNumber::Pid as u32))
+                } else {
+                    None
+                }
+            },
+        )
+    }
+
+    // Renumbers the process and thread ids of a process or thread descriptor,
+    // whose pid is field `pid_id` and tid, if any, the next field.
+    fn normalize_os_ids(&mut self, data: &[u8], pid_id: u32) -> Result<Vec<u8>, PbDecoderError> {
+        let tid_id = ThreadDescriptorFieldNumber::Tid as u32;
+        let is_thread = pid_id == ThreadDescriptorFieldNumber::Pid as u32;
+        self.normalize_message(
+            data,
+            |id| (id == pid_id || (is_thread && id == tid_id)).then_some(Id::Os),
+            |_, _, _| None,
+        )
+    }
+
+    fn normalize_track_event(&mut self, data: &[u8]) -> Result<Vec<u8>, PbDecoderError> {
+        use TrackEventFieldNumber as Event;
+        self.normalize_message(
+            data,
+            |id| match id {
+                id if id == Event::TrackUuid as u32
+                    || id == Event::ExtraCounterTrackUuids as u32
+                    || id == Event::ExtraDoubleCounterTrackUuids as u32 =>
+                {
+                    Some(Id::Track)
+                }
+                id if id == Event::FlowIds as u32
+                    || id == Event::FlowIdsOld as u32
+                    || id == Event::TerminatingFlowIds as u32
+                    || id == Event::TerminatingFlowIdsOld as u32 =>
+                {
+                    Some(Id::Flow)
+                }
+                _ => None,
+            },
+            |_, _, _| None,
+        )
+    }
+
+    fn normalize_packet_defaults(&mut self, data: &[u8]) -> Result<Vec<u8>, PbDecoderError> {
+        use TrackEventDefaultsFieldNumber as Defaults;
+        self.normalize_message(
+            data,
+            |_| None,
+            |normalizer, id, data| {
+                (id == TracePacketDefaultsFieldNumber::TrackEventDefaults as u32).then(|| {
+                    normalizer.normalize_message(
+                        data,
+                        |id| {
+                            (id == Defaults::TrackUuid as u32
+                                || id == Defaults::ExtraCounterTrackUuids as u32
+                                || id == Defaults::ExtraDoubleCounterTrackUuids as u32)
+                                .then_some(Id::Track)
+                        },
+                        |_, _, _| None,
+                    )
+                })
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        platform,
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        track_event::TrackEvent,
+        track_event_begin, track_event_end, track_event_instant,
+    };
+    use std::{error::Error, sync::Arc, thread};
+
+    crate::track_event_categories! {
+        pub mod test_te_ns {
+            ( "golden", "Test deterministic output", [] ),
+        }
+    }
+    use test_te_ns as perfetto_te_ns;
+
+    // Records the same events on a new thread, with a new session and clock.
+    fn record() -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name("track_event")
+            .add_enabled_category("golden")
+            .add_disabled_category("*")
+            .build()?;
+        session.start_blocking();
+        platform::install(Arc::new(CounterClock::new(
+            Duration::from_secs(1),
+            Duration::from_micros(1),
+        )));
+        thread::spawn(|| {
+            track_event_begin!("golden", "work");
+            track_event_instant!("golden", "step");
+            track_event_end!("golden");
+        })
+        .join()
+        .unwrap();
+        platform::uninstall();
+        session.stop_blocking();
+        Ok(read_trace_data(&mut session))
+    }
+
+    #[test]
+    fn golden() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        TrackEvent::init();
+        test_te_ns::register()?;
+        let run1 = record()?;
+        let run2 = record()?;
+        test_te_ns::unregister()?;
+        assert_ne!(run1, run2);
+        let golden = TraceNormalizer::new().normalize_trace(&run1)?;
+        assert!(!golden.is_empty());
+        assert_eq!(golden, TraceNormalizer::new().normalize_trace(&run2)?);
+        Ok(())
+    }
+
+    #[test]
+    fn renumber() {
+        use crate::protos::trace::track_event::track_event::TrackEventFieldNumber;
+        let mut normalizer = TraceNormalizer::new();
+        let mut event = vec![];
+        append_field(
+            &mut event,
+            TrackEventFieldNumber::TrackUuid as u32,
+            &PbDecoderField::Varint(0xabcd),
+        );
+        append_field(
+            &mut event,
+            TrackEventFieldNumber::FlowIds as u32,
+            &PbDecoderField::Fixed64(0xabcd),
+        );
+        let mut packet = vec![];
+        append_field(
+            &mut packet,
+            TracePacketFieldNumber::TrackEvent as u32,
+            &PbDecoderField::Delimited(&event),
+        );
+        append_field(
+            &mut packet,
+            TracePacketFieldNumber::TrustedPid as u32,
+            &PbDecoderField::Varint(1234),
+        );
+        let normalized = normalizer.normalize_packet(&packet).unwrap().unwrap();
+        let items: Vec<_> = PbDecoder::new(&normalized)
+            .map(|item| item.unwrap())
+            .collect();
+        assert_eq!(items.len(), 1);
+        let (_, PbDecoderField::Delimited(event)) = items[0] else {
+            panic!("unexpected field {:?}", items[0]);
+        };
+        let fields: Vec<_> = PbDecoder::new(event).map(|item| item.unwrap().1).collect();
+        // Track uuids and flow ids are numbered separately.
+        assert_eq!(
+            fields,
+            vec![PbDecoderField::Varint(1), PbDecoderField::Fixed64(1)]
+        );
+
+        let mut service_packet = vec![];
+        append_field(
+            &mut service_packet,
+            TracePacketFieldNumber::TrustedPacketSequenceId as u32,
+            &PbDecoderField::Varint(SERVICE_SEQUENCE_ID),
+        );
+        assert_eq!(normalizer.normalize_packet(&service_packet).unwrap(), None);
+    }
+}
@@ -0,0 +1,453 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{Clear, DataSourceTimestamp, TraceContextBase},
+    protos::trace::{
+        trace_packet::TracePacket,
+        track_event::{
+            counter_descriptor::CounterDescriptor,
+            track_descriptor::TrackDescriptor,
+            track_event::{TrackEvent, TrackEventType},
+        },
+    },
+    track_event::TrackEventTrack,
+};
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Histogram errors.
+#[derive(Error, Debug, PartialEq)]
+pub enum HistogramError {
+    /// The bucket boundaries aren't finite and strictly increasing.
+    #[error("Bucket boundaries must be finite and strictly increasing.")]
+    InvalidBoundaries,
+    /// The number of bucket counts doesn't match the boundaries.
+    #[error("Expected {0} bucket counts, got {1}.")]
+    CountMismatch(usize, usize),
+}
+
+/// A bucket of a [`Histogram`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HistogramBucket {
+    /// Inclusive lower bound of the bucket, or negative infinity for the
+    /// first bucket.
+    pub lower: f64,
+    /// Exclusive upper bound of the bucket, or infinity for the last bucket.
+    pub upper: f64,
+    /// Number of values in the bucket.
+    pub count: u64,
+}
+
+/// A pre-aggregated distribution of values, e.g. of GPU job durations.
+///
+/// `n` bucket boundaries define `n + 1` buckets: values below the first
+/// boundary, values between consecutive boundaries, and values at or above
+/// the last boundary.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::histogram::Histogram;
+///
+/// let mut histogram = Histogram::new(vec![1.0, 2.0, 4.0]).unwrap();
+/// for duration_ms in [0.5, 1.5, 3.0, 3.5, 10.0] {
+///     histogram.record(duration_ms);
+/// }
+/// assert_eq!(histogram.counts(), &[1, 1, 2, 1]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    boundaries: Vec<f64>,
+    counts: Vec<u64>,
+    sum: f64,
+}
+
+impl Histogram {
+    /// Creates an empty histogram with the bucket `boundaries`.
+    pub fn new(boundaries: Vec<f64>) -> Result<Self, HistogramError> {
+        let counts = vec![0; boundaries.len() + 1];
+        Self::with_counts(boundaries, counts, 0.0)
+    }
+
+    /// Creates a histogram from bucket counts aggregated elsewhere. `counts`
+    /// has one more element than `boundaries` and `sum` is the sum of the
+    /// values.
+    pub fn with_counts(
+        boundaries: Vec<f64>,
+        counts: Vec<u64>,
+        sum: f64,
+    ) -> Result<Self, HistogramError> {
+        if boundaries.iter().any(|boundary| !boundary.is_finite())
+            || boundaries.windows(2).any(|pair| pair[0] >= pair[1])
+        {
+            return Err(HistogramError::InvalidBoundaries);
+        }
+        if counts.len() != boundaries.len() + 1 {
+            return Err(HistogramError::CountMismatch(
+                boundaries.len() + 1,
+                counts.len(),
+            ));
+        }
+        Ok(Self {
+            boundaries,
+            counts,
+            sum,
+        })
+    }
+
+    /// Returns `count` boundaries starting at `start`, each `factor` times
+    /// the previous one.
+    pub fn exponential_boundaries(start: f64, factor: f64, count: usize) -> Vec<f64> {
+        std::iter::successors(Some(start), |boundary| Some(boundary * factor))
+            .take(count)
+            .collect()
+    }
+
+    /// Returns `count` boundaries starting at `start`, `width` apart.
+    pub fn linear_boundaries(start: f64, width: f64, count: usize) -> Vec<f64> {
+        (0..count).map(|i| start + width * i as f64).collect()
+    }
+
+    /// Adds `value` to its bucket.
+    pub fn record(&mut self, value: f64) {
+        self.record_n(value, 1);
+    }
+
+    /// Adds `value` `n` times to its bucket.
+    pub fn record_n(&mut self, value: f64, n: u64) {
+        let bucket = self
+            .boundaries
+            .partition_point(|boundary| *boundary <= value);
+        self.counts[bucket] += n;
+        self.sum += value * n as f64;
+    }
+
+    /// Returns the bucket boundaries.
+    pub fn boundaries(&self) -> &[f64] {
+        &self.boundaries
+    }
+
+    /// Returns the number of values in each bucket.
+    pub fn counts(&self) -> &[u64] {
+        &self.counts
+    }
+
+    /// Returns the buckets, in increasing order.
+    pub fn buckets(&self) -> impl Iterator<Item = HistogramBucket> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| HistogramBucket {
+                lower: i
+                    .checked_sub(1)
+                    .map_or(f64::NEG_INFINITY, |i| self.boundaries[i]),
+                upper: self.boundaries.get(i).copied().unwrap_or(f64::INFINITY),
+                count: *count,
+            })
+    }
+
+    /// Returns the number of values.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the sum of the values.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Returns the mean of the values, if any.
+    pub fn mean(&self) -> Option<f64> {
+        let count = self.count();
+        (count > 0).then(|| self.sum / count as f64)
+    }
+
+    /// Removes all values, keeping the buckets.
+    pub fn reset(&mut self) {
+        self.counts.fill(0);
+        self.sum = 0.0;
+    }
+}
+
+/// Track of a histogram written by a [`HistogramWriter`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramTrack {
+    uuid: u64,
+    parent_uuid: u64,
+    name: String,
+}
+
+impl HistogramTrack {
+    /// Creates a histogram track named `name`. The track is nested under
+    /// `parent_uuid` unless it is zero.
+    pub fn new(name: impl Into<String>, parent_uuid: u64) -> Self {
+        let name = name.into();
+        Self {
+            uuid: TrackEventTrack::named_track_uuid(&name, 0, parent_uuid),
+            parent_uuid,
+            name,
+        }
+    }
+
+    /// Returns the track UUID.
+    pub fn uuid(&self) -> u64 {
+        self.uuid
+    }
+}
+
+// Returns the name of the counter track of `bucket`, e.g. "[1, 2)".
+fn bucket_name(bucket: &HistogramBucket) -> String {
+    match (bucket.lower.is_finite(), bucket.upper.is_finite()) {
+        (false, true) => format!("< {}", bucket.upper),
+        (true, false) => format!(">= {}", bucket.lower),
+        (true, true) => format!("[{}, {})", bucket.lower, bucket.upper),
+        (false, false) => "all".to_string(),
+    }
+}
+
+/// Writes histograms as a track with a counter track per bucket, whose value
+/// is the number of values in the bucket, and counter tracks for the count
+/// and the sum of the values. Trace processor and the Perfetto UI show the
+/// bucket counters next to each other, under the track of the histogram.
+///
+/// Only a snapshot of the buckets is written, so producers don't have to emit
+/// every sample. The writer keeps per-sequence state and is meant to be used
+/// as (or be part of) the incremental state of a data source. It writes the
+/// descriptors of the tracks when they're first used on a sequence.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     data_source::{DataSource, DataSourceArgsBuilder, DataSourceTimestamp, TraceContext},
+///     histogram::{Histogram, HistogramTrack, HistogramWriter},
+/// };
+///
+/// let mut data_source = DataSource::<HistogramWriter>::new_with_incremental_state_type();
+/// data_source
+///     .register("com.example.histograms", DataSourceArgsBuilder::new().build())
+///     .unwrap();
+/// let track = HistogramTrack::new("gpu.job_duration_ms", 0);
+/// let mut histogram = Histogram::new(Histogram::exponential_boundaries(1.0, 2.0, 8)).unwrap();
+/// histogram.record(3.5);
+/// data_source.trace(|ctx: &mut TraceContext<HistogramWriter>| {
+///     ctx.with_incremental_state(|ctx, writer| {
+///         writer.write_histogram(ctx, &track, DataSourceTimestamp::now(), &histogram);
+///     });
+/// });
+/// ```
+#[derive(Debug, Default)]
+pub struct HistogramWriter {
+    described_tracks: HashSet<u64>,
+}
+
+impl Clear for HistogramWriter {
+    fn clear(&mut self) {
+        self.described_tracks.clear();
+    }
+}
+
+impl HistogramWriter {
+    /// Creates a new writer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes a snapshot of `histogram` at `timestamp` to `track`.
+    pub fn write_histogram(
+        &mut self,
+        ctx: &mut TraceContextBase,
+        track: &HistogramTrack,
+        timestamp: DataSourceTimestamp,
+        histogram: &Histogram,
+    ) {
+        if self.described_tracks.insert(track.uuid) {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+                    desc.set_uuid(track.uuid);
+                    if track.parent_uuid != 0 {
+                        desc.set_parent_uuid(track.parent_uuid);
+                    }
+                    desc.set_name(track.name.as_str());
+                });
+            });
+        }
+        let count = histogram.count() as i64;
+        self.write_counter(ctx, track, "count", timestamp, |event| {
+            event.set_counter_value(count);
+        });
+        self.write_counter(ctx, track, "sum", timestamp, |event| {
+            event.set_double_counter_value(histogram.sum());
+        });
+        for bucket in histogram.buckets() {
+            let count = bucket.count as i64;
+            self.write_counter(ctx, track, &bucket_name(&bucket), timestamp, |event| {
+                event.set_counter_value(count);
+            });
+        }
+    }
+
+    // Writes a value to the counter track `name` of the histogram `track`,
+    // after its descriptor if it's the first value on the sequence.
+    fn write_counter<F>(
+        &mut self,
+        ctx: &mut TraceContextBase,
+        track: &HistogramTrack,
+        name: &str,
+        timestamp: DataSourceTimestamp,
+        cb: F,
+    ) where
+        F: Fn(&mut TrackEvent),
+    {
+        let uuid = TrackEventTrack::counter_track_uuid(name, track.uuid);
+        if self.described_tracks.insert(uuid) {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+                    desc.set_uuid(uuid);
+                    desc.set_parent_uuid(track.uuid);
+                    desc.set_name(name);
+                    desc.set_counter(|_: &mut CounterDescriptor| {});
+                });
+            });
+        }
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet
+                .set_timestamp(timestamp.timestamp())
+                .set_timestamp_clock_id(timestamp.clock_id());
+            packet.set_track_event(|event: &mut TrackEvent| {
+                event.set_type(TrackEventType::TypeCounter);
+                event.set_track_uuid(uuid);
+                cb(event);
+            });
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_source::TraceContext,
+        pb_decoder::PbDecoderField,
+        protos::trace::{
+            trace_packet::TracePacketFieldNumber,
+            track_event::{
+                track_descriptor::TrackDescriptorFieldNumber, track_event::TrackEventFieldNumber,
+            },
+        },
+        test_util::field,
+        test_util::{data_source_name, data_source_with_state},
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        trace_reader::TraceReader,
+    };
+    use std::{collections::HashMap, error::Error, time::Duration};
+
+    #[test]
+    fn buckets() -> Result<(), Box<dyn Error>> {
+        let mut histogram = Histogram::new(Histogram::linear_boundaries(0.0, 10.0, 3))?;
+        histogram.record(-1.0);
+        histogram.record(10.0);
+        histogram.record_n(25.0, 2);
+        assert_eq!(histogram.counts(), &[1, 0, 1, 2]);
+        assert_eq!(histogram.count(), 4);
+        assert_eq!(histogram.mean(), Some(59.0 / 4.0));
+        let buckets: Vec<_> = histogram.buckets().collect();
+        assert_eq!(buckets[0].lower, f64::NEG_INFINITY);
+        assert_eq!((buckets[2].lower, buckets[2].upper), (10.0, 20.0));
+        assert_eq!(buckets[3].upper, f64::INFINITY);
+        histogram.reset();
+        assert_eq!(histogram.mean(), None);
+
+        assert_eq!(
+            Histogram::exponential_boundaries(1.0, 2.0, 4),
+            vec![1.0, 2.0, 4.0, 8.0]
+        );
+        assert_eq!(
+            Histogram::new(vec![2.0, 1.0]),
+            Err(HistogramError::InvalidBoundaries)
+        );
+        assert_eq!(
+            Histogram::with_counts(vec![1.0], vec![1], 1.0),
+            Err(HistogramError::CountMismatch(2, 1))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn write_histogram() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source_with_state::<HistogramWriter>();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(data_source_name::<HistogramWriter>())
+            .build()?;
+        session.start_blocking();
+        let track = HistogramTrack::new("latency", 0);
+        let mut histogram = Histogram::new(vec![1.0, 2.0])?;
+        for value in [0.5, 1.5, 1.5] {
+            histogram.record(value);
+            data_source.trace(|ctx: &mut TraceContext<HistogramWriter>| {
+                ctx.with_incremental_state(|ctx, writer| {
+                    let timestamp = DataSourceTimestamp::Boot(Duration::from_nanos(1000));
+                    writer.write_histogram(ctx, &track, timestamp, &histogram);
+                });
+            });
+        }
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        let mut names = HashMap::new();
+        let mut descriptors = 0;
+        let mut last_values = HashMap::new();
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            if let Some(PbDecoderField::Delimited(desc)) =
+                field(&packet, TracePacketFieldNumber::TrackDescriptor as u32)
+            {
+                let Some(PbDecoderField::Varint(uuid)) =
+                    field(desc, TrackDescriptorFieldNumber::Uuid as u32)
+                else {
+                    panic!("missing uuid");
+                };
+                let Some(name) = field(desc, TrackDescriptorFieldNumber::Name as u32) else {
+                    panic!("missing name");
+                };
+                names.insert(uuid, name.as_str()?.to_string());
+                descriptors += 1;
+            }
+            if let Some(PbDecoderField::Delimited(event)) =
+                field(&packet, TracePacketFieldNumber::TrackEvent as u32)
+            {
+                let Some(PbDecoderField::Varint(uuid)) =
+                    field(event, TrackEventFieldNumber::TrackUuid as u32)
+                else {
+                    panic!("missing track uuid");
+                };
+                if let Some(PbDecoderField::Varint(value)) =
+                    field(event, TrackEventFieldNumber::CounterValue as u32)
+                {
+                    last_values.insert(names[&uuid].clone(), value);
+                }
+            }
+        }
+        // The histogram, count, sum and three bucket tracks.
+        assert_eq!(descriptors, 6);
+        assert_eq!(names[&track.uuid()], "latency");
+        assert_eq!(last_values["count"], 3);
+        assert_eq!(last_values["< 1"], 1);
+        assert_eq!(last_values["[1, 2)"], 2);
+        assert_eq!(last_values[">= 2"], 0);
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField},
+    protos::config::data_source_config::DataSourceConfigFieldNumber,
+};
+
+/// Standard `DataSourceConfig` fields of a data source instance, decoded
+/// from the config passed to the `on_setup` callback, see
+/// [`TraceContext::config`](crate::data_source::TraceContext::config).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InstanceConfig {
+    /// Name of the data source.
+    pub name: String,
+    /// Index of the trace buffer the instance writes to.
+    pub target_buffer: u32,
+    /// Duration of the trace, or zero if unbounded.
+    pub trace_duration_ms: u32,
+    /// Time the service waits for the instance to stop.
+    pub stop_timeout_ms: u32,
+    /// Set if the session requested extra guardrails, e.g. to avoid
+    /// expensive collection.
+    pub enable_extra_guardrails: bool,
+    /// Unique id of the tracing session.
+    pub tracing_session_id: u64,
+    /// The complete encoded `DataSourceConfig`, for decoding any other
+    /// fields.
+    pub raw: Vec<u8>,
+}
+
+impl InstanceConfig {
+    /// Decodes the standard fields of the encoded `DataSourceConfig` in
+    /// `config`. Unknown fields are ignored.
+    pub fn decode(config: &[u8]) -> Result<Self, PbDecoderError> {
+        use DataSourceConfigFieldNumber::*;
+        let mut decoded = Self {
+            raw: config.to_vec(),
+            ..Default::default()
+        };
+        for item in PbDecoder::new(config) {
+            match item? {
+                (id, PbDecoderField::Delimited(value)) if id == Name as u32 => {
+                    decoded.name = String::from_utf8_lossy(value).into_owned();
+                }
+                (id, PbDecoderField::Varint(value)) if id == TargetBuffer as u32 => {
+                    decoded.target_buffer = value as u32;
+                }
+                (id, PbDecoderField::Varint(value)) if id == TraceDurationMs as u32 => {
+                    decoded.trace_duration_ms = value as u32;
+                }
+                (id, PbDecoderField::Varint(value)) if id == StopTimeoutMs as u32 => {
+                    decoded.stop_timeout_ms = value as u32;
+                }
+                (id, PbDecoderField::Varint(value)) if id == EnableExtraGuardrails as u32 => {
+                    decoded.enable_extra_guardrails = value != 0;
+                }
+                (id, PbDecoderField::Varint(value)) if id == TracingSessionId as u32 => {
+                    decoded.tracing_session_id = value;
+                }
+                _ => {}
+            }
+        }
+        Ok(decoded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        heap_buffer::HeapBuffer,
+        pb_msg::{PbMsg, PbMsgWriter},
+        protos::config::data_source_config::DataSourceConfig,
+    };
+
+    fn encode_config(name: &str, guardrails: bool, session_id: u64) -> Vec<u8> {
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        {
+            let mut cfg = DataSourceConfig { msg: &mut msg };
+            cfg.set_name(name);
+            cfg.set_target_buffer(1);
+            cfg.set_enable_extra_guardrails(guardrails);
+            cfg.set_tracing_session_id(session_id);
+        }
+        msg.finalize();
+        let size = writer.writer.get_written_size();
+        let mut buffer = vec![0u8; size];
+        hb.copy_into(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn decode() {
+        let raw = encode_config("com.example.ds", true, 42);
+        let config = InstanceConfig::decode(&raw).unwrap();
+        assert_eq!(
+            config,
+            InstanceConfig {
+                name: "com.example.ds".to_string(),
+                target_buffer: 1,
+                enable_extra_guardrails: true,
+                tracing_session_id: 42,
+                raw: raw.clone(),
+                ..Default::default()
+            }
+        );
+    }
+}
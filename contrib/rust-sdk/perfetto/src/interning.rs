@@ -0,0 +1,283 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{Clear, TraceContextBase},
+    protos::trace::{
+        interned_data::interned_data::InternedData,
+        trace_packet::{TracePacket, TracePacketSequenceFlags},
+    },
+};
+use std::{any::Any, cell::Cell, collections::HashMap, hash::Hash};
+
+// Interning ids of the entries of one kind.
+struct InternTable<K> {
+    iids: HashMap<K, u64>,
+}
+
+/// Interned data of a packet sequence, for data sources that define their
+/// own kinds of interned entries, e.g. GPU shader names keyed by hash.
+///
+/// A kind is identified by the field number of its entries in the
+/// `InternedData` message, typically an extension field defined with
+/// [`pb_msg_ext!`](crate::pb_msg_ext). Each entry is written once per
+/// sequence, in a packet of its own, and payload packets reference it by its
+/// interning id (iid). Iids start at 1 and are allocated per kind.
+///
+/// The interner is meant to be used as (or be part of) the incremental state
+/// of a data source. After the incremental state is cleared, the first entry
+/// written marks the sequence as cleared, so that entries written before
+/// aren't used anymore. Payload packets that reference iids must set the
+/// `SEQ_NEEDS_INCREMENTAL_STATE` sequence flag.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     data_source::{DataSource, DataSourceArgsBuilder, TraceContext},
+///     interning::Interner,
+///     pb_msg, pb_msg_ext,
+///     protos::trace::{
+///         interned_data::interned_data::InternedData,
+///         trace_packet::{TracePacket, TracePacketSequenceFlags},
+///     },
+/// };
+///
+/// pb_msg!(ShaderName {
+///     iid: u64, primitive, 1,
+///     name: String, primitive, 2,
+/// });
+///
+/// pb_msg_ext!(InternedData {
+///     shader_names: ShaderName, msg, 1000,
+/// });
+///
+/// let mut data_source = DataSource::<Interner>::new_with_incremental_state_type();
+/// data_source
+///     .register("com.example.shaders", DataSourceArgsBuilder::new().build())
+///     .unwrap();
+/// let (hash, name) = (0x1234u64, "blur.frag");
+/// data_source.trace(|ctx: &mut TraceContext<Interner>| {
+///     ctx.with_incremental_state(|ctx, interner| {
+///         let iid = interner.intern(
+///             ctx,
+///             InternedDataExtFieldNumber::ShaderNames as u32,
+///             &hash,
+///             |data: &mut InternedData, iid| {
+///                 data.set_shader_names(|entry: &mut ShaderName| {
+///                     entry.set_iid(iid);
+///                     entry.set_name(name);
+///                 });
+///             },
+///         );
+///         ctx.add_packet(|packet: &mut TracePacket| {
+///             packet.set_sequence_flags(TracePacketSequenceFlags::SeqNeedsIncrementalState.into());
+///             // Reference `iid` from the payload.
+///         });
+///     });
+/// });
+/// ```
+pub struct Interner {
+    tables: HashMap<u32, Box<dyn Any + Send>>,
+    cleared: bool,
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self {
+            tables: HashMap::new(),
+            cleared: true,
+        }
+    }
+}
+
+impl Clear for Interner {
+    fn clear(&mut self) {
+        self.tables.clear();
+        self.cleared = true;
+    }
+}
+
+impl Interner {
+    /// Creates an empty interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn table<K>(&mut self, field_id: u32) -> &mut InternTable<K>
+    where
+        K: Hash + Eq + Send + 'static,
+    {
+        self.tables
+            .entry(field_id)
+            .or_insert_with(|| {
+                Box::new(InternTable::<K> {
+                    iids: HashMap::new(),
+                })
+            })
+            .downcast_mut()
+            .expect("interned data kind used with different key types")
+    }
+
+    /// Returns the iid of `key` in the kind `field_id`, or `None` if it wasn't
+    /// interned since the incremental state was last cleared.
+    ///
+    /// Panics if the kind was used with a different key type.
+    pub fn lookup<K>(&mut self, field_id: u32, key: &K) -> Option<u64>
+    where
+        K: Hash + Eq + Send + 'static,
+    {
+        self.table::<K>(field_id).iids.get(key).copied()
+    }
+
+    /// Returns the iid of `key` in the kind `field_id`. If `key` wasn't
+    /// interned yet, allocates a new iid and calls `write` with the
+    /// `InternedData` of a new packet and the iid to write the entry.
+    ///
+    /// Panics if the kind was used with a different key type.
+    pub fn intern<K, F>(
+        &mut self,
+        ctx: &mut TraceContextBase,
+        field_id: u32,
+        key: &K,
+        write: F,
+    ) -> u64
+    where
+        K: Hash + Eq + Clone + Send + 'static,
+        F: FnOnce(&mut InternedData, u64),
+    {
+        let table = self.table::<K>(field_id);
+        if let Some(iid) = table.iids.get(key) {
+            return *iid;
+        }
+        let iid = table.iids.len() as u64 + 1;
+        table.iids.insert(key.clone(), iid);
+
+        let mut flags: u32 = TracePacketSequenceFlags::SeqNeedsIncrementalState.into();
+        if self.cleared {
+            flags |= u32::from(TracePacketSequenceFlags::SeqIncrementalStateCleared);
+            self.cleared = false;
+        }
+        let write = Cell::new(Some(write));
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet.set_sequence_flags(flags);
+            packet.set_interned_data(|data: &mut InternedData| {
+                if let Some(write) = write.take() {
+                    write(data, iid);
+                }
+            });
+        });
+        iid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_source::TraceContext,
+        pb_decoder::PbDecoderField,
+        pb_msg, pb_msg_ext,
+        protos::trace::{
+            interned_data::interned_data::InternedData, trace_packet::TracePacketFieldNumber,
+        },
+        test_util::field,
+        test_util::{data_source_name, data_source_with_state},
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        trace_reader::TraceReader,
+    };
+    use std::error::Error;
+
+    #[allow(dead_code)]
+    mod shader {
+        use super::*;
+        pb_msg!(ShaderName {
+            iid: u64, primitive, 1,
+            name: String, primitive, 2,
+        });
+
+        pb_msg_ext!(InternedData {
+            shader_names: ShaderName, msg, 5000,
+        });
+    }
+    use shader::*;
+
+    fn intern_shader(ctx: &mut TraceContextBase, interner: &mut Interner, hash: u64) -> u64 {
+        interner.intern(
+            ctx,
+            InternedDataExtFieldNumber::ShaderNames as u32,
+            &hash,
+            |data: &mut InternedData, iid| {
+                data.set_shader_names(|entry: &mut ShaderName| {
+                    entry.set_iid(iid);
+                    entry.set_name(format!("shader_{hash}").as_str());
+                });
+            },
+        )
+    }
+
+    #[test]
+    fn interns_once_per_sequence() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source_with_state::<Interner>();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(data_source_name::<Interner>())
+            .build()?;
+        session.start_blocking();
+        let mut iids = vec![];
+        data_source.trace(|ctx: &mut TraceContext<Interner>| {
+            ctx.with_incremental_state(|ctx, interner| {
+                iids.push(intern_shader(ctx, interner, 10));
+                iids.push(intern_shader(ctx, interner, 20));
+                iids.push(intern_shader(ctx, interner, 10));
+                assert_eq!(interner.lookup(5000, &20u64), Some(2));
+                assert_eq!(interner.lookup(5000, &30u64), None);
+                interner.clear();
+                iids.push(intern_shader(ctx, interner, 20));
+            });
+        });
+        session.stop_blocking();
+        assert_eq!(iids, vec![1, 2, 1, 1]);
+
+        let data = read_trace_data(&mut session);
+        let mut entries = vec![];
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            let Some(PbDecoderField::Delimited(interned)) =
+                field(&packet, TracePacketFieldNumber::InternedData as u32)
+            else {
+                continue;
+            };
+            let Some(PbDecoderField::Varint(flags)) =
+                field(&packet, TracePacketFieldNumber::SequenceFlags as u32)
+            else {
+                panic!("missing sequence flags");
+            };
+            let Some(PbDecoderField::Delimited(entry)) = field(interned, 5000) else {
+                panic!("missing entry");
+            };
+            let Some(PbDecoderField::Varint(iid)) = field(entry, 1) else {
+                panic!("missing iid");
+            };
+            let cleared = flags
+                & u64::from(u32::from(
+                    TracePacketSequenceFlags::SeqIncrementalStateCleared,
+                ))
+                != 0;
+            entries.push((iid, cleared));
+        }
+        assert_eq!(entries, vec![(1, true), (2, false), (1, true)]);
+        Ok(())
+    }
+}
@@ -21,36 +21,177 @@
     feature(core_intrinsics)
 )]
 
+/// Async task module.
+pub mod async_task;
+
+/// Async trace reader module.
+#[cfg(feature = "tokio")]
+pub mod async_trace_reader;
+
+/// Byte budget module.
+pub mod byte_budget;
+
+/// Chrome JSON trace importer module.
+pub mod chrome_json;
+
+/// Clock sync module.
+pub mod clock_sync;
+
+/// Data source config capture module.
+pub mod config_capture;
+
+/// Data source config schema module.
+pub mod config_schema;
+
+/// Counter value module.
+pub mod counter_value;
+
 /// Data source module.
 pub mod data_source;
 
+/// Delta counter encoding module.
+pub mod delta_counter;
+
+/// Deterministic trace output module.
+pub mod deterministic;
+
 /// Heap buffer module.
 pub mod heap_buffer;
 
+/// Histogram module.
+pub mod histogram;
+
+/// Data source instance config module.
+pub mod instance_config;
+
+/// Interning module.
+pub mod interning;
+
+/// Live counter subscription module.
+pub mod live_counters;
+
+/// SDK logging module.
+pub mod logging;
+
+/// Object lifetime tracking module.
+pub mod object_lifetime;
+
+/// Trace packet defaults module.
+pub mod packet_defaults;
+
+/// Packet dispatcher module.
+pub mod packet_dispatcher;
+
+/// Packet sequence module.
+pub mod packet_sequence;
+
+/// Large payload chunking module.
+pub mod payload_chunks;
+
 /// Protobuf decoder module.
 pub mod pb_decoder;
 
+/// Protobuf descriptor module.
+pub mod pb_descriptor;
+
+/// Protobuf extension module.
+pub mod pb_ext;
+
+/// Protobuf formatting module.
+pub mod pb_format;
+
 /// Protobuf message module.
 pub mod pb_msg;
 
 /// Protobuf utils module.
 pub mod pb_utils;
 
+/// Platform abstraction module.
+pub mod platform;
+
+/// Plugin module.
+#[cfg(feature = "plugin")]
+pub mod plugin;
+
+/// Polling data source module.
+pub mod polling;
+
 /// Producer module.
 pub mod producer;
 
 /// Protobuf bindings module.
 pub mod protos;
 
+/// Trace redaction module.
+pub mod redaction;
+
+/// Remote trace packet relay module.
+pub mod relay;
+
+/// Runtime metrics module.
+pub mod runtime_metrics;
+
+/// Self-profiling module.
+pub mod self_profiling;
+
+/// Graceful shutdown module.
+pub mod shutdown;
+
+/// Track event slice checker module.
+pub mod slice_checker;
+
 /// Stream writer module.
 pub mod stream_writer;
 
+/// Symbolization data module.
+pub mod symbols;
+
+/// Test utilities module.
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+
+/// Thread pool instrumentation module.
+pub mod thread_pool;
+
+/// Trace analyzer module.
+pub mod trace_analyzer;
+
+/// Trace config module.
+pub mod trace_config;
+
+/// Trace diff module.
+pub mod trace_diff;
+
+/// Ftrace trace marker fallback module.
+pub mod trace_marker;
+
+/// Trace metadata module.
+pub mod trace_metadata;
+
+/// Trace reader module.
+pub mod trace_reader;
+
+/// Trace recorder module.
+pub mod trace_recorder;
+
+/// Trace stats module.
+pub mod trace_stats;
+
+/// Tracing service module.
+pub mod tracing_service;
+
 /// Tracing session module.
 pub mod tracing_session;
 
 /// Track event module.
 pub mod track_event;
 
+/// Track UUID registry module.
+pub mod track_registry;
+
+/// Callback watchdog module.
+pub mod watchdog;
+
 // FNV-1a 64-bit constants
 const FNV64_OFFSET: u64 = 0xcbf29ce484222325;
 const FNV64_PRIME: u64 = 0x00000100000001B3;
@@ -136,6 +277,11 @@ mod tests {
         data_source_name: String,
         enabled_categories: Vec<String>,
         disabled_categories: Vec<String>,
+        incremental_state_clear_period_ms: u32,
+        start_trigger: Option<String>,
+        file_write_period_ms: u32,
+        data_source_config_fields: Vec<(u32, u64)>,
+        unique_session_name: Option<String>,
     }
 
     impl TracingSessionBuilder {
@@ -161,13 +307,49 @@ mod tests {
             self
         }
 
+        #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+        pub fn set_incremental_state_clear_period_ms(mut self, period_ms: u32) -> Self {
+            self.incremental_state_clear_period_ms = period_ms;
+            self
+        }
+
+        #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+        pub fn set_start_trigger(mut self, name: impl Into<String>) -> Self {
+            self.start_trigger = Some(name.into());
+            self
+        }
+
+        #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+        pub fn set_file_write_period_ms(mut self, period_ms: u32) -> Self {
+            self.file_write_period_ms = period_ms;
+            self
+        }
+
+        #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+        pub fn set_unique_session_name(mut self, name: impl Into<String>) -> Self {
+            self.unique_session_name = Some(name.into());
+            self
+        }
+
+        // Adds the varint field `field_id` to the data source config, e.g. for
+        // fields of the config extension of a test data source.
+        #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+        pub fn add_data_source_config_field(mut self, field_id: u32, value: u64) -> Self {
+            self.data_source_config_fields.push((field_id, value));
+            self
+        }
+
         fn build_proto_config(&self) -> Vec<u8> {
             use crate::{
                 heap_buffer::HeapBuffer,
                 pb_msg::{PbMsg, PbMsgWriter},
                 protos::config::{
                     data_source_config::DataSourceConfig,
-                    trace_config::{TraceConfig, TraceConfigBufferConfig, TraceConfigDataSource},
+                    trace_config::{
+                        TraceConfig, TraceConfigBufferConfig, TraceConfigDataSource,
+                        TraceConfigIncrementalStateConfig, TraceConfigTriggerConfig,
+                        TraceConfigTriggerConfigTrigger, TriggerConfigTriggerMode,
+                    },
                     track_event::track_event_config::TrackEventConfig,
                 },
             };
@@ -179,6 +361,31 @@ mod tests {
                 cfg.set_buffers(|buf_cfg: &mut TraceConfigBufferConfig| {
                     buf_cfg.set_size_kb(1024);
                 });
+                if self.incremental_state_clear_period_ms != 0 {
+                    cfg.set_incremental_state_config(
+                        |incr_cfg: &mut TraceConfigIncrementalStateConfig| {
+                            incr_cfg.set_clear_period_ms(self.incremental_state_clear_period_ms);
+                        },
+                    );
+                }
+                if self.file_write_period_ms != 0 {
+                    cfg.set_write_into_file(true);
+                    cfg.set_file_write_period_ms(self.file_write_period_ms);
+                }
+                if let Some(unique_session_name) = &self.unique_session_name {
+                    cfg.set_unique_session_name(unique_session_name);
+                }
+                if let Some(start_trigger) = &self.start_trigger {
+                    cfg.set_trigger_config(|trigger_cfg: &mut TraceConfigTriggerConfig| {
+                        trigger_cfg.set_trigger_mode(TriggerConfigTriggerMode::StartTracing);
+                        trigger_cfg.set_trigger_timeout_ms(60_000);
+                        trigger_cfg.set_triggers(
+                            |trigger: &mut TraceConfigTriggerConfigTrigger| {
+                                trigger.set_name(start_trigger);
+                            },
+                        );
+                    });
+                }
                 cfg.set_data_sources(|data_sources: &mut TraceConfigDataSource| {
                     data_sources.set_config(|ds_cfg: &mut DataSourceConfig| {
                         ds_cfg.set_name(&self.data_source_name);
@@ -194,6 +401,9 @@ mod tests {
                                 }
                             });
                         }
+                        for (field_id, value) in &self.data_source_config_fields {
+                            ds_cfg.msg.append_type0_field(*field_id, *value);
+                        }
                     });
                 });
             }
@@ -211,6 +421,28 @@ mod tests {
             ts.setup(&config);
             Ok(ts)
         }
+
+        #[cfg(unix)]
+        pub fn build_with_file(
+            &self,
+            file: std::fs::File,
+        ) -> Result<TracingSession, TracingSessionError> {
+            let config = self.build_proto_config();
+            let mut ts = TracingSession::in_process()?;
+            ts.setup_with_file(&config, file);
+            Ok(ts)
+        }
+    }
+
+    pub(crate) fn read_trace_data(session: &mut TracingSession) -> Vec<u8> {
+        use std::sync::Arc;
+        let trace_data = Arc::new(Mutex::new(vec![]));
+        let trace_data_for_write = Arc::clone(&trace_data);
+        session.read_trace_blocking(move |data, _end| {
+            trace_data_for_write.lock().unwrap().extend_from_slice(data);
+        });
+        let data = trace_data.lock().unwrap();
+        data.clone()
     }
 
     #[test]
@@ -0,0 +1,1029 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    counter_value::CounterValue,
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField},
+    pb_utils::pb_parse_packed_varints,
+    platform::{self, PlatformThread},
+    producer::{Backends, InitState, Producer},
+    protos::trace::{
+        clock_snapshot::{ClockSnapshotClockFieldNumber, ClockSnapshotFieldNumber},
+        trace_packet::{
+            TracePacketDefaultsFieldNumber, TracePacketFieldNumber, TracePacketSequenceFlags,
+        },
+        track_event::{
+            counter_descriptor::CounterDescriptorFieldNumber,
+            track_descriptor::TrackDescriptorFieldNumber,
+            track_event::{TrackEventDefaultsFieldNumber, TrackEventFieldNumber, TrackEventType},
+        },
+    },
+    trace_reader::TraceStreamReader,
+    tracing_session::{TracingSession, TracingSessionError},
+};
+use std::{
+    collections::HashMap,
+    io,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+    },
+    time::Duration,
+};
+use thiserror::Error;
+
+/// Default time between two reads of the buffers of a subscription, see
+/// [`LiveCounters::read_period`].
+pub const DEFAULT_READ_PERIOD: Duration = Duration::from_millis(100);
+
+/// Default time the data sources have to flush their data before each read,
+/// see [`LiveCounters::flush_timeout`].
+pub const DEFAULT_FLUSH_TIMEOUT: Duration = Duration::from_millis(500);
+
+// Fields of the GPU counter protos, which are defined in the
+// `perfetto-sdk-protos-gpu` crate.
+const GPU_COUNTER_EVENT_FIELD_ID: u32 = 52;
+const GPU_COUNTER_EVENT_DESCRIPTOR_ID: u32 = 1;
+const GPU_COUNTER_EVENT_COUNTERS_ID: u32 = 2;
+const GPU_COUNTER_EVENT_GPU_ID_ID: u32 = 3;
+const GPU_COUNTER_DESCRIPTOR_SPECS_ID: u32 = 1;
+const GPU_COUNTER_SPEC_COUNTER_ID_ID: u32 = 1;
+const GPU_COUNTER_SPEC_NAME_ID: u32 = 2;
+const GPU_COUNTER_COUNTER_ID_ID: u32 = 1;
+const GPU_COUNTER_INT_VALUE_ID: u32 = 2;
+const GPU_COUNTER_DOUBLE_VALUE_ID: u32 = 3;
+
+/// Live counter subscription errors.
+#[derive(Error, Debug)]
+pub enum LiveCountersError {
+    /// The tracing session couldn't be created.
+    #[error("Failed to create tracing session: {0}")]
+    Session(#[from] TracingSessionError),
+    /// The thread reading the session couldn't be created.
+    #[error("Failed to spawn reader thread: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Track of a [`CounterSample`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CounterTrack {
+    /// Counter track of track events, by track UUID.
+    TrackEvent(u64),
+    /// GPU counter, by GPU id, zero if unset, and counter id.
+    Gpu {
+        /// Id of the GPU.
+        gpu_id: i32,
+        /// Id of the counter.
+        counter_id: u32,
+    },
+}
+
+/// Value of a counter decoded from a trace.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CounterSample {
+    /// Track of the counter.
+    pub track: CounterTrack,
+    /// Name of the track, if its descriptor was decoded.
+    pub name: Option<Arc<str>>,
+    /// Timestamp of the value, in nanoseconds.
+    pub timestamp: u64,
+    /// Value of the counter. Values of incremental counter tracks are
+    /// accumulated.
+    pub value: CounterValue,
+}
+
+#[derive(Debug, Default)]
+struct SequenceState {
+    timestamp_clock_id: Option<u32>,
+    track_uuid: Option<u64>,
+    extra_counter_track_uuids: Vec<u64>,
+    extra_double_counter_track_uuids: Vec<u64>,
+    // Incremental clocks of the sequence, with their last timestamp on the
+    // non-incremental clock of their snapshot and their unit.
+    incremental_clocks: HashMap<u32, (u64, u64)>,
+}
+
+/// Decodes the counter values of the packets of a trace: the counter track
+/// events, including their extra counter values, and the GPU counter events.
+///
+/// The decoder keeps the state that the packets of each sequence depend on,
+/// i.e. the names of the tracks, the track event defaults and the
+/// incremental clocks and counters, so packets must be decoded in the order
+/// of the trace.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::{live_counters::CounterDecoder, trace_reader::TraceReader};
+///
+/// # let trace: Vec<u8> = vec![];
+/// let mut decoder = CounterDecoder::new();
+/// for packet in TraceReader::new(&trace) {
+///     decoder
+///         .decode_packet(&packet.unwrap(), |sample| {
+///             println!("{:?} {} {:?}", sample.name, sample.timestamp, sample.value);
+///         })
+///         .unwrap();
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct CounterDecoder {
+    sequences: HashMap<u32, SequenceState>,
+    names: HashMap<CounterTrack, Arc<str>>,
+    // Totals of the incremental counter tracks.
+    totals: HashMap<u64, CounterValue>,
+}
+
+impl CounterDecoder {
+    /// Creates a new decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decodes the encoded `TracePacket` `packet` and calls `on_sample` with
+    /// each counter value it contains.
+    pub fn decode_packet<F>(
+        &mut self,
+        packet: &[u8],
+        mut on_sample: F,
+    ) -> Result<(), PbDecoderError>
+    where
+        F: FnMut(CounterSample),
+    {
+        use TracePacketFieldNumber as Packet;
+        let mut timestamp = None;
+        let mut timestamp_clock_id = None;
+        let mut sequence_id = 0;
+        let mut sequence_flags = 0;
+        let mut messages = Vec::new();
+        for item in PbDecoder::new(packet) {
+            match item? {
+                (id, PbDecoderField::Varint(value)) if id == Packet::Timestamp as u32 => {
+                    timestamp = Some(value)
+                }
+                (id, PbDecoderField::Varint(value)) if id == Packet::TimestampClockId as u32 => {
+                    timestamp_clock_id = Some(value as u32)
+                }
+                (id, PbDecoderField::Varint(value))
+                    if id == Packet::TrustedPacketSequenceId as u32 =>
+                {
+                    sequence_id = value as u32
+                }
+                (id, PbDecoderField::Varint(value)) if id == Packet::SequenceFlags as u32 => {
+                    sequence_flags = value
+                }
+                (id, PbDecoderField::Delimited(data))
+                    if id == Packet::ClockSnapshot as u32
+                        || id == Packet::TracePacketDefaults as u32
+                        || id == Packet::TrackDescriptor as u32
+                        || id == Packet::TrackEvent as u32
+                        || id == GPU_COUNTER_EVENT_FIELD_ID =>
+                {
+                    messages.push((id, data))
+                }
+                _ => {}
+            }
+        }
+
+        if sequence_flags & TracePacketSequenceFlags::SeqIncrementalStateCleared as u64 != 0 {
+            self.sequences.remove(&sequence_id);
+        }
+        // The state set by the packet applies to the packet itself.
+        for (id, data) in &messages {
+            match *id {
+                id if id == Packet::ClockSnapshot as u32 => {
+                    self.decode_clock_snapshot(sequence_id, data)?
+                }
+                id if id == Packet::TracePacketDefaults as u32 => {
+                    self.decode_packet_defaults(sequence_id, data)?
+                }
+                id if id == Packet::TrackDescriptor as u32 => self.decode_track_descriptor(data)?,
+                _ => {}
+            }
+        }
+        let Some(timestamp) = timestamp else {
+            return Ok(());
+        };
+        let timestamp = self.resolve_timestamp(sequence_id, timestamp, timestamp_clock_id);
+        for (id, data) in messages {
+            match id {
+                id if id == Packet::TrackEvent as u32 => {
+                    self.decode_track_event(sequence_id, timestamp, data, &mut on_sample)?
+                }
+                GPU_COUNTER_EVENT_FIELD_ID => {
+                    self.decode_gpu_counter_event(timestamp, data, &mut on_sample)?
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the timestamp of a packet of `sequence_id`, converting the
+    // deltas of incremental clocks to timestamps.
+    fn resolve_timestamp(
+        &mut self,
+        sequence_id: u32,
+        timestamp: u64,
+        clock_id: Option<u32>,
+    ) -> u64 {
+        let Some(sequence) = self.sequences.get_mut(&sequence_id) else {
+            return timestamp;
+        };
+        let Some(clock_id) = clock_id.or(sequence.timestamp_clock_id) else {
+            return timestamp;
+        };
+        match sequence.incremental_clocks.get_mut(&clock_id) {
+            Some((last, unit)) => {
+                *last = last.wrapping_add(timestamp.wrapping_mul(*unit));
+                *last
+            }
+            None => timestamp,
+        }
+    }
+
+    fn decode_clock_snapshot(
+        &mut self,
+        sequence_id: u32,
+        data: &[u8],
+    ) -> Result<(), PbDecoderError> {
+        use ClockSnapshotClockFieldNumber as Clock;
+        let mut incremental = Vec::new();
+        let mut reference = None;
+        for item in PbDecoder::new(data) {
+            let (id, field) = item?;
+            if id != ClockSnapshotFieldNumber::Clocks as u32 {
+                continue;
+            }
+            let (mut clock_id, mut timestamp, mut is_incremental, mut unit) = (0, 0, false, 1);
+            for item in field.as_decoder()? {
+                match item? {
+                    (id, PbDecoderField::Varint(value)) if id == Clock::ClockId as u32 => {
+                        clock_id = value as u32
+                    }
+                    (id, PbDecoderField::Varint(value)) if id == Clock::Timestamp as u32 => {
+                        timestamp = value
+                    }
+                    (id, PbDecoderField::Varint(value)) if id == Clock::IsIncremental as u32 => {
+                        is_incremental = value != 0
+                    }
+                    (id, PbDecoderField::Varint(value)) if id == Clock::UnitMultiplierNs as u32 => {
+                        unit = value.max(1)
+                    }
+                    _ => {}
+                }
+            }
+            if is_incremental {
+                incremental.push((clock_id, timestamp, unit));
+            } else if reference.is_none() {
+                reference = Some(timestamp);
+            }
+        }
+        let sequence = self.sequences.entry(sequence_id).or_default();
+        for (clock_id, timestamp, unit) in incremental {
+            let reference = reference.unwrap_or(timestamp.wrapping_mul(unit));
+            sequence
+                .incremental_clocks
+                .insert(clock_id, (reference, unit));
+        }
+        Ok(())
+    }
+
+    fn decode_packet_defaults(
+        &mut self,
+        sequence_id: u32,
+        data: &[u8],
+    ) -> Result<(), PbDecoderError> {
+        use TrackEventDefaultsFieldNumber as Defaults;
+        let sequence = self.sequences.entry(sequence_id).or_default();
+        for item in PbDecoder::new(data) {
+            match item? {
+                (id, PbDecoderField::Varint(value))
+                    if id == TracePacketDefaultsFieldNumber::TimestampClockId as u32 =>
+                {
+                    sequence.timestamp_clock_id = Some(value as u32)
+                }
+                (id, field) if id == TracePacketDefaultsFieldNumber::TrackEventDefaults as u32 => {
+                    sequence.track_uuid = None;
+                    sequence.extra_counter_track_uuids.clear();
+                    sequence.extra_double_counter_track_uuids.clear();
+                    for item in field.as_decoder()? {
+                        match item? {
+                            (id, PbDecoderField::Varint(value))
+                                if id == Defaults::TrackUuid as u32 =>
+                            {
+                                sequence.track_uuid = Some(value)
+                            }
+                            (id, field) if id == Defaults::ExtraCounterTrackUuids as u32 => {
+                                push_varints(&mut sequence.extra_counter_track_uuids, field)
+                            }
+                            (id, field) if id == Defaults::ExtraDoubleCounterTrackUuids as u32 => {
+                                push_varints(&mut sequence.extra_double_counter_track_uuids, field)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_track_descriptor(&mut self, data: &[u8]) -> Result<(), PbDecoderError> {
+        use TrackDescriptorFieldNumber as Track;
+        let (mut uuid, mut name, mut is_incremental) = (None, None, false);
+        for item in PbDecoder::new(data) {
+            match item? {
+                (id, PbDecoderField::Varint(value)) if id == Track::Uuid as u32 => {
+                    uuid = Some(value)
+                }
+                (id, field) if id == Track::Name as u32 || id == Track::StaticName as u32 => {
+                    name = Some(field.as_str()?)
+                }
+                (id, field) if id == Track::Counter as u32 => {
+                    for item in field.as_decoder()? {
+                        if let (id, PbDecoderField::Varint(value)) = item?
+                            && id == CounterDescriptorFieldNumber::IsIncremental as u32
+                        {
+                            is_incremental = value != 0;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let Some(uuid) = uuid else {
+            return Ok(());
+        };
+        if let Some(name) = name {
+            self.names
+                .insert(CounterTrack::TrackEvent(uuid), name.into());
+        }
+        // Incremental counters start over from zero each time their
+        // descriptor is written, i.e. after the incremental state of the
+        // sequence was cleared.
+        if is_incremental {
+            self.totals.insert(uuid, CounterValue::Int(0));
+        } else {
+            self.totals.remove(&uuid);
+        }
+        Ok(())
+    }
+
+    fn decode_track_event<F>(
+        &mut self,
+        sequence_id: u32,
+        timestamp: u64,
+        data: &[u8],
+        on_sample: &mut F,
+    ) -> Result<(), PbDecoderError>
+    where
+        F: FnMut(CounterSample),
+    {
+        use TrackEventFieldNumber as Event;
+        let (mut is_counter, mut track_uuid, mut value) = (false, None, None);
+        let (mut extra_uuids, mut extra_values) = (None, Vec::new());
+        let (mut extra_double_uuids, mut extra_double_values) = (None, Vec::new());
+        for item in PbDecoder::new(data) {
+            match item? {
+                (id, PbDecoderField::Varint(value)) if id == Event::Type as u32 => {
+                    is_counter = value == TrackEventType::TypeCounter as u64
+                }
+                (id, PbDecoderField::Varint(value)) if id == Event::TrackUuid as u32 => {
+                    track_uuid = Some(value)
+                }
+                (id, PbDecoderField::Varint(v)) if id == Event::CounterValue as u32 => {
+                    value = Some(CounterValue::Int(v as i64))
+                }
+                (id, PbDecoderField::Fixed64(v)) if id == Event::DoubleCounterValue as u32 => {
+                    value = Some(CounterValue::Double(f64::from_bits(v)))
+                }
+                (id, field) if id == Event::ExtraCounterTrackUuids as u32 => {
+                    push_varints(extra_uuids.get_or_insert_with(Vec::new), field)
+                }
+                (id, field) if id == Event::ExtraCounterValues as u32 => {
+                    let mut values = Vec::new();
+                    push_varints(&mut values, field);
+                    extra_values.extend(values.into_iter().map(|v| CounterValue::Int(v as i64)));
+                }
+                (id, field) if id == Event::ExtraDoubleCounterTrackUuids as u32 => {
+                    push_varints(extra_double_uuids.get_or_insert_with(Vec::new), field)
+                }
+                (id, field) if id == Event::ExtraDoubleCounterValues as u32 => {
+                    push_doubles(&mut extra_double_values, field)
+                }
+                _ => {}
+            }
+        }
+
+        let sequence = self.sequences.get(&sequence_id);
+        let mut samples = Vec::new();
+        if is_counter
+            && let Some(value) = value
+            && let Some(uuid) = track_uuid.or(sequence.and_then(|s| s.track_uuid))
+        {
+            samples.push((uuid, value));
+        }
+        let extra_uuids = extra_uuids
+            .or_else(|| sequence.map(|s| s.extra_counter_track_uuids.clone()))
+            .unwrap_or_default();
+        samples.extend(extra_uuids.into_iter().zip(extra_values));
+        let extra_double_uuids = extra_double_uuids
+            .or_else(|| sequence.map(|s| s.extra_double_counter_track_uuids.clone()))
+            .unwrap_or_default();
+        samples.extend(extra_double_uuids.into_iter().zip(extra_double_values));
+
+        for (uuid, value) in samples {
+            let value = match self.totals.get_mut(&uuid) {
+                Some(total) => {
+                    *total = match (*total, value) {
+                        (CounterValue::Int(total), CounterValue::Int(delta)) => {
+                            CounterValue::Int(total.wrapping_add(delta))
+                        }
+                        (total, delta) => CounterValue::Double(as_f64(total) + as_f64(delta)),
+                    };
+                    *total
+                }
+                None => value,
+            };
+            let track = CounterTrack::TrackEvent(uuid);
+            on_sample(CounterSample {
+                track,
+                name: self.names.get(&track).cloned(),
+                timestamp,
+                value,
+            });
+        }
+        Ok(())
+    }
+
+    fn decode_gpu_counter_event<F>(
+        &mut self,
+        timestamp: u64,
+        data: &[u8],
+        on_sample: &mut F,
+    ) -> Result<(), PbDecoderError>
+    where
+        F: FnMut(CounterSample),
+    {
+        let mut gpu_id = 0;
+        let mut specs = Vec::new();
+        let mut counters = Vec::new();
+        for item in PbDecoder::new(data) {
+            match item? {
+                (GPU_COUNTER_EVENT_GPU_ID_ID, PbDecoderField::Varint(value)) => {
+                    gpu_id = value as i32
+                }
+                (GPU_COUNTER_EVENT_DESCRIPTOR_ID, field) => {
+                    for item in field.as_decoder()? {
+                        if let (GPU_COUNTER_DESCRIPTOR_SPECS_ID, field) = item? {
+                            specs.push(field.as_bytes()?);
+                        }
+                    }
+                }
+                (GPU_COUNTER_EVENT_COUNTERS_ID, field) => counters.push(field.as_bytes()?),
+                _ => {}
+            }
+        }
+
+        for spec in specs {
+            let (mut counter_id, mut name) = (0, None);
+            for item in PbDecoder::new(spec) {
+                match item? {
+                    (GPU_COUNTER_SPEC_COUNTER_ID_ID, PbDecoderField::Varint(value)) => {
+                        counter_id = value as u32
+                    }
+                    (GPU_COUNTER_SPEC_NAME_ID, field) => name = Some(field.as_str()?),
+                    _ => {}
+                }
+            }
+            if let Some(name) = name {
+                self.names
+                    .insert(CounterTrack::Gpu { gpu_id, counter_id }, name.into());
+            }
+        }
+        for counter in counters {
+            let (mut counter_id, mut value) = (0, None);
+            for item in PbDecoder::new(counter) {
+                match item? {
+                    (GPU_COUNTER_COUNTER_ID_ID, PbDecoderField::Varint(v)) => counter_id = v as u32,
+                    (GPU_COUNTER_INT_VALUE_ID, PbDecoderField::Varint(v)) => {
+                        value = Some(CounterValue::Int(v as i64))
+                    }
+                    (GPU_COUNTER_DOUBLE_VALUE_ID, PbDecoderField::Fixed64(v)) => {
+                        value = Some(CounterValue::Double(f64::from_bits(v)))
+                    }
+                    _ => {}
+                }
+            }
+            let Some(value) = value else {
+                continue;
+            };
+            let track = CounterTrack::Gpu { gpu_id, counter_id };
+            on_sample(CounterSample {
+                track,
+                name: self.names.get(&track).cloned(),
+                timestamp,
+                value,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn as_f64(value: CounterValue) -> f64 {
+    match value {
+        CounterValue::Int(value) => value as f64,
+        CounterValue::Double(value) => value,
+    }
+}
+
+// Appends the values of a repeated varint field, packed or not.
+fn push_varints(values: &mut Vec<u64>, field: PbDecoderField) {
+    match field {
+        PbDecoderField::Varint(value) => values.push(value),
+        PbDecoderField::Delimited(data) => values.extend(pb_parse_packed_varints(data)),
+        _ => {}
+    }
+}
+
+// Appends the values of a repeated double field, packed or not.
+fn push_doubles(values: &mut Vec<CounterValue>, field: PbDecoderField) {
+    match field {
+        PbDecoderField::Fixed64(value) => values.push(CounterValue::Double(f64::from_bits(value))),
+        PbDecoderField::Delimited(data) => values.extend(
+            data.chunks_exact(8)
+                .map(|bytes| CounterValue::Double(f64::from_le_bytes(bytes.try_into().unwrap()))),
+        ),
+        _ => {}
+    }
+}
+
+/// Subscribes to the counters of a live tracing session, for tools that plot
+/// them while they are recorded rather than analyze the trace afterwards.
+///
+/// The subscription starts a session with the given config, and a thread
+/// that periodically flushes the data sources, reads the buffers of the
+/// session, decodes their counter values with a [`CounterDecoder`] and sends
+/// them over a channel.
+///
+/// The buffers are drained by each read, so that the session only needs
+/// buffers large enough for the data written during one read period.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{live_counters::LiveCounters, trace_config::TraceConfigBuilder};
+///
+/// let config = TraceConfigBuilder::ring_buffer(1024)
+///     .track_event(&["gpu"])
+///     .build()
+///     .unwrap();
+/// let subscription = LiveCounters::new().subscribe(&config).unwrap();
+/// for sample in subscription.receiver() {
+///     println!("{:?} {} {:?}", sample.name, sample.timestamp, sample.value);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+#[must_use = "This is a builder; remember to call `.subscribe()` (or keep chaining)."]
+pub struct LiveCounters {
+    backend: Backends,
+    read_period: Duration,
+    flush_timeout: Duration,
+}
+
+impl Default for LiveCounters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LiveCounters {
+    /// Creates a subscription builder using the system backend if the
+    /// producer initialized it, and the in-process backend otherwise.
+    pub fn new() -> Self {
+        let backend = match Producer::init_state() {
+            InitState::Initialized(backends) if backends.contains(Backends::SYSTEM) => {
+                Backends::SYSTEM
+            }
+            _ => Backends::IN_PROCESS,
+        };
+        Self {
+            backend,
+            read_period: DEFAULT_READ_PERIOD,
+            flush_timeout: DEFAULT_FLUSH_TIMEOUT,
+        }
+    }
+
+    /// Sets the backend of the session, either [`Backends::IN_PROCESS`] or
+    /// [`Backends::SYSTEM`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn backend(mut self, backend: Backends) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets the time between two reads of the buffers of the session, which
+    /// is the latency of the samples. Defaults to [`DEFAULT_READ_PERIOD`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn read_period(mut self, period: Duration) -> Self {
+        self.read_period = period;
+        self
+    }
+
+    /// Sets how long the data sources have to flush their data before each
+    /// read. Defaults to [`DEFAULT_FLUSH_TIMEOUT`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn flush_timeout(mut self, timeout: Duration) -> Self {
+        self.flush_timeout = timeout;
+        self
+    }
+
+    /// Starts a session with the encoded `TraceConfig` `config` and returns
+    /// the subscription to its counters, once the session is started.
+    pub fn subscribe(&self, config: &[u8]) -> Result<LiveCounterSubscription, LiveCountersError> {
+        let (sender, receiver) = mpsc::channel();
+        let (started_sender, started) = mpsc::channel();
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let reader = Reader {
+            settings: self.clone(),
+            stop: Arc::clone(&stop),
+            sender,
+        };
+        let config = config.to_vec();
+        // Sessions are created on the reader thread as they aren't `Send`.
+        let thread = platform::spawn("perfetto-live-counters", move || {
+            reader.run(&config, started_sender)
+        })?;
+        match started.recv() {
+            Ok(Ok(())) => Ok(LiveCounterSubscription {
+                receiver,
+                stop,
+                thread: Some(thread),
+            }),
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                Err(err.into())
+            }
+            Err(_) => {
+                let _ = thread.join();
+                Err(TracingSessionError::CreateError.into())
+            }
+        }
+    }
+}
+
+// State of the thread of a subscription.
+struct Reader {
+    settings: LiveCounters,
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    sender: Sender<CounterSample>,
+}
+
+impl Reader {
+    fn run(self, config: &[u8], started: Sender<Result<(), TracingSessionError>>) {
+        let session = if self.settings.backend.contains(Backends::SYSTEM) {
+            TracingSession::system()
+        } else {
+            TracingSession::in_process()
+        };
+        let mut session = match session {
+            Ok(session) => session,
+            Err(err) => {
+                let _ = started.send(Err(err));
+                return;
+            }
+        };
+        session.setup(config);
+        let stopped_by_service = Arc::new(AtomicBool::new(false));
+        let on_stop = Arc::clone(&stopped_by_service);
+        let stop = Arc::clone(&self.stop);
+        session.set_stop_callback(move || {
+            on_stop.store(true, Ordering::Relaxed);
+            stop.1.notify_all();
+        });
+        session.start_blocking();
+        let _ = started.send(Ok(()));
+
+        let mut stream = TraceStreamReader::new();
+        let mut decoder = CounterDecoder::new();
+        loop {
+            let stopping = {
+                let (lock, condvar) = &*self.stop;
+                let stopped = lock.lock().unwrap();
+                let stopped = condvar
+                    .wait_timeout_while(stopped, self.settings.read_period, |stopped| {
+                        !*stopped && !stopped_by_service.load(Ordering::Relaxed)
+                    })
+                    .unwrap()
+                    .0;
+                *stopped || stopped_by_service.load(Ordering::Relaxed)
+            };
+            if !stopped_by_service.load(Ordering::Relaxed) {
+                session.flush_blocking(self.settings.flush_timeout);
+                if stopping {
+                    session.stop_blocking();
+                }
+            }
+            self.read(&mut session, &mut stream, &mut decoder);
+            if stopping {
+                break;
+            }
+        }
+    }
+
+    // Reads the buffers of `session` and sends their counter values.
+    fn read(
+        &self,
+        session: &mut TracingSession,
+        stream: &mut TraceStreamReader,
+        decoder: &mut CounterDecoder,
+    ) {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        let data_for_read = Arc::clone(&data);
+        session.read_trace_blocking(move |data, _has_more| {
+            data_for_read.lock().unwrap().extend_from_slice(data);
+        });
+        stream.push(&data.lock().unwrap());
+        while let Some(packet) = stream.next_packet() {
+            // Packets that can't be decoded are skipped rather than ending
+            // the subscription.
+            let Ok(packet) = packet else {
+                continue;
+            };
+            let _ = decoder.decode_packet(&packet, |sample| {
+                let _ = self.sender.send(sample);
+            });
+        }
+    }
+}
+
+/// Subscription to the counters of a live tracing session, created by
+/// [`LiveCounters::subscribe`]. The session is stopped when the subscription
+/// is stopped or dropped.
+pub struct LiveCounterSubscription {
+    receiver: Receiver<CounterSample>,
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<PlatformThread>,
+}
+
+impl LiveCounterSubscription {
+    /// Returns the receiver of the counter values. The channel is
+    /// disconnected once the session stops, e.g. once the duration of its
+    /// config elapsed.
+    pub fn receiver(&self) -> &Receiver<CounterSample> {
+        &self.receiver
+    }
+
+    /// Stops the session, after a last read of its buffers, and returns the
+    /// counter values that weren't received yet.
+    pub fn stop(mut self) -> Vec<CounterSample> {
+        self.stop_thread();
+        self.receiver.try_iter().collect()
+    }
+
+    fn stop_thread(&mut self) {
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+        let (lock, condvar) = &*self.stop;
+        *lock.lock().unwrap() = true;
+        condvar.notify_all();
+        let _ = thread.join();
+    }
+}
+
+impl Drop for LiveCounterSubscription {
+    fn drop(&mut self) {
+        self.stop_thread();
+    }
+}
+
+impl std::fmt::Debug for LiveCounterSubscription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LiveCounterSubscription")
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_decoder::append_field,
+        tests::acquire_test_environment,
+        trace_config::TraceConfigBuilder,
+        track_event::{EventContext, TrackEvent, TrackEventTrack},
+        track_event_counter,
+    };
+    use std::error::Error;
+
+    crate::track_event_categories! {
+        pub mod test_te_ns {
+            ( "live", "Test live counters", [] ),
+        }
+    }
+    use test_te_ns as perfetto_te_ns;
+
+    // Returns the message with `fields`.
+    fn message(fields: &[(u32, PbDecoderField)]) -> Vec<u8> {
+        let mut out = vec![];
+        for (id, field) in fields {
+            append_field(&mut out, *id, field);
+        }
+        out
+    }
+
+    fn decode(decoder: &mut CounterDecoder, packet: &[u8]) -> Vec<CounterSample> {
+        let mut samples = vec![];
+        decoder
+            .decode_packet(packet, |sample| samples.push(sample))
+            .unwrap();
+        samples
+    }
+
+    #[test]
+    fn decode_counters() {
+        use PbDecoderField::*;
+        use TracePacketFieldNumber as Packet;
+        let mut decoder = CounterDecoder::new();
+        let clock = |id: u32, timestamp: u64, is_incremental: bool| {
+            message(&[
+                (
+                    ClockSnapshotClockFieldNumber::ClockId as u32,
+                    Varint(id.into()),
+                ),
+                (
+                    ClockSnapshotClockFieldNumber::Timestamp as u32,
+                    Varint(timestamp),
+                ),
+                (
+                    ClockSnapshotClockFieldNumber::IsIncremental as u32,
+                    Varint(is_incremental.into()),
+                ),
+            ])
+        };
+        let (incremental, boot) = (clock(64, 1_000, true), clock(6, 5_000, false));
+        let snapshot = message(&[
+            (
+                ClockSnapshotFieldNumber::Clocks as u32,
+                Delimited(&incremental),
+            ),
+            (ClockSnapshotFieldNumber::Clocks as u32, Delimited(&boot)),
+        ]);
+        let defaults = message(&[(
+            TracePacketDefaultsFieldNumber::TimestampClockId as u32,
+            Varint(64),
+        )]);
+        let counter = message(&[(
+            CounterDescriptorFieldNumber::IsIncremental as u32,
+            Varint(1),
+        )]);
+        let track = message(&[
+            (TrackDescriptorFieldNumber::Uuid as u32, Varint(10)),
+            (TrackDescriptorFieldNumber::Name as u32, Delimited(b"bytes")),
+            (
+                TrackDescriptorFieldNumber::Counter as u32,
+                Delimited(&counter),
+            ),
+        ]);
+        let setup = message(&[
+            (Packet::TrustedPacketSequenceId as u32, Varint(7)),
+            (
+                Packet::SequenceFlags as u32,
+                Varint(TracePacketSequenceFlags::SeqIncrementalStateCleared as u64),
+            ),
+            (Packet::ClockSnapshot as u32, Delimited(&snapshot)),
+            (Packet::TracePacketDefaults as u32, Delimited(&defaults)),
+            (Packet::TrackDescriptor as u32, Delimited(&track)),
+        ]);
+        assert!(decode(&mut decoder, &setup).is_empty());
+
+        let event = |delta: u64, value: u64| {
+            let event = message(&[
+                (
+                    TrackEventFieldNumber::Type as u32,
+                    Varint(TrackEventType::TypeCounter as u64),
+                ),
+                (TrackEventFieldNumber::TrackUuid as u32, Varint(10)),
+                (TrackEventFieldNumber::CounterValue as u32, Varint(value)),
+            ]);
+            message(&[
+                (Packet::Timestamp as u32, Varint(delta)),
+                (Packet::TrustedPacketSequenceId as u32, Varint(7)),
+                (Packet::TrackEvent as u32, Delimited(&event)),
+            ])
+        };
+        let samples: Vec<_> = [event(10, 5), event(3, 2)]
+            .iter()
+            .flat_map(|packet| decode(&mut decoder, packet))
+            .map(|sample| (sample.track, sample.name, sample.timestamp, sample.value))
+            .collect();
+        let name = Some(Arc::from("bytes"));
+        assert_eq!(
+            samples,
+            vec![
+                (
+                    CounterTrack::TrackEvent(10),
+                    name.clone(),
+                    5_010,
+                    CounterValue::Int(5)
+                ),
+                (
+                    CounterTrack::TrackEvent(10),
+                    name,
+                    5_013,
+                    CounterValue::Int(7)
+                ),
+            ]
+        );
+
+        let spec = message(&[
+            (GPU_COUNTER_SPEC_COUNTER_ID_ID, Varint(1)),
+            (GPU_COUNTER_SPEC_NAME_ID, Delimited(b"GPU Frequency")),
+        ]);
+        let descriptor = message(&[(GPU_COUNTER_DESCRIPTOR_SPECS_ID, Delimited(&spec))]);
+        let gpu_counter = message(&[
+            (GPU_COUNTER_COUNTER_ID_ID, Varint(1)),
+            (GPU_COUNTER_DOUBLE_VALUE_ID, Fixed64(300.5f64.to_bits())),
+        ]);
+        let gpu_event = message(&[
+            (GPU_COUNTER_EVENT_DESCRIPTOR_ID, Delimited(&descriptor)),
+            (GPU_COUNTER_EVENT_COUNTERS_ID, Delimited(&gpu_counter)),
+            (GPU_COUNTER_EVENT_GPU_ID_ID, Varint(2)),
+        ]);
+        let packet = message(&[
+            (Packet::Timestamp as u32, Varint(42)),
+            (Packet::TrustedPacketSequenceId as u32, Varint(8)),
+            (GPU_COUNTER_EVENT_FIELD_ID, Delimited(&gpu_event)),
+        ]);
+        assert_eq!(
+            decode(&mut decoder, &packet),
+            vec![CounterSample {
+                track: CounterTrack::Gpu {
+                    gpu_id: 2,
+                    counter_id: 1
+                },
+                name: Some(Arc::from("GPU Frequency")),
+                timestamp: 42,
+                value: CounterValue::Double(300.5),
+            }]
+        );
+    }
+
+    #[test]
+    fn subscribe() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        TrackEvent::init();
+        test_te_ns::register()?;
+        let track = TrackEventTrack::register_counter_track("live_counter", 0)?;
+        let config = TraceConfigBuilder::ring_buffer(1024)
+            .track_event(&["live"])
+            .build()?;
+        let subscription = LiveCounters::new()
+            .backend(Backends::IN_PROCESS)
+            .read_period(Duration::from_millis(10))
+            .subscribe(&config)?;
+        for value in 0..3i64 {
+            track_event_counter!("live", |ctx: &mut EventContext| {
+                ctx.set_track(&track).set_counter(value);
+            });
+        }
+        let sample = subscription
+            .receiver()
+            .recv_timeout(Duration::from_secs(10))?;
+        let mut samples = vec![sample];
+        samples.extend(subscription.stop());
+        test_te_ns::unregister()?;
+
+        assert!(
+            samples
+                .iter()
+                .all(|sample| sample.track == CounterTrack::TrackEvent(track.uuid()))
+        );
+        assert_eq!(samples[0].name.as_deref(), Some("live_counter"));
+        let values: Vec<_> = samples.iter().map(|sample| sample.value).collect();
+        assert_eq!(
+            values,
+            vec![
+                CounterValue::Int(0),
+                CounterValue::Int(1),
+                CounterValue::Int(2)
+            ]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,341 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fmt,
+    str::FromStr,
+    sync::{
+        Arc, PoisonError, RwLock,
+        atomic::{AtomicU8, Ordering},
+    },
+};
+use thiserror::Error;
+
+/// Environment variable with the initial maximum level of the SDK logs, one
+/// of `off`, `error`, `warn`, `info` or `debug`.
+pub const LOG_LEVEL_ENV: &str = "PERFETTO_SDK_LOG";
+
+/// Level of an SDK log message, from the most to the least severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    /// Failures that lose trace data or abort the process.
+    Error = 1,
+    /// Unexpected conditions, e.g. slow callbacks or dropped packets.
+    Warn,
+    /// Informational messages.
+    Info,
+    /// Verbose diagnostics, e.g. for every dropped packet.
+    Debug,
+}
+
+impl LogLevel {
+    /// Returns the name of the level.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+        }
+    }
+
+    fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            1 => Some(LogLevel::Error),
+            2 => Some(LogLevel::Warn),
+            3 => Some(LogLevel::Info),
+            4 => Some(LogLevel::Debug),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Error returned when parsing an unknown log level.
+#[derive(Error, Debug, PartialEq)]
+#[error("unknown log level: {0}")]
+pub struct ParseLogLevelError(String);
+
+/// Parses a maximum log level, case-insensitively. `off` is parsed as `None`.
+pub fn parse_max_level(s: &str) -> Result<Option<LogLevel>, ParseLogLevelError> {
+    match s.to_ascii_lowercase().as_str() {
+        "off" => Ok(None),
+        "error" => Ok(Some(LogLevel::Error)),
+        "warn" => Ok(Some(LogLevel::Warn)),
+        "info" => Ok(Some(LogLevel::Info)),
+        "debug" => Ok(Some(LogLevel::Debug)),
+        _ => Err(ParseLogLevelError(s.to_string())),
+    }
+}
+
+impl FromStr for LogLevel {
+    type Err = ParseLogLevelError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_max_level(s)?.ok_or_else(|| ParseLogLevelError(s.to_string()))
+    }
+}
+
+/// Log message of the SDK, passed to the handler set with
+/// [`set_log_handler`].
+#[derive(Debug, Clone, Copy)]
+pub struct LogRecord<'a> {
+    /// Level of the message.
+    pub level: LogLevel,
+    /// Module of the SDK that logged the message.
+    pub target: &'a str,
+    /// Message.
+    pub args: fmt::Arguments<'a>,
+}
+
+type LogHandler = Arc<dyn Fn(&LogRecord) + Send + Sync>;
+
+static LOG_HANDLER: RwLock<Option<LogHandler>> = RwLock::new(None);
+
+// Maximum level of the logs, or 0 if they are off. `MAX_LEVEL_UNSET` until
+// the level is set or read from `LOG_LEVEL_ENV`.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(MAX_LEVEL_UNSET);
+
+const MAX_LEVEL_UNSET: u8 = u8::MAX;
+
+const DEFAULT_MAX_LEVEL: LogLevel = LogLevel::Warn;
+
+fn encode_max_level(level: Option<LogLevel>) -> u8 {
+    level.map_or(0, |level| level as u8)
+}
+
+/// Returns the maximum level of the logged messages, or `None` if logging is
+/// off.
+///
+/// Unless set with [`set_max_level`], it is read from the
+/// `PERFETTO_SDK_LOG` environment variable, and defaults to
+/// [`LogLevel::Warn`].
+pub fn max_level() -> Option<LogLevel> {
+    let value = MAX_LEVEL.load(Ordering::Relaxed);
+    if value != MAX_LEVEL_UNSET {
+        return LogLevel::from_u8(value);
+    }
+    let level = std::env::var(LOG_LEVEL_ENV)
+        .ok()
+        .and_then(|value| parse_max_level(&value).ok())
+        .unwrap_or(Some(DEFAULT_MAX_LEVEL));
+    // A level set concurrently with `set_max_level` wins.
+    match MAX_LEVEL.compare_exchange(
+        MAX_LEVEL_UNSET,
+        encode_max_level(level),
+        Ordering::Relaxed,
+        Ordering::Relaxed,
+    ) {
+        Ok(_) => level,
+        Err(value) => LogLevel::from_u8(value),
+    }
+}
+
+/// Sets the maximum level of the logged messages, overriding the
+/// `PERFETTO_SDK_LOG` environment variable. `None` turns logging off.
+pub fn set_max_level(level: Option<LogLevel>) {
+    MAX_LEVEL.store(encode_max_level(level), Ordering::Relaxed);
+}
+
+/// Returns true if messages of level `level` are logged.
+#[inline]
+pub fn enabled(level: LogLevel) -> bool {
+    max_level().is_some_and(|max| level <= max)
+}
+
+/// Sets the function called with the log messages of the SDK, instead of
+/// printing them to stderr, e.g. to capture the warnings of the SDK in the
+/// logs of the application.
+///
+/// Only the messages up to the [`max_level`] are passed to `handler`, which
+/// may be called on any thread, including the internal threads of the SDK.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::logging::{LogLevel, set_log_handler, set_max_level};
+///
+/// set_max_level(Some(LogLevel::Info));
+/// set_log_handler(|record| {
+///     eprintln!("[{}] {}: {}", record.level, record.target, record.args);
+/// });
+/// ```
+pub fn set_log_handler<F>(handler: F)
+where
+    F: Fn(&LogRecord) + Send + Sync + 'static,
+{
+    *LOG_HANDLER.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(handler));
+}
+
+/// Removes the function set with [`set_log_handler`], so that the log
+/// messages are printed to stderr again.
+pub fn clear_log_handler() {
+    *LOG_HANDLER.write().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// Log handler that forwards the messages of the SDK to the `log` crate,
+/// with their module as target.
+///
+/// Example:
+///
+/// ```
+/// perfetto_sdk::logging::set_log_handler(perfetto_sdk::logging::log_crate_handler);
+/// ```
+#[cfg(feature = "log")]
+pub fn log_crate_handler(record: &LogRecord) {
+    let level = match record.level {
+        LogLevel::Error => log::Level::Error,
+        LogLevel::Warn => log::Level::Warn,
+        LogLevel::Info => log::Level::Info,
+        LogLevel::Debug => log::Level::Debug,
+    };
+    log::logger().log(
+        &log::Record::builder()
+            .level(level)
+            .target(record.target)
+            .args(record.args)
+            .build(),
+    );
+}
+
+/// Internal function that passes a message to the log handler.
+#[doc(hidden)]
+pub fn __log(level: LogLevel, target: &str, args: fmt::Arguments) {
+    let record = LogRecord {
+        level,
+        target,
+        args,
+    };
+    // Logging must not panic, e.g. while unwinding, so a poisoned lock is
+    // used as is: it only holds an `Arc`.
+    let handler = LOG_HANDLER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    match handler {
+        Some(handler) => handler(&record),
+        None => eprintln!("perfetto {}: {}", record.level, record.args),
+    }
+}
+
+/// Internal function that logs the reason of an abort, then aborts the
+/// process.
+#[doc(hidden)]
+pub fn __fatal(target: &str, args: fmt::Arguments) -> ! {
+    // The message is always written to stderr, so that the abort is
+    // explained even if logging is off or the handler drops the message.
+    eprintln!("perfetto {}: {}", LogLevel::Error, args);
+    let handler = LOG_HANDLER
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    if let Some(handler) = handler
+        && enabled(LogLevel::Error)
+    {
+        handler(&LogRecord {
+            level: LogLevel::Error,
+            target,
+            args,
+        });
+    }
+    std::process::abort();
+}
+
+/// Logs a message of the SDK with the level `$level`, a
+/// [`LogLevel`](crate::logging::LogLevel) variant, if the level is enabled.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::sdk_log;
+///
+/// sdk_log!(Warn, "unknown config field: {}", 42);
+/// ```
+#[macro_export]
+macro_rules! sdk_log {
+    ($level:ident, $($arg:tt)+) => {
+        if $crate::logging::enabled($crate::logging::LogLevel::$level) {
+            $crate::logging::__log(
+                $crate::logging::LogLevel::$level,
+                module_path!(),
+                format_args!($($arg)+),
+            );
+        }
+    };
+}
+
+/// Helper macro that logs the reason of an abort, to stderr and to the log
+/// handler, then aborts the process.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sdk_fatal {
+    ($($arg:tt)+) => {
+        $crate::logging::__fatal(module_path!(), format_args!($($arg)+))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::acquire_test_environment;
+    use std::sync::Mutex;
+
+    #[test]
+    fn parse_levels() {
+        assert_eq!(parse_max_level("off"), Ok(None));
+        assert_eq!(parse_max_level("WARN"), Ok(Some(LogLevel::Warn)));
+        assert_eq!("debug".parse(), Ok(LogLevel::Debug));
+        assert!("off".parse::<LogLevel>().is_err());
+        assert!(parse_max_level("verbose").is_err());
+        assert!(LogLevel::Error < LogLevel::Debug);
+    }
+
+    #[test]
+    fn handler() {
+        let _lock = acquire_test_environment();
+        let records = Arc::new(Mutex::new(vec![]));
+        let records_for_handler = Arc::clone(&records);
+        set_log_handler(move |record| {
+            // Other tests may log concurrently.
+            let message = record.args.to_string();
+            if message.starts_with("logging test") {
+                records_for_handler.lock().unwrap().push((
+                    record.level,
+                    record.target.to_string(),
+                    message,
+                ));
+            }
+        });
+        set_max_level(Some(LogLevel::Info));
+        crate::sdk_log!(Info, "logging test {}", 1);
+        crate::sdk_log!(Debug, "logging test {}", 2);
+        set_max_level(None);
+        crate::sdk_log!(Error, "logging test {}", 3);
+        set_max_level(Some(DEFAULT_MAX_LEVEL));
+        clear_log_handler();
+        assert_eq!(
+            *records.lock().unwrap(),
+            vec![(
+                LogLevel::Info,
+                module_path!().to_string(),
+                "logging test 1".to_string()
+            )]
+        );
+    }
+}
@@ -0,0 +1,302 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    async_task::TaskCategory,
+    track_event::{EventContext, TrackEventDebugArg, TrackEventTrack, TrackEventType},
+};
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    sync::{Arc, Mutex},
+};
+
+// Name of the instants emitted for the snapshots of an object.
+const SNAPSHOT_EVENT_NAME: &CStr = c"snapshot";
+
+#[derive(Debug)]
+struct TrackerInner {
+    category: TaskCategory,
+    kind: String,
+    c_kind: CString,
+    // Tracks of the live objects, registered when their first event is
+    // emitted.
+    tracks: Mutex<HashMap<u64, Option<Arc<TrackEventTrack>>>>,
+}
+
+/// Tracker of the lifetime of the objects of one kind, e.g. the textures or
+/// buffers of a GPU driver.
+///
+/// Each object gets a track of its own nested under the process track, keyed
+/// by the id of the object. Its lifetime is emitted as a slice named after the
+/// kind of the objects on that track, from [`ObjectTracker::create`] to
+/// [`ObjectTracker::delete`], and its snapshots as instants inside the slice,
+/// with the state of the object as debug annotations.
+///
+/// Objects created before tracing started only have their snapshots and end
+/// in the trace.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     async_task_category,
+///     object_lifetime::ObjectTracker,
+///     track_event::TrackEventDebugArg,
+/// };
+///
+/// perfetto_sdk::track_event_categories! {
+///     pub mod my_categories {
+///         ("gpu.memory", "GPU resources", []),
+///     }
+/// }
+/// use my_categories as perfetto_te_ns;
+///
+/// let textures = ObjectTracker::new(async_task_category!("gpu.memory"), "Texture");
+/// textures.create(0x1000, |ctx| {
+///     ctx.add_debug_arg("width", TrackEventDebugArg::Uint64(1920));
+/// });
+/// textures.snapshot(0x1000, |ctx| {
+///     ctx.add_debug_arg("resident", TrackEventDebugArg::Bool(true));
+/// });
+/// textures.delete(0x1000);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ObjectTracker {
+    inner: Arc<TrackerInner>,
+}
+
+impl ObjectTracker {
+    /// Creates a tracker of the objects of kind `kind`, whose events are
+    /// emitted in `category`.
+    pub fn new(category: TaskCategory, kind: &str) -> Self {
+        Self {
+            inner: Arc::new(TrackerInner {
+                category,
+                kind: kind.to_string(),
+                c_kind: CString::new(kind).expect("object kind contains a NUL byte"),
+                tracks: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Returns the kind of the tracked objects.
+    pub fn kind(&self) -> &str {
+        &self.inner.kind
+    }
+
+    /// Returns the number of objects created and not yet deleted.
+    pub fn live_objects(&self) -> usize {
+        self.inner.tracks.lock().unwrap().len()
+    }
+
+    /// Returns the UUID of the track of the object `id`.
+    pub fn track_uuid(&self, id: u64) -> u64 {
+        TrackEventTrack::named_track_uuid(
+            &self.track_name(id),
+            id,
+            TrackEventTrack::process_track_uuid(),
+        )
+    }
+
+    fn track_name(&self, id: u64) -> String {
+        format!("{} {id:#x}", self.inner.kind)
+    }
+
+    fn register_track(&self, id: u64) -> Arc<TrackEventTrack> {
+        let track = TrackEventTrack::register_named_track_with_dynamic_name(
+            &self.track_name(id),
+            id,
+            TrackEventTrack::process_track_uuid(),
+        )
+        .expect("failed to register object track");
+        Arc::new(track)
+    }
+
+    fn emit<F>(&self, id: u64, variant: TrackEventType, cb: F)
+    where
+        F: FnOnce(&mut EventContext),
+    {
+        if !self.inner.category.is_enabled() {
+            return;
+        }
+        // The tracks of objects that aren't live, e.g. snapshots after their
+        // deletion, aren't kept.
+        let track = match self.inner.tracks.lock().unwrap().get_mut(&id) {
+            Some(track) => Arc::clone(track.get_or_insert_with(|| self.register_track(id))),
+            None => self.register_track(id),
+        };
+        let mut ctx = EventContext::default();
+        ctx.set_track(&track);
+        cb(&mut ctx);
+        self.inner.category.emit(variant, &mut ctx);
+    }
+
+    /// Emits the creation of the object `id`, which begins the slice of its
+    /// lifetime. `cb` adds the attributes of the object, e.g. its size.
+    pub fn create<F>(&self, id: u64, cb: F)
+    where
+        F: FnOnce(&mut EventContext),
+    {
+        self.inner.tracks.lock().unwrap().entry(id).or_default();
+        self.emit(
+            id,
+            TrackEventType::SliceBegin(self.inner.c_kind.as_ptr()),
+            |ctx| {
+                ctx.add_debug_arg("id", TrackEventDebugArg::Uint64(id));
+                cb(ctx);
+            },
+        );
+    }
+
+    /// Emits a snapshot of the object `id`, with the state added by `cb`.
+    pub fn snapshot<F>(&self, id: u64, cb: F)
+    where
+        F: FnOnce(&mut EventContext),
+    {
+        self.emit(
+            id,
+            TrackEventType::Instant(SNAPSHOT_EVENT_NAME.as_ptr()),
+            cb,
+        );
+    }
+
+    /// Emits the deletion of the object `id`, which ends the slice of its
+    /// lifetime.
+    pub fn delete(&self, id: u64) {
+        self.emit(id, TrackEventType::SliceEnd, |_| {});
+        self.inner.tracks.lock().unwrap().remove(&id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        async_task_category,
+        pb_decoder::{PbDecoder, PbDecoderField},
+        protos::trace::{
+            trace::TraceFieldNumber,
+            trace_packet::TracePacketFieldNumber,
+            track_event::track_event::{TrackEventFieldNumber, TrackEventType as EventType},
+        },
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        track_event::TrackEvent,
+    };
+    use std::error::Error;
+
+    crate::track_event_categories! {
+        pub mod test_te_ns {
+            ( "objects", "Test object lifetimes", [] ),
+        }
+    }
+    use test_te_ns as perfetto_te_ns;
+
+    #[derive(Default, Debug)]
+    struct Event {
+        r#type: Option<EventType>,
+        track_uuid: Option<u64>,
+        debug_annotations: usize,
+    }
+
+    fn read_trace_events(data: &[u8]) -> Vec<Event> {
+        use PbDecoderField::*;
+        const PACKET_ID: u32 = TraceFieldNumber::Packet as u32;
+        const TRACK_EVENT_ID: u32 = TracePacketFieldNumber::TrackEvent as u32;
+        const TYPE_ID: u32 = TrackEventFieldNumber::Type as u32;
+        const TRACK_UUID_ID: u32 = TrackEventFieldNumber::TrackUuid as u32;
+        const DEBUG_ANNOTATIONS_ID: u32 = TrackEventFieldNumber::DebugAnnotations as u32;
+        let mut events = vec![];
+        for trace_field in PbDecoder::new(data) {
+            if let (PACKET_ID, Delimited(packet)) = trace_field.unwrap() {
+                for packet_field in PbDecoder::new(packet) {
+                    if let (TRACK_EVENT_ID, Delimited(v)) = packet_field.unwrap() {
+                        let mut event = Event::default();
+                        for field in PbDecoder::new(v) {
+                            match field.unwrap() {
+                                (TYPE_ID, Varint(v)) => {
+                                    event.r#type = EventType::try_from(v as u32).ok()
+                                }
+                                (TRACK_UUID_ID, Varint(v)) => event.track_uuid = Some(v),
+                                (DEBUG_ANNOTATIONS_ID, Delimited(_)) => {
+                                    event.debug_annotations += 1
+                                }
+                                _ => {}
+                            }
+                        }
+                        events.push(event);
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn lifetime() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        TrackEvent::init();
+        test_te_ns::register()?;
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name("track_event")
+            .add_enabled_category("objects")
+            .add_disabled_category("*")
+            .build()?;
+        session.start_blocking();
+        let buffers = ObjectTracker::new(async_task_category!("objects"), "Buffer");
+        buffers.create(1, |ctx| {
+            ctx.add_debug_arg("size", TrackEventDebugArg::Uint64(4096));
+        });
+        buffers.create(2, |_| {});
+        assert_eq!(buffers.live_objects(), 2);
+        buffers.snapshot(1, |ctx| {
+            ctx.add_debug_arg("mapped", TrackEventDebugArg::Bool(true));
+        });
+        buffers.delete(1);
+        buffers.delete(2);
+        assert_eq!(buffers.live_objects(), 0);
+        session.stop_blocking();
+        test_te_ns::unregister()?;
+
+        let events = read_trace_events(&read_trace_data(&mut session));
+        let uuid = buffers.track_uuid(1);
+        assert_ne!(uuid, buffers.track_uuid(2));
+        let object: Vec<_> = events
+            .iter()
+            .filter(|event| event.track_uuid == Some(uuid))
+            .map(|event| (event.r#type, event.debug_annotations))
+            .collect();
+        assert_eq!(
+            object,
+            vec![
+                (Some(EventType::TypeSliceBegin), 2),
+                (Some(EventType::TypeInstant), 1),
+                (Some(EventType::TypeSliceEnd), 0),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn disabled_category() {
+        let _lock = acquire_test_environment();
+        TrackEvent::init();
+        let buffers = ObjectTracker::new(async_task_category!("objects"), "Buffer");
+        buffers.create(1, |_| unreachable!());
+        buffers.snapshot(1, |_| unreachable!());
+        assert_eq!(buffers.live_objects(), 1);
+        buffers.delete(1);
+        assert_eq!(buffers.live_objects(), 0);
+    }
+}
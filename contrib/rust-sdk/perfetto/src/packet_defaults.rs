@@ -0,0 +1,310 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{DataSourceTimestamp, TraceContextBase},
+    protos::trace::{
+        trace_packet::{TracePacket, TracePacketDefaults, TracePacketSequenceFlags},
+        track_event::track_event::{TrackEvent, TrackEventDefaults},
+    },
+};
+
+/// Default values for the packets of a packet sequence.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PacketDefaults {
+    /// Clock used for packet timestamps that don't specify a clock.
+    pub timestamp_clock_id: Option<u32>,
+    /// Track used for track events that don't specify a track.
+    pub track_uuid: Option<u64>,
+    /// Counter tracks that every track event carries extra values for.
+    pub extra_counter_track_uuids: Vec<u64>,
+}
+
+impl PacketDefaults {
+    /// Sets the timestamp of `packet`, omitting the clock id if it matches
+    /// the default clock.
+    pub fn set_timestamp(&self, packet: &mut TracePacket, timestamp: DataSourceTimestamp) {
+        packet.set_timestamp(timestamp.timestamp());
+        if self.timestamp_clock_id != Some(timestamp.clock_id()) {
+            packet.set_timestamp_clock_id(timestamp.clock_id());
+        }
+    }
+
+    /// Sets the track of `event`, omitting the track uuid if it matches the
+    /// default track.
+    pub fn set_track_uuid(&self, event: &mut TrackEvent, uuid: u64) {
+        if self.track_uuid != Some(uuid) {
+            event.set_track_uuid(uuid);
+        }
+    }
+
+    fn write(&self, defaults: &mut TracePacketDefaults) {
+        if let Some(clock_id) = self.timestamp_clock_id {
+            defaults.set_timestamp_clock_id(clock_id);
+        }
+        if self.track_uuid.is_some() || !self.extra_counter_track_uuids.is_empty() {
+            defaults.set_track_event_defaults(|te_defaults: &mut TrackEventDefaults| {
+                if let Some(uuid) = self.track_uuid {
+                    te_defaults.set_track_uuid(uuid);
+                }
+                for uuid in &self.extra_counter_track_uuids {
+                    te_defaults.set_extra_counter_track_uuids(*uuid);
+                }
+            });
+        }
+    }
+}
+
+/// Writes packets that rely on `TracePacketDefaults` for the fields they
+/// have in common.
+///
+/// The defaults are part of the incremental state of a packet sequence, so
+/// the writer is meant to be stored in the incremental state of a data
+/// source. The defaults are emitted before the first packet written after
+/// the state is (re)created, and all packets are marked as depending on
+/// incremental state.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     data_source::{Clear, DataSource, DataSourceArgsBuilder, DataSourceTimestamp, TraceContext},
+///     packet_defaults::{PacketDefaults, PacketDefaultsWriter},
+///     protos::trace::track_event::track_event::{TrackEvent, TrackEventType},
+/// };
+///
+/// struct State {
+///     writer: PacketDefaultsWriter,
+/// }
+///
+/// impl Default for State {
+///     fn default() -> Self {
+///         Self {
+///             writer: PacketDefaultsWriter::new(PacketDefaults {
+///                 timestamp_clock_id: Some(DataSourceTimestamp::now().clock_id()),
+///                 track_uuid: Some(1234),
+///                 ..Default::default()
+///             }),
+///         }
+///     }
+/// }
+///
+/// impl Clear for State {}
+///
+/// let mut data_source = DataSource::<State>::new_with_incremental_state_type();
+/// data_source
+///     .register("com.example.counters", DataSourceArgsBuilder::new().build())
+///     .unwrap();
+/// data_source.trace(|ctx: &mut TraceContext<State>| {
+///     ctx.with_incremental_state(|ctx, state| {
+///         state.writer.add_packet(ctx, |packet, defaults| {
+///             defaults.set_timestamp(packet, DataSourceTimestamp::now());
+///             packet.set_track_event(|event: &mut TrackEvent| {
+///                 event.set_type(TrackEventType::TypeCounter);
+///                 defaults.set_track_uuid(event, 1234);
+///                 event.set_counter_value(42);
+///             });
+///         });
+///     });
+/// });
+/// ```
+#[derive(Debug, Default)]
+pub struct PacketDefaultsWriter {
+    defaults: PacketDefaults,
+    emitted: bool,
+}
+
+impl PacketDefaultsWriter {
+    /// Creates a writer using `defaults`.
+    pub fn new(defaults: PacketDefaults) -> Self {
+        Self {
+            defaults,
+            emitted: false,
+        }
+    }
+
+    /// Returns the defaults.
+    pub fn defaults(&self) -> &PacketDefaults {
+        &self.defaults
+    }
+
+    /// Replaces the defaults. The new defaults are emitted before the next
+    /// packet.
+    ///
+    /// That packet is marked as clearing the incremental state of the
+    /// sequence, so the trace processor also drops the other incremental
+    /// state written before it, e.g. interned data, which must be written
+    /// again before packets that use it.
+    pub fn set_defaults(&mut self, defaults: PacketDefaults) {
+        self.defaults = defaults;
+        self.emitted = false;
+    }
+
+    /// Creates a new trace packet and calls `cb` to write data to it. `cb`
+    /// receives the defaults in effect to omit fields that match them.
+    pub fn add_packet<F>(&mut self, ctx: &mut TraceContextBase, mut cb: F)
+    where
+        F: FnMut(&mut TracePacket, &PacketDefaults),
+    {
+        let emit_defaults = !self.emitted;
+        self.emitted = true;
+        let defaults = &self.defaults;
+        ctx.add_packet(|packet: &mut TracePacket| {
+            if emit_defaults {
+                packet.set_sequence_flags(
+                    TracePacketSequenceFlags::SeqIncrementalStateCleared as u32
+                        | TracePacketSequenceFlags::SeqNeedsIncrementalState as u32,
+                );
+                packet.set_trace_packet_defaults(|packet_defaults: &mut TracePacketDefaults| {
+                    defaults.write(packet_defaults);
+                });
+            } else {
+                packet
+                    .set_sequence_flags(TracePacketSequenceFlags::SeqNeedsIncrementalState.into());
+            }
+            cb(packet, defaults);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_source::{Clear, TraceContext},
+        pb_decoder::{PbDecoder, PbDecoderField},
+        protos::trace::{
+            trace_packet::TracePacketFieldNumber, track_event::track_event::TrackEventFieldNumber,
+        },
+        test_util::{data_source_name, data_source_with_state, has_field, varint},
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        trace_reader::TraceReader,
+    };
+    use std::{error::Error, time::Duration};
+
+    const TRACK_UUID: u64 = 1234;
+
+    struct State {
+        writer: PacketDefaultsWriter,
+    }
+
+    impl Default for State {
+        fn default() -> Self {
+            Self {
+                writer: PacketDefaultsWriter::new(PacketDefaults {
+                    timestamp_clock_id: Some(DataSourceTimestamp::Boot(Duration::ZERO).clock_id()),
+                    track_uuid: Some(TRACK_UUID),
+                    ..Default::default()
+                }),
+            }
+        }
+    }
+
+    impl Clear for State {}
+
+    #[test]
+    fn omits_default_fields() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source_with_state::<State>();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(data_source_name::<State>())
+            .build()?;
+        session.start_blocking();
+        for (clock, uuid) in [
+            (
+                DataSourceTimestamp::Boot(Duration::from_nanos(10)),
+                TRACK_UUID,
+            ),
+            (
+                DataSourceTimestamp::Monotonic(Duration::from_nanos(20)),
+                5678,
+            ),
+        ] {
+            data_source.trace(|ctx: &mut TraceContext<State>| {
+                ctx.with_incremental_state(|ctx, state| {
+                    state.writer.add_packet(ctx, |packet, defaults| {
+                        defaults.set_timestamp(packet, clock);
+                        packet.set_track_event(|event: &mut TrackEvent| {
+                            defaults.set_track_uuid(event, uuid);
+                        });
+                    });
+                });
+            });
+        }
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        let mut packets = vec![];
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            let Some((_, PbDecoderField::Delimited(event))) = PbDecoder::new(&packet)
+                .map(|f| f.unwrap())
+                .find(|(id, _)| *id == TracePacketFieldNumber::TrackEvent as u32)
+            else {
+                continue;
+            };
+            packets.push((
+                has_field(&packet, TracePacketFieldNumber::TracePacketDefaults as u32),
+                has_field(&packet, TracePacketFieldNumber::TimestampClockId as u32),
+                has_field(event, TrackEventFieldNumber::TrackUuid as u32),
+            ));
+        }
+        assert_eq!(packets, vec![(true, false, false), (false, true, true)]);
+        Ok(())
+    }
+    #[test]
+    fn set_defaults_clears_incremental_state() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source_with_state::<State>();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(data_source_name::<State>())
+            .build()?;
+        session.start_blocking();
+        for replace_defaults in [false, true, false] {
+            data_source.trace(|ctx: &mut TraceContext<State>| {
+                ctx.with_incremental_state(|ctx, state| {
+                    if replace_defaults {
+                        state.writer.set_defaults(PacketDefaults {
+                            track_uuid: Some(5678),
+                            ..Default::default()
+                        });
+                    }
+                    state.writer.add_packet(ctx, |packet, defaults| {
+                        packet.set_track_event(|event: &mut TrackEvent| {
+                            defaults.set_track_uuid(event, 5678);
+                        });
+                    });
+                });
+            });
+        }
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        let cleared = TracePacketSequenceFlags::SeqIncrementalStateCleared as u64;
+        let mut packets = vec![];
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            if !has_field(&packet, TracePacketFieldNumber::TrackEvent as u32) {
+                continue;
+            }
+            let flags = varint(&packet, TracePacketFieldNumber::SequenceFlags as u32);
+            packets.push((
+                has_field(&packet, TracePacketFieldNumber::TracePacketDefaults as u32),
+                flags.unwrap_or(0) & cleared != 0,
+            ));
+        }
+        assert_eq!(packets, vec![(true, true), (true, true), (false, false)]);
+        Ok(())
+    }
+}
@@ -0,0 +1,267 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField},
+    pb_ext::PbMsgExtension,
+    protos::trace::trace_packet::TracePacketFieldNumber,
+    trace_reader::{TraceReaderError, TraceStreamReader},
+};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Packet dispatcher errors.
+#[derive(Error, Debug, PartialEq)]
+pub enum PacketDispatcherError {
+    /// The extension doesn't extend `TracePacket`.
+    #[error("Extension of {0} isn't a TracePacket extension.")]
+    NotTracePacketExtension(&'static str),
+    /// The extension doesn't define a field with this name.
+    #[error("Extension of {0} has no field `{1}`.")]
+    UnknownField(&'static str, String),
+    /// The trace stream couldn't be parsed.
+    #[error("Failed to read trace: {0}")]
+    ReaderError(#[from] TraceReaderError),
+    /// A packet couldn't be decoded.
+    #[error("Failed to decode packet: {0}")]
+    DecoderError(#[from] PbDecoderError),
+}
+
+/// Packet that a [`PacketDispatcher`] callback is called for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DispatchedPacket<'a> {
+    /// Timestamp of the packet, if set.
+    pub timestamp: Option<u64>,
+    /// Trusted packet sequence id of the packet, if set.
+    pub trusted_packet_sequence_id: Option<u32>,
+    /// Encoded packet.
+    pub data: &'a [u8],
+}
+
+type FieldCallback = Box<dyn FnMut(&DispatchedPacket, PbDecoderField) + Send>;
+
+/// Dispatches the fields of the packets of a trace to the callbacks
+/// registered for them, e.g. to only look at the `GpuCounterEvent` packets of
+/// a live session.
+///
+/// Only the top-level fields of each packet are parsed to find the fields
+/// with callbacks. The payload of these fields is passed to their callbacks
+/// without being decoded further, and the other fields are skipped, so
+/// monitoring tools only pay to decode the messages they need.
+///
+/// Packets are dispatched one at a time with
+/// [`dispatch_packet`](Self::dispatch_packet), or from the chunks of a
+/// serialized trace with [`push`](Self::push), e.g. the data of
+/// [`TracingSession::read_trace_blocking`](crate::tracing_session::TracingSession::read_trace_blocking).
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::{
+///     packet_dispatcher::PacketDispatcher, pb_decoder::PbDecoderField,
+///     protos::trace::trace_packet::TracePacketFieldNumber,
+/// };
+/// use std::sync::{Arc, Mutex};
+///
+/// let events = Arc::new(Mutex::new(Vec::new()));
+/// let mut dispatcher = PacketDispatcher::new();
+/// let track_events = Arc::clone(&events);
+/// dispatcher.on_field(
+///     TracePacketFieldNumber::TrackEvent as u32,
+///     move |packet, field: PbDecoderField| {
+///         let event = field.as_bytes().unwrap().to_vec();
+///         track_events.lock().unwrap().push((packet.timestamp, event));
+///     },
+/// );
+/// // A packet with a timestamp of 7 and an empty track event.
+/// dispatcher.push(b"\x0a\x04\x40\x07\x5a\x00").unwrap();
+/// assert_eq!(*events.lock().unwrap(), vec![(Some(7), vec![])]);
+/// ```
+#[derive(Default)]
+pub struct PacketDispatcher {
+    callbacks: HashMap<u32, Vec<FieldCallback>>,
+    reader: TraceStreamReader,
+    dispatched_fields: u64,
+}
+
+impl PacketDispatcher {
+    /// Creates a dispatcher with no callbacks.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `cb` for each field number `id` of the dispatched packets, e.g.
+    /// `TracePacketFieldNumber::TrackEvent as u32`.
+    pub fn on_field<F>(&mut self, id: u32, cb: F) -> &mut Self
+    where
+        F: FnMut(&DispatchedPacket, PbDecoderField) + Send + 'static,
+    {
+        self.callbacks.entry(id).or_default().push(Box::new(cb));
+        self
+    }
+
+    /// Calls `cb` for each field `name` of the `TracePacket` extension `E` of
+    /// the dispatched packets, e.g. `gpu_counter_event`.
+    pub fn on_extension<E, F>(
+        &mut self,
+        name: &str,
+        cb: F,
+    ) -> Result<&mut Self, PacketDispatcherError>
+    where
+        E: PbMsgExtension,
+        F: FnMut(&DispatchedPacket, PbDecoderField) + Send + 'static,
+    {
+        if E::EXTENDEE != "TracePacket" {
+            return Err(PacketDispatcherError::NotTracePacketExtension(E::EXTENDEE));
+        }
+        let Some(&(id, _)) = E::FIELDS.iter().find(|(_, field)| *field == name) else {
+            return Err(PacketDispatcherError::UnknownField(
+                E::EXTENDEE,
+                name.to_string(),
+            ));
+        };
+        Ok(self.on_field(id, cb))
+    }
+
+    /// Calls the callbacks of the fields of the encoded `packet`. Returns the
+    /// number of fields that callbacks were called for.
+    pub fn dispatch_packet(&mut self, packet: &[u8]) -> Result<usize, PacketDispatcherError> {
+        let mut dispatched = DispatchedPacket {
+            timestamp: None,
+            trusted_packet_sequence_id: None,
+            data: packet,
+        };
+        let mut has_callbacks = false;
+        for item in PbDecoder::new(packet) {
+            let (id, field) = item?;
+            match (id, field) {
+                (id, PbDecoderField::Varint(value))
+                    if id == TracePacketFieldNumber::Timestamp as u32 =>
+                {
+                    dispatched.timestamp = Some(value);
+                }
+                (id, PbDecoderField::Varint(value))
+                    if id == TracePacketFieldNumber::TrustedPacketSequenceId as u32 =>
+                {
+                    dispatched.trusted_packet_sequence_id = Some(value as u32);
+                }
+                _ => {}
+            }
+            has_callbacks |= self.callbacks.contains_key(&id);
+        }
+        if !has_callbacks {
+            return Ok(0);
+        }
+        // The packet is parsed again so that callbacks see the timestamp and
+        // sequence id even if they come after the field.
+        let mut count = 0;
+        for item in PbDecoder::new(packet) {
+            let (id, field) = item?;
+            let Some(callbacks) = self.callbacks.get_mut(&id) else {
+                continue;
+            };
+            for cb in callbacks {
+                cb(&dispatched, field);
+            }
+            count += 1;
+        }
+        self.dispatched_fields += count as u64;
+        Ok(count)
+    }
+
+    /// Dispatches the packets of the next chunk of a serialized trace. Chunks
+    /// can split packets at any byte, and packets are dispatched once they
+    /// are complete.
+    pub fn push(&mut self, data: &[u8]) -> Result<(), PacketDispatcherError> {
+        self.reader.push(data);
+        while let Some(packet) = self.reader.next_packet() {
+            self.dispatch_packet(&packet?)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the pushed trace ended at a packet boundary.
+    pub fn finish(&self) -> Result<(), PacketDispatcherError> {
+        Ok(self.reader.finish()?)
+    }
+
+    /// Returns the number of fields that callbacks were called for so far.
+    pub fn dispatched_fields(&self) -> u64 {
+        self.dispatched_fields
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_msg, pb_msg_ext,
+        protos::trace::{test_event::TestEvent, trace_packet::TracePacket},
+    };
+    use std::sync::{Arc, Mutex};
+
+    #[allow(dead_code)]
+    mod ext {
+        use super::*;
+        pb_msg_ext!(TracePacket {
+            ext_event: TestEvent, msg, 5000,
+            ext_value: u64, primitive, 5001,
+        });
+    }
+
+    #[test]
+    fn dispatch_extension() {
+        let values = Arc::new(Mutex::new(Vec::new()));
+        let mut dispatcher = PacketDispatcher::new();
+        let ext_values = Arc::clone(&values);
+        dispatcher
+            .on_extension::<ext::TracePacketExtFieldNumber, _>("ext_value", move |packet, field| {
+                let PbDecoderField::Varint(value) = field else {
+                    panic!("Unexpected field: {:?}", field);
+                };
+                ext_values.lock().unwrap().push((
+                    packet.timestamp,
+                    packet.trusted_packet_sequence_id,
+                    value,
+                ));
+            })
+            .unwrap();
+        assert_eq!(
+            dispatcher
+                .on_extension::<ext::TracePacketExtFieldNumber, _>("missing", |_, _| {})
+                .err(),
+            Some(PacketDispatcherError::UnknownField(
+                "TracePacket",
+                "missing".to_string()
+            ))
+        );
+
+        // Field 5001 followed by fields 8 (timestamp) and 10 (sequence id).
+        assert_eq!(
+            dispatcher.dispatch_packet(b"\xc8\xb8\x02\x2a\x40\x07\x50\x03"),
+            Ok(1)
+        );
+        // Packets without the field are skipped.
+        assert_eq!(dispatcher.dispatch_packet(b"\x40\x08"), Ok(0));
+        // Split packets are dispatched once complete.
+        dispatcher.push(b"\x0a\x04\xc8\xb8").unwrap();
+        dispatcher.push(b"\x02\x2b").unwrap();
+        dispatcher.finish().unwrap();
+        assert_eq!(
+            *values.lock().unwrap(),
+            vec![(Some(7), Some(3), 42), (None, None, 43),]
+        );
+        assert_eq!(dispatcher.dispatched_fields(), 2);
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{Clear, DataSource, TraceContext},
+    platform::{self, PlatformThread},
+};
+use std::{io, sync::mpsc};
+
+type Task = Box<dyn FnOnce() + Send + 'static>;
+
+/// A packet sequence that is independent of the sequences of the calling
+/// threads.
+///
+/// Each thread writes to its own packet sequence (with its own trusted
+/// sequence id and incremental state) for every data source instance. A
+/// `PacketSequence` owns a dedicated writer thread, so that data relayed from
+/// e.g. a co-processor can be kept on a sequence of its own instead of being
+/// interleaved with other data written by the thread that receives it.
+///
+/// Trusted packet fields, such as `trusted_packet_sequence_id` and
+/// `machine_id`, are always set by the tracing service and packets that set
+/// them are rejected. The machine id of the process is configured using
+/// [`ProducerInitArgsBuilder::machine_id`](crate::producer::ProducerInitArgsBuilder::machine_id).
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     data_source::{DataSource, DataSourceArgsBuilder, TraceContext},
+///     packet_sequence::PacketSequence,
+///     protos::trace::trace_packet::TracePacket,
+/// };
+/// use std::sync::OnceLock;
+///
+/// static DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+/// let data_source = DATA_SOURCE.get_or_init(|| {
+///     let mut data_source = DataSource::new();
+///     data_source
+///         .register("com.example.coprocessor", DataSourceArgsBuilder::new().build())
+///         .unwrap();
+///     data_source
+/// });
+/// let dsp = PacketSequence::new("dsp0").unwrap();
+/// dsp.trace(data_source, |ctx: &mut TraceContext| {
+///     ctx.add_packet(|packet: &mut TracePacket| {
+///         packet.set_timestamp(1234);
+///     });
+/// });
+/// dsp.sync();
+/// ```
+pub struct PacketSequence {
+    sender: Option<mpsc::Sender<Task>>,
+    thread: Option<PlatformThread>,
+}
+
+impl PacketSequence {
+    /// Creates a new packet sequence with a writer thread named `name`.
+    pub fn new(name: &str) -> io::Result<Self> {
+        let (sender, receiver) = mpsc::channel::<Task>();
+        let thread = platform::spawn(name, move || {
+            for task in receiver {
+                task();
+            }
+        })?;
+        Ok(Self {
+            sender: Some(sender),
+            thread: Some(thread),
+        })
+    }
+
+    /// Calls `cb` for all the active instances of `data_source` on the writer
+    /// thread of the sequence. Calls are made in order and `trace` returns
+    /// without waiting for `cb` to be called.
+    pub fn trace<IncrT, F>(&self, data_source: &'static DataSource<'static, IncrT>, cb: F)
+    where
+        IncrT: Default + Clear + 'static,
+        F: FnMut(&mut TraceContext<'_, IncrT>) + Send + 'static,
+    {
+        self.run(move || {
+            data_source.trace(cb);
+        });
+    }
+
+    /// Waits until all the calls made using [`trace`](Self::trace) so far have
+    /// completed.
+    pub fn sync(&self) {
+        let (sender, receiver) = mpsc::channel();
+        self.run(move || {
+            let _ = sender.send(());
+        });
+        let _ = receiver.recv();
+    }
+
+    fn run<F>(&self, task: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if let Some(sender) = &self.sender {
+            // The writer thread only exits once the sender is dropped.
+            let _ = sender.send(Box::new(task));
+        }
+    }
+}
+
+impl Drop for PacketSequence {
+    fn drop(&mut self) {
+        // Closing the channel makes the writer thread exit after completing the
+        // queued calls.
+        drop(self.sender.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_decoder::{PbDecoder, PbDecoderField},
+        protos::trace::{
+            test_event::{TestEvent, TestEventFieldNumber},
+            trace_packet::{TracePacket, TracePacketFieldNumber},
+        },
+        test_util::{DATA_SOURCE_NAME, data_source},
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        trace_reader::TraceReader,
+    };
+    use std::{collections::HashMap, error::Error};
+
+    fn write_counter(data_source: &'static DataSource, sequence: &PacketSequence, counter: u64) {
+        sequence.trace(data_source, move |ctx: &mut TraceContext| {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_for_testing(|for_testing: &mut TestEvent| {
+                    for_testing.set_counter(counter);
+                });
+            });
+        });
+    }
+
+    #[test]
+    fn distinct_sequences() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let first = PacketSequence::new("first")?;
+        let second = PacketSequence::new("second")?;
+        write_counter(data_source, &first, 1);
+        write_counter(data_source, &second, 2);
+        write_counter(data_source, &first, 1);
+        first.sync();
+        second.sync();
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        // Maps counter values to the sequence ids of the packets carrying them.
+        let mut sequences: HashMap<u64, Vec<u64>> = HashMap::new();
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            let mut sequence_id = None;
+            let mut counter = None;
+            for field in PbDecoder::new(&packet) {
+                match field? {
+                    (id, PbDecoderField::Varint(value))
+                        if id == TracePacketFieldNumber::TrustedPacketSequenceId as u32 =>
+                    {
+                        sequence_id = Some(value);
+                    }
+                    (id, PbDecoderField::Delimited(data))
+                        if id == TracePacketFieldNumber::ForTesting as u32 =>
+                    {
+                        for field in PbDecoder::new(data) {
+                            if let (id, PbDecoderField::Varint(value)) = field?
+                                && id == TestEventFieldNumber::Counter as u32
+                            {
+                                counter = Some(value);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if let (Some(sequence_id), Some(counter)) = (sequence_id, counter) {
+                sequences.entry(counter).or_default().push(sequence_id);
+            }
+        }
+        assert_eq!(sequences[&1].len(), 2);
+        assert_eq!(sequences[&1][0], sequences[&1][1]);
+        assert_eq!(sequences[&2].len(), 1);
+        assert_ne!(sequences[&1][0], sequences[&2][0]);
+        Ok(())
+    }
+}
@@ -0,0 +1,362 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::TraceContextBase,
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField},
+    pb_msg, pb_msg_ext,
+    protos::trace::trace_packet::{TracePacket, TracePacketFieldNumber},
+    trace_reader::{TraceReader, TraceReaderError},
+};
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use thiserror::Error;
+
+/// Default maximum number of payload bytes per packet, see
+/// [`PayloadWriter::chunk_size`].
+pub const DEFAULT_PAYLOAD_CHUNK_SIZE: usize = 16 * 1024;
+
+pb_msg!(PayloadChunk {
+    payload_id: u64, primitive, 1,
+    name: String, primitive, 2,
+    total_size: u64, primitive, 3,
+    offset: u64, primitive, 4,
+    data: Bytes, primitive, 5,
+});
+
+// Field 1900 is in the range of `TracePacket` reserved for out-of-tree
+// extensions.
+pb_msg_ext!(TracePacket {
+    payload_chunk: PayloadChunk, msg, 1900,
+});
+
+/// Payload chunking errors.
+#[derive(Error, Debug, PartialEq)]
+pub enum PayloadChunkError {
+    /// The trace couldn't be parsed.
+    #[error("Failed to read trace: {0}")]
+    Reader(#[from] TraceReaderError),
+    /// A packet or chunk couldn't be decoded.
+    #[error("Failed to decode payload chunk: {0}")]
+    Decode(#[from] PbDecoderError),
+    /// A chunk of the payload is missing, e.g. because it was overwritten in
+    /// a ring buffer. Holds the payload id and the offset of the chunk that
+    /// came after the missing one.
+    #[error("Payload {0} is missing the chunk before offset {1}.")]
+    MissingChunk(u64, u64),
+    /// The chunks of the payload are larger than its total size.
+    #[error("Payload {0} is larger than its total size of {1} bytes.")]
+    Overflow(u64, u64),
+}
+
+// Ids of the payloads written by this process.
+static NEXT_PAYLOAD_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Writes a payload of any size, e.g. a GPU crash dump, as a sequence of
+/// packets holding at most [`chunk_size`](Self::chunk_size) bytes of it each,
+/// so that large payloads don't exceed the size limits of chunks and IPCs.
+///
+/// Each packet carries a `PayloadChunk` extension with the id, name and total
+/// size of the payload and the offset of its data, from which a
+/// [`PayloadReassembler`] rebuilds the payload when reading the trace.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::{data_source::TraceContext, payload_chunks::PayloadWriter};
+///
+/// fn write_crash_dump(ctx: &mut TraceContext, dump: &[u8]) {
+///     PayloadWriter::new("gpu_crash_dump").write(ctx, dump);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+#[must_use = "This is a builder; remember to call `.write()` (or keep chaining)."]
+pub struct PayloadWriter<'a> {
+    name: &'a str,
+    chunk_size: usize,
+}
+
+impl<'a> PayloadWriter<'a> {
+    /// Creates a writer for payloads named `name`.
+    pub fn new(name: &'a str) -> Self {
+        Self {
+            name,
+            chunk_size: DEFAULT_PAYLOAD_CHUNK_SIZE,
+        }
+    }
+
+    /// Sets the maximum number of payload bytes per packet. Defaults to
+    /// [`DEFAULT_PAYLOAD_CHUNK_SIZE`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = size.max(1);
+        self
+    }
+
+    /// Writes `data` as a new payload, and returns the id of the payload.
+    /// Empty payloads are written as a single packet.
+    pub fn write(&self, ctx: &mut TraceContextBase, data: &[u8]) -> u64 {
+        let payload_id = NEXT_PAYLOAD_ID.fetch_add(1, Ordering::Relaxed);
+        let count = data.len().div_ceil(self.chunk_size).max(1);
+        ctx.add_packets(count, |index, packet: &mut TracePacket| {
+            let offset = index * self.chunk_size;
+            let end = data.len().min(offset + self.chunk_size);
+            packet.set_payload_chunk(|chunk: &mut PayloadChunk| {
+                chunk.set_payload_id(payload_id);
+                if index == 0 {
+                    chunk.set_name(self.name);
+                }
+                chunk
+                    .set_total_size(data.len() as u64)
+                    .set_offset(offset as u64)
+                    .set_data(&data[offset..end]);
+            });
+        });
+        payload_id
+    }
+}
+
+/// Payload rebuilt by a [`PayloadReassembler`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payload {
+    /// Id of the payload, unique within the process that wrote it.
+    pub id: u64,
+    /// Name of the payload.
+    pub name: String,
+    /// Content of the payload.
+    pub data: Vec<u8>,
+}
+
+// Decoded `PayloadChunk`.
+#[derive(Default)]
+struct Chunk<'a> {
+    payload_id: u64,
+    name: Option<&'a str>,
+    total_size: u64,
+    offset: u64,
+    data: &'a [u8],
+}
+
+impl<'a> Chunk<'a> {
+    fn decode(data: &'a [u8]) -> Result<Self, PbDecoderError> {
+        let mut chunk = Self::default();
+        for item in PbDecoder::new(data) {
+            match item? {
+                (id, PbDecoderField::Varint(value))
+                    if id == PayloadChunkFieldNumber::PayloadId as u32 =>
+                {
+                    chunk.payload_id = value;
+                }
+                (id, PbDecoderField::Delimited(name))
+                    if id == PayloadChunkFieldNumber::Name as u32 =>
+                {
+                    chunk.name = std::str::from_utf8(name).ok();
+                }
+                (id, PbDecoderField::Varint(size))
+                    if id == PayloadChunkFieldNumber::TotalSize as u32 =>
+                {
+                    chunk.total_size = size;
+                }
+                (id, PbDecoderField::Varint(offset))
+                    if id == PayloadChunkFieldNumber::Offset as u32 =>
+                {
+                    chunk.offset = offset;
+                }
+                (id, PbDecoderField::Delimited(data))
+                    if id == PayloadChunkFieldNumber::Data as u32 =>
+                {
+                    chunk.data = data;
+                }
+                _ => {}
+            }
+        }
+        Ok(chunk)
+    }
+}
+
+/// Rebuilds the payloads written by [`PayloadWriter`] from the packets of a
+/// trace.
+///
+/// Chunks are matched by payload id and packet sequence, and must be pushed
+/// in the order they were written, which is the order of the packets of a
+/// sequence in the trace. Payloads whose first chunks were lost are dropped
+/// with an error, and the ones whose last chunks were lost stay
+/// [`pending`](Self::pending_payloads).
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::payload_chunks::reassemble_payloads;
+///
+/// # let trace: Vec<u8> = Vec::new();
+/// for payload in reassemble_payloads(&trace).unwrap() {
+///     std::fs::write(format!("{}.bin", payload.name), &payload.data).unwrap();
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct PayloadReassembler {
+    // Payloads with chunks left to push, by sequence id and payload id.
+    pending: HashMap<(Option<u32>, u64), Payload>,
+}
+
+impl PayloadReassembler {
+    /// Creates a reassembler with no pending payloads.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pushes the encoded `packet`, and returns the payload it completes, if
+    /// any. Packets without a payload chunk are skipped.
+    pub fn push_packet(&mut self, packet: &[u8]) -> Result<Option<Payload>, PayloadChunkError> {
+        let mut sequence_id = None;
+        let mut chunk = None;
+        for item in PbDecoder::new(packet) {
+            match item? {
+                (id, PbDecoderField::Varint(value))
+                    if id == TracePacketFieldNumber::TrustedPacketSequenceId as u32 =>
+                {
+                    sequence_id = Some(value as u32);
+                }
+                (id, PbDecoderField::Delimited(data))
+                    if id == TracePacketExtFieldNumber::PayloadChunk as u32 =>
+                {
+                    chunk = Some(Chunk::decode(data)?);
+                }
+                _ => {}
+            }
+        }
+        let Some(chunk) = chunk else {
+            return Ok(None);
+        };
+        let key = (sequence_id, chunk.payload_id);
+        let mut payload = match self.pending.remove(&key) {
+            Some(payload) => payload,
+            None if chunk.offset == 0 => Payload {
+                id: chunk.payload_id,
+                name: chunk.name.unwrap_or_default().to_string(),
+                data: Vec::with_capacity(chunk.total_size.min(1 << 20) as usize),
+            },
+            None => return Err(PayloadChunkError::MissingChunk(key.1, chunk.offset)),
+        };
+        if chunk.offset != payload.data.len() as u64 {
+            return Err(PayloadChunkError::MissingChunk(key.1, chunk.offset));
+        }
+        if chunk.offset + chunk.data.len() as u64 > chunk.total_size {
+            return Err(PayloadChunkError::Overflow(key.1, chunk.total_size));
+        }
+        payload.data.extend_from_slice(chunk.data);
+        if payload.data.len() as u64 == chunk.total_size {
+            return Ok(Some(payload));
+        }
+        self.pending.insert(key, payload);
+        Ok(None)
+    }
+
+    /// Returns the number of payloads with chunks left to push.
+    pub fn pending_payloads(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// Returns the complete payloads written by [`PayloadWriter`] into the
+/// serialized trace `data`, in the order they were completed.
+pub fn reassemble_payloads(data: &[u8]) -> Result<Vec<Payload>, PayloadChunkError> {
+    let mut reassembler = PayloadReassembler::new();
+    let mut payloads = Vec::new();
+    for packet in TraceReader::new(data) {
+        if let Some(payload) = reassembler.push_packet(&packet?)? {
+            payloads.push(payload);
+        }
+    }
+    Ok(payloads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_source::TraceContext,
+        test_util::{DATA_SOURCE_NAME, data_source},
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+    };
+    use std::error::Error;
+
+    #[test]
+    fn write_and_reassemble() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let dump: Vec<u8> = (0..40_000u32).map(|i| (i % 251) as u8).collect();
+        let mut ids = Vec::new();
+        data_source.trace(|ctx: &mut TraceContext| {
+            ids.push(PayloadWriter::new("dump").write(ctx, &dump));
+            ids.push(
+                PayloadWriter::new("small")
+                    .chunk_size(4)
+                    .write(ctx, b"0123456789"),
+            );
+            ids.push(PayloadWriter::new("empty").write(ctx, b""));
+        });
+        session.stop_blocking();
+        let trace = read_trace_data(&mut session);
+        let payloads = reassemble_payloads(&trace)?;
+        assert_eq!(
+            payloads,
+            vec![
+                Payload {
+                    id: ids[0],
+                    name: "dump".to_string(),
+                    data: dump,
+                },
+                Payload {
+                    id: ids[1],
+                    name: "small".to_string(),
+                    data: b"0123456789".to_vec(),
+                },
+                Payload {
+                    id: ids[2],
+                    name: "empty".to_string(),
+                    data: Vec::new(),
+                },
+            ]
+        );
+
+        // Chunks after a lost chunk are rejected.
+        let mut reassembler = PayloadReassembler::new();
+        let packets: Vec<_> = TraceReader::new(&trace).collect::<Result<_, _>>()?;
+        let chunks: Vec<_> = packets
+            .iter()
+            .filter(|packet| {
+                PbDecoder::new(packet).any(|item| {
+                    matches!(item, Ok((id, _)) if id == TracePacketExtFieldNumber::PayloadChunk as u32)
+                })
+            })
+            .collect();
+        assert_eq!(reassembler.push_packet(chunks[0])?, None);
+        assert_eq!(reassembler.pending_payloads(), 1);
+        assert_eq!(
+            reassembler.push_packet(chunks[2]),
+            Err(PayloadChunkError::MissingChunk(
+                ids[0],
+                2 * DEFAULT_PAYLOAD_CHUNK_SIZE as u64
+            ))
+        );
+        Ok(())
+    }
+}
@@ -12,8 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::pb_utils::PbWireType;
+use crate::pb_utils::{
+    PB_VARINT_MAX_SIZE_64, PbWireType, pb_make_tag, pb_write_fixed32, pb_write_fixed64,
+    pb_write_varint,
+};
+use crate::protos::PbEnumValue;
 use perfetto_sdk_sys::*;
+use std::str::Utf8Error;
 use thiserror::Error;
 
 /// Protobuf decoder errors.
@@ -22,10 +27,19 @@ pub enum PbDecoderError {
     /// Encountered an invalid wire type.
     #[error("Invalid wire type: {0}.")]
     InvalidWireType(u32),
+    /// Expected a delimited field.
+    #[error("Field is not a delimited field.")]
+    NotDelimited,
+    /// Expected a varint field.
+    #[error("Field is not a varint field.")]
+    NotVarint,
+    /// Expected a string field with valid UTF-8.
+    #[error("Invalid UTF-8 string: {0}.")]
+    InvalidUtf8(#[from] Utf8Error),
 }
 
 /// Protobuf decoder field types.
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PbDecoderField<'a> {
     /// Varint field.
     Varint(u64),
@@ -37,6 +51,50 @@ pub enum PbDecoderField<'a> {
     Fixed32(u32),
 }
 
+impl<'a> PbDecoderField<'a> {
+    /// Returns the data of a delimited field.
+    pub fn as_bytes(&self) -> Result<&'a [u8], PbDecoderError> {
+        match self {
+            PbDecoderField::Delimited(data) => Ok(data),
+            _ => Err(PbDecoderError::NotDelimited),
+        }
+    }
+
+    /// Returns a decoder for the fields of a nested message field.
+    pub fn as_decoder(&self) -> Result<PbDecoder<'a>, PbDecoderError> {
+        self.as_bytes().map(PbDecoder::new)
+    }
+
+    /// Returns the value of a string field, after validating it is UTF-8.
+    pub fn as_str(&self) -> Result<&'a str, PbDecoderError> {
+        Ok(std::str::from_utf8(self.as_bytes()?)?)
+    }
+
+    /// Returns the value of an enum field, which is preserved if unknown to
+    /// enum `E`.
+    pub fn as_enum<E: TryFrom<i32>>(&self) -> Result<PbEnumValue<E>, PbDecoderError> {
+        match self {
+            PbDecoderField::Varint(value) => Ok((*value as i32).into()),
+            _ => Err(PbDecoderError::NotVarint),
+        }
+    }
+
+    /// Returns the key and value of an entry of a map field. They are `None`
+    /// when they have the default value.
+    #[allow(clippy::type_complexity)]
+    pub fn as_map_entry(&self) -> Result<(Option<Self>, Option<Self>), PbDecoderError> {
+        let mut entry = (None, None);
+        for item in self.as_decoder()? {
+            match item? {
+                (1, key) => entry.0 = Some(key),
+                (2, value) => entry.1 = Some(value),
+                _ => {}
+            }
+        }
+        Ok(entry)
+    }
+}
+
 /// Decoder for parsing protobuf messages.
 ///
 /// Example:
@@ -46,7 +104,12 @@ pub enum PbDecoderField<'a> {
 ///                       \x28\xff\xff\xff\xff\xff\xff\xff\xff\xff\x01";
 ///
 /// for item in perfetto_sdk::pb_decoder::PbDecoder::new(MSG) {
-///     // Do something with item
+///     let (id, field) = item.unwrap();
+///     if id == 5 {
+///         for nested in field.as_decoder().unwrap() {
+///             // Do something with nested item
+///         }
+///     }
 /// }
 /// ```
 pub struct PbDecoder<'a> {
@@ -67,6 +130,14 @@ impl<'a> PbDecoder<'a> {
             _data: data,
         }
     }
+
+    /// Returns the number of bytes left to decode. Non-zero once the decoder
+    /// has stopped means that the data is malformed.
+    pub fn remaining(&self) -> usize {
+        // SAFETY: `read_ptr` and `end_ptr` point into the same slice, with
+        // `read_ptr` at most at `end_ptr`.
+        unsafe { self.decoder.end_ptr.offset_from(self.decoder.read_ptr) as usize }
+    }
 }
 
 impl<'a> Iterator for PbDecoder<'a> {
@@ -105,6 +176,38 @@ impl<'a> Iterator for PbDecoder<'a> {
     }
 }
 
+/// Appends a decoded `field` back to the encoded message in `buffer`.
+pub(crate) fn append_field(buffer: &mut Vec<u8>, field_id: u32, field: &PbDecoderField) {
+    let mut scratch = [0u8; PB_VARINT_MAX_SIZE_64];
+    let mut append_varint = |buffer: &mut Vec<u8>, value: u64| {
+        let size = pb_write_varint(value, &mut scratch);
+        buffer.extend_from_slice(&scratch[..size]);
+    };
+    match field {
+        PbDecoderField::Varint(value) => {
+            append_varint(buffer, pb_make_tag(field_id, PbWireType::Varint).into());
+            append_varint(buffer, *value);
+        }
+        PbDecoderField::Fixed64(value) => {
+            append_varint(buffer, pb_make_tag(field_id, PbWireType::Fixed64).into());
+            let mut bytes = [0u8; 8];
+            pb_write_fixed64(*value, &mut bytes);
+            buffer.extend_from_slice(&bytes);
+        }
+        PbDecoderField::Delimited(data) => {
+            append_varint(buffer, pb_make_tag(field_id, PbWireType::Delimited).into());
+            append_varint(buffer, data.len() as u64);
+            buffer.extend_from_slice(data);
+        }
+        PbDecoderField::Fixed32(value) => {
+            append_varint(buffer, pb_make_tag(field_id, PbWireType::Fixed32).into());
+            let mut bytes = [0u8; 4];
+            pb_write_fixed32(*value, &mut bytes);
+            buffer.extend_from_slice(&bytes);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -132,13 +235,34 @@ mod tests {
         let items: Vec<_> = decoder.collect();
         assert_eq!(items[0], Ok((3, Varint(5))));
         match &items[1] {
-            Ok((5, Delimited(data))) => {
-                let payload_decoder = PbDecoder::new(data);
-                let payload_items: Vec<_> = payload_decoder.collect();
+            Ok((5, payload)) => {
+                let payload_items: Vec<_> = payload.as_decoder().unwrap().collect();
                 assert_eq!(payload_items[0], Ok((1, Delimited(b"hello"))));
                 assert_eq!(payload_items[1], Ok((5, Varint(-1i64 as u64))));
+                let (_, str) = payload_items[0].as_ref().unwrap();
+                assert_eq!(str.as_str(), Ok("hello"));
             }
             other => panic!("unexpected item: {:?}", other),
         }
+        let (_, counter) = items[0].as_ref().unwrap();
+        assert_eq!(counter.as_str(), Err(PbDecoderError::NotDelimited));
+        assert!(matches!(
+            Delimited(b"\xff").as_str(),
+            Err(PbDecoderError::InvalidUtf8(_))
+        ));
+    }
+
+    #[test]
+    fn map_entry() {
+        use PbDecoderField::*;
+        assert_eq!(
+            Delimited(b"\x08\x07\x12\x03foo").as_map_entry(),
+            Ok((Some(Varint(7)), Some(Delimited(b"foo"))))
+        );
+        assert_eq!(
+            Delimited(b"\x12\x00").as_map_entry(),
+            Ok((None, Some(Delimited(b""))))
+        );
+        assert_eq!(Varint(1).as_map_entry(), Err(PbDecoderError::NotDelimited));
     }
 }
@@ -0,0 +1,152 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Protobuf message with a runtime descriptor. Implemented by the types
+/// defined with [`pb_msg!`](crate::pb_msg).
+pub trait PbMessage {
+    /// Returns the descriptor of the message.
+    fn descriptor() -> &'static MessageDescriptor;
+}
+
+/// Protobuf enum with a runtime descriptor. Implemented by the types defined
+/// with [`pb_enum!`](crate::pb_enum).
+pub trait PbEnum {
+    /// Returns the descriptor of the enum.
+    fn descriptor() -> &'static EnumDescriptor;
+}
+
+/// Type of a message field.
+#[derive(Debug, Clone, Copy)]
+pub enum FieldType {
+    /// `bool` field.
+    Bool,
+    /// `int32`, `sint32` or `sfixed32` field.
+    I32,
+    /// `int64`, `sint64` or `sfixed64` field.
+    I64,
+    /// `uint32` or `fixed32` field.
+    U32,
+    /// `uint64` or `fixed64` field.
+    U64,
+    /// `float` field.
+    F32,
+    /// `double` field.
+    F64,
+    /// `string` field.
+    String,
+    /// `bytes` field.
+    Bytes,
+    /// Enum field.
+    Enum(fn() -> &'static EnumDescriptor),
+    /// Nested message field.
+    Message(fn() -> &'static MessageDescriptor),
+    /// Map field, with the descriptor of the entry message.
+    Map(fn() -> &'static MessageDescriptor),
+}
+
+/// Descriptor of a message field.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldDescriptor {
+    /// Field name.
+    pub name: &'static str,
+    /// Field number.
+    pub number: u32,
+    /// Field type.
+    pub field_type: FieldType,
+}
+
+/// Descriptor of a protobuf message.
+#[derive(Debug)]
+pub struct MessageDescriptor {
+    /// Message name.
+    pub name: &'static str,
+    /// Message fields.
+    pub fields: &'static [FieldDescriptor],
+}
+
+impl MessageDescriptor {
+    /// Returns the field with number `number`.
+    pub fn field(&self, number: u32) -> Option<&'static FieldDescriptor> {
+        self.fields.iter().find(|field| field.number == number)
+    }
+
+    /// Returns the field named `name`.
+    pub fn field_by_name(&self, name: &str) -> Option<&'static FieldDescriptor> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+}
+
+/// Descriptor of a protobuf enum.
+#[derive(Debug)]
+pub struct EnumDescriptor {
+    /// Enum name.
+    pub name: &'static str,
+    /// Enum values, with their names.
+    pub values: &'static [(&'static str, u32)],
+}
+
+impl EnumDescriptor {
+    /// Returns the name of value `value`.
+    pub fn value_name(&self, value: u32) -> Option<&'static str> {
+        self.values
+            .iter()
+            .find(|(_, number)| *number == value)
+            .map(|(name, _)| *name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protos::trace::{
+        trace_packet::TracePacket,
+        track_event::{
+            debug_annotation::DebugAnnotation,
+            track_event::{TrackEvent, TrackEventType},
+        },
+    };
+
+    #[test]
+    fn descriptors() {
+        let packet = TracePacket::descriptor();
+        assert_eq!(packet.name, "TracePacket");
+        let track_event = packet.field_by_name("track_event").unwrap();
+        assert_eq!(track_event.number, 11);
+        let FieldType::Message(track_event) = track_event.field_type else {
+            panic!("unexpected type {:?}", track_event.field_type);
+        };
+        assert!(std::ptr::eq(track_event(), TrackEvent::descriptor()));
+        assert!(matches!(
+            packet.field(8).unwrap().field_type,
+            FieldType::U64
+        ));
+
+        let event_type = TrackEvent::descriptor().field_by_name("type").unwrap();
+        let FieldType::Enum(event_type) = event_type.field_type else {
+            panic!("unexpected type {:?}", event_type.field_type);
+        };
+        assert_eq!(event_type().name, "TrackEventType");
+        assert_eq!(event_type().value_name(3), Some("TYPE_INSTANT"));
+        assert_eq!(TrackEventType::descriptor().value_name(100), None);
+
+        // Recursive message.
+        let nested = DebugAnnotation::descriptor()
+            .field_by_name("dict_entries")
+            .unwrap();
+        let FieldType::Message(nested) = nested.field_type else {
+            panic!("unexpected type {:?}", nested.field_type);
+        };
+        assert!(std::ptr::eq(nested(), DebugAnnotation::descriptor()));
+    }
+}
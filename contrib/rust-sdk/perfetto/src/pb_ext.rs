@@ -0,0 +1,190 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Extension registry errors.
+#[derive(Error, Debug, PartialEq)]
+pub enum ExtensionError {
+    /// Two extensions use the same field number of a message.
+    #[error("Field {1} of {0} is used by both `{2}` and `{3}`.")]
+    Conflict(&'static str, u32, &'static str, &'static str),
+}
+
+/// Extension fields of a protobuf message, as defined by
+/// [`pb_msg_ext!`](crate::pb_msg_ext).
+///
+/// Implemented by the `FooExtFieldNumber` enums defined by the macro.
+pub trait PbMsgExtension {
+    /// Name of the extended message, e.g. `"TracePacket"`.
+    const EXTENDEE: &'static str;
+    /// Numbers and names of the extension fields.
+    const FIELDS: &'static [(u32, &'static str)];
+}
+
+/// Extension field of a registered [`PbMsgExtension`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtensionField {
+    /// Name of the extended message.
+    pub extendee: &'static str,
+    /// Field number.
+    pub id: u32,
+    /// Field name.
+    pub name: &'static str,
+}
+
+/// Registry of the extension fields of messages, to dispatch the fields of
+/// decoded messages to the extensions that define them.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::{
+///     pb_ext::ExtensionRegistry, pb_msg, pb_msg_ext, protos::trace::trace_packet::TracePacket,
+/// };
+///
+/// pb_msg!(MyEvent {
+///     value: u64, primitive, 1,
+/// });
+///
+/// pb_msg_ext!(TracePacket {
+///     my_event: MyEvent, msg, 5000,
+/// });
+///
+/// let mut registry = ExtensionRegistry::new();
+/// registry.register::<TracePacketExtFieldNumber>().unwrap();
+/// assert_eq!(registry.lookup("TracePacket", 5000).unwrap().name, "my_event");
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ExtensionRegistry {
+    // Extension fields by extendee and field number.
+    fields: HashMap<&'static str, HashMap<u32, ExtensionField>>,
+}
+
+impl ExtensionRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the fields of extension `E`. Fails without registering any
+    /// field if a field number is already used by another extension of the
+    /// same message. Registering the same extension again has no effect.
+    pub fn register<E: PbMsgExtension>(&mut self) -> Result<&mut Self, ExtensionError> {
+        for &(id, name) in E::FIELDS {
+            if let Some(existing) = self.lookup(E::EXTENDEE, id)
+                && existing.name != name
+            {
+                return Err(ExtensionError::Conflict(
+                    E::EXTENDEE,
+                    id,
+                    existing.name,
+                    name,
+                ));
+            }
+        }
+        let fields = self.fields.entry(E::EXTENDEE).or_default();
+        for &(id, name) in E::FIELDS {
+            fields.insert(
+                id,
+                ExtensionField {
+                    extendee: E::EXTENDEE,
+                    id,
+                    name,
+                },
+            );
+        }
+        Ok(self)
+    }
+
+    /// Returns the extension field number `id` of message `extendee`.
+    pub fn lookup(&self, extendee: &str, id: u32) -> Option<&ExtensionField> {
+        self.fields.get(extendee)?.get(&id)
+    }
+
+    /// Calls `cb` for each field of the encoded `message` of type `extendee`
+    /// that is a registered extension field. Other fields are skipped.
+    pub fn decode_extensions<'a, F>(
+        &self,
+        extendee: &str,
+        message: &'a [u8],
+        mut cb: F,
+    ) -> Result<(), PbDecoderError>
+    where
+        F: FnMut(&ExtensionField, PbDecoderField<'a>),
+    {
+        for item in PbDecoder::new(message) {
+            let (id, field) = item?;
+            if let Some(extension) = self.lookup(extendee, id) {
+                cb(extension, field);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_msg, pb_msg_ext,
+        protos::trace::{test_event::TestEvent, trace_packet::TracePacket},
+    };
+
+    #[allow(dead_code)]
+    mod first {
+        use super::*;
+        pb_msg_ext!(TracePacket {
+            first_event: TestEvent, msg, 5000,
+            first_value: u64, primitive, 5001,
+        });
+    }
+
+    #[allow(dead_code)]
+    mod conflicting {
+        use super::*;
+        pb_msg_ext!(TracePacket {
+            conflicting_value: u64, primitive, 5001,
+        });
+    }
+
+    #[test]
+    fn register_and_decode() {
+        let mut registry = ExtensionRegistry::new();
+        registry
+            .register::<first::TracePacketExtFieldNumber>()
+            .unwrap()
+            .register::<first::TracePacketExtFieldNumber>()
+            .unwrap();
+        assert_eq!(
+            registry
+                .register::<conflicting::TracePacketExtFieldNumber>()
+                .unwrap_err(),
+            ExtensionError::Conflict("TracePacket", 5001, "first_value", "conflicting_value")
+        );
+        assert!(registry.lookup("TrackEvent", 5000).is_none());
+
+        // Field 8 (timestamp) followed by field 5001.
+        let packet = b"\x40\x07\xc8\xb8\x02\x2a";
+        let mut fields = Vec::new();
+        registry
+            .decode_extensions("TracePacket", packet, |extension, field| {
+                fields.push((extension.name, field));
+            })
+            .unwrap();
+        assert_eq!(fields, vec![("first_value", PbDecoderField::Varint(42))]);
+    }
+}
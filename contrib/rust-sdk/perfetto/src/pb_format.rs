@@ -0,0 +1,365 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    pb_decoder::{PbDecoder, PbDecoderField},
+    pb_descriptor::{FieldType, MessageDescriptor, PbMessage},
+    pb_ext::ExtensionRegistry,
+    pb_utils::pb_parse_varint,
+    protos::config::data_source_config::DataSourceConfig,
+};
+use std::fmt::Write;
+
+// Bytes printed for bytes fields and undecodable payloads.
+const MAX_HEX_BYTES: usize = 64;
+
+// Nesting depth up to which unknown payloads are decoded as messages.
+const MAX_UNKNOWN_DEPTH: usize = 16;
+
+/// Renders encoded protobuf messages as an indented, text-proto like tree,
+/// e.g. to log the configs received by the `on_setup` callback of a data
+/// source.
+///
+/// Fields that are in the descriptor of the message are printed with their
+/// name and decoded according to their type. Other fields, e.g. the configs
+/// of vendor data sources, are printed with their number, or with their name
+/// if they are registered in the [`ExtensionRegistry`] passed to
+/// [`extensions`](Self::extensions). Their payloads are printed as strings
+/// if they are printable, as nested messages if they decode as such, and as
+/// hex bytes otherwise.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::{
+///     pb_format::PbFormatter,
+///     protos::config::data_source_config::DataSourceConfig,
+/// };
+///
+/// // name: "com.example", target_buffer: 1 and an unknown field 5000.
+/// let config = b"\x0a\x0bcom.example\x10\x01\xc0\xb8\x02\x2a";
+/// assert_eq!(
+///     PbFormatter::new().format::<DataSourceConfig>(config),
+///     "DataSourceConfig {\n  name: \"com.example\"\n  target_buffer: 1\n  #5000: 42\n}\n"
+/// );
+/// ```
+#[derive(Default)]
+#[must_use = "This is a builder; remember to call `.format()` (or keep chaining)."]
+pub struct PbFormatter<'r> {
+    extensions: Option<&'r ExtensionRegistry>,
+}
+
+impl<'r> PbFormatter<'r> {
+    /// Creates a formatter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the registry used to name the extension fields of messages.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn extensions(mut self, extensions: &'r ExtensionRegistry) -> Self {
+        self.extensions = Some(extensions);
+        self
+    }
+
+    /// Renders the encoded message `message` of type `M`.
+    pub fn format<M: PbMessage>(&self, message: &[u8]) -> String {
+        self.format_with(M::descriptor(), message)
+    }
+
+    /// Renders the encoded message `message` described by `descriptor`.
+    pub fn format_with(&self, descriptor: &'static MessageDescriptor, message: &[u8]) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{} {{", descriptor.name);
+        self.write_message(&mut out, Some(descriptor), message, 1);
+        out.push_str("}\n");
+        out
+    }
+
+    fn write_message(
+        &self,
+        out: &mut String,
+        descriptor: Option<&'static MessageDescriptor>,
+        message: &[u8],
+        depth: usize,
+    ) {
+        let indent = "  ".repeat(depth);
+        let mut decoder = PbDecoder::new(message);
+        for item in &mut decoder {
+            let (id, field) = match item {
+                Ok(item) => item,
+                Err(err) => {
+                    let _ = writeln!(out, "{indent}<malformed: {err}>");
+                    return;
+                }
+            };
+            let known = descriptor.and_then(|descriptor| descriptor.field(id));
+            let name = match (known, descriptor) {
+                (Some(known), _) => known.name.to_string(),
+                (None, Some(descriptor)) => self
+                    .extensions
+                    .and_then(|extensions| extensions.lookup(descriptor.name, id))
+                    .map_or_else(|| format!("#{id}"), |extension| extension.name.to_string()),
+                (None, None) => format!("#{id}"),
+            };
+            match (known.map(|known| known.field_type), &field) {
+                (
+                    Some(FieldType::Message(nested) | FieldType::Map(nested)),
+                    PbDecoderField::Delimited(value),
+                ) => {
+                    let _ = writeln!(out, "{indent}{name} {{");
+                    self.write_message(out, Some(nested()), value, depth + 1);
+                    let _ = writeln!(out, "{indent}}}");
+                }
+                (Some(field_type), _) if write_typed(out, &indent, &name, field_type, &field) => {}
+                _ => self.write_unknown(out, &indent, &name, &field, depth),
+            }
+        }
+        if decoder.remaining() > 0 {
+            let _ = writeln!(
+                out,
+                "{indent}<malformed: {} trailing bytes>",
+                decoder.remaining()
+            );
+        }
+    }
+
+    fn write_unknown(
+        &self,
+        out: &mut String,
+        indent: &str,
+        name: &str,
+        field: &PbDecoderField,
+        depth: usize,
+    ) {
+        let _ = match field {
+            PbDecoderField::Varint(value) => writeln!(out, "{indent}{name}: {value}"),
+            PbDecoderField::Fixed32(value) => writeln!(out, "{indent}{name}: 0x{value:08x}"),
+            PbDecoderField::Fixed64(value) => writeln!(out, "{indent}{name}: 0x{value:016x}"),
+            PbDecoderField::Delimited(value) => {
+                if let Some(value) = printable_str(value) {
+                    writeln!(out, "{indent}{name}: {value:?}")
+                } else if depth < MAX_UNKNOWN_DEPTH && is_message(value) {
+                    let _ = writeln!(out, "{indent}{name} {{");
+                    self.write_message(out, None, value, depth + 1);
+                    writeln!(out, "{indent}}}")
+                } else {
+                    writeln!(out, "{indent}{name}: {}", hex(value))
+                }
+            }
+        };
+    }
+}
+
+// Writes a field of a known type, or returns false if the field doesn't match
+// its type.
+fn write_typed(
+    out: &mut String,
+    indent: &str,
+    name: &str,
+    field_type: FieldType,
+    field: &PbDecoderField,
+) -> bool {
+    use PbDecoderField::*;
+    let value = match (field_type, field) {
+        (FieldType::String, Delimited(value)) => match std::str::from_utf8(value) {
+            Ok(value) => format!("{value:?}"),
+            Err(_) => hex(value),
+        },
+        (FieldType::Bytes, Delimited(value)) => hex(value),
+        (FieldType::Enum(descriptor), Varint(value)) => descriptor()
+            .value_name(*value as u32)
+            .map_or_else(|| (*value as i32).to_string(), str::to_string),
+        (FieldType::F32, Fixed32(value)) => f32::from_bits(*value).to_string(),
+        (FieldType::F64, Fixed64(value)) => f64::from_bits(*value).to_string(),
+        (FieldType::I32 | FieldType::U32, Fixed32(value)) => {
+            format_scalar(field_type, u64::from(*value))
+        }
+        (FieldType::I64 | FieldType::U64, Fixed64(value)) => format_scalar(field_type, *value),
+        (
+            FieldType::Bool | FieldType::I32 | FieldType::I64 | FieldType::U32 | FieldType::U64,
+            Varint(value),
+        ) => format_scalar(field_type, *value),
+        // Packed repeated field.
+        (
+            FieldType::Bool | FieldType::I32 | FieldType::I64 | FieldType::U32 | FieldType::U64,
+            Delimited(value),
+        ) => {
+            let Some(values) = parse_packed(value) else {
+                return false;
+            };
+            let values: Vec<String> = values
+                .into_iter()
+                .map(|value| format_scalar(field_type, value))
+                .collect();
+            format!("[{}]", values.join(", "))
+        }
+        _ => return false,
+    };
+    let _ = writeln!(out, "{indent}{name}: {value}");
+    true
+}
+
+fn format_scalar(field_type: FieldType, value: u64) -> String {
+    match field_type {
+        FieldType::Bool => (value != 0).to_string(),
+        FieldType::I32 => (value as i32).to_string(),
+        FieldType::I64 => (value as i64).to_string(),
+        FieldType::U32 => (value as u32).to_string(),
+        _ => value.to_string(),
+    }
+}
+
+fn parse_packed(mut data: &[u8]) -> Option<Vec<u64>> {
+    let mut values = Vec::new();
+    while !data.is_empty() {
+        let (value, size) = pb_parse_varint(data);
+        if size == 0 {
+            return None;
+        }
+        values.push(value);
+        data = &data[size..];
+    }
+    Some(values)
+}
+
+fn printable_str(data: &[u8]) -> Option<&str> {
+    let value = std::str::from_utf8(data).ok()?;
+    value
+        .chars()
+        .all(|c| !c.is_control() || c == '\n' || c == '\t')
+        .then_some(value)
+}
+
+fn is_message(data: &[u8]) -> bool {
+    let mut decoder = PbDecoder::new(data);
+    !data.is_empty()
+        && decoder
+            .by_ref()
+            .all(|item| item.is_ok_and(|(id, _)| id != 0))
+        && decoder.remaining() == 0
+}
+
+fn hex(data: &[u8]) -> String {
+    let mut out = String::from("[");
+    for (index, byte) in data.iter().take(MAX_HEX_BYTES).enumerate() {
+        if index > 0 {
+            out.push(' ');
+        }
+        let _ = write!(out, "{byte:02x}");
+    }
+    if data.len() > MAX_HEX_BYTES {
+        let _ = write!(out, " ... ({} bytes)", data.len());
+    }
+    out.push(']');
+    out
+}
+
+/// Renders the encoded `DataSourceConfig` `config`, as passed to the
+/// `on_setup` callback of a data source, see [`PbFormatter`].
+pub fn format_config(config: &[u8]) -> String {
+    PbFormatter::new().format::<DataSourceConfig>(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        heap_buffer::HeapBuffer,
+        pb_msg,
+        pb_msg::{PbMsg, PbMsgWriter},
+        pb_msg_ext,
+        protos::config::test_config::{TestConfig, TestConfigDummyFields},
+    };
+
+    #[allow(dead_code)]
+    mod vendor {
+        use super::*;
+        pb_msg_ext!(DataSourceConfig {
+            vendor_config: TestConfig, msg, 5000,
+        });
+    }
+
+    #[test]
+    fn data_source_config() {
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        {
+            let mut config = DataSourceConfig { msg: &mut msg };
+            config
+                .set_name("com.example")
+                .set_stop_timeout_ms(500)
+                .set_for_testing(|test_config: &mut TestConfig| {
+                    test_config.set_message_count(3).set_dummy_fields(
+                        |fields: &mut TestConfigDummyFields| {
+                            fields
+                                .set_field_int32(-1)
+                                .set_field_double(0.5)
+                                .set_field_bytes([0xff, 0x00]);
+                        },
+                    );
+                });
+        }
+        msg.append_nested(5000, |msg| {
+            msg.append_type0_field(1, 7);
+            msg.append_type2_field(2, b"vendor");
+        });
+        msg.append_type2_field(5001, &[0xff; 100]);
+        msg.finalize();
+        let mut config = vec![0u8; writer.writer.get_written_size()];
+        hb.copy_into(&mut config);
+
+        let mut extensions = ExtensionRegistry::new();
+        extensions
+            .register::<vendor::DataSourceConfigExtFieldNumber>()
+            .unwrap();
+        let formatted = PbFormatter::new()
+            .extensions(&extensions)
+            .format::<DataSourceConfig>(&config);
+        let bytes = vec!["ff"; MAX_HEX_BYTES].join(" ");
+        assert_eq!(
+            formatted,
+            format!(
+                "DataSourceConfig {{
+  name: \"com.example\"
+  stop_timeout_ms: 500
+  for_testing {{
+    message_count: 3
+    dummy_fields {{
+      field_int32: -1
+      field_double: 0.5
+      field_bytes: [ff 00]
+    }}
+  }}
+  vendor_config {{
+    #1: 7
+    #2: \"vendor\"
+  }}
+  #5001: [{bytes} ... (100 bytes)]
+}}
+"
+            )
+        );
+        assert!(format_config(&config).contains("  #5000 {\n"));
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(
+            format_config(b"\x10\x01\x0a\x05ab"),
+            "DataSourceConfig {\n  target_buffer: 1\n  <malformed: 4 trailing bytes>\n}\n"
+        );
+    }
+}
@@ -19,6 +19,7 @@ use std::{
     cell::RefCell,
     ptr,
     rc::{Rc, Weak},
+    sync::atomic::{AtomicUsize, Ordering},
 };
 use thiserror::Error;
 
@@ -28,6 +29,47 @@ pub enum PbMsgError {
     /// No output for writer.
     #[error("Message writer is missing an output.")]
     MissingOutputForWriter,
+    /// String contains a NUL character.
+    #[error("String for field {field_id} contains a NUL character at byte {position}.")]
+    InteriorNul {
+        /// Number of the field.
+        field_id: u32,
+        /// Byte offset of the first NUL character in the string.
+        position: usize,
+    },
+}
+
+// Maximum length in bytes of string fields, `usize::MAX` when unlimited.
+static MAX_STRING_LEN: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Sets the maximum length in bytes of the string fields written by the
+/// setters of protobuf messages, or `None` to not limit it (the default).
+///
+/// Longer strings are truncated at the last char boundary before the limit,
+/// which keeps packets built from user input bounded in size. Bytes fields
+/// are never truncated.
+pub fn set_max_string_len(max_len: Option<usize>) {
+    MAX_STRING_LEN.store(max_len.unwrap_or(usize::MAX), Ordering::Relaxed);
+}
+
+/// Returns the maximum length in bytes of string fields, if any.
+pub fn max_string_len() -> Option<usize> {
+    match MAX_STRING_LEN.load(Ordering::Relaxed) {
+        usize::MAX => None,
+        max_len => Some(max_len),
+    }
+}
+
+// Truncates `value` to at most `max_len` bytes, at a char boundary.
+fn truncate_str(value: &str, max_len: usize) -> &str {
+    if value.len() <= max_len {
+        return value;
+    }
+    let mut end = max_len;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
 }
 
 /// Reference to the memory used by a `PbMsg` for writing.
@@ -90,6 +132,11 @@ pub struct PbMsg<'a> {
     size_field: Rc<RefCell<PbMsgSizeField>>,
     size: usize,
     writer: &'a PbMsgWriter,
+    // Bitmask of the fields below 64 that have been written.
+    present: u64,
+    // Fields that have been written, in the order they were last written,
+    // with the last value of varint and fixed fields.
+    fields: Vec<(u32, Option<u64>)>,
 }
 
 impl<'a> PbMsg<'a> {
@@ -105,9 +152,55 @@ impl<'a> PbMsg<'a> {
             })),
             size: 0,
             writer,
+            present: 0,
+            fields: Vec::new(),
         })
     }
 
+    /// Returns true if field `field_id` has been written with one of the
+    /// `append_*_field` functions or `append_nested`.
+    pub fn has_field(&self, field_id: u32) -> bool {
+        if field_id < u64::BITS {
+            self.present & (1 << field_id) != 0
+        } else {
+            self.fields.iter().any(|(id, _)| *id == field_id)
+        }
+    }
+
+    /// Returns the last value written to varint or fixed field `field_id`.
+    /// Fixed32 values are zero-extended and float values are returned as
+    /// their bit representation.
+    pub fn field_value(&self, field_id: u32) -> Option<u64> {
+        self.fields
+            .iter()
+            .find(|(id, _)| *id == field_id)
+            .and_then(|(_, value)| *value)
+    }
+
+    /// Returns the field of `field_ids` that has been written last, e.g. to
+    /// find out which field of a oneof is set.
+    pub fn last_field_of(&self, field_ids: &[u32]) -> Option<u32> {
+        self.fields
+            .iter()
+            .rev()
+            .map(|(id, _)| *id)
+            .find(|id| field_ids.contains(id))
+    }
+
+    fn record_field(&mut self, field_id: u32, value: Option<u64>) {
+        if field_id < u64::BITS {
+            self.present |= 1 << field_id;
+        }
+        match self.fields.iter().position(|(id, _)| *id == field_id) {
+            Some(pos) if pos + 1 == self.fields.len() => self.fields[pos].1 = value,
+            Some(pos) => {
+                self.fields.remove(pos);
+                self.fields.push((field_id, value));
+            }
+            None => self.fields.push((field_id, value)),
+        }
+    }
+
     /// Append bytes to message.
     pub fn append_bytes(&mut self, bytes: &[u8]) {
         if crate::__unlikely!(bytes.len() > self.writer.writer.available_bytes()) {
@@ -117,6 +210,25 @@ impl<'a> PbMsg<'a> {
         self.size += bytes.len();
     }
 
+    /// Returns the number of bytes that can be appended to the message before
+    /// the writer switches to a new chunk.
+    pub fn remaining_chunk_bytes(&self) -> usize {
+        self.writer.writer.available_bytes()
+    }
+
+    /// Makes sure that the next `size` bytes appended to the message are
+    /// written to the same chunk, starting a new chunk now if fewer bytes are
+    /// left in the current one.
+    ///
+    /// This is a hint: more than a chunk of bytes are still split across
+    /// chunks.
+    pub fn reserve(&mut self, size: usize) {
+        if crate::__unlikely!(size > self.writer.writer.available_bytes()) {
+            self.size_field.borrow_mut().patch_stack(self.writer);
+            self.writer.writer.new_chunk();
+        }
+    }
+
     /// Append byte to message.
     pub fn append_byte(&mut self, value: u8) {
         self.append_bytes(&[value]);
@@ -151,6 +263,7 @@ impl<'a> PbMsg<'a> {
         let mut written = pb_write_varint(tag.into(), &mut buf);
         written += pb_write_varint(value, &mut buf[written..]);
         self.append_bytes(&buf[..written]);
+        self.record_field(field_id, Some(value));
     }
 
     /// Append delimited field to message.
@@ -162,6 +275,38 @@ impl<'a> PbMsg<'a> {
         written += pb_write_varint(data.len() as u64, &mut buf[written..]);
         self.append_bytes(&buf[..written]);
         self.append_bytes(data);
+        self.record_field(field_id, None);
+    }
+
+    /// Append packed repeated varint field to message.
+    pub fn append_packed_varint_field(&mut self, field_id: u32, values: impl Iterator<Item = u64>) {
+        let mut data = Vec::new();
+        let mut buf: [u8; PB_VARINT_MAX_SIZE_64] = [0; PB_VARINT_MAX_SIZE_64];
+        for value in values {
+            let written = pb_write_varint(value, &mut buf);
+            data.extend_from_slice(&buf[..written]);
+        }
+        self.append_type2_field(field_id, &data);
+    }
+
+    /// Append packed repeated fixed32 field to message.
+    pub fn append_packed_fixed32_field(
+        &mut self,
+        field_id: u32,
+        values: impl Iterator<Item = u32>,
+    ) {
+        let data: Vec<u8> = values.flat_map(u32::to_le_bytes).collect();
+        self.append_type2_field(field_id, &data);
+    }
+
+    /// Append packed repeated fixed64 field to message.
+    pub fn append_packed_fixed64_field(
+        &mut self,
+        field_id: u32,
+        values: impl Iterator<Item = u64>,
+    ) {
+        let data: Vec<u8> = values.flat_map(u64::to_le_bytes).collect();
+        self.append_type2_field(field_id, &data);
     }
 
     /// Append fixed32 field to message.
@@ -172,6 +317,7 @@ impl<'a> PbMsg<'a> {
         let mut written = pb_write_varint(tag.into(), &mut buf);
         written += pb_write_fixed32(value, &mut buf[written..]);
         self.append_bytes(&buf[..written]);
+        self.record_field(field_id, Some(value.into()));
     }
 
     /// Append float field to message.
@@ -187,6 +333,7 @@ impl<'a> PbMsg<'a> {
         let mut written = pb_write_varint(tag.into(), &mut buf);
         written += pb_write_fixed64(value, &mut buf[written..]);
         self.append_bytes(&buf[..written]);
+        self.record_field(field_id, Some(value));
     }
 
     /// Append doubles field to message.
@@ -199,10 +346,31 @@ impl<'a> PbMsg<'a> {
         self.append_type2_field(field_id, c_str.as_bytes());
     }
 
+    /// Append string field to message, truncated to [`max_string_len`].
+    pub fn append_str_field(&mut self, field_id: u32, value: &str) {
+        self.append_type2_field(
+            field_id,
+            truncate_str(value, MAX_STRING_LEN.load(Ordering::Relaxed)).as_bytes(),
+        );
+    }
+
+    /// Append string field to message, truncated to [`max_string_len`], if
+    /// it doesn't contain NUL characters.
+    ///
+    /// This is for strings that end up as C strings in the service or in
+    /// the trace processor, e.g. names, where a NUL would cut them short.
+    pub fn try_append_str_field(&mut self, field_id: u32, value: &str) -> Result<(), PbMsgError> {
+        if let Some(position) = value.bytes().position(|b| b == 0) {
+            return Err(PbMsgError::InteriorNul { field_id, position });
+        }
+        self.append_str_field(field_id, value);
+        Ok(())
+    }
+
     /// Append nested message to message.
-    pub fn append_nested<F>(&mut self, field_id: u32, mut cb: F)
+    pub fn append_nested<F>(&mut self, field_id: u32, cb: F)
     where
-        F: FnMut(&mut PbMsg),
+        F: FnOnce(&mut PbMsg),
     {
         let tag = pb_make_tag(field_id, PbWireType::Delimited);
         self.append_varint(tag.into());
@@ -223,9 +391,12 @@ impl<'a> PbMsg<'a> {
             })),
             size: 0,
             writer: self.writer,
+            present: 0,
+            fields: Vec::new(),
         };
         cb(&mut nested);
         self.size += nested.finalize();
+        self.record_field(field_id, None);
     }
 
     /// Finalize message and return size.
@@ -290,9 +461,59 @@ mod tests {
         assert_eq!(written_size, 13);
         let mut result: Vec<u8> = vec![0u8; written_size];
         hb.copy_into(&mut result);
-        assert_eq!(result, [
-            102, 111, 111, 26, 133, 128, 128, 0, 82, 3, 98, 97, 114
-        ]);
+        assert_eq!(
+            result,
+            [102, 111, 111, 26, 133, 128, 128, 0, 82, 3, 98, 97, 114]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn field_presence() -> Result<(), Box<dyn Error>> {
+        let writer = PbMsgWriter::new();
+        let _hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer)?;
+        msg.append_type0_field(1, 5);
+        msg.append_type0_field(1, 7);
+        msg.append_float_field(2, 1.5);
+        msg.append_cstr_field(100, "foo");
+        msg.append_nested(3, |_| {});
+        assert!(msg.has_field(1) && msg.has_field(2) && msg.has_field(3));
+        assert!(msg.has_field(100));
+        assert!(!msg.has_field(4) && !msg.has_field(101));
+        assert_eq!(msg.field_value(1), Some(7));
+        assert_eq!(msg.field_value(2), Some(1.5f32.to_bits().into()));
+        assert_eq!(msg.field_value(100), None);
+        assert_eq!(msg.last_field_of(&[1, 2]), Some(2));
+        msg.append_type0_field(1, 8);
+        assert_eq!(msg.last_field_of(&[1, 2]), Some(1));
+        assert_eq!(msg.last_field_of(&[4]), None);
+        msg.finalize();
+        Ok(())
+    }
+
+    #[test]
+    fn string_fields() -> Result<(), Box<dyn Error>> {
+        assert_eq!(max_string_len(), None);
+        assert_eq!(truncate_str("héllo", 16), "héllo");
+        assert_eq!(truncate_str("héllo", 3), "hé");
+        assert_eq!(truncate_str("héllo", 2), "h");
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer)?;
+        msg.try_append_str_field(1, "ok")?;
+        assert_eq!(
+            msg.try_append_str_field(2, "n\0k"),
+            Err(PbMsgError::InteriorNul {
+                field_id: 2,
+                position: 1
+            })
+        );
+        assert!(msg.has_field(1) && !msg.has_field(2));
+        msg.finalize();
+        let mut result: Vec<u8> = vec![0u8; writer.writer.get_written_size()];
+        hb.copy_into(&mut result);
+        assert_eq!(result, [10, 2, 111, 107]);
         Ok(())
     }
 }
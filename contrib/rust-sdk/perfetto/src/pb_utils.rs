@@ -233,9 +233,10 @@ mod tests {
         assert_eq!(pb_parse_packed_varints(&[]), vec![]);
 
         // Single-byte varints: [1, 2, 127]
-        assert_eq!(pb_parse_packed_varints(&[0x01, 0x02, 0x7f]), vec![
-            1, 2, 127
-        ]);
+        assert_eq!(
+            pb_parse_packed_varints(&[0x01, 0x02, 0x7f]),
+            vec![1, 2, 127]
+        );
 
         // Multi-byte varints: [128] encoded as [0x80, 0x01]
         assert_eq!(pb_parse_packed_varints(&[0x80, 0x01]), vec![128]);
@@ -250,8 +251,9 @@ mod tests {
         assert_eq!(pb_parse_packed_varints(&[0xac, 0x02]), vec![300]);
 
         // Multiple larger values: [300, 400]
-        assert_eq!(pb_parse_packed_varints(&[0xac, 0x02, 0x90, 0x03]), vec![
-            300, 400
-        ]);
+        assert_eq!(
+            pb_parse_packed_varints(&[0xac, 0x02, 0x90, 0x03]),
+            vec![300, 400]
+        );
     }
 }
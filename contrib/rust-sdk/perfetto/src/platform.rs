@@ -0,0 +1,333 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::data_source::DataSourceTimestamp;
+#[cfg(unix)]
+use std::os::fd::OwnedFd;
+use std::{
+    io,
+    sync::{
+        Arc, LazyLock, RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::{self, JoinHandle},
+};
+
+/// Primitives the SDK uses to read the time, spawn threads and connect to the
+/// tracing service, so that it can run in environments with primitives of
+/// their own, such as simulators, RTOS ports or tests with fake time.
+///
+/// A platform is installed with
+/// [`ProducerInitArgsBuilder::platform`](crate::producer::ProducerInitArgsBuilder::platform).
+/// All methods have defaults that use the primitives of the standard library,
+/// so platforms only implement the ones they replace:
+///
+/// - [`Platform::now`] is the clock of [`DataSourceTimestamp::now`],
+///   [`TrackEventTimestamp::now`](crate::track_event::TrackEventTimestamp::now)
+///   and of the track events emitted without an explicit timestamp. It is
+///   the clock of all sequences, so a custom clock needs a global clock id
+///   (128 and up) and a `ClockSnapshot` that relates it to the other clocks
+///   of the trace, e.g. written with [`ClockSync`](crate::clock_sync::ClockSync).
+/// - [`Platform::spawn`] creates the threads of the SDK, e.g. the ones of
+///   polling data sources, packet sequences and the callback watchdog.
+/// - [`Platform::connect`] creates the connection of the system backend to
+///   the tracing service, if [`Platform::overrides_connect`] returns true.
+///   Otherwise the system backend connects on its own, with support for all
+///   its socket formats, e.g. vsock addresses.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     data_source::DataSourceTimestamp,
+///     platform::Platform,
+///     producer::{Backends, Producer, ProducerInitArgsBuilder},
+/// };
+/// use std::{
+///     sync::{
+///         Arc,
+///         atomic::{AtomicU64, Ordering},
+///     },
+///     time::Duration,
+/// };
+///
+/// #[derive(Default)]
+/// struct SimulatedPlatform {
+///     cycles: AtomicU64,
+/// }
+///
+/// impl Platform for SimulatedPlatform {
+///     fn now(&self) -> Option<DataSourceTimestamp> {
+///         Some(DataSourceTimestamp::Custom {
+///             // Global clock id, see `ClockSync`.
+///             id: 128,
+///             value: Duration::from_nanos(self.cycles.load(Ordering::Relaxed)),
+///         })
+///     }
+/// }
+///
+/// Producer::init(
+///     ProducerInitArgsBuilder::new()
+///         .backends(Backends::IN_PROCESS)
+///         .platform(Arc::new(SimulatedPlatform::default()))
+///         .build(),
+/// );
+/// ```
+pub trait Platform: Send + Sync {
+    /// Returns the current time of the trace clock, or `None` to use the
+    /// clock of the SDK.
+    fn now(&self) -> Option<DataSourceTimestamp> {
+        None
+    }
+
+    /// Spawns a thread named `name` that runs `f`.
+    ///
+    /// The SDK keeps per-thread state for each thread that writes trace data,
+    /// so `f` must run on a thread of its own.
+    fn spawn(&self, name: &str, f: Box<dyn FnOnce() + Send>) -> io::Result<PlatformThread> {
+        Ok(thread::Builder::new()
+            .name(name.to_string())
+            .spawn(f)?
+            .into())
+    }
+
+    /// Returns a socket connected to the tracing service socket
+    /// `socket_name`, see [`connect_socket`]. Only called if
+    /// [`Platform::overrides_connect`] returns true.
+    #[cfg(unix)]
+    fn connect(&self, socket_name: &str) -> io::Result<OwnedFd> {
+        connect_socket(socket_name)
+    }
+
+    /// Returns whether the system backend connects to the tracing service
+    /// with [`Platform::connect`]. Platforms that implement `connect` must
+    /// return true.
+    #[cfg(unix)]
+    fn overrides_connect(&self) -> bool {
+        false
+    }
+}
+
+/// Platform that uses the primitives of the standard library.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DefaultPlatform;
+
+impl Platform for DefaultPlatform {}
+
+/// Thread spawned by a [`Platform`].
+#[must_use = "Dropping a platform thread detaches it."]
+pub struct PlatformThread {
+    join: Box<dyn FnOnce() -> thread::Result<()> + Send>,
+}
+
+impl PlatformThread {
+    /// Creates a thread that is joined by calling `join`.
+    pub fn new<F>(join: F) -> Self
+    where
+        F: FnOnce() -> thread::Result<()> + Send + 'static,
+    {
+        Self {
+            join: Box::new(join),
+        }
+    }
+
+    /// Waits for the thread to finish, see [`JoinHandle::join`].
+    pub fn join(self) -> thread::Result<()> {
+        (self.join)()
+    }
+}
+
+impl From<JoinHandle<()>> for PlatformThread {
+    fn from(handle: JoinHandle<()>) -> Self {
+        Self::new(move || handle.join())
+    }
+}
+
+impl std::fmt::Debug for PlatformThread {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PlatformThread").finish_non_exhaustive()
+    }
+}
+
+/// Connects to the tracing service socket `socket_name` with the sockets of
+/// the standard library. Supports UNIX socket paths, `@abstract` socket names
+/// on Linux and Android and `host:port` TCP addresses, but not vsock
+/// addresses.
+#[cfg(unix)]
+pub fn connect_socket(socket_name: &str) -> io::Result<OwnedFd> {
+    use std::{net::TcpStream, os::unix::net::UnixStream};
+
+    if socket_name.starts_with("vsock://") {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "vsock sockets aren't supported",
+        ));
+    }
+    if let Some(name) = socket_name.strip_prefix('@') {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        {
+            #[cfg(target_os = "android")]
+            use std::os::android::net::SocketAddrExt;
+            #[cfg(target_os = "linux")]
+            use std::os::linux::net::SocketAddrExt;
+            let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+            return Ok(UnixStream::connect_addr(&addr)?.into());
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "android")))]
+        {
+            let _ = name;
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "abstract sockets aren't supported",
+            ));
+        }
+    }
+    if !socket_name.contains('/') && socket_name.contains(':') {
+        return Ok(TcpStream::connect(socket_name)?.into());
+    }
+    Ok(UnixStream::connect(socket_name)?.into())
+}
+
+static DEFAULT_PLATFORM: LazyLock<Arc<dyn Platform>> = LazyLock::new(|| Arc::new(DefaultPlatform));
+
+// Whether a platform was installed, so that the default clock is used without
+// taking the lock.
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+static PLATFORM: RwLock<Option<Arc<dyn Platform>>> = RwLock::new(None);
+
+/// Returns the installed platform, or the [`DefaultPlatform`].
+pub fn platform() -> Arc<dyn Platform> {
+    if !INSTALLED.load(Ordering::Acquire) {
+        return Arc::clone(&DEFAULT_PLATFORM);
+    }
+    PLATFORM
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| Arc::clone(&DEFAULT_PLATFORM))
+}
+
+// Installs `platform` unless a platform was already installed. Returns true
+// if `platform` was installed.
+pub(crate) fn install(platform: Arc<dyn Platform>) -> bool {
+    let mut installed = PLATFORM.write().unwrap();
+    if installed.is_some() {
+        return false;
+    }
+    *installed = Some(platform);
+    INSTALLED.store(true, Ordering::Release);
+    true
+}
+
+#[cfg(test)]
+pub(crate) fn uninstall() {
+    let mut installed = PLATFORM.write().unwrap();
+    *installed = None;
+    INSTALLED.store(false, Ordering::Release);
+}
+
+// Returns whether a platform was installed, so that callers can skip the
+// work of using its clock otherwise.
+#[inline]
+pub(crate) fn is_installed() -> bool {
+    INSTALLED.load(Ordering::Acquire)
+}
+
+// Returns the time of the installed platform, or `None` to use the clock of
+// the SDK.
+pub(crate) fn now() -> Option<DataSourceTimestamp> {
+    if !is_installed() {
+        return None;
+    }
+    PLATFORM.read().unwrap().as_ref()?.now()
+}
+
+// Spawns a thread of the SDK with the installed platform.
+pub(crate) fn spawn<F>(name: &str, f: F) -> io::Result<PlatformThread>
+where
+    F: FnOnce() + Send + 'static,
+{
+    platform().spawn(name, Box::new(f))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::acquire_test_environment;
+    use std::{
+        io::{Read, Write},
+        sync::{Mutex, atomic::AtomicU64},
+        time::Duration,
+    };
+
+    #[derive(Default)]
+    struct FakePlatform {
+        time: AtomicU64,
+        threads: Mutex<Vec<String>>,
+    }
+
+    impl Platform for FakePlatform {
+        fn now(&self) -> Option<DataSourceTimestamp> {
+            Some(DataSourceTimestamp::Custom {
+                id: 128,
+                value: Duration::from_nanos(self.time.fetch_add(10, Ordering::Relaxed)),
+            })
+        }
+
+        fn spawn(&self, name: &str, f: Box<dyn FnOnce() + Send>) -> io::Result<PlatformThread> {
+            self.threads.lock().unwrap().push(name.to_string());
+            DefaultPlatform.spawn(name, f)
+        }
+    }
+
+    #[test]
+    fn fake_platform() {
+        let _lock = acquire_test_environment();
+        let fake = Arc::new(FakePlatform::default());
+        assert!(install(fake.clone()));
+        assert!(!install(Arc::new(DefaultPlatform)));
+        let timestamps: Vec<_> = [DataSourceTimestamp::now(), DataSourceTimestamp::now()]
+            .iter()
+            .map(|timestamp| (timestamp.clock_id(), timestamp.timestamp()))
+            .collect();
+        spawn("fake-thread", || {}).unwrap().join().unwrap();
+        uninstall();
+        assert_eq!(timestamps, vec![(128, 0), (128, 10)]);
+        assert_eq!(*fake.threads.lock().unwrap(), vec!["fake-thread"]);
+        assert!(now().is_none());
+        // The system backend keeps connecting on its own.
+        #[cfg(unix)]
+        assert!(!fake.overrides_connect());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn connect_unix_socket() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("platform_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = std::os::unix::net::UnixListener::bind(&path)?;
+        let fd = DefaultPlatform.connect(path.to_str().unwrap())?;
+        let (mut server, _) = listener.accept()?;
+        std::os::unix::net::UnixStream::from(fd).write_all(b"ping")?;
+        let mut data = [0u8; 4];
+        server.read_exact(&mut data)?;
+        assert_eq!(&data, b"ping");
+        assert_eq!(
+            connect_socket("vsock://2:10001").unwrap_err().kind(),
+            io::ErrorKind::Unsupported
+        );
+        std::fs::remove_file(&path)
+    }
+}
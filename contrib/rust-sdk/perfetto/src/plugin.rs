@@ -0,0 +1,394 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::data_source::{DataSource, DataSourceArgs, DataSourceError};
+use std::{
+    ffi::{CStr, c_char},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use thiserror::Error;
+
+/// Versions of the SDK and of the compiler that it is built with, which
+/// plugins must match to be loaded.
+pub const PLUGIN_ABI_VERSION: &CStr = match CStr::from_bytes_with_nul(
+    concat!(
+        env!("CARGO_PKG_VERSION"),
+        " (",
+        env!("PERFETTO_SDK_RUSTC_VERSION"),
+        ")\0"
+    )
+    .as_bytes(),
+) {
+    Ok(version) => version,
+    Err(_) => panic!("invalid plugin ABI version"),
+};
+
+/// Name of the function that plugins export with [`perfetto_plugin!`] to
+/// register their data sources.
+pub const PLUGIN_ENTRY_SYMBOL: &str = "perfetto_sdk_plugin_register";
+
+/// Name of the function that plugins export with [`perfetto_plugin!`] for
+/// the [`PLUGIN_ABI_VERSION`] that they are built with.
+pub const PLUGIN_VERSION_SYMBOL: &str = "perfetto_sdk_plugin_version";
+
+/// Entry point of a plugin, exported with [`perfetto_plugin!`].
+///
+/// Uses the Rust ABI, which only matches between the host and the plugin when
+/// they are built with the same compiler, see [`PLUGIN_ABI_VERSION`].
+pub type PluginEntry = fn(&mut PluginRegistrar) -> Result<(), DataSourceError>;
+
+/// Version function of a plugin, exported with [`perfetto_plugin!`]. Uses the
+/// C ABI so that it can be called whatever the plugin is built with.
+pub type PluginVersion = extern "C" fn() -> *const c_char;
+
+/// Plugin errors.
+#[derive(Error, Debug)]
+pub enum PluginError {
+    /// The shared object couldn't be loaded, or doesn't export the plugin
+    /// symbols.
+    #[error("Failed to load plugin: {0}.")]
+    LoadError(#[from] libloading::Error),
+    /// The plugin is built against another version of the SDK, or with
+    /// another compiler.
+    #[error("Plugin is built against SDK version {plugin}, expected {host}.")]
+    VersionMismatchError {
+        /// Versions of the SDK and compiler of the plugin.
+        plugin: String,
+        /// Versions of the SDK and compiler of the host, see
+        /// [`PLUGIN_ABI_VERSION`].
+        host: &'static str,
+    },
+    /// The plugin failed to register its data sources.
+    #[error("Plugin failed to register: {0}")]
+    RegisterError(#[from] DataSourceError),
+}
+
+// Names of the data source types registered by plugins, which stay registered
+// once the plugins are unloaded.
+static PLUGIN_DATA_SOURCE_NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+// Registers data sources with the code of the host, so that the trampolines
+// called by the tracing service outlive the plugin. Also checks the names
+// there, as each plugin has its own copy of the statics of the SDK.
+fn register_data_source(
+    name: &str,
+    args: DataSourceArgs,
+) -> Result<&'static DataSource<'static>, DataSourceError> {
+    let mut names = PLUGIN_DATA_SOURCE_NAMES.lock().unwrap();
+    if names.iter().any(|registered| registered == name) {
+        return Err(DataSourceError::PluginNameTakenError(name.to_string()));
+    }
+    let mut data_source = DataSource::new();
+    data_source.register(name, args)?;
+    names.push(name.to_string());
+    Ok(Box::leak(Box::new(data_source)))
+}
+
+/// Registrar passed to the entry point of a plugin, to register the data
+/// sources of the plugin against the producer of the host process.
+pub struct PluginRegistrar {
+    register: fn(&str, DataSourceArgs) -> Result<&'static DataSource<'static>, DataSourceError>,
+    data_sources: Vec<&'static DataSource<'static>>,
+}
+
+impl PluginRegistrar {
+    fn new() -> Self {
+        Self {
+            register: register_data_source,
+            data_sources: Vec::new(),
+        }
+    }
+
+    /// Registers the data source type named `name`, which is retired when
+    /// the plugin is unloaded.
+    ///
+    /// The returned data source type is used to trace from the plugin.
+    ///
+    /// Data source types can't be unregistered with the C API, so a retired
+    /// type still takes one of the 32 data source types that a process can
+    /// register, and its name can't be registered again, e.g. when the plugin
+    /// is loaded again, which returns
+    /// [`DataSourceError::PluginNameTakenError`].
+    pub fn register(
+        &mut self,
+        name: &str,
+        args: DataSourceArgs,
+    ) -> Result<&'static DataSource<'static>, DataSourceError> {
+        let data_source = (self.register)(name, args)?;
+        self.data_sources.push(data_source);
+        Ok(data_source)
+    }
+
+    fn retire(&mut self) {
+        for data_source in self.data_sources.drain(..) {
+            data_source.retire();
+        }
+    }
+}
+
+/// Shared object loaded at runtime that registers data sources against the
+/// producer of the host process, e.g. to extend an agent daemon.
+///
+/// Plugins export their entry point with [`perfetto_plugin!`]. The entry point
+/// is called when the plugin is loaded and registers its data sources with
+/// the [`PluginRegistrar`]. When the plugin is unloaded, its data sources are
+/// retired, see [`DataSource::retire`], so that none of their callbacks run
+/// once the code of the plugin is unmapped. Retiring doesn't free the data
+/// source types, see [`PluginRegistrar::register`].
+///
+/// Plugins must be built with the same compiler and version of the SDK as the
+/// host, which is checked on load, and both must link the Perfetto C library
+/// as a shared library, i.e. without the `vendored` feature, so that they
+/// share the producer of the process.
+///
+/// Example plugin:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     data_source::{DataSourceArgsBuilder, DataSourceError},
+///     perfetto_plugin,
+///     plugin::PluginRegistrar,
+/// };
+///
+/// fn register(registrar: &mut PluginRegistrar) -> Result<(), DataSourceError> {
+///     let data_source = registrar.register(
+///         "com.example.plugin_data_source",
+///         DataSourceArgsBuilder::new().build(),
+///     )?;
+///     std::thread::spawn(move || {
+///         data_source.trace(|ctx| ctx.add_packet(|_packet| {}));
+///     });
+///     Ok(())
+/// }
+///
+/// perfetto_plugin!(register);
+/// ```
+///
+/// Example host:
+///
+/// ```no_run
+/// use perfetto_sdk::{plugin::Plugin, producer::*};
+///
+/// Producer::init(ProducerInitArgsBuilder::new().backends(Backends::SYSTEM).build());
+/// // SAFETY: The plugin is built against this SDK and its initialization is
+/// // sound.
+/// let plugin = unsafe { Plugin::load("libexample_plugin.so") }.unwrap();
+/// // ...
+/// plugin.unload();
+/// ```
+pub struct Plugin {
+    path: PathBuf,
+    registrar: PluginRegistrar,
+    // Closed once dropped, after the data sources are retired.
+    _library: libloading::Library,
+}
+
+impl Plugin {
+    /// Loads the plugin at `path` and registers its data sources.
+    ///
+    /// # Safety
+    ///
+    /// Loading a shared object runs its initialization code, and the plugin
+    /// must be built as described in [`Plugin`]. Its entry point is trusted to
+    /// be what the symbols say.
+    pub unsafe fn load(path: impl AsRef<Path>) -> Result<Self, PluginError> {
+        let path = path.as_ref().to_path_buf();
+        // SAFETY: See the safety section of this function.
+        let library = unsafe { libloading::Library::new(&path)? };
+        // SAFETY: The version function is exported by `perfetto_plugin!` as a
+        // `PluginVersion`.
+        let version = unsafe { *library.get::<PluginVersion>(PLUGIN_VERSION_SYMBOL.as_bytes())? };
+        // SAFETY: `PluginVersion` returns a nul-terminated static string.
+        let version = unsafe { CStr::from_ptr(version()) };
+        if version != PLUGIN_ABI_VERSION {
+            return Err(PluginError::VersionMismatchError {
+                plugin: version.to_string_lossy().into_owned(),
+                host: PLUGIN_ABI_VERSION.to_str().unwrap(),
+            });
+        }
+        // SAFETY: The entry point is exported by `perfetto_plugin!` as a
+        // `PluginEntry`, with the same compiler as the host.
+        let entry = unsafe { *library.get::<PluginEntry>(PLUGIN_ENTRY_SYMBOL.as_bytes())? };
+        let mut registrar = PluginRegistrar::new();
+        if let Err(err) = entry(&mut registrar) {
+            registrar.retire();
+            return Err(err.into());
+        }
+        Ok(Self {
+            path,
+            registrar,
+            _library: library,
+        })
+    }
+
+    /// Path of the shared object of the plugin.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Data source types registered by the plugin.
+    pub fn data_sources(&self) -> &[&'static DataSource<'static>] {
+        &self.registrar.data_sources
+    }
+
+    /// Retires the data sources of the plugin and unloads it. Same as
+    /// dropping the plugin.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from one of the callbacks of the data sources of the
+    /// plugin, see [`DataSource::retire`], as the code of the callback would
+    /// be unloaded while it runs.
+    pub fn unload(self) {}
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        self.registrar.retire();
+    }
+}
+
+/// Exports `$entry`, a [`PluginEntry`](crate::plugin::PluginEntry), as the
+/// entry point of a plugin loaded with [`Plugin::load`](crate::plugin::Plugin::load).
+#[macro_export]
+macro_rules! perfetto_plugin {
+    ($entry:path) => {
+        #[unsafe(no_mangle)]
+        pub extern "C" fn perfetto_sdk_plugin_version() -> *const ::std::ffi::c_char {
+            $crate::plugin::PLUGIN_ABI_VERSION.as_ptr()
+        }
+
+        #[unsafe(no_mangle)]
+        pub fn perfetto_sdk_plugin_register(
+            registrar: &mut $crate::plugin::PluginRegistrar,
+        ) -> ::std::result::Result<(), $crate::data_source::DataSourceError> {
+            let entry: $crate::plugin::PluginEntry = $entry;
+            entry(registrar)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_source::DataSourceArgsBuilder,
+        test_util::{DATA_SOURCE_NAME, data_source},
+        tests::acquire_test_environment,
+    };
+    use std::{
+        env::consts::{DLL_PREFIX, DLL_SUFFIX},
+        error::Error,
+    };
+
+    const FIXTURE_DATA_SOURCE_NAME: &str = "com.example.plugin_fixture";
+
+    // Returns the path of the plugin fixture built as the example `name`, next
+    // to the directory of the test binary. Cargo doesn't build the examples
+    // when the tests are filtered by name.
+    fn fixture_path(name: &str) -> PathBuf {
+        let mut path = std::env::current_exe().unwrap();
+        path.pop();
+        if path.ends_with("deps") {
+            path.pop();
+        }
+        let path = path
+            .join("examples")
+            .join(format!("{DLL_PREFIX}{name}{DLL_SUFFIX}"));
+        assert!(
+            path.exists(),
+            "{} isn't built, build the examples with the plugin feature",
+            path.display()
+        );
+        path
+    }
+
+    // Hands out the shared test data source type instead of registering a new
+    // one, as the number of types is limited and they can't be unregistered.
+    fn get_registered_data_source(
+        name: &str,
+        _args: DataSourceArgs,
+    ) -> Result<&'static DataSource<'static>, DataSourceError> {
+        assert_eq!(name, DATA_SOURCE_NAME);
+        Ok(data_source())
+    }
+
+    #[test]
+    fn registrar() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let mut registrar = PluginRegistrar {
+            register: get_registered_data_source,
+            data_sources: Vec::new(),
+        };
+        let registered =
+            registrar.register(DATA_SOURCE_NAME, DataSourceArgsBuilder::new().build())?;
+        assert!(std::ptr::eq(registered, data_source()));
+        assert_eq!(registrar.data_sources.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn load_and_unload() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let path = fixture_path("plugin_fixture");
+        // SAFETY: The fixture is built with `perfetto_plugin!` against this
+        // SDK.
+        let plugin = unsafe { Plugin::load(&path) }?;
+        assert_eq!(plugin.path(), path);
+        assert_eq!(plugin.data_sources().len(), 1);
+        let data_source = plugin.data_sources()[0];
+        assert!(!data_source.is_retired());
+        plugin.unload();
+        assert!(data_source.is_retired());
+
+        // The name stays taken once the plugin is unloaded, which is checked
+        // by the host as the plugin is loaded again with fresh statics.
+        // SAFETY: See above.
+        let err = unsafe { Plugin::load(&path) }.err();
+        assert!(matches!(
+            err,
+            Some(PluginError::RegisterError(DataSourceError::PluginNameTakenError(name)))
+                if name == FIXTURE_DATA_SOURCE_NAME
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn version_mismatch() {
+        // SAFETY: The fixture only exports a version function.
+        let err = unsafe { Plugin::load(fixture_path("old_sdk_plugin_fixture")) }.err();
+        assert!(matches!(
+            err,
+            Some(PluginError::VersionMismatchError { plugin, host })
+                if plugin == "0.0.0" && host == PLUGIN_ABI_VERSION.to_str().unwrap()
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn missing_symbols() {
+        // SAFETY: The C library has no initialization code that is unsound to
+        // run again.
+        let err = unsafe { Plugin::load("libc.so.6") }.err();
+        assert!(matches!(err, Some(PluginError::LoadError(_))));
+    }
+
+    #[test]
+    fn abi_version() {
+        let version = PLUGIN_ABI_VERSION.to_str().unwrap();
+        assert!(version.starts_with(env!("CARGO_PKG_VERSION")));
+        assert!(version.contains("rustc "));
+    }
+}
@@ -0,0 +1,659 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{DataSource, DataSourceArgsBuilder, DataSourceError, TraceContext},
+    platform::{self, PlatformThread},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Condvar, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+/// Default sampling period of a [`PollingDataSource`].
+pub const DEFAULT_POLLING_PERIOD: Duration = Duration::from_secs(1);
+
+// Shortest sampling period, to avoid spinning on a zero period.
+const MIN_POLLING_PERIOD: Duration = Duration::from_micros(1);
+
+type PeriodCallback = Box<dyn Fn(&[u8]) -> Option<Duration> + Send + Sync + 'static>;
+type InstrumentedCallback = Box<dyn Fn(&[u8]) -> bool + Send + Sync + 'static>;
+type SampleCallback = Box<dyn FnMut(&mut TraceContext) + Send + 'static>;
+
+/// Hook called at the sample points of the instances of a
+/// [`PollingDataSource`] that use instrumented sampling, instead of at a
+/// fixed period.
+///
+/// Sample points are reported with [`PollingDataSource::sample_point`], e.g.
+/// by a GPU driver from callbacks issued by instrumentation inserted in the
+/// command stream. Implemented for closures with the same signature as
+/// [`InstrumentedSampler::on_sample_point`].
+pub trait InstrumentedSampler: Send {
+    /// Called for each started instance that uses instrumented sampling, with
+    /// the queue and the timestamp of the sample point.
+    fn on_sample_point(&mut self, ctx: &mut TraceContext, queue: u32, timestamp: u64);
+}
+
+impl<F> InstrumentedSampler for F
+where
+    F: FnMut(&mut TraceContext, u32, u64) + Send,
+{
+    fn on_sample_point(&mut self, ctx: &mut TraceContext, queue: u32, timestamp: u64) {
+        self(ctx, queue, timestamp)
+    }
+}
+
+struct PolledInstance {
+    period: Duration,
+    // Time of the next sample, or `None` until the instance is started.
+    next: Option<Instant>,
+    // Sampled at the reported sample points instead of by the polling thread.
+    instrumented: bool,
+    started: bool,
+}
+
+#[derive(Default)]
+struct Schedule {
+    instances: HashMap<u32, PolledInstance>,
+    // Polling thread, while there are instances to sample.
+    thread: Option<PlatformThread>,
+    running: bool,
+    exit: bool,
+}
+
+impl Schedule {
+    fn has_polled_instances(&self) -> bool {
+        self.instances
+            .values()
+            .any(|instance| instance.next.is_some())
+    }
+}
+
+// State shared with the polling thread. The thread only runs while there are
+// started instances to sample, so that a registered data source without
+// sessions doesn't wake up the process. This includes the samplers of the SDK
+// itself, e.g. the runtime metrics and self-profiling data sources.
+struct Shared {
+    schedule: Mutex<Schedule>,
+    wakeup: Condvar,
+    data_source: OnceLock<&'static DataSource<'static>>,
+    sample: Mutex<SampleCallback>,
+    name: String,
+}
+
+impl Shared {
+    // Starts the polling thread if there are instances to sample and it isn't
+    // running.
+    fn ensure_polling(self: &Arc<Self>) {
+        let mut schedule = self.schedule.lock().unwrap();
+        if schedule.running || schedule.exit || !schedule.has_polled_instances() {
+            return;
+        }
+        let Some(&data_source) = self.data_source.get() else {
+            return;
+        };
+        let thread_shared = Arc::clone(self);
+        let thread = platform::spawn(&format!("{}-poll", self.name), move || {
+            poll(&thread_shared, data_source)
+        })
+        .expect("failed to create polling thread");
+        schedule.running = true;
+        let previous = schedule.thread.replace(thread);
+        drop(schedule);
+        // A previous thread has marked itself as not running and released the
+        // lock, so it only has to return.
+        if let Some(previous) = previous {
+            let _ = previous.join();
+        }
+    }
+}
+
+/// Polling data source builder.
+#[must_use = "This is a builder; remember to call `.register()` (or keep chaining)."]
+pub struct PollingDataSourceBuilder {
+    args: DataSourceArgsBuilder,
+    default_period: Duration,
+    period_from_config: Option<PeriodCallback>,
+    instrumented_from_config: Option<InstrumentedCallback>,
+    sampler: Option<Box<dyn InstrumentedSampler>>,
+}
+
+impl Default for PollingDataSourceBuilder {
+    fn default() -> Self {
+        Self {
+            args: DataSourceArgsBuilder::new(),
+            default_period: DEFAULT_POLLING_PERIOD,
+            period_from_config: None,
+            instrumented_from_config: None,
+            sampler: None,
+        }
+    }
+}
+
+impl PollingDataSourceBuilder {
+    /// Create new polling data source builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the data source arguments. Setup, start and stop callbacks are
+    /// called before the instance is scheduled or unscheduled.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn data_source_args(mut self, args: DataSourceArgsBuilder) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Set the sampling period of instances whose period isn't set by the
+    /// config.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn default_period(mut self, period: Duration) -> Self {
+        self.default_period = period;
+        self
+    }
+
+    /// Set a callback that returns the sampling period of an instance from its
+    /// encoded `DataSourceConfig`, e.g. the `counter_period_ns` of a
+    /// `GpuCounterConfig`. The default period is used if `cb` returns `None`.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn period_from_config<F>(mut self, cb: F) -> Self
+    where
+        F: Fn(&[u8]) -> Option<Duration> + Send + Sync + 'static,
+    {
+        self.period_from_config = Some(Box::new(cb));
+        self
+    }
+
+    /// Set the hook that samples instances that use instrumented sampling,
+    /// see [`Self::instrumented_from_config`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn instrumented_sampler<S>(mut self, sampler: S) -> Self
+    where
+        S: InstrumentedSampler + 'static,
+    {
+        self.sampler = Some(Box::new(sampler));
+        self
+    }
+
+    /// Set a callback that returns true if an instance uses instrumented
+    /// sampling according to its encoded `DataSourceConfig`, e.g. the
+    /// `instrumented_sampling` field of a `GpuCounterConfig`. Such instances
+    /// aren't sampled by the polling thread but by the
+    /// [`InstrumentedSampler`], at the points reported with
+    /// [`PollingDataSource::sample_point`].
+    ///
+    /// Ignored unless an instrumented sampler is set.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn instrumented_from_config<F>(mut self, cb: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.instrumented_from_config = Some(Box::new(cb));
+        self
+    }
+
+    /// Registers the data source type named `name` and starts polling it.
+    ///
+    /// `sample` is called on the polling thread for each started instance at
+    /// the sampling period of that instance, until the instance is stopped.
+    /// Samples that are missed because `sample` took too long are skipped.
+    /// The polling thread is started with the first instance and exits after
+    /// the last one is stopped, so the data source doesn't wake up the
+    /// process while there are no sessions.
+    ///
+    /// Panics if the polling thread can't be created.
+    pub fn register<F>(self, name: &str, sample: F) -> Result<PollingDataSource, DataSourceError>
+    where
+        F: FnMut(&mut TraceContext) + Send + 'static,
+    {
+        let shared = Arc::new(Shared {
+            schedule: Mutex::new(Schedule::default()),
+            wakeup: Condvar::new(),
+            data_source: OnceLock::new(),
+            sample: Mutex::new(Box::new(sample)),
+            name: name.to_string(),
+        });
+        let default_period = self.default_period;
+        let period_from_config = self.period_from_config;
+        let instrumented_from_config = self.instrumented_from_config;
+        let has_sampler = self.sampler.is_some();
+        let setup_shared = Arc::clone(&shared);
+        let start_shared = Arc::clone(&shared);
+        let stop_shared = Arc::clone(&shared);
+        let args = self.args.observe_instances(
+            move |inst_id, config| {
+                let period = period_from_config
+                    .as_ref()
+                    .and_then(|cb| cb(config))
+                    .unwrap_or(default_period)
+                    .max(MIN_POLLING_PERIOD);
+                let instrumented = has_sampler
+                    && instrumented_from_config
+                        .as_ref()
+                        .is_some_and(|cb| cb(config));
+                let mut schedule = setup_shared.schedule.lock().unwrap();
+                schedule.instances.insert(
+                    inst_id,
+                    PolledInstance {
+                        period,
+                        next: None,
+                        instrumented,
+                        started: false,
+                    },
+                );
+            },
+            move |inst_id| {
+                let mut schedule = start_shared.schedule.lock().unwrap();
+                if let Some(instance) = schedule.instances.get_mut(&inst_id) {
+                    instance.started = true;
+                    if !instance.instrumented {
+                        instance.next = Some(Instant::now());
+                        start_shared.wakeup.notify_one();
+                    }
+                }
+                drop(schedule);
+                start_shared.ensure_polling();
+            },
+            move |inst_id| {
+                let mut schedule = stop_shared.schedule.lock().unwrap();
+                schedule.instances.remove(&inst_id);
+                // Let the polling thread exit now rather than at the next
+                // sample of the instance.
+                stop_shared.wakeup.notify_one();
+            },
+        );
+
+        // Data sources can't be unregistered, and the registered data source
+        // must not move, so it lives for the rest of the process.
+        let data_source: &'static mut DataSource<'static> = Box::leak(Box::new(DataSource::new()));
+        data_source.register(name, args.build())?;
+        let data_source: &'static DataSource<'static> = data_source;
+        let _ = shared.data_source.set(data_source);
+        // Instances may have been started before the data source was set.
+        shared.ensure_polling();
+        Ok(PollingDataSource {
+            data_source,
+            shared,
+            sampler: self.sampler.map(Mutex::new),
+        })
+    }
+}
+
+fn poll(shared: &Shared, data_source: &'static DataSource<'static>) {
+    let mut due = Vec::new();
+    let mut schedule = shared.schedule.lock().unwrap();
+    while !schedule.exit && schedule.has_polled_instances() {
+        let now = Instant::now();
+        let mut next_deadline: Option<Instant> = None;
+        due.clear();
+        for (inst_id, instance) in schedule.instances.iter_mut() {
+            let Some(mut next) = instance.next else {
+                continue;
+            };
+            if next <= now {
+                due.push(*inst_id);
+                next += instance.period;
+                if next <= now {
+                    next = now + instance.period;
+                }
+                instance.next = Some(next);
+            }
+            next_deadline = Some(next_deadline.map_or(next, |deadline| deadline.min(next)));
+        }
+        if !due.is_empty() {
+            // Callbacks of the data source take the lock, so don't hold it while
+            // tracing.
+            drop(schedule);
+            let mut sample = shared.sample.lock().unwrap();
+            data_source.trace(|ctx: &mut TraceContext| {
+                if due.contains(&ctx.instance_index()) {
+                    sample(ctx);
+                }
+            });
+            drop(sample);
+            schedule = shared.schedule.lock().unwrap();
+            continue;
+        }
+        schedule = match next_deadline {
+            Some(deadline) => {
+                shared
+                    .wakeup
+                    .wait_timeout(schedule, deadline - now)
+                    .unwrap()
+                    .0
+            }
+            None => shared.wakeup.wait(schedule).unwrap(),
+        };
+    }
+    schedule.running = false;
+}
+
+/// A data source that is sampled at a fixed period by a timer thread owned
+/// by the SDK, instead of by a loop written by the user.
+///
+/// Each instance is sampled at its own period, which can be derived from its
+/// config. Sampling starts when the instance is started and stops when it is
+/// stopped. Instances can instead be sampled at points reported by the
+/// instrumented workload, see [`InstrumentedSampler`].
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     data_source::{DataSourceTimestamp, TraceContext},
+///     polling::PollingDataSourceBuilder,
+///     protos::trace::trace_packet::TracePacket,
+/// };
+/// use std::time::Duration;
+///
+/// let data_source = PollingDataSourceBuilder::new()
+///     .default_period(Duration::from_millis(10))
+///     .register("com.example.sampler", |ctx: &mut TraceContext| {
+///         ctx.add_packet(|packet: &mut TracePacket| {
+///             packet.set_timestamp(DataSourceTimestamp::now().timestamp());
+///         });
+///     })
+///     .unwrap();
+/// ```
+pub struct PollingDataSource {
+    data_source: &'static DataSource<'static>,
+    shared: Arc<Shared>,
+    sampler: Option<Mutex<Box<dyn InstrumentedSampler>>>,
+}
+
+impl PollingDataSource {
+    /// Returns the underlying data source, e.g. to trace data outside of the
+    /// sampling callback.
+    pub fn data_source(&self) -> &'static DataSource<'static> {
+        self.data_source
+    }
+
+    /// Reports a sample point of `queue` at `timestamp`. Calls the
+    /// [`InstrumentedSampler`] for each started instance that uses
+    /// instrumented sampling, on the calling thread. Does nothing if there is
+    /// no such instance.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use perfetto_sdk::{
+    ///     data_source::{DataSourceTimestamp, TraceContext},
+    ///     polling::PollingDataSourceBuilder,
+    /// };
+    ///
+    /// let data_source = PollingDataSourceBuilder::new()
+    ///     // Decode the `instrumented_sampling` field of the config.
+    ///     .instrumented_from_config(|_config| true)
+    ///     .instrumented_sampler(|ctx: &mut TraceContext, queue: u32, timestamp: u64| {
+    ///         // Read the counters of `queue` and write them at `timestamp`.
+    ///     })
+    ///     .register("com.example.gpu.counters", |_ctx: &mut TraceContext| {
+    ///         // Read the counters and write them at the current time.
+    ///     })
+    ///     .unwrap();
+    ///
+    /// // From the callback of the command stream instrumentation.
+    /// data_source.sample_point(0, DataSourceTimestamp::now().timestamp());
+    /// ```
+    pub fn sample_point(&self, queue: u32, timestamp: u64) {
+        let Some(sampler) = &self.sampler else {
+            return;
+        };
+        let instrumented: Vec<u32> = {
+            let schedule = self.shared.schedule.lock().unwrap();
+            schedule
+                .instances
+                .iter()
+                .filter(|(_, instance)| instance.instrumented && instance.started)
+                .map(|(inst_id, _)| *inst_id)
+                .collect()
+        };
+        if instrumented.is_empty() {
+            return;
+        }
+        let mut sampler = sampler.lock().unwrap();
+        self.data_source.trace(|ctx: &mut TraceContext| {
+            if instrumented.contains(&ctx.instance_index()) {
+                sampler.on_sample_point(ctx, queue, timestamp);
+            }
+        });
+    }
+}
+
+impl Drop for PollingDataSource {
+    /// Stops polling. The data source remains registered.
+    fn drop(&mut self) {
+        let thread = {
+            let mut schedule = self.shared.schedule.lock().unwrap();
+            schedule.exit = true;
+            schedule.thread.take()
+        };
+        self.shared.wakeup.notify_one();
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_decoder::{PbDecoder, PbDecoderField},
+        protos::trace::{
+            test_event::TestEvent,
+            trace_packet::{TracePacket, TracePacketFieldNumber},
+        },
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        trace_reader::TraceReader,
+    };
+    use std::{
+        error::Error,
+        sync::atomic::{AtomicU64, Ordering},
+        thread,
+    };
+
+    const DATA_SOURCE_NAME: &str = "com.example.polling_data_source";
+
+    // Generous, so that the tests don't flake on a loaded machine.
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    // Polls `cond` until it returns true or `TIMEOUT` expires. Returns the
+    // last result of `cond`.
+    fn wait_until(mut cond: impl FnMut() -> bool) -> bool {
+        let deadline = Instant::now() + TIMEOUT;
+        while !cond() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        true
+    }
+
+    // Returns true if the polling thread of `data_source` is running.
+    fn is_polling(data_source: &PollingDataSource) -> bool {
+        data_source.shared.schedule.lock().unwrap().running
+    }
+
+    #[test]
+    fn samples_until_stopped() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let samples = Arc::new((Mutex::new(0u64), Condvar::new()));
+        let sample_count = Arc::clone(&samples);
+        let started = Arc::new(AtomicU64::new(0));
+        let start_count = Arc::clone(&started);
+        let data_source = PollingDataSourceBuilder::new()
+            .data_source_args(DataSourceArgsBuilder::new().on_start(move |_, _| {
+                start_count.fetch_add(1, Ordering::Relaxed);
+            }))
+            .period_from_config(|_| Some(Duration::from_millis(1)))
+            .register(DATA_SOURCE_NAME, move |ctx: &mut TraceContext| {
+                let (count, sampled) = &*sample_count;
+                let counter = {
+                    let mut count = count.lock().unwrap();
+                    *count += 1;
+                    *count - 1
+                };
+                sampled.notify_all();
+                ctx.add_packet(|packet: &mut TracePacket| {
+                    packet.set_for_testing(|for_testing: &mut TestEvent| {
+                        for_testing.set_counter(counter);
+                    });
+                });
+            })?;
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let (count, sampled) = &*samples;
+        let (count_guard, timeout) = sampled
+            .wait_timeout_while(count.lock().unwrap(), TIMEOUT, |count| *count < 5)
+            .unwrap();
+        assert!(!timeout.timed_out(), "only {} samples", *count_guard);
+        drop(count_guard);
+        session.stop_blocking();
+        // The polling thread exits once the instance is stopped, after any
+        // sample that raced with stopping it, and nothing samples after that.
+        assert!(wait_until(|| !is_polling(&data_source)));
+        let stopped_samples = *count.lock().unwrap();
+        assert_eq!(started.load(Ordering::Relaxed), 1);
+        assert!(stopped_samples >= 5);
+
+        let data = read_trace_data(&mut session);
+        let mut packets = 0;
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            if PbDecoder::new(&packet).any(|f| {
+                matches!(f, Ok((id, PbDecoderField::Delimited(_)))
+                    if id == TracePacketFieldNumber::ForTesting as u32)
+            }) {
+                packets += 1;
+            }
+        }
+        assert!(packets >= 5 && packets <= stopped_samples);
+        Ok(())
+    }
+
+    // Returns the number of context switches of the threads named `name`, or
+    // `None` if there is no such thread, as counted by the kernel.
+    #[cfg(target_os = "linux")]
+    fn context_switches(name: &str) -> Option<u64> {
+        // Thread names are truncated to 15 bytes.
+        let name = &name[..name.len().min(15)];
+        let mut switches = None;
+        for task in std::fs::read_dir("/proc/self/task").ok()?.flatten() {
+            // The thread may exit while it is being read.
+            let Ok(comm) = std::fs::read_to_string(task.path().join("comm")) else {
+                continue;
+            };
+            let Ok(status) = std::fs::read_to_string(task.path().join("status")) else {
+                continue;
+            };
+            if comm.trim_end() != name {
+                continue;
+            }
+            let count = status
+                .lines()
+                .filter_map(|line| {
+                    line.strip_prefix("voluntary_ctxt_switches:")
+                        .or_else(|| line.strip_prefix("nonvoluntary_ctxt_switches:"))
+                })
+                .filter_map(|value| value.trim().parse::<u64>().ok())
+                .sum::<u64>();
+            *switches.get_or_insert(0) += count;
+        }
+        switches
+    }
+
+    // Only the wakeups of the polling thread are measured: the other threads
+    // of the SDK, e.g. the one that talks to the tracing service, aren't
+    // covered by this test.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn idle_without_sessions() -> Result<(), Box<dyn Error>> {
+        const IDLE_DATA_SOURCE_NAME: &str = "idle.polling";
+        const THREAD_NAME: &str = "idle.polling-poll";
+        let _lock = acquire_test_environment();
+        let data_source = PollingDataSourceBuilder::new()
+            .period_from_config(|_| Some(Duration::from_millis(1)))
+            .register(IDLE_DATA_SOURCE_NAME, |_: &mut TraceContext| {})?;
+        // Nothing runs, let alone wakes up, while there are no sessions.
+        assert!(!is_polling(&data_source));
+        assert_eq!(context_switches(THREAD_NAME), None);
+
+        for _ in 0..2 {
+            let mut session = TracingSessionBuilder::new()
+                .set_data_source_name(IDLE_DATA_SOURCE_NAME)
+                .build()?;
+            session.start_blocking();
+            let mut switches = None;
+            assert!(
+                wait_until(|| {
+                    switches = context_switches(THREAD_NAME);
+                    switches.is_some()
+                }),
+                "polling thread not running"
+            );
+            assert!(wait_until(
+                || context_switches(THREAD_NAME).unwrap_or(0) > switches.unwrap()
+            ));
+            session.stop_blocking();
+            assert!(wait_until(|| context_switches(THREAD_NAME).is_none()));
+            assert!(!is_polling(&data_source));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn instrumented_sampling() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let timer_samples = Arc::new(AtomicU64::new(0));
+        let timer_count = Arc::clone(&timer_samples);
+        let sample_points = Arc::new(Mutex::new(Vec::new()));
+        let points = Arc::clone(&sample_points);
+        let data_source = PollingDataSourceBuilder::new()
+            .period_from_config(|_| Some(Duration::from_millis(1)))
+            .instrumented_from_config(|_| true)
+            .instrumented_sampler(move |ctx: &mut TraceContext, queue: u32, timestamp: u64| {
+                points.lock().unwrap().push((queue, timestamp));
+                ctx.add_packet(|packet: &mut TracePacket| {
+                    packet.set_timestamp(timestamp);
+                });
+            })
+            .register(
+                "com.example.instrumented_data_source",
+                move |_: &mut TraceContext| {
+                    timer_count.fetch_add(1, Ordering::Relaxed);
+                },
+            )?;
+        // Not started yet.
+        data_source.sample_point(0, 1);
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name("com.example.instrumented_data_source")
+            .build()?;
+        session.start_blocking();
+        data_source.sample_point(0, 10);
+        data_source.sample_point(1, 20);
+        // Instances that use instrumented sampling don't start the polling
+        // thread.
+        assert!(!is_polling(&data_source));
+        session.stop_blocking();
+        data_source.sample_point(0, 30);
+        assert_eq!(timer_samples.load(Ordering::Relaxed), 0);
+        assert_eq!(*sample_points.lock().unwrap(), vec![(0, 10), (1, 20)]);
+        Ok(())
+    }
+}
@@ -12,9 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::platform::{self, Platform};
 use bitflags::bitflags;
 use perfetto_sdk_sys::*;
-use std::{ffi::CString, ptr, time::Duration};
+use std::{
+    ffi::CString,
+    ptr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use thiserror::Error;
 
 /// Producer errors.
@@ -28,6 +34,9 @@ pub enum ProducerError {
     InvalidTTL(std::num::TryFromIntError),
 }
 
+/// Context id of the host of a virtual machine for vsock connections.
+pub const VSOCK_CID_HOST: u32 = 2;
+
 bitflags! {
     /// Producer backend flags.
     #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -41,11 +50,17 @@ bitflags! {
 }
 
 /// Producer arguments struct.
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct ProducerInitArgs {
     backends: Backends,
     shmem_size_hint_kb: u32,
+    shmem_page_size_hint_kb: u32,
+    shmem_batch_commits_duration_ms: u32,
+    shmem_direct_patching_enabled: bool,
     machine_id: u32,
+    producer_socket_name: Option<CString>,
+    shmem_emulation_enabled: bool,
+    platform: Option<Arc<dyn Platform>>,
 }
 
 /// Producer arguments builder.
@@ -79,6 +94,35 @@ impl ProducerInitArgsBuilder {
         self
     }
 
+    /// Specifies the preferred size of each page in the shared memory buffer.
+    /// This is a trade-off between IPC overhead and fragmentation/efficiency of
+    /// the shared memory buffer in presence of multiple writer threads. Must be
+    /// one of 4, 8, 16 or 32.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn shmem_page_size_hint_kb(mut self, shmem_page_size_hint_kb: u32) -> Self {
+        self.args.shmem_page_size_hint_kb = shmem_page_size_hint_kb;
+        self
+    }
+
+    /// Sets the period during which filled shared memory buffer chunks are
+    /// batched before the service is notified of them. If the buffer gets too
+    /// full while this period lasts, the service is notified immediately.
+    /// Longer periods reduce IPC traffic but leave less buffer space to hide
+    /// the latency of the service.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn shmem_batch_commits_duration_ms(mut self, shmem_batch_commits_duration_ms: u32) -> Self {
+        self.args.shmem_batch_commits_duration_ms = shmem_batch_commits_duration_ms;
+        self
+    }
+
+    /// Enables direct producer-side patching of chunks that have not yet been
+    /// committed to the service. Ignored if the service doesn't support it.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn shmem_direct_patching_enabled(mut self, shmem_direct_patching_enabled: bool) -> Self {
+        self.args.shmem_direct_patching_enabled = shmem_direct_patching_enabled;
+        self
+    }
+
     /// Sets the machine id this process's trace data is attributed to. Only
     /// honored by the in-process backend; the system backend derives the
     /// machine id service-side and ignores this. Lets separate in-process
@@ -90,12 +134,114 @@ impl ProducerInitArgsBuilder {
         self
     }
 
+    /// Sets the name of the socket used by the system backend to connect to the
+    /// tracing service, instead of the platform default or the
+    /// `PERFETTO_PRODUCER_SOCK_NAME` environment variable. Accepts UNIX socket
+    /// paths, `@abstract` socket names, `host:port` TCP addresses and
+    /// `vsock://cid:port` addresses.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` contains a NUL byte.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn producer_socket_name(mut self, name: impl Into<String>) -> Self {
+        let name = CString::new(name.into()).expect("socket name must not contain NUL bytes");
+        self.args.producer_socket_name = Some(name);
+        self
+    }
+
+    /// Connects the system backend to a tracing service listening on vsock
+    /// `port` of the virtual machine or host with context id `cid`, e.g.
+    /// [`VSOCK_CID_HOST`] for producers running in a VM guest. Only supported
+    /// on Linux.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn vsock_producer_socket(self, cid: u32, port: u32) -> Self {
+        self.producer_socket_name(format!("vsock://{}:{}", cid, port))
+    }
+
+    /// Makes the system backend commit trace data over the producer socket
+    /// instead of mapping the shared memory buffer provided by the service,
+    /// e.g. for sandboxed processes that can't map it. The system backend also
+    /// falls back to this mode if mapping the buffer fails. Ignored by the
+    /// in-process backend.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn shmem_emulation_enabled(mut self, shmem_emulation_enabled: bool) -> Self {
+        self.args.shmem_emulation_enabled = shmem_emulation_enabled;
+        self
+    }
+
+    /// Sets the platform that provides the clock, the threads and the
+    /// connection to the tracing service of the SDK, see [`Platform`]. Only
+    /// the platform of the first initialization that sets one is installed.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn platform(mut self, platform: Arc<dyn Platform>) -> Self {
+        self.args.platform = Some(platform);
+        self
+    }
+
     /// Returns producer arguments struct.
     pub fn build(&self) -> &ProducerInitArgs {
         &self.args
     }
 }
 
+/// Initialization state of the global producer, see [`Producer::init_state`].
+///
+/// Only tracks the calls made to initialize the backends, not whether they
+/// are connected to a tracing service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitState {
+    /// No backend was initialized.
+    Uninitialized,
+    /// The producer was initialized with [`Producer::init_deferred`] and
+    /// [`DeferredProducer::connect`] wasn't called yet.
+    Deferred,
+    /// The backends were initialized. The system backend connects to the
+    /// service asynchronously, and reconnects if the service restarts, so it
+    /// may not be connected yet.
+    Initialized(Backends),
+}
+
+static INIT_STATE: Mutex<InitState> = Mutex::new(InitState::Uninitialized);
+
+/// Producer initialized with [`Producer::init_deferred`], which connects to
+/// the backends once [`connect`](Self::connect) is called.
+#[must_use = "The producer doesn't connect until `.connect()` is called."]
+pub struct DeferredProducer {
+    args: ProducerInitArgs,
+}
+
+impl DeferredProducer {
+    /// Initializes the backends of the producer, see [`Producer::init`].
+    pub fn connect(self) {
+        Producer::init(&self.args);
+    }
+}
+
+// Connects the system backend to the tracing service with the installed
+// platform.
+#[cfg(unix)]
+unsafe extern "C" fn create_socket_trampoline(
+    socket_name: *const std::os::raw::c_char,
+    _user_arg: *mut std::os::raw::c_void,
+    done_arg: *mut std::os::raw::c_void,
+) {
+    use std::os::fd::IntoRawFd;
+
+    // SAFETY: `socket_name` is a valid C string for the duration of the call.
+    let socket_name = unsafe { std::ffi::CStr::from_ptr(socket_name) }.to_string_lossy();
+    let fd = match platform::platform().connect(&socket_name) {
+        Ok(fd) => fd.into_raw_fd(),
+        Err(err) => {
+            crate::sdk_log!(Warn, "Failed to connect to {}: {}", socket_name, err);
+            -1
+        }
+    };
+    // SAFETY: `done_arg` was passed to the callback by the system backend, and
+    // ownership of `fd` is transferred to it.
+    unsafe { PerfettoProducerCreateSocketDone(done_arg, fd) };
+}
+
 /// Opaque struct to an object that stores the initialization params.
 pub struct Producer {}
 
@@ -105,6 +251,14 @@ impl Producer {
     /// It's ok to call this function multiple times, but if a backend was already
     /// initialized, most of `args` would be ignored.
     pub fn init(args: &ProducerInitArgs) {
+        #[cfg(unix)]
+        let connects_with_platform = args.platform.as_ref().is_some_and(|platform| {
+            platform::install(Arc::clone(platform)) && platform.overrides_connect()
+        });
+        #[cfg(not(unix))]
+        if let Some(platform) = &args.platform {
+            platform::install(Arc::clone(platform));
+        }
         // SAFETY: FFI call with no outstanding preconditions.
         let backend_args = unsafe { PerfettoProducerBackendInitArgsCreate() };
         // SAFETY: `backend_args` must have been created using
@@ -114,7 +268,34 @@ impl Producer {
                 backend_args,
                 args.shmem_size_hint_kb,
             );
+            PerfettoProducerBackendInitArgsSetShmemPageSizeHintKb(
+                backend_args,
+                args.shmem_page_size_hint_kb,
+            );
+            PerfettoProducerBackendInitArgsSetShmemBatchCommitsDurationMs(
+                backend_args,
+                args.shmem_batch_commits_duration_ms,
+            );
+            PerfettoProducerBackendInitArgsSetShmemDirectPatchingEnabled(
+                backend_args,
+                args.shmem_direct_patching_enabled,
+            );
             PerfettoProducerBackendInitArgsSetMachineId(backend_args, args.machine_id);
+            if let Some(name) = &args.producer_socket_name {
+                PerfettoProducerBackendInitArgsSetProducerSocketName(backend_args, name.as_ptr());
+            }
+            PerfettoProducerBackendInitArgsSetShmemEmulationEnabled(
+                backend_args,
+                args.shmem_emulation_enabled,
+            );
+            #[cfg(unix)]
+            if connects_with_platform {
+                PerfettoProducerBackendInitArgsSetCreateSocketCb(
+                    backend_args,
+                    Some(create_socket_trampoline),
+                    ptr::null_mut(),
+                );
+            }
             if args.backends.contains(Backends::IN_PROCESS) {
                 PerfettoProducerInProcessInit(backend_args);
             }
@@ -125,6 +306,48 @@ impl Producer {
         // SAFETY: `backend_args` must have been created using
         // PerfettoProducerBackendInitArgsCreate.
         unsafe { PerfettoProducerBackendInitArgsDestroy(backend_args) };
+        let mut state = INIT_STATE.lock().unwrap();
+        *state = match *state {
+            InitState::Initialized(backends) => InitState::Initialized(backends | args.backends),
+            _ => InitState::Initialized(args.backends),
+        };
+    }
+
+    /// Initializes the global perfetto producer without connecting to the
+    /// backends, e.g. to connect once the process has dropped privileges or
+    /// finished setting up its sandbox. The backends are initialized with
+    /// `args` when [`DeferredProducer::connect`] is called.
+    ///
+    /// Data sources and track event categories can be registered before the
+    /// producer connects.
+    ///
+    /// ```
+    /// use perfetto_sdk::producer::*;
+    ///
+    /// let producer = Producer::init_deferred(
+    ///     ProducerInitArgsBuilder::new()
+    ///         .backends(Backends::IN_PROCESS)
+    ///         .build(),
+    /// );
+    /// assert_eq!(Producer::init_state(), InitState::Deferred);
+    /// // ... drop privileges ...
+    /// producer.connect();
+    /// assert_eq!(
+    ///     Producer::init_state(),
+    ///     InitState::Initialized(Backends::IN_PROCESS)
+    /// );
+    /// ```
+    pub fn init_deferred(args: &ProducerInitArgs) -> DeferredProducer {
+        let mut state = INIT_STATE.lock().unwrap();
+        if *state == InitState::Uninitialized {
+            *state = InitState::Deferred;
+        }
+        DeferredProducer { args: args.clone() }
+    }
+
+    /// Returns the initialization state of the global producer.
+    pub fn init_state() -> InitState {
+        *INIT_STATE.lock().unwrap()
     }
 
     /// Informs the tracing services to activate the single trigger `trigger_name` if
@@ -152,6 +375,47 @@ mod tests {
     use crate::tests::acquire_test_environment;
     use std::error::Error;
 
+    #[test]
+    fn init_args() {
+        let builder = ProducerInitArgsBuilder::new()
+            .shmem_size_hint_kb(1024)
+            .shmem_page_size_hint_kb(16)
+            .shmem_batch_commits_duration_ms(10)
+            .shmem_direct_patching_enabled(true)
+            .shmem_emulation_enabled(true);
+        let args = builder.build();
+        assert_eq!(args.shmem_size_hint_kb, 1024);
+        assert_eq!(args.shmem_page_size_hint_kb, 16);
+        assert_eq!(args.shmem_batch_commits_duration_ms, 10);
+        assert!(args.shmem_direct_patching_enabled);
+        assert!(args.shmem_emulation_enabled);
+    }
+
+    #[test]
+    fn vsock_producer_socket() {
+        let builder = ProducerInitArgsBuilder::new().vsock_producer_socket(VSOCK_CID_HOST, 10001);
+        assert_eq!(
+            builder.build().producer_socket_name.as_deref(),
+            Some(c"vsock://2:10001")
+        );
+    }
+
+    #[test]
+    fn init_deferred() {
+        let _lock = acquire_test_environment();
+        let state = Producer::init_state();
+        assert_eq!(state, InitState::Initialized(Backends::IN_PROCESS));
+        let producer = Producer::init_deferred(
+            ProducerInitArgsBuilder::new()
+                .backends(Backends::IN_PROCESS)
+                .build(),
+        );
+        // Already initialized producers stay initialized.
+        assert_eq!(Producer::init_state(), state);
+        producer.connect();
+        assert_eq!(Producer::init_state(), state);
+    }
+
     #[test]
     fn activate_trigger() -> Result<(), Box<dyn Error>> {
         let _lock = acquire_test_environment();
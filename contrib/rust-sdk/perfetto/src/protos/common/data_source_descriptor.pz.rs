@@ -16,8 +16,14 @@
 // DataSourceDescriptor fields to limit core proto bindings.
 
 use crate::pb_msg;
+use crate::protos::common::descriptor::*;
 use crate::protos::common::track_event_descriptor::*;
 
+pb_msg!(DataSourceConfigSchema {
+    field_id: u32, primitive, 1,
+    message: DescriptorProto, msg, 2,
+});
+
 pb_msg!(DataSourceDescriptor {
     name: String, primitive, 1,
     id: u64, primitive, 7,
@@ -26,4 +32,5 @@ pb_msg!(DataSourceDescriptor {
     handles_incremental_state_clear: bool, primitive, 4,
     no_flush: bool, primitive, 9,
     track_event_descriptor: TrackEventDescriptor, msg, 6,
+    config_schema: DataSourceConfigSchema, msg, 11,
 });
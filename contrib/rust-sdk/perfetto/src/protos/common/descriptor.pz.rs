@@ -55,8 +55,8 @@ pb_msg!(EnumValueDescriptorProto {
 
 pb_msg!(EnumDescriptorProto {
     name: String, primitive, 1,
-    value: EnumValueDescriptorProto, msg, 2,
-    reserved_name: String, primitive, 5,
+    value: [msg, EnumValueDescriptorProto], repeated, 2,
+    reserved_name: [primitive, String], repeated, 5,
 });
 
 pb_msg!(OneofDescriptorProto {
@@ -78,16 +78,16 @@ pb_msg!(FieldDescriptorProto {
 
 pb_msg!(FieldOptions {
     packed: bool, primitive, 2,
-    uninterpreted_option: UninterpretedOption, msg, 999,
+    uninterpreted_option: [msg, UninterpretedOption], repeated, 999,
 });
 
 pb_msg!(UninterpretedOption {
-    name: UninterpretedOptionNamePart, msg, 2,
+    name: [msg, UninterpretedOptionNamePart], repeated, 2,
     identifier_value: String, primitive, 3,
     positive_int_value: u64, primitive, 4,
     negative_int_value: i64, primitive, 5,
     double_value: f64, primitive, 6,
-    string_value: String, primitive, 7,
+    string_value: Bytes, primitive, 7,
     aggregate_value: String, primitive, 8,
 });
 
@@ -98,13 +98,13 @@ pb_msg!(UninterpretedOptionNamePart {
 
 pb_msg!(DescriptorProto {
     name: String, primitive, 1,
-    field: FieldDescriptorProto, msg, 2,
-    extension: FieldDescriptorProto, msg, 6,
-    nested_type: DescriptorProto, msg, 3,
-    enum_type: EnumDescriptorProto, msg, 4,
-    oneof_decl: OneofDescriptorProto, msg, 8,
-    reserved_range: DescriptorProtoReservedRange, msg, 9,
-    reserved_name: String, primitive, 10,
+    field: [msg, FieldDescriptorProto], repeated, 2,
+    extension: [msg, FieldDescriptorProto], repeated, 6,
+    nested_type: [msg, DescriptorProto], repeated, 3,
+    enum_type: [msg, EnumDescriptorProto], repeated, 4,
+    oneof_decl: [msg, OneofDescriptorProto], repeated, 8,
+    reserved_range: [msg, DescriptorProtoReservedRange], repeated, 9,
+    reserved_name: [primitive, String], repeated, 10,
 });
 
 pb_msg!(DescriptorProtoReservedRange {
@@ -115,14 +115,14 @@ pb_msg!(DescriptorProtoReservedRange {
 pb_msg!(FileDescriptorProto {
     name: String, primitive, 1,
     package: String, primitive, 2,
-    dependency: String, primitive, 3,
-    public_dependency: i32, primitive, 10,
-    weak_dependency: i32, primitive, 11,
-    message_type: DescriptorProto, msg, 4,
-    enum_type: EnumDescriptorProto, msg, 5,
-    extension: FieldDescriptorProto, msg, 7,
+    dependency: [primitive, String], repeated, 3,
+    public_dependency: [primitive, i32], repeated, 10,
+    weak_dependency: [primitive, i32], repeated, 11,
+    message_type: [msg, DescriptorProto], repeated, 4,
+    enum_type: [msg, EnumDescriptorProto], repeated, 5,
+    extension: [msg, FieldDescriptorProto], repeated, 7,
 });
 
 pb_msg!(FileDescriptorSet {
-    file: FileDescriptorProto, msg, 1,
+    file: [msg, FileDescriptorProto], repeated, 1,
 });
@@ -19,11 +19,11 @@
 use crate::pb_msg;
 
 pb_msg!(TrackEventDescriptor {
-    available_categories: TrackEventCategory, msg, 1,
+    available_categories: [msg, TrackEventCategory], repeated, 1,
 });
 
 pb_msg!(TrackEventCategory {
     name: String, primitive, 1,
     description: String, primitive, 2,
-    tags: String, primitive, 3,
+    tags: [primitive, String], repeated, 3,
 });
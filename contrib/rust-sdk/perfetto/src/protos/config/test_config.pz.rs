@@ -41,5 +41,5 @@ pb_msg!(TestConfigDummyFields {
     field_sint64: i64, primitive, 11,
     field_sint32: i32, primitive, 12,
     field_string: String, primitive, 13,
-    field_bytes: String, primitive, 14,
+    field_bytes: Bytes, primitive, 14,
 });
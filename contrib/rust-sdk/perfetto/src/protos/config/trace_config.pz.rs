@@ -82,14 +82,14 @@ pb_enum!(BufferConfigExperimentalMode {
 });
 
 pb_msg!(TraceConfig {
-    buffers: TraceConfigBufferConfig, msg, 1,
-    data_sources: TraceConfigDataSource, msg, 2,
+    buffers: [msg, TraceConfigBufferConfig], repeated, 1,
+    data_sources: [msg, TraceConfigDataSource], repeated, 2,
     builtin_data_sources: TraceConfigBuiltinDataSource, msg, 20,
     duration_ms: u32, primitive, 3,
     prefer_suspend_clock_for_duration: bool, primitive, 36,
     enable_extra_guardrails: bool, primitive, 4,
     lockdown_mode: TraceConfigLockdownModeOperation, enum, 5,
-    producers: TraceConfigProducerConfig, msg, 6,
+    producers: [msg, TraceConfigProducerConfig], repeated, 6,
     statsd_metadata: TraceConfigStatsdMetadata, msg, 7,
     write_into_file: bool, primitive, 8,
     output_path: String, primitive, 29,
@@ -104,7 +104,7 @@ pb_msg!(TraceConfig {
     bugreport_score: i32, primitive, 30,
     bugreport_filename: String, primitive, 38,
     trigger_config: TraceConfigTriggerConfig, msg, 17,
-    activate_triggers: String, primitive, 18,
+    activate_triggers: [primitive, String], repeated, 18,
     incremental_state_config: TraceConfigIncrementalStateConfig, msg, 21,
     allow_user_build_tracing: bool, primitive, 19,
     unique_session_name: String, primitive, 22,
@@ -116,7 +116,7 @@ pb_msg!(TraceConfig {
     trace_filter: TraceConfigTraceFilter, msg, 33,
     android_report_config: TraceConfigAndroidReportConfig, msg, 34,
     cmd_trace_start_delay: TraceConfigCmdTraceStartDelay, msg, 35,
-    session_semaphores: TraceConfigSessionSemaphore, msg, 39,
+    session_semaphores: [msg, TraceConfigSessionSemaphore], repeated, 39,
     priority_boost: PriorityBoostConfig, msg, 40,
     exclusive_prio: u32, primitive, 41,
     write_flush_mode: TraceConfigWriteFlushMode, enum, 44,
@@ -148,15 +148,15 @@ pb_msg!(TraceConfigAndroidReportConfig {
 });
 
 pb_msg!(TraceConfigTraceFilter {
-    bytecode: String, primitive, 1,
-    bytecode_v2: String, primitive, 2,
+    bytecode: Bytes, primitive, 1,
+    bytecode_v2: Bytes, primitive, 2,
     string_filter_chain: TraceConfigTraceFilterStringFilterChain, msg, 3,
-    bytecode_overlay_v54: String, primitive, 4,
+    bytecode_overlay_v54: Bytes, primitive, 4,
     string_filter_chain_v54: TraceConfigTraceFilterStringFilterChain, msg, 5,
 });
 
 pb_msg!(TraceConfigTraceFilterStringFilterChain {
-    rules: TraceConfigTraceFilterStringFilterRule, msg, 1,
+    rules: [msg, TraceConfigTraceFilterStringFilterRule], repeated, 1,
 });
 
 pb_msg!(TraceConfigTraceFilterStringFilterRule {
@@ -164,7 +164,7 @@ pb_msg!(TraceConfigTraceFilterStringFilterRule {
     regex_pattern: String, primitive, 2,
     atrace_payload_starts_with: String, primitive, 3,
     name: String, primitive, 4,
-    semantic_type: SemanticType, enum, 5,
+    semantic_type: [enum, SemanticType], repeated, 5,
 });
 
 pb_msg!(TraceConfigIncidentReportConfig {
@@ -182,7 +182,7 @@ pb_msg!(TraceConfigIncrementalStateConfig {
 pb_msg!(TraceConfigTriggerConfig {
     trigger_mode: TriggerConfigTriggerMode, enum, 1,
     use_clone_snapshot_if_available: bool, primitive, 5,
-    triggers: TraceConfigTriggerConfigTrigger, msg, 2,
+    triggers: [msg, TraceConfigTriggerConfigTrigger], repeated, 2,
     trigger_timeout_ms: u32, primitive, 3,
 });
 
@@ -226,9 +226,9 @@ pb_msg!(TraceConfigBuiltinDataSource {
 
 pb_msg!(TraceConfigDataSource {
     config: DataSourceConfig, msg, 1,
-    producer_name_filter: String, primitive, 2,
-    producer_name_regex_filter: String, primitive, 3,
-    machine_name_filter: String, primitive, 4,
+    producer_name_filter: [primitive, String], repeated, 2,
+    producer_name_regex_filter: [primitive, String], repeated, 3,
+    machine_name_filter: [primitive, String], repeated, 4,
 });
 
 pb_msg!(TraceConfigBufferConfig {
@@ -19,10 +19,10 @@
 use crate::pb_msg;
 
 pb_msg!(TrackEventConfig {
-    disabled_categories: String, primitive, 1,
-    enabled_categories: String, primitive, 2,
-    disabled_tags: String, primitive, 3,
-    enabled_tags: String, primitive, 4,
+    disabled_categories: [primitive, String], repeated, 1,
+    enabled_categories: [primitive, String], repeated, 2,
+    disabled_tags: [primitive, String], repeated, 3,
+    enabled_tags: [primitive, String], repeated, 4,
     disable_incremental_timestamps: bool, primitive, 5,
     timestamp_unit_multiplier: u64, primitive, 6,
     filter_debug_annotations: bool, primitive, 7,
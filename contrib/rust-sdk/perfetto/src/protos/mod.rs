@@ -22,7 +22,45 @@ pub mod config;
 #[allow(clippy::module_inception)]
 pub mod trace;
 
+/// Value of a protobuf enum field, which can be a value that isn't known to
+/// the enum, e.g. when decoding a trace written with newer protos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PbEnumValue<E> {
+    /// Value of the enum.
+    Known(E),
+    /// Value that isn't known to the enum.
+    Unknown(i32),
+}
+
+impl<E> PbEnumValue<E> {
+    /// Returns the value of the enum, if known.
+    pub fn known(self) -> Option<E> {
+        match self {
+            PbEnumValue::Known(value) => Some(value),
+            PbEnumValue::Unknown(_) => None,
+        }
+    }
+}
+
+impl<E: TryFrom<i32>> From<i32> for PbEnumValue<E> {
+    fn from(v: i32) -> Self {
+        E::try_from(v).map_or(PbEnumValue::Unknown(v), PbEnumValue::Known)
+    }
+}
+
+impl<E: Into<i32>> From<PbEnumValue<E>> for i32 {
+    fn from(v: PbEnumValue<E>) -> i32 {
+        match v {
+            PbEnumValue::Known(v) => v.into(),
+            PbEnumValue::Unknown(v) => v,
+        }
+    }
+}
+
 /// Defines a protobuf enum.
+///
+/// The enum converts to and from `u32` and `i32`, and to and from
+/// [`PbEnumValue`], which preserves unknown values.
 #[macro_export]
 macro_rules! pb_enum {
     (
@@ -58,6 +96,34 @@ macro_rules! pb_enum {
                     }
                 }
             }
+
+            impl From<$name> for i32 {
+                #[inline]
+                fn from(v: $name) -> i32 { v as i32 }
+            }
+
+            impl TryFrom<i32> for $name {
+                type Error = ();
+                fn try_from(v: i32) -> Result<Self, Self::Error> {
+                    u32::try_from(v).map_err(|_| ())?.try_into()
+                }
+            }
+
+            impl From<$name> for $crate::protos::PbEnumValue<$name> {
+                #[inline]
+                fn from(v: $name) -> Self { Self::Known(v) }
+            }
+
+            impl $crate::pb_descriptor::PbEnum for $name {
+                fn descriptor() -> &'static $crate::pb_descriptor::EnumDescriptor {
+                    static DESCRIPTOR: $crate::pb_descriptor::EnumDescriptor =
+                        $crate::pb_descriptor::EnumDescriptor {
+                            name: stringify!($name),
+                            values: &[ $( (stringify!($entry), $id) ),* ],
+                        };
+                    &DESCRIPTOR
+                }
+            }
         }
     };
 }
@@ -65,6 +131,31 @@ macro_rules! pb_enum {
 /// Defines a protobuf message.
 ///
 /// Defines the type for a protobuf message. `name` is the name of the message type.
+/// The type implements [`PbMessage`](crate::pb_descriptor::PbMessage), which
+/// describes its fields at runtime.
+///
+/// The fields can be followed by `oneof name { field, ... }` clauses, which
+/// define a `FooNameCase` enum with the fields of the oneof, and a
+/// `get_name_case()` method returning the field of the oneof that has been
+/// set last. Setting a field of a oneof doesn't clear the other fields.
+///
+/// Map fields use the `map` kind, with the generated entry message type, the
+/// key type, and the kind and type of the values, e.g.
+/// `counts: [FooCountsEntry, String, primitive, u64], map, 1`. Their setter
+/// adds one entry.
+///
+/// Repeated fields use the `repeated` kind, with the kind and type of their
+/// elements, e.g. `names: [primitive, String], repeated, 1`. Their setter adds
+/// one element, and an `add_` setter adds the elements of an iterator, with a
+/// callback writing each element for messages. Repeated numeric fields
+/// declared with `[packed = true]` use the `packed` kind instead, with a
+/// setter taking a slice of values, which are written as a single field.
+///
+/// `string` fields use the `String` type and `bytes` fields the `Bytes` type.
+/// Their setters take `impl AsRef<str>` and `impl AsRef<[u8]>` values. String
+/// setters truncate the value to
+/// [`max_string_len`](crate::pb_msg::max_string_len), and are paired with a
+/// `try_set_` setter which rejects values with NUL characters.
 #[macro_export]
 macro_rules! pb_msg {
     // Empty message (no fields)
@@ -80,19 +171,34 @@ macro_rules! pb_msg {
                 pub msg: &'a mut $crate::pb_msg::PbMsg<'b>,
             }
         }
+
+        impl $crate::pb_descriptor::PbMessage for $name<'_, '_> {
+            fn descriptor() -> &'static $crate::pb_descriptor::MessageDescriptor {
+                static DESCRIPTOR: $crate::pb_descriptor::MessageDescriptor =
+                    $crate::pb_descriptor::MessageDescriptor {
+                        name: stringify!($name),
+                        fields: &[],
+                    };
+                &DESCRIPTOR
+            }
+        }
     };
 
-    // Message with fields
+    // Message with fields. Fields can be preceded by `#[cfg(...)]` attributes,
+    // e.g. to only generate fields of nested messages behind a cargo feature.
+    // The fields can be followed by the oneofs of the message.
     (
         $name:ident {
-            $( $field:ident : $tp:tt, $kind:ident, $id:literal ),+ $(,)?
+            $( $(#[$attr:meta])* $field:ident : $tp:tt, $kind:ident, $id:literal ),+ $(,)?
         }
+        $( oneof $oneof:ident { $( $(#[$mattr:meta])* $member:ident ),+ $(,)? } )*
     ) => {
         paste::paste! {
             #[doc = concat!("Protobuf field numbers for `", stringify!($name), "`")]
             #[repr(u32)]
             pub enum [<$name:camel FieldNumber>] {
                 $(
+                    $(#[$attr])*
                     #[doc = concat!("Field number for `", stringify!($field), "`")]
                     [<$field:camel>] = $id
                 ),*
@@ -108,33 +214,275 @@ macro_rules! pb_msg {
             }
         }
 
+        impl $crate::pb_descriptor::PbMessage for $name<'_, '_> {
+            fn descriptor() -> &'static $crate::pb_descriptor::MessageDescriptor {
+                static DESCRIPTOR: $crate::pb_descriptor::MessageDescriptor =
+                    $crate::pb_descriptor::MessageDescriptor {
+                        name: stringify!($name),
+                        fields: &[
+                            $(
+                                $(#[$attr])*
+                                $crate::pb_descriptor::FieldDescriptor {
+                                    name: stringify!($field),
+                                    number: $id,
+                                    field_type: pb_msg!(@field_type $kind, $tp),
+                                }
+                            ),+
+                        ],
+                    };
+                &DESCRIPTOR
+            }
+        }
+
         impl<'a, 'b> $name<'a, 'b> {
             $(
+                $(#[$attr])*
                 pb_msg!(@setter pub fn $name, $field, $id, $kind, $tp);
+                $(#[$attr])*
+                pb_msg!(@has pub fn $field, $id);
+                $(#[$attr])*
+                pb_msg!(@getter pub fn $field, $id, $kind, $tp);
             )*
         }
+
+        $(
+            pb_msg!(@oneof $name, $oneof { $( $(#[$mattr])* $member ),+ });
+        )*
+    };
+
+    // Oneof. Setting a field of a oneof doesn't clear the other fields that
+    // have already been written. Like for any protobuf message, decoders use
+    // the field that comes last.
+    (@oneof $name:ident, $oneof:ident { $( $(#[$mattr:meta])* $member:ident ),+ }) => {
+        paste::paste! {
+            #[doc = concat!("Fields of oneof `", stringify!($oneof), "` of `", stringify!($name), "`")]
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            pub enum [<$name $oneof:camel Case>] {
+                $(
+                    $(#[$mattr])*
+                    #[doc = concat!("Variant for `", stringify!($member), "`")]
+                    [<$member:camel>]
+                ),+
+            }
+
+            impl [<$name $oneof:camel Case>] {
+                /// Field numbers of the fields of the oneof.
+                pub const FIELDS: &'static [u32] = &[
+                    $(
+                        $(#[$mattr])*
+                        [<$name:camel FieldNumber>]::[<$member:camel>] as u32
+                    ),+
+                ];
+
+                /// Returns the field of the oneof that is set in the encoded
+                /// `message`, i.e. the one that comes last, with its value.
+                pub fn decode(
+                    message: &[u8],
+                ) -> Result<
+                    Option<(Self, $crate::pb_decoder::PbDecoderField<'_>)>,
+                    $crate::pb_decoder::PbDecoderError,
+                > {
+                    let mut case = None;
+                    for item in $crate::pb_decoder::PbDecoder::new(message) {
+                        let (id, field) = item?;
+                        if let Ok(value) = Self::try_from(id) {
+                            case = Some((value, field));
+                        }
+                    }
+                    Ok(case)
+                }
+            }
+
+            impl From<[<$name $oneof:camel Case>]> for u32 {
+                fn from(v: [<$name $oneof:camel Case>]) -> u32 {
+                    match v {
+                        $(
+                            $(#[$mattr])*
+                            [<$name $oneof:camel Case>]::[<$member:camel>] =>
+                                [<$name:camel FieldNumber>]::[<$member:camel>] as u32,
+                        )+
+                    }
+                }
+            }
+
+            impl TryFrom<u32> for [<$name $oneof:camel Case>] {
+                type Error = ();
+                fn try_from(v: u32) -> Result<Self, Self::Error> {
+                    $(
+                        $(#[$mattr])*
+                        if v == [<$name:camel FieldNumber>]::[<$member:camel>] as u32 {
+                            return Ok([<$name $oneof:camel Case>]::[<$member:camel>]);
+                        }
+                    )+
+                    Err(())
+                }
+            }
+
+            impl<'a, 'b> $name<'a, 'b> {
+                #[doc = concat!("Returns the field of oneof `", stringify!($oneof), "` that has been set last")]
+                pub fn [<get_ $oneof _case>] (&self) -> Option<[<$name $oneof:camel Case>]> {
+                    self.msg
+                        .last_field_of([<$name $oneof:camel Case>]::FIELDS)
+                        .and_then(|id| id.try_into().ok())
+                }
+            }
+        }
+    };
+
+    // Descriptor field types.
+    (@field_type primitive, bool) => { $crate::pb_descriptor::FieldType::Bool };
+    (@field_type primitive, i32) => { $crate::pb_descriptor::FieldType::I32 };
+    (@field_type primitive, i64) => { $crate::pb_descriptor::FieldType::I64 };
+    (@field_type primitive, u32) => { $crate::pb_descriptor::FieldType::U32 };
+    (@field_type primitive, u64) => { $crate::pb_descriptor::FieldType::U64 };
+    (@field_type primitive, f32) => { $crate::pb_descriptor::FieldType::F32 };
+    (@field_type primitive, f64) => { $crate::pb_descriptor::FieldType::F64 };
+    (@field_type primitive, String) => { $crate::pb_descriptor::FieldType::String };
+    (@field_type primitive, Bytes) => { $crate::pb_descriptor::FieldType::Bytes };
+    (@field_type enum, $tp:tt) => {
+        $crate::pb_descriptor::FieldType::Enum(
+            <$tp as $crate::pb_descriptor::PbEnum>::descriptor,
+        )
+    };
+    (@field_type msg, $tp:tt) => {
+        $crate::pb_descriptor::FieldType::Message(
+            <$tp<'static, 'static> as $crate::pb_descriptor::PbMessage>::descriptor,
+        )
+    };
+    (@field_type repeated, [$ekind:ident, $etp:tt]) => { pb_msg!(@field_type $ekind, $etp) };
+    (@field_type packed, [$ekind:ident, $etp:tt]) => { pb_msg!(@field_type $ekind, $etp) };
+    (@field_type map, [$entry:ident, $ktp:ident, $vkind:ident, $vtp:ident]) => {
+        $crate::pb_descriptor::FieldType::Map(
+            <$entry<'static, 'static> as $crate::pb_descriptor::PbMessage>::descriptor,
+        )
+    };
+
+    // Presence check, for all fields.
+    (@has_decl $vis:vis fn $field:ident, $id:literal) => {
+        paste::paste! {
+            #[doc = concat!("Returns true if `", stringify!($field), "` field has been set")]
+            $vis fn [<has_ $field>] (&self) -> bool;
+        }
+    };
+    (@has $vis:vis fn $field:ident, $id:literal) => {
+        paste::paste! {
+            #[doc = concat!("Returns true if `", stringify!($field), "` field has been set")]
+            $vis fn [<has_ $field>] (&self) -> bool {
+                self.msg.has_field($id)
+            }
+        }
+    };
+
+    // Getters, for scalar fields. Strings, bytes and messages are written out
+    // directly and can't be read back.
+    (@getter_decl $vis:vis fn $field:ident, $id:literal, primitive, String) => {};
+    (@getter $vis:vis fn $field:ident, $id:literal, primitive, String) => {};
+    (@getter_decl $vis:vis fn $field:ident, $id:literal, primitive, Bytes) => {};
+    (@getter $vis:vis fn $field:ident, $id:literal, primitive, Bytes) => {};
+    (@getter_decl $vis:vis fn $field:ident, $id:literal, msg, $tp:tt) => {};
+    (@getter $vis:vis fn $field:ident, $id:literal, msg, $tp:tt) => {};
+    (@getter_decl $vis:vis fn $field:ident, $id:literal, map, $tp:tt) => {};
+    (@getter $vis:vis fn $field:ident, $id:literal, map, $tp:tt) => {};
+    (@getter_decl $vis:vis fn $field:ident, $id:literal, packed, $tp:tt) => {};
+    (@getter $vis:vis fn $field:ident, $id:literal, packed, $tp:tt) => {};
+    (@getter_decl $vis:vis fn $field:ident, $id:literal, repeated, [$ekind:ident, $etp:tt]) => {
+        pb_msg!(@getter_decl $vis fn $field, $id, $ekind, $etp);
+    };
+    (@getter $vis:vis fn $field:ident, $id:literal, repeated, [$ekind:ident, $etp:tt]) => {
+        pb_msg!(@getter $vis fn $field, $id, $ekind, $etp);
+    };
+    (@getter_decl $vis:vis fn $field:ident, $id:literal, enum, $tp:tt) => {
+        paste::paste! {
+            #[doc = concat!("Returns the last value set for the `", stringify!($field), "` field")]
+            $vis fn [<get_ $field>] (&self) -> Option<$crate::protos::PbEnumValue<$tp>>;
+        }
+    };
+    (@getter_decl $vis:vis fn $field:ident, $id:literal, $kind:ident, $tp:tt) => {
+        paste::paste! {
+            #[doc = concat!("Returns the last value set for the `", stringify!($field), "` field")]
+            $vis fn [<get_ $field>] (&self) -> Option<$tp>;
+        }
+    };
+    (@getter $vis:vis fn $field:ident, $id:literal, primitive, bool) => {
+        pb_msg!(@value_getter $vis fn $field, $id, bool, |v| v != 0);
+    };
+    (@getter $vis:vis fn $field:ident, $id:literal, primitive, f32) => {
+        pb_msg!(@value_getter $vis fn $field, $id, f32, |v| f32::from_bits(v as u32));
+    };
+    (@getter $vis:vis fn $field:ident, $id:literal, primitive, f64) => {
+        pb_msg!(@value_getter $vis fn $field, $id, f64, |v| f64::from_bits(v));
+    };
+    (@getter $vis:vis fn $field:ident, $id:literal, primitive, $tp:tt) => {
+        pb_msg!(@value_getter $vis fn $field, $id, $tp, |v| v as $tp);
+    };
+    (@getter $vis:vis fn $field:ident, $id:literal, enum, $tp:tt) => {
+        paste::paste! {
+            #[doc = concat!("Returns the last value set for the `", stringify!($field), "` field")]
+            $vis fn [<get_ $field>] (&self) -> Option<$crate::protos::PbEnumValue<$tp>> {
+                self.msg.field_value($id).map(|v| (v as i32).into())
+            }
+        }
+    };
+    (@value_getter $vis:vis fn $field:ident, $id:literal, $tp:tt, |$v:ident| $conv:expr) => {
+        paste::paste! {
+            #[doc = concat!("Returns the last value set for the `", stringify!($field), "` field")]
+            $vis fn [<get_ $field>] (&self) -> Option<$tp> {
+                self.msg.field_value($id).map(|$v| $conv)
+            }
+        }
     };
 
-    // Cstr
+    // String. The setter truncates the value to the maximum string length,
+    // see `pb_msg::set_max_string_len`, and the fallible setter also rejects
+    // values with NUL characters.
     (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, primitive, String) => {
         paste::paste! {
             #[doc = concat!("Set `", stringify!($field), "` field")]
-            $vis fn [<set_ $field>] (&mut self, value: impl Into<String>) -> &mut Self;
+            $vis fn [<set_ $field>] (&mut self, value: impl AsRef<str>) -> &mut Self;
+            #[doc = concat!("Set `", stringify!($field), "` field, unless the value contains a NUL character")]
+            $vis fn [<try_set_ $field>] (
+                &mut self,
+                value: impl AsRef<str>,
+            ) -> Result<&mut Self, $crate::pb_msg::PbMsgError>;
         }
     };
     (@setter $vis:vis fn $name:ident, $field:ident, $id: literal, primitive, String) => {
         paste::paste! {
             #[doc = concat!("Set `", stringify!($field), "` field")]
-            $vis fn [<set_ $field>] (&mut self, value: impl Into<String>) -> &mut Self {
-                let s: String = value.into();
-                self.msg.append_type2_field($id, s.as_bytes());
+            $vis fn [<set_ $field>] (&mut self, value: impl AsRef<str>) -> &mut Self {
+                self.msg.append_str_field($id, value.as_ref());
+                self
+            }
+            #[doc = concat!("Set `", stringify!($field), "` field, unless the value contains a NUL character")]
+            $vis fn [<try_set_ $field>] (
+                &mut self,
+                value: impl AsRef<str>,
+            ) -> Result<&mut Self, $crate::pb_msg::PbMsgError> {
+                self.msg.try_append_str_field($id, value.as_ref())?;
+                Ok(self)
+            }
+        }
+    };
+
+    // Bytes
+    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, primitive, Bytes) => {
+        paste::paste! {
+            #[doc = concat!("Set `", stringify!($field), "` field")]
+            $vis fn [<set_ $field>] (&mut self, value: impl AsRef<[u8]>) -> &mut Self;
+        }
+    };
+    (@setter $vis:vis fn $name:ident, $field:ident, $id: literal, primitive, Bytes) => {
+        paste::paste! {
+            #[doc = concat!("Set `", stringify!($field), "` field")]
+            $vis fn [<set_ $field>] (&mut self, value: impl AsRef<[u8]>) -> &mut Self {
+                self.msg.append_type2_field($id, value.as_ref());
                 self
             }
         }
     };
 
     // float
-    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, primitive, String) => {
+    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, primitive, f32) => {
         paste::paste! {
             #[doc = concat!("Set `", stringify!($field), "` field")]
             $vis fn [<set_ $field>] (&mut self, value: f32) -> &mut Self;
@@ -151,7 +499,7 @@ macro_rules! pb_msg {
     };
 
     // double
-    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, primitive, String) => {
+    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, primitive, f64) => {
         paste::paste! {
             #[doc = concat!("Set `", stringify!($field), "` field")]
             $vis fn [<set_ $field>] (&mut self, value: f64) -> &mut Self;
@@ -219,19 +567,224 @@ macro_rules! pb_msg {
     (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, enum, $tp:tt) => {
         paste::paste! {
             #[doc = concat!("Set `", stringify!($field), "` field")]
-            $vis fn [<set_ $field>] (&mut self, value: $tp) -> &mut Self;
+            $vis fn [<set_ $field>] (
+                &mut self,
+                value: impl Into<$crate::protos::PbEnumValue<$tp>>,
+            ) -> &mut Self;
         }
     };
     (@setter $vis:vis fn $name:ident, $field:ident, $id: literal, enum, $tp:tt) => {
         paste::paste! {
             #[doc = concat!("Set `", stringify!($field), "` field")]
-            $vis fn [<set_ $field>] (&mut self, value: $tp) -> &mut Self {
-                self.msg.append_type0_field($id, value as u64);
+            $vis fn [<set_ $field>] (
+                &mut self,
+                value: impl Into<$crate::protos::PbEnumValue<$tp>>,
+            ) -> &mut Self {
+                // Negative values are sign extended, like for int32 fields.
+                let value: i32 = value.into().into();
+                self.msg.append_type0_field($id, value as i64 as u64);
+                self
+            }
+        }
+    };
+
+    // Map, with the entry message type, and the type of the keys and the kind
+    // and type of the values, e.g. `[FooBarEntry, u64, primitive, String]`.
+    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, map,
+        [$entry:ident, $ktp:ident, msg, $vtp:ident]) => {
+        paste::paste! {
+            #[doc = concat!("Add entry to `", stringify!($field), "` map field")]
+            $vis fn [<set_ $field>] <F>(&mut self, key: pb_msg!(@map_arg $ktp), cb: F) -> &mut Self
+            where
+                F: for<'p> Fn(&'p mut $vtp);
+        }
+    };
+    (@setter $vis:vis fn $name:ident, $field:ident, $id: literal, map,
+        [$entry:ident, $ktp:ident, msg, $vtp:ident]) => {
+        paste::paste! {
+            #[doc = concat!("Add entry to `", stringify!($field), "` map field")]
+            $vis fn [<set_ $field>] <F>(&mut self, key: pb_msg!(@map_arg $ktp), cb: F) -> &mut Self
+            where
+                F: for<'p> Fn(&'p mut $vtp),
+            {
+                self.msg.append_nested($id, |nested_msg| {
+                    let mut entry: $entry<'_, '_> = $entry { msg: nested_msg };
+                    entry.set_key(key).set_value(cb);
+                });
+                self
+            }
+        }
+    };
+    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, map,
+        [$entry:ident, $ktp:ident, $vkind:ident, $vtp:ident]) => {
+        paste::paste! {
+            #[doc = concat!("Add entry to `", stringify!($field), "` map field")]
+            $vis fn [<set_ $field>] (
+                &mut self,
+                key: pb_msg!(@map_arg $ktp),
+                value: pb_msg!(@map_arg $vtp),
+            ) -> &mut Self;
+        }
+    };
+    (@setter $vis:vis fn $name:ident, $field:ident, $id: literal, map,
+        [$entry:ident, $ktp:ident, $vkind:ident, $vtp:ident]) => {
+        paste::paste! {
+            #[doc = concat!("Add entry to `", stringify!($field), "` map field")]
+            $vis fn [<set_ $field>] (
+                &mut self,
+                key: pb_msg!(@map_arg $ktp),
+                value: pb_msg!(@map_arg $vtp),
+            ) -> &mut Self {
+                self.msg.append_nested($id, |nested_msg| {
+                    let mut entry: $entry<'_, '_> = $entry { msg: nested_msg };
+                    entry.set_key(key).set_value(value);
+                });
                 self
             }
         }
     };
 
+    // Repeated, with the kind and type of the elements, e.g.
+    // `[primitive, String]`. The setter of the elements is paired with an
+    // `add_` setter, which sets an element for each item of an iterator.
+    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, repeated, [msg, $etp:tt]) => {
+        pb_msg!(@decl $vis fn $name, $field, $id, msg, $etp);
+        paste::paste! {
+            #[doc = concat!("Add an element to `", stringify!($field), "` field for each item, written by `cb`")]
+            $vis fn [<add_ $field _from>] <I, F>(&mut self, items: I, cb: F) -> &mut Self
+            where
+                I: IntoIterator,
+                F: FnMut(&mut $etp, I::Item);
+        }
+    };
+    (@setter $vis:vis fn $name:ident, $field:ident, $id: literal, repeated, [msg, $etp:tt]) => {
+        pb_msg!(@setter $vis fn $name, $field, $id, msg, $etp);
+        paste::paste! {
+            #[doc = concat!("Add an element to `", stringify!($field), "` field for each item, written by `cb`")]
+            $vis fn [<add_ $field _from>] <I, F>(&mut self, items: I, mut cb: F) -> &mut Self
+            where
+                I: IntoIterator,
+                F: FnMut(&mut $etp, I::Item),
+            {
+                for item in items {
+                    self.msg.append_nested($id, |nested_msg| {
+                        let mut msg_field: $etp<'_, '_> = $etp {
+                            msg: nested_msg,
+                        };
+                        cb(&mut msg_field, item);
+                    });
+                }
+                self
+            }
+        }
+    };
+    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, repeated, [primitive, String]) => {
+        pb_msg!(@decl $vis fn $name, $field, $id, primitive, String);
+        pb_msg!(@add_from_decl $vis fn $field, [AsRef<str>]);
+    };
+    (@setter $vis:vis fn $name:ident, $field:ident, $id: literal, repeated, [primitive, String]) => {
+        pb_msg!(@setter $vis fn $name, $field, $id, primitive, String);
+        pb_msg!(@add_from $vis fn $field, [AsRef<str>], std::convert::identity);
+    };
+    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, repeated, [primitive, Bytes]) => {
+        pb_msg!(@decl $vis fn $name, $field, $id, primitive, Bytes);
+        pb_msg!(@add_from_decl $vis fn $field, [AsRef<[u8]>]);
+    };
+    (@setter $vis:vis fn $name:ident, $field:ident, $id: literal, repeated, [primitive, Bytes]) => {
+        pb_msg!(@setter $vis fn $name, $field, $id, primitive, Bytes);
+        pb_msg!(@add_from $vis fn $field, [AsRef<[u8]>], std::convert::identity);
+    };
+    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, repeated, [enum, $etp:tt]) => {
+        pb_msg!(@decl $vis fn $name, $field, $id, enum, $etp);
+        pb_msg!(@add_from_decl $vis fn $field, [Into<$crate::protos::PbEnumValue<$etp>>]);
+    };
+    (@setter $vis:vis fn $name:ident, $field:ident, $id: literal, repeated, [enum, $etp:tt]) => {
+        pb_msg!(@setter $vis fn $name, $field, $id, enum, $etp);
+        pb_msg!(
+            @add_from $vis fn $field,
+            [Into<$crate::protos::PbEnumValue<$etp>>],
+            std::convert::identity
+        );
+    };
+    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, repeated, [primitive, $etp:tt]) => {
+        pb_msg!(@decl $vis fn $name, $field, $id, primitive, $etp);
+        pb_msg!(@add_from_decl $vis fn $field, [Into<$etp>]);
+    };
+    (@setter $vis:vis fn $name:ident, $field:ident, $id: literal, repeated, [primitive, $etp:tt]) => {
+        pb_msg!(@setter $vis fn $name, $field, $id, primitive, $etp);
+        pb_msg!(@add_from $vis fn $field, [Into<$etp>], Into::into);
+    };
+
+    (@add_from_decl $vis:vis fn $field:ident, [$($bound:tt)+]) => {
+        paste::paste! {
+            #[doc = concat!("Add an element to `", stringify!($field), "` field for each item")]
+            $vis fn [<add_ $field _from>] <I>(&mut self, items: I) -> &mut Self
+            where
+                I: IntoIterator,
+                I::Item: $($bound)+;
+        }
+    };
+    (@add_from $vis:vis fn $field:ident, [$($bound:tt)+], $conv:path) => {
+        paste::paste! {
+            #[doc = concat!("Add an element to `", stringify!($field), "` field for each item")]
+            $vis fn [<add_ $field _from>] <I>(&mut self, items: I) -> &mut Self
+            where
+                I: IntoIterator,
+                I::Item: $($bound)+,
+            {
+                for item in items {
+                    self.[<set_ $field>]($conv(item));
+                }
+                self
+            }
+        }
+    };
+
+    // Packed, with the kind and type of the elements, e.g. `[primitive, u64]`.
+    // All the values of a call are written as a single field.
+    (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, packed, [$ekind:ident, $etp:tt]) => {
+        paste::paste! {
+            #[doc = concat!("Set the values of packed `", stringify!($field), "` field")]
+            $vis fn [<set_ $field>] (&mut self, values: &[$etp]) -> &mut Self;
+        }
+    };
+    (@setter $vis:vis fn $name:ident, $field:ident, $id: literal, packed, [$ekind:ident, $etp:tt]) => {
+        paste::paste! {
+            #[doc = concat!("Set the values of packed `", stringify!($field), "` field")]
+            $vis fn [<set_ $field>] (&mut self, values: &[$etp]) -> &mut Self {
+                pb_msg!(@packed self, $id, values, $ekind, $etp);
+                self
+            }
+        }
+    };
+
+    (@packed $self:ident, $id:literal, $values:ident, primitive, f32) => {
+        $self.msg.append_packed_fixed32_field(
+            $id,
+            $values.iter().map(|v| $crate::pb_utils::pb_float_to_fixed32(*v)),
+        )
+    };
+    (@packed $self:ident, $id:literal, $values:ident, primitive, f64) => {
+        $self.msg.append_packed_fixed64_field(
+            $id,
+            $values.iter().map(|v| $crate::pb_utils::pb_double_to_fixed64(*v)),
+        )
+    };
+    (@packed $self:ident, $id:literal, $values:ident, enum, $tp:tt) => {
+        // Negative values are sign extended, like for int32 fields.
+        $self.msg.append_packed_varint_field(
+            $id,
+            $values.iter().map(|v| i32::from(*v) as i64 as u64),
+        )
+    };
+    (@packed $self:ident, $id:literal, $values:ident, primitive, $tp:tt) => {
+        $self.msg.append_packed_varint_field($id, $values.iter().map(|v| *v as u64))
+    };
+
+    (@map_arg String) => { impl AsRef<str> };
+    (@map_arg Bytes) => { impl AsRef<[u8]> };
+    (@map_arg $tp:ident) => { $tp };
+
     // Fallback to message
     (@decl $vis:vis fn $name:ident, $field:ident, $id: literal, msg, $tp:tt) => {
         paste::paste! {
@@ -261,11 +814,48 @@ macro_rules! pb_msg {
 }
 
 /// Defines extra fields for a protobuf message.
+///
+/// This is how crates other than this one extend messages of the SDK, e.g.
+/// `TracePacket`, with fields of their own protos. For a message `Foo`, the
+/// macro defines:
+///
+/// - a `FooExt` trait, implemented for `Foo`, with a setter, a presence check
+///   and, for scalar fields, a getter for each field, and
+/// - a `FooExtFieldNumber` enum with the field numbers, which implements
+///   [`PbMsgExtension`](crate::pb_ext::PbMsgExtension) so that the fields can
+///   be registered for decoding with an
+///   [`ExtensionRegistry`](crate::pb_ext::ExtensionRegistry).
+///
+/// The macro must be invoked in a module where `Foo`, the types of the fields
+/// and the `pb_msg` macro are in scope. Extension crates conventionally
+/// re-export the trait from a `prelude` module next to the invocation.
+///
+/// Like with [`pb_msg!`], fields can be preceded by `#[cfg(...)]` attributes.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::{pb_msg, pb_msg_ext, protos::trace::trace_packet::TracePacket};
+///
+/// pb_msg!(MyEvent {
+///     value: u64, primitive, 1,
+/// });
+///
+/// pb_msg_ext!(TracePacket {
+///     my_event: MyEvent, msg, 5000,
+/// });
+///
+/// fn write(packet: &mut TracePacket) {
+///     packet.set_my_event(|event: &mut MyEvent| {
+///         event.set_value(42);
+///     });
+/// }
+/// ```
 #[macro_export]
 macro_rules! pb_msg_ext {
     (
         $name:ident {
-            $( $field:ident : $tp:tt, $kind:ident, $id:literal ),+ $(,)?
+            $( $(#[$attr:meta])* $field:ident : $tp:tt, $kind:ident, $id:literal ),+ $(,)?
         }
     ) => {
         paste::paste! {
@@ -273,10 +863,18 @@ macro_rules! pb_msg_ext {
             #[repr(u32)]
             pub enum [<$name:camel ExtFieldNumber>] {
                 $(
+                    $(#[$attr])*
                     #[doc = concat!("Field number for `", stringify!($field), "`")]
                     [<$field:camel>] = $id
                 ),*
             }
+
+            impl $crate::pb_ext::PbMsgExtension for [<$name:camel ExtFieldNumber>] {
+                const EXTENDEE: &'static str = stringify!($name);
+                const FIELDS: &'static [(u32, &'static str)] = &[
+                    $( $(#[$attr])* ($id, stringify!($field)) ),*
+                ];
+            }
         }
 
         paste::paste! {
@@ -284,15 +882,231 @@ macro_rules! pb_msg_ext {
             #[allow(non_camel_case_types)]
             pub trait [<$name Ext>]<'a, 'b> {
                 $(
+                    $(#[$attr])*
                     pb_msg!(@decl fn $name, $field, $id, $kind, $tp);
+                    $(#[$attr])*
+                    pb_msg!(@has_decl fn $field, $id);
+                    $(#[$attr])*
+                    pb_msg!(@getter_decl fn $field, $id, $kind, $tp);
                 )*
             }
 
             impl<'a, 'b> [<$name Ext>]<'_, '_> for $name<'a, 'b> {
                 $(
+                    $(#[$attr])*
                     pb_msg!(@setter fn $name, $field, $id, $kind, $tp);
+                    $(#[$attr])*
+                    pb_msg!(@has fn $field, $id);
+                    $(#[$attr])*
+                    pb_msg!(@getter fn $field, $id, $kind, $tp);
                 )*
             }
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PbEnumValue;
+    use crate::{
+        heap_buffer::HeapBuffer,
+        pb_decoder::{
+            PbDecoder,
+            PbDecoderField::{self, Delimited, Varint},
+        },
+        pb_msg::{PbMsg, PbMsgWriter},
+        protos::trace::{
+            trace_packet::TracePacket,
+            track_event::track_event::{TrackEvent, TrackEventNameFieldCase, TrackEventType},
+        },
+    };
+
+    #[test]
+    fn field_accessors() {
+        let writer = PbMsgWriter::new();
+        let _hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        let mut packet = TracePacket { msg: &mut msg };
+        assert!(!packet.has_timestamp());
+        assert_eq!(packet.get_timestamp(), None);
+        packet.set_timestamp(42).set_trusted_pid(-1);
+        packet.set_track_event(|event: &mut TrackEvent| {
+            event.set_type(TrackEventType::TypeInstant);
+            assert!(event.has_type());
+            assert_eq!(event.get_type(), Some(TrackEventType::TypeInstant.into()));
+        });
+        assert!(packet.has_timestamp() && packet.has_track_event());
+        assert_eq!(packet.get_timestamp(), Some(42));
+        assert_eq!(packet.get_trusted_pid(), Some(-1));
+        assert!(!packet.has_sequence_flags());
+        msg.finalize();
+    }
+
+    #[allow(dead_code)]
+    mod map_messages {
+        use super::*;
+
+        pb_msg!(TestMapCountsEntry {
+            key: String, primitive, 1,
+            value: u64, primitive, 2,
+        });
+
+        pb_msg!(TestMapEventsEntry {
+            key: u32, primitive, 1,
+            value: TrackEvent, msg, 2,
+        });
+
+        pb_msg!(TestMap {
+            counts: [TestMapCountsEntry, String, primitive, u64], map, 1,
+            events: [TestMapEventsEntry, u32, msg, TrackEvent], map, 2,
+        });
+    }
+    use map_messages::*;
+
+    #[test]
+    fn map() {
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        let mut map = TestMap { msg: &mut msg };
+        map.set_counts("foo", 3).set_counts(String::from("bar"), 4);
+        map.set_events(7, |event: &mut TrackEvent| {
+            event.set_name("baz");
+        });
+        assert!(map.has_counts() && map.has_events());
+        msg.finalize();
+        let mut buf = vec![0u8; writer.writer.get_written_size()];
+        hb.copy_into(&mut buf);
+        let mut counts = Vec::new();
+        let mut events = Vec::new();
+        for item in PbDecoder::new(&buf) {
+            let (id, field) = item.unwrap();
+            let (key, value) = field.as_map_entry().unwrap();
+            match id {
+                1 => counts.push((key.unwrap().as_str().unwrap(), value.unwrap())),
+                2 => {
+                    let (_, name) = value
+                        .unwrap()
+                        .as_decoder()
+                        .unwrap()
+                        .next()
+                        .unwrap()
+                        .unwrap();
+                    events.push((key.unwrap(), name.as_str().unwrap()));
+                }
+                _ => panic!("unexpected field {id}"),
+            }
+        }
+        assert_eq!(counts, vec![("foo", Varint(3)), ("bar", Varint(4))]);
+        assert_eq!(events, vec![(Varint(7), "baz")]);
+    }
+
+    #[allow(dead_code)]
+    mod repeated_messages {
+        use super::*;
+
+        pb_msg!(TestRepeated {
+            names: [primitive, String], repeated, 1,
+            types: [enum, TrackEventType], repeated, 2,
+            events: [msg, TrackEvent], repeated, 3,
+            values: [primitive, u64], packed, 4,
+            ratios: [primitive, f32], packed, 5,
+        });
+    }
+    use repeated_messages::*;
+
+    #[test]
+    fn repeated() {
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        let mut repeated = TestRepeated { msg: &mut msg };
+        repeated
+            .set_names("foo")
+            .add_names_from(["bar", "baz"])
+            .add_types_from([TrackEventType::TypeSliceBegin, TrackEventType::TypeSliceEnd])
+            .add_events_from(["qux"], |event: &mut TrackEvent, name| {
+                event.set_name(name);
+            })
+            .set_values(&[1, 300])
+            .set_ratios(&[0.5]);
+        assert_eq!(
+            repeated.get_types(),
+            Some(TrackEventType::TypeSliceEnd.into())
+        );
+        msg.finalize();
+        let mut buf = vec![0u8; writer.writer.get_written_size()];
+        hb.copy_into(&mut buf);
+        let fields: Vec<(u32, PbDecoderField)> =
+            PbDecoder::new(&buf).map(|item| item.unwrap()).collect();
+        assert_eq!(fields.len(), 8);
+        let names: Vec<&str> = fields[..3]
+            .iter()
+            .map(|(_, field)| field.as_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["foo", "bar", "baz"]);
+        assert_eq!(fields[3], (2, Varint(1)));
+        assert_eq!(fields[4], (2, Varint(2)));
+        assert_eq!(fields[6], (4, Delimited(&[0x01, 0xac, 0x02])));
+        assert_eq!(fields[7], (5, Delimited(&0.5f32.to_le_bytes())));
+    }
+
+    #[test]
+    fn enum_values() {
+        assert_eq!(TrackEventType::try_from(3), Ok(TrackEventType::TypeInstant));
+        assert_eq!(TrackEventType::try_from(-1), Err(()));
+        assert_eq!(i32::from(TrackEventType::TypeCounter), 4);
+        assert_eq!(
+            PbEnumValue::<TrackEventType>::from(42),
+            PbEnumValue::Unknown(42)
+        );
+
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        let mut event = TrackEvent { msg: &mut msg };
+        event.set_type(PbEnumValue::Unknown(-2));
+        assert_eq!(event.get_type(), Some(PbEnumValue::Unknown(-2)));
+        event.set_type(TrackEventType::TypeSliceEnd);
+        msg.finalize();
+        let mut buf = vec![0u8; writer.writer.get_written_size()];
+        hb.copy_into(&mut buf);
+        let types: Vec<PbEnumValue<TrackEventType>> = PbDecoder::new(&buf)
+            .map(|item| item.unwrap().1.as_enum().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                PbEnumValue::Unknown(-2),
+                PbEnumValue::Known(TrackEventType::TypeSliceEnd)
+            ]
+        );
+        assert_eq!(types[1].known(), Some(TrackEventType::TypeSliceEnd));
+    }
+
+    #[test]
+    fn oneof() {
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        let mut event = TrackEvent { msg: &mut msg };
+        assert_eq!(event.get_name_field_case(), None);
+        event.set_name("foo");
+        assert_eq!(
+            event.get_name_field_case(),
+            Some(TrackEventNameFieldCase::Name)
+        );
+        event.set_name_iid(1).set_track_uuid(2);
+        assert_eq!(
+            event.get_name_field_case(),
+            Some(TrackEventNameFieldCase::NameIid)
+        );
+        msg.finalize();
+        let mut buf = vec![0u8; writer.writer.get_written_size()];
+        hb.copy_into(&mut buf);
+        let (case, field) = TrackEventNameFieldCase::decode(&buf).unwrap().unwrap();
+        assert_eq!(case, TrackEventNameFieldCase::NameIid);
+        assert_eq!(field, crate::pb_decoder::PbDecoderField::Varint(1));
+        assert_eq!(u32::from(case), 10);
+    }
+}
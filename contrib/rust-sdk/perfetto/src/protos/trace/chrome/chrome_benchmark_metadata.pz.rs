@@ -0,0 +1,31 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Autogenerated by the ProtoZero Rust compiler plugin.
+// Invoked by contrib/rust-sdk/tools/gen_rust_protos
+// DO NOT EDIT.
+
+use crate::pb_msg;
+
+pb_msg!(ChromeBenchmarkMetadata {
+    benchmark_start_time_us: i64, primitive, 1,
+    story_run_time_us: i64, primitive, 2,
+    benchmark_name: String, primitive, 3,
+    benchmark_description: String, primitive, 4,
+    label: String, primitive, 5,
+    story_name: String, primitive, 6,
+    story_tags: [primitive, String], repeated, 7,
+    story_run_index: i32, primitive, 8,
+    had_failures: bool, primitive, 9,
+});
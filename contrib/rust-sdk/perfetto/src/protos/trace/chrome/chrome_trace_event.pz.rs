@@ -0,0 +1,96 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Autogenerated by the ProtoZero Rust compiler plugin.
+// Invoked by contrib/rust-sdk/tools/gen_rust_protos
+// DO NOT EDIT.
+
+use crate::pb_enum;
+use crate::pb_msg;
+
+pb_enum!(ChromeLegacyJsonTraceTraceType {
+    USER_TRACE: 0,
+    SYSTEM_TRACE: 1,
+});
+
+pb_enum!(ChromeTracedValueNestedType { DICT: 0, ARRAY: 1 });
+
+pb_msg!(ChromeEventBundle {
+    trace_events: [msg, ChromeTraceEvent], repeated, 1,
+    metadata: [msg, ChromeMetadata], repeated, 2,
+    legacy_ftrace_output: [primitive, String], repeated, 4,
+    legacy_json_trace: [msg, ChromeLegacyJsonTrace], repeated, 5,
+    string_table: [msg, ChromeStringTableEntry], repeated, 3,
+});
+
+pb_msg!(ChromeLegacyJsonTrace {
+    type: ChromeLegacyJsonTraceTraceType, enum, 1,
+    data: String, primitive, 2,
+});
+
+pb_msg!(ChromeMetadata {
+    name: String, primitive, 1,
+    string_value: String, primitive, 2,
+    bool_value: bool, primitive, 3,
+    int_value: i64, primitive, 4,
+    json_value: String, primitive, 5,
+});
+
+pb_msg!(ChromeTraceEvent {
+    name: String, primitive, 1,
+    timestamp: i64, primitive, 2,
+    phase: i32, primitive, 3,
+    thread_id: i32, primitive, 4,
+    duration: i64, primitive, 5,
+    thread_duration: i64, primitive, 6,
+    scope: String, primitive, 7,
+    id: u64, primitive, 8,
+    flags: u32, primitive, 9,
+    category_group_name: String, primitive, 10,
+    process_id: i32, primitive, 11,
+    thread_timestamp: i64, primitive, 12,
+    bind_id: u64, primitive, 13,
+    args: [msg, ChromeTraceEventArg], repeated, 14,
+    name_index: u32, primitive, 15,
+    category_group_name_index: u32, primitive, 16,
+});
+
+pb_msg!(ChromeTraceEventArg {
+    name: String, primitive, 1,
+    bool_value: bool, primitive, 2,
+    uint_value: u64, primitive, 3,
+    int_value: i64, primitive, 4,
+    double_value: f64, primitive, 5,
+    string_value: String, primitive, 6,
+    pointer_value: u64, primitive, 7,
+    json_value: String, primitive, 8,
+    traced_value: ChromeTracedValue, msg, 10,
+    name_index: u32, primitive, 9,
+});
+
+pb_msg!(ChromeStringTableEntry {
+    value: String, primitive, 1,
+    index: i32, primitive, 2,
+});
+
+pb_msg!(ChromeTracedValue {
+    nested_type: ChromeTracedValueNestedType, enum, 1,
+    dict_keys: [primitive, String], repeated, 2,
+    dict_values: [msg, ChromeTracedValue], repeated, 3,
+    array_values: [msg, ChromeTracedValue], repeated, 4,
+    int_value: i32, primitive, 5,
+    double_value: f64, primitive, 6,
+    bool_value: bool, primitive, 7,
+    string_value: String, primitive, 8,
+});
@@ -32,7 +32,7 @@ pb_enum!(ClockBuiltinClocks {
 });
 
 pb_msg!(ClockSnapshot {
-    clocks: ClockSnapshotClock, msg, 1,
+    clocks: [msg, ClockSnapshotClock], repeated, 1,
     primary_trace_clock: BuiltinClock, enum, 2,
 });
 
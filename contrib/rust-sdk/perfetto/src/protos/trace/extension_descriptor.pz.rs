@@ -21,6 +21,10 @@ use crate::protos::common::descriptor::*;
 
 pb_msg!(ExtensionDescriptor {
     extension_set: FileDescriptorSet, msg, 1,
-    extension_set_gzip: String, primitive, 2,
+    extension_set_gzip: Bytes, primitive, 2,
     file_name: String, primitive, 3,
+}
+oneof descriptor {
+    extension_set,
+    extension_set_gzip,
 });
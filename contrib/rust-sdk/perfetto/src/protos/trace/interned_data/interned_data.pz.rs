@@ -17,6 +17,7 @@
 
 use crate::pb_msg;
 use crate::protos::trace::profiling::profile_common::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_histogram_sample::*;
 use crate::protos::trace::track_event::debug_annotation::*;
 use crate::protos::trace::track_event::log_message::*;
@@ -24,13 +25,14 @@ use crate::protos::trace::track_event::source_location::*;
 use crate::protos::trace::track_event::track_event::*;
 
 pb_msg!(InternedData {
-    event_categories: EventCategory, msg, 1,
-    event_names: EventName, msg, 2,
-    debug_annotation_names: DebugAnnotationName, msg, 3,
-    debug_annotation_value_type_names: DebugAnnotationValueTypeName, msg, 27,
-    source_locations: SourceLocation, msg, 4,
-    unsymbolized_source_locations: UnsymbolizedSourceLocation, msg, 28,
-    log_message_body: LogMessageBody, msg, 20,
-    histogram_names: HistogramName, msg, 25,
-    debug_annotation_string_values: InternedString, msg, 29,
+    event_categories: [msg, EventCategory], repeated, 1,
+    event_names: [msg, EventName], repeated, 2,
+    debug_annotation_names: [msg, DebugAnnotationName], repeated, 3,
+    debug_annotation_value_type_names: [msg, DebugAnnotationValueTypeName], repeated, 27,
+    source_locations: [msg, SourceLocation], repeated, 4,
+    unsymbolized_source_locations: [msg, UnsymbolizedSourceLocation], repeated, 28,
+    log_message_body: [msg, LogMessageBody], repeated, 20,
+    #[cfg(feature = "chrome")]
+    histogram_names: [msg, HistogramName], repeated, 25,
+    debug_annotation_string_values: [msg, InternedString], repeated, 29,
 });
@@ -15,6 +15,9 @@
 // Autogenerated by the gen_rust_protos script.
 // DO NOT EDIT.
 
+/// `chrome` protos.
+pub mod chrome;
+
 /// `clock_snapshot` protos.
 #[path = "clock_snapshot.pz.rs"]
 pub mod clock_snapshot;
@@ -41,5 +44,13 @@ pub mod trace;
 #[path = "trace_packet.pz.rs"]
 pub mod trace_packet;
 
+/// `trace_uuid` protos.
+#[path = "trace_uuid.pz.rs"]
+pub mod trace_uuid;
+
 /// `track_event` protos.
 pub mod track_event;
+
+/// `ui_state` protos.
+#[path = "ui_state.pz.rs"]
+pub mod ui_state;
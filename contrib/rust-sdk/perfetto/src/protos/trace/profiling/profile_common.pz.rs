@@ -20,7 +20,7 @@ use crate::pb_msg;
 
 pb_msg!(Callstack {
     iid: u64, primitive, 1,
-    frame_ids: u64, primitive, 2,
+    frame_ids: [primitive, u64], repeated, 2,
 });
 
 pb_msg!(Frame {
@@ -40,18 +40,18 @@ pb_msg!(Mapping {
     start: u64, primitive, 4,
     end: u64, primitive, 5,
     load_bias: u64, primitive, 6,
-    path_string_ids: u64, primitive, 7,
+    path_string_ids: [primitive, u64], repeated, 7,
 });
 
 pb_msg!(ModuleSymbols {
     path: String, primitive, 1,
     build_id: String, primitive, 2,
-    address_symbols: AddressSymbols, msg, 3,
+    address_symbols: [msg, AddressSymbols], repeated, 3,
 });
 
 pb_msg!(AddressSymbols {
     address: u64, primitive, 1,
-    lines: Line, msg, 2,
+    lines: [msg, Line], repeated, 2,
 });
 
 pb_msg!(Line {
@@ -62,5 +62,5 @@ pb_msg!(Line {
 
 pb_msg!(InternedString {
     iid: u64, primitive, 1,
-    str: String, primitive, 2,
+    str: Bytes, primitive, 2,
 });
@@ -33,15 +33,15 @@ pb_msg!(TestEventProtoVmIncrementalState {
     string_merged: String, primitive, 1,
     int_merged: i32, primitive, 2,
     single_message: TestEventProtoVmMessage, msg, 3,
-    messages: TestEventProtoVmMessage, msg, 4,
+    messages: [msg, TestEventProtoVmMessage], repeated, 4,
 });
 
 pb_msg!(TestEventProtoVmPatch {
     string_to_merge: String, primitive, 1,
     int_to_merge: i32, primitive, 2,
     single_message: TestEventProtoVmMessage, msg, 3,
-    messages: TestEventProtoVmMessage, msg, 4,
-    delete_message_ids: u32, primitive, 5,
+    messages: [msg, TestEventProtoVmMessage], repeated, 4,
+    delete_message_ids: [primitive, u32], repeated, 5,
 });
 
 pb_msg!(TestEventProtoVmMessage {
@@ -54,11 +54,11 @@ pb_msg!(TestEventProtoVmMessageProtoVmSubmessage {
 });
 
 pb_msg!(TestEventTestPayload {
-    str: String, primitive, 1,
-    nested: TestEventTestPayload, msg, 2,
+    str: [primitive, String], repeated, 1,
+    nested: [msg, TestEventTestPayload], repeated, 2,
     single_string: String, primitive, 4,
     single_int: i32, primitive, 5,
-    repeated_ints: i32, primitive, 6,
+    repeated_ints: [primitive, i32], repeated, 6,
     remaining_nesting_depth: u32, primitive, 3,
-    debug_annotations: DebugAnnotation, msg, 7,
+    debug_annotations: [msg, DebugAnnotation], repeated, 7,
 });
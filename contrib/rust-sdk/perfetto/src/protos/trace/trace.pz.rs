@@ -20,5 +20,5 @@ use crate::pb_msg;
 use crate::protos::trace::trace_packet::*;
 
 pb_msg!(Trace {
-    packet: TracePacket, msg, 1,
+    packet: [msg, TracePacket], repeated, 1,
 });
@@ -17,12 +17,17 @@
 
 use crate::pb_enum;
 use crate::pb_msg;
+use crate::protos::trace::chrome::chrome_benchmark_metadata::*;
+use crate::protos::trace::chrome::chrome_trace_event::*;
 use crate::protos::trace::clock_snapshot::*;
 use crate::protos::trace::extension_descriptor::*;
 use crate::protos::trace::interned_data::interned_data::*;
+use crate::protos::trace::profiling::profile_common::*;
 use crate::protos::trace::test_event::*;
+use crate::protos::trace::trace_uuid::*;
 use crate::protos::trace::track_event::track_descriptor::*;
 use crate::protos::trace::track_event::track_event::*;
+use crate::protos::trace::ui_state::*;
 
 pb_enum!(TracePacketSequenceFlags {
     SEQ_UNSPECIFIED: 0,
@@ -30,6 +35,19 @@ pb_enum!(TracePacketSequenceFlags {
     SEQ_NEEDS_INCREMENTAL_STATE: 2,
 });
 
+pb_enum!(TracePacketDataLossReason {
+    DATA_LOSS_UNSPECIFIED: 0,
+    DATA_LOSS_PRESENT: 1,
+    DATA_LOSS_READ_GAP: 2,
+    DATA_LOSS_CHUNK_CORRUPTED: 4,
+    DATA_LOSS_ORPHAN_CONTINUATION: 8,
+    DATA_LOSS_REASSEMBLY_GAP: 16,
+    DATA_LOSS_REASSEMBLY_BROKEN_CHAIN: 32,
+    DATA_LOSS_OVERWRITE: 64,
+    DATA_LOSS_WRITER_ABORT: 128,
+    DATA_LOSS_SMB_FULL: 256,
+});
+
 pb_msg!(TracePacketDefaults {
     timestamp_clock_id: u32, primitive, 58,
     track_event_defaults: TrackEventDefaults, msg, 11,
@@ -49,5 +67,12 @@ pb_msg!(TracePacket {
     for_testing: TestEvent, msg, 900,
     interned_data: InternedData, msg, 12,
     sequence_flags: u32, primitive, 13,
+    previous_packet_dropped: u32, primitive, 42,
     trace_packet_defaults: TracePacketDefaults, msg, 59,
+    compressed_packets: Bytes, primitive, 50,
+    module_symbols: ModuleSymbols, msg, 61,
+    chrome_events: ChromeEventBundle, msg, 5,
+    chrome_benchmark_metadata: ChromeBenchmarkMetadata, msg, 48,
+    ui_state: UiState, msg, 78,
+    trace_uuid: TraceUuid, msg, 89,
 });
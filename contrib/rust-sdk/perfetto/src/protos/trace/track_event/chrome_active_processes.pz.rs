@@ -19,5 +19,5 @@
 use crate::pb_msg;
 
 pb_msg!(ChromeActiveProcesses {
-    pid: i32, primitive, 1,
+    pid: [primitive, i32], repeated, 1,
 });
@@ -131,6 +131,10 @@ pb_msg!(BeginImplFrameArgs {
     current_args: BeginFrameArgs, msg, 4,
     last_args: BeginFrameArgs, msg, 5,
     timestamps_in_us: BeginImplFrameArgsTimestampsInUs, msg, 6,
+}
+oneof args {
+    current_args,
+    last_args,
 });
 
 pb_msg!(BeginImplFrameArgsTimestampsInUs {
@@ -155,6 +159,10 @@ pb_msg!(BeginFrameArgs {
     source_location_iid: u64, primitive, 9,
     source_location: SourceLocation, msg, 10,
     frames_throttled_since_last: i64, primitive, 12,
+}
+oneof created_from {
+    source_location_iid,
+    source_location,
 });
 
 pb_msg!(ChromeCompositorStateMachine {
@@ -60,7 +60,7 @@ pb_msg!(ChromeFrameReporter {
     layer_tree_host_id: u64, primitive, 11,
     has_high_latency: bool, primitive, 12,
     frame_type: ChromeFrameReporterFrameType, enum, 13,
-    high_latency_contribution_stage: String, primitive, 14,
+    high_latency_contribution_stage: [primitive, String], repeated, 14,
     checkerboarded_needs_raster: bool, primitive, 15,
     checkerboarded_needs_record: bool, primitive, 16,
     surface_frame_trace_id: i64, primitive, 17,
@@ -66,7 +66,7 @@ pb_msg!(ChromeLatencyInfo {
     trace_id: i64, primitive, 1,
     step: ChromeLatencyInfoStep, enum, 2,
     frame_tree_node_id: i32, primitive, 3,
-    component_info: ChromeLatencyInfoComponentInfo, msg, 4,
+    component_info: [msg, ChromeLatencyInfoComponentInfo], repeated, 4,
     is_coalesced: bool, primitive, 5,
     gesture_scroll_id: i64, primitive, 6,
     touch_id: i64, primitive, 7,
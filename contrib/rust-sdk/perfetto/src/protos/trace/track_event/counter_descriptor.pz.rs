@@ -34,7 +34,7 @@ pb_enum!(CounterDescriptorUnit {
 
 pb_msg!(CounterDescriptor {
     type: CounterDescriptorBuiltinCounterType, enum, 1,
-    categories: String, primitive, 2,
+    categories: [primitive, String], repeated, 2,
     unit: CounterDescriptorUnit, enum, 3,
     unit_name: String, primitive, 6,
     unit_multiplier: i64, primitive, 4,
@@ -49,16 +49,35 @@ pb_msg!(DebugAnnotation {
     string_value_iid: u64, primitive, 17,
     proto_type_name: String, primitive, 16,
     proto_type_name_iid: u64, primitive, 13,
-    proto_value: String, primitive, 14,
-    dict_entries: DebugAnnotation, msg, 11,
-    array_values: DebugAnnotation, msg, 12,
+    proto_value: Bytes, primitive, 14,
+    dict_entries: [msg, DebugAnnotation], repeated, 11,
+    array_values: [msg, DebugAnnotation], repeated, 12,
+}
+oneof name_field {
+    name_iid,
+    name,
+}
+oneof value {
+    bool_value,
+    uint_value,
+    int_value,
+    double_value,
+    pointer_value,
+    nested_value,
+    legacy_json_value,
+    string_value,
+    string_value_iid,
+}
+oneof proto_type_descriptor {
+    proto_type_name,
+    proto_type_name_iid,
 });
 
 pb_msg!(DebugAnnotationNestedValue {
     nested_type: NestedValueNestedType, enum, 1,
-    dict_keys: String, primitive, 2,
-    dict_values: DebugAnnotationNestedValue, msg, 3,
-    array_values: DebugAnnotationNestedValue, msg, 4,
+    dict_keys: [primitive, String], repeated, 2,
+    dict_values: [msg, DebugAnnotationNestedValue], repeated, 3,
+    array_values: [msg, DebugAnnotationNestedValue], repeated, 4,
     int_value: i64, primitive, 5,
     double_value: f64, primitive, 6,
     bool_value: bool, primitive, 7,
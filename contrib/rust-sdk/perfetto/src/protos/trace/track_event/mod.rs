@@ -16,66 +16,82 @@
 // DO NOT EDIT.
 
 /// `chrome_active_processes` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_active_processes.pz.rs"]
 pub mod chrome_active_processes;
 
 /// `chrome_application_state_info` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_application_state_info.pz.rs"]
 pub mod chrome_application_state_info;
 
 /// `chrome_compositor_scheduler_state` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_compositor_scheduler_state.pz.rs"]
 pub mod chrome_compositor_scheduler_state;
 
 /// `chrome_content_settings_event_info` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_content_settings_event_info.pz.rs"]
 pub mod chrome_content_settings_event_info;
 
 /// `chrome_frame_reporter` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_frame_reporter.pz.rs"]
 pub mod chrome_frame_reporter;
 
 /// `chrome_histogram_sample` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_histogram_sample.pz.rs"]
 pub mod chrome_histogram_sample;
 
 /// `chrome_keyed_service` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_keyed_service.pz.rs"]
 pub mod chrome_keyed_service;
 
 /// `chrome_latency_info` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_latency_info.pz.rs"]
 pub mod chrome_latency_info;
 
 /// `chrome_legacy_ipc` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_legacy_ipc.pz.rs"]
 pub mod chrome_legacy_ipc;
 
 /// `chrome_message_pump` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_message_pump.pz.rs"]
 pub mod chrome_message_pump;
 
 /// `chrome_mojo_event_info` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_mojo_event_info.pz.rs"]
 pub mod chrome_mojo_event_info;
 
 /// `chrome_process_descriptor` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_process_descriptor.pz.rs"]
 pub mod chrome_process_descriptor;
 
 /// `chrome_renderer_scheduler_state` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_renderer_scheduler_state.pz.rs"]
 pub mod chrome_renderer_scheduler_state;
 
 /// `chrome_thread_descriptor` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_thread_descriptor.pz.rs"]
 pub mod chrome_thread_descriptor;
 
 /// `chrome_user_event` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_user_event.pz.rs"]
 pub mod chrome_user_event;
 
 /// `chrome_window_handle_event_info` protos.
+#[cfg(feature = "chrome")]
 #[path = "chrome_window_handle_event_info.pz.rs"]
 pub mod chrome_window_handle_event_info;
 
@@ -33,11 +33,11 @@ pb_enum!(ProcessDescriptorChromeProcessType {
 
 pb_msg!(ProcessDescriptor {
     pid: i32, primitive, 1,
-    cmdline: String, primitive, 2,
+    cmdline: [primitive, String], repeated, 2,
     process_name: String, primitive, 6,
     process_priority: i32, primitive, 5,
     start_timestamp_ns: i64, primitive, 7,
     chrome_process_type: ProcessDescriptorChromeProcessType, enum, 4,
     legacy_sort_index: i32, primitive, 3,
-    process_labels: String, primitive, 8,
+    process_labels: [primitive, String], repeated, 8,
 });
@@ -19,7 +19,7 @@
 use crate::pb_msg;
 
 pb_msg!(Screenshot {
-    jpg_image: String, primitive, 1,
-    pam_image: String, primitive, 2,
-    ppm_image: String, primitive, 3,
+    jpg_image: Bytes, primitive, 1,
+    pam_image: Bytes, primitive, 2,
+    ppm_image: Bytes, primitive, 3,
 });
@@ -18,7 +18,9 @@
 
 use crate::pb_enum;
 use crate::pb_msg;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_process_descriptor::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_thread_descriptor::*;
 use crate::protos::trace::track_event::counter_descriptor::*;
 use crate::protos::trace::track_event::process_descriptor::*;
@@ -57,8 +59,10 @@ pb_msg!(TrackDescriptor {
     atrace_name: String, primitive, 13,
     description: String, primitive, 14,
     process: ProcessDescriptor, msg, 3,
+    #[cfg(feature = "chrome")]
     chrome_process: ChromeProcessDescriptor, msg, 6,
     thread: ThreadDescriptor, msg, 4,
+    #[cfg(feature = "chrome")]
     chrome_thread: ChromeThreadDescriptor, msg, 7,
     counter: CounterDescriptor, msg, 8,
     state: StateDescriptor, msg, 18,
@@ -70,4 +74,13 @@ pb_msg!(TrackDescriptor {
     sibling_merge_key_int: u64, primitive, 17,
     process_ordering: TrackDescriptorProcessOrdering, enum, 19,
     thread_ordering: TrackDescriptorThreadOrdering, enum, 20,
+}
+oneof static_or_dynamic_name {
+    name,
+    static_name,
+    atrace_name,
+}
+oneof sibling_merge_key_field {
+    sibling_merge_key,
+    sibling_merge_key_int,
 });
@@ -18,19 +18,33 @@
 
 use crate::pb_enum;
 use crate::pb_msg;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_active_processes::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_application_state_info::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_compositor_scheduler_state::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_content_settings_event_info::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_frame_reporter::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_histogram_sample::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_keyed_service::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_latency_info::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_legacy_ipc::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_message_pump::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_mojo_event_info::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_renderer_scheduler_state::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_user_event::*;
+#[cfg(feature = "chrome")]
 use crate::protos::trace::track_event::chrome_window_handle_event_info::*;
 use crate::protos::trace::track_event::debug_annotation::*;
 use crate::protos::trace::track_event::log_message::*;
@@ -73,51 +87,65 @@ pb_msg!(EventCategory {
 
 pb_msg!(TrackEventDefaults {
     track_uuid: u64, primitive, 11,
-    extra_counter_track_uuids: u64, primitive, 31,
-    extra_double_counter_track_uuids: u64, primitive, 45,
+    extra_counter_track_uuids: [primitive, u64], repeated, 31,
+    extra_double_counter_track_uuids: [primitive, u64], repeated, 45,
 });
 
 pb_msg!(TrackEvent {
-    category_iids: u64, primitive, 3,
-    categories: String, primitive, 22,
+    category_iids: [primitive, u64], repeated, 3,
+    categories: [primitive, String], repeated, 22,
     name_iid: u64, primitive, 10,
     name: String, primitive, 23,
     type: TrackEventType, enum, 9,
     track_uuid: u64, primitive, 11,
     counter_value: i64, primitive, 30,
     double_counter_value: f64, primitive, 44,
-    extra_counter_track_uuids: u64, primitive, 31,
-    extra_counter_values: i64, primitive, 12,
-    extra_double_counter_track_uuids: u64, primitive, 45,
-    extra_double_counter_values: f64, primitive, 46,
-    flow_ids_old: u64, primitive, 36,
-    flow_ids: u64, primitive, 47,
-    terminating_flow_ids_old: u64, primitive, 42,
-    terminating_flow_ids: u64, primitive, 48,
+    extra_counter_track_uuids: [primitive, u64], repeated, 31,
+    extra_counter_values: [primitive, i64], repeated, 12,
+    extra_double_counter_track_uuids: [primitive, u64], repeated, 45,
+    extra_double_counter_values: [primitive, f64], repeated, 46,
+    flow_ids_old: [primitive, u64], repeated, 36,
+    flow_ids: [primitive, u64], repeated, 47,
+    terminating_flow_ids_old: [primitive, u64], repeated, 42,
+    terminating_flow_ids: [primitive, u64], repeated, 48,
     correlation_id: u64, primitive, 52,
     correlation_id_str: String, primitive, 53,
     correlation_id_str_iid: u64, primitive, 54,
     callstack: TrackEventCallstack, msg, 55,
     callstack_iid: u64, primitive, 56,
-    debug_annotations: DebugAnnotation, msg, 4,
+    debug_annotations: [msg, DebugAnnotation], repeated, 4,
     task_execution: TaskExecution, msg, 5,
     log_message: LogMessage, msg, 21,
+    #[cfg(feature = "chrome")]
     cc_scheduler_state: ChromeCompositorSchedulerState, msg, 24,
+    #[cfg(feature = "chrome")]
     chrome_user_event: ChromeUserEvent, msg, 25,
+    #[cfg(feature = "chrome")]
     chrome_keyed_service: ChromeKeyedService, msg, 26,
+    #[cfg(feature = "chrome")]
     chrome_legacy_ipc: ChromeLegacyIpc, msg, 27,
+    #[cfg(feature = "chrome")]
     chrome_histogram_sample: ChromeHistogramSample, msg, 28,
+    #[cfg(feature = "chrome")]
     chrome_latency_info: ChromeLatencyInfo, msg, 29,
+    #[cfg(feature = "chrome")]
     chrome_frame_reporter: ChromeFrameReporter, msg, 32,
+    #[cfg(feature = "chrome")]
     chrome_application_state_info: ChromeApplicationStateInfo, msg, 39,
+    #[cfg(feature = "chrome")]
     chrome_renderer_scheduler_state: ChromeRendererSchedulerState, msg, 40,
+    #[cfg(feature = "chrome")]
     chrome_window_handle_event_info: ChromeWindowHandleEventInfo, msg, 41,
+    #[cfg(feature = "chrome")]
     chrome_content_settings_event_info: ChromeContentSettingsEventInfo, msg, 43,
+    #[cfg(feature = "chrome")]
     chrome_active_processes: ChromeActiveProcesses, msg, 49,
     screenshot: Screenshot, msg, 50,
     source_location: SourceLocation, msg, 33,
     source_location_iid: u64, primitive, 34,
+    #[cfg(feature = "chrome")]
     chrome_message_pump: ChromeMessagePump, msg, 35,
+    #[cfg(feature = "chrome")]
     chrome_mojo_event_info: ChromeMojoEventInfo, msg, 38,
     timestamp_delta_us: i64, primitive, 1,
     timestamp_absolute_us: i64, primitive, 16,
@@ -126,6 +154,39 @@ pb_msg!(TrackEvent {
     thread_instruction_count_delta: i64, primitive, 8,
     thread_instruction_count_absolute: i64, primitive, 20,
     legacy_event: TrackEventLegacyEvent, msg, 6,
+}
+oneof name_field {
+    name_iid,
+    name,
+}
+oneof counter_value_field {
+    counter_value,
+    double_counter_value,
+}
+oneof correlation_id_field {
+    correlation_id,
+    correlation_id_str,
+    correlation_id_str_iid,
+}
+oneof callstack_field {
+    callstack,
+    callstack_iid,
+}
+oneof source_location_field {
+    source_location,
+    source_location_iid,
+}
+oneof timestamp {
+    timestamp_delta_us,
+    timestamp_absolute_us,
+}
+oneof thread_time {
+    thread_time_delta_us,
+    thread_time_absolute_us,
+}
+oneof thread_instruction_count {
+    thread_instruction_count_delta,
+    thread_instruction_count_absolute,
 });
 
 pb_msg!(TrackEventLegacyEvent {
@@ -145,6 +206,11 @@ pb_msg!(TrackEventLegacyEvent {
     instant_event_scope: LegacyEventInstantEventScope, enum, 14,
     pid_override: i32, primitive, 18,
     tid_override: i32, primitive, 19,
+}
+oneof id {
+    unscoped_id,
+    local_id,
+    global_id,
 });
 
 pb_msg!(TrackEventCallstack {
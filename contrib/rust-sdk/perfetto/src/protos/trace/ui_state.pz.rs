@@ -0,0 +1,30 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Autogenerated by the ProtoZero Rust compiler plugin.
+// Invoked by contrib/rust-sdk/tools/gen_rust_protos
+// DO NOT EDIT.
+
+use crate::pb_msg;
+
+pb_msg!(UiState {
+    timeline_start_ts: i64, primitive, 1,
+    timeline_end_ts: i64, primitive, 2,
+    highlight_process: UiStateHighlightProcess, msg, 3,
+});
+
+pb_msg!(UiStateHighlightProcess {
+    pid: u32, primitive, 1,
+    cmdline: String, primitive, 2,
+});
@@ -0,0 +1,403 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField, append_field},
+    protos::trace::{
+        interned_data::interned_data::InternedDataFieldNumber,
+        profiling::profile_common::InternedStringFieldNumber,
+        trace::TraceFieldNumber,
+        trace_packet::TracePacketFieldNumber,
+        track_event::{
+            debug_annotation::DebugAnnotationFieldNumber, log_message::LogMessageBodyFieldNumber,
+            process_descriptor::ProcessDescriptorFieldNumber,
+            track_descriptor::TrackDescriptorFieldNumber, track_event::TrackEventFieldNumber,
+        },
+    },
+    trace_reader::{TraceReader, TraceReaderError},
+};
+use std::collections::HashMap;
+use thiserror::Error;
+
+// Fields of protos that aren't part of the SDK.
+const TRACE_PACKET_PROCESS_TREE: u32 = 2;
+const TRACE_PACKET_PACKAGES_LIST: u32 = 47;
+const PROCESS_TREE_PROCESSES: u32 = 1;
+const PROCESS_TREE_PROCESS_CMDLINE: u32 = 3;
+const PACKAGES_LIST_PACKAGES: u32 = 1;
+const PACKAGES_LIST_PACKAGE_INFO_NAME: u32 = 1;
+
+// Nesting depth of debug annotation dictionaries and arrays that the PII
+// filter descends into. Deeper values are dropped.
+const MAX_PII_ANNOTATION_DEPTH: usize = 8;
+
+/// Trace redaction errors.
+#[derive(Error, Debug, PartialEq)]
+pub enum RedactionError {
+    /// The trace could not be read.
+    #[error("Failed to read trace: {0}")]
+    Trace(#[from] TraceReaderError),
+    /// A filtered message could not be decoded.
+    #[error("Failed to decode filtered message: {0}")]
+    Decode(#[from] PbDecoderError),
+}
+
+#[derive(Clone, Debug)]
+enum FieldAction {
+    Keep,
+    Drop,
+    Filter(FieldFilter),
+}
+
+/// Filter that removes fields from encoded protobuf messages, e.g. to strip
+/// personally identifiable information from a trace before it leaves the
+/// device.
+///
+/// Fields are selected by paths of field ids, starting from the message that
+/// is filtered. All the fields of a path but the last must be nested messages.
+/// An [`allowlist`](Self::allowlist) filter drops all the fields that aren't
+/// allowed, and a [`denylist`](Self::denylist) filter keeps all the fields
+/// that aren't denied. The most specific path wins, so that e.g. allowing
+/// `[1, 2]` after `[1]` only keeps field 2 of field 1.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::{
+///     protos::trace::{
+///         trace_packet::TracePacketFieldNumber,
+///         track_event::track_event::TrackEventFieldNumber,
+///     },
+///     redaction::FieldFilter,
+/// };
+///
+/// // Removes the names of all the track events.
+/// let filter = FieldFilter::denylist().deny(&[
+///     TracePacketFieldNumber::TrackEvent as u32,
+///     TrackEventFieldNumber::Name as u32,
+/// ]);
+/// let packet = filter.filter_packet(b"\x5a\x04\xba\x01\x01x").unwrap();
+/// assert_eq!(packet, b"\x5a\x00");
+/// ```
+#[derive(Clone, Debug)]
+pub struct FieldFilter {
+    keep_unlisted: bool,
+    fields: HashMap<u32, FieldAction>,
+}
+
+impl FieldFilter {
+    /// Creates a filter that only keeps the allowed fields.
+    pub fn allowlist() -> Self {
+        Self {
+            keep_unlisted: false,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Creates a filter that keeps all the fields, except the denied ones.
+    pub fn denylist() -> Self {
+        Self {
+            keep_unlisted: true,
+            fields: HashMap::new(),
+        }
+    }
+
+    /// Creates a denylist filter for `TracePacket`s that removes the fields of
+    /// the SDK and system protos that commonly carry personally identifiable
+    /// information: process command lines, package names, debug annotation
+    /// string values and log message bodies.
+    pub fn pii() -> Self {
+        use InternedDataFieldNumber as Interned;
+        use TracePacketFieldNumber as Packet;
+        Self::denylist()
+            .deny(&[
+                TRACE_PACKET_PROCESS_TREE,
+                PROCESS_TREE_PROCESSES,
+                PROCESS_TREE_PROCESS_CMDLINE,
+            ])
+            .deny(&[
+                TRACE_PACKET_PACKAGES_LIST,
+                PACKAGES_LIST_PACKAGES,
+                PACKAGES_LIST_PACKAGE_INFO_NAME,
+            ])
+            .deny(&[
+                Packet::TrackDescriptor as u32,
+                TrackDescriptorFieldNumber::Process as u32,
+                ProcessDescriptorFieldNumber::Cmdline as u32,
+            ])
+            .filter(
+                &[
+                    Packet::TrackEvent as u32,
+                    TrackEventFieldNumber::DebugAnnotations as u32,
+                ],
+                debug_annotation_pii_filter(MAX_PII_ANNOTATION_DEPTH),
+            )
+            .deny(&[
+                Packet::InternedData as u32,
+                Interned::DebugAnnotationStringValues as u32,
+                InternedStringFieldNumber::Str as u32,
+            ])
+            .deny(&[
+                Packet::InternedData as u32,
+                Interned::LogMessageBody as u32,
+                LogMessageBodyFieldNumber::Body as u32,
+            ])
+    }
+
+    /// Keeps the field at `path`, including all of its nested fields.
+    #[must_use = "Builder methods return an updated filter; use the returned value or keep chaining."]
+    pub fn allow(mut self, path: &[u32]) -> Self {
+        self.set(path, FieldAction::Keep);
+        self
+    }
+
+    /// Drops the field at `path`.
+    #[must_use = "Builder methods return an updated filter; use the returned value or keep chaining."]
+    pub fn deny(mut self, path: &[u32]) -> Self {
+        self.set(path, FieldAction::Drop);
+        self
+    }
+
+    /// Filters the nested message at `path` with `filter`, e.g. to reuse a
+    /// filter of a message that is nested in several places.
+    #[must_use = "Builder methods return an updated filter; use the returned value or keep chaining."]
+    pub fn filter(mut self, path: &[u32], filter: FieldFilter) -> Self {
+        self.set(path, FieldAction::Filter(filter));
+        self
+    }
+
+    fn set(&mut self, path: &[u32], action: FieldAction) {
+        let Some((&last, parents)) = path.split_last() else {
+            return;
+        };
+        let mut filter = self;
+        for &field_id in parents {
+            let keep_unlisted = filter.keep_unlisted;
+            let entry = filter.fields.entry(field_id).or_insert(FieldAction::Keep);
+            if !matches!(entry, FieldAction::Filter(_)) {
+                *entry = FieldAction::Filter(FieldFilter {
+                    keep_unlisted,
+                    fields: HashMap::new(),
+                });
+            }
+            let FieldAction::Filter(nested) = entry else {
+                unreachable!();
+            };
+            filter = nested;
+        }
+        filter.fields.insert(last, action);
+    }
+
+    /// Returns the encoded `message` without the filtered fields.
+    pub fn filter_message(&self, message: &[u8]) -> Result<Vec<u8>, PbDecoderError> {
+        let mut out = Vec::with_capacity(message.len());
+        self.append_filtered(&mut out, message)?;
+        Ok(out)
+    }
+
+    /// Returns the encoded `TracePacket` without the filtered fields. Packets
+    /// of a live stream can be filtered one at a time, as they don't depend on
+    /// each other.
+    pub fn filter_packet(&self, packet: &[u8]) -> Result<Vec<u8>, PbDecoderError> {
+        self.filter_message(packet)
+    }
+
+    /// Returns the serialized `Trace` without the filtered fields of its
+    /// packets. Compressed packets are decompressed.
+    pub fn filter_trace(&self, trace: &[u8]) -> Result<Vec<u8>, RedactionError> {
+        let mut out = Vec::with_capacity(trace.len());
+        for packet in TraceReader::new(trace) {
+            let packet = self.filter_packet(&packet?)?;
+            append_field(
+                &mut out,
+                TraceFieldNumber::Packet as u32,
+                &PbDecoderField::Delimited(&packet),
+            );
+        }
+        Ok(out)
+    }
+
+    fn append_filtered(&self, out: &mut Vec<u8>, message: &[u8]) -> Result<(), PbDecoderError> {
+        for item in PbDecoder::new(message) {
+            let (field_id, field) = item?;
+            match (self.fields.get(&field_id), &field) {
+                (Some(FieldAction::Keep), _) => append_field(out, field_id, &field),
+                (Some(FieldAction::Drop), _) => {}
+                (Some(FieldAction::Filter(nested)), PbDecoderField::Delimited(data)) => {
+                    let filtered = nested.filter_message(data)?;
+                    append_field(out, field_id, &PbDecoderField::Delimited(&filtered));
+                }
+                // Scalars can't be filtered, so they are dropped to be safe.
+                (Some(FieldAction::Filter(_)), _) => {}
+                (None, _) if self.keep_unlisted => append_field(out, field_id, &field),
+                (None, _) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn debug_annotation_pii_filter(depth: usize) -> FieldFilter {
+    use DebugAnnotationFieldNumber as Annotation;
+    let filter = FieldFilter::denylist()
+        .deny(&[Annotation::StringValue as u32])
+        .deny(&[Annotation::LegacyJsonValue as u32])
+        .deny(&[Annotation::NestedValue as u32]);
+    if depth == 0 {
+        return filter
+            .deny(&[Annotation::DictEntries as u32])
+            .deny(&[Annotation::ArrayValues as u32]);
+    }
+    let nested = debug_annotation_pii_filter(depth - 1);
+    filter
+        .filter(&[Annotation::DictEntries as u32], nested.clone())
+        .filter(&[Annotation::ArrayValues as u32], nested)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        heap_buffer::HeapBuffer,
+        pb_msg::{PbMsg, PbMsgWriter},
+        protos::trace::{
+            trace_packet::TracePacket,
+            track_event::{
+                debug_annotation::DebugAnnotation, process_descriptor::ProcessDescriptor,
+                track_descriptor::TrackDescriptor, track_event::TrackEvent,
+            },
+        },
+    };
+    use std::error::Error;
+
+    fn encode_packet<F>(cb: F) -> Vec<u8>
+    where
+        F: FnOnce(&mut TracePacket),
+    {
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        {
+            let mut packet = TracePacket { msg: &mut msg };
+            cb(&mut packet);
+        }
+        msg.finalize();
+        let size = writer.writer.get_written_size();
+        let mut data = vec![0u8; size];
+        hb.copy_into(&mut data);
+        data
+    }
+
+    fn has_string(data: &[u8], value: &str) -> bool {
+        data.windows(value.len()).any(|w| w == value.as_bytes())
+    }
+
+    fn fields(msg: &[u8]) -> Vec<(u32, PbDecoderField<'_>)> {
+        PbDecoder::new(msg).map(|f| f.unwrap()).collect()
+    }
+
+    #[test]
+    fn pii() -> Result<(), Box<dyn Error>> {
+        let packet = encode_packet(|packet| {
+            packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+                desc.set_uuid(1);
+                desc.set_process(|process: &mut ProcessDescriptor| {
+                    process.set_pid(42);
+                    process.set_cmdline("secret-cmdline");
+                });
+            });
+        });
+        let filtered = FieldFilter::pii().filter_packet(&packet)?;
+        let [(id, PbDecoderField::Delimited(desc))] = fields(&filtered)[..] else {
+            panic!("unexpected packet");
+        };
+        assert_eq!(id, TracePacketFieldNumber::TrackDescriptor as u32);
+        let [
+            (_, PbDecoderField::Varint(1)),
+            (id, PbDecoderField::Delimited(process)),
+        ] = fields(desc)[..]
+        else {
+            panic!("unexpected track descriptor");
+        };
+        assert_eq!(id, TrackDescriptorFieldNumber::Process as u32);
+        assert_eq!(
+            fields(process),
+            vec![(
+                ProcessDescriptorFieldNumber::Pid as u32,
+                PbDecoderField::Varint(42)
+            )]
+        );
+
+        let packet = encode_packet(|packet| {
+            packet.set_track_event(|event: &mut TrackEvent| {
+                event.set_name("event");
+                event.set_debug_annotations(|annotation: &mut DebugAnnotation| {
+                    annotation.set_name("outer");
+                    annotation.set_dict_entries(|entry: &mut DebugAnnotation| {
+                        entry.set_name("inner");
+                        entry.set_string_value("secret-value");
+                    });
+                });
+            });
+        });
+        let filtered = FieldFilter::pii().filter_packet(&packet)?;
+        assert!(!has_string(&filtered, "secret-value"));
+        assert!(has_string(&filtered, "event"));
+        assert!(has_string(&filtered, "outer"));
+        assert!(has_string(&filtered, "inner"));
+        Ok(())
+    }
+
+    #[test]
+    fn allowlist() -> Result<(), Box<dyn Error>> {
+        let packet = encode_packet(|packet| {
+            packet.set_timestamp(1234);
+            packet.set_track_event(|event: &mut TrackEvent| {
+                event.set_name("event");
+                event.set_track_uuid(5);
+            });
+        });
+        let filter = FieldFilter::allowlist()
+            .allow(&[TracePacketFieldNumber::TrackEvent as u32])
+            .allow(&[
+                TracePacketFieldNumber::TrackEvent as u32,
+                TrackEventFieldNumber::TrackUuid as u32,
+            ]);
+        let filtered = filter.filter_packet(&packet)?;
+        let [(id, PbDecoderField::Delimited(event))] = fields(&filtered)[..] else {
+            panic!("unexpected packet");
+        };
+        assert_eq!(id, TracePacketFieldNumber::TrackEvent as u32);
+        assert_eq!(
+            fields(event),
+            vec![(
+                TrackEventFieldNumber::TrackUuid as u32,
+                PbDecoderField::Varint(5)
+            )]
+        );
+
+        let mut trace = Vec::new();
+        for _ in 0..2 {
+            append_field(
+                &mut trace,
+                TraceFieldNumber::Packet as u32,
+                &PbDecoderField::Delimited(&packet),
+            );
+        }
+        let filtered_trace = filter.filter_trace(&trace)?;
+        let packets: Vec<_> = TraceReader::new(&filtered_trace).collect::<Result<_, _>>()?;
+        assert_eq!(packets, vec![filtered.clone(), filtered]);
+        Ok(())
+    }
+}
@@ -0,0 +1,487 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{DataSource, TraceContext},
+    packet_sequence::PacketSequence,
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField, append_field},
+    protos::{
+        common::builtin_clock::BuiltinClock,
+        trace::{
+            clock_snapshot::{ClockSnapshotClockFieldNumber, ClockSnapshotFieldNumber},
+            trace_packet::{
+                TracePacket, TracePacketDefaultsFieldNumber, TracePacketFieldNumber,
+                TracePacketSequenceFlags,
+            },
+        },
+    },
+    trace_reader::{TraceReader, TraceReaderError, decompress_packets},
+};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Packet relay errors.
+#[derive(Error, Debug)]
+pub enum RelayError {
+    /// The relayed trace could not be read.
+    #[error("Failed to read relayed trace: {0}")]
+    Trace(#[from] TraceReaderError),
+    /// A relayed packet could not be decoded.
+    #[error("Failed to decode relayed packet: {0}")]
+    Decode(#[from] PbDecoderError),
+    /// The writer thread of a relayed sequence could not be created.
+    #[error("Failed to create relayed packet sequence: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+// TracePacket fields that are set by the tracing service. Producers are not
+// allowed to write them and the service drops packets that do.
+const TRACE_CONFIG_FIELD: u32 = 33;
+const TRACE_STATS_FIELD: u32 = 35;
+const SYNCHRONIZATION_MARKER_FIELD: u32 = 36;
+const SERVICE_EVENT_FIELD: u32 = 69;
+const TRUSTED_PID_FIELD: u32 = 79;
+const MACHINE_ID_FIELD: u32 = 98;
+const TRACE_PROVENANCE_FIELD: u32 = 124;
+const PROTOVMS_FIELD: u32 = 125;
+const ZSTD_COMPRESSED_PACKETS_FIELD: u32 = 133;
+
+fn is_reserved_field(field_id: u32) -> bool {
+    field_id == TracePacketFieldNumber::TrustedUid as u32
+        || field_id == TracePacketFieldNumber::TrustedPacketSequenceId as u32
+        || matches!(
+            field_id,
+            TRACE_CONFIG_FIELD
+                | TRACE_STATS_FIELD
+                | SYNCHRONIZATION_MARKER_FIELD
+                | SERVICE_EVENT_FIELD
+                | TRUSTED_PID_FIELD
+                | MACHINE_ID_FIELD
+                | TRACE_PROVENANCE_FIELD
+                | PROTOVMS_FIELD
+                | ZSTD_COMPRESSED_PACKETS_FIELD
+        )
+}
+
+// Clock ids above this are defined by a packet sequence and are delta encoded
+// or relative to a builtin clock, so they are never translated.
+const MAX_BUILTIN_CLOCK_ID: u32 = 63;
+
+/// Forwards the packets of traces recorded elsewhere, e.g. by producers in a
+/// VM guest or on a co-processor, into the local tracing session.
+///
+/// Serialized `Trace` data or individual `TracePacket`s are received over a
+/// channel of the user's choice and passed to the relay. The packets of each
+/// remote packet sequence, identified by their `trusted_packet_sequence_id`,
+/// are written to a dedicated local [`PacketSequence`] so that incremental
+/// state, interned data and sequence flags keep their meaning.
+///
+/// Fields that only the tracing service is allowed to set, such as the
+/// trusted uid, pid and sequence id, are stripped from the relayed packets.
+/// The `machine_id` of packets is also assigned by the service, based on the
+/// producer connection. Packets relayed from another machine are therefore
+/// attributed to the local machine and should be distinguishable by their
+/// own content, e.g. by using a per-remote data source name.
+///
+/// Remote timestamps are translated into the local clock domain using the
+/// offsets set with [`set_clock_offset`](Self::set_clock_offset). Offsets
+/// apply to packet timestamps and to the clocks of relayed `ClockSnapshot`
+/// packets, which keeps remote sequence-scoped clocks consistent.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     data_source::{DataSource, DataSourceArgsBuilder},
+///     relay::PacketRelay,
+/// };
+/// use std::sync::OnceLock;
+///
+/// static DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+/// let data_source = DATA_SOURCE.get_or_init(|| {
+///     let mut data_source = DataSource::new();
+///     data_source
+///         .register("com.example.guest_relay", DataSourceArgsBuilder::new().build())
+///         .unwrap();
+///     data_source
+/// });
+/// let mut relay = PacketRelay::new("guest", data_source);
+/// // The guest boot clock started 1s after the host one.
+/// relay.set_clock_offset(6, 1_000_000_000);
+/// # let received_from_guest: Vec<u8> = vec![];
+/// relay.forward_trace(&received_from_guest).unwrap();
+/// relay.sync();
+/// ```
+pub struct PacketRelay {
+    name: String,
+    data_source: &'static DataSource<'static>,
+    clock_offsets: HashMap<u32, i64>,
+    sequences: HashMap<u64, RemoteSequence>,
+}
+
+struct RemoteSequence {
+    local: PacketSequence,
+    default_clock_id: Option<u32>,
+}
+
+impl PacketRelay {
+    /// Creates a relay that writes the forwarded packets to the instances of
+    /// `data_source`. `name` is used to name the writer threads of the
+    /// relayed sequences.
+    pub fn new(name: impl Into<String>, data_source: &'static DataSource<'static>) -> Self {
+        Self {
+            name: name.into(),
+            data_source,
+            clock_offsets: HashMap::new(),
+            sequences: HashMap::new(),
+        }
+    }
+
+    /// Sets the offset, in nanoseconds, to add to remote timestamps of
+    /// builtin clock `clock_id` to translate them to the local clock.
+    /// Timestamps are forwarded unchanged for clocks without an offset.
+    pub fn set_clock_offset(&mut self, clock_id: u32, offset_ns: i64) -> &mut Self {
+        self.clock_offsets.insert(clock_id, offset_ns);
+        self
+    }
+
+    /// Sets the offset of builtin clock `clock_id` from a pair of remote and
+    /// local timestamps sampled at the same time.
+    pub fn set_clock_sync(&mut self, clock_id: u32, remote_ns: u64, local_ns: u64) -> &mut Self {
+        self.set_clock_offset(clock_id, local_ns.wrapping_sub(remote_ns) as i64)
+    }
+
+    /// Forwards all the packets of the serialized `Trace` in `data`.
+    pub fn forward_trace(&mut self, data: &[u8]) -> Result<(), RelayError> {
+        for packet in TraceReader::new(data) {
+            self.forward_packet(&packet?)?;
+        }
+        Ok(())
+    }
+
+    /// Forwards the serialized `TracePacket` in `packet`.
+    pub fn forward_packet(&mut self, packet: &[u8]) -> Result<(), RelayError> {
+        let mut sequence_id = 0;
+        let mut clock_id = None;
+        let mut defaults_clock_id = None;
+        let mut cleared = false;
+        for item in PbDecoder::new(packet) {
+            match item? {
+                (id, PbDecoderField::Delimited(data))
+                    if id == TracePacketFieldNumber::CompressedPackets as u32 =>
+                {
+                    for packet in decompress_packets(data)? {
+                        self.forward_packet(&packet)?;
+                    }
+                    return Ok(());
+                }
+                (id, PbDecoderField::Varint(value))
+                    if id == TracePacketFieldNumber::TrustedPacketSequenceId as u32 =>
+                {
+                    sequence_id = value;
+                }
+                (id, PbDecoderField::Varint(value))
+                    if id == TracePacketFieldNumber::TimestampClockId as u32 =>
+                {
+                    clock_id = Some(value as u32);
+                }
+                (id, PbDecoderField::Varint(value))
+                    if id == TracePacketFieldNumber::SequenceFlags as u32 =>
+                {
+                    cleared |=
+                        value & TracePacketSequenceFlags::SeqIncrementalStateCleared as u64 != 0;
+                }
+                (id, PbDecoderField::Delimited(data))
+                    if id == TracePacketFieldNumber::TracePacketDefaults as u32 =>
+                {
+                    for item in PbDecoder::new(data) {
+                        if let (id, PbDecoderField::Varint(value)) = item?
+                            && id == TracePacketDefaultsFieldNumber::TimestampClockId as u32
+                        {
+                            defaults_clock_id = Some(value as u32);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let sequence = match self.sequences.entry(sequence_id) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => entry.insert(RemoteSequence {
+                local: PacketSequence::new(&format!("{}-{}", self.name, sequence_id))?,
+                default_clock_id: None,
+            }),
+        };
+        if cleared {
+            sequence.default_clock_id = None;
+        }
+        if defaults_clock_id.is_some() {
+            sequence.default_clock_id = defaults_clock_id;
+        }
+        let clock_id = clock_id
+            .or(sequence.default_clock_id)
+            .unwrap_or(BuiltinClock::BuiltinClockBoottime as u32);
+
+        let relayed = rewrite_packet(packet, self.clock_offsets.get(&clock_id).copied(), |id| {
+            self.clock_offsets.get(&id).copied()
+        })?;
+        sequence
+            .local
+            .trace(self.data_source, move |ctx: &mut TraceContext| {
+                ctx.add_packet(|packet: &mut TracePacket| {
+                    packet.msg.append_bytes(&relayed);
+                });
+            });
+        Ok(())
+    }
+
+    /// Waits until all the packets forwarded so far have been written.
+    pub fn sync(&self) {
+        for sequence in self.sequences.values() {
+            sequence.local.sync();
+        }
+    }
+}
+
+fn translate(timestamp: u64, offset: Option<i64>) -> u64 {
+    match offset {
+        Some(offset) => timestamp.wrapping_add_signed(offset),
+        None => timestamp,
+    }
+}
+
+/// Re-encodes `packet` without the fields reserved to the tracing service.
+/// The packet timestamp is translated using `offset` and the clocks of a
+/// `ClockSnapshot` using the offsets returned by `clock_offset`.
+fn rewrite_packet<F>(
+    packet: &[u8],
+    offset: Option<i64>,
+    clock_offset: F,
+) -> Result<Vec<u8>, PbDecoderError>
+where
+    F: Fn(u32) -> Option<i64>,
+{
+    let mut relayed = Vec::with_capacity(packet.len());
+    for item in PbDecoder::new(packet) {
+        match item? {
+            (id, _) if is_reserved_field(id) => {}
+            (id, PbDecoderField::Varint(value))
+                if id == TracePacketFieldNumber::Timestamp as u32 =>
+            {
+                append_field(
+                    &mut relayed,
+                    id,
+                    &PbDecoderField::Varint(translate(value, offset)),
+                );
+            }
+            (id, PbDecoderField::Delimited(data))
+                if id == TracePacketFieldNumber::ClockSnapshot as u32 =>
+            {
+                let snapshot = rewrite_clock_snapshot(data, &clock_offset)?;
+                append_field(&mut relayed, id, &PbDecoderField::Delimited(&snapshot));
+            }
+            (id, field) => append_field(&mut relayed, id, &field),
+        }
+    }
+    Ok(relayed)
+}
+
+fn rewrite_clock_snapshot<F>(snapshot: &[u8], clock_offset: &F) -> Result<Vec<u8>, PbDecoderError>
+where
+    F: Fn(u32) -> Option<i64>,
+{
+    let mut relayed = Vec::with_capacity(snapshot.len());
+    for item in PbDecoder::new(snapshot) {
+        match item? {
+            (id, PbDecoderField::Delimited(data))
+                if id == ClockSnapshotFieldNumber::Clocks as u32 =>
+            {
+                let mut clock_id = None;
+                for item in PbDecoder::new(data) {
+                    if let (id, PbDecoderField::Varint(value)) = item?
+                        && id == ClockSnapshotClockFieldNumber::ClockId as u32
+                    {
+                        clock_id = Some(value as u32);
+                    }
+                }
+                let offset = clock_id
+                    .filter(|clock_id| *clock_id <= MAX_BUILTIN_CLOCK_ID)
+                    .and_then(clock_offset);
+                let mut clock = Vec::with_capacity(data.len());
+                for item in PbDecoder::new(data) {
+                    match item? {
+                        (id, PbDecoderField::Varint(value))
+                            if id == ClockSnapshotClockFieldNumber::Timestamp as u32 =>
+                        {
+                            let value = translate(value, offset);
+                            append_field(&mut clock, id, &PbDecoderField::Varint(value));
+                        }
+                        (id, field) => append_field(&mut clock, id, &field),
+                    }
+                }
+                append_field(&mut relayed, id, &PbDecoderField::Delimited(&clock));
+            }
+            (id, field) => append_field(&mut relayed, id, &field),
+        }
+    }
+    Ok(relayed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        heap_buffer::HeapBuffer,
+        pb_msg::{PbMsg, PbMsgWriter},
+        protos::trace::{
+            clock_snapshot::{ClockSnapshot, ClockSnapshotClock},
+            test_event::{TestEvent, TestEventFieldNumber},
+            trace::Trace,
+        },
+        test_util::{DATA_SOURCE_NAME, data_source, field},
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+    };
+    use std::error::Error;
+
+    // Encodes a remote trace with packets on sequences 7 and 9.
+    fn encode_remote_trace() -> Vec<u8> {
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        {
+            let mut trace = Trace { msg: &mut msg };
+            for (sequence_id, timestamp, clock_id, counter) in
+                [(7, 100, None, 1), (9, 200, Some(3), 2)]
+            {
+                trace.set_packet(|packet: &mut TracePacket| {
+                    packet.set_trusted_uid(1234);
+                    packet.set_trusted_packet_sequence_id(sequence_id);
+                    packet.set_timestamp(timestamp);
+                    if let Some(clock_id) = clock_id {
+                        packet.set_timestamp_clock_id(clock_id);
+                    }
+                    packet.set_for_testing(|for_testing: &mut TestEvent| {
+                        for_testing.set_counter(counter);
+                    });
+                });
+            }
+            trace.set_packet(|packet: &mut TracePacket| {
+                packet.set_trusted_packet_sequence_id(7);
+                packet.set_clock_snapshot(|snapshot: &mut ClockSnapshot| {
+                    snapshot.set_clocks(|clock: &mut ClockSnapshotClock| {
+                        clock.set_clock_id(6);
+                        clock.set_timestamp(1000);
+                    });
+                    snapshot.set_clocks(|clock: &mut ClockSnapshotClock| {
+                        clock.set_clock_id(64);
+                        clock.set_timestamp(1000);
+                    });
+                });
+            });
+        }
+        msg.finalize();
+        let size = writer.writer.get_written_size();
+        let mut buffer = vec![0u8; size];
+        hb.copy_into(&mut buffer);
+        buffer
+    }
+
+    #[test]
+    fn strips_reserved_fields() {
+        let trace = encode_remote_trace();
+        let packet = TraceReader::new(&trace).next().unwrap().unwrap();
+        let relayed = rewrite_packet(&packet, Some(-50), |_| None).unwrap();
+        assert!(field(&relayed, TracePacketFieldNumber::TrustedUid as u32).is_none());
+        assert!(
+            field(
+                &relayed,
+                TracePacketFieldNumber::TrustedPacketSequenceId as u32
+            )
+            .is_none()
+        );
+        assert_eq!(
+            field(&relayed, TracePacketFieldNumber::Timestamp as u32),
+            Some(PbDecoderField::Varint(50))
+        );
+        assert_eq!(
+            field(&relayed, TracePacketFieldNumber::ForTesting as u32),
+            field(&packet, TracePacketFieldNumber::ForTesting as u32)
+        );
+    }
+
+    #[test]
+    fn forward_trace() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let mut relay = PacketRelay::new("relay", data_source);
+        relay.set_clock_offset(6, 50);
+        relay.forward_trace(&encode_remote_trace())?;
+        relay.sync();
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        // (counter, timestamp, local sequence id) of the relayed test events.
+        let mut events = vec![];
+        let mut snapshot_clocks = vec![];
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            let Some(PbDecoderField::Varint(sequence_id)) = field(
+                &packet,
+                TracePacketFieldNumber::TrustedPacketSequenceId as u32,
+            ) else {
+                continue;
+            };
+            if let Some(PbDecoderField::Delimited(for_testing)) =
+                field(&packet, TracePacketFieldNumber::ForTesting as u32)
+                && let Some(PbDecoderField::Varint(counter)) =
+                    field(for_testing, TestEventFieldNumber::Counter as u32)
+                && let Some(PbDecoderField::Varint(timestamp)) =
+                    field(&packet, TracePacketFieldNumber::Timestamp as u32)
+            {
+                events.push((counter, timestamp, sequence_id));
+            }
+            if let Some(PbDecoderField::Delimited(snapshot)) =
+                field(&packet, TracePacketFieldNumber::ClockSnapshot as u32)
+            {
+                for (_, clock) in PbDecoder::new(snapshot).map(|f| f.unwrap()) {
+                    let PbDecoderField::Delimited(clock) = clock else {
+                        continue;
+                    };
+                    let Some(PbDecoderField::Varint(timestamp)) =
+                        field(clock, ClockSnapshotClockFieldNumber::Timestamp as u32)
+                    else {
+                        continue;
+                    };
+                    snapshot_clocks.push((timestamp, sequence_id));
+                }
+            }
+        }
+        assert_eq!(events.len(), 2);
+        assert_eq!((events[0].0, events[0].1), (1, 150));
+        assert_eq!((events[1].0, events[1].1), (2, 200));
+        assert_ne!(events[0].2, events[1].2);
+        // Only the boot clock of the snapshot relayed on sequence 7 is translated.
+        snapshot_clocks.retain(|(_, sequence_id)| *sequence_id == events[0].2);
+        assert_eq!(
+            snapshot_clocks,
+            vec![(1050, events[0].2), (1000, events[0].2)]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,517 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{DataSourceError, DataSourceTimestamp, TraceContext},
+    polling::{PollingDataSource, PollingDataSourceBuilder},
+    protos::trace::{
+        trace_packet::TracePacket,
+        track_event::{
+            counter_descriptor::{CounterDescriptor, CounterDescriptorUnit},
+            track_descriptor::TrackDescriptor,
+            track_event::{TrackEvent, TrackEventType},
+        },
+    },
+    track_event::TrackEventTrack,
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Name of the data source registered by [`RuntimeMetricsDataSourceBuilder`].
+pub const RUNTIME_METRICS_DATA_SOURCE_NAME: &str = "perfetto.sdk.runtime_metrics";
+
+/// Default sampling period of the runtime metrics data source.
+pub const DEFAULT_RUNTIME_METRICS_PERIOD: Duration = Duration::from_secs(1);
+
+/// Name of the track the counter tracks are nested under.
+pub const RUNTIME_METRICS_TRACK_NAME: &str = "Rust runtime";
+
+// Allocations are counted with plain atomics rather than sharded counters, as
+// the allocator can't use thread locals that may allocate themselves.
+static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+static FREED_BYTES: AtomicU64 = AtomicU64::new(0);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Global allocator that counts the allocations of the process, for the
+/// allocation counters of the runtime metrics. Wraps another allocator,
+/// [`System`] by default.
+///
+/// Counting adds two relaxed atomic additions to each allocation.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::runtime_metrics::CountingAllocator;
+/// use std::alloc::System;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator = CountingAllocator::new(System);
+/// ```
+#[derive(Debug, Default)]
+pub struct CountingAllocator<A = System> {
+    inner: A,
+}
+
+impl<A> CountingAllocator<A> {
+    /// Creates an allocator that counts the allocations of `inner`.
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+}
+
+fn record_alloc(size: usize) {
+    ALLOCATED_BYTES.fetch_add(size as u64, Ordering::Relaxed);
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+fn record_dealloc(size: usize) {
+    FREED_BYTES.fetch_add(size as u64, Ordering::Relaxed);
+    DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+// SAFETY: All allocations are forwarded to the inner allocator, which upholds
+// the contract of `GlobalAlloc`.
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // SAFETY: The caller upholds the contract of `GlobalAlloc::alloc`.
+        let ptr = unsafe { self.inner.alloc(layout) };
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        // SAFETY: The caller upholds the contract of
+        // `GlobalAlloc::alloc_zeroed`.
+        let ptr = unsafe { self.inner.alloc_zeroed(layout) };
+        if !ptr.is_null() {
+            record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: The caller upholds the contract of `GlobalAlloc::dealloc`.
+        unsafe { self.inner.dealloc(ptr, layout) };
+        record_dealloc(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        // SAFETY: The caller upholds the contract of `GlobalAlloc::realloc`.
+        let new_ptr = unsafe { self.inner.realloc(ptr, layout, new_size) };
+        if !new_ptr.is_null() {
+            ALLOCATED_BYTES.fetch_add(new_size as u64, Ordering::Relaxed);
+            FREED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+/// Metrics of the Rust runtime of the current process, see
+/// [`runtime_metrics`]. Metrics that aren't available are `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RuntimeMetrics {
+    /// Number of bytes currently allocated, if [`CountingAllocator`] is the
+    /// global allocator.
+    pub allocated_bytes: Option<u64>,
+    /// Number of live allocations, if [`CountingAllocator`] is the global
+    /// allocator.
+    pub allocations: Option<u64>,
+    /// Number of threads of the process. Only available on Linux and
+    /// Android.
+    pub threads: Option<u64>,
+    /// Number of worker threads of the tokio runtime.
+    pub tokio_workers: Option<u64>,
+    /// Number of alive tasks of the tokio runtime.
+    pub tokio_alive_tasks: Option<u64>,
+    /// Number of tasks in the global queue of the tokio runtime.
+    pub tokio_global_queue_depth: Option<u64>,
+}
+
+fn thread_count() -> Option<u64> {
+    if cfg!(any(target_os = "linux", target_os = "android")) {
+        std::fs::read_dir("/proc/self/task")
+            .ok()
+            .map(|tasks| tasks.count() as u64)
+    } else {
+        None
+    }
+}
+
+/// Returns the allocation and thread metrics of the process. The tokio
+/// metrics are only sampled by the data source of a
+/// [`RuntimeMetricsDataSourceBuilder`] given the runtime.
+pub fn runtime_metrics() -> RuntimeMetrics {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    // Nothing was allocated through the counting allocator, so it isn't the
+    // global allocator.
+    let counted = allocations > 0;
+    RuntimeMetrics {
+        allocated_bytes: counted.then(|| {
+            ALLOCATED_BYTES
+                .load(Ordering::Relaxed)
+                .saturating_sub(FREED_BYTES.load(Ordering::Relaxed))
+        }),
+        allocations: counted
+            .then(|| allocations.saturating_sub(DEALLOCATIONS.load(Ordering::Relaxed))),
+        threads: thread_count(),
+        ..Default::default()
+    }
+}
+
+// Counter tracks written by the data source, with their unit and value.
+fn counters(metrics: &RuntimeMetrics) -> [(&'static str, CounterDescriptorUnit, Option<u64>); 6] {
+    [
+        (
+            "allocated_bytes",
+            CounterDescriptorUnit::UnitSizeBytes,
+            metrics.allocated_bytes,
+        ),
+        (
+            "allocations",
+            CounterDescriptorUnit::UnitCount,
+            metrics.allocations,
+        ),
+        ("threads", CounterDescriptorUnit::UnitCount, metrics.threads),
+        (
+            "tokio_workers",
+            CounterDescriptorUnit::UnitCount,
+            metrics.tokio_workers,
+        ),
+        (
+            "tokio_alive_tasks",
+            CounterDescriptorUnit::UnitCount,
+            metrics.tokio_alive_tasks,
+        ),
+        (
+            "tokio_global_queue_depth",
+            CounterDescriptorUnit::UnitCount,
+            metrics.tokio_global_queue_depth,
+        ),
+    ]
+}
+
+fn write_parent_descriptor(ctx: &mut TraceContext, parent_uuid: u64) {
+    ctx.add_packet(|packet: &mut TracePacket| {
+        packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+            desc.set_uuid(parent_uuid);
+            desc.set_name(RUNTIME_METRICS_TRACK_NAME);
+        });
+    });
+}
+
+fn write_counter_descriptor(
+    ctx: &mut TraceContext,
+    parent_uuid: u64,
+    name: &str,
+    unit: CounterDescriptorUnit,
+) {
+    ctx.add_packet(|packet: &mut TracePacket| {
+        packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+            desc.set_uuid(TrackEventTrack::counter_track_uuid(name, parent_uuid));
+            desc.set_parent_uuid(parent_uuid);
+            desc.set_name(name);
+            desc.set_counter(|counter: &mut CounterDescriptor| {
+                counter.set_unit(unit);
+            });
+        });
+    });
+}
+
+// Writes the available metrics of `metrics` as counter events. `described` is
+// the bitmask of the counter tracks already described on the sequence of the
+// instance, as metrics can become available after the first sample, e.g. once
+// the counting allocator has allocated.
+fn write_counters(
+    ctx: &mut TraceContext,
+    parent_uuid: u64,
+    metrics: &RuntimeMetrics,
+    described: &mut u8,
+) {
+    ctx.with_incremental_state(|ctx, state| {
+        if state.was_cleared {
+            write_parent_descriptor(ctx, parent_uuid);
+            *described = 0;
+            state.was_cleared = false;
+        }
+    });
+    let timestamp = DataSourceTimestamp::now();
+    for (index, (name, unit, value)) in counters(metrics).into_iter().enumerate() {
+        let Some(value) = value else {
+            continue;
+        };
+        if *described & (1 << index) == 0 {
+            write_counter_descriptor(ctx, parent_uuid, name, unit);
+            *described |= 1 << index;
+        }
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet.set_timestamp(timestamp.timestamp());
+            packet.set_timestamp_clock_id(timestamp.clock_id());
+            packet.set_track_event(|event: &mut TrackEvent| {
+                event.set_type(TrackEventType::TypeCounter);
+                event.set_track_uuid(TrackEventTrack::counter_track_uuid(name, parent_uuid));
+                event.set_counter(value);
+            });
+        });
+    }
+}
+
+/// Runtime metrics data source builder.
+///
+/// The data source named [`RUNTIME_METRICS_DATA_SOURCE_NAME`] writes the
+/// available [`RuntimeMetrics`] as counter tracks at its sampling period,
+/// nested under a track named [`RUNTIME_METRICS_TRACK_NAME`]. The allocation
+/// counters need [`CountingAllocator`] to be the global allocator, and the
+/// tokio counters need the `tokio` feature and a runtime, see
+/// [`Self::tokio_runtime`].
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::runtime_metrics::{CountingAllocator, RuntimeMetricsDataSourceBuilder};
+/// use std::alloc::System;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator = CountingAllocator::new(System);
+///
+/// let _runtime_metrics = RuntimeMetricsDataSourceBuilder::new().register().unwrap();
+/// ```
+#[must_use = "This is a builder; remember to call `.register()` (or keep chaining)."]
+pub struct RuntimeMetricsDataSourceBuilder {
+    period: Duration,
+    #[cfg(feature = "tokio")]
+    tokio_runtime: Option<tokio::runtime::Handle>,
+}
+
+impl Default for RuntimeMetricsDataSourceBuilder {
+    fn default() -> Self {
+        Self {
+            period: DEFAULT_RUNTIME_METRICS_PERIOD,
+            #[cfg(feature = "tokio")]
+            tokio_runtime: None,
+        }
+    }
+}
+
+impl RuntimeMetricsDataSourceBuilder {
+    /// Create new runtime metrics data source builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the sampling period. Defaults to
+    /// [`DEFAULT_RUNTIME_METRICS_PERIOD`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn period(mut self, period: Duration) -> Self {
+        self.period = period;
+        self
+    }
+
+    /// Set the tokio runtime whose worker, task and queue counts are
+    /// sampled, e.g. `tokio::runtime::Handle::current()`.
+    #[cfg(feature = "tokio")]
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn tokio_runtime(mut self, handle: tokio::runtime::Handle) -> Self {
+        self.tokio_runtime = Some(handle);
+        self
+    }
+
+    /// Registers the data source named [`RUNTIME_METRICS_DATA_SOURCE_NAME`].
+    pub fn register(self) -> Result<PollingDataSource, DataSourceError> {
+        let parent_uuid = TrackEventTrack::named_track_uuid(
+            RUNTIME_METRICS_TRACK_NAME,
+            std::process::id().into(),
+            0,
+        );
+        #[cfg(feature = "tokio")]
+        let tokio_runtime = self.tokio_runtime;
+        // Counter tracks described for each instance, reset with the
+        // incremental state of the instance.
+        let mut described: HashMap<u32, u8> = HashMap::new();
+        PollingDataSourceBuilder::new()
+            .default_period(self.period)
+            .register(
+                RUNTIME_METRICS_DATA_SOURCE_NAME,
+                move |ctx: &mut TraceContext| {
+                    #[allow(unused_mut)]
+                    let mut metrics = runtime_metrics();
+                    #[cfg(feature = "tokio")]
+                    if let Some(handle) = &tokio_runtime {
+                        let tokio_metrics = handle.metrics();
+                        metrics.tokio_workers = Some(tokio_metrics.num_workers() as u64);
+                        metrics.tokio_alive_tasks = Some(tokio_metrics.num_alive_tasks() as u64);
+                        metrics.tokio_global_queue_depth =
+                            Some(tokio_metrics.global_queue_depth() as u64);
+                    }
+                    let described = described.entry(ctx.instance_index()).or_default();
+                    write_counters(ctx, parent_uuid, &metrics, described);
+                },
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_decoder::PbDecoderField,
+        protos::trace::{
+            trace_packet::TracePacketFieldNumber,
+            track_event::{
+                track_descriptor::TrackDescriptorFieldNumber, track_event::TrackEventFieldNumber,
+            },
+        },
+        test_util::field,
+        test_util::{DATA_SOURCE_NAME, data_source},
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        trace_reader::TraceReader,
+    };
+    use std::{error::Error, thread};
+
+    #[test]
+    fn counts_allocations() {
+        let allocator = CountingAllocator::new(System);
+        let layout = Layout::from_size_align(64, 8).unwrap();
+        // SAFETY: The layout has a non-zero size, and the allocation is freed
+        // with the same layout after being grown.
+        unsafe {
+            let ptr = allocator.alloc(layout);
+            assert!(!ptr.is_null());
+            let metrics = runtime_metrics();
+            assert!(metrics.allocated_bytes.unwrap() >= 64);
+            assert!(metrics.allocations.unwrap() >= 1);
+            let ptr = allocator.realloc(ptr, layout, 128);
+            assert!(!ptr.is_null());
+            assert!(runtime_metrics().allocated_bytes.unwrap() >= 128);
+            allocator.dealloc(ptr, Layout::from_size_align(128, 8).unwrap());
+        }
+        assert_eq!(runtime_metrics().allocated_bytes, Some(0));
+        assert_eq!(runtime_metrics().allocations, Some(0));
+    }
+
+    #[test]
+    fn writes_counter_tracks() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let _data_source = RuntimeMetricsDataSourceBuilder::new()
+            .period(Duration::from_millis(1))
+            .register()?;
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(RUNTIME_METRICS_DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        thread::sleep(Duration::from_millis(20));
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        let parent_uuid = TrackEventTrack::named_track_uuid(
+            RUNTIME_METRICS_TRACK_NAME,
+            std::process::id().into(),
+            0,
+        );
+        let threads_uuid = TrackEventTrack::counter_track_uuid("threads", parent_uuid);
+        let mut described = false;
+        let mut values = vec![];
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            if let Some(PbDecoderField::Delimited(desc)) =
+                field(&packet, TracePacketFieldNumber::TrackDescriptor as u32)
+                && field(desc, TrackDescriptorFieldNumber::Uuid as u32)
+                    == Some(PbDecoderField::Varint(threads_uuid))
+            {
+                described = true;
+            }
+            if let Some(PbDecoderField::Delimited(event)) =
+                field(&packet, TracePacketFieldNumber::TrackEvent as u32)
+                && field(event, TrackEventFieldNumber::TrackUuid as u32)
+                    == Some(PbDecoderField::Varint(threads_uuid))
+                && let Some(PbDecoderField::Varint(value)) =
+                    field(event, TrackEventFieldNumber::CounterValue as u32)
+            {
+                values.push(value);
+            }
+        }
+        if cfg!(target_os = "linux") {
+            assert!(described);
+            assert!(!values.is_empty());
+            // At least the test thread and the polling thread.
+            assert!(values.iter().all(|threads| *threads >= 2));
+        } else {
+            assert!(!described);
+            assert!(values.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn describes_metrics_that_become_available() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let parent_uuid = TrackEventTrack::named_track_uuid(
+            RUNTIME_METRICS_TRACK_NAME,
+            std::process::id().into(),
+            0,
+        );
+        // The runtime only shows up after a few samples.
+        let mut described = 0;
+        for sample in 0..6 {
+            let metrics = RuntimeMetrics {
+                tokio_workers: (sample > 2).then_some(4),
+                ..Default::default()
+            };
+            data_source.trace(|ctx: &mut TraceContext| {
+                write_counters(ctx, parent_uuid, &metrics, &mut described);
+            });
+        }
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        let workers_uuid = TrackEventTrack::counter_track_uuid("tokio_workers", parent_uuid);
+        let mut descriptors = 0;
+        let mut values = vec![];
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            if let Some(PbDecoderField::Delimited(desc)) =
+                field(&packet, TracePacketFieldNumber::TrackDescriptor as u32)
+                && field(desc, TrackDescriptorFieldNumber::Uuid as u32)
+                    == Some(PbDecoderField::Varint(workers_uuid))
+            {
+                descriptors += 1;
+            }
+            if let Some(PbDecoderField::Delimited(event)) =
+                field(&packet, TracePacketFieldNumber::TrackEvent as u32)
+                && field(event, TrackEventFieldNumber::TrackUuid as u32)
+                    == Some(PbDecoderField::Varint(workers_uuid))
+                && let Some(PbDecoderField::Varint(value)) =
+                    field(event, TrackEventFieldNumber::CounterValue as u32)
+            {
+                // The track is described before its first value.
+                assert_eq!(descriptors, 1);
+                values.push(value);
+            }
+        }
+        assert_eq!(descriptors, 1);
+        assert_eq!(values, vec![4, 4, 4]);
+        Ok(())
+    }
+}
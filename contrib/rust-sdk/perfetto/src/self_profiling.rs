@@ -0,0 +1,316 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{
+        DataSourceError, DataSourceTimestamp, ShardedCounter, TraceContext, registered_stats,
+    },
+    polling::{PollingDataSource, PollingDataSourceBuilder},
+    protos::trace::{
+        trace_packet::TracePacket,
+        track_event::{
+            counter_descriptor::{CounterDescriptor, CounterDescriptorUnit},
+            track_descriptor::TrackDescriptor,
+            track_event::{TrackEvent, TrackEventType},
+        },
+    },
+    track_event::TrackEventTrack,
+};
+use perfetto_sdk_sys::*;
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+/// Name of the data source registered by [`register`].
+pub const SELF_PROFILING_DATA_SOURCE_NAME: &str = "perfetto.sdk.self_profiling";
+
+/// Default sampling period of the data source registered by [`register`].
+pub const DEFAULT_SELF_PROFILING_PERIOD: Duration = Duration::from_millis(100);
+
+/// Name of the track the counter tracks are nested under.
+pub const SELF_PROFILING_TRACK_NAME: &str = "Perfetto SDK";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static TRACK_EVENTS: ShardedCounter = ShardedCounter::new();
+static TRACE_TIME_NS: ShardedCounter = ShardedCounter::new();
+
+/// Overhead of the SDK in the current process, see [`stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SelfProfilingStats {
+    /// Number of trace packets written by the data sources registered with
+    /// [`DataSource::register`](crate::data_source::DataSource::register).
+    pub packets_written: u64,
+    /// Number of bytes written in those trace packets.
+    pub bytes_written: u64,
+    /// Number of track events emitted.
+    pub track_events_emitted: u64,
+    /// Time spent in trace callbacks and emitting track events while
+    /// self-profiling was enabled.
+    pub trace_time: Duration,
+    /// Number of times a writer stalled because the shared memory buffer was
+    /// full.
+    pub stalled_chunk_requests: u64,
+    /// Number of times a writer dropped data because the shared memory buffer
+    /// was full.
+    pub dropped_chunk_requests: u64,
+}
+
+/// Enables or disables measuring the time spent in trace callbacks and
+/// counting track events. Enabled by [`register`].
+///
+/// Measuring adds two clock reads to each trace callback and track event.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns true if self-profiling is enabled.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// Calls `f`, accounting the time it takes if self-profiling is enabled.
+pub(crate) fn measure<R>(f: impl FnOnce() -> R) -> R {
+    if !is_enabled() {
+        return f();
+    }
+    let start = Instant::now();
+    let result = f();
+    TRACE_TIME_NS.add(start.elapsed().as_nanos() as u64);
+    result
+}
+
+// Counts a track event, if self-profiling is enabled.
+pub(crate) fn record_track_event() {
+    if is_enabled() {
+        TRACK_EVENTS.add(1);
+    }
+}
+
+/// Returns the overhead of the SDK since the process started. Packets and
+/// bytes are always accounted; time and track events only while
+/// self-profiling is enabled.
+pub fn stats() -> SelfProfilingStats {
+    let data_sources = registered_stats();
+    SelfProfilingStats {
+        packets_written: data_sources.packets_written,
+        bytes_written: data_sources.bytes_written,
+        track_events_emitted: TRACK_EVENTS.get(),
+        trace_time: Duration::from_nanos(TRACE_TIME_NS.get()),
+        // SAFETY: FFI calls with no outstanding preconditions.
+        stalled_chunk_requests: unsafe { PerfettoProducerGetStalledChunkRequests() },
+        // SAFETY: See above.
+        dropped_chunk_requests: unsafe { PerfettoProducerGetDroppedChunkRequests() },
+    }
+}
+
+// Counter tracks written by the data source, with their unit and value.
+fn counters(stats: &SelfProfilingStats) -> [(&'static str, CounterDescriptorUnit, u64); 6] {
+    [
+        (
+            "packets_written",
+            CounterDescriptorUnit::UnitCount,
+            stats.packets_written,
+        ),
+        (
+            "bytes_written",
+            CounterDescriptorUnit::UnitSizeBytes,
+            stats.bytes_written,
+        ),
+        (
+            "track_events_emitted",
+            CounterDescriptorUnit::UnitCount,
+            stats.track_events_emitted,
+        ),
+        (
+            "trace_time",
+            CounterDescriptorUnit::UnitTimeNs,
+            stats.trace_time.as_nanos() as u64,
+        ),
+        (
+            "stalled_chunk_requests",
+            CounterDescriptorUnit::UnitCount,
+            stats.stalled_chunk_requests,
+        ),
+        (
+            "dropped_chunk_requests",
+            CounterDescriptorUnit::UnitCount,
+            stats.dropped_chunk_requests,
+        ),
+    ]
+}
+
+fn write_descriptors(ctx: &mut TraceContext, parent_uuid: u64) {
+    ctx.add_packet(|packet: &mut TracePacket| {
+        packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+            desc.set_uuid(parent_uuid);
+            desc.set_name(SELF_PROFILING_TRACK_NAME);
+        });
+    });
+    for (name, unit, _) in counters(&SelfProfilingStats::default()) {
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+                desc.set_uuid(TrackEventTrack::counter_track_uuid(name, parent_uuid));
+                desc.set_parent_uuid(parent_uuid);
+                desc.set_name(name);
+                desc.set_counter(|counter: &mut CounterDescriptor| {
+                    counter.set_unit(unit);
+                });
+            });
+        });
+    }
+}
+
+fn write_counters(ctx: &mut TraceContext, parent_uuid: u64) {
+    ctx.with_incremental_state(|ctx, state| {
+        if state.was_cleared {
+            write_descriptors(ctx, parent_uuid);
+            state.was_cleared = false;
+        }
+    });
+    let timestamp = DataSourceTimestamp::now();
+    for (name, _, value) in counters(&stats()) {
+        ctx.add_packet(|packet: &mut TracePacket| {
+            packet.set_timestamp(timestamp.timestamp());
+            packet.set_timestamp_clock_id(timestamp.clock_id());
+            packet.set_track_event(|event: &mut TrackEvent| {
+                event.set_type(TrackEventType::TypeCounter);
+                event.set_track_uuid(TrackEventTrack::counter_track_uuid(name, parent_uuid));
+                event.set_counter(value);
+            });
+        });
+    }
+}
+
+/// Registers the data source named [`SELF_PROFILING_DATA_SOURCE_NAME`], which
+/// writes the [`stats`] of the SDK as counter tracks every `period`, and
+/// enables self-profiling.
+///
+/// The counters are cumulative and include the packets written by this data
+/// source itself. They are nested under a track named
+/// [`SELF_PROFILING_TRACK_NAME`].
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::self_profiling::{self, DEFAULT_SELF_PROFILING_PERIOD};
+///
+/// let _self_profiling = self_profiling::register(DEFAULT_SELF_PROFILING_PERIOD).unwrap();
+/// ```
+pub fn register(period: Duration) -> Result<PollingDataSource, DataSourceError> {
+    let parent_uuid =
+        TrackEventTrack::named_track_uuid(SELF_PROFILING_TRACK_NAME, std::process::id().into(), 0);
+    let data_source = PollingDataSourceBuilder::new()
+        .default_period(period)
+        .register(
+            SELF_PROFILING_DATA_SOURCE_NAME,
+            move |ctx: &mut TraceContext| write_counters(ctx, parent_uuid),
+        )?;
+    set_enabled(true);
+    Ok(data_source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_decoder::PbDecoderField,
+        protos::trace::{
+            trace_packet::TracePacketFieldNumber,
+            track_event::{
+                track_descriptor::TrackDescriptorFieldNumber, track_event::TrackEventFieldNumber,
+            },
+        },
+        test_util::{DATA_SOURCE_NAME, data_source, field},
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        trace_reader::TraceReader,
+    };
+    use std::{error::Error, thread};
+
+    #[test]
+    fn measures_trace_callbacks() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        set_enabled(true);
+        let before = stats();
+        data_source.trace(|ctx: &mut TraceContext| {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_timestamp(1);
+            });
+            thread::sleep(Duration::from_millis(1));
+        });
+        let after = stats();
+        session.stop_blocking();
+        assert_eq!(after.packets_written, before.packets_written + 1);
+        assert!(after.bytes_written > before.bytes_written);
+        assert!(after.trace_time >= before.trace_time + Duration::from_millis(1));
+        assert!(after.stalled_chunk_requests >= before.stalled_chunk_requests);
+        Ok(())
+    }
+
+    #[test]
+    fn writes_counter_tracks() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let _data_source = register(Duration::from_millis(1))?;
+        assert!(is_enabled());
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(SELF_PROFILING_DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        thread::sleep(Duration::from_millis(20));
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        let parent_uuid = TrackEventTrack::named_track_uuid(
+            SELF_PROFILING_TRACK_NAME,
+            std::process::id().into(),
+            0,
+        );
+        let packets_uuid = TrackEventTrack::counter_track_uuid("packets_written", parent_uuid);
+        let mut described = false;
+        let mut values = vec![];
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            if let Some(PbDecoderField::Delimited(desc)) =
+                field(&packet, TracePacketFieldNumber::TrackDescriptor as u32)
+                && field(desc, TrackDescriptorFieldNumber::Uuid as u32)
+                    == Some(PbDecoderField::Varint(packets_uuid))
+            {
+                assert_eq!(
+                    field(desc, TrackDescriptorFieldNumber::ParentUuid as u32),
+                    Some(PbDecoderField::Varint(parent_uuid))
+                );
+                described = true;
+            }
+            if let Some(PbDecoderField::Delimited(event)) =
+                field(&packet, TracePacketFieldNumber::TrackEvent as u32)
+                && field(event, TrackEventFieldNumber::TrackUuid as u32)
+                    == Some(PbDecoderField::Varint(packets_uuid))
+                && let Some(PbDecoderField::Varint(value)) =
+                    field(event, TrackEventFieldNumber::CounterValue as u32)
+            {
+                values.push(value);
+            }
+        }
+        assert!(described);
+        assert!(values.len() >= 2);
+        // The counters include the packets of the data source itself.
+        assert!(values.windows(2).all(|w| w[0] < w[1]));
+        Ok(())
+    }
+}
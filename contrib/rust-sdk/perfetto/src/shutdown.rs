@@ -0,0 +1,576 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{
+        DataSourceTimestamp, TraceContextBase, is_in_trace, trace_all_data_sources,
+        try_trace_all_data_sources,
+    },
+    protos::trace::{
+        trace_packet::TracePacket,
+        track_event::{
+            debug_annotation::DebugAnnotation,
+            track_descriptor::TrackDescriptor,
+            track_event::{TrackEvent, TrackEventType},
+        },
+    },
+    track_event::{self as te, TrackEventTrack},
+};
+use std::{
+    backtrace::Backtrace,
+    panic::{self, PanicHookInfo},
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Name of the track and of the instant event of the marker written by
+/// [`shutdown`].
+pub const SHUTDOWN_MARKER_NAME: &str = "Shutdown";
+
+/// Name of the track and of the instant event of the marker written when a
+/// thread panics, see [`install_panic_hook`] and [`PanicGuard`].
+pub const PANIC_MARKER_NAME: &str = "Panic";
+
+type ShutdownHook = Box<dyn FnOnce() + Send + 'static>;
+
+static SHUT_DOWN: AtomicBool = AtomicBool::new(false);
+static HOOKS: OnceLock<Mutex<Vec<ShutdownHook>>> = OnceLock::new();
+
+fn hooks() -> &'static Mutex<Vec<ShutdownHook>> {
+    HOOKS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Registers `cb` to be called by [`shutdown`] before the data sources are
+/// flushed, e.g. to write final track events with
+/// [`EventContext::set_flush`](crate::track_event::EventContext::set_flush).
+pub fn on_shutdown<F>(cb: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    hooks().lock().unwrap().push(Box::new(cb));
+}
+
+fn write_marker(ctx: &mut TraceContextBase, name: &str, annotations: &[(&str, &str)]) {
+    let uuid = TrackEventTrack::named_track_uuid(name, std::process::id().into(), 0);
+    ctx.add_packet(|packet: &mut TracePacket| {
+        packet.set_track_descriptor(|desc: &mut TrackDescriptor| {
+            desc.set_uuid(uuid);
+            desc.set_name(name);
+        });
+    });
+    let timestamp = DataSourceTimestamp::now();
+    ctx.add_packet(|packet: &mut TracePacket| {
+        packet.set_timestamp(timestamp.timestamp());
+        packet.set_timestamp_clock_id(timestamp.clock_id());
+        packet.set_track_event(|event: &mut TrackEvent| {
+            event.set_type(TrackEventType::TypeInstant);
+            event.set_track_uuid(uuid);
+            event.set_name(name);
+            for (name, value) in annotations {
+                event.set_debug_annotations(|annotation: &mut DebugAnnotation| {
+                    annotation.set_name(*name);
+                    annotation.set_string_value(*value);
+                });
+            }
+        });
+    });
+}
+
+// Writes a marker to all the active instances of the registered data sources
+// and of the track event data source, and flushes them. Returns false if
+// flushing didn't complete by `deadline`.
+//
+// Panics can happen at any point, so for the markers of panics, nothing is
+// written if the thread panicked inside a trace callback, where a second
+// packet would corrupt the one open on its trace writer, or while holding the
+// registry of the data sources.
+fn write_marker_and_flush(
+    name: &str,
+    annotations: &[(&str, &str)],
+    deadline: Instant,
+    panicking: bool,
+) -> bool {
+    if panicking && is_in_trace() {
+        return false;
+    }
+    let (sender, receiver) = mpsc::channel();
+    let mut pending_flushes = 0;
+    let mut write = |ctx: &mut TraceContextBase| {
+        write_marker(ctx, name, annotations);
+        let sender = sender.clone();
+        ctx.flush(move || {
+            let _ = sender.send(());
+        });
+        pending_flushes += 1;
+    };
+    if panicking {
+        if !try_trace_all_data_sources(&mut write) {
+            return false;
+        }
+    } else {
+        trace_all_data_sources(&mut write);
+    }
+    te::trace_all_instances(|ctx| write(ctx));
+    for _ in 0..pending_flushes {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if receiver.recv_timeout(remaining).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+/// Prepares the process for exiting without truncating its traces.
+///
+/// Calls the hooks registered with [`on_shutdown`], then writes a marker
+/// event named [`SHUTDOWN_MARKER_NAME`] to all the active instances of the
+/// data sources registered with
+/// [`DataSource::register`](crate::data_source::DataSource::register) and of
+/// the track event data source, and flushes them. Returns false if flushing didn't complete within `timeout`.
+///
+/// Only data written on the calling thread is flushed. Data that other threads
+/// have not committed yet is recovered by the tracing service when the process
+/// exits.
+///
+/// Only the first call has an effect; later calls return true immediately.
+pub fn shutdown(timeout: Duration) -> bool {
+    if SHUT_DOWN.swap(true, Ordering::AcqRel) {
+        return true;
+    }
+    let deadline = Instant::now() + timeout;
+    let pending_hooks = std::mem::take(&mut *hooks().lock().unwrap());
+    for hook in pending_hooks {
+        hook();
+    }
+    write_marker_and_flush(SHUTDOWN_MARKER_NAME, &[], deadline, false)
+}
+
+fn panic_message(info: &PanicHookInfo) -> String {
+    let payload = info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+    match info.location() {
+        Some(location) => format!("{} at {}", message, location),
+        None => message.to_string(),
+    }
+}
+
+fn thread_name() -> String {
+    let thread = std::thread::current();
+    thread
+        .name()
+        .map_or_else(|| format!("{:?}", thread.id()), str::to_string)
+}
+
+/// Installs a panic hook that writes a marker event named
+/// [`PANIC_MARKER_NAME`], with the panic message, the panicking thread and
+/// a backtrace as debug annotations, to all the active instances of the data
+/// sources registered with
+/// [`DataSource::register`](crate::data_source::DataSource::register) and of
+/// the track event data source, and flushes them for up to `timeout`. The previously installed hook is called
+/// afterwards.
+///
+/// The hook also runs when panics abort the process, so the trace explains
+/// the crash instead of ending abruptly. As with [`shutdown`], only data
+/// written on the panicking thread is flushed. No marker is written for panics
+/// inside the callback of a trace call, e.g.
+/// [`DataSource::trace`](crate::data_source::DataSource::trace) or
+/// [`TrackEventCategory::trace`](crate::track_event::TrackEventCategory::trace),
+/// as a packet may be open on the trace writer of the thread.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::shutdown::install_panic_hook;
+/// use std::time::Duration;
+///
+/// install_panic_hook(Duration::from_secs(1));
+/// ```
+pub fn install_panic_hook(timeout: Duration) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let deadline = Instant::now() + timeout;
+        let message = panic_message(info);
+        let backtrace = Backtrace::force_capture().to_string();
+        write_marker_and_flush(
+            PANIC_MARKER_NAME,
+            &[
+                ("message", &message),
+                ("thread", &thread_name()),
+                ("backtrace", &backtrace),
+            ],
+            deadline,
+            true,
+        );
+        previous(info);
+    }));
+}
+
+/// Guard that writes a marker event named [`PANIC_MARKER_NAME`] and flushes
+/// the data sources if it is dropped while its thread unwinds from a panic,
+/// for code that can't install a process-wide hook with
+/// [`install_panic_hook`].
+///
+/// The marker only records the panicking thread, as the panic message isn't
+/// available while unwinding. Nothing is written if panics abort the
+/// process.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::shutdown::PanicGuard;
+/// use std::time::Duration;
+///
+/// std::thread::spawn(|| {
+///     let _guard = PanicGuard::new(Duration::from_secs(1));
+///     // Work that may panic.
+/// });
+/// ```
+#[must_use = "The guard only flushes on panic while it is alive."]
+pub struct PanicGuard {
+    timeout: Duration,
+}
+
+impl PanicGuard {
+    /// Creates a guard that flushes for up to `timeout` on panic.
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl Drop for PanicGuard {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            write_marker_and_flush(
+                PANIC_MARKER_NAME,
+                &[("thread", &thread_name())],
+                Instant::now() + self.timeout,
+                true,
+            );
+        }
+    }
+}
+
+#[cfg(unix)]
+mod signals {
+    use super::shutdown;
+    use std::{
+        io,
+        os::raw::{c_int, c_void},
+        sync::atomic::{AtomicI32, Ordering},
+        time::Duration,
+    };
+
+    // Write end of the pipe used to hand signals over to the shutdown thread.
+    static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+    extern "C" fn on_signal(signal: c_int) {
+        let byte = signal as u8;
+        // SAFETY: `write` is async-signal-safe and `byte` outlives the call.
+        unsafe {
+            libc::write(
+                SIGNAL_PIPE.load(Ordering::Relaxed),
+                &raw const byte as *const c_void,
+                1,
+            )
+        };
+    }
+
+    fn wait_for_signal(read_fd: c_int) -> Option<c_int> {
+        let mut byte = 0u8;
+        loop {
+            // SAFETY: `byte` is valid for writes of one byte.
+            match unsafe { libc::read(read_fd, &raw mut byte as *mut c_void, 1) } {
+                1 => return Some(byte as c_int),
+                -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => {}
+                _ => return None,
+            }
+        }
+    }
+
+    /// Installs handlers for `SIGINT` and `SIGTERM` that call [`shutdown`]
+    /// with `timeout` and then terminate the process as the default handler of
+    /// the signal would.
+    ///
+    /// `shutdown` runs on a dedicated thread, as it isn't safe to call from a
+    /// signal handler. Previously installed handlers of the signals are
+    /// replaced.
+    pub fn install_signal_handler(timeout: Duration) -> io::Result<()> {
+        let mut fds = [0 as c_int; 2];
+        // SAFETY: `fds` is valid for writes of two file descriptors.
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let [read_fd, write_fd] = fds;
+        if SIGNAL_PIPE
+            .compare_exchange(-1, write_fd, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            // SAFETY: Both file descriptors were created above and are unused.
+            unsafe {
+                libc::close(read_fd);
+                libc::close(write_fd);
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "signal handler already installed",
+            ));
+        }
+        // Restores the handlers replaced so far and releases the pipe, so that
+        // a failed installation leaves nothing behind.
+        let tear_down = |previous: &[(c_int, libc::sighandler_t)]| {
+            for &(signal, handler) in previous {
+                // SAFETY: `handler` was the disposition of `signal` before.
+                unsafe { libc::signal(signal, handler) };
+            }
+            SIGNAL_PIPE.store(-1, Ordering::Release);
+            // SAFETY: Both file descriptors were created above and no handler
+            // writes to them anymore.
+            unsafe {
+                libc::close(read_fd);
+                libc::close(write_fd);
+            }
+        };
+        // Signals received before the thread starts wait in the pipe.
+        let mut previous = Vec::new();
+        for signal in [libc::SIGINT, libc::SIGTERM] {
+            let handler = on_signal as extern "C" fn(c_int) as libc::sighandler_t;
+            // SAFETY: `on_signal` only calls async-signal-safe functions.
+            let old = unsafe { libc::signal(signal, handler) };
+            if old == libc::SIG_ERR {
+                let err = io::Error::last_os_error();
+                tear_down(&previous);
+                return Err(err);
+            }
+            previous.push((signal, old));
+        }
+        // The signal thread is never joined.
+        let spawned = crate::platform::spawn("perfetto-shutdown", move || {
+            let Some(signal) = wait_for_signal(read_fd) else {
+                return;
+            };
+            shutdown(timeout);
+            // SAFETY: Restoring the default disposition and raising the signal
+            // has no outstanding preconditions.
+            unsafe {
+                libc::signal(signal, libc::SIG_DFL);
+                libc::raise(signal);
+            }
+        });
+        if let Err(err) = spawned {
+            tear_down(&previous);
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+pub use signals::install_signal_handler;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_decoder::{PbDecoder, PbDecoderField},
+        protos::trace::{
+            trace_packet::TracePacketFieldNumber,
+            track_event::{
+                debug_annotation::DebugAnnotationFieldNumber, track_event::TrackEventFieldNumber,
+            },
+        },
+        test_util::{DATA_SOURCE_NAME, data_source},
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        trace_reader::TraceReader,
+        track_event::TrackEvent,
+    };
+    use std::{
+        error::Error,
+        sync::{Arc, atomic::AtomicU32},
+    };
+
+    #[test]
+    fn flushes_and_writes_marker() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let _ = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let hook_calls = Arc::new(AtomicU32::new(0));
+        let hook_counter = Arc::clone(&hook_calls);
+        on_shutdown(move || {
+            hook_counter.fetch_add(1, Ordering::Relaxed);
+        });
+        assert!(shutdown(Duration::from_secs(5)));
+        assert!(shutdown(Duration::from_secs(5)));
+        assert_eq!(hook_calls.load(Ordering::Relaxed), 1);
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        let mut markers = 0;
+        for packet in TraceReader::new(&data) {
+            let packet = packet?;
+            for field in PbDecoder::new(&packet) {
+                if let (id, PbDecoderField::Delimited(event)) = field?
+                    && id == TracePacketFieldNumber::TrackEvent as u32
+                    && PbDecoder::new(event).any(|f| {
+                        f.unwrap()
+                            == (
+                                TrackEventFieldNumber::Name as u32,
+                                PbDecoderField::Delimited(SHUTDOWN_MARKER_NAME.as_bytes()),
+                            )
+                    })
+                {
+                    markers += 1;
+                }
+            }
+        }
+        assert_eq!(markers, 1);
+        Ok(())
+    }
+
+    // Returns the debug annotations of the markers named `name`.
+    fn read_markers(data: &[u8], name: &str) -> Vec<Vec<(String, String)>> {
+        let mut markers = vec![];
+        for packet in TraceReader::new(data) {
+            for field in PbDecoder::new(&packet.unwrap()) {
+                let (id, field) = field.unwrap();
+                if id != TracePacketFieldNumber::TrackEvent as u32 {
+                    continue;
+                }
+                let fields: Vec<_> = field.as_decoder().unwrap().map(|f| f.unwrap()).collect();
+                if !fields.contains(&(
+                    TrackEventFieldNumber::Name as u32,
+                    PbDecoderField::Delimited(name.as_bytes()),
+                )) {
+                    continue;
+                }
+                let mut annotations = vec![];
+                for (id, field) in fields {
+                    if id != TrackEventFieldNumber::DebugAnnotations as u32 {
+                        continue;
+                    }
+                    let (mut name, mut value) = (String::new(), String::new());
+                    for item in field.as_decoder().unwrap() {
+                        match item.unwrap() {
+                            (id, f) if id == DebugAnnotationFieldNumber::Name as u32 => {
+                                name = f.as_str().unwrap().to_string();
+                            }
+                            (id, f) if id == DebugAnnotationFieldNumber::StringValue as u32 => {
+                                value = f.as_str().unwrap().to_string();
+                            }
+                            _ => {}
+                        }
+                    }
+                    annotations.push((name, value));
+                }
+                markers.push(annotations);
+            }
+        }
+        markers
+    }
+
+    #[test]
+    fn writes_panic_markers() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let _ = data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        let result = std::thread::Builder::new()
+            .name("guarded".to_string())
+            .spawn(|| {
+                let _guard = PanicGuard::new(Duration::from_secs(5));
+                panic!("guarded panic");
+            })?
+            .join();
+        assert!(result.is_err());
+        let previous_hook = panic::take_hook();
+        install_panic_hook(Duration::from_secs(5));
+        let result = std::thread::Builder::new()
+            .name("hooked".to_string())
+            .spawn(|| panic!("hooked panic"))?
+            .join();
+        assert!(result.is_err());
+        // No marker is written from inside a trace callback.
+        let result = std::thread::Builder::new()
+            .name("tracing".to_string())
+            .spawn(|| {
+                data_source().trace(|_| panic!("tracing panic"));
+            })?
+            .join();
+        panic::set_hook(previous_hook);
+        assert!(result.is_err());
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        let markers = read_markers(&data, PANIC_MARKER_NAME);
+        assert_eq!(markers.len(), 2);
+        assert_eq!(
+            markers[0],
+            vec![("thread".to_string(), "guarded".to_string())]
+        );
+        let hooked = &markers[1];
+        assert_eq!(hooked.len(), 3);
+        assert_eq!(hooked[0].0, "message");
+        assert!(hooked[0].1.starts_with("hooked panic at "));
+        assert_eq!(hooked[1], ("thread".to_string(), "hooked".to_string()));
+        assert_eq!(hooked[2].0, "backtrace");
+        assert!(!hooked[2].1.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn writes_markers_to_track_event() -> Result<(), Box<dyn Error>> {
+        crate::track_event_categories! {
+            pub mod shutdown_te_ns {
+                ( "shutdown_cat", "Category of a track event only app", [] ),
+            }
+        }
+        let _lock = acquire_test_environment();
+        TrackEvent::init();
+        shutdown_te_ns::register()?;
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name("track_event")
+            .build()?;
+        session.start_blocking();
+        let result = std::thread::Builder::new()
+            .name("guarded".to_string())
+            .spawn(|| {
+                let _guard = PanicGuard::new(Duration::from_secs(5));
+                panic!("guarded panic");
+            })?
+            .join();
+        assert!(result.is_err());
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+        shutdown_te_ns::unregister()?;
+
+        let markers = read_markers(&data, PANIC_MARKER_NAME);
+        assert_eq!(
+            markers,
+            vec![vec![("thread".to_string(), "guarded".to_string())]]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// The checker only runs in builds with debug assertions.
+#![cfg_attr(not(debug_assertions), allow(dead_code))]
+
+use crate::track_event::{EventContext, TeHlExtra, TrackEventTrack, TrackEventType};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::CStr,
+    fmt,
+    sync::{Arc, LazyLock, Mutex, RwLock},
+};
+use thiserror::Error;
+
+/// Number of slices open on a track from which the checker reports that
+/// slices aren't ended, see [`SliceError::TooManyOpenSlices`].
+pub const MAX_OPEN_SLICES: usize = 1024;
+
+/// Track of a slice checked by the slice checker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SliceTrack {
+    /// Track of the thread that emitted the event.
+    Thread,
+    /// Track with a UUID, e.g. a registered or named track.
+    Uuid(u64),
+}
+
+impl fmt::Display for SliceTrack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Thread => write!(f, "the thread track"),
+            Self::Uuid(uuid) => write!(f, "track {:#x}", uuid),
+        }
+    }
+}
+
+/// Unbalanced slice reported by the slice checker.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SliceError {
+    /// A slice was ended on a track with no open slice.
+    #[error("Slice end on {track} without a matching begin.")]
+    UnmatchedEnd {
+        /// Track of the end event.
+        track: SliceTrack,
+    },
+    /// A slice was ended on a track with no open slice, while the innermost
+    /// slice begun by the thread is open on another track, e.g. because the
+    /// track of the begin or end event was forgotten.
+    #[error("Slice end on {track}, but slice \"{open_name}\" is open on {open_track}.")]
+    CrossTrackEnd {
+        /// Track of the end event.
+        track: SliceTrack,
+        /// Track of the innermost slice begun by the thread.
+        open_track: SliceTrack,
+        /// Name of the innermost slice begun by the thread.
+        open_name: String,
+    },
+    /// [`MAX_OPEN_SLICES`] slices are open on a track, which usually means
+    /// that its slices are begun but never ended.
+    #[error("{count} slices are open on {track}, innermost \"{name}\".")]
+    TooManyOpenSlices {
+        /// Track of the slices.
+        track: SliceTrack,
+        /// Number of open slices.
+        count: usize,
+        /// Name of the innermost slice.
+        name: String,
+    },
+}
+
+type ErrorHandler = Arc<dyn Fn(&SliceError) + Send + Sync>;
+
+static ERROR_HANDLER: RwLock<Option<ErrorHandler>> = RwLock::new(None);
+
+// Slices open on the tracks with a UUID, which can be begun and ended on
+// different threads.
+static TRACK_SLICES: LazyLock<Mutex<HashMap<u64, Vec<String>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Default)]
+struct ThreadSlices {
+    // Slices open on the thread track, or `None` until a slice is begun on
+    // it.
+    thread: Option<Vec<String>>,
+    // Tracks of the slices begun by the thread, innermost last.
+    begun: Vec<SliceTrack>,
+}
+
+thread_local! {
+    static THREAD_SLICES: RefCell<ThreadSlices> = RefCell::new(ThreadSlices::default());
+}
+
+/// Sets the function called with the unbalanced slices found by the slice
+/// checker, instead of printing them to stderr.
+///
+/// In builds with debug assertions, the begin and end track events emitted
+/// into a session are checked as they are emitted: the checker tracks the
+/// slices open on each track, and reports the ends without a matching begin,
+/// in particular when the thread has a slice open on another track, and the
+/// tracks on which slices are begun but never ended. Events on nested tracks
+/// aren't checked, and the checker is compiled out of release builds.
+///
+/// Only the events of enabled categories are checked, so the end of a slice
+/// begun before a session started isn't reported unless a slice already
+/// began and ended on its track.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::slice_checker::set_error_handler;
+///
+/// set_error_handler(|err| panic!("{}", err));
+/// ```
+pub fn set_error_handler<F>(handler: F)
+where
+    F: Fn(&SliceError) + Send + Sync + 'static,
+{
+    *ERROR_HANDLER.write().unwrap() = Some(Arc::new(handler));
+}
+
+fn report(err: SliceError) {
+    let handler = ERROR_HANDLER.read().unwrap().clone();
+    match handler {
+        Some(handler) => handler(&err),
+        None => crate::sdk_log!(Warn, "Unbalanced track event slices: {}", err),
+    }
+}
+
+// Returns the track of the event of `ctx`, or `None` if it isn't checked.
+fn track_of(ctx: &EventContext) -> Option<SliceTrack> {
+    let mut track = SliceTrack::Thread;
+    for extra in &ctx.extras {
+        match extra {
+            TeHlExtra::Track(registered) => {
+                // SAFETY: the registered track outlives the event.
+                track = SliceTrack::Uuid(unsafe { (*registered.track).uuid });
+            }
+            TeHlExtra::NamedTrack(named, name) => {
+                let name = name.to_str().ok()?;
+                track = SliceTrack::Uuid(TrackEventTrack::named_track_uuid(
+                    name,
+                    named.id,
+                    named.parent_uuid,
+                ));
+            }
+            TeHlExtra::ProtoTrack(proto, _, _) => track = SliceTrack::Uuid(proto.uuid),
+            TeHlExtra::NestedTracks(..) => return None,
+            _ => {}
+        }
+    }
+    Some(track)
+}
+
+// Checks the begin and end events emitted with `ctx`.
+pub(crate) fn check(variant: &TrackEventType, ctx: &EventContext) {
+    match variant {
+        TrackEventType::SliceBegin(name) => {
+            let Some(track) = track_of(ctx) else {
+                return;
+            };
+            // SAFETY: the names of the begin events are NUL-terminated strings.
+            let name = unsafe { CStr::from_ptr(*name) }
+                .to_string_lossy()
+                .into_owned();
+            if let Some(err) = begin(track, name) {
+                report(err);
+            }
+        }
+        TrackEventType::SliceEnd => {
+            if let Some(track) = track_of(ctx)
+                && let Some(err) = end(track)
+            {
+                report(err);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn with_slices<R>(track: SliceTrack, f: impl FnOnce(&mut Option<Vec<String>>) -> R) -> R {
+    match track {
+        SliceTrack::Thread => THREAD_SLICES.with_borrow_mut(|slices| f(&mut slices.thread)),
+        SliceTrack::Uuid(uuid) => {
+            let mut tracks = TRACK_SLICES.lock().unwrap();
+            let mut slices = tracks.remove(&uuid);
+            let result = f(&mut slices);
+            if let Some(slices) = slices {
+                tracks.insert(uuid, slices);
+            }
+            result
+        }
+    }
+}
+
+fn begin(track: SliceTrack, name: String) -> Option<SliceError> {
+    let err = with_slices(track, |slices| {
+        let slices = slices.get_or_insert_with(Vec::new);
+        slices.push(name);
+        (slices.len() == MAX_OPEN_SLICES).then(|| SliceError::TooManyOpenSlices {
+            track,
+            count: slices.len(),
+            name: slices.last().cloned().unwrap_or_default(),
+        })
+    });
+    THREAD_SLICES.with_borrow_mut(|slices| {
+        // Slices of other tracks can be ended on other threads, so the ones
+        // that aren't open anymore are dropped once in a while.
+        if slices.begun.len() >= MAX_OPEN_SLICES {
+            let mut begun = std::mem::take(&mut slices.begun);
+            begun.retain(|track| is_open(*track, slices));
+            slices.begun = begun;
+        }
+        slices.begun.push(track);
+    });
+    err
+}
+
+// Returns whether a slice is open on `track`, `slices` being the slices of
+// the current thread.
+fn is_open(track: SliceTrack, slices: &ThreadSlices) -> bool {
+    match track {
+        SliceTrack::Thread => slices.thread.as_ref().is_some_and(|s| !s.is_empty()),
+        SliceTrack::Uuid(uuid) => TRACK_SLICES
+            .lock()
+            .unwrap()
+            .get(&uuid)
+            .is_some_and(|s| !s.is_empty()),
+    }
+}
+
+fn end(track: SliceTrack) -> Option<SliceError> {
+    // Whether the track ever had a slice, and the name of the ended slice.
+    let (known, ended) = with_slices(track, |slices| match slices {
+        Some(slices) => (true, slices.pop()),
+        None => (false, None),
+    });
+    THREAD_SLICES.with_borrow_mut(|slices| {
+        if ended.is_some() {
+            if let Some(index) = slices.begun.iter().rposition(|begun| *begun == track) {
+                slices.begun.remove(index);
+            }
+            return None;
+        }
+        while let Some(open_track) = slices.begun.last().copied() {
+            if !is_open(open_track, slices) {
+                slices.begun.pop();
+                continue;
+            }
+            let open_name = match open_track {
+                SliceTrack::Thread => slices.thread.as_ref().and_then(|s| s.last().cloned()),
+                SliceTrack::Uuid(uuid) => TRACK_SLICES
+                    .lock()
+                    .unwrap()
+                    .get(&uuid)
+                    .and_then(|s| s.last().cloned()),
+            };
+            return Some(SliceError::CrossTrackEnd {
+                track,
+                open_track,
+                open_name: open_name.unwrap_or_default(),
+            });
+        }
+        known.then_some(SliceError::UnmatchedEnd { track })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    // Runs `f` on a new thread, so that its thread track has no slices, and
+    // returns the errors it found.
+    fn errors(f: impl FnOnce() -> Vec<Option<SliceError>> + Send + 'static) -> Vec<SliceError> {
+        thread::spawn(f)
+            .join()
+            .unwrap()
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    #[test]
+    fn balanced() {
+        let errs = errors(|| {
+            vec![
+                begin(SliceTrack::Thread, "outer".to_string()),
+                begin(SliceTrack::Uuid(1), "async".to_string()),
+                begin(SliceTrack::Thread, "inner".to_string()),
+                end(SliceTrack::Thread),
+                end(SliceTrack::Thread),
+                end(SliceTrack::Uuid(1)),
+            ]
+        });
+        assert!(errs.is_empty());
+        // Slices of tracks with a UUID can end on other threads.
+        errors(|| vec![begin(SliceTrack::Uuid(2), "moved".to_string())]);
+        assert!(errors(|| vec![end(SliceTrack::Uuid(2))]).is_empty());
+    }
+
+    #[test]
+    fn unbalanced() {
+        let errs = errors(|| {
+            vec![
+                // Ends of slices begun before the first checked event aren't
+                // reported.
+                end(SliceTrack::Thread),
+                begin(SliceTrack::Thread, "work".to_string()),
+                end(SliceTrack::Uuid(3)),
+                end(SliceTrack::Thread),
+                end(SliceTrack::Thread),
+            ]
+        });
+        assert_eq!(
+            errs,
+            vec![
+                SliceError::CrossTrackEnd {
+                    track: SliceTrack::Uuid(3),
+                    open_track: SliceTrack::Thread,
+                    open_name: "work".to_string(),
+                },
+                SliceError::UnmatchedEnd {
+                    track: SliceTrack::Thread
+                },
+            ]
+        );
+
+        let errs = errors(|| {
+            (0..MAX_OPEN_SLICES)
+                .map(|_| begin(SliceTrack::Thread, "leak".to_string()))
+                .collect()
+        });
+        assert_eq!(
+            errs,
+            vec![SliceError::TooManyOpenSlices {
+                track: SliceTrack::Thread,
+                count: MAX_OPEN_SLICES,
+                name: "leak".to_string(),
+            }]
+        );
+    }
+}
@@ -145,6 +145,16 @@ impl StreamWriter {
         unsafe { slice::from_raw_parts_mut(start_ptr, size) }
     }
 
+    /// Moves the writer to a new chunk, leaving the rest of the current chunk
+    /// unused.
+    pub(crate) fn new_chunk(&self) {
+        let mut writer = self.writer.borrow_mut();
+        assert!(!writer.impl_.is_null());
+
+        // SAFETY: `writer` must be valid.
+        unsafe { PerfettoStreamWriterNewChunk(&mut *writer as *mut _) };
+    }
+
     /// Returns the number of bytes written to the stream writer from the start.
     pub fn get_written_size(&self) -> usize {
         let writer = self.writer.borrow();
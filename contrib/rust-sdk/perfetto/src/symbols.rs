@@ -0,0 +1,242 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    heap_buffer::HeapBuffer,
+    pb_msg::{PbMsg, PbMsgWriter},
+    protos::trace::{
+        profiling::profile_common::{
+            AddressSymbols, Line, ModuleSymbols, ModuleSymbolsFieldNumber,
+        },
+        trace::Trace,
+        trace_packet::TracePacket,
+    },
+};
+
+/// Source line that an address symbolizes to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolLine {
+    /// Name of the function.
+    pub function_name: String,
+    /// Path of the source file.
+    pub source_file_name: String,
+    /// Line number in the source file.
+    pub line_number: u32,
+}
+
+impl SymbolLine {
+    /// Creates a new source line.
+    pub fn new(
+        function_name: impl Into<String>,
+        source_file_name: impl Into<String>,
+        line_number: u32,
+    ) -> Self {
+        Self {
+            function_name: function_name.into(),
+            source_file_name: source_file_name.into(),
+            line_number,
+        }
+    }
+}
+
+/// Symbol data for the addresses seen in one module, emitted as a
+/// `ModuleSymbols` packet for offline symbolization of profiling data.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::symbols::{SymbolLine, SymbolizedModule, encode_symbol_packets};
+///
+/// let mut module = SymbolizedModule::new("/usr/lib/libfoo.so", b"\x12\x34\xab".to_vec());
+/// module.add_address(0x1000, [SymbolLine::new("foo", "foo.rs", 42)]);
+/// // Symbol packets can be appended to an existing `.perfetto-trace` file.
+/// let packets = encode_symbol_packets(&[module]);
+/// assert!(!packets.is_empty());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SymbolizedModule {
+    path: String,
+    build_id: Vec<u8>,
+    addresses: Vec<(u64, Vec<SymbolLine>)>,
+}
+
+impl SymbolizedModule {
+    /// Creates symbol data for the module at `path`.
+    ///
+    /// `build_id` is the raw (not hex encoded) `.note.gnu.build-id` on Linux,
+    /// the uuid on MacOS or the module GUID on Windows. It must match the
+    /// build id of the mapping in the profiling packets.
+    pub fn new(path: impl Into<String>, build_id: impl Into<Vec<u8>>) -> Self {
+        Self {
+            path: path.into(),
+            build_id: build_id.into(),
+            addresses: Vec::new(),
+        }
+    }
+
+    /// Adds the source lines for `address`, relative to the module load
+    /// address. With inlining several lines can map to a single address, in
+    /// which case the innermost frame comes first.
+    pub fn add_address(
+        &mut self,
+        address: u64,
+        lines: impl IntoIterator<Item = SymbolLine>,
+    ) -> &mut Self {
+        self.addresses.push((address, lines.into_iter().collect()));
+        self
+    }
+
+    /// Returns true if no addresses have been added.
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    /// Writes the symbol data as the `module_symbols` field of `packet`.
+    pub fn write_to_packet(&self, packet: &mut TracePacket) {
+        packet.set_module_symbols(|module: &mut ModuleSymbols| {
+            module.set_path(self.path.as_str());
+            // The build id is raw bytes and not necessarily valid UTF-8.
+            module
+                .msg
+                .append_type2_field(ModuleSymbolsFieldNumber::BuildId as u32, &self.build_id);
+            for (address, lines) in &self.addresses {
+                module.set_address_symbols(|symbols: &mut AddressSymbols| {
+                    symbols.set_address(*address);
+                    for line in lines {
+                        symbols.set_lines(|l: &mut Line| {
+                            l.set_function_name(line.function_name.as_str());
+                            l.set_source_file_name(line.source_file_name.as_str());
+                            l.set_line_number(line.line_number);
+                        });
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// Serializes one `ModuleSymbols` packet per module as a `Trace` protobuf.
+///
+/// Serialized traces can be concatenated, so the result can be appended to a
+/// trace file to make its symbols available to trace processor.
+pub fn encode_symbol_packets(modules: &[SymbolizedModule]) -> Vec<u8> {
+    let writer = PbMsgWriter::new();
+    let hb = HeapBuffer::new(&writer.writer);
+    let mut msg = PbMsg::new(&writer).unwrap();
+    {
+        let mut trace = Trace { msg: &mut msg };
+        for module in modules {
+            trace.set_packet(|packet: &mut TracePacket| {
+                module.write_to_packet(packet);
+            });
+        }
+    }
+    msg.finalize();
+    let size = writer.writer.get_written_size();
+    let mut buffer = vec![0u8; size];
+    hb.copy_into(&mut buffer);
+    buffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_decoder::{PbDecoder, PbDecoderField},
+        protos::trace::{
+            profiling::profile_common::{AddressSymbolsFieldNumber, LineFieldNumber},
+            trace_packet::TracePacketFieldNumber,
+        },
+        trace_reader::TraceReader,
+    };
+
+    fn fields(msg: &[u8]) -> Vec<(u32, PbDecoderField<'_>)> {
+        PbDecoder::new(msg).map(|f| f.unwrap()).collect()
+    }
+
+    fn delimited<'a>(field: &PbDecoderField<'a>) -> &'a [u8] {
+        match field {
+            PbDecoderField::Delimited(data) => data,
+            _ => panic!("expected delimited field"),
+        }
+    }
+
+    #[test]
+    fn module_symbols_packets() {
+        let mut module = SymbolizedModule::new("/lib/libfoo.so", vec![0xff, 0x00, 0x80]);
+        module
+            .add_address(
+                0x10,
+                [
+                    SymbolLine::new("inlined", "a.rs", 1),
+                    SymbolLine::new("outer", "b.rs", 2),
+                ],
+            )
+            .add_address(0x20, []);
+        let empty = SymbolizedModule::new("/lib/libbar.so", vec![]);
+        assert!(empty.is_empty());
+
+        let trace = encode_symbol_packets(&[module, empty]);
+        let packets: Vec<_> = TraceReader::new(&trace).map(|p| p.unwrap()).collect();
+        assert_eq!(packets.len(), 2);
+
+        let packet = fields(&packets[0]);
+        assert_eq!(packet.len(), 1);
+        assert_eq!(packet[0].0, TracePacketFieldNumber::ModuleSymbols as u32);
+        let module = fields(delimited(&packet[0].1));
+        assert_eq!(
+            module[0],
+            (
+                ModuleSymbolsFieldNumber::Path as u32,
+                PbDecoderField::Delimited(b"/lib/libfoo.so")
+            )
+        );
+        assert_eq!(
+            module[1],
+            (
+                ModuleSymbolsFieldNumber::BuildId as u32,
+                PbDecoderField::Delimited(&[0xff, 0x00, 0x80])
+            )
+        );
+        assert_eq!(module.len(), 4);
+
+        let symbols = fields(delimited(&module[2].1));
+        assert_eq!(
+            symbols[0],
+            (
+                AddressSymbolsFieldNumber::Address as u32,
+                PbDecoderField::Varint(0x10)
+            )
+        );
+        assert_eq!(symbols.len(), 3);
+        let line = fields(delimited(&symbols[1].1));
+        assert_eq!(
+            line,
+            vec![
+                (
+                    LineFieldNumber::FunctionName as u32,
+                    PbDecoderField::Delimited(b"inlined")
+                ),
+                (
+                    LineFieldNumber::SourceFileName as u32,
+                    PbDecoderField::Delimited(b"a.rs")
+                ),
+                (
+                    LineFieldNumber::LineNumber as u32,
+                    PbDecoderField::Varint(1)
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers for the tests of crates that extend the SDK, e.g. with data sources
+//! writing their own protos: an in-process session recording the packets of a
+//! data source, data source types shared by the tests, and accessors of the
+//! fields of the recorded packets.
+//!
+//! Enabled by the `test-util` feature, usually from `[dev-dependencies]`.
+
+use crate::{
+    data_source::{Clear, DataSource, DataSourceArgsBuilder, DataSourceBufferExhaustedPolicy},
+    heap_buffer::HeapBuffer,
+    pb_decoder::{PbDecoder, PbDecoderField},
+    pb_msg::{PbMsg, PbMsgWriter},
+    producer::{Backends, Producer, ProducerInitArgsBuilder},
+    protos::config::{
+        data_source_config::DataSourceConfig,
+        trace_config::{TraceConfig, TraceConfigBufferConfig, TraceConfigDataSource},
+    },
+    trace_reader::TraceReader,
+    tracing_session::TracingSession,
+};
+use std::{
+    any::{Any, TypeId, type_name},
+    sync::{Arc, Mutex, MutexGuard, Once, OnceLock},
+};
+
+static INIT_TEST_ENVIRONMENT: Once = Once::new();
+static TEST_ENVIRONMENT_MUTEX: Mutex<()> = Mutex::new(());
+
+/// Initializes the producer with the in-process backend, and returns a guard
+/// that serializes the tests using it.
+///
+/// Perfetto uses global state internally that cannot be uninitialized, so the
+/// test environment and registered data sources must also be global.
+pub fn acquire_test_environment() -> MutexGuard<'static, ()> {
+    INIT_TEST_ENVIRONMENT.call_once(|| {
+        let producer_args = ProducerInitArgsBuilder::new().backends(Backends::IN_PROCESS);
+        Producer::init(producer_args.build());
+    });
+    TEST_ENVIRONMENT_MUTEX.lock().unwrap()
+}
+
+/// Name of the data source type returned by [`data_source`].
+pub const DATA_SOURCE_NAME: &str = "com.example.test_data_source";
+
+/// Returns the data source type shared by the tests of the process, which is
+/// registered as [`DATA_SOURCE_NAME`] without callbacks.
+///
+/// A process can only register 32 data source types, and they can't be
+/// unregistered, so tests that don't need callbacks of their own share this
+/// one. It stalls and aborts when the buffer is exhausted, so that tests don't
+/// silently lose packets.
+pub fn data_source() -> &'static DataSource<'static> {
+    static DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
+    DATA_SOURCE.get_or_init(|| register(DATA_SOURCE_NAME))
+}
+
+/// Returns the name of the data source type returned by
+/// [`data_source_with_state`] for `IncrT`.
+pub fn data_source_name<IncrT: 'static>() -> String {
+    format!("{}.{}", DATA_SOURCE_NAME, type_name::<IncrT>())
+}
+
+/// Returns the data source type with the incremental state `IncrT` shared by
+/// the tests of the process, see [`data_source`]. It's registered as
+/// [`data_source_name::<IncrT>()`](data_source_name).
+pub fn data_source_with_state<IncrT>() -> &'static DataSource<'static, IncrT>
+where
+    IncrT: Default + Clear + 'static,
+{
+    type DataSources = Vec<(TypeId, &'static (dyn Any + Send + Sync))>;
+    static DATA_SOURCES: Mutex<DataSources> = Mutex::new(Vec::new());
+    let mut data_sources = DATA_SOURCES.lock().unwrap();
+    let data_source = match data_sources
+        .iter()
+        .find(|(id, _)| *id == TypeId::of::<IncrT>())
+    {
+        Some((_, data_source)) => *data_source,
+        None => {
+            let data_source: &'static DataSource<'static, IncrT> =
+                Box::leak(Box::new(register(&data_source_name::<IncrT>())));
+            data_sources.push((TypeId::of::<IncrT>(), data_source));
+            data_source
+        }
+    };
+    data_source.downcast_ref().unwrap()
+}
+
+fn register<IncrT: Default + Clear>(name: &str) -> DataSource<'static, IncrT> {
+    let args = DataSourceArgsBuilder::new()
+        .buffer_exhausted_policy(DataSourceBufferExhaustedPolicy::StallAndAbort);
+    let mut data_source = DataSource::new_with_incremental_state_type();
+    data_source
+        .register(name, args.build())
+        .expect("failed to register data source");
+    data_source
+}
+
+/// Records the packets written by the data source `name` while `cb` runs, in
+/// an in-process session whose data source config is completed by `config`.
+///
+/// Call with the guard of [`acquire_test_environment`] held.
+pub fn record_packets(
+    name: &str,
+    config: impl Fn(&mut DataSourceConfig),
+    cb: impl FnOnce(),
+) -> Vec<Vec<u8>> {
+    let writer = PbMsgWriter::new();
+    let hb = HeapBuffer::new(writer.stream_writer());
+    let mut msg = PbMsg::new(&writer).unwrap();
+    {
+        let mut cfg = TraceConfig { msg: &mut msg };
+        cfg.set_buffers(|buf_cfg: &mut TraceConfigBufferConfig| {
+            buf_cfg.set_size_kb(1024);
+        });
+        cfg.set_data_sources(|data_sources: &mut TraceConfigDataSource| {
+            data_sources.set_config(|ds_cfg: &mut DataSourceConfig| {
+                ds_cfg.set_name(name);
+                config(ds_cfg);
+            });
+        });
+    }
+    msg.finalize();
+    let mut trace_config = vec![0u8; writer.stream_writer().get_written_size()];
+    hb.copy_into(&mut trace_config);
+
+    let mut session = TracingSession::in_process().unwrap();
+    session.setup(&trace_config);
+    session.start_blocking();
+    cb();
+    session.stop_blocking();
+    let trace_data = Arc::new(Mutex::new(vec![]));
+    let trace_data_for_write = Arc::clone(&trace_data);
+    session.read_trace_blocking(move |data, _end| {
+        trace_data_for_write.lock().unwrap().extend_from_slice(data);
+    });
+    let data = trace_data.lock().unwrap();
+    TraceReader::new(&data)
+        .map(|packet| packet.unwrap().into_owned())
+        .collect()
+}
+
+/// Returns the fields `id` of the encoded message `data`.
+pub fn fields(data: &[u8], id: u32) -> Vec<PbDecoderField<'_>> {
+    PbDecoder::new(data)
+        .map(|field| field.unwrap())
+        .filter(|(field_id, _)| *field_id == id)
+        .map(|(_, field)| field)
+        .collect()
+}
+
+/// Returns the first field `id` of `data`, if any.
+pub fn field(data: &[u8], id: u32) -> Option<PbDecoderField<'_>> {
+    PbDecoder::new(data)
+        .map(|field| field.unwrap())
+        .find(|(field_id, _)| *field_id == id)
+        .map(|(_, field)| field)
+}
+
+/// Returns true if `data` has the field `id`.
+pub fn has_field(data: &[u8], id: u32) -> bool {
+    field(data, id).is_some()
+}
+
+/// Returns the values of the varint fields `id` of `data`.
+pub fn varints(data: &[u8], id: u32) -> Vec<u64> {
+    fields(data, id)
+        .into_iter()
+        .filter_map(|field| match field {
+            PbDecoderField::Varint(value) => Some(value),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the value of the varint field `id` of `data`, if set. The last
+/// value wins if the field is repeated, like when decoding a proto.
+pub fn varint(data: &[u8], id: u32) -> Option<u64> {
+    varints(data, id).pop()
+}
+
+/// Returns the payloads of the delimited fields `id` of `data`.
+pub fn messages(data: &[u8], id: u32) -> Vec<&[u8]> {
+    fields(data, id)
+        .into_iter()
+        .map(|field| field.as_bytes().unwrap())
+        .collect()
+}
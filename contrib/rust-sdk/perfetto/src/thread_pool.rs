@@ -0,0 +1,403 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    async_task::TaskCategory,
+    track_event::{
+        EventContext, ScopeGuard, TrackEventDebugArg, TrackEventFlow, TrackEventTimestamp,
+        TrackEventTrack, TrackEventType,
+    },
+};
+use std::{
+    ffi::{CStr, CString},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+// Name of the events emitted when a task is submitted.
+const SUBMIT_EVENT_NAME: &CStr = c"submit";
+
+// Name of the slices emitted for the time a task waited in its queue.
+const QUEUED_SLICE_NAME: &CStr = c"queued";
+
+// Task ids are allocated per task, as the tracks of queued tasks are named
+// after their queue.
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug)]
+struct QueueInner {
+    category: TaskCategory,
+    name: String,
+    c_name: CString,
+}
+
+impl QueueInner {
+    fn emit<F>(&self, variant: TrackEventType, cb: F)
+    where
+        F: FnOnce(&mut EventContext),
+    {
+        let mut ctx = EventContext::default();
+        cb(&mut ctx);
+        self.category.emit(variant, &mut ctx);
+    }
+
+    // Returns the UUID of the track of the task `id`, which is also the id of
+    // its flow.
+    fn task_uuid(&self, id: u64) -> u64 {
+        TrackEventTrack::named_track_uuid(&self.name, id, TrackEventTrack::process_track_uuid())
+    }
+}
+
+/// Instrumentation of a thread pool or task queue, that traces the tasks
+/// submitted to it.
+///
+/// When a task is submitted, an instant is emitted on the track of the
+/// submitting thread. When it runs, the time it waited in the queue is
+/// emitted as a slice on a track of the task nested under the process track,
+/// and its execution as a slice named after the queue on the track of the
+/// worker thread. The three events are connected by a flow, so that the UI
+/// links each task from the place it was submitted to the place it ran.
+///
+/// Closures are instrumented with [`TaskQueue::wrap`], which fits the
+/// spawn functions of thread pools such as `rayon::ThreadPool::spawn` and
+/// `rayon::spawn`. Work items sent through channels, such as the ones of
+/// `crossbeam_channel` or `std::sync::mpsc`, are instrumented with
+/// [`TaskQueue::submit`] and [`QueuedTask::run`] on the worker side.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     async_task_category,
+///     thread_pool::{QueuedTask, TaskQueue},
+/// };
+/// use std::{sync::mpsc, thread};
+///
+/// perfetto_sdk::track_event_categories! {
+///     pub mod my_categories {
+///         ("pool", "Thread pool scheduling", []),
+///     }
+/// }
+/// use my_categories as perfetto_te_ns;
+///
+/// let queue = TaskQueue::new(async_task_category!("pool"), "jobs");
+/// let (sender, receiver) = mpsc::channel::<QueuedTask<u32>>();
+/// let worker = thread::spawn(move || {
+///     for job in receiver {
+///         job.run(|value| println!("{value}"));
+///     }
+/// });
+/// sender.send(queue.submit(42)).unwrap();
+/// drop(sender);
+/// worker.join().unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TaskQueue {
+    inner: Arc<QueueInner>,
+}
+
+impl TaskQueue {
+    /// Creates the instrumentation of a queue named `name`, whose tasks are
+    /// emitted in `category`. NUL bytes in `name` are replaced by spaces in
+    /// the emitted slice names.
+    pub fn new(category: TaskCategory, name: &str) -> Self {
+        Self {
+            inner: Arc::new(QueueInner {
+                category,
+                name: name.to_string(),
+                c_name: CString::new(name.replace('\0', " ")).unwrap(),
+            }),
+        }
+    }
+
+    /// Returns the name of the queue.
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Submits `item` to the queue: emits the submit event on the calling
+    /// thread and returns the item wrapped in a [`QueuedTask`], to be sent to
+    /// the thread that runs it.
+    ///
+    /// Nothing is emitted, and the task isn't traced when it runs, if the
+    /// category is disabled when the item is submitted.
+    pub fn submit<T>(&self, item: T) -> QueuedTask<T> {
+        let submission = self.inner.category.is_enabled().then(|| {
+            let id = NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed);
+            let flow = TrackEventFlow::process_scoped_flow(self.inner.task_uuid(id));
+            self.inner
+                .emit(TrackEventType::Instant(SUBMIT_EVENT_NAME.as_ptr()), |ctx| {
+                    ctx.set_flow(&flow)
+                        .add_debug_arg("queue", TrackEventDebugArg::String(&self.inner.name));
+                });
+            Submission {
+                id,
+                timestamp: TrackEventTimestamp::now(),
+            }
+        });
+        QueuedTask {
+            item,
+            queue: Arc::clone(&self.inner),
+            submission,
+        }
+    }
+
+    /// Submits the closure `f` and returns a closure that runs it, see
+    /// [`TaskQueue::submit`] and [`QueuedTask::call`].
+    ///
+    /// Example:
+    ///
+    /// ```ignore
+    /// let queue = TaskQueue::new(async_task_category!("pool"), "rayon");
+    /// pool.spawn(queue.wrap(|| compress_block()));
+    /// ```
+    pub fn wrap<F, R>(&self, f: F) -> impl FnOnce() -> R + Send + 'static
+    where
+        F: FnOnce() -> R + Send + 'static,
+    {
+        let task = self.submit(f);
+        move || task.call()
+    }
+}
+
+#[derive(Debug)]
+struct Submission {
+    id: u64,
+    timestamp: TrackEventTimestamp,
+}
+
+/// Work item submitted to a [`TaskQueue`], that traces the time it waited in
+/// the queue and its execution when it runs.
+#[derive(Debug)]
+#[must_use = "Queued tasks are only traced when they run."]
+pub struct QueuedTask<T> {
+    item: T,
+    queue: Arc<QueueInner>,
+    submission: Option<Submission>,
+}
+
+impl<T> QueuedTask<T> {
+    /// Returns the id of the task, or `None` if the task isn't traced.
+    pub fn id(&self) -> Option<u64> {
+        self.submission.as_ref().map(|submission| submission.id)
+    }
+
+    /// Returns the work item.
+    pub fn get(&self) -> &T {
+        &self.item
+    }
+
+    /// Returns the work item without tracing its execution.
+    pub fn into_inner(self) -> T {
+        self.item
+    }
+
+    /// Runs `f` with the work item on the calling thread: emits the time the
+    /// item waited in the queue, and the execution of `f` as a slice.
+    pub fn run<F, R>(self, f: F) -> R
+    where
+        F: FnOnce(T) -> R,
+    {
+        let Some(submission) = self.submission else {
+            return f(self.item);
+        };
+        let queue = self.queue;
+        if !queue.category.is_enabled() {
+            return f(self.item);
+        }
+        let flow = TrackEventFlow::process_scoped_flow(queue.task_uuid(submission.id));
+        let started = TrackEventTimestamp::now();
+        let track = TrackEventTrack::register_named_track_with_dynamic_name(
+            &queue.name,
+            submission.id,
+            TrackEventTrack::process_track_uuid(),
+        )
+        .expect("failed to register queued task track");
+        queue.emit(
+            TrackEventType::SliceBegin(QUEUED_SLICE_NAME.as_ptr()),
+            |ctx| {
+                ctx.set_track(&track)
+                    .set_timestamp(submission.timestamp)
+                    .set_flow(&flow);
+            },
+        );
+        queue.emit(TrackEventType::SliceEnd, |ctx| {
+            ctx.set_track(&track).set_timestamp(started);
+        });
+        let delay = started
+            .timestamp()
+            .saturating_sub(submission.timestamp.timestamp());
+        queue.emit(TrackEventType::SliceBegin(queue.c_name.as_ptr()), |ctx| {
+            ctx.set_terminating_flow(&flow)
+                .add_debug_arg("queue_delay_ns", TrackEventDebugArg::Uint64(delay));
+        });
+        // The execution slice is ended if `f` panics, so that the slices of
+        // the worker thread stay balanced.
+        let _end = ScopeGuard::new(|| queue.emit(TrackEventType::SliceEnd, |_| {}));
+        f(self.item)
+    }
+}
+
+impl<F, R> QueuedTask<F>
+where
+    F: FnOnce() -> R,
+{
+    /// Runs the submitted closure on the calling thread, see
+    /// [`QueuedTask::run`].
+    pub fn call(self) -> R {
+        self.run(|f| f())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        async_task_category,
+        pb_decoder::{PbDecoder, PbDecoderField},
+        protos::trace::{
+            trace::TraceFieldNumber,
+            trace_packet::TracePacketFieldNumber,
+            track_event::track_event::{TrackEventFieldNumber, TrackEventType as EventType},
+        },
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        track_event::TrackEvent,
+    };
+    use std::{error::Error, sync::mpsc, thread};
+
+    crate::track_event_categories! {
+        pub mod test_te_ns {
+            ( "pool", "Test thread pool", [] ),
+        }
+    }
+    use test_te_ns as perfetto_te_ns;
+
+    #[derive(Default, Debug)]
+    struct Event {
+        r#type: Option<EventType>,
+        track_uuid: Option<u64>,
+        flow_ids: Vec<u64>,
+        terminating_flow_ids: Vec<u64>,
+    }
+
+    impl Event {
+        fn decode(data: &[u8]) -> Self {
+            use PbDecoderField::*;
+            let mut event = Event::default();
+            const TYPE_ID: u32 = TrackEventFieldNumber::Type as u32;
+            const TRACK_UUID_ID: u32 = TrackEventFieldNumber::TrackUuid as u32;
+            const FLOW_IDS_ID: u32 = TrackEventFieldNumber::FlowIds as u32;
+            const TERMINATING_FLOW_IDS_ID: u32 = TrackEventFieldNumber::TerminatingFlowIds as u32;
+            for field in PbDecoder::new(data) {
+                match field.unwrap() {
+                    (TYPE_ID, Varint(v)) => event.r#type = EventType::try_from(v as u32).ok(),
+                    (TRACK_UUID_ID, Varint(v)) => event.track_uuid = Some(v),
+                    (FLOW_IDS_ID, Fixed64(v)) => event.flow_ids.push(v),
+                    (TERMINATING_FLOW_IDS_ID, Fixed64(v)) => event.terminating_flow_ids.push(v),
+                    _ => {}
+                }
+            }
+            event
+        }
+    }
+
+    fn read_trace_events(data: &[u8]) -> Vec<Event> {
+        const PACKET_ID: u32 = TraceFieldNumber::Packet as u32;
+        const TRACK_EVENT_ID: u32 = TracePacketFieldNumber::TrackEvent as u32;
+        let mut events = vec![];
+        for trace_field in PbDecoder::new(data) {
+            if let (PACKET_ID, PbDecoderField::Delimited(packet)) = trace_field.unwrap() {
+                for packet_field in PbDecoder::new(packet) {
+                    if let (TRACK_EVENT_ID, PbDecoderField::Delimited(v)) = packet_field.unwrap() {
+                        events.push(Event::decode(v));
+                    }
+                }
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn submit_and_run() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        TrackEvent::init();
+        test_te_ns::register()?;
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name("track_event")
+            .add_enabled_category("pool")
+            .add_disabled_category("*")
+            .build()?;
+        session.start_blocking();
+        let queue = TaskQueue::new(async_task_category!("pool"), "jobs");
+        let (sender, receiver) = mpsc::channel::<QueuedTask<u32>>();
+        let worker = thread::spawn(move || {
+            receiver
+                .into_iter()
+                .map(|task| task.run(|value| value * 2))
+                .sum::<u32>()
+        });
+        let task = queue.submit(21);
+        let id = task.id().unwrap();
+        sender.send(task)?;
+        drop(sender);
+        assert_eq!(worker.join().unwrap(), 42);
+        assert_eq!(thread::spawn(queue.wrap(|| 7)).join().unwrap(), 7);
+        session.stop_blocking();
+        test_te_ns::unregister()?;
+
+        let events = read_trace_events(&read_trace_data(&mut session));
+        let count = |r#type| events.iter().filter(|event| event.r#type == r#type).count();
+        assert_eq!(count(Some(EventType::TypeInstant)), 2);
+        assert_eq!(count(Some(EventType::TypeSliceBegin)), 4);
+        assert_eq!(count(Some(EventType::TypeSliceEnd)), 4);
+        // The queued slice is on the track of the task, and the submit event,
+        // the queued slice and the execution slice are connected by a flow.
+        let process_uuid = TrackEventTrack::process_track_uuid();
+        let uuid = TrackEventTrack::named_track_uuid("jobs", id, process_uuid);
+        let flow_ids = vec![uuid ^ process_uuid];
+        let submit = events
+            .iter()
+            .find(|event| event.flow_ids == flow_ids)
+            .unwrap();
+        assert_eq!(submit.r#type, Some(EventType::TypeInstant));
+        let queued = events
+            .iter()
+            .position(|event| event.track_uuid == Some(uuid))
+            .unwrap();
+        assert_eq!(events[queued].flow_ids, flow_ids);
+        assert_eq!(events[queued + 1].r#type, Some(EventType::TypeSliceEnd));
+        assert_eq!(events[queued + 1].track_uuid, Some(uuid));
+        assert_eq!(events[queued + 2].terminating_flow_ids, flow_ids);
+        Ok(())
+    }
+
+    #[test]
+    fn disabled_category() {
+        let _lock = acquire_test_environment();
+        TrackEvent::init();
+        let queue = TaskQueue::new(async_task_category!("pool"), "jobs");
+        let task = queue.submit(1);
+        assert_eq!(task.id(), None);
+        assert_eq!(task.run(|value| value + 1), 2);
+    }
+
+    #[test]
+    fn name_with_nul_byte() {
+        let queue = TaskQueue::new(async_task_category!("pool"), "jobs\0high");
+        assert_eq!(queue.name(), "jobs\0high");
+        assert_eq!(queue.inner.c_name.to_str(), Ok("jobs high"));
+    }
+}
@@ -0,0 +1,418 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    pb_decoder::{PbDecoder, PbDecoderField},
+    protos::trace::trace_packet::{TracePacketFieldNumber, TracePacketSequenceFlags},
+    trace_reader::{TraceReader, TraceReaderError},
+};
+use std::{collections::BTreeMap, fmt};
+
+// Fields of protos that aren't part of the SDK.
+const TRACE_PACKET_TRACE_STATS: u32 = 35;
+const TRACE_PACKET_PREVIOUS_PACKET_DROPPED: u32 = 42;
+const TRACE_STATS_BUFFER_STATS: u32 = 1;
+const BUFFER_STATS_CHUNKS_OVERWRITTEN: u32 = 3;
+const BUFFER_STATS_PATCHES_FAILED: u32 = 6;
+const BUFFER_STATS_ABI_VIOLATIONS: u32 = 9;
+const BUFFER_STATS_CHUNKS_DISCARDED: u32 = 18;
+const BUFFER_STATS_TRACE_WRITER_PACKET_LOSS: u32 = 19;
+
+/// Returns the name of the `TracePacket` field with id `field_id`, for the
+/// payload types that are common in traces.
+pub fn packet_type_name(field_id: u32) -> Option<&'static str> {
+    use TracePacketFieldNumber as Packet;
+    let name = match field_id {
+        1 => "ftrace_events",
+        2 => "process_tree",
+        9 => "process_stats",
+        33 => "trace_config",
+        TRACE_PACKET_TRACE_STATS => "trace_stats",
+        36 => "profile_packet",
+        51 => "system_info",
+        62 => "perf_sample",
+        _ if field_id == Packet::ClockSnapshot as u32 => "clock_snapshot",
+        _ if field_id == Packet::TrackEvent as u32 => "track_event",
+        _ if field_id == Packet::TrackDescriptor as u32 => "track_descriptor",
+        _ if field_id == Packet::ExtensionDescriptor as u32 => "extension_descriptor",
+        _ if field_id == Packet::ForTesting as u32 => "for_testing",
+        _ if field_id == Packet::InternedData as u32 => "interned_data",
+        _ if field_id == Packet::TracePacketDefaults as u32 => "trace_packet_defaults",
+        _ if field_id == Packet::ModuleSymbols as u32 => "module_symbols",
+        _ => return None,
+    };
+    Some(name)
+}
+
+// Fields of `TracePacket` that are set in addition to the payload of the
+// packet and don't determine its type.
+pub(crate) fn is_packet_metadata(field_id: u32) -> bool {
+    use TracePacketFieldNumber as Packet;
+    [
+        Packet::Timestamp as u32,
+        Packet::TimestampClockId as u32,
+        Packet::TrustedUid as u32,
+        Packet::TrustedPacketSequenceId as u32,
+        Packet::TrustedPid as u32,
+        Packet::FirstPacketOnSequence as u32,
+        Packet::InternedData as u32,
+        Packet::SequenceFlags as u32,
+        Packet::TracePacketDefaults as u32,
+        TRACE_PACKET_PREVIOUS_PACKET_DROPPED,
+    ]
+    .contains(&field_id)
+}
+
+/// Statistics of the packets of a payload type.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PacketTypeStats {
+    /// Number of packets.
+    pub packets: u64,
+    /// Encoded size of the packets in bytes.
+    pub bytes: u64,
+    /// Smallest packet timestamp, in the clock of the packet.
+    pub min_timestamp: Option<u64>,
+    /// Largest packet timestamp, in the clock of the packet.
+    pub max_timestamp: Option<u64>,
+}
+
+impl PacketTypeStats {
+    fn add(&mut self, bytes: usize, timestamp: Option<u64>) {
+        self.packets += 1;
+        self.bytes += bytes as u64;
+        if let Some(timestamp) = timestamp {
+            self.min_timestamp = Some(self.min_timestamp.map_or(timestamp, |t| t.min(timestamp)));
+            self.max_timestamp = Some(self.max_timestamp.map_or(timestamp, |t| t.max(timestamp)));
+        }
+    }
+}
+
+/// Statistics of the packets of a packet sequence.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SequenceStats {
+    /// Number of packets.
+    pub packets: u64,
+    /// Encoded size of the packets in bytes.
+    pub bytes: u64,
+    /// Number of packets preceded by packets that were lost, as indicated by
+    /// `previous_packet_dropped`.
+    pub packets_dropped_before: u64,
+    /// Number of times the incremental state of the sequence was cleared.
+    pub incremental_state_clears: u64,
+}
+
+/// Loss counters of the trace buffers, summed over the buffers, as reported
+/// by the `trace_stats` packets of the tracing service.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BufferLossStats {
+    /// Chunks overwritten before they were read, in ring buffers.
+    pub chunks_overwritten: u64,
+    /// Chunks discarded because the buffer was full.
+    pub chunks_discarded: u64,
+    /// Patches of chunks that failed to apply.
+    pub patches_failed: u64,
+    /// Chunks dropped because their producer violated the ABI.
+    pub abi_violations: u64,
+    /// Packets lost by trace writers, e.g. when the buffer was full.
+    pub trace_writer_packet_loss: u64,
+}
+
+impl BufferLossStats {
+    /// Returns true if any data was lost.
+    pub fn has_losses(&self) -> bool {
+        *self != Self::default()
+    }
+}
+
+/// Statistics of a serialized `Trace`, to find out which data takes up its
+/// space and whether data was lost.
+///
+/// Packets are grouped by the type of their payload, e.g. `track_event`,
+/// which for most data sources identifies the data source that wrote them,
+/// and by packet sequence.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::trace_analyzer::TraceAnalysis;
+///
+/// // A trace with a single packet with a timestamp.
+/// let trace: &[u8] = b"\x0a\x02\x40\x07";
+/// let analysis = TraceAnalysis::new(trace).unwrap();
+/// assert_eq!(analysis.packets, 1);
+/// println!("{analysis}");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraceAnalysis {
+    /// Number of packets.
+    pub packets: u64,
+    /// Encoded size of the packets in bytes. Compressed packets are counted
+    /// with their decompressed size.
+    pub bytes: u64,
+    /// Statistics by payload type, keyed by `TracePacket` field id. Packets
+    /// without payload, e.g. that only carry interned data, use id 0.
+    pub packet_types: BTreeMap<u32, PacketTypeStats>,
+    /// Statistics by `trusted_packet_sequence_id`.
+    pub sequences: BTreeMap<u32, SequenceStats>,
+    /// Loss counters of the trace buffers.
+    pub buffer_losses: BufferLossStats,
+}
+
+impl TraceAnalysis {
+    /// Analyzes the serialized trace in `data`.
+    pub fn new(data: &[u8]) -> Result<Self, TraceReaderError> {
+        let mut analysis = Self::default();
+        for packet in TraceReader::new(data) {
+            analysis.add_packet(&packet?);
+        }
+        Ok(analysis)
+    }
+
+    /// Adds an encoded `TracePacket` to the statistics, e.g. to analyze packets
+    /// as they are read from a live session. Packets that fail to decode are
+    /// counted with the type they were found to have so far.
+    pub fn add_packet(&mut self, packet: &[u8]) {
+        let mut packet_type = 0;
+        let mut timestamp = None;
+        let mut sequence_id = 0;
+        let mut dropped_before = false;
+        let mut cleared = false;
+        for (field_id, field) in PbDecoder::new(packet).map_while(Result::ok) {
+            match field {
+                PbDecoderField::Varint(value)
+                    if field_id == TracePacketFieldNumber::Timestamp as u32 =>
+                {
+                    timestamp = Some(value);
+                }
+                PbDecoderField::Varint(value)
+                    if field_id == TracePacketFieldNumber::TrustedPacketSequenceId as u32 =>
+                {
+                    sequence_id = value as u32;
+                }
+                PbDecoderField::Varint(value)
+                    if field_id == TracePacketFieldNumber::SequenceFlags as u32 =>
+                {
+                    cleared |=
+                        value & TracePacketSequenceFlags::SeqIncrementalStateCleared as u64 != 0;
+                }
+                PbDecoderField::Varint(value)
+                    if field_id == TRACE_PACKET_PREVIOUS_PACKET_DROPPED =>
+                {
+                    dropped_before = value != 0;
+                }
+                PbDecoderField::Delimited(data) if field_id == TRACE_PACKET_TRACE_STATS => {
+                    packet_type = field_id;
+                    self.add_trace_stats(data);
+                }
+                _ if !is_packet_metadata(field_id) => packet_type = field_id,
+                _ => {}
+            }
+        }
+        self.packets += 1;
+        self.bytes += packet.len() as u64;
+        self.packet_types
+            .entry(packet_type)
+            .or_default()
+            .add(packet.len(), timestamp);
+        let sequence = self.sequences.entry(sequence_id).or_default();
+        sequence.packets += 1;
+        sequence.bytes += packet.len() as u64;
+        sequence.packets_dropped_before += dropped_before as u64;
+        sequence.incremental_state_clears += cleared as u64;
+    }
+
+    fn add_trace_stats(&mut self, trace_stats: &[u8]) {
+        for (field_id, field) in PbDecoder::new(trace_stats).map_while(Result::ok) {
+            let PbDecoderField::Delimited(buffer_stats) = field else {
+                continue;
+            };
+            if field_id != TRACE_STATS_BUFFER_STATS {
+                continue;
+            }
+            for (field_id, field) in PbDecoder::new(buffer_stats).map_while(Result::ok) {
+                let PbDecoderField::Varint(value) = field else {
+                    continue;
+                };
+                let losses = &mut self.buffer_losses;
+                match field_id {
+                    BUFFER_STATS_CHUNKS_OVERWRITTEN => losses.chunks_overwritten += value,
+                    BUFFER_STATS_CHUNKS_DISCARDED => losses.chunks_discarded += value,
+                    BUFFER_STATS_PATCHES_FAILED => losses.patches_failed += value,
+                    BUFFER_STATS_ABI_VIOLATIONS => losses.abi_violations += value,
+                    BUFFER_STATS_TRACE_WRITER_PACKET_LOSS => {
+                        losses.trace_writer_packet_loss += value
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Returns the payload types sorted by decreasing encoded size.
+    pub fn largest_packet_types(&self) -> Vec<(u32, &PacketTypeStats)> {
+        let mut types: Vec<_> = self.packet_types.iter().map(|(id, s)| (*id, s)).collect();
+        types.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then(a.0.cmp(&b.0)));
+        types
+    }
+
+    /// Returns the number of packets that were preceded by lost packets on
+    /// their sequence.
+    pub fn packets_dropped_before(&self) -> u64 {
+        self.sequences
+            .values()
+            .map(|sequence| sequence.packets_dropped_before)
+            .sum()
+    }
+}
+
+impl fmt::Display for TraceAnalysis {
+    /// Writes a summary of the payload types, largest first, and of the losses.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} packets, {} bytes, {} sequences",
+            self.packets,
+            self.bytes,
+            self.sequences.len()
+        )?;
+        for (field_id, stats) in self.largest_packet_types() {
+            let name = match (field_id, packet_type_name(field_id)) {
+                (0, _) => "<no payload>".to_string(),
+                (_, Some(name)) => name.to_string(),
+                (_, None) => format!("field {field_id}"),
+            };
+            let share = stats.bytes as f64 * 100.0 / self.bytes.max(1) as f64;
+            write!(
+                f,
+                "  {name}: {} packets, {} bytes ({share:.1}%)",
+                stats.packets, stats.bytes
+            )?;
+            if let (Some(min), Some(max)) = (stats.min_timestamp, stats.max_timestamp) {
+                write!(f, ", timestamps {min}..={max}")?;
+            }
+            writeln!(f)?;
+        }
+        let dropped = self.packets_dropped_before();
+        if dropped > 0 {
+            writeln!(f, "  {dropped} packets preceded by lost packets")?;
+        }
+        if self.buffer_losses.has_losses() {
+            writeln!(f, "  buffer losses: {:?}", self.buffer_losses)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_decoder::append_field,
+        protos::trace::{test_event::TestEventFieldNumber, trace::TraceFieldNumber},
+    };
+    use std::error::Error;
+
+    fn append_packet(trace: &mut Vec<u8>, fields: &[(u32, PbDecoderField)]) {
+        let mut packet = Vec::new();
+        for (field_id, field) in fields {
+            append_field(&mut packet, *field_id, field);
+        }
+        append_field(
+            trace,
+            TraceFieldNumber::Packet as u32,
+            &PbDecoderField::Delimited(&packet),
+        );
+    }
+
+    #[test]
+    fn analyze() -> Result<(), Box<dyn Error>> {
+        use TracePacketFieldNumber as Packet;
+        let mut for_testing = Vec::new();
+        append_field(
+            &mut for_testing,
+            TestEventFieldNumber::Counter as u32,
+            &PbDecoderField::Varint(1),
+        );
+        let mut trace = Vec::new();
+        for (timestamp, dropped) in [(300, 0), (100, 1)] {
+            append_packet(
+                &mut trace,
+                &[
+                    (Packet::Timestamp as u32, PbDecoderField::Varint(timestamp)),
+                    (
+                        Packet::TrustedPacketSequenceId as u32,
+                        PbDecoderField::Varint(2),
+                    ),
+                    (
+                        TRACE_PACKET_PREVIOUS_PACKET_DROPPED,
+                        PbDecoderField::Varint(dropped),
+                    ),
+                    (
+                        Packet::ForTesting as u32,
+                        PbDecoderField::Delimited(&for_testing),
+                    ),
+                ],
+            );
+        }
+        let mut buffer_stats = Vec::new();
+        append_field(
+            &mut buffer_stats,
+            BUFFER_STATS_CHUNKS_DISCARDED,
+            &PbDecoderField::Varint(4),
+        );
+        let mut trace_stats = Vec::new();
+        append_field(
+            &mut trace_stats,
+            TRACE_STATS_BUFFER_STATS,
+            &PbDecoderField::Delimited(&buffer_stats),
+        );
+        append_packet(
+            &mut trace,
+            &[
+                (
+                    Packet::TrustedPacketSequenceId as u32,
+                    PbDecoderField::Varint(1),
+                ),
+                (
+                    TRACE_PACKET_TRACE_STATS,
+                    PbDecoderField::Delimited(&trace_stats),
+                ),
+            ],
+        );
+
+        let analysis = TraceAnalysis::new(&trace)?;
+        assert_eq!(analysis.packets, 3);
+        assert_eq!(analysis.sequences.len(), 2);
+        let testing = &analysis.packet_types[&(Packet::ForTesting as u32)];
+        assert_eq!(testing.packets, 2);
+        assert_eq!(testing.min_timestamp, Some(100));
+        assert_eq!(testing.max_timestamp, Some(300));
+        assert_eq!(
+            analysis.largest_packet_types()[0].0,
+            Packet::ForTesting as u32
+        );
+        assert_eq!(analysis.sequences[&2].packets_dropped_before, 1);
+        assert_eq!(analysis.packets_dropped_before(), 1);
+        assert_eq!(analysis.buffer_losses.chunks_discarded, 4);
+        assert!(analysis.buffer_losses.has_losses());
+        assert_eq!(
+            analysis.bytes,
+            analysis.packet_types.values().map(|s| s.bytes).sum::<u64>()
+        );
+        let summary = analysis.to_string();
+        assert!(summary.contains("for_testing: 2 packets"));
+        assert!(summary.contains("timestamps 100..=300"));
+        Ok(())
+    }
+}
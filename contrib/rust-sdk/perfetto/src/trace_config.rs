@@ -0,0 +1,530 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    heap_buffer::HeapBuffer,
+    pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField},
+    pb_msg::{PbMsg, PbMsgWriter},
+    protos::config::{
+        data_source_config::{DataSourceConfig, DataSourceConfigFieldNumber},
+        trace_config::{
+            BufferConfigFillPolicy, TraceConfig, TraceConfigBufferConfig,
+            TraceConfigBufferConfigFieldNumber, TraceConfigDataSource,
+            TraceConfigDataSourceFieldNumber, TraceConfigFieldNumber, TraceConfigTriggerConfig,
+            TraceConfigTriggerConfigFieldNumber, TraceConfigTriggerConfigTrigger,
+            TriggerConfigTriggerMode,
+        },
+        track_event::track_event_config::TrackEventConfig,
+    },
+};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Default time that a session with triggers waits for a trigger, see
+/// [`TraceConfigBuilder::trigger_timeout`].
+pub const DEFAULT_TRIGGER_TIMEOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Trace config builder errors.
+#[derive(Error, Debug, PartialEq)]
+pub enum TraceConfigError {
+    /// Both start and stop triggers were added.
+    #[error("A trace config can't have both start and stop triggers.")]
+    ConflictingTriggerModes,
+    /// A duration was set for a session with triggers, which the tracing
+    /// service rejects.
+    #[error("The duration of a session with triggers is set by the triggers.")]
+    DurationWithTriggers,
+    /// The trigger timeout is zero or longer than the tracing service allows.
+    #[error("Invalid trigger timeout: {0:?}.")]
+    InvalidTriggerTimeout(Duration),
+}
+
+type DataSourceConfigCallback = Box<dyn Fn(&mut DataSourceConfig) + Send + Sync + 'static>;
+
+// Longest trigger timeout accepted by the tracing service.
+const MAX_TRIGGER_TIMEOUT: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Builds the encoded `TraceConfig` of common session setups, for
+/// [`TracingSession::setup`](crate::tracing_session::TracingSession::setup).
+///
+/// The config has a single trace buffer that all data sources write to. Adding
+/// triggers sets up the `trigger_config` of the session, including the trigger
+/// mode and timeout that the tracing service requires.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::trace_config::TraceConfigBuilder;
+/// use std::time::Duration;
+///
+/// // Keeps the last 16 MB of track events, until "jank" is triggered.
+/// let config = TraceConfigBuilder::ring_buffer(16 * 1024)
+///     .track_event(&["gfx", "input"])
+///     .stop_on_trigger("jank", Duration::from_millis(500))
+///     .build()
+///     .unwrap();
+/// ```
+#[must_use = "This is a builder; remember to call `.build()` (or keep chaining)."]
+pub struct TraceConfigBuilder {
+    buffer_size_kb: u32,
+    fill_policy: BufferConfigFillPolicy,
+    duration: Option<Duration>,
+    data_source_stop_timeout: Option<Duration>,
+    data_sources: Vec<(String, DataSourceConfigCallback)>,
+    trigger_mode: Option<TriggerConfigTriggerMode>,
+    triggers: Vec<(String, Duration)>,
+    trigger_timeout: Duration,
+    conflicting_triggers: bool,
+}
+
+impl TraceConfigBuilder {
+    fn new(buffer_size_kb: u32, fill_policy: BufferConfigFillPolicy) -> Self {
+        Self {
+            buffer_size_kb,
+            fill_policy,
+            duration: None,
+            data_source_stop_timeout: None,
+            data_sources: Vec::new(),
+            trigger_mode: None,
+            triggers: Vec::new(),
+            trigger_timeout: DEFAULT_TRIGGER_TIMEOUT,
+            conflicting_triggers: false,
+        }
+    }
+
+    /// Creates a config with a `size_kb` buffer that overwrites the oldest
+    /// data when full.
+    pub fn ring_buffer(size_kb: u32) -> Self {
+        Self::new(size_kb, BufferConfigFillPolicy::RingBuffer)
+    }
+
+    /// Creates a config with a `size_kb` buffer that drops new data when full.
+    pub fn discard(size_kb: u32) -> Self {
+        Self::new(size_kb, BufferConfigFillPolicy::Discard)
+    }
+
+    /// Adds the data source named `name`.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn data_source(self, name: impl Into<String>) -> Self {
+        self.data_source_with(name, |_config: &mut DataSourceConfig| {})
+    }
+
+    /// Adds the data source named `name`, with `cb` filling in the rest of its
+    /// config.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn data_source_with<F>(mut self, name: impl Into<String>, cb: F) -> Self
+    where
+        F: Fn(&mut DataSourceConfig) + Send + Sync + 'static,
+    {
+        self.data_sources.push((name.into(), Box::new(cb)));
+        self
+    }
+
+    /// Adds the track event data source with the `enabled` categories enabled
+    /// and all the others disabled.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn track_event(self, enabled: &[&str]) -> Self {
+        let enabled: Vec<String> = enabled
+            .iter()
+            .map(|category| category.to_string())
+            .collect();
+        self.data_source_with("track_event", move |config: &mut DataSourceConfig| {
+            config.set_track_event_config(|te_config: &mut TrackEventConfig| {
+                for category in &enabled {
+                    te_config.set_enabled_categories(category);
+                }
+                te_config.set_disabled_categories("*");
+            });
+        })
+    }
+
+    /// Stops the session after `duration`. Not supported with triggers.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Sets how long the tracing service waits for the data sources to stop,
+    /// e.g. for data sources that postpone their stop to tear down hardware.
+    /// The service passes it to the data sources in their config, see
+    /// [`DataSourceArgsBuilder::stop_timeout`](crate::data_source::DataSourceArgsBuilder::stop_timeout).
+    /// Defaults to [`DEFAULT_STOP_TIMEOUT`](crate::data_source::DEFAULT_STOP_TIMEOUT).
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn data_source_stop_timeout(mut self, timeout: Duration) -> Self {
+        self.data_source_stop_timeout = Some(timeout);
+        self
+    }
+
+    /// Starts the data sources when `name` is triggered rather than when the
+    /// session is started, and stops the session `stop_delay` later.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn start_on_trigger(self, name: impl Into<String>, stop_delay: Duration) -> Self {
+        self.add_trigger(TriggerConfigTriggerMode::StartTracing, name, stop_delay)
+    }
+
+    /// Stops the session `stop_delay` after `name` is triggered. Combined with
+    /// a ring buffer, the trace has the data recorded before the trigger.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn stop_on_trigger(self, name: impl Into<String>, stop_delay: Duration) -> Self {
+        self.add_trigger(TriggerConfigTriggerMode::StopTracing, name, stop_delay)
+    }
+
+    /// Sets how long a session with triggers waits for a trigger before
+    /// stopping. Defaults to [`DEFAULT_TRIGGER_TIMEOUT`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn trigger_timeout(mut self, timeout: Duration) -> Self {
+        self.trigger_timeout = timeout;
+        self
+    }
+
+    fn add_trigger(
+        mut self,
+        mode: TriggerConfigTriggerMode,
+        name: impl Into<String>,
+        stop_delay: Duration,
+    ) -> Self {
+        if self.trigger_mode.is_some_and(|existing| existing != mode) {
+            self.conflicting_triggers = true;
+        }
+        self.trigger_mode = Some(mode);
+        self.triggers.push((name.into(), stop_delay));
+        self
+    }
+
+    /// Returns the encoded `TraceConfig`.
+    pub fn build(&self) -> Result<Vec<u8>, TraceConfigError> {
+        if self.conflicting_triggers {
+            return Err(TraceConfigError::ConflictingTriggerModes);
+        }
+        if self.trigger_mode.is_some() {
+            if self.duration.is_some() {
+                return Err(TraceConfigError::DurationWithTriggers);
+            }
+            if self.trigger_timeout.as_millis() == 0 || self.trigger_timeout > MAX_TRIGGER_TIMEOUT {
+                return Err(TraceConfigError::InvalidTriggerTimeout(
+                    self.trigger_timeout,
+                ));
+            }
+        }
+        let writer = PbMsgWriter::new();
+        let hb = HeapBuffer::new(&writer.writer);
+        let mut msg = PbMsg::new(&writer).unwrap();
+        {
+            let mut cfg = TraceConfig { msg: &mut msg };
+            cfg.set_buffers(|buf_cfg: &mut TraceConfigBufferConfig| {
+                buf_cfg.set_size_kb(self.buffer_size_kb);
+                buf_cfg.set_fill_policy(self.fill_policy);
+            });
+            if let Some(duration) = self.duration {
+                cfg.set_duration_ms(duration.as_millis().try_into().unwrap_or(u32::MAX));
+            }
+            if let Some(timeout) = self.data_source_stop_timeout {
+                cfg.set_data_source_stop_timeout_ms(
+                    timeout.as_millis().try_into().unwrap_or(u32::MAX),
+                );
+            }
+            for (name, cb) in &self.data_sources {
+                cfg.set_data_sources(|data_source: &mut TraceConfigDataSource| {
+                    data_source.set_config(|ds_cfg: &mut DataSourceConfig| {
+                        ds_cfg.set_name(name).set_target_buffer(0);
+                        cb(ds_cfg);
+                    });
+                });
+            }
+            if let Some(mode) = self.trigger_mode {
+                cfg.set_trigger_config(|trigger_cfg: &mut TraceConfigTriggerConfig| {
+                    trigger_cfg.set_trigger_mode(mode);
+                    trigger_cfg.set_trigger_timeout_ms(self.trigger_timeout.as_millis() as u32);
+                    for (name, stop_delay) in &self.triggers {
+                        trigger_cfg.set_triggers(
+                            |trigger: &mut TraceConfigTriggerConfigTrigger| {
+                                trigger.set_name(name);
+                                trigger.set_stop_delay_ms(
+                                    stop_delay.as_millis().try_into().unwrap_or(u32::MAX),
+                                );
+                            },
+                        );
+                    }
+                });
+            }
+        }
+        msg.finalize();
+        let size = writer.writer.get_written_size();
+        let mut buffer = vec![0u8; size];
+        hb.copy_into(&mut buffer);
+        Ok(buffer)
+    }
+}
+
+/// Main settings of an encoded `TraceConfig`, see
+/// [`TracingSession::query_config`](crate::tracing_session::TracingSession::query_config).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceConfigSummary {
+    /// Sizes of the trace buffers, in KB.
+    pub buffer_sizes_kb: Vec<u32>,
+    /// Names of the data sources, in the order of the config.
+    pub data_sources: Vec<String>,
+    /// Duration of the session, if set.
+    pub duration: Option<Duration>,
+    /// Whether the trace is periodically written into a file.
+    pub write_into_file: bool,
+    /// Unique name of the session, if set.
+    pub unique_session_name: Option<String>,
+    /// Time the session waits for a trigger, if it has triggers.
+    pub trigger_timeout: Option<Duration>,
+}
+
+impl TraceConfigSummary {
+    /// Decodes the main settings of the encoded `TraceConfig` `cfg`. Other
+    /// fields are ignored.
+    pub fn decode(cfg: &[u8]) -> Result<Self, PbDecoderError> {
+        const BUFFERS: u32 = TraceConfigFieldNumber::Buffers as u32;
+        const DATA_SOURCES: u32 = TraceConfigFieldNumber::DataSources as u32;
+        const DURATION_MS: u32 = TraceConfigFieldNumber::DurationMs as u32;
+        const WRITE_INTO_FILE: u32 = TraceConfigFieldNumber::WriteIntoFile as u32;
+        const UNIQUE_SESSION_NAME: u32 = TraceConfigFieldNumber::UniqueSessionName as u32;
+        const TRIGGER_CONFIG: u32 = TraceConfigFieldNumber::TriggerConfig as u32;
+        let varint = |field: &PbDecoderField| match field {
+            PbDecoderField::Varint(value) => Ok(*value),
+            _ => Err(PbDecoderError::NotVarint),
+        };
+        let mut summary = Self::default();
+        for item in PbDecoder::new(cfg) {
+            let (id, field) = item?;
+            match id {
+                BUFFERS => {
+                    for item in field.as_decoder()? {
+                        let (id, field) = item?;
+                        if id == TraceConfigBufferConfigFieldNumber::SizeKb as u32 {
+                            summary.buffer_sizes_kb.push(varint(&field)? as u32);
+                        }
+                    }
+                }
+                DATA_SOURCES => {
+                    for item in field.as_decoder()? {
+                        let (id, field) = item?;
+                        if id != TraceConfigDataSourceFieldNumber::Config as u32 {
+                            continue;
+                        }
+                        for item in field.as_decoder()? {
+                            let (id, field) = item?;
+                            if id == DataSourceConfigFieldNumber::Name as u32 {
+                                summary.data_sources.push(field.as_str()?.to_string());
+                            }
+                        }
+                    }
+                }
+                DURATION_MS => {
+                    summary.duration = Some(Duration::from_millis(varint(&field)?));
+                }
+                WRITE_INTO_FILE => summary.write_into_file = varint(&field)? != 0,
+                UNIQUE_SESSION_NAME => {
+                    summary.unique_session_name = Some(field.as_str()?.to_string());
+                }
+                TRIGGER_CONFIG => {
+                    for item in field.as_decoder()? {
+                        let (id, field) = item?;
+                        if id == TraceConfigTriggerConfigFieldNumber::TriggerTimeoutMs as u32 {
+                            summary.trigger_timeout = Some(Duration::from_millis(varint(&field)?));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data_source::TraceContext,
+        producer::Producer,
+        protos::config::trace_config::TraceConfigTriggerConfigTriggerFieldNumber,
+        test_util::{DATA_SOURCE_NAME, data_source, field},
+        tests::{acquire_test_environment, read_trace_data},
+        tracing_session::TracingSession,
+    };
+    use std::error::Error;
+
+    fn message(msg: &[u8], field_id: u32) -> &[u8] {
+        match field(msg, field_id) {
+            Some(PbDecoderField::Delimited(value)) => value,
+            field => panic!("unexpected field {:?}", field),
+        }
+    }
+
+    fn varint(msg: &[u8], field_id: u32) -> u64 {
+        match field(msg, field_id) {
+            Some(PbDecoderField::Varint(value)) => value,
+            field => panic!("unexpected field {:?}", field),
+        }
+    }
+
+    #[test]
+    fn stop_on_trigger() {
+        let config = TraceConfigBuilder::ring_buffer(2048)
+            .data_source_with(DATA_SOURCE_NAME, |config: &mut DataSourceConfig| {
+                config.set_trace_duration_ms(1);
+            })
+            .stop_on_trigger("trigger", Duration::from_millis(200))
+            .build()
+            .unwrap();
+        let buffer = message(&config, TraceConfigFieldNumber::Buffers as u32);
+        assert_eq!(
+            varint(buffer, TraceConfigBufferConfigFieldNumber::SizeKb as u32),
+            2048
+        );
+        assert_eq!(
+            varint(
+                buffer,
+                TraceConfigBufferConfigFieldNumber::FillPolicy as u32
+            ),
+            BufferConfigFillPolicy::RingBuffer as u64
+        );
+        let data_source = message(&config, TraceConfigFieldNumber::DataSources as u32);
+        let ds_config = message(data_source, TraceConfigDataSourceFieldNumber::Config as u32);
+        assert_eq!(
+            field(ds_config, DataSourceConfigFieldNumber::Name as u32),
+            Some(PbDecoderField::Delimited(DATA_SOURCE_NAME.as_bytes()))
+        );
+        assert_eq!(
+            varint(
+                ds_config,
+                DataSourceConfigFieldNumber::TraceDurationMs as u32
+            ),
+            1
+        );
+        let trigger_config = message(&config, TraceConfigFieldNumber::TriggerConfig as u32);
+        assert_eq!(
+            varint(
+                trigger_config,
+                TraceConfigTriggerConfigFieldNumber::TriggerMode as u32
+            ),
+            TriggerConfigTriggerMode::StopTracing as u64
+        );
+        assert_eq!(
+            varint(
+                trigger_config,
+                TraceConfigTriggerConfigFieldNumber::TriggerTimeoutMs as u32
+            ),
+            DEFAULT_TRIGGER_TIMEOUT.as_millis() as u64
+        );
+        let trigger = message(
+            trigger_config,
+            TraceConfigTriggerConfigFieldNumber::Triggers as u32,
+        );
+        assert_eq!(
+            field(
+                trigger,
+                TraceConfigTriggerConfigTriggerFieldNumber::Name as u32
+            ),
+            Some(PbDecoderField::Delimited(b"trigger"))
+        );
+        assert_eq!(
+            varint(
+                trigger,
+                TraceConfigTriggerConfigTriggerFieldNumber::StopDelayMs as u32
+            ),
+            200
+        );
+    }
+
+    #[test]
+    fn invalid_configs() {
+        assert_eq!(
+            TraceConfigBuilder::ring_buffer(1024)
+                .start_on_trigger("start", Duration::ZERO)
+                .stop_on_trigger("stop", Duration::ZERO)
+                .build(),
+            Err(TraceConfigError::ConflictingTriggerModes)
+        );
+        assert_eq!(
+            TraceConfigBuilder::discard(1024)
+                .duration(Duration::from_secs(1))
+                .stop_on_trigger("stop", Duration::ZERO)
+                .build(),
+            Err(TraceConfigError::DurationWithTriggers)
+        );
+        assert_eq!(
+            TraceConfigBuilder::discard(1024)
+                .stop_on_trigger("stop", Duration::ZERO)
+                .trigger_timeout(Duration::ZERO)
+                .build(),
+            Err(TraceConfigError::InvalidTriggerTimeout(Duration::ZERO))
+        );
+        let config = TraceConfigBuilder::discard(1024)
+            .duration(Duration::from_secs(1))
+            .build()
+            .unwrap();
+        assert_eq!(
+            varint(&config, TraceConfigFieldNumber::DurationMs as u32),
+            1000
+        );
+        assert!(field(&config, TraceConfigFieldNumber::TriggerConfig as u32).is_none());
+    }
+
+    #[test]
+    fn summary() {
+        let config = TraceConfigBuilder::discard(2048)
+            .data_source("com.example.first")
+            .track_event(&["gfx"])
+            .duration(Duration::from_secs(3))
+            .build()
+            .unwrap();
+        assert_eq!(
+            TraceConfigSummary::decode(&config).unwrap(),
+            TraceConfigSummary {
+                buffer_sizes_kb: vec![2048],
+                data_sources: vec!["com.example.first".to_string(), "track_event".to_string()],
+                duration: Some(Duration::from_secs(3)),
+                write_into_file: false,
+                unique_session_name: None,
+                trigger_timeout: None,
+            }
+        );
+        let config = TraceConfigBuilder::ring_buffer(1024)
+            .stop_on_trigger("com.example.stop", Duration::ZERO)
+            .trigger_timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+        assert_eq!(
+            TraceConfigSummary::decode(&config).unwrap().trigger_timeout,
+            Some(Duration::from_secs(5))
+        );
+    }
+
+    #[test]
+    fn session() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let config = TraceConfigBuilder::ring_buffer(1024)
+            .data_source(DATA_SOURCE_NAME)
+            .stop_on_trigger("com.example.stop", Duration::ZERO)
+            .build()?;
+        let mut session = TracingSession::in_process()?;
+        session.setup(&config);
+        session.start_blocking();
+        let mut traced = false;
+        data_source.trace(|_ctx: &mut TraceContext| traced = true);
+        assert!(traced);
+        Producer::activate_trigger("com.example.stop", Duration::from_secs(10))?;
+        session.stop_blocking();
+        assert!(!read_trace_data(&mut session).is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,561 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    pb_decoder::{PbDecoder, PbDecoderError, append_field},
+    protos::trace::trace_packet::TracePacketFieldNumber,
+    trace_analyzer::{is_packet_metadata, packet_type_name},
+    trace_reader::{TraceReader, TraceReaderError},
+};
+use std::{collections::HashMap, fmt};
+use thiserror::Error;
+
+// Fields of protos that aren't part of the SDK.
+const TRACE_PACKET_TRACE_CONFIG: u32 = 33;
+const TRACE_PACKET_TRACE_STATS: u32 = 35;
+const TRACE_PACKET_SYNCHRONIZATION_MARKER: u32 = 36;
+const TRACE_PACKET_SYSTEM_INFO: u32 = 45;
+const TRACE_PACKET_SERVICE_EVENT: u32 = 69;
+const TRACE_PACKET_TRACE_UUID: u32 = 89;
+const TRACE_PACKET_MACHINE_ID: u32 = 98;
+const TRACE_PACKET_REMOTE_CLOCK_SYNC: u32 = 107;
+const TRACE_PACKET_TRACE_PROVENANCE: u32 = 124;
+const TRACE_PACKET_PREVIOUS_PACKET_DROPPED: u32 = 42;
+
+/// Trace diff errors.
+#[derive(Error, Debug, PartialEq)]
+pub enum TraceDiffError {
+    /// A trace could not be read.
+    #[error("Failed to read trace: {0}")]
+    Trace(#[from] TraceReaderError),
+    /// A packet could not be decoded.
+    #[error("Failed to decode packet: {0}")]
+    Decode(#[from] PbDecoderError),
+}
+
+// Fields of a packet, re-encoded one by one and sorted by field id.
+type PacketFields = Vec<(u32, Vec<u8>)>;
+
+#[derive(Debug)]
+struct NormalizedPacket {
+    index: usize,
+    packet_type: u32,
+    fields: PacketFields,
+}
+
+/// A difference between the packets of two traces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacketDiff {
+    /// A packet of the actual trace that isn't in the expected trace.
+    Added {
+        /// Index of the packet in the actual trace.
+        index: usize,
+        /// Payload type of the packet, as a `TracePacket` field id.
+        packet_type: u32,
+    },
+    /// A packet of the expected trace that isn't in the actual trace.
+    Removed {
+        /// Index of the packet in the expected trace.
+        index: usize,
+        /// Payload type of the packet, as a `TracePacket` field id.
+        packet_type: u32,
+    },
+    /// A packet of the expected trace that differs from the packet of the
+    /// same payload type of the actual trace it was paired with.
+    Changed {
+        /// Index of the packet in the expected trace.
+        expected_index: usize,
+        /// Index of the packet in the actual trace.
+        actual_index: usize,
+        /// Payload type of the packets, as a `TracePacket` field id.
+        packet_type: u32,
+        /// Ids of the `TracePacket` fields that differ.
+        fields: Vec<u32>,
+    },
+}
+
+/// Result of the comparison of two traces, see [`TraceDiffBuilder`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceDiff {
+    /// Number of packets of the expected trace that were compared.
+    pub expected_packets: usize,
+    /// Number of packets of the actual trace that were compared.
+    pub actual_packets: usize,
+    /// Differences between the traces, in the order of the expected trace,
+    /// followed by the added packets.
+    pub diffs: Vec<PacketDiff>,
+}
+
+impl TraceDiff {
+    /// Compares the serialized traces `expected` and `actual` with the default
+    /// options of [`TraceDiffBuilder`].
+    pub fn new(expected: &[u8], actual: &[u8]) -> Result<Self, TraceDiffError> {
+        TraceDiffBuilder::new().diff(expected, actual)
+    }
+
+    /// Returns true if the traces are equivalent.
+    pub fn is_empty(&self) -> bool {
+        self.diffs.is_empty()
+    }
+}
+
+fn type_name(packet_type: u32) -> String {
+    match (packet_type, packet_type_name(packet_type)) {
+        (0, _) => "<no payload>".to_string(),
+        (_, Some(name)) => name.to_string(),
+        (_, None) => format!("field {packet_type}"),
+    }
+}
+
+impl fmt::Display for TraceDiff {
+    /// Writes a line per difference, like a unified diff.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} expected packets, {} actual packets, {} differences",
+            self.expected_packets,
+            self.actual_packets,
+            self.diffs.len()
+        )?;
+        for diff in &self.diffs {
+            match diff {
+                PacketDiff::Added { index, packet_type } => {
+                    writeln!(f, "+ packet {index} ({})", type_name(*packet_type))?
+                }
+                PacketDiff::Removed { index, packet_type } => {
+                    writeln!(f, "- packet {index} ({})", type_name(*packet_type))?
+                }
+                PacketDiff::Changed {
+                    expected_index,
+                    actual_index,
+                    packet_type,
+                    fields,
+                } => writeln!(
+                    f,
+                    "~ packet {expected_index} -> {actual_index} ({}): fields {fields:?}",
+                    type_name(*packet_type)
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Semantic comparison of two traces, e.g. of the trace of a producer with a
+/// golden trace in a regression test.
+///
+/// Packets are compared field by field, regardless of the order of their
+/// fields. By default, the fields and packets that vary from one run to the
+/// next are ignored: packet timestamps, the fields set by the tracing
+/// service, such as `trusted_packet_sequence_id`, and the packets written by
+/// the tracing service, such as `trace_config` and `clock_snapshot`. The order
+/// of the packets is ignored by default as well, since the packets of
+/// different sequences are interleaved differently in each run.
+///
+/// Unmatched packets of the same payload type are reported as changed, in
+/// order, and the other ones as removed or added.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::trace_diff::TraceDiffBuilder;
+///
+/// // Traces with a single packet, with different timestamps.
+/// let expected: &[u8] = b"\x0a\x02\x40\x07";
+/// let actual: &[u8] = b"\x0a\x02\x40\x08";
+/// assert!(TraceDiffBuilder::new().diff(expected, actual).unwrap().is_empty());
+/// let diff = TraceDiffBuilder::new()
+///     .ignore_timestamps(false)
+///     .diff(expected, actual)
+///     .unwrap();
+/// assert_eq!(diff.diffs.len(), 1);
+/// println!("{diff}");
+/// ```
+#[must_use = "This is a builder; remember to call `.diff()` (or keep chaining)."]
+pub struct TraceDiffBuilder {
+    ignore_timestamps: bool,
+    ignore_order: bool,
+    ignored_fields: Vec<u32>,
+    ignored_packet_types: Vec<u32>,
+}
+
+impl Default for TraceDiffBuilder {
+    fn default() -> Self {
+        use TracePacketFieldNumber as Packet;
+        Self {
+            ignore_timestamps: true,
+            ignore_order: true,
+            ignored_fields: vec![
+                Packet::TrustedUid as u32,
+                Packet::TrustedPacketSequenceId as u32,
+                Packet::TrustedPid as u32,
+                Packet::FirstPacketOnSequence as u32,
+                TRACE_PACKET_PREVIOUS_PACKET_DROPPED,
+                TRACE_PACKET_MACHINE_ID,
+            ],
+            ignored_packet_types: vec![
+                Packet::ClockSnapshot as u32,
+                TRACE_PACKET_TRACE_CONFIG,
+                TRACE_PACKET_TRACE_STATS,
+                TRACE_PACKET_SYNCHRONIZATION_MARKER,
+                TRACE_PACKET_SYSTEM_INFO,
+                TRACE_PACKET_SERVICE_EVENT,
+                TRACE_PACKET_TRACE_UUID,
+                TRACE_PACKET_REMOTE_CLOCK_SYNC,
+                TRACE_PACKET_TRACE_PROVENANCE,
+            ],
+        }
+    }
+}
+
+impl TraceDiffBuilder {
+    /// Create new trace diff builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether the `timestamp` of packets is ignored. Defaults to true.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn ignore_timestamps(mut self, ignore: bool) -> Self {
+        self.ignore_timestamps = ignore;
+        self
+    }
+
+    /// Set whether the order of packets is ignored. Defaults to true. When
+    /// the order isn't ignored, packets are matched by a longest common
+    /// subsequence of the traces.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn ignore_order(mut self, ignore: bool) -> Self {
+        self.ignore_order = ignore;
+        self
+    }
+
+    /// Ignore the `TracePacket` field `field_id` in all packets.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn ignore_field(mut self, field_id: u32) -> Self {
+        self.ignored_fields.push(field_id);
+        self
+    }
+
+    /// Ignore the packets whose payload is the `TracePacket` field `field_id`.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn ignore_packet_type(mut self, field_id: u32) -> Self {
+        self.ignored_packet_types.push(field_id);
+        self
+    }
+
+    /// Compares the serialized traces `expected` and `actual`.
+    pub fn diff(&self, expected: &[u8], actual: &[u8]) -> Result<TraceDiff, TraceDiffError> {
+        let expected = TraceReader::new(expected).collect::<Result<Vec<_>, _>>()?;
+        let actual = TraceReader::new(actual).collect::<Result<Vec<_>, _>>()?;
+        self.diff_packets(&expected, &actual)
+    }
+
+    /// Compares the encoded `TracePacket`s of two packet streams.
+    pub fn diff_packets<P: AsRef<[u8]>>(
+        &self,
+        expected: &[P],
+        actual: &[P],
+    ) -> Result<TraceDiff, TraceDiffError> {
+        let expected = self.normalize(expected)?;
+        let actual = self.normalize(actual)?;
+        let matches = if self.ignore_order {
+            match_unordered(&expected, &actual)
+        } else {
+            match_ordered(&expected, &actual)
+        };
+        let mut expected_matched = vec![false; expected.len()];
+        let mut actual_matched = vec![false; actual.len()];
+        for (e, a) in matches {
+            expected_matched[e] = true;
+            actual_matched[a] = true;
+        }
+
+        let mut diffs = Vec::new();
+        for (e, expected_packet) in expected.iter().enumerate() {
+            if expected_matched[e] {
+                continue;
+            }
+            let paired = actual.iter().enumerate().position(|(a, actual_packet)| {
+                !actual_matched[a] && actual_packet.packet_type == expected_packet.packet_type
+            });
+            match paired {
+                Some(a) => {
+                    actual_matched[a] = true;
+                    diffs.push(PacketDiff::Changed {
+                        expected_index: expected_packet.index,
+                        actual_index: actual[a].index,
+                        packet_type: expected_packet.packet_type,
+                        fields: changed_fields(&expected_packet.fields, &actual[a].fields),
+                    });
+                }
+                None => diffs.push(PacketDiff::Removed {
+                    index: expected_packet.index,
+                    packet_type: expected_packet.packet_type,
+                }),
+            }
+        }
+        for (a, actual_packet) in actual.iter().enumerate() {
+            if !actual_matched[a] {
+                diffs.push(PacketDiff::Added {
+                    index: actual_packet.index,
+                    packet_type: actual_packet.packet_type,
+                });
+            }
+        }
+        Ok(TraceDiff {
+            expected_packets: expected.len(),
+            actual_packets: actual.len(),
+            diffs,
+        })
+    }
+
+    // Decodes the packets that aren't ignored into their compared fields.
+    fn normalize<P: AsRef<[u8]>>(
+        &self,
+        packets: &[P],
+    ) -> Result<Vec<NormalizedPacket>, TraceDiffError> {
+        let mut normalized = Vec::new();
+        for (index, packet) in packets.iter().enumerate() {
+            let mut packet_type = 0;
+            let mut fields = Vec::new();
+            for item in PbDecoder::new(packet.as_ref()) {
+                let (field_id, field) = item?;
+                if !is_packet_metadata(field_id) {
+                    packet_type = field_id;
+                }
+                if self.ignored_fields.contains(&field_id)
+                    || (self.ignore_timestamps
+                        && field_id == TracePacketFieldNumber::Timestamp as u32)
+                {
+                    continue;
+                }
+                let mut encoded = Vec::new();
+                append_field(&mut encoded, field_id, &field);
+                fields.push((field_id, encoded));
+            }
+            if self.ignored_packet_types.contains(&packet_type) {
+                continue;
+            }
+            // Repeated fields keep their order.
+            fields.sort_by_key(|(field_id, _)| *field_id);
+            normalized.push(NormalizedPacket {
+                index,
+                packet_type,
+                fields,
+            });
+        }
+        Ok(normalized)
+    }
+}
+
+// Matches equal packets regardless of their order.
+fn match_unordered(
+    expected: &[NormalizedPacket],
+    actual: &[NormalizedPacket],
+) -> Vec<(usize, usize)> {
+    let mut unmatched: HashMap<&PacketFields, Vec<usize>> = HashMap::new();
+    for (a, packet) in actual.iter().enumerate().rev() {
+        unmatched.entry(&packet.fields).or_default().push(a);
+    }
+    expected
+        .iter()
+        .enumerate()
+        .filter_map(|(e, packet)| {
+            let a = unmatched.get_mut(&packet.fields)?.pop()?;
+            Some((e, a))
+        })
+        .collect()
+}
+
+// Matches the equal packets of a longest common subsequence.
+fn match_ordered(
+    expected: &[NormalizedPacket],
+    actual: &[NormalizedPacket],
+) -> Vec<(usize, usize)> {
+    let (n, m) = (expected.len(), actual.len());
+    // lengths[i][j] is the length of the LCS of expected[i..] and actual[j..].
+    let mut lengths = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if expected[i].fields == actual[j].fields {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut matches = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if expected[i].fields == actual[j].fields {
+            matches.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    matches
+}
+
+// Returns the ids of the fields whose values differ.
+fn changed_fields(expected: &PacketFields, actual: &PacketFields) -> Vec<u32> {
+    let values = |fields: &PacketFields, field_id: u32| -> Vec<Vec<u8>> {
+        fields
+            .iter()
+            .filter(|(id, _)| *id == field_id)
+            .map(|(_, value)| value.clone())
+            .collect()
+    };
+    let mut field_ids: Vec<u32> = expected.iter().chain(actual).map(|(id, _)| *id).collect();
+    field_ids.sort_unstable();
+    field_ids.dedup();
+    field_ids
+        .into_iter()
+        .filter(|field_id| values(expected, *field_id) != values(actual, *field_id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_decoder::PbDecoderField,
+        protos::trace::{test_event::TestEventFieldNumber, trace::TraceFieldNumber},
+    };
+    use std::error::Error;
+
+    fn packet(fields: &[(u32, PbDecoderField)]) -> Vec<u8> {
+        let mut packet = Vec::new();
+        for (field_id, field) in fields {
+            append_field(&mut packet, *field_id, field);
+        }
+        packet
+    }
+
+    fn test_packet(timestamp: u64, sequence_id: u64, counter: u64) -> Vec<u8> {
+        use TracePacketFieldNumber as Packet;
+        let for_testing = packet(&[(
+            TestEventFieldNumber::Counter as u32,
+            PbDecoderField::Varint(counter),
+        )]);
+        packet(&[
+            (Packet::Timestamp as u32, PbDecoderField::Varint(timestamp)),
+            (
+                Packet::TrustedPacketSequenceId as u32,
+                PbDecoderField::Varint(sequence_id),
+            ),
+            (
+                Packet::ForTesting as u32,
+                PbDecoderField::Delimited(&for_testing),
+            ),
+        ])
+    }
+
+    fn trace(packets: &[Vec<u8>]) -> Vec<u8> {
+        let mut trace = Vec::new();
+        for packet in packets {
+            append_field(
+                &mut trace,
+                TraceFieldNumber::Packet as u32,
+                &PbDecoderField::Delimited(packet),
+            );
+        }
+        trace
+    }
+
+    #[test]
+    fn equivalent_traces() -> Result<(), Box<dyn Error>> {
+        let trace_config = packet(&[(TRACE_PACKET_TRACE_CONFIG, PbDecoderField::Delimited(b""))]);
+        let expected = trace(&[trace_config, test_packet(100, 1, 1), test_packet(200, 2, 2)]);
+        let actual = trace(&[test_packet(300, 3, 2), test_packet(400, 4, 1)]);
+        let diff = TraceDiff::new(&expected, &actual)?;
+        assert!(diff.is_empty(), "{diff}");
+        assert_eq!(diff.expected_packets, 2);
+        assert_eq!(diff.actual_packets, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn changed_packets() -> Result<(), Box<dyn Error>> {
+        use TracePacketFieldNumber as Packet;
+        let expected = [test_packet(100, 1, 1), test_packet(200, 1, 2)];
+        let interned_data =
+            packet(&[(Packet::InternedData as u32, PbDecoderField::Delimited(b""))]);
+        let actual = [
+            test_packet(100, 1, 1),
+            test_packet(200, 1, 3),
+            interned_data,
+        ];
+        let diff = TraceDiffBuilder::new()
+            .ignore_order(false)
+            .diff_packets(&expected, &actual)?;
+        assert_eq!(
+            diff.diffs,
+            vec![
+                PacketDiff::Changed {
+                    expected_index: 1,
+                    actual_index: 1,
+                    packet_type: Packet::ForTesting as u32,
+                    fields: vec![Packet::ForTesting as u32],
+                },
+                PacketDiff::Added {
+                    index: 2,
+                    packet_type: 0,
+                },
+            ]
+        );
+        assert!(diff.to_string().contains("~ packet 1 -> 1 (for_testing)"));
+
+        let diff = TraceDiffBuilder::new()
+            .ignore_timestamps(false)
+            .diff_packets(&expected[..1], &[test_packet(300, 1, 1)])?;
+        assert_eq!(
+            diff.diffs,
+            vec![PacketDiff::Changed {
+                expected_index: 0,
+                actual_index: 0,
+                packet_type: Packet::ForTesting as u32,
+                fields: vec![Packet::Timestamp as u32],
+            }]
+        );
+
+        let diff = TraceDiff::new(&trace(&expected), &trace(&[]))?;
+        assert_eq!(diff.diffs.len(), 2);
+        assert!(matches!(
+            diff.diffs[0],
+            PacketDiff::Removed { index: 0, .. }
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn ordered_diff() -> Result<(), Box<dyn Error>> {
+        let expected = [test_packet(0, 1, 1), test_packet(0, 1, 2)];
+        let actual = [test_packet(0, 1, 2), test_packet(0, 1, 1)];
+        assert!(
+            TraceDiffBuilder::new()
+                .diff_packets(&expected, &actual)?
+                .is_empty()
+        );
+        let diff = TraceDiffBuilder::new()
+            .ignore_order(false)
+            .diff_packets(&expected, &actual)?;
+        assert_eq!(diff.diffs.len(), 1);
+        Ok(())
+    }
+}
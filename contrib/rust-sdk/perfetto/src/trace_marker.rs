@@ -0,0 +1,459 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    counter_value::CounterValue,
+    pb_decoder::{PbDecoder, PbDecoderField},
+    protos::trace::track_event::track_descriptor::TrackDescriptorFieldNumber,
+    track_event::{EventContext, TeHlExtra, TrackEventType},
+};
+use std::{
+    ffi::CStr,
+    fmt,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        RwLock,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+/// Paths of the ftrace `trace_marker` file, tried in order by
+/// [`TraceMarker::open`].
+pub const TRACE_MARKER_PATHS: [&str; 2] = [
+    "/sys/kernel/tracing/trace_marker",
+    "/sys/kernel/debug/tracing/trace_marker",
+];
+
+/// Writes events into the ftrace `trace_marker` file, in the format of the
+/// atrace events that Perfetto imports from ftrace, e.g. `B|pid|name`.
+///
+/// The events are recorded by ftrace in the buffers of the kernel, with the
+/// timestamp and the thread of the write, and show up as slices, instants
+/// and counters of the thread or process in the system traces recorded with
+/// ftrace, even without a Perfetto tracing service.
+///
+/// Each event is written with a single write. If the kernel only accepts
+/// part of it, the truncated event is recorded as is and the method returns
+/// an error of kind [`io::ErrorKind::WriteZero`].
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::trace_marker::TraceMarker;
+///
+/// let marker = TraceMarker::open().unwrap();
+/// marker.begin("draw").unwrap();
+/// marker.counter("frames", 42).unwrap();
+/// marker.end().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct TraceMarker {
+    file: File,
+    pid: u32,
+}
+
+impl TraceMarker {
+    /// Opens the first of the [`TRACE_MARKER_PATHS`] that can be written.
+    pub fn open() -> io::Result<Self> {
+        let mut last_err = io::Error::from(io::ErrorKind::NotFound);
+        for path in TRACE_MARKER_PATHS {
+            match Self::open_path(path) {
+                Ok(marker) => return Ok(marker),
+                Err(err) => last_err = err,
+            }
+        }
+        Err(last_err)
+    }
+
+    /// Opens the `trace_marker` file at `path`.
+    pub fn open_path(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            file: OpenOptions::new().write(true).open(path)?,
+            pid: std::process::id(),
+        })
+    }
+
+    /// Begins a slice named `name` on the current thread.
+    pub fn begin(&self, name: &str) -> io::Result<()> {
+        self.write(format_args!("B|{}|{}", self.pid, Sanitized(name)))
+    }
+
+    /// Ends the last slice begun on the current thread.
+    pub fn end(&self) -> io::Result<()> {
+        self.write(format_args!("E|{}", self.pid))
+    }
+
+    /// Writes an instant named `name` on the current thread.
+    pub fn instant(&self, name: &str) -> io::Result<()> {
+        self.write(format_args!("I|{}|{}", self.pid, Sanitized(name)))
+    }
+
+    /// Writes the `value` of the process counter `name`.
+    pub fn counter(&self, name: &str, value: impl Into<CounterValue>) -> io::Result<()> {
+        let name = Sanitized(name);
+        match value.into() {
+            CounterValue::Int(value) => {
+                self.write(format_args!("C|{}|{}|{}", self.pid, name, value))
+            }
+            CounterValue::Double(value) => {
+                self.write(format_args!("C|{}|{}|{}", self.pid, name, value))
+            }
+        }
+    }
+
+    /// Begins the async slice `name` with `cookie`, which can end on another
+    /// thread.
+    pub fn async_begin(&self, name: &str, cookie: i32) -> io::Result<()> {
+        self.write(format_args!(
+            "S|{}|{}|{}",
+            self.pid,
+            Sanitized(name),
+            cookie
+        ))
+    }
+
+    /// Ends the async slice `name` with `cookie`.
+    pub fn async_end(&self, name: &str, cookie: i32) -> io::Result<()> {
+        self.write(format_args!(
+            "F|{}|{}|{}",
+            self.pid,
+            Sanitized(name),
+            cookie
+        ))
+    }
+
+    // Writes an event with a single write, as ftrace records each write to
+    // the marker as one event. A short write can't be completed by writing
+    // the rest, which would be recorded as another event, so it is an error.
+    fn write(&self, event: fmt::Arguments) -> io::Result<()> {
+        let mut line = Vec::with_capacity(64);
+        line.write_fmt(event)?;
+        let written = (&self.file).write(&line)?;
+        if written < line.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::WriteZero,
+                format!("event truncated to {written} of {} bytes", line.len()),
+            ));
+        }
+        Ok(())
+    }
+}
+
+// Formats a name without the characters that end atrace events.
+struct Sanitized<'a>(&'a str);
+
+impl fmt::Display for Sanitized<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '\n' | '|' => f.write_str(" ")?,
+                c => fmt::Write::write_char(f, c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Returns the name of the producer socket of the tracing service, like
+// `GetProducerSocket` does.
+fn default_producer_socket() -> String {
+    if let Ok(name) = std::env::var("PERFETTO_PRODUCER_SOCK_NAME") {
+        return name;
+    }
+    if cfg!(target_os = "android") {
+        "/dev/socket/traced_producer".to_string()
+    } else if cfg!(target_os = "linux") && Path::new("/run/perfetto/").is_dir() {
+        "/run/perfetto/traced-producer.sock".to_string()
+    } else {
+        "/tmp/perfetto-producer".to_string()
+    }
+}
+
+struct Fallback {
+    marker: TraceMarker,
+    categories: Vec<String>,
+}
+
+static FALLBACK_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+static FALLBACK: RwLock<Option<Fallback>> = RwLock::new(None);
+
+/// Writes the track events of the categories that no tracing session
+/// enabled into the ftrace `trace_marker` file, for processes that run
+/// without a Perfetto tracing service but with ftrace, so that their
+/// instrumentation still shows up in the system traces recorded by other
+/// means, e.g. `trace-cmd` or `atrace`.
+///
+/// Once installed, the track event macros write slices as `B` and `E`
+/// events of the current thread, instants as `I` events, and counters as `C`
+/// events named after their track or, if they have none, their category.
+/// Other fields of the events, e.g. debug arguments and flows, aren't
+/// written. Events of the categories enabled by a session are written into
+/// the session instead.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::trace_marker::TraceMarkerFallback;
+///
+/// if TraceMarkerFallback::new().categories(&["gfx"]).install().unwrap() {
+///     println!("No tracing service, writing track events into ftrace");
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+#[must_use = "This is a builder; remember to call `.install()` (or keep chaining)."]
+pub struct TraceMarkerFallback {
+    categories: Vec<String>,
+    path: Option<PathBuf>,
+    service_socket: Option<String>,
+}
+
+impl TraceMarkerFallback {
+    /// Creates a fallback for all categories.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only writes the events of `categories` into the marker.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn categories(mut self, categories: &[&str]) -> Self {
+        self.categories = categories.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    /// Writes the events into the `trace_marker` file at `path`, instead of
+    /// the first of the [`TRACE_MARKER_PATHS`] that can be written.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Sets the producer socket of the tracing service whose presence is
+    /// checked, see
+    /// [`ProducerInitArgsBuilder::producer_socket_name`](crate::producer::ProducerInitArgsBuilder::producer_socket_name).
+    /// Defaults to the socket used by the system backend.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn service_socket(mut self, name: impl Into<String>) -> Self {
+        self.service_socket = Some(name.into());
+        self
+    }
+
+    /// Installs the fallback, unless a tracing service accepts connections
+    /// on its producer socket. Returns whether the fallback was installed, or
+    /// an error if the marker couldn't be opened, e.g. because ftrace isn't
+    /// mounted or writable.
+    pub fn install(self) -> io::Result<bool> {
+        if self.service_running() {
+            return Ok(false);
+        }
+        let marker = match &self.path {
+            Some(path) => TraceMarker::open_path(path)?,
+            None => TraceMarker::open()?,
+        };
+        *FALLBACK.write().unwrap() = Some(Fallback {
+            marker,
+            categories: self.categories,
+        });
+        FALLBACK_ACTIVE.store(true, Ordering::Relaxed);
+        crate::track_event::update_fallback_categories();
+        Ok(true)
+    }
+
+    /// Uninstalls the fallback, e.g. once the tracing service started.
+    pub fn uninstall() {
+        FALLBACK_ACTIVE.store(false, Ordering::Relaxed);
+        *FALLBACK.write().unwrap() = None;
+        crate::track_event::update_fallback_categories();
+    }
+
+    /// Returns whether the fallback is installed.
+    pub fn is_installed() -> bool {
+        FALLBACK_ACTIVE.load(Ordering::Relaxed)
+    }
+
+    fn service_running(&self) -> bool {
+        #[cfg(unix)]
+        {
+            let socket = self
+                .service_socket
+                .clone()
+                .unwrap_or_else(default_producer_socket);
+            crate::platform::connect_socket(&socket).is_ok()
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = default_producer_socket;
+            false
+        }
+    }
+}
+
+// Returns whether the installed fallback writes the events of `category`.
+pub(crate) fn covers(category: &str) -> bool {
+    FALLBACK.read().unwrap().as_ref().is_some_and(|fallback| {
+        fallback.categories.is_empty() || fallback.categories.iter().any(|c| c == category)
+    })
+}
+
+/// Internal utility function used by the track event macros to write an
+/// event of a disabled category into the trace marker.
+#[doc(hidden)]
+pub fn __emit_fallback(category: &str, variant: TrackEventType, ctx: &EventContext) {
+    let fallback = FALLBACK.read().unwrap();
+    let Some(fallback) = fallback.as_ref() else {
+        return;
+    };
+    if !fallback.categories.is_empty() && !fallback.categories.iter().any(|c| c == category) {
+        return;
+    }
+    // SAFETY: the names of the track event macros are NUL-terminated static
+    // strings.
+    let name = |name| unsafe { CStr::from_ptr(name) }.to_string_lossy();
+    // Write errors are ignored, like the events of a full buffer.
+    let _ = match variant {
+        TrackEventType::Instant(name_ptr) => fallback.marker.instant(&name(name_ptr)),
+        TrackEventType::SliceBegin(name_ptr) => fallback.marker.begin(&name(name_ptr)),
+        TrackEventType::SliceEnd => fallback.marker.end(),
+        TrackEventType::Counter => {
+            let (name, value) = counter_of(ctx);
+            match value {
+                Some(value) => fallback
+                    .marker
+                    .counter(name.as_deref().unwrap_or(category), value),
+                None => Ok(()),
+            }
+        }
+    };
+}
+
+// Returns the name of the track and the value of a counter event.
+fn counter_of(ctx: &EventContext) -> (Option<String>, Option<CounterValue>) {
+    let (mut name, mut value) = (None, None);
+    for extra in &ctx.extras {
+        match extra {
+            TeHlExtra::CounterInt64(counter) => value = Some(CounterValue::Int(counter.value)),
+            TeHlExtra::CounterDouble(counter) => value = Some(CounterValue::Double(counter.value)),
+            TeHlExtra::NamedTrack(_, track_name) => {
+                name = Some(track_name.to_string_lossy().into_owned())
+            }
+            TeHlExtra::Track(track) => {
+                // SAFETY: the registered track outlives the event, as it does
+                // when the event is emitted.
+                let track = unsafe { &*track.track };
+                // SAFETY: the descriptor of a registered track points to
+                // `descriptor_size` bytes owned by the track.
+                let descriptor = unsafe {
+                    std::slice::from_raw_parts(track.descriptor as *const u8, track.descriptor_size)
+                };
+                name = track_name(descriptor);
+            }
+            _ => {}
+        }
+    }
+    (name, value)
+}
+
+// Returns the name of the encoded `TrackDescriptor` `descriptor`.
+fn track_name(descriptor: &[u8]) -> Option<String> {
+    PbDecoder::new(descriptor).find_map(|item| match item {
+        Ok((id, PbDecoderField::Delimited(data)))
+            if id == TrackDescriptorFieldNumber::Name as u32
+                || id == TrackDescriptorFieldNumber::StaticName as u32 =>
+        {
+            std::str::from_utf8(data).ok().map(str::to_string)
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        tests::acquire_test_environment,
+        track_event::{TrackEvent, TrackEventTrack},
+        track_event_begin, track_event_counter, track_event_end, track_event_instant,
+    };
+    use std::{error::Error, fs};
+
+    crate::track_event_categories! {
+        pub mod test_te_ns {
+            ( "marker", "Test trace marker fallback", [] ),
+            ( "unmarked", "Test trace marker fallback filter", [] ),
+        }
+    }
+    use test_te_ns as perfetto_te_ns;
+
+    #[test]
+    fn marker_format() -> io::Result<()> {
+        let path = std::env::temp_dir().join(format!("trace_marker_{}", std::process::id()));
+        fs::write(&path, "")?;
+        let marker = TraceMarker::open_path(&path)?;
+        let pid = std::process::id();
+        marker.begin("draw|frame\n")?;
+        marker.end()?;
+        marker.instant("vsync")?;
+        marker.counter("fps", 59.5)?;
+        marker.async_begin("load", 7)?;
+        marker.async_end("load", 7)?;
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            format!(
+                "B|{pid}|draw frame E|{pid}I|{pid}|vsyncC|{pid}|fps|59.5S|{pid}|load|7F|{pid}|load|7"
+            )
+        );
+        fs::remove_file(&path)
+    }
+
+    #[test]
+    fn fallback() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        TrackEvent::init();
+        test_te_ns::register()?;
+        let path = std::env::temp_dir().join(format!("trace_marker_te_{}", std::process::id()));
+        fs::write(&path, "")?;
+        let installed = TraceMarkerFallback::new()
+            .categories(&["marker"])
+            .path(&path)
+            .service_socket(path.with_extension("sock").to_str().unwrap())
+            .install()?;
+        assert!(installed);
+        assert!(TraceMarkerFallback::is_installed());
+        // The fallback only activates the categories it covers.
+        assert!(test_te_ns::is_category_active(0));
+        assert!(!test_te_ns::is_category_active(1));
+        let track = TrackEventTrack::register_counter_track("queue_depth", 0)?;
+        track_event_begin!("marker", "work");
+        track_event_instant!("unmarked", "skipped");
+        track_event_counter!("marker", |ctx: &mut EventContext| {
+            ctx.set_track(&track).set_counter(3);
+        });
+        track_event_end!("marker");
+        TraceMarkerFallback::uninstall();
+        assert!(!test_te_ns::is_category_active(0));
+        track_event_instant!("marker", "after");
+        test_te_ns::unregister()?;
+
+        let pid = std::process::id();
+        assert_eq!(
+            fs::read_to_string(&path)?,
+            format!("B|{pid}|workC|{pid}|queue_depth|3E|{pid}")
+        );
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,461 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    data_source::{DataSource, DataSourceArgsBuilder, DataSourceError, TraceContextBase},
+    protos::trace::{
+        chrome::{
+            chrome_benchmark_metadata::ChromeBenchmarkMetadata,
+            chrome_trace_event::{ChromeEventBundle, ChromeMetadata},
+        },
+        trace_packet::TracePacket,
+        trace_uuid::TraceUuid,
+        ui_state::{UiState, UiStateHighlightProcess},
+    },
+};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Name of the data source registered by default by
+/// [`TraceMetadata::register`].
+pub const TRACE_METADATA_DATA_SOURCE_NAME: &str = "perfetto.sdk.trace_metadata";
+
+/// Value of a trace annotation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataValue {
+    /// String value.
+    String(String),
+    /// Boolean value.
+    Bool(bool),
+    /// Integer value.
+    Int(i64),
+    /// JSON value, e.g. a serialized object.
+    Json(String),
+}
+
+impl From<&str> for MetadataValue {
+    fn from(value: &str) -> Self {
+        MetadataValue::String(value.to_string())
+    }
+}
+
+impl From<String> for MetadataValue {
+    fn from(value: String) -> Self {
+        MetadataValue::String(value)
+    }
+}
+
+impl From<bool> for MetadataValue {
+    fn from(value: bool) -> Self {
+        MetadataValue::Bool(value)
+    }
+}
+
+impl From<i64> for MetadataValue {
+    fn from(value: i64) -> Self {
+        MetadataValue::Int(value)
+    }
+}
+
+/// Process that the UI should highlight when the trace is opened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HighlightProcess {
+    /// Process with this pid.
+    Pid(u32),
+    /// Process with this command line, e.g. the package name of an app.
+    Cmdline(String),
+}
+
+/// Metadata of a benchmark run, e.g. from a CI job.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BenchmarkMetadata {
+    /// Time the benchmark started, in microseconds since the Unix epoch.
+    pub benchmark_start_time_us: Option<i64>,
+    /// Time the story ran, in microseconds since the Unix epoch.
+    pub story_run_time_us: Option<i64>,
+    /// Name of the benchmark.
+    pub benchmark_name: Option<String>,
+    /// Description of the benchmark.
+    pub benchmark_description: Option<String>,
+    /// Label of the run, e.g. an experiment name.
+    pub label: Option<String>,
+    /// Name of the story.
+    pub story_name: Option<String>,
+    /// Tags of the story.
+    pub story_tags: Vec<String>,
+    /// Index of the run if the story ran several times.
+    pub story_run_index: Option<i32>,
+    /// Whether the run failed.
+    pub had_failures: Option<bool>,
+}
+
+/// Trace-level metadata, e.g. build ids, device info and experiment labels,
+/// written as trace packets that trace processor and the UI import for the
+/// whole trace.
+///
+/// - The trace UUID is written as a `TraceUuid` packet. The tracing service
+///   also writes one, and the last one in the trace wins.
+/// - The timeline and highlighted process are written as a `UiState` packet,
+///   which the UI uses to set up its initial view.
+/// - The benchmark metadata is written as a `ChromeBenchmarkMetadata`
+///   packet, whose fields trace processor imports in its `metadata` table.
+/// - Annotations are written as `ChromeMetadata` entries, which trace
+///   processor imports in its `metadata` table with a `cr-` prefix, e.g.
+///   `cr-build_id`.
+///
+/// The metadata is either written with [`write`](Self::write) from a trace
+/// callback, or by a dedicated data source, see [`register`](Self::register).
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::trace_metadata::TraceMetadataBuilder;
+///
+/// let metadata = TraceMetadataBuilder::new()
+///     .annotation("build_id", "4a3f9c")
+///     .annotation("device", "pixel-9")
+///     .annotation("experiment_enabled", true)
+///     .build();
+/// let data_source = metadata.register().unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TraceMetadata {
+    trace_uuid: Option<u128>,
+    timeline: Option<(i64, i64)>,
+    highlight_process: Option<HighlightProcess>,
+    benchmark: Option<BenchmarkMetadata>,
+    annotations: Vec<(String, MetadataValue)>,
+}
+
+impl TraceMetadata {
+    /// Returns the annotations, in the order they were added.
+    pub fn annotations(&self) -> &[(String, MetadataValue)] {
+        &self.annotations
+    }
+
+    /// Writes the metadata as trace packets.
+    pub fn write(&self, ctx: &mut TraceContextBase) {
+        if let Some(uuid) = self.trace_uuid {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_trace_uuid(|trace_uuid: &mut TraceUuid| {
+                    trace_uuid.set_msb((uuid >> 64) as i64).set_lsb(uuid as i64);
+                });
+            });
+        }
+        if self.timeline.is_some() || self.highlight_process.is_some() {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_ui_state(|ui_state: &mut UiState| {
+                    if let Some((start, end)) = self.timeline {
+                        ui_state
+                            .set_timeline_start_ts(start)
+                            .set_timeline_end_ts(end);
+                    }
+                    if let Some(process) = &self.highlight_process {
+                        ui_state.set_highlight_process(
+                            |highlight: &mut UiStateHighlightProcess| match process {
+                                HighlightProcess::Pid(pid) => {
+                                    highlight.set_pid(*pid);
+                                }
+                                HighlightProcess::Cmdline(cmdline) => {
+                                    highlight.set_cmdline(cmdline);
+                                }
+                            },
+                        );
+                    }
+                });
+            });
+        }
+        if let Some(benchmark) = &self.benchmark {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_chrome_benchmark_metadata(|metadata: &mut ChromeBenchmarkMetadata| {
+                    write_benchmark(metadata, benchmark);
+                });
+            });
+        }
+        if !self.annotations.is_empty() {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_chrome_events(|bundle: &mut ChromeEventBundle| {
+                    for (name, value) in &self.annotations {
+                        bundle.set_metadata(|metadata: &mut ChromeMetadata| {
+                            metadata.set_name(name);
+                            match value {
+                                MetadataValue::String(value) => {
+                                    metadata.set_string_value(value);
+                                }
+                                MetadataValue::Bool(value) => {
+                                    metadata.set_bool_value(*value);
+                                }
+                                MetadataValue::Int(value) => {
+                                    metadata.set_int_value(*value);
+                                }
+                                MetadataValue::Json(value) => {
+                                    metadata.set_json_value(value);
+                                }
+                            }
+                        });
+                    }
+                });
+            });
+        }
+    }
+
+    /// Registers a data source named [`TRACE_METADATA_DATA_SOURCE_NAME`]
+    /// that writes the metadata to each of its instances when they start,
+    /// e.g. for a controller that adds the data source to the trace configs
+    /// it sends.
+    pub fn register(self) -> Result<TraceMetadataDataSource, DataSourceError> {
+        self.register_with_name(TRACE_METADATA_DATA_SOURCE_NAME)
+    }
+
+    /// Same as [`register`](Self::register) with a data source named `name`.
+    pub fn register_with_name(
+        self,
+        name: &str,
+    ) -> Result<TraceMetadataDataSource, DataSourceError> {
+        let shared = Arc::new(Shared {
+            metadata: Mutex::new(self),
+            data_source: OnceLock::new(),
+        });
+        let start_shared = Arc::clone(&shared);
+        let args = DataSourceArgsBuilder::new().on_start(move |inst_id, _| {
+            start_shared.write(Some(inst_id));
+        });
+        let mut data_source = DataSource::new();
+        data_source.register(name, args.build())?;
+        let data_source: &'static DataSource<'static> = Box::leak(Box::new(data_source));
+        let _ = shared.data_source.set(data_source);
+        Ok(TraceMetadataDataSource { shared })
+    }
+}
+
+fn write_benchmark(metadata: &mut ChromeBenchmarkMetadata, benchmark: &BenchmarkMetadata) {
+    if let Some(value) = benchmark.benchmark_start_time_us {
+        metadata.set_benchmark_start_time_us(value);
+    }
+    if let Some(value) = benchmark.story_run_time_us {
+        metadata.set_story_run_time_us(value);
+    }
+    if let Some(value) = &benchmark.benchmark_name {
+        metadata.set_benchmark_name(value);
+    }
+    if let Some(value) = &benchmark.benchmark_description {
+        metadata.set_benchmark_description(value);
+    }
+    if let Some(value) = &benchmark.label {
+        metadata.set_label(value);
+    }
+    if let Some(value) = &benchmark.story_name {
+        metadata.set_story_name(value);
+    }
+    for tag in &benchmark.story_tags {
+        metadata.set_story_tags(tag);
+    }
+    if let Some(value) = benchmark.story_run_index {
+        metadata.set_story_run_index(value);
+    }
+    if let Some(value) = benchmark.had_failures {
+        metadata.set_had_failures(value);
+    }
+}
+
+/// Trace metadata builder.
+#[must_use = "This is a builder; remember to call `.build()` (or keep chaining)."]
+#[derive(Default)]
+pub struct TraceMetadataBuilder {
+    metadata: TraceMetadata,
+}
+
+impl TraceMetadataBuilder {
+    /// Create new trace metadata builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the UUID of the trace.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn trace_uuid(mut self, uuid: u128) -> Self {
+        self.metadata.trace_uuid = Some(uuid);
+        self
+    }
+
+    /// Set the bounds of the initial viewport of the UI, in nanoseconds of
+    /// the trace clock.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn timeline(mut self, start_ts: i64, end_ts: i64) -> Self {
+        self.metadata.timeline = Some((start_ts, end_ts));
+        self
+    }
+
+    /// Set the process that the UI highlights.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn highlight_process(mut self, process: HighlightProcess) -> Self {
+        self.metadata.highlight_process = Some(process);
+        self
+    }
+
+    /// Set the metadata of the benchmark run that the trace is recorded for.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn benchmark(mut self, benchmark: BenchmarkMetadata) -> Self {
+        self.metadata.benchmark = Some(benchmark);
+        self
+    }
+
+    /// Add the annotation `name` with `value`.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn annotation(mut self, name: &str, value: impl Into<MetadataValue>) -> Self {
+        self.metadata
+            .annotations
+            .push((name.to_string(), value.into()));
+        self
+    }
+
+    /// Build the trace metadata.
+    pub fn build(self) -> TraceMetadata {
+        self.metadata
+    }
+}
+
+struct Shared {
+    metadata: Mutex<TraceMetadata>,
+    data_source: OnceLock<&'static DataSource<'static>>,
+}
+
+impl Shared {
+    // Writes the metadata to the instance `inst_id`, or to all instances if
+    // `None`.
+    fn write(&self, inst_id: Option<u32>) {
+        let Some(data_source) = self.data_source.get() else {
+            return;
+        };
+        let metadata = self.metadata.lock().unwrap();
+        data_source.trace(|ctx| {
+            if inst_id.is_none_or(|inst_id| inst_id == ctx.instance_index()) {
+                metadata.write(ctx);
+            }
+        });
+    }
+}
+
+/// A data source that writes [`TraceMetadata`] to each of its instances when
+/// they start.
+pub struct TraceMetadataDataSource {
+    shared: Arc<Shared>,
+}
+
+impl TraceMetadataDataSource {
+    /// Replaces the metadata, and writes it to the running instances.
+    pub fn set_metadata(&self, metadata: TraceMetadata) {
+        *self.shared.metadata.lock().unwrap() = metadata;
+        self.shared.write(None);
+    }
+
+    /// Returns the underlying data source.
+    pub fn data_source(&self) -> &'static DataSource<'static> {
+        self.shared.data_source.get().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        pb_decoder::{PbDecoder, PbDecoderField},
+        pb_format::PbFormatter,
+        protos::trace::trace_packet::TracePacketFieldNumber,
+        tests::{TracingSessionBuilder, acquire_test_environment, read_trace_data},
+        trace_reader::TraceReader,
+    };
+    use std::error::Error;
+
+    #[test]
+    fn data_source() -> Result<(), Box<dyn Error>> {
+        const DATA_SOURCE_NAME: &str = "com.example.trace_metadata";
+        let _lock = acquire_test_environment();
+        let data_source = TraceMetadataBuilder::new()
+            .trace_uuid(0x1234_u128 << 64 | 0x5678)
+            .highlight_process(HighlightProcess::Pid(42))
+            .benchmark(BenchmarkMetadata {
+                benchmark_name: Some("startup".to_string()),
+                ..Default::default()
+            })
+            .annotation("build_id", "4a3f9c")
+            .build()
+            .register_with_name(DATA_SOURCE_NAME)?;
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        session.start_blocking();
+        data_source.set_metadata(TraceMetadataBuilder::new().annotation("run", 2_i64).build());
+        session.stop_blocking();
+        let data = read_trace_data(&mut session);
+
+        let formatter = PbFormatter::new();
+        let mut messages = Vec::new();
+        for packet in TraceReader::new(&data) {
+            for item in PbDecoder::new(&packet?) {
+                let (id, PbDecoderField::Delimited(value)) = item? else {
+                    continue;
+                };
+                messages.push(match id {
+                    id if id == TracePacketFieldNumber::TraceUuid as u32 => {
+                        formatter.format::<TraceUuid>(value)
+                    }
+                    id if id == TracePacketFieldNumber::UiState as u32 => {
+                        formatter.format::<UiState>(value)
+                    }
+                    id if id == TracePacketFieldNumber::ChromeBenchmarkMetadata as u32 => {
+                        formatter.format::<ChromeBenchmarkMetadata>(value)
+                    }
+                    id if id == TracePacketFieldNumber::ChromeEvents as u32 => {
+                        formatter.format::<ChromeEventBundle>(value)
+                    }
+                    _ => continue,
+                });
+            }
+        }
+        // Skip the trace UUID written by the service.
+        let formatted: String = messages
+            .into_iter()
+            .filter(|message| !message.starts_with("TraceUuid") || message.contains("msb: 4660"))
+            .collect();
+        assert_eq!(
+            formatted,
+            "TraceUuid {
+  msb: 4660
+  lsb: 22136
+}
+UiState {
+  highlight_process {
+    pid: 42
+  }
+}
+ChromeBenchmarkMetadata {
+  benchmark_name: \"startup\"
+}
+ChromeEventBundle {
+  metadata {
+    name: \"build_id\"
+    string_value: \"4a3f9c\"
+  }
+}
+ChromeEventBundle {
+  metadata {
+    name: \"run\"
+    int_value: 2
+  }
+}
+"
+        );
+        Ok(())
+    }
+}
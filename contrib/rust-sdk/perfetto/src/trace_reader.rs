@@ -0,0 +1,544 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    pb_decoder::{PbDecoder, PbDecoderField},
+    pb_utils::{PbWireType, pb_parse_varint},
+    protos::trace::{trace::TraceFieldNumber, trace_packet::TracePacketFieldNumber},
+};
+use std::{borrow::Cow, collections::VecDeque};
+use thiserror::Error;
+
+/// Trace reader errors.
+#[derive(Error, Debug, PartialEq)]
+pub enum TraceReaderError {
+    /// The trace ends in the middle of a field.
+    #[error("Trace is truncated at byte offset {0}.")]
+    Truncated(usize),
+    /// The trace contains a field with an invalid wire type.
+    #[error("Invalid wire type {1} at byte offset {0}.")]
+    InvalidWireType(usize, u32),
+    /// The trace contains a varint longer than 10 bytes, or a field length
+    /// past the end of the address space.
+    #[error("Malformed varint at byte offset {0}.")]
+    MalformedVarint(usize),
+    /// A `compressed_packets` field could not be decompressed.
+    #[error("Failed to decompress compressed packets.")]
+    DecompressionFailed,
+    /// A `compressed_packets` field decompresses to more than
+    /// [`MAX_DECOMPRESSED_SIZE`] bytes.
+    #[error("Compressed packets exceed {MAX_DECOMPRESSED_SIZE} bytes once decompressed.")]
+    DecompressedTooLarge,
+    /// A field received by a [`TraceStreamReader`] declares a length of more
+    /// than [`MAX_DECOMPRESSED_SIZE`] bytes.
+    #[error("Field at byte offset {0} exceeds {MAX_DECOMPRESSED_SIZE} bytes.")]
+    FieldTooLarge(usize),
+    /// A `compressed_packets` field was found but the `zlib` feature is disabled.
+    #[error("Compressed packets require the `zlib` feature.")]
+    CompressionUnsupported,
+}
+
+/// Maximum size of the packets of a `compressed_packets` field once
+/// decompressed. The tracing service compresses the packets it reads in
+/// batches of tens of kilobytes, so larger payloads are rejected rather than
+/// inflated into memory.
+pub const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+// Maximum size of an encoded varint.
+const MAX_VARINT_SIZE: usize = 10;
+
+// Returns the length declared by the length-delimited field at `offset`, if
+// its header is complete.
+fn declared_length(data: &[u8], offset: usize) -> Option<u64> {
+    let (tag, tag_size) = parse_varint(data, offset).ok()?;
+    if (tag & 7) as u32 != PbWireType::Delimited as u32 {
+        return None;
+    }
+    parse_varint(data, offset + tag_size)
+        .ok()
+        .map(|(len, _)| len)
+}
+
+// Parses the varint at `offset`, which is either truncated or malformed if it
+// can't be parsed.
+fn parse_varint(data: &[u8], offset: usize) -> Result<(u64, usize), TraceReaderError> {
+    match pb_parse_varint(&data[offset..]) {
+        (_, 0) if data.len() - offset >= MAX_VARINT_SIZE => {
+            Err(TraceReaderError::MalformedVarint(offset))
+        }
+        (_, 0) => Err(TraceReaderError::Truncated(offset)),
+        (value, size) => Ok((value, size)),
+    }
+}
+
+/// Parses the next `packet` field of a serialized `Trace` starting at
+/// `*offset`, skipping any other fields. Returns `None` at the end of `data`.
+pub(crate) fn next_trace_packet<'a>(
+    data: &'a [u8],
+    offset: &mut usize,
+) -> Option<Result<&'a [u8], TraceReaderError>> {
+    while *offset < data.len() {
+        let start = *offset;
+        let (tag, tag_size) = match parse_varint(data, start) {
+            Ok(tag) => tag,
+            Err(err) => return Some(Err(err)),
+        };
+        let pos = start + tag_size;
+        let field_id = (tag >> 3) as u32;
+        let wire_type = (tag & 7) as u32;
+        let end = match PbWireType::try_from(wire_type) {
+            Ok(PbWireType::Varint) => parse_varint(data, pos).map(|(_, size)| pos + size),
+            Ok(PbWireType::Fixed64) => Ok(pos + 8),
+            Ok(PbWireType::Fixed32) => Ok(pos + 4),
+            Ok(PbWireType::Delimited) => parse_varint(data, pos).and_then(|(len, size)| {
+                usize::try_from(len)
+                    .ok()
+                    .and_then(|len| (pos + size).checked_add(len))
+                    .ok_or(TraceReaderError::MalformedVarint(pos))
+            }),
+            Err(_) => return Some(Err(TraceReaderError::InvalidWireType(start, wire_type))),
+        };
+        let end = match end {
+            Ok(end) if end <= data.len() => end,
+            // Fields are reported at their start when truncated.
+            Ok(_) | Err(TraceReaderError::Truncated(_)) => {
+                return Some(Err(TraceReaderError::Truncated(start)));
+            }
+            Err(err) => return Some(Err(err)),
+        };
+        *offset = end;
+        if field_id == TraceFieldNumber::Packet as u32 && wire_type == PbWireType::Delimited as u32
+        {
+            let (len, size) = pb_parse_varint(&data[pos..]);
+            let payload_start = pos + size;
+            return Some(Ok(&data[payload_start..payload_start + len as usize]));
+        }
+    }
+    None
+}
+
+/// Returns the payload of the `compressed_packets` field of `packet`, if any.
+fn compressed_packets(packet: &[u8]) -> Option<&[u8]> {
+    PbDecoder::new(packet).find_map(|item| match item {
+        Ok((id, PbDecoderField::Delimited(data)))
+            if id == TracePacketFieldNumber::CompressedPackets as u32 =>
+        {
+            Some(data)
+        }
+        _ => None,
+    })
+}
+
+#[cfg(feature = "zlib")]
+fn decompress(data: &[u8]) -> Result<Vec<u8>, TraceReaderError> {
+    use std::io::Read;
+    let mut out = Vec::new();
+    // Reads one byte past the limit to tell payloads that exceed it.
+    flate2::read::ZlibDecoder::new(data)
+        .take(MAX_DECOMPRESSED_SIZE as u64 + 1)
+        .read_to_end(&mut out)
+        .map_err(|_| TraceReaderError::DecompressionFailed)?;
+    if out.len() > MAX_DECOMPRESSED_SIZE {
+        return Err(TraceReaderError::DecompressedTooLarge);
+    }
+    Ok(out)
+}
+
+#[cfg(not(feature = "zlib"))]
+fn decompress(_data: &[u8]) -> Result<Vec<u8>, TraceReaderError> {
+    Err(TraceReaderError::CompressionUnsupported)
+}
+
+/// Expands a `compressed_packets` payload into the packets it contains.
+pub(crate) fn decompress_packets(data: &[u8]) -> Result<Vec<Vec<u8>>, TraceReaderError> {
+    let decompressed = decompress(data)?;
+    let mut packets = Vec::new();
+    let mut offset = 0;
+    while let Some(packet) = next_trace_packet(&decompressed, &mut offset) {
+        packets.push(
+            packet
+                .map_err(|_| TraceReaderError::DecompressionFailed)?
+                .to_vec(),
+        );
+    }
+    Ok(packets)
+}
+
+/// Reader that iterates over the `TracePacket`s of a serialized `Trace`,
+/// e.g. the contents of a `.perfetto-trace` file.
+///
+/// Packets wrapped in `compressed_packets` fields are transparently
+/// decompressed and returned in order in place of the wrapping packet.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::trace_reader::TraceReader;
+///
+/// // A trace with a single empty packet.
+/// let trace: &[u8] = b"\x0a\x00";
+/// for packet in TraceReader::new(trace) {
+///     let packet = packet.unwrap();
+///     assert!(packet.is_empty());
+/// }
+/// ```
+pub struct TraceReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    pending: VecDeque<Vec<u8>>,
+    failed: bool,
+}
+
+impl<'a> TraceReader<'a> {
+    /// Creates a reader over the serialized trace in `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            offset: 0,
+            pending: VecDeque::new(),
+            failed: false,
+        }
+    }
+
+    /// Returns the number of bytes of the input consumed so far.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'a> Iterator for TraceReader<'a> {
+    type Item = Result<Cow<'a, [u8]>, TraceReaderError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(packet) = self.pending.pop_front() {
+                return Some(Ok(Cow::Owned(packet)));
+            }
+            if self.failed {
+                return None;
+            }
+            let packet = match next_trace_packet(self.data, &mut self.offset)? {
+                Ok(packet) => packet,
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            };
+            let Some(compressed) = compressed_packets(packet) else {
+                return Some(Ok(Cow::Borrowed(packet)));
+            };
+            match decompress_packets(compressed) {
+                Ok(packets) => self.pending.extend(packets),
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+}
+
+/// Reader that parses the `TracePacket`s of a serialized `Trace` that is
+/// received in chunks, e.g. from
+/// [`TracingSession::read_trace_blocking`](crate::tracing_session::TracingSession::read_trace_blocking)
+/// on a live session or from a socket.
+///
+/// Chunks can split packets at any byte. Packets are returned once they are
+/// complete, decompressed like by [`TraceReader`]. Incomplete fields are
+/// buffered, so fields that declare more than [`MAX_DECOMPRESSED_SIZE`] bytes
+/// are rejected as soon as their length is received.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::trace_reader::TraceStreamReader;
+///
+/// let mut reader = TraceStreamReader::new();
+/// reader.push(b"\x0a\x02\x08");
+/// assert!(reader.next_packet().is_none());
+/// reader.push(b"\x01");
+/// assert_eq!(reader.next_packet().unwrap().unwrap(), b"\x08\x01");
+/// ```
+#[derive(Default)]
+pub struct TraceStreamReader {
+    buffer: Vec<u8>,
+    offset: usize,
+    // Number of bytes of the stream removed from the front of `buffer`.
+    consumed: usize,
+    pending: VecDeque<Vec<u8>>,
+    failed: bool,
+}
+
+impl TraceStreamReader {
+    /// Creates a reader with no data.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the next chunk of the stream.
+    pub fn push(&mut self, data: &[u8]) {
+        if self.offset > 0 {
+            self.buffer.drain(..self.offset);
+            self.consumed += self.offset;
+            self.offset = 0;
+        }
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the next complete packet, or `None` if more data is needed.
+    ///
+    /// After an error, no more packets are returned.
+    pub fn next_packet(&mut self) -> Option<Result<Vec<u8>, TraceReaderError>> {
+        loop {
+            if let Some(packet) = self.pending.pop_front() {
+                return Some(Ok(packet));
+            }
+            if self.failed {
+                return None;
+            }
+            let packet = match next_trace_packet(&self.buffer, &mut self.offset)? {
+                Ok(packet) => packet,
+                Err(TraceReaderError::Truncated(offset)) => {
+                    match declared_length(&self.buffer, offset) {
+                        Some(len) if len > MAX_DECOMPRESSED_SIZE as u64 => {
+                            self.failed = true;
+                            return Some(Err(TraceReaderError::FieldTooLarge(
+                                self.consumed + offset,
+                            )));
+                        }
+                        // The rest of the field hasn't been received yet.
+                        _ => return None,
+                    }
+                }
+                Err(TraceReaderError::InvalidWireType(offset, wire_type)) => {
+                    self.failed = true;
+                    return Some(Err(TraceReaderError::InvalidWireType(
+                        self.consumed + offset,
+                        wire_type,
+                    )));
+                }
+                Err(TraceReaderError::MalformedVarint(offset)) => {
+                    self.failed = true;
+                    return Some(Err(TraceReaderError::MalformedVarint(
+                        self.consumed + offset,
+                    )));
+                }
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            };
+            let Some(compressed) = compressed_packets(packet) else {
+                return Some(Ok(packet.to_vec()));
+            };
+            match decompress_packets(compressed) {
+                Ok(packets) => self.pending.extend(packets),
+                Err(err) => {
+                    self.failed = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+    }
+
+    /// Returns the number of bytes received that aren't part of a returned
+    /// packet or skipped field yet.
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffer.len() - self.offset
+    }
+
+    /// Checks that the stream ended at a field boundary, once all the packets
+    /// have been returned.
+    pub fn finish(&self) -> Result<(), TraceReaderError> {
+        if self.buffered_bytes() > 0 && !self.failed {
+            return Err(TraceReaderError::Truncated(self.consumed + self.offset));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PACKET_TAG: u8 = 0x0a;
+    #[cfg(feature = "zlib")]
+    const COMPRESSED_PACKETS_TAG: [u8; 2] = [0x92, 0x03];
+
+    // Wraps `payload` as a length-delimited field.
+    fn delimited(tag: &[u8], payload: &[u8]) -> Vec<u8> {
+        let mut out = tag.to_vec();
+        let mut len = [0u8; 10];
+        let len_size = crate::pb_utils::pb_write_varint(payload.len() as u64, &mut len);
+        out.extend_from_slice(&len[..len_size]);
+        out.extend_from_slice(payload);
+        out
+    }
+
+    #[test]
+    fn plain_packets() {
+        let mut trace = delimited(&[PACKET_TAG], b"\x08\x01");
+        // Non-packet fields are skipped.
+        trace.extend_from_slice(b"\x10\x05");
+        trace.extend(delimited(&[PACKET_TAG], b"\x08\x02"));
+        let packets: Vec<_> = TraceReader::new(&trace).map(|p| p.unwrap()).collect();
+        assert_eq!(packets, vec![&b"\x08\x01"[..], &b"\x08\x02"[..]]);
+    }
+
+    #[test]
+    fn truncated_trace() {
+        let trace = delimited(&[PACKET_TAG], b"\x08\x01\x08\x02");
+        let mut reader = TraceReader::new(&trace[..4]);
+        assert_eq!(reader.next(), Some(Err(TraceReaderError::Truncated(0))));
+        assert_eq!(reader.next(), None);
+    }
+
+    #[test]
+    fn malformed_varint() {
+        // A packet whose length has 11 bytes.
+        let mut trace = vec![PACKET_TAG];
+        trace.extend_from_slice(&[0xff; 10]);
+        trace.push(0x01);
+        let mut reader = TraceReader::new(&trace);
+        assert_eq!(
+            reader.next(),
+            Some(Err(TraceReaderError::MalformedVarint(1)))
+        );
+        assert_eq!(reader.next(), None);
+
+        // The stream reader stops instead of waiting for the rest of the
+        // field.
+        let mut reader = TraceStreamReader::new();
+        reader.push(b"\x0a\x00");
+        assert!(reader.next_packet().unwrap().is_ok());
+        reader.push(&trace);
+        assert_eq!(
+            reader.next_packet(),
+            Some(Err(TraceReaderError::MalformedVarint(3)))
+        );
+        assert_eq!(reader.next_packet(), None);
+    }
+
+    #[test]
+    fn stream_packets() {
+        let mut trace = delimited(&[PACKET_TAG], b"\x08\x01");
+        trace.extend_from_slice(b"\x10\x05");
+        trace.extend(delimited(&[PACKET_TAG], b"\x08\x02"));
+        trace.extend_from_slice(&[PACKET_TAG, 0x04, 0x08]);
+
+        // Pushes the trace one byte at a time.
+        let mut reader = TraceStreamReader::new();
+        let mut packets = Vec::new();
+        for byte in &trace {
+            reader.push(&[*byte]);
+            while let Some(packet) = reader.next_packet() {
+                packets.push(packet.unwrap());
+            }
+        }
+        assert_eq!(packets, vec![b"\x08\x01".to_vec(), b"\x08\x02".to_vec()]);
+        assert_eq!(reader.buffered_bytes(), 3);
+        assert_eq!(reader.finish(), Err(TraceReaderError::Truncated(10)));
+    }
+
+    #[test]
+    fn stream_oversized_field() {
+        let mut reader = TraceStreamReader::new();
+        reader.push(&delimited(&[PACKET_TAG], b"\x08\x01"));
+        assert!(reader.next_packet().unwrap().is_ok());
+        // A packet that declares one byte more than the limit, of which only
+        // the header is received.
+        let mut header = vec![PACKET_TAG];
+        let mut len = [0u8; 10];
+        let len_size = crate::pb_utils::pb_write_varint(MAX_DECOMPRESSED_SIZE as u64 + 1, &mut len);
+        header.extend_from_slice(&len[..len_size]);
+        reader.push(&header[..2]);
+        assert_eq!(reader.next_packet(), None);
+        reader.push(&header[2..]);
+        assert_eq!(
+            reader.next_packet(),
+            Some(Err(TraceReaderError::FieldTooLarge(4)))
+        );
+        assert_eq!(reader.next_packet(), None);
+    }
+
+    #[cfg(feature = "zlib")]
+    #[test]
+    fn compressed_packets() {
+        use std::io::Write;
+
+        let mut inner = delimited(&[PACKET_TAG], b"\x08\x02");
+        inner.extend(delimited(&[PACKET_TAG], b"\x08\x03"));
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&inner).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut trace = delimited(&[PACKET_TAG], b"\x08\x01");
+        trace.extend(delimited(
+            &[PACKET_TAG],
+            &delimited(&COMPRESSED_PACKETS_TAG, &compressed),
+        ));
+        trace.extend(delimited(&[PACKET_TAG], b"\x08\x04"));
+
+        let packets: Vec<_> = TraceReader::new(&trace).map(|p| p.unwrap()).collect();
+        assert_eq!(
+            packets,
+            vec![
+                &b"\x08\x01"[..],
+                &b"\x08\x02"[..],
+                &b"\x08\x03"[..],
+                &b"\x08\x04"[..]
+            ]
+        );
+    }
+
+    #[cfg(feature = "zlib")]
+    #[test]
+    fn corrupt_compressed_packets() {
+        let trace = delimited(
+            &[PACKET_TAG],
+            &delimited(&COMPRESSED_PACKETS_TAG, b"not zlib"),
+        );
+        let mut reader = TraceReader::new(&trace);
+        assert_eq!(
+            reader.next(),
+            Some(Err(TraceReaderError::DecompressionFailed))
+        );
+        assert_eq!(reader.next(), None);
+    }
+
+    #[cfg(feature = "zlib")]
+    #[test]
+    fn oversized_compressed_packets() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        let zeros = vec![0u8; 1024 * 1024];
+        for _ in 0..=MAX_DECOMPRESSED_SIZE / zeros.len() {
+            encoder.write_all(&zeros).unwrap();
+        }
+        let compressed = encoder.finish().unwrap();
+        let mut trace = delimited(
+            &[PACKET_TAG],
+            &delimited(&COMPRESSED_PACKETS_TAG, &compressed),
+        );
+        trace.extend(delimited(&[PACKET_TAG], b"\x08\x01"));
+        let mut reader = TraceReader::new(&trace);
+        assert_eq!(
+            reader.next(),
+            Some(Err(TraceReaderError::DecompressedTooLarge))
+        );
+        assert_eq!(reader.next(), None);
+    }
+}
@@ -0,0 +1,688 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{
+    pb_decoder::{self, PbDecoder, PbDecoderError, PbDecoderField},
+    platform::{self, PlatformThread},
+    producer::{Backends, InitState, Producer},
+    protos::trace::{trace::TraceFieldNumber, trace_packet::TracePacketFieldNumber},
+    trace_config::TraceConfigSummary,
+    trace_reader::TraceStreamReader,
+    tracing_session::{TracingSession, TracingSessionError},
+};
+use std::{
+    collections::VecDeque,
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Sender},
+    },
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Default time the data sources have to flush their data before a recorded
+/// session is stopped, see [`TraceRecorder::flush_timeout`].
+pub const DEFAULT_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default period at which the buffers of rotating recordings are read, see
+/// [`TraceRecorder::read_period`].
+pub const DEFAULT_READ_PERIOD: Duration = Duration::from_secs(1);
+
+/// Trace recorder errors.
+#[derive(Error, Debug)]
+pub enum TraceRecorderError {
+    /// The tracing session couldn't be created.
+    #[error("Failed to create tracing session: {0}")]
+    Session(#[from] TracingSessionError),
+    /// The trace config couldn't be decoded.
+    #[error("Invalid trace config: {0}")]
+    Config(#[from] PbDecoderError),
+    /// The trace file couldn't be written.
+    #[error("Failed to write trace: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Trace written by a [`TraceRecorder`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordedTrace {
+    /// Size of the trace file, in bytes.
+    pub size: u64,
+    /// Whether the session was stopped by the tracing service, e.g. once the
+    /// duration of its config elapsed or it was triggered, rather than by the
+    /// recorder.
+    pub stopped_by_service: bool,
+}
+
+/// Records a trace into a file, like `perfetto` does from the command line:
+/// sets up and starts a session, waits for it to stop, reads the trace back
+/// and writes it into the file.
+///
+/// Sessions are stopped by the recorder once the recording duration elapses,
+/// unless the tracing service stopped them first. Sessions with triggers are
+/// given at least their trigger timeout, after which the service stops them
+/// if they weren't triggered.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{trace_config::TraceConfigBuilder, trace_recorder::record_trace};
+/// use std::time::Duration;
+///
+/// let config = TraceConfigBuilder::ring_buffer(16 * 1024)
+///     .track_event(&["gfx"])
+///     .build()
+///     .unwrap();
+/// record_trace(&config, Duration::from_secs(10), "trace.pftrace").unwrap();
+/// ```
+#[derive(Debug, Clone)]
+#[must_use = "This is a builder; remember to call `.record()` (or keep chaining)."]
+pub struct TraceRecorder {
+    backend: Backends,
+    flush_timeout: Duration,
+    read_period: Duration,
+    rotate_every: Option<Duration>,
+    rotate_at_size: Option<u64>,
+    max_files: Option<usize>,
+}
+
+impl Default for TraceRecorder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TraceRecorder {
+    /// Creates a recorder using the system backend if the producer
+    /// initialized it, and the in-process backend otherwise.
+    pub fn new() -> Self {
+        let backend = match Producer::init_state() {
+            InitState::Initialized(backends) if backends.contains(Backends::SYSTEM) => {
+                Backends::SYSTEM
+            }
+            _ => Backends::IN_PROCESS,
+        };
+        Self {
+            backend,
+            flush_timeout: DEFAULT_FLUSH_TIMEOUT,
+            read_period: DEFAULT_READ_PERIOD,
+            rotate_every: None,
+            rotate_at_size: None,
+            max_files: None,
+        }
+    }
+
+    /// Sets the backend of the recorded sessions, either
+    /// [`Backends::IN_PROCESS`] or [`Backends::SYSTEM`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn backend(mut self, backend: Backends) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets how long the data sources have to flush their data when the
+    /// recorder stops a session. Defaults to [`DEFAULT_FLUSH_TIMEOUT`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn flush_timeout(mut self, timeout: Duration) -> Self {
+        self.flush_timeout = timeout;
+        self
+    }
+
+    /// Sets how often [`record_rotating`](Self::record_rotating) flushes and
+    /// reads the buffers of the session. Defaults to [`DEFAULT_READ_PERIOD`].
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn read_period(mut self, period: Duration) -> Self {
+        self.read_period = period;
+        self
+    }
+
+    /// Makes [`record_rotating`](Self::record_rotating) start a new file once
+    /// the current one was written for `period`.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn rotate_every(mut self, period: Duration) -> Self {
+        self.rotate_every = Some(period);
+        self
+    }
+
+    /// Makes [`record_rotating`](Self::record_rotating) start a new file
+    /// instead of growing the current one beyond `size` bytes. Packets larger
+    /// than `size` get a file of their own.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn rotate_at_size(mut self, size: u64) -> Self {
+        self.rotate_at_size = Some(size);
+        self
+    }
+
+    /// Makes [`record_rotating`](Self::record_rotating) delete the oldest file
+    /// once more than `count` files were written, which bounds the disk usage
+    /// along with [`rotate_at_size`](Self::rotate_at_size).
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn max_files(mut self, count: usize) -> Self {
+        self.max_files = Some(count.max(1));
+        self
+    }
+
+    /// Records a session with the encoded `TraceConfig` `config` for up to
+    /// `duration`, and writes the trace into `output_path`.
+    pub fn record(
+        &self,
+        config: &[u8],
+        duration: Duration,
+        output_path: impl AsRef<Path>,
+    ) -> Result<RecordedTrace, TraceRecorderError> {
+        let summary = TraceConfigSummary::decode(config)?;
+        // The file is created first so that sessions aren't recorded for
+        // nothing.
+        let file = File::create(output_path)?;
+        let mut session = if self.backend.contains(Backends::SYSTEM) {
+            TracingSession::system()?
+        } else {
+            TracingSession::in_process()?
+        };
+        session.setup(config);
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let on_stop = Arc::clone(&stopped);
+        session.set_stop_callback(move || {
+            let (lock, condvar) = &*on_stop;
+            *lock.lock().unwrap() = true;
+            condvar.notify_all();
+        });
+        session.start_blocking();
+
+        let duration = duration.max(summary.trigger_timeout.unwrap_or_default());
+        let deadline = Instant::now() + duration;
+        let stopped_by_service = {
+            let (lock, condvar) = &*stopped;
+            let mut stopped = lock.lock().unwrap();
+            while !*stopped {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                if timeout.is_zero() {
+                    break;
+                }
+                stopped = condvar.wait_timeout(stopped, timeout).unwrap().0;
+            }
+            *stopped
+        };
+        if !stopped_by_service {
+            session.flush_blocking(self.flush_timeout);
+            session.stop_blocking();
+        }
+
+        let output = Arc::new(Mutex::new((BufWriter::new(file), Ok(0))));
+        let writer = Arc::clone(&output);
+        session.read_trace_blocking(move |data, _has_more| {
+            let (file, written): &mut (BufWriter<File>, io::Result<u64>) =
+                &mut writer.lock().unwrap();
+            if let Ok(size) = written {
+                *written = file.write_all(data).map(|_| *size + data.len() as u64);
+            }
+        });
+        let (file, written) = &mut *output.lock().unwrap();
+        let size = std::mem::replace(written, Ok(0))?;
+        file.flush()?;
+        Ok(RecordedTrace {
+            size,
+            stopped_by_service,
+        })
+    }
+
+    /// Records a session with the encoded `TraceConfig` `config` until the
+    /// returned recording is stopped, or the tracing service stops the session,
+    /// periodically reading its buffers into files named after `output_path`:
+    /// `trace.pftrace` is recorded into `trace.0.pftrace`, `trace.1.pftrace`
+    /// and so on.
+    ///
+    /// A new file is started as set by [`rotate_every`](Self::rotate_every)
+    /// and [`rotate_at_size`](Self::rotate_at_size). Each file is a complete
+    /// `Trace`, which starts with the latest clock snapshot of the session so
+    /// that its timestamps can be converted on its own. Data that refers to
+    /// interned data or descriptors written into a previous file needs them to
+    /// be written again, e.g. by setting the `clear_period_ms` of the
+    /// `incremental_state_config` of the config to the rotation period.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use perfetto_sdk::{trace_config::TraceConfigBuilder, trace_recorder::TraceRecorder};
+    /// use std::time::Duration;
+    ///
+    /// let config = TraceConfigBuilder::ring_buffer(4 * 1024)
+    ///     .track_event(&["gfx"])
+    ///     .build()
+    ///     .unwrap();
+    /// let recording = TraceRecorder::new()
+    ///     .rotate_every(Duration::from_secs(60))
+    ///     .rotate_at_size(64 * 1024 * 1024)
+    ///     .max_files(10)
+    ///     .record_rotating(&config, "/data/traces/trace.pftrace")
+    ///     .unwrap();
+    /// // ...
+    /// let files = recording.stop().unwrap();
+    /// ```
+    pub fn record_rotating(
+        &self,
+        config: &[u8],
+        output_path: impl AsRef<Path>,
+    ) -> Result<RotatingRecording, TraceRecorderError> {
+        TraceConfigSummary::decode(config)?;
+        let files = Arc::new(Mutex::new(VecDeque::new()));
+        let error = Arc::new(Mutex::new(None));
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let rotator = Rotator {
+            settings: self.clone(),
+            base_path: output_path.as_ref().to_path_buf(),
+            files: Arc::clone(&files),
+            error: Arc::clone(&error),
+            index: 0,
+            current: None,
+            clock_snapshot: None,
+        };
+        let (started_sender, started) = mpsc::channel();
+        let config = config.to_vec();
+        let thread_stop = Arc::clone(&stop);
+        // Sessions are created on the recording thread as they aren't `Send`.
+        let thread = platform::spawn("perfetto-trace-rotation", move || {
+            rotator.run(&config, thread_stop, started_sender)
+        })?;
+        match started.recv() {
+            Ok(Ok(())) => Ok(RotatingRecording {
+                files,
+                error,
+                stop,
+                thread: Some(thread),
+            }),
+            Ok(Err(err)) => {
+                let _ = thread.join();
+                Err(err.into())
+            }
+            Err(_) => {
+                let _ = thread.join();
+                Err(TracingSessionError::CreateError.into())
+            }
+        }
+    }
+}
+
+/// Recording started by [`TraceRecorder::record_rotating`]. The session is
+/// stopped when the recording is stopped or dropped.
+pub struct RotatingRecording {
+    files: Arc<Mutex<VecDeque<PathBuf>>>,
+    // First error writing the files.
+    error: Arc<Mutex<Option<io::Error>>>,
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<PlatformThread>,
+}
+
+impl RotatingRecording {
+    /// Returns the files of the recording that weren't deleted, oldest first.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.files.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Returns true once the session stopped, e.g. because the tracing
+    /// service stopped it, or a file couldn't be written.
+    pub fn is_finished(&self) -> bool {
+        *self.stop.0.lock().unwrap()
+    }
+
+    /// Stops the session, writes the rest of its data and returns the files
+    /// of the recording, oldest first.
+    pub fn stop(mut self) -> Result<Vec<PathBuf>, TraceRecorderError> {
+        match self.finish() {
+            Some(err) => Err(err.into()),
+            None => Ok(self.files()),
+        }
+    }
+
+    // Stops the recording thread and returns the first error it ran into.
+    fn finish(&mut self) -> Option<io::Error> {
+        let thread = self.thread.take()?;
+        {
+            let (lock, condvar) = &*self.stop;
+            *lock.lock().unwrap() = true;
+            condvar.notify_all();
+        }
+        if thread.join().is_err() {
+            return Some(io::Error::other("trace rotation thread panicked"));
+        }
+        self.error.lock().unwrap().take()
+    }
+}
+
+impl Drop for RotatingRecording {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+// File being written by a rotating recording.
+struct RotatedFile {
+    writer: BufWriter<File>,
+    size: u64,
+    opened: Instant,
+}
+
+// State of the thread of a rotating recording.
+struct Rotator {
+    settings: TraceRecorder,
+    base_path: PathBuf,
+    files: Arc<Mutex<VecDeque<PathBuf>>>,
+    error: Arc<Mutex<Option<io::Error>>>,
+    // Index of the next file.
+    index: u64,
+    current: Option<RotatedFile>,
+    // Latest clock snapshot packet, as a field of a `Trace`.
+    clock_snapshot: Option<Vec<u8>>,
+}
+
+impl Rotator {
+    fn run(
+        mut self,
+        config: &[u8],
+        stop: Arc<(Mutex<bool>, Condvar)>,
+        started: Sender<Result<(), TracingSessionError>>,
+    ) {
+        let session = if self.settings.backend.contains(Backends::SYSTEM) {
+            TracingSession::system()
+        } else {
+            TracingSession::in_process()
+        };
+        let mut session = match session {
+            Ok(session) => session,
+            Err(err) => {
+                let _ = started.send(Err(err));
+                return;
+            }
+        };
+        session.setup(config);
+        let stopped_by_service = Arc::new(AtomicBool::new(false));
+        let on_stop = Arc::clone(&stopped_by_service);
+        let stop_for_service = Arc::clone(&stop);
+        session.set_stop_callback(move || {
+            on_stop.store(true, Ordering::Relaxed);
+            stop_for_service.1.notify_all();
+        });
+        session.start_blocking();
+        let _ = started.send(Ok(()));
+
+        let mut stream = TraceStreamReader::new();
+        loop {
+            let stopping = {
+                let (lock, condvar) = &*stop;
+                let stopped = lock.lock().unwrap();
+                let stopped = condvar
+                    .wait_timeout_while(stopped, self.settings.read_period, |stopped| {
+                        !*stopped && !stopped_by_service.load(Ordering::Relaxed)
+                    })
+                    .unwrap()
+                    .0;
+                *stopped || stopped_by_service.load(Ordering::Relaxed)
+            };
+            if !stopped_by_service.load(Ordering::Relaxed) {
+                session.flush_blocking(self.settings.flush_timeout);
+                if stopping {
+                    session.stop_blocking();
+                }
+            }
+            if let Err(err) = self.read(&mut session, &mut stream) {
+                *self.error.lock().unwrap() = Some(err);
+                if !stopping && !stopped_by_service.load(Ordering::Relaxed) {
+                    session.stop_blocking();
+                }
+                break;
+            }
+            if stopping {
+                break;
+            }
+        }
+        *stop.0.lock().unwrap() = true;
+    }
+
+    // Reads the buffers of `session` into the files.
+    fn read(
+        &mut self,
+        session: &mut TracingSession,
+        stream: &mut TraceStreamReader,
+    ) -> io::Result<()> {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        let data_for_read = Arc::clone(&data);
+        session.read_trace_blocking(move |data, _has_more| {
+            data_for_read.lock().unwrap().extend_from_slice(data);
+        });
+        stream.push(&data.lock().unwrap());
+        while let Some(packet) = stream.next_packet() {
+            // Packets that can't be decoded are skipped rather than ending
+            // the recording.
+            let Ok(packet) = packet else {
+                continue;
+            };
+            self.write_packet(&packet)?;
+        }
+        if let Some(current) = &mut self.current {
+            current.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn write_packet(&mut self, packet: &[u8]) -> io::Result<()> {
+        let mut framed = Vec::with_capacity(packet.len() + 6);
+        pb_decoder::append_field(
+            &mut framed,
+            TraceFieldNumber::Packet as u32,
+            &PbDecoderField::Delimited(packet),
+        );
+        let size = framed.len() as u64;
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|current| self.is_due(current, size))
+            && let Some(mut current) = self.current.take()
+        {
+            current.writer.flush()?;
+        }
+        let is_clock_snapshot = is_clock_snapshot(packet);
+        if self.current.is_none() {
+            self.open(!is_clock_snapshot)?;
+        }
+        let current = self.current.as_mut().unwrap();
+        current.writer.write_all(&framed)?;
+        current.size += size;
+        if is_clock_snapshot {
+            self.clock_snapshot = Some(framed);
+        }
+        Ok(())
+    }
+
+    // Returns whether a new file must be started instead of writing `size`
+    // more bytes into `current`.
+    fn is_due(&self, current: &RotatedFile, size: u64) -> bool {
+        current.size > 0
+            && (self
+                .settings
+                .rotate_every
+                .is_some_and(|period| current.opened.elapsed() >= period)
+                || self
+                    .settings
+                    .rotate_at_size
+                    .is_some_and(|max_size| current.size + size > max_size))
+    }
+
+    // Starts the next file, with the latest clock snapshot if `with_snapshot`,
+    // and deletes the oldest files beyond the maximum count.
+    fn open(&mut self, with_snapshot: bool) -> io::Result<()> {
+        let path = rotated_path(&self.base_path, self.index);
+        self.index += 1;
+        let mut current = RotatedFile {
+            writer: BufWriter::new(File::create(&path)?),
+            size: 0,
+            opened: Instant::now(),
+        };
+        if with_snapshot && let Some(snapshot) = &self.clock_snapshot {
+            current.writer.write_all(snapshot)?;
+            current.size += snapshot.len() as u64;
+        }
+        self.current = Some(current);
+        let mut files = self.files.lock().unwrap();
+        files.push_back(path);
+        while self
+            .settings
+            .max_files
+            .is_some_and(|max_files| files.len() > max_files)
+        {
+            let oldest = files.pop_front().unwrap();
+            match fs::remove_file(&oldest) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// Returns the path of the file `index` of a rotating recording into `base`:
+// `trace.pftrace` becomes `trace.<index>.pftrace`.
+fn rotated_path(base: &Path, index: u64) -> PathBuf {
+    let mut name = OsString::from(base.file_stem().unwrap_or_default());
+    name.push(format!(".{}", index));
+    if let Some(extension) = base.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    base.with_file_name(name)
+}
+
+fn is_clock_snapshot(packet: &[u8]) -> bool {
+    PbDecoder::new(packet).any(|field| {
+        matches!(field, Ok((id, PbDecoderField::Delimited(_)))
+            if id == TracePacketFieldNumber::ClockSnapshot as u32)
+    })
+}
+
+/// Records a session with the encoded `TraceConfig` `config` for up to
+/// `duration`, and writes the trace into `output_path`, with a
+/// [`TraceRecorder`] using the default settings.
+pub fn record_trace(
+    config: &[u8],
+    duration: Duration,
+    output_path: impl AsRef<Path>,
+) -> Result<RecordedTrace, TraceRecorderError> {
+    TraceRecorder::new().record(config, duration, output_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{DATA_SOURCE_NAME, data_source},
+        tests::acquire_test_environment,
+        trace_config::TraceConfigBuilder,
+        trace_reader::TraceReader,
+    };
+    use std::{error::Error, fs};
+
+    #[test]
+    fn record() -> Result<(), Box<dyn Error>> {
+        let _lock = acquire_test_environment();
+        data_source();
+        let path =
+            std::env::temp_dir().join(format!("trace_recorder_{}.pftrace", std::process::id()));
+        let recorder = TraceRecorder::new().backend(Backends::IN_PROCESS);
+
+        let config = TraceConfigBuilder::ring_buffer(1024)
+            .data_source(DATA_SOURCE_NAME)
+            .build()?;
+        let trace = recorder.record(&config, Duration::from_millis(20), &path)?;
+        assert!(!trace.stopped_by_service);
+        let data = fs::read(&path)?;
+        assert_eq!(trace.size, data.len() as u64);
+        assert!(TraceReader::new(&data).count() > 0);
+
+        // Sessions stopped by the service aren't waited for.
+        let config = TraceConfigBuilder::ring_buffer(1024)
+            .data_source(DATA_SOURCE_NAME)
+            .duration(Duration::from_millis(10))
+            .build()?;
+        let start = Instant::now();
+        let trace = recorder.record(&config, Duration::from_secs(30), &path)?;
+        assert!(trace.stopped_by_service);
+        assert!(start.elapsed() < Duration::from_secs(30));
+        fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn record_rotating() -> Result<(), Box<dyn Error>> {
+        use crate::{
+            data_source::TraceContext,
+            protos::trace::{test_event::*, trace_packet::*},
+        };
+        let _lock = acquire_test_environment();
+        let data_source = data_source();
+        let dir = std::env::temp_dir().join(format!("trace_rotation_{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let config = TraceConfigBuilder::ring_buffer(1024)
+            .data_source(DATA_SOURCE_NAME)
+            .build()?;
+        // Every packet gets a file of its own.
+        let recording = TraceRecorder::new()
+            .backend(Backends::IN_PROCESS)
+            .read_period(Duration::from_millis(10))
+            .rotate_at_size(1)
+            .max_files(3)
+            .record_rotating(&config, dir.join("trace.pftrace"))?;
+        for index in 0..8 {
+            data_source.trace(|ctx: &mut TraceContext| {
+                ctx.add_packet(|packet: &mut TracePacket| {
+                    packet.set_for_testing(|for_testing: &mut TestEvent| {
+                        for_testing.set_counter(index);
+                    });
+                });
+            });
+        }
+        let files = recording.stop()?;
+        assert_eq!(files.len(), 3);
+        let names: Vec<_> = fs::read_dir(&dir)?.collect::<Result<_, _>>()?;
+        assert_eq!(names.len(), 3);
+        let last = files.last().unwrap().file_name().unwrap().to_str().unwrap();
+        // At least one file was written per packet, and the oldest ones were
+        // deleted.
+        let index: u64 = last
+            .strip_prefix("trace.")
+            .and_then(|name| name.strip_suffix(".pftrace"))
+            .unwrap()
+            .parse()?;
+        assert!(index >= 8);
+        for file in &files {
+            let data = fs::read(file)?;
+            let packets = TraceReader::new(&data).collect::<Result<Vec<_>, _>>()?;
+            // Files start with the latest clock snapshot.
+            assert!(is_clock_snapshot(&packets[0]));
+        }
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn rotated_paths() {
+        assert_eq!(
+            rotated_path(Path::new("/tmp/trace.pftrace"), 2),
+            Path::new("/tmp/trace.2.pftrace")
+        );
+        assert_eq!(rotated_path(Path::new("trace"), 0), Path::new("trace.0"));
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::pb_decoder::{PbDecoder, PbDecoderError, PbDecoderField};
+
+// Field numbers of `perfetto.protos.TraceStats`.
+const TRACE_STATS_BUFFER_STATS: u32 = 1;
+const TRACE_STATS_PRODUCERS_CONNECTED: u32 = 2;
+const TRACE_STATS_PRODUCERS_SEEN: u32 = 3;
+const TRACE_STATS_DATA_SOURCES_REGISTERED: u32 = 4;
+const TRACE_STATS_DATA_SOURCES_SEEN: u32 = 5;
+const TRACE_STATS_TRACING_SESSIONS: u32 = 6;
+const TRACE_STATS_CHUNKS_DISCARDED: u32 = 8;
+const TRACE_STATS_INVALID_PACKETS: u32 = 10;
+const TRACE_STATS_FLUSHES_REQUESTED: u32 = 12;
+const TRACE_STATS_FLUSHES_SUCCEEDED: u32 = 13;
+const TRACE_STATS_FLUSHES_FAILED: u32 = 14;
+const TRACE_STATS_DATA_SOURCE_SETUP_FAILURES: u32 = 19;
+
+// Field numbers of `perfetto.protos.TraceStats.BufferStats`.
+const BUFFER_STATS_BYTES_WRITTEN: u32 = 1;
+const BUFFER_STATS_CHUNKS_WRITTEN: u32 = 2;
+const BUFFER_STATS_CHUNKS_OVERWRITTEN: u32 = 3;
+const BUFFER_STATS_WRITE_WRAP_COUNT: u32 = 4;
+const BUFFER_STATS_BUFFER_SIZE: u32 = 12;
+const BUFFER_STATS_BYTES_OVERWRITTEN: u32 = 13;
+const BUFFER_STATS_BYTES_READ: u32 = 14;
+const BUFFER_STATS_CHUNKS_READ: u32 = 17;
+const BUFFER_STATS_CHUNKS_DISCARDED: u32 = 18;
+const BUFFER_STATS_TRACE_WRITER_PACKET_LOSS: u32 = 19;
+
+fn varint(field: &PbDecoderField) -> Result<u64, PbDecoderError> {
+    match field {
+        PbDecoderField::Varint(value) => Ok(*value),
+        _ => Err(PbDecoderError::NotVarint),
+    }
+}
+
+/// Statistics of a buffer of a tracing session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferStats {
+    /// Size of the buffer, in bytes.
+    pub buffer_size: u64,
+    /// Number of bytes written into the buffer, including overwritten ones.
+    pub bytes_written: u64,
+    /// Number of bytes overwritten before being read, in ring buffer mode.
+    pub bytes_overwritten: u64,
+    /// Number of bytes read from the buffer.
+    pub bytes_read: u64,
+    /// Number of chunks written into the buffer.
+    pub chunks_written: u64,
+    /// Number of chunks overwritten before being read, in ring buffer mode.
+    pub chunks_overwritten: u64,
+    /// Number of chunks discarded because the buffer was full, in discard
+    /// mode.
+    pub chunks_discarded: u64,
+    /// Number of chunks read from the buffer.
+    pub chunks_read: u64,
+    /// Number of times the writes wrapped around the end of the buffer.
+    pub write_wrap_count: u64,
+    /// Number of times packets were lost by trace writers, e.g. because the
+    /// shared memory buffer was full.
+    pub trace_writer_packet_loss: u64,
+}
+
+impl BufferStats {
+    /// Returns the fraction of the buffer that was written to, between 0 and 1.
+    /// A ring buffer stays at 1 once it wrapped.
+    pub fn usage(&self) -> f64 {
+        if self.buffer_size == 0 {
+            return 0.0;
+        }
+        if self.write_wrap_count > 0 {
+            return 1.0;
+        }
+        (self.bytes_written as f64 / self.buffer_size as f64).min(1.0)
+    }
+
+    /// Returns true if data was lost: overwritten before being read,
+    /// discarded because the buffer was full, or lost by trace writers.
+    pub fn has_overrun(&self) -> bool {
+        self.chunks_overwritten > 0
+            || self.chunks_discarded > 0
+            || self.trace_writer_packet_loss > 0
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, PbDecoderError> {
+        let mut stats = Self::default();
+        for item in PbDecoder::new(data) {
+            let (id, field) = item?;
+            let value = match id {
+                BUFFER_STATS_BUFFER_SIZE => &mut stats.buffer_size,
+                BUFFER_STATS_BYTES_WRITTEN => &mut stats.bytes_written,
+                BUFFER_STATS_BYTES_OVERWRITTEN => &mut stats.bytes_overwritten,
+                BUFFER_STATS_BYTES_READ => &mut stats.bytes_read,
+                BUFFER_STATS_CHUNKS_WRITTEN => &mut stats.chunks_written,
+                BUFFER_STATS_CHUNKS_OVERWRITTEN => &mut stats.chunks_overwritten,
+                BUFFER_STATS_CHUNKS_DISCARDED => &mut stats.chunks_discarded,
+                BUFFER_STATS_CHUNKS_READ => &mut stats.chunks_read,
+                BUFFER_STATS_WRITE_WRAP_COUNT => &mut stats.write_wrap_count,
+                BUFFER_STATS_TRACE_WRITER_PACKET_LOSS => &mut stats.trace_writer_packet_loss,
+                _ => continue,
+            };
+            *value = varint(&field)?;
+        }
+        Ok(stats)
+    }
+}
+
+/// Statistics of a tracing session and of the tracing service, see
+/// [`TracingSession::get_trace_stats`](crate::tracing_session::TracingSession::get_trace_stats).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceStats {
+    /// Statistics of the buffers of the session, in the order of the config.
+    pub buffers: Vec<BufferStats>,
+    /// Number of producers currently connected to the service.
+    pub producers_connected: u32,
+    /// Number of producers that connected since the service started.
+    pub producers_seen: u64,
+    /// Number of data sources currently registered with the service.
+    pub data_sources_registered: u32,
+    /// Number of data sources registered since the service started.
+    pub data_sources_seen: u64,
+    /// Number of tracing sessions of the service.
+    pub tracing_sessions: u32,
+    /// Number of chunks discarded because they were invalid or too late.
+    pub chunks_discarded: u64,
+    /// Number of invalid packets written by producers.
+    pub invalid_packets: u64,
+    /// Number of flushes requested.
+    pub flushes_requested: u64,
+    /// Number of flushes acked by all data sources.
+    pub flushes_succeeded: u64,
+    /// Number of flushes that timed out.
+    pub flushes_failed: u64,
+    /// Number of data source instances of the session that failed to set up,
+    /// e.g. because they rejected their config.
+    pub data_source_setup_failures: u64,
+}
+
+impl TraceStats {
+    /// Decodes an encoded `perfetto.protos.TraceStats` message. Unknown fields
+    /// are ignored.
+    pub fn decode(data: &[u8]) -> Result<Self, PbDecoderError> {
+        let mut stats = Self::default();
+        for item in PbDecoder::new(data) {
+            let (id, field) = item?;
+            match id {
+                TRACE_STATS_BUFFER_STATS => {
+                    stats.buffers.push(BufferStats::decode(field.as_bytes()?)?)
+                }
+                TRACE_STATS_PRODUCERS_CONNECTED => {
+                    stats.producers_connected = varint(&field)? as u32
+                }
+                TRACE_STATS_PRODUCERS_SEEN => stats.producers_seen = varint(&field)?,
+                TRACE_STATS_DATA_SOURCES_REGISTERED => {
+                    stats.data_sources_registered = varint(&field)? as u32
+                }
+                TRACE_STATS_DATA_SOURCES_SEEN => stats.data_sources_seen = varint(&field)?,
+                TRACE_STATS_TRACING_SESSIONS => stats.tracing_sessions = varint(&field)? as u32,
+                TRACE_STATS_CHUNKS_DISCARDED => stats.chunks_discarded = varint(&field)?,
+                TRACE_STATS_INVALID_PACKETS => stats.invalid_packets = varint(&field)?,
+                TRACE_STATS_FLUSHES_REQUESTED => stats.flushes_requested = varint(&field)?,
+                TRACE_STATS_FLUSHES_SUCCEEDED => stats.flushes_succeeded = varint(&field)?,
+                TRACE_STATS_FLUSHES_FAILED => stats.flushes_failed = varint(&field)?,
+                TRACE_STATS_DATA_SOURCE_SETUP_FAILURES => {
+                    stats.data_source_setup_failures = varint(&field)?
+                }
+                _ => {}
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Returns true if data was lost in any buffer of the session.
+    pub fn has_overrun(&self) -> bool {
+        self.buffers.iter().any(BufferStats::has_overrun)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        // buffer_stats { buffer_size: 4096 bytes_written: 1024 }
+        // buffer_stats { buffer_size: 1024 chunks_overwritten: 2 write_wrap_count: 1 }
+        // producers_connected: 3 data_sources_registered: 5 flushes_failed: 1
+        // data_source_setup_failures: 2
+        let data = b"\x0a\x06\x60\x80\x20\x08\x80\x08\x0a\x07\x60\x80\x08\x18\x02\x20\x01\
+                     \x10\x03\x20\x05\x70\x01\x98\x01\x02";
+        let stats = TraceStats::decode(data).unwrap();
+        assert_eq!(stats.buffers.len(), 2);
+        assert_eq!(stats.buffers[0].usage(), 0.25);
+        assert!(!stats.buffers[0].has_overrun());
+        assert_eq!(stats.buffers[1].usage(), 1.0);
+        assert!(stats.buffers[1].has_overrun());
+        assert!(stats.has_overrun());
+        assert_eq!(stats.producers_connected, 3);
+        assert_eq!(stats.data_sources_registered, 5);
+        assert_eq!(stats.flushes_failed, 1);
+        assert_eq!(stats.data_source_setup_failures, 2);
+
+        // buffer_stats: 42
+        assert_eq!(
+            TraceStats::decode(b"\x08\x2a"),
+            Err(PbDecoderError::NotDelimited)
+        );
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use perfetto_sdk_sys::*;
+use std::{
+    ffi::{CString, NulError},
+    ptr,
+};
+use thiserror::Error;
+
+/// Tracing service errors.
+#[derive(Error, Debug, PartialEq)]
+pub enum TracingServiceError {
+    /// Invalid socket name.
+    #[error("Invalid socket name: {0}.")]
+    InvalidSocketName(#[from] NulError),
+    /// The service couldn't listen on its sockets.
+    #[error("Failed to start the tracing service.")]
+    StartFailed,
+}
+
+/// Tracing service builder.
+#[derive(Default)]
+#[must_use = "This is a builder; remember to call `.start()` (or keep chaining)."]
+pub struct TracingServiceBuilder {
+    producer_socket: Option<String>,
+    consumer_socket: Option<String>,
+}
+
+impl TracingServiceBuilder {
+    /// Create new tracing service builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the socket the service listens on for producers, instead of the
+    /// platform default. Supports the same formats as
+    /// [`ProducerInitArgsBuilder::producer_socket_name`](crate::producer::ProducerInitArgsBuilder::producer_socket_name).
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn producer_socket(mut self, name: impl Into<String>) -> Self {
+        self.producer_socket = Some(name.into());
+        self
+    }
+
+    /// Sets the socket the service listens on for consumers, instead of the
+    /// platform default.
+    #[must_use = "Builder methods return an updated builder; use the returned value or keep chaining."]
+    pub fn consumer_socket(mut self, name: impl Into<String>) -> Self {
+        self.consumer_socket = Some(name.into());
+        self
+    }
+
+    /// Starts the tracing service.
+    pub fn start(self) -> Result<TracingService, TracingServiceError> {
+        let producer_socket = self.producer_socket.map(CString::new).transpose()?;
+        let consumer_socket = self.consumer_socket.map(CString::new).transpose()?;
+        // SAFETY: the socket names must be NULL or valid C strings, which only
+        // need to outlive the call.
+        let service = unsafe {
+            PerfettoServiceStart(
+                producer_socket
+                    .as_ref()
+                    .map_or(ptr::null(), |name| name.as_ptr()),
+                consumer_socket
+                    .as_ref()
+                    .map_or(ptr::null(), |name| name.as_ptr()),
+            )
+        };
+        if service.is_null() {
+            return Err(TracingServiceError::StartFailed);
+        }
+        Ok(TracingService { service })
+    }
+}
+
+/// Tracing service running on a dedicated thread of the current process, like
+/// the `traced` daemon on platforms that have one. Producers connect to it
+/// with the system backend, and consumers with system tracing sessions or the
+/// `perfetto` command line tool. The service stops when dropped.
+///
+/// This lets a single binary be producer, service and consumer of traces.
+/// Note that the [`IN_PROCESS`](crate::producer::Backends::IN_PROCESS)
+/// backend is a simpler option when other processes don't need to connect.
+///
+/// Example:
+///
+/// ```no_run
+/// use perfetto_sdk::{
+///     producer::{Backends, Producer, ProducerInitArgsBuilder},
+///     tracing_service::TracingServiceBuilder,
+///     tracing_session::TracingSession,
+/// };
+///
+/// // Listens on the platform default sockets.
+/// let _service = TracingServiceBuilder::new().start().unwrap();
+/// Producer::init(ProducerInitArgsBuilder::new().backends(Backends::SYSTEM).build());
+/// let session = TracingSession::system().unwrap();
+/// ```
+pub struct TracingService {
+    service: *mut PerfettoService,
+}
+
+// SAFETY: The service is only accessed through its thread-safe C API.
+unsafe impl Send for TracingService {}
+// SAFETY: The service is only accessed through its thread-safe C API.
+unsafe impl Sync for TracingService {}
+
+impl Drop for TracingService {
+    fn drop(&mut self) {
+        // SAFETY: `self.service` must have been returned by
+        // PerfettoServiceStart and not destroyed yet.
+        unsafe { PerfettoServiceDestroy(self.service) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn start() {
+        let pid = std::process::id();
+        let builder = || {
+            TracingServiceBuilder::new()
+                .producer_socket(format!("@perfetto-test-producer-{pid}"))
+                .consumer_socket(format!("@perfetto-test-consumer-{pid}"))
+        };
+        let service = builder().start().unwrap();
+        // The sockets are already in use.
+        assert_eq!(
+            builder().start().err(),
+            Some(TracingServiceError::StartFailed)
+        );
+        drop(service);
+        builder().start().unwrap();
+        assert!(matches!(
+            TracingServiceBuilder::new().producer_socket("\0").start(),
+            Err(TracingServiceError::InvalidSocketName(_))
+        ));
+    }
+}
@@ -12,8 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::{
+    pb_decoder::PbDecoderError, trace_config::TraceConfigSummary, trace_stats::TraceStats,
+};
 use perfetto_sdk_sys::*;
-use std::{ffi::c_void, time::Duration};
+#[cfg(unix)]
+use std::os::fd::{IntoRawFd, OwnedFd};
+use std::{
+    ffi::{CString, NulError, c_void},
+    time::Duration,
+};
 use thiserror::Error;
 
 /// Tracing session errors.
@@ -22,6 +30,28 @@ pub enum TracingSessionError {
     /// Error creating tracing session.
     #[error("Failed to create tracing session.")]
     CreateError,
+    /// Invalid session key.
+    #[error("Invalid session key: {0}.")]
+    InvalidKey(#[from] NulError),
+    /// The session isn't started, or another session was detached with the
+    /// same key.
+    #[error("Failed to detach tracing session.")]
+    DetachError,
+    /// No session was detached with the key.
+    #[error("Failed to attach tracing session.")]
+    AttachError,
+    /// No session has the unique session name, or it couldn't be cloned.
+    #[error("Failed to clone tracing session.")]
+    CloneError,
+    /// The session wasn't set up, e.g. because it was attached.
+    #[error("The tracing session has no config.")]
+    NoConfig,
+    /// The tracing service couldn't be queried.
+    #[error("Failed to query the tracing service.")]
+    QueryError,
+    /// The tracing service replied with an invalid message.
+    #[error("Invalid reply from the tracing service: {0}")]
+    InvalidReply(#[from] PbDecoderError),
 }
 
 type FlushCallback = Box<dyn Fn(bool) + Send + Sync + 'static>;
@@ -40,8 +70,41 @@ unsafe extern "C" fn flush_callback_trampoline(
         f(success);
     });
     if let Err(err) = result {
-        eprintln!("Fatal panic: {:?}", err);
-        std::process::abort();
+        crate::__sdk_fatal!("Fatal panic: {:?}", err);
+    }
+}
+
+unsafe extern "C" fn query_callback_trampoline(
+    _impl: *mut PerfettoTracingSessionImpl,
+    data: *const c_void,
+    size: usize,
+    user_arg: *mut c_void,
+) {
+    let result = std::panic::catch_unwind(|| {
+        // SAFETY: `data` must point to a buffer of `size` length.
+        let bytes = unsafe { std::slice::from_raw_parts(data as *const u8, size) };
+        // SAFETY: `user_arg` must be a `Vec<u8>` that outlives the query.
+        let reply: &mut Vec<u8> = unsafe { &mut *(user_arg as *mut _) };
+        reply.extend_from_slice(bytes);
+    });
+    if let Err(err) = result {
+        crate::__sdk_fatal!("Fatal panic: {:?}", err);
+    }
+}
+
+type StopCallback = Box<dyn Fn() + Send + Sync + 'static>;
+
+unsafe extern "C" fn stop_callback_trampoline(
+    _impl: *mut PerfettoTracingSessionImpl,
+    user_arg: *mut c_void,
+) {
+    let result = std::panic::catch_unwind(|| {
+        // SAFETY: `user_arg` must be a boxed StopCallback that outlives the session.
+        let f: &StopCallback = unsafe { &*(user_arg as *const _) };
+        f();
+    });
+    if let Err(err) = result {
+        crate::__sdk_fatal!("Fatal panic: {:?}", err);
     }
 }
 
@@ -71,14 +134,18 @@ unsafe extern "C" fn read_callback_trampoline(
         }
     });
     if let Err(err) = result {
-        eprintln!("Fatal panic: {:?}", err);
-        std::process::abort();
+        crate::__sdk_fatal!("Fatal panic: {:?}", err);
     }
 }
 
 /// An opaque structure used as the representation of a tracing session.
 pub struct TracingSession {
     impl_: *mut PerfettoTracingSessionImpl,
+    // Config the session was set up with.
+    config: Option<Vec<u8>>,
+    // Callback called when the session stops. Dropped after the session is
+    // destroyed.
+    stop_callback: Option<Box<StopCallback>>,
 }
 
 impl TracingSession {
@@ -89,7 +156,11 @@ impl TracingSession {
         if impl_.is_null() {
             return Err(TracingSessionError::CreateError);
         }
-        Ok(Self { impl_ })
+        Ok(Self {
+            impl_,
+            config: None,
+            stop_callback: None,
+        })
     }
 
     /// Creates an in-process tracing session.
@@ -99,7 +170,11 @@ impl TracingSession {
         if impl_.is_null() {
             return Err(TracingSessionError::CreateError);
         }
-        Ok(Self { impl_ })
+        Ok(Self {
+            impl_,
+            config: None,
+            stop_callback: None,
+        })
     }
 
     /// Setup tracing session using the provided `cfg` trace config.
@@ -113,6 +188,60 @@ impl TracingSession {
         //   `PerfettoTracingSessionInProcessCreate`.
         // - `cfg` must be a properly encoded trace config.
         unsafe { PerfettoTracingSessionSetup(self.impl_, cfg.as_ptr() as *mut c_void, cfg.len()) };
+        self.config = Some(cfg.to_vec());
+    }
+
+    /// Setup tracing session using the provided `cfg` trace config, with the
+    /// tracing service periodically writing the trace into `file` instead of
+    /// keeping it in its buffers until it is read, so that long traces don't
+    /// need large buffers. The period is `file_write_period_ms` in the config,
+    /// and `write_into_file` is set in the config.
+    ///
+    /// To have the tracing service create the trace file instead, set
+    /// `write_into_file` and `output_path` in the config and use
+    /// [`setup`](Self::setup).
+    ///
+    /// # Safety
+    ///
+    /// - `cfg` must be a properly encoded trace config.
+    #[cfg(unix)]
+    pub fn setup_with_file(&mut self, cfg: &[u8], file: impl Into<OwnedFd>) {
+        let fd = file.into().into_raw_fd();
+        // SAFETY:
+        // - `self.impl_` must be created using `PerfettoTracingSessionSystemCreate` or
+        //   `PerfettoTracingSessionInProcessCreate`.
+        // - `cfg` must be a properly encoded trace config.
+        // - The session takes ownership of `fd`.
+        unsafe {
+            PerfettoTracingSessionSetupWithFd(
+                self.impl_,
+                cfg.as_ptr() as *mut c_void,
+                cfg.len(),
+                fd,
+            )
+        };
+        self.config = Some(cfg.to_vec());
+    }
+
+    /// Calls `cb` on an internal perfetto thread when the session stops,
+    /// whether it is stopped by [`stop_blocking`](Self::stop_blocking) or by
+    /// the tracing service, e.g. once the duration of its config elapsed. Must
+    /// be called before the session is started.
+    pub fn set_stop_callback<F>(&mut self, cb: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        let mut boxed: Box<StopCallback> = Box::new(Box::new(cb));
+        let user_arg = crate::__box_as_mut_ptr(&mut boxed) as *mut c_void;
+        // SAFETY:
+        // - `self.impl_` must be created using `PerfettoTracingSessionSystemCreate` or
+        //   `PerfettoTracingSessionInProcessCreate`.
+        // - `user_arg` must be a boxed StopCallback, which is kept until the
+        //   session is destroyed.
+        unsafe {
+            PerfettoTracingSessionSetStopCb(self.impl_, Some(stop_callback_trampoline), user_arg)
+        };
+        self.stop_callback = Some(boxed);
     }
 
     /// Asynchronous start of tracing session.
@@ -136,6 +265,127 @@ impl TracingSession {
         unsafe { PerfettoTracingSessionStopBlocking(self.impl_) };
     }
 
+    /// Detaches the started tracing session, which keeps running in the tracing
+    /// service, and isn't stopped when `self` is dropped, until a tracing
+    /// session attaches to it with [`attach`](Self::attach) and the same `key`.
+    /// With system sessions, this lets a process start a long trace and exit,
+    /// and a later process collect it. Stopping `self` afterwards has no effect.
+    pub fn detach(&mut self, key: &str) -> Result<(), TracingSessionError> {
+        let key = CString::new(key)?;
+        // SAFETY:
+        // - `self.impl_` must be created using `PerfettoTracingSessionSystemCreate` or
+        //   `PerfettoTracingSessionInProcessCreate`.
+        // - `key` must be a valid C string.
+        if !unsafe { PerfettoTracingSessionDetachBlocking(self.impl_, key.as_ptr()) } {
+            return Err(TracingSessionError::DetachError);
+        }
+        Ok(())
+    }
+
+    /// Attaches to the tracing session detached with `key`, instead of setting
+    /// up and starting a new one. The session can then be stopped, flushed and
+    /// read as if it had been started by `self`.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use perfetto_sdk::tracing_session::TracingSession;
+    ///
+    /// let mut session = TracingSession::system().unwrap();
+    /// session.attach("background-trace").unwrap();
+    /// session.stop_blocking();
+    /// session.read_trace_blocking(|data, _has_more| {
+    ///     println!("{} bytes", data.len());
+    /// });
+    /// ```
+    pub fn attach(&mut self, key: &str) -> Result<(), TracingSessionError> {
+        let key = CString::new(key)?;
+        // SAFETY:
+        // - `self.impl_` must be created using `PerfettoTracingSessionSystemCreate` or
+        //   `PerfettoTracingSessionInProcessCreate`.
+        // - `key` must be a valid C string.
+        if !unsafe { PerfettoTracingSessionAttachBlocking(self.impl_, key.as_ptr()) } {
+            return Err(TracingSessionError::AttachError);
+        }
+        Ok(())
+    }
+
+    /// Clones the tracing session whose config has the `unique_session_name`,
+    /// e.g. to take a snapshot of a long running session without stopping it,
+    /// instead of setting up and starting a new one. The data sources of the
+    /// cloned session are flushed first. `self` is attached to the read-only
+    /// clone, which can then be read.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use perfetto_sdk::tracing_session::TracingSession;
+    ///
+    /// let mut snapshot = TracingSession::system().unwrap();
+    /// snapshot.clone_session("background-trace").unwrap();
+    /// snapshot.read_trace_blocking(|data, _has_more| {
+    ///     println!("{} bytes", data.len());
+    /// });
+    /// ```
+    pub fn clone_session(&mut self, unique_session_name: &str) -> Result<(), TracingSessionError> {
+        let unique_session_name = CString::new(unique_session_name)?;
+        // SAFETY:
+        // - `self.impl_` must be created using `PerfettoTracingSessionSystemCreate` or
+        //   `PerfettoTracingSessionInProcessCreate`.
+        // - `unique_session_name` must be a valid C string.
+        if !unsafe { PerfettoTracingSessionCloneBlocking(self.impl_, unique_session_name.as_ptr()) }
+        {
+            return Err(TracingSessionError::CloneError);
+        }
+        Ok(())
+    }
+
+    /// Returns the main settings of the config the session was set up with.
+    /// Fails with [`TracingSessionError::NoConfig`] if the session wasn't set
+    /// up, e.g. because it was attached.
+    pub fn query_config(&self) -> Result<TraceConfigSummary, TracingSessionError> {
+        let config = self.config.as_ref().ok_or(TracingSessionError::NoConfig)?;
+        Ok(TraceConfigSummary::decode(config)?)
+    }
+
+    /// Queries the statistics of the session from the tracing service, e.g. to
+    /// show the usage of its buffers while it is running, or detect that data
+    /// was lost because they overran.
+    ///
+    /// Example:
+    ///
+    /// ```no_run
+    /// use perfetto_sdk::tracing_session::TracingSession;
+    ///
+    /// let mut session = TracingSession::system().unwrap();
+    /// session.attach("background-trace").unwrap();
+    /// let stats = session.get_trace_stats().unwrap();
+    /// for buffer in &stats.buffers {
+    ///     println!("{:.0}% used", buffer.usage() * 100.0);
+    /// }
+    /// if stats.has_overrun() {
+    ///     println!("Data was lost");
+    /// }
+    /// ```
+    pub fn get_trace_stats(&mut self) -> Result<TraceStats, TracingSessionError> {
+        let mut reply: Vec<u8> = Vec::new();
+        // SAFETY:
+        // - `self.impl_` must be created using `PerfettoTracingSessionSystemCreate` or
+        //   `PerfettoTracingSessionInProcessCreate`.
+        // - `reply` outlives the blocking query.
+        let success = unsafe {
+            PerfettoTracingSessionGetTraceStatsBlocking(
+                self.impl_,
+                Some(query_callback_trampoline),
+                &raw mut reply as *mut c_void,
+            )
+        };
+        if !success {
+            return Err(TracingSessionError::QueryError);
+        }
+        Ok(TraceStats::decode(&reply)?)
+    }
+
     /// Issues a flush request, asking all data sources to ack the request, within
     /// the specified timeout. A "flush" is a fence to ensure visibility of data in
     /// the async tracing pipeline. It guarantees that all data written before the
@@ -207,34 +457,58 @@ impl Drop for TracingSession {
 
 #[cfg(test)]
 mod tests {
-    use crate::data_source::DataSource;
+    use super::*;
+    use crate::test_util::{self, DATA_SOURCE_NAME};
     use crate::tests::{TracingSessionBuilder, acquire_test_environment};
     use crate::{track_event::TrackEvent, track_event_categories};
-    use std::{
-        error::Error,
-        sync::{MutexGuard, OnceLock},
-    };
-
-    const DATA_SOURCE_NAME: &str = "dev.perfetto.example_data_source";
-    static DATA_SOURCE: OnceLock<DataSource> = OnceLock::new();
-
-    fn get_data_source() -> &'static DataSource<'static> {
-        use crate::data_source::DataSourceArgsBuilder;
-        DATA_SOURCE.get_or_init(|| {
-            let data_source_args = DataSourceArgsBuilder::new();
-            let mut data_source = DataSource::new();
-            data_source
-                .register(DATA_SOURCE_NAME, data_source_args.build())
-                .expect("failed to register data source");
-            data_source
-        })
+    use std::{error::Error, sync::MutexGuard};
+
+    #[test]
+    fn stats_and_config() -> Result<(), Box<dyn Error>> {
+        use crate::{data_source::*, protos::trace::trace_packet::TracePacket};
+        let _lock = acquire_test_environment();
+        let data_source = test_util::data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        let config = session.query_config()?;
+        assert_eq!(config.buffer_sizes_kb, vec![1024]);
+        assert_eq!(config.data_sources, vec![DATA_SOURCE_NAME.to_string()]);
+        session.start_blocking();
+        data_source.trace(|ctx: &mut TraceContext| {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_timestamp(1);
+            });
+            ctx.flush(|| {});
+        });
+        session.flush_blocking(Duration::from_secs(5));
+        let stats = session.get_trace_stats()?;
+        assert_eq!(stats.buffers.len(), 1);
+        assert_eq!(stats.buffers[0].buffer_size, 1024 * 1024);
+        assert!(stats.buffers[0].bytes_written > 0);
+        assert!(stats.buffers[0].usage() > 0.0);
+        assert!(!stats.has_overrun());
+        assert!(stats.producers_connected >= 1);
+        assert!(stats.tracing_sessions >= 1);
+        session.stop_blocking();
+
+        let mut attached = TracingSession::in_process()?;
+        assert_eq!(
+            attached.query_config().unwrap_err(),
+            TracingSessionError::NoConfig
+        );
+        assert_eq!(
+            attached.get_trace_stats().unwrap_err(),
+            TracingSessionError::QueryError
+        );
+        Ok(())
     }
 
     #[test]
     fn data_source() -> Result<(), Box<dyn Error>> {
         use crate::data_source::*;
         let _lock = acquire_test_environment();
-        let data_source = get_data_source();
+        let data_source = test_util::data_source();
         let mut session = TracingSessionBuilder::new()
             .set_data_source_name(DATA_SOURCE_NAME)
             .build()?;
@@ -304,7 +578,7 @@ mod tests {
         use crate::protos::trace::{test_event::*, trace::*, trace_packet::*};
         use std::sync::{Arc, Mutex};
         let _lock = acquire_test_environment();
-        let data_source = get_data_source();
+        let data_source = test_util::data_source();
         let mut session = TracingSessionBuilder::new()
             .set_data_source_name(DATA_SOURCE_NAME)
             .build()?;
@@ -343,4 +617,134 @@ mod tests {
         assert!(for_testing_found);
         Ok(())
     }
+
+    #[test]
+    fn detach_and_attach() -> Result<(), Box<dyn Error>> {
+        use super::*;
+        use crate::data_source::TraceContext;
+        use crate::protos::trace::{test_event::*, trace_packet::*};
+        use crate::tests::read_trace_data;
+        use crate::trace_reader::TraceReader;
+        let _lock = acquire_test_environment();
+        let data_source = test_util::data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        let mut not_started = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .build()?;
+        assert_eq!(
+            not_started.detach("detach-test"),
+            Err(TracingSessionError::DetachError)
+        );
+        session.start_blocking();
+        session.detach("detach-test")?;
+        // Neither stopping nor dropping the session stops the detached session.
+        session.stop_blocking();
+        drop(session);
+        assert!(data_source.is_enabled());
+        data_source.trace(|ctx: &mut TraceContext| {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_for_testing(|for_testing: &mut TestEvent| {
+                    for_testing.set_str("Detached session");
+                });
+            });
+        });
+
+        let mut session = TracingSession::in_process()?;
+        assert_eq!(
+            session.attach("unknown-key"),
+            Err(TracingSessionError::AttachError)
+        );
+        session.attach("detach-test")?;
+        session.stop_blocking();
+        assert!(!data_source.is_enabled());
+        let data = read_trace_data(&mut session);
+        let packets = TraceReader::new(&data).collect::<Result<Vec<_>, _>>()?;
+        assert!(
+            packets
+                .iter()
+                .any(|packet| packet.windows(16).any(|w| w == b"Detached session"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn clone_session() -> Result<(), Box<dyn Error>> {
+        use super::*;
+        use crate::data_source::TraceContext;
+        use crate::protos::trace::{test_event::*, trace_packet::*};
+        use crate::tests::read_trace_data;
+        use crate::trace_reader::TraceReader;
+        let _lock = acquire_test_environment();
+        let data_source = test_util::data_source();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .set_unique_session_name("clone-test")
+            .build()?;
+        session.start_blocking();
+        data_source.trace(|ctx: &mut TraceContext| {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_for_testing(|for_testing: &mut TestEvent| {
+                    for_testing.set_str("Cloned session");
+                });
+            });
+        });
+
+        let mut clone = TracingSession::in_process()?;
+        assert_eq!(
+            clone.clone_session("unknown-name"),
+            Err(TracingSessionError::CloneError)
+        );
+        let mut clone = TracingSession::in_process()?;
+        clone.clone_session("clone-test")?;
+        // The cloned session keeps running.
+        assert!(data_source.is_enabled());
+        let data = read_trace_data(&mut clone);
+        let packets = TraceReader::new(&data).collect::<Result<Vec<_>, _>>()?;
+        assert!(
+            packets
+                .iter()
+                .any(|packet| packet.windows(14).any(|w| w == b"Cloned session"))
+        );
+        session.stop_blocking();
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn write_into_file() -> Result<(), Box<dyn Error>> {
+        use crate::data_source::TraceContext;
+        use crate::protos::trace::{test_event::*, trace_packet::*};
+        use crate::trace_reader::TraceReader;
+        let _lock = acquire_test_environment();
+        let data_source = test_util::data_source();
+        let path = std::env::temp_dir().join(format!(
+            "perfetto-write-into-file-{}.pftrace",
+            std::process::id()
+        ));
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name(DATA_SOURCE_NAME)
+            .set_file_write_period_ms(100)
+            .build_with_file(std::fs::File::create(&path)?)?;
+        session.start_blocking();
+        data_source.trace(|ctx: &mut TraceContext| {
+            ctx.add_packet(|packet: &mut TracePacket| {
+                packet.set_for_testing(|for_testing: &mut TestEvent| {
+                    for_testing.set_str("Written into file");
+                });
+            });
+        });
+        session.stop_blocking();
+        drop(session);
+        let data = std::fs::read(&path)?;
+        std::fs::remove_file(&path)?;
+        let packets = TraceReader::new(&data).collect::<Result<Vec<_>, _>>()?;
+        assert!(
+            packets
+                .iter()
+                .any(|packet| packet.windows(17).any(|w| w == b"Written into file"))
+        );
+        Ok(())
+    }
 }
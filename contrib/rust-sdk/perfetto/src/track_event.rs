@@ -13,13 +13,17 @@
 // limitations under the License.
 
 use crate::{
-    data_source::TraceContextBase,
+    counter_value::CounterValue,
+    data_source::{DataSourceTimestamp, InTraceScope, TraceContextBase, TraceOutcome},
     fnv1a,
     heap_buffer::HeapBuffer,
     pb_msg::{PbMsg, PbMsgWriter},
     protos::trace::{
         interned_data::interned_data::InternedDataFieldNumber,
-        track_event::{counter_descriptor::CounterDescriptor, track_descriptor::TrackDescriptor},
+        track_event::{
+            counter_descriptor::CounterDescriptor, track_descriptor::TrackDescriptor,
+            track_event::TrackEventFieldNumber,
+        },
     },
 };
 use perfetto_sdk_sys::*;
@@ -28,7 +32,10 @@ use std::{
     marker::PhantomData,
     os::raw::{c_char, c_void},
     ptr,
-    sync::atomic::{AtomicBool, Ordering},
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicPtr, Ordering},
+    },
     time::Duration,
 };
 use thiserror::Error;
@@ -107,6 +114,24 @@ impl std::ops::DerefMut for TraceContext {
     }
 }
 
+/// How the `track_event` config of a data source instance, received when the
+/// instance is set up, enables categories.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CategoryFiltering {
+    /// Categories are enabled by the `enabled_categories` and `enabled_tags`
+    /// patterns of the config, which take precedence over the disabled ones,
+    /// and categories that match nothing are disabled.
+    #[default]
+    C,
+    /// Same as the C++ SDK: exact matches take precedence over patterns,
+    /// which take precedence over a single `"*"` wildcard. For each kind of
+    /// match, enabled categories take precedence over disabled ones, then
+    /// disabled tags over enabled ones. The `"slow"` and `"debug"` tags are
+    /// disabled unless the config lists tags, and categories that match
+    /// nothing are enabled.
+    Cpp,
+}
+
 /// An opaque struct used to represent the track event machinery.
 pub struct TrackEvent {}
 
@@ -125,6 +150,79 @@ impl TrackEvent {
         // SAFETY: FFI call with no outstanding preconditions.
         unsafe { PerfettoTePublishCategories() };
     }
+
+    /// Sets how the configs of track event data source instances enable
+    /// categories. Must be called before setting up the tracing sessions. Each
+    /// instance applies its own config, so concurrent sessions can enable
+    /// different categories.
+    pub fn set_category_filtering(filtering: CategoryFiltering) {
+        let mode = match filtering {
+            CategoryFiltering::C => PerfettoTeCategoryFiltering_PERFETTO_TE_CATEGORY_FILTERING_C,
+            CategoryFiltering::Cpp => {
+                PerfettoTeCategoryFiltering_PERFETTO_TE_CATEGORY_FILTERING_CPP
+            }
+        };
+        // SAFETY: FFI call with no outstanding preconditions.
+        unsafe { PerfettoTeSetCategoryFiltering(mode) };
+    }
+}
+
+// State of a registered category, updated by its callback and by the trace
+// marker fallback, so that the track event macros check a single flag.
+struct CategoryState {
+    // Whether a session enabled the category or the fallback covers it.
+    active: AtomicBool,
+    enabled: *const AtomicBool,
+    name: *const c_char,
+    // Whether the fallback covers the category. Also serializes the updates
+    // of `active`.
+    fallback: Mutex<bool>,
+    callback: AtomicPtr<CategoryCallback>,
+}
+
+// SAFETY: `enabled` and `name` point to data owned by the registered
+// category, which is only read.
+unsafe impl Sync for CategoryState {}
+
+impl CategoryState {
+    // Updates `active`, after setting whether the fallback covers the
+    // category if `fallback` is set.
+    fn update(&self, fallback: Option<bool>) {
+        let mut covered = self.fallback.lock().unwrap();
+        if let Some(fallback) = fallback {
+            *covered = fallback;
+        }
+        // SAFETY: `enabled` points to a primitive with layout that matches C11
+        // atomic_bool.
+        let enabled = unsafe { (*self.enabled).load(Ordering::Relaxed) };
+        self.active.store(enabled || *covered, Ordering::Relaxed);
+    }
+}
+
+static INACTIVE_CATEGORY: CategoryState = CategoryState {
+    active: AtomicBool::new(false),
+    enabled: &raw const perfetto_atomic_false as *const AtomicBool,
+    name: ptr::null(),
+    fallback: Mutex::new(false),
+    callback: AtomicPtr::new(ptr::null_mut()),
+};
+
+struct CategoryStatePtr(*const CategoryState);
+
+// SAFETY: the pointed to states are `Sync`.
+unsafe impl Send for CategoryStatePtr {}
+
+static REGISTERED_CATEGORIES: Mutex<Vec<CategoryStatePtr>> = Mutex::new(Vec::new());
+
+// Updates whether the trace marker fallback covers the registered categories.
+pub(crate) fn update_fallback_categories() {
+    for state in REGISTERED_CATEGORIES.lock().unwrap().iter() {
+        // SAFETY: registered states are freed after being removed.
+        let state = unsafe { &*state.0 };
+        // SAFETY: the names of registered states are null-terminated C strings.
+        let name = unsafe { CStr::from_ptr(state.name) }.to_string_lossy();
+        state.update(Some(crate::trace_marker::covers(&name)));
+    }
 }
 
 /// Category callback type.
@@ -136,6 +234,7 @@ pub type CategoryCallback = Box<dyn FnMut(u32, bool, bool) + Send + Sync + 'stat
 /// this struct directly.
 pub struct TrackEventCategory {
     enabled: *mut bool,
+    state: *const CategoryState,
     impl_: *mut PerfettoTeCategoryImpl,
     desc: PerfettoTeCategoryDescriptor,
     cat_iid: u64,
@@ -151,6 +250,7 @@ impl TrackEventCategory {
     pub const unsafe fn new(name: &CStr, desc: &CStr, tags: &[*const c_char]) -> Self {
         Self {
             enabled: &raw mut perfetto_atomic_false,
+            state: &INACTIVE_CATEGORY,
             impl_: ptr::null_mut(),
             desc: PerfettoTeCategoryDescriptor {
                 name: name.as_ptr() as *const c_char,
@@ -173,6 +273,15 @@ impl TrackEventCategory {
         }
     }
 
+    /// Returns whether the events of the category are written, either because
+    /// a session enabled it or into the
+    /// [`TraceMarkerFallback`](crate::trace_marker::TraceMarkerFallback).
+    pub fn is_active(&self) -> bool {
+        // SAFETY: `self.state` points to `INACTIVE_CATEGORY` or to the state
+        // allocated by `register`, which is freed after being replaced.
+        unsafe { (*self.state).active.load(Ordering::Relaxed) }
+    }
+
     /// Registers the category.
     pub fn register(&mut self) {
         assert!(!self.desc.name.is_null());
@@ -187,6 +296,35 @@ impl TrackEventCategory {
             self.enabled = PerfettoTeCategoryImplGetEnabled(self.impl_);
             self.cat_iid = PerfettoTeCategoryImplGetIid(self.impl_);
         }
+        let state = Box::into_raw(Box::new(CategoryState {
+            active: AtomicBool::new(false),
+            enabled: self.enabled as *const AtomicBool,
+            name: self.desc.name,
+            fallback: Mutex::new(false),
+            callback: AtomicPtr::new(ptr::null_mut()),
+        }));
+        self.state = state;
+        {
+            let mut registered = REGISTERED_CATEGORIES.lock().unwrap();
+            // SAFETY: `state` was just allocated.
+            unsafe { &*state }.update(Some(crate::trace_marker::covers(&self.name())));
+            registered.push(CategoryStatePtr(state));
+        }
+        // SAFETY:
+        // - `self.impl_` was just created using PerfettoTeCategoryImplCreate.
+        // - `state` is a CategoryState that outlives the category.
+        unsafe {
+            PerfettoTeCategoryImplSetCallback(
+                self.impl_,
+                Some(Self::callback_trampoline),
+                state as *mut c_void,
+            )
+        };
+    }
+
+    fn name(&self) -> std::borrow::Cow<'_, str> {
+        // SAFETY: `self.desc.name` is a null-terminated C string.
+        unsafe { CStr::from_ptr(self.desc.name) }.to_string_lossy()
     }
 
     /// Unregisters the category. Must have been previously registered.
@@ -197,6 +335,14 @@ impl TrackEventCategory {
         self.impl_ = ptr::null_mut();
         self.enabled = &raw mut perfetto_atomic_false;
         self.cat_iid = 0;
+        let state = std::mem::replace(&mut self.state, &INACTIVE_CATEGORY);
+        REGISTERED_CATEGORIES
+            .lock()
+            .unwrap()
+            .retain(|registered| registered.0 != state);
+        // SAFETY: `state` was allocated by `register` and, with the category
+        // destroyed, is no longer used by its callback.
+        drop(unsafe { Box::from_raw(state as *mut CategoryState) });
     }
 
     unsafe extern "C" fn callback_trampoline(
@@ -207,13 +353,19 @@ impl TrackEventCategory {
         user_arg: *mut c_void,
     ) {
         let result = std::panic::catch_unwind(|| {
-            // SAFETY: `user_arg` must be a CategoryCallback.
-            let f: &mut CategoryCallback = unsafe { &mut *(user_arg as *mut _) };
-            f(inst_id, enabled, global_state_changed);
+            // SAFETY: `user_arg` is the CategoryState of the category.
+            let state = unsafe { &*(user_arg as *const CategoryState) };
+            state.update(None);
+            let callback = state.callback.load(Ordering::Acquire);
+            if !callback.is_null() {
+                // SAFETY: `callback` is kept alive by the caller of
+                // `set_callback`.
+                let f: &mut CategoryCallback = unsafe { &mut *callback };
+                f(inst_id, enabled, global_state_changed);
+            }
         });
         if let Err(err) = result {
-            eprintln!("Fatal panic: {:?}", err);
-            std::process::abort();
+            crate::__sdk_fatal!("Fatal panic: {:?}", err);
         }
     }
 
@@ -225,18 +377,30 @@ impl TrackEventCategory {
     ///   category has been unregistered.
     pub unsafe fn set_callback(&mut self, callback: &mut CategoryCallback) {
         assert!(!self.impl_.is_null());
-        let user_arg = crate::__box_as_mut_ptr(callback) as *mut c_void;
+        // SAFETY: `self.state` was allocated by `register`.
+        let state = unsafe { &*self.state };
+        state
+            .callback
+            .store(callback as *mut CategoryCallback, Ordering::Release);
+        // Setting the callback again calls it for the instances that are
+        // already enabled.
         // SAFETY:
         // - `self.impl_` must be previously created using PerfettoTeCategoryImplCreate.
-        // - `user_arg` must be a CategoryCallback.
+        // - `self.state` is a CategoryState that outlives the category.
         unsafe {
-            PerfettoTeCategoryImplSetCallback(self.impl_, Some(Self::callback_trampoline), user_arg)
+            PerfettoTeCategoryImplSetCallback(
+                self.impl_,
+                Some(Self::callback_trampoline),
+                self.state as *mut c_void,
+            )
         };
     }
 
     /// Emit track event of a specific type.
     pub fn emit(&mut self, variant: TrackEventType, ctx: &mut EventContext) {
         assert!(!self.impl_.is_null());
+        #[cfg(debug_assertions)]
+        crate::slice_checker::check(&variant, ctx);
         let te_type = match variant {
             TrackEventType::Instant(_) => PerfettoTeType_PERFETTO_TE_TYPE_INSTANT,
             TrackEventType::SliceBegin(_) => PerfettoTeType_PERFETTO_TE_TYPE_SLICE_BEGIN,
@@ -248,6 +412,17 @@ impl TrackEventCategory {
             TrackEventType::SliceBegin(name) => Some(name),
             _ => None,
         };
+        // Events without an explicit timestamp use the clock of the installed
+        // platform, if any. Without one, the extras aren't scanned.
+        if crate::platform::is_installed()
+            && !ctx
+                .extras
+                .iter()
+                .any(|extra| matches!(extra, TeHlExtra::Timestamp(_)))
+            && let Some(timestamp) = crate::platform::now()
+        {
+            ctx.set_timestamp(timestamp.into());
+        }
         let mut te_extras: Vec<*mut PerfettoTeHlExtra> = ctx
             .extras
             .iter_mut()
@@ -295,6 +470,9 @@ impl TrackEventCategory {
                 TeHlExtra::NestedTracks(te_fields, _, _) => {
                     &mut te_fields.header as *mut PerfettoTeHlExtra
                 }
+                TeHlExtra::SourceLocation(te_location, _, _) => {
+                    &mut te_location.header as *mut PerfettoTeHlExtra
+                }
             })
             .collect();
         te_extras.push(ptr::null_mut());
@@ -304,48 +482,83 @@ impl TrackEventCategory {
         // - `te_type` must be a valid PerfettoTeType_* value.
         // - `name` must be a null-terminated C string or null.
         // - `te_extras` must be a null-terminated array of PerfettoTeHlExtra pointers.
-        unsafe {
+        crate::self_profiling::measure(|| unsafe {
             PerfettoTeHlEmitImpl(
                 self.impl_,
                 te_type as i32,
                 te_name.unwrap_or(ptr::null_mut()),
                 te_extras.as_ptr(),
             )
-        };
+        });
+        crate::self_profiling::record_track_event();
     }
 
     /// Calls `cb` for all active track event data source instances for this category.
-    pub fn trace<F>(&self, mut cb: F)
+    pub fn trace<F>(&self, cb: F)
     where
         F: FnMut(&mut TraceContext),
     {
-        // SAFETY: FFI call with no outstanding preconditions.
-        let timestamp = unsafe { PerfettoTeGetTimestamp() };
+        // SAFETY: `self.impl_` must be previously created using PerfettoTeCategoryImplCreate.
+        unsafe { trace_category(self.impl_, cb) };
+    }
+}
+
+// Calls `cb` for all active track event data source instances for `category`.
+//
+// # Safety
+//
+// - `category` must be a registered category.
+unsafe fn trace_category<F>(category: *mut PerfettoTeCategoryImpl, mut cb: F)
+where
+    F: FnMut(&mut TraceContext),
+{
+    let _in_trace = InTraceScope::enter();
+    // SAFETY: FFI call with no outstanding preconditions.
+    let timestamp = unsafe { PerfettoTeGetTimestamp() };
+
+    // SAFETY:
+    // - `category` must be a registered category.
+    // - `timestamp` must be timestamp from PerfettoTeGetTimestamp().
+    let mut iterator = unsafe { PerfettoTeLlImplBegin(category, timestamp) };
+    loop {
+        if iterator.ds.tracer.is_null() {
+            break;
+        }
+
+        let mut ctx = TraceContext {
+            base: TraceContextBase {
+                iterator: iterator.ds,
+                stats: ptr::null(),
+                outcome: TraceOutcome::default(),
+                bytes_written: 0,
+                budget: ptr::null(),
+                chunk: Default::default(),
+            },
+            incr: iterator.incr,
+        };
+        crate::self_profiling::measure(|| cb(&mut ctx));
 
         // SAFETY:
-        // - `self.impl_` must be previously created using PerfettoTeCategoryImplCreate.
+        // - `category` must be a registered category.
         // - `timestamp` must be timestamp from PerfettoTeGetTimestamp().
-        let mut iterator = unsafe { PerfettoTeLlImplBegin(self.impl_, timestamp) };
-        loop {
-            if iterator.ds.tracer.is_null() {
-                break;
-            }
+        // - `iterator` must be a value returned from PerfettoTeLlImplBegin or
+        //   PerfettoTeLlImplNext with a non-null `iterator.ds.tracer`.
+        unsafe { PerfettoTeLlImplNext(category, timestamp, &raw mut iterator) };
+    }
+}
 
-            let mut ctx = TraceContext {
-                base: TraceContextBase {
-                    iterator: iterator.ds,
-                },
-                incr: iterator.incr,
-            };
-            cb(&mut ctx);
-
-            // SAFETY:
-            // - `self.impl_` must be previously created using PerfettoTeCategoryImplCreate.
-            // - `timestamp` must be timestamp from PerfettoTeGetTimestamp().
-            // - `iterator` must be a value returned from PerfettoTeLlImplBegin or
-            //   PerfettoTeLlImplNext with a non-null `iterator.ds.tracer`.
-            unsafe { PerfettoTeLlImplNext(self.impl_, timestamp, &raw mut iterator) };
-        }
+// Calls `cb` for all active track event data source instances, whatever
+// categories they enable. Does nothing if the track event machinery isn't
+// initialized.
+pub(crate) fn trace_all_instances<F>(cb: F)
+where
+    F: FnMut(&mut TraceContext),
+{
+    // SAFETY: `perfetto_te_any_categories` is only written by `PerfettoTeInit`.
+    let category = unsafe { perfetto_te_any_categories };
+    if !category.is_null() {
+        // SAFETY: `perfetto_te_any_categories` is registered by `PerfettoTeInit`.
+        unsafe { trace_category(category, cb) };
     }
 }
 
@@ -529,6 +742,17 @@ macro_rules! track_event_categories {
                 unsafe { CATEGORIES[category_index].is_enabled() }
             }
 
+            /// Whether the events of the category are written into a session
+            /// or into the trace marker fallback.
+            #[allow(unused)]
+            $vis fn is_category_active(category_index: usize) -> bool {
+                assert!(category_index < CATEGORY_COUNT);
+                // SAFETY:
+                //
+                // - Safe to call on any thread as it uses atomics.
+                unsafe { CATEGORIES[category_index].is_active() }
+            }
+
             #[allow(unused)]
             $vis fn set_category_callback<F>(category_index: usize, cb: F)
             where
@@ -641,6 +865,9 @@ pub enum TrackEventTimestamp {
 impl TrackEventTimestamp {
     /// Get a track event timestamp.
     pub fn now() -> Self {
+        if let Some(timestamp) = crate::platform::now() {
+            return timestamp.into();
+        }
         // SAFETY: Track event machinery must have been initialized.
         let te_timestamp = unsafe { PerfettoTeGetTimestamp() };
         #[allow(non_upper_case_globals)]
@@ -682,6 +909,37 @@ impl TrackEventTimestamp {
     }
 }
 
+impl From<DataSourceTimestamp> for TrackEventTimestamp {
+    fn from(timestamp: DataSourceTimestamp) -> Self {
+        match timestamp {
+            DataSourceTimestamp::Monotonic(value) => TrackEventTimestamp::Monotonic(value),
+            DataSourceTimestamp::Boot(value) => TrackEventTimestamp::Boot(value),
+            DataSourceTimestamp::Custom { id, value } => TrackEventTimestamp::Custom { id, value },
+        }
+    }
+}
+
+/// Returns the CPU time consumed by the calling thread, or `None` on
+/// platforms without a per-thread CPU time clock.
+pub fn thread_cpu_time() -> Option<Duration> {
+    #[cfg(unix)]
+    {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `ts` is a valid, writable timespec.
+        if unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) } != 0 {
+            return None;
+        }
+        Some(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
 const COUNTER_MAGIC: u64 = 0xb1a4a67d7970839e;
 
 /// Struct used to represent a track event track.
@@ -1122,6 +1380,36 @@ impl ToTeHlNestedTrack for TrackEventNestedTrack<'_> {
     }
 }
 
+/// Source location of a track event, see
+/// [`EventContext::set_source_location`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TrackEventSourceLocation<'a> {
+    /// Name of the source file.
+    pub file_name: &'a str,
+    /// Name of the function, if known.
+    pub function_name: Option<&'a str>,
+    /// Line of the source file.
+    pub line_number: u32,
+}
+
+impl TrackEventSourceLocation<'static> {
+    /// Returns the location of the caller, see [`std::panic::Location::caller`].
+    #[track_caller]
+    pub fn caller() -> Self {
+        std::panic::Location::caller().into()
+    }
+}
+
+impl From<&'static std::panic::Location<'static>> for TrackEventSourceLocation<'static> {
+    fn from(location: &'static std::panic::Location<'static>) -> Self {
+        Self {
+            file_name: location.file(),
+            function_name: None,
+            line_number: location.line(),
+        }
+    }
+}
+
 /// Struct containing references to a hierarchy of nested tracks.
 #[derive(Debug)]
 pub struct TrackEventNestedTracks<'a> {
@@ -1162,6 +1450,7 @@ pub(crate) enum TeHlExtra {
         Vec<TeHlNestedTrack>,
         Vec<*mut PerfettoTeHlNestedTrack>,
     ),
+    SourceLocation(PerfettoTeHlExtraSourceLocation, CString, Option<CString>),
 }
 
 /// Struct with extra data for a track event instance.
@@ -1224,6 +1513,37 @@ impl EventContext {
         self
     }
 
+    /// Add the CPU time consumed by the calling thread, so that the thread
+    /// time spent in a slice shows up in the UI. Does nothing on platforms
+    /// without a per-thread CPU time clock.
+    pub fn set_thread_time(&mut self) -> &mut Self {
+        match thread_cpu_time() {
+            Some(thread_time) => self.set_thread_time_absolute(thread_time),
+            None => self,
+        }
+    }
+
+    /// Add explicit thread CPU time.
+    pub fn set_thread_time_absolute(&mut self, thread_time: Duration) -> &mut Self {
+        self.set_proto_fields(&TrackEventProtoFields {
+            fields: &[TrackEventProtoField::VarInt(
+                TrackEventFieldNumber::ThreadTimeAbsoluteUs as u32,
+                thread_time.as_micros() as u64,
+            )],
+        })
+    }
+
+    /// Add the number of instructions executed by the calling thread, e.g.
+    /// as read from a hardware performance counter.
+    pub fn set_thread_instruction_count(&mut self, count: u64) -> &mut Self {
+        self.set_proto_fields(&TrackEventProtoFields {
+            fields: &[TrackEventProtoField::VarInt(
+                TrackEventFieldNumber::ThreadInstructionCountAbsolute as u32,
+                count,
+            )],
+        })
+    }
+
     /// Add debug arg.
     pub fn add_debug_arg(&mut self, name: &str, arg: TrackEventDebugArg) -> &mut Self {
         use TrackEventDebugArg::*;
@@ -1298,10 +1618,11 @@ impl EventContext {
         self
     }
 
-    /// Add counter value.
-    pub fn set_counter(&mut self, counter: TrackEventCounter) -> &mut Self {
+    /// Add counter value. Accepts a [`TrackEventCounter`] or any value that
+    /// converts into a [`CounterValue`], e.g. `ctx.set_counter(bytes_used)`.
+    pub fn set_counter(&mut self, counter: impl Into<CounterValue>) -> &mut Self {
         use TrackEventCounter::*;
-        match counter {
+        match TrackEventCounter::from(counter.into()) {
             Int64(value) => {
                 let int_counter = PerfettoTeHlExtraCounterInt64 {
                     header: PerfettoTeHlExtra {
@@ -1473,21 +1794,87 @@ impl EventContext {
         ));
         self
     }
+
+    /// Add the source location that emitted the event, e.g. from
+    /// [`track_event_source_location!`](crate::track_event_source_location).
+    ///
+    /// Locations are interned like event names: each distinct location is
+    /// written once per sequence and the events reference it by its iid,
+    /// unless [`set_no_intern`](Self::set_no_intern) is set. Ignored for end
+    /// events.
+    pub fn set_source_location(&mut self, location: &TrackEventSourceLocation) -> &mut Self {
+        let file_name = CString::new(location.file_name).unwrap();
+        let function_name = location
+            .function_name
+            .map(|function_name| CString::new(function_name).unwrap());
+        let source_location = PerfettoTeHlExtraSourceLocation {
+            header: PerfettoTeHlExtra {
+                type_: PerfettoTeHlExtraType_PERFETTO_TE_HL_EXTRA_TYPE_SOURCE_LOCATION,
+            },
+            file_name: file_name.as_ptr(),
+            function_name: function_name
+                .as_ref()
+                .map_or(ptr::null(), |function_name| function_name.as_ptr()),
+            line_number: location.line_number,
+        };
+        self.extras.push(TeHlExtra::SourceLocation(
+            source_location,
+            file_name,
+            function_name,
+        ));
+        self
+    }
+
+    /// Add the location of the caller as the source location of the event,
+    /// see [`set_source_location`](Self::set_source_location).
+    #[track_caller]
+    pub fn set_caller_location(&mut self) -> &mut Self {
+        self.set_source_location(&TrackEventSourceLocation::caller())
+    }
+}
+
+/// Returns the [`TrackEventSourceLocation`] of the macro invocation, with the
+/// module path as the function name.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::{track_event::EventContext, track_event_source_location};
+///
+/// let mut ctx = EventContext::default();
+/// ctx.set_source_location(&track_event_source_location!());
+/// ```
+#[macro_export]
+macro_rules! track_event_source_location {
+    () => {
+        $crate::track_event::TrackEventSourceLocation {
+            file_name: file!(),
+            function_name: Some(module_path!()),
+            line_number: line!(),
+        }
+    };
 }
 
 /// Emits a track event when `category` is enabled. The optional `lambda` is only called
 /// when emitting an event.
+///
+/// Events of disabled categories are written into the ftrace trace marker instead if a
+/// [`TraceMarkerFallback`](crate::trace_marker::TraceMarkerFallback) is installed.
 #[macro_export]
 macro_rules! track_event {
     ($category:literal, $variant:expr) => {{ $crate::track_event!($category, $variant, |_| {}) }};
     ($category:literal, $variant:expr, $lambda:expr) => {{
         const CATEGORY_INDEX: usize = perfetto_te_ns::category_index($category);
-        if $crate::__unlikely!(perfetto_te_ns::is_category_enabled(CATEGORY_INDEX)) {
+        if $crate::__unlikely!(perfetto_te_ns::is_category_active(CATEGORY_INDEX)) {
             let mut ctx = $crate::track_event::EventContext::default();
 
             $lambda(&mut ctx);
 
-            perfetto_te_ns::emit(CATEGORY_INDEX, $variant, &mut ctx);
+            if perfetto_te_ns::is_category_enabled(CATEGORY_INDEX) {
+                perfetto_te_ns::emit(CATEGORY_INDEX, $variant, &mut ctx);
+            } else {
+                $crate::trace_marker::__emit_fallback($category, $variant, &ctx);
+            }
         }
     }};
 }
@@ -1683,7 +2070,11 @@ mod tests {
         name: Option<String>,
         r#type: Option<EventType>,
         counter_value: Option<i64>,
+        thread_time_absolute_us: Option<i64>,
+        thread_instruction_count_absolute: Option<i64>,
         debug_annotations: Vec<DebugAnnotation>,
+        source_location_iid: Option<u64>,
+        source_location: Option<Vec<u8>>,
     }
 
     impl Event {
@@ -1696,6 +2087,11 @@ mod tests {
             const TYPE_ID: u32 = TrackEventFieldNumber::Type as u32;
             const COUNTER_VALUE_ID: u32 = TrackEventFieldNumber::CounterValue as u32;
             const DEBUG_ANNOTATIONS_ID: u32 = TrackEventFieldNumber::DebugAnnotations as u32;
+            const THREAD_TIME_ID: u32 = TrackEventFieldNumber::ThreadTimeAbsoluteUs as u32;
+            const THREAD_INSTRUCTION_COUNT_ID: u32 =
+                TrackEventFieldNumber::ThreadInstructionCountAbsolute as u32;
+            const SOURCE_LOCATION_IID_ID: u32 = TrackEventFieldNumber::SourceLocationIid as u32;
+            const SOURCE_LOCATION_ID: u32 = TrackEventFieldNumber::SourceLocation as u32;
             for field in PbDecoder::new(data) {
                 match field.as_ref().unwrap_or_else(|e| panic!("Error: {}", e)) {
                     (CATEGORY_IIDS_ID, Varint(v)) => event.category_iids = Some(*v),
@@ -1707,9 +2103,15 @@ mod tests {
                         event.r#type = Some(EventType::try_from(*v as u32).unwrap())
                     }
                     (COUNTER_VALUE_ID, Varint(v)) => event.counter_value = Some(*v as i64),
+                    (THREAD_TIME_ID, Varint(v)) => event.thread_time_absolute_us = Some(*v as i64),
+                    (THREAD_INSTRUCTION_COUNT_ID, Varint(v)) => {
+                        event.thread_instruction_count_absolute = Some(*v as i64)
+                    }
                     (DEBUG_ANNOTATIONS_ID, Delimited(v)) => {
                         event.debug_annotations.push(DebugAnnotation::decode(v))
                     }
+                    (SOURCE_LOCATION_IID_ID, Varint(v)) => event.source_location_iid = Some(*v),
+                    (SOURCE_LOCATION_ID, Delimited(v)) => event.source_location = Some(v.to_vec()),
                     _ => println!("WARNING: unknown TrackEvent field: {:?}", field),
                 }
             }
@@ -1794,6 +2196,43 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn category_filtering() -> Result<(), Box<dyn Error>> {
+        use test_te_ns as perfetto_te_ns;
+        let _fx = TeTestFixture::new();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name("track_event")
+            .add_disabled_category("cat1")
+            .build()?;
+        session.start_blocking();
+        assert!(!track_event_category_enabled!("cat2"));
+        session.stop_blocking();
+
+        TrackEvent::set_category_filtering(CategoryFiltering::Cpp);
+        let mut session1 = TracingSessionBuilder::new()
+            .set_data_source_name("track_event")
+            .add_disabled_category("cat1")
+            .build()?;
+        let mut session2 = TracingSessionBuilder::new()
+            .set_data_source_name("track_event")
+            .add_enabled_category("cat1")
+            .add_disabled_category("*")
+            .build()?;
+        session1.start_blocking();
+        session2.start_blocking();
+        track_event_instant!("cat1", "name1");
+        track_event_instant!("cat2", "name2");
+        session1.stop_blocking();
+        session2.stop_blocking();
+        TrackEvent::set_category_filtering(CategoryFiltering::C);
+        let events1 = read_trace_events(&mut session1);
+        let events2 = read_trace_events(&mut session2);
+        assert_eq!(events1.len(), 1);
+        assert_eq!(events2.len(), 1);
+        assert_ne!(events1[0].category_iids, events2[0].category_iids);
+        Ok(())
+    }
+
     #[test]
     fn category_callback() -> Result<(), Box<dyn Error>> {
         use std::sync::{
@@ -1863,6 +2302,46 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn source_location() -> Result<(), Box<dyn Error>> {
+        use test_te_ns as perfetto_te_ns;
+        let _fx = TeTestFixture::new();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name("track_event")
+            .add_enabled_category("cat1")
+            .add_disabled_category("*")
+            .build()?;
+        session.start_blocking();
+        for _ in 0..2 {
+            track_event_instant!("cat1", "name1", |ctx: &mut EventContext| {
+                ctx.set_source_location(&track_event_source_location!());
+            });
+        }
+        track_event_instant!("cat1", "name2", |ctx: &mut EventContext| {
+            ctx.set_caller_location();
+        });
+        track_event_instant!("cat1", "name3", |ctx: &mut EventContext| {
+            ctx.set_no_intern().set_caller_location();
+        });
+        session.stop_blocking();
+        let events = read_trace_events(&mut session);
+        assert_eq!(events.len(), 4);
+        // Events from the same location share its iid.
+        assert!(events[0].source_location_iid.is_some());
+        assert_eq!(events[0].source_location_iid, events[1].source_location_iid);
+        assert!(events[2].source_location_iid.is_some());
+        assert_ne!(events[0].source_location_iid, events[2].source_location_iid);
+        assert_eq!(events[3].source_location_iid, None);
+        let location = events[3].source_location.as_deref().unwrap();
+        use crate::protos::trace::track_event::source_location::SourceLocationFieldNumber;
+        assert!(PbDecoder::new(location).any(|field| {
+            matches!(field, Ok((id, PbDecoderField::Delimited(file_name)))
+                if id == SourceLocationFieldNumber::FileName as u32
+                    && file_name == file!().as_bytes())
+        }));
+        Ok(())
+    }
+
     #[test]
     fn slice() -> Result<(), Box<dyn Error>> {
         use test_te_ns as perfetto_te_ns;
@@ -1885,6 +2364,35 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn slice_with_thread_time() -> Result<(), Box<dyn Error>> {
+        use test_te_ns as perfetto_te_ns;
+        let _fx = TeTestFixture::new();
+        let mut session = TracingSessionBuilder::new()
+            .set_data_source_name("track_event")
+            .add_enabled_category("cat1")
+            .add_disabled_category("*")
+            .build()?;
+        session.start_blocking();
+        track_event_begin!("cat1", "name2", |ctx: &mut EventContext| {
+            ctx.set_thread_time_absolute(Duration::from_micros(100))
+                .set_thread_instruction_count(1000);
+        });
+        track_event_end!("cat1", |ctx: &mut EventContext| {
+            ctx.set_thread_time();
+        });
+        session.stop_blocking();
+        let events = read_trace_events(&mut session);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].thread_time_absolute_us, Some(100));
+        assert_eq!(events[0].thread_instruction_count_absolute, Some(1000));
+        assert_eq!(
+            events[1].thread_time_absolute_us.is_some(),
+            thread_cpu_time().is_some()
+        );
+        Ok(())
+    }
+
     #[test]
     fn counter() -> Result<(), Box<dyn Error>> {
         use test_te_ns as perfetto_te_ns;
@@ -1898,11 +2406,15 @@ mod tests {
         track_event_counter!("cat2", |ctx: &mut EventContext| {
             ctx.set_counter(TrackEventCounter::Int64(56));
         });
+        track_event_counter!("cat2", |ctx: &mut EventContext| {
+            ctx.set_counter(1u64 << 60);
+        });
         session.stop_blocking();
         let events = read_trace_events(&mut session);
-        assert_eq!(events.len(), 1);
+        assert_eq!(events.len(), 2);
         assert_eq!(events[0].r#type, Some(EventType::TypeCounter));
         assert_eq!(events[0].counter_value, Some(56));
+        assert_eq!(events[1].counter_value, Some(1 << 60));
         Ok(())
     }
 
@@ -0,0 +1,219 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::fnv1a;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+// Magic values used to derive track UUIDs that are unlikely to collide with
+// the UUIDs of tracks emitted by the SDK itself.
+const PROCESS_MAGIC: u64 = 0x3b9e6d14a2c75f08;
+const THREAD_MAGIC: u64 = 0x8f41c0b6e53a2d97;
+const CUSTOM_MAGIC: u64 = 0x5d2a7e9f108c46b3;
+
+/// Key of a track in a [`TrackRegistry`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TrackKey {
+    /// Process track of the process `pid`.
+    Process(i32),
+    /// Thread track of the thread `tid` of the process `pid`.
+    Thread {
+        /// Process id.
+        pid: i32,
+        /// Thread id.
+        tid: i32,
+    },
+    /// Track named `name` nested under the track `parent_uuid`, or a global
+    /// track if zero.
+    Custom {
+        /// Name of the track.
+        name: String,
+        /// UUID of the parent track.
+        parent_uuid: u64,
+    },
+}
+
+impl TrackKey {
+    fn derive_uuid(&self) -> u64 {
+        match self {
+            TrackKey::Process(pid) => PROCESS_MAGIC ^ fnv1a(&pid.to_le_bytes()),
+            TrackKey::Thread { pid, tid } => {
+                let mut bytes = [0u8; 8];
+                bytes[..4].copy_from_slice(&pid.to_le_bytes());
+                bytes[4..].copy_from_slice(&tid.to_le_bytes());
+                THREAD_MAGIC ^ fnv1a(&bytes)
+            }
+            TrackKey::Custom { name, parent_uuid } => {
+                CUSTOM_MAGIC ^ parent_uuid ^ fnv1a(name.as_bytes())
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    uuids: HashMap<TrackKey, u64>,
+    keys: HashMap<u64, TrackKey>,
+    sequence_ids: HashMap<String, u32>,
+}
+
+/// Registry of the track UUIDs and packet sequence ids used by a process.
+///
+/// Track UUIDs are derived from their [`TrackKey`], so that the same track
+/// gets the same UUID in all the modules of a process, and two tracks never
+/// get the same UUID: on a hash collision, the UUID of the new track is
+/// rehashed until it is unique in the registry. UUIDs are stable unless
+/// there is a collision, which makes them depend on the order in which the
+/// tracks are registered.
+///
+/// Packet sequence ids are allocated sequentially by name, for packets that
+/// are written without the tracing service, e.g. to a trace file, which
+/// assigns the trusted ids of the packets of its producers itself.
+///
+/// Most modules should share the [`global`](Self::global) registry.
+///
+/// Example:
+///
+/// ```
+/// use perfetto_sdk::track_registry::TrackRegistry;
+///
+/// let registry = TrackRegistry::global();
+/// let process_uuid = registry.process_track_uuid(1234);
+/// let thread_uuid = registry.thread_track_uuid(1234, 1235);
+/// assert_ne!(process_uuid, thread_uuid);
+/// let queue_uuid = registry.custom_track_uuid("queue", process_uuid);
+/// assert_eq!(queue_uuid, registry.custom_track_uuid("queue", process_uuid));
+/// ```
+#[derive(Default)]
+pub struct TrackRegistry {
+    inner: Mutex<Inner>,
+}
+
+impl TrackRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the registry shared by all the modules of the process.
+    pub fn global() -> &'static TrackRegistry {
+        static REGISTRY: OnceLock<TrackRegistry> = OnceLock::new();
+        REGISTRY.get_or_init(TrackRegistry::new)
+    }
+
+    /// Returns the UUID of the track `key`, registering it if needed.
+    pub fn track_uuid(&self, key: &TrackKey) -> u64 {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(uuid) = inner.uuids.get(key) {
+            return *uuid;
+        }
+        let mut uuid = key.derive_uuid();
+        // Zero is the UUID of the root track.
+        while uuid == 0 || inner.keys.contains_key(&uuid) {
+            uuid = fnv1a(&uuid.to_le_bytes());
+        }
+        inner.uuids.insert(key.clone(), uuid);
+        inner.keys.insert(uuid, key.clone());
+        uuid
+    }
+
+    /// Returns the UUID of the process track of `pid`.
+    pub fn process_track_uuid(&self, pid: i32) -> u64 {
+        self.track_uuid(&TrackKey::Process(pid))
+    }
+
+    /// Returns the UUID of the thread track of `tid` in the process `pid`.
+    pub fn thread_track_uuid(&self, pid: i32, tid: i32) -> u64 {
+        self.track_uuid(&TrackKey::Thread { pid, tid })
+    }
+
+    /// Returns the UUID of the track named `name` nested under
+    /// `parent_uuid`, or a global track if zero.
+    pub fn custom_track_uuid(&self, name: &str, parent_uuid: u64) -> u64 {
+        self.track_uuid(&TrackKey::Custom {
+            name: name.to_string(),
+            parent_uuid,
+        })
+    }
+
+    /// Returns the key of the track with the UUID `uuid`, if registered.
+    pub fn track_key(&self, uuid: u64) -> Option<TrackKey> {
+        self.inner.lock().unwrap().keys.get(&uuid).cloned()
+    }
+
+    /// Returns the packet sequence id of the sequence `name`, allocating it
+    /// if needed. Ids start at 1.
+    pub fn packet_sequence_id(&self, name: &str) -> u32 {
+        let mut inner = self.inner.lock().unwrap();
+        let next_id = inner.sequence_ids.len() as u32 + 1;
+        *inner
+            .sequence_ids
+            .entry(name.to_string())
+            .or_insert(next_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_uuids() {
+        let registry = TrackRegistry::new();
+        let process = registry.process_track_uuid(10);
+        let thread = registry.thread_track_uuid(10, 11);
+        let custom = registry.custom_track_uuid("queue", process);
+        assert_eq!(process, registry.process_track_uuid(10));
+        assert_eq!(thread, registry.thread_track_uuid(10, 11));
+        assert_eq!(custom, registry.custom_track_uuid("queue", process));
+        assert_ne!(process, registry.process_track_uuid(20));
+        assert_ne!(custom, registry.custom_track_uuid("queue", thread));
+        assert_eq!(
+            registry.track_key(thread),
+            Some(TrackKey::Thread { pid: 10, tid: 11 })
+        );
+        assert_eq!(registry.track_key(1), None);
+        // UUIDs only depend on the keys unless they collide.
+        assert_eq!(process, TrackRegistry::new().process_track_uuid(10));
+    }
+
+    #[test]
+    fn collisions() {
+        let registry = TrackRegistry::new();
+        let key = TrackKey::Custom {
+            name: "a".to_string(),
+            parent_uuid: 0,
+        };
+        // A parent UUID that makes both keys hash to the same UUID.
+        let colliding = TrackKey::Custom {
+            name: "b".to_string(),
+            parent_uuid: fnv1a(b"a") ^ fnv1a(b"b"),
+        };
+        assert_eq!(key.derive_uuid(), colliding.derive_uuid());
+        let uuid = registry.track_uuid(&key);
+        let colliding_uuid = registry.track_uuid(&colliding);
+        assert_ne!(uuid, colliding_uuid);
+        assert_eq!(registry.track_key(colliding_uuid), Some(colliding));
+    }
+
+    #[test]
+    fn packet_sequence_ids() {
+        let registry = TrackRegistry::new();
+        assert_eq!(registry.packet_sequence_id("import"), 1);
+        assert_eq!(registry.packet_sequence_id("relay"), 2);
+        assert_eq!(registry.packet_sequence_id("import"), 1);
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    sync::{Condvar, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+/// Action taken by a [`Watchdog`] when a callback exceeds its deadline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WatchdogAction {
+    /// Log a warning and let the callback run.
+    #[default]
+    Log,
+    /// Log an error and abort the process.
+    Abort,
+}
+
+/// Deadline of the callbacks of a data source, see
+/// [`DataSourceArgsBuilder::watchdog`](crate::data_source::DataSourceArgsBuilder::watchdog).
+///
+/// Callbacks run on the thread of the producer connection, so a callback that
+/// blocks wedges all the data sources of the process. The watchdog runs on a
+/// thread of its own, and takes its action once a callback has run for
+/// longer than the deadline. It can't interrupt the callback.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchdog {
+    /// Time a callback can run for.
+    pub deadline: Duration,
+    /// Action taken when a callback exceeds the deadline.
+    pub action: WatchdogAction,
+}
+
+impl Watchdog {
+    /// Watchdog that logs a warning for callbacks that exceed `deadline`.
+    pub fn log(deadline: Duration) -> Self {
+        Self {
+            deadline,
+            action: WatchdogAction::Log,
+        }
+    }
+
+    /// Watchdog that aborts the process when a callback exceeds `deadline`.
+    pub fn abort(deadline: Duration) -> Self {
+        Self {
+            deadline,
+            action: WatchdogAction::Abort,
+        }
+    }
+
+    /// Watches the callback described by `description` until the returned
+    /// guard is dropped. `on_expired` is called on the watchdog thread if the
+    /// deadline is exceeded.
+    pub(crate) fn arm<F>(&self, description: String, on_expired: F) -> WatchdogGuard
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let watched = watched();
+        let mut state = watched.state.lock().unwrap();
+        if !state.running {
+            // The watchdog thread is never joined.
+            let spawned = crate::platform::spawn("perfetto-watchdog", run);
+            if let Err(err) = spawned {
+                crate::sdk_log!(Error, "Failed to start watchdog thread: {}", err);
+                return WatchdogGuard { id: None };
+            }
+            state.running = true;
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        state.armed.insert(
+            id,
+            Armed {
+                start: Instant::now(),
+                deadline: self.deadline,
+                action: self.action,
+                description,
+                on_expired: Box::new(on_expired),
+            },
+        );
+        watched.condvar.notify_one();
+        WatchdogGuard { id: Some(id) }
+    }
+}
+
+// Callback being watched.
+struct Armed {
+    start: Instant,
+    deadline: Duration,
+    action: WatchdogAction,
+    description: String,
+    on_expired: Box<dyn FnOnce() + Send>,
+}
+
+#[derive(Default)]
+struct State {
+    armed: HashMap<u64, Armed>,
+    next_id: u64,
+    running: bool,
+}
+
+#[derive(Default)]
+struct Watched {
+    state: Mutex<State>,
+    condvar: Condvar,
+}
+
+fn watched() -> &'static Watched {
+    static WATCHED: OnceLock<Watched> = OnceLock::new();
+    WATCHED.get_or_init(Watched::default)
+}
+
+// Body of the watchdog thread, which runs until the process exits.
+fn run() {
+    let watched = watched();
+    let mut state = watched.state.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        let expired: Vec<u64> = state
+            .armed
+            .iter()
+            .filter(|(_, armed)| now >= armed.start + armed.deadline)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in expired {
+            let armed = state.armed.remove(&id).unwrap();
+            match armed.action {
+                WatchdogAction::Log => crate::sdk_log!(
+                    Warn,
+                    "{} has been running for more than {:?}",
+                    armed.description,
+                    armed.deadline
+                ),
+                WatchdogAction::Abort => {
+                    crate::__sdk_fatal!(
+                        "{} has been running for more than {:?}, aborting",
+                        armed.description,
+                        armed.deadline
+                    );
+                }
+            }
+            (armed.on_expired)();
+        }
+        let next = state
+            .armed
+            .values()
+            .map(|armed| armed.start + armed.deadline)
+            .min();
+        state = match next {
+            Some(next) => {
+                let timeout = next.saturating_duration_since(Instant::now());
+                watched.condvar.wait_timeout(state, timeout).unwrap().0
+            }
+            None => watched.condvar.wait(state).unwrap(),
+        };
+    }
+}
+
+/// Guard of a watched callback, which stops watching it once dropped.
+pub(crate) struct WatchdogGuard {
+    id: Option<u64>,
+}
+
+impl Drop for WatchdogGuard {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            watched().state.lock().unwrap().armed.remove(&id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicU32, Ordering},
+        },
+        thread,
+    };
+
+    #[test]
+    fn expired_callbacks() {
+        let expired = Arc::new(AtomicU32::new(0));
+        let watchdog = Watchdog::log(Duration::from_millis(20));
+        let on_expired = Arc::clone(&expired);
+        {
+            let _guard = watchdog.arm("Fast callback".to_string(), move || {
+                on_expired.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        let on_expired = Arc::clone(&expired);
+        {
+            let _guard = watchdog.arm("Slow callback".to_string(), move || {
+                on_expired.fetch_add(1, Ordering::Relaxed);
+            });
+            thread::sleep(Duration::from_millis(200));
+        }
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(expired.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,23 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Plugin built against another version of the SDK, which the tests of
+// `Plugin` fail to load.
+
+use std::ffi::c_char;
+
+#[unsafe(no_mangle)]
+pub extern "C" fn perfetto_sdk_plugin_version() -> *const c_char {
+    c"0.0.0".as_ptr()
+}
@@ -0,0 +1,32 @@
+// Copyright (C) 2025 Rivos Inc.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Plugin loaded by the tests of `Plugin`. It doesn't trace, as it links its
+// own copy of the vendored C library, which isn't initialized.
+
+use perfetto_sdk::{
+    data_source::{DataSourceArgsBuilder, DataSourceError},
+    perfetto_plugin,
+    plugin::PluginRegistrar,
+};
+
+fn register(registrar: &mut PluginRegistrar) -> Result<(), DataSourceError> {
+    registrar.register(
+        "com.example.plugin_fixture",
+        DataSourceArgsBuilder::new().build(),
+    )?;
+    Ok(())
+}
+
+perfetto_plugin!(register);
@@ -20,9 +20,13 @@
 )]
 
 use std::ffi::CString;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{self, ThreadId};
 
 use perfetto_sdk::producer::{Backends, Producer, ProducerInitArgsBuilder};
-use perfetto_sdk::track_event::{EventContext, TrackEvent, TrackEventDebugArg, TrackEventType};
+use perfetto_sdk::track_event::{
+    EventContext, TrackEvent, TrackEventDebugArg, TrackEventFlow, TrackEventTrack, TrackEventType,
+};
 use tracing_core::span;
 use tracing_subscriber::Layer;
 use tracing_subscriber::layer::Context;
@@ -65,10 +69,24 @@ fn init_with_backends(backends: Backends) {
     perfetto_te_ns::register().ok();
 }
 
+// Flow ids are allocated per span, as span ids are reused once closed.
+static NEXT_FLOW_ID: AtomicU64 = AtomicU64::new(1);
+
 /// Per-span data stored in tracing-subscriber's Extensions.
 struct SpanData {
     name: CString,
     fields: Vec<(&'static str, FieldValue)>,
+    flow_id: u64,
+    // Flows of other spans that end at the next entry of the span.
+    incoming_flows: Vec<u64>,
+    // Thread that last entered the span, or `None` until it is entered.
+    last_thread: Option<ThreadId>,
+}
+
+impl SpanData {
+    fn name(&self) -> &str {
+        self.name.to_str().unwrap_or_default()
+    }
 }
 
 enum FieldValue {
@@ -83,8 +101,16 @@ enum FieldValue {
 ///
 /// Spans become duration slices (begin/end) and events become instant
 /// events, all routed through the Perfetto SDK's track event system.
+///
+/// The slices of a span are connected by a flow, so that a span entered
+/// several times, e.g. an async task polled on different threads, renders as
+/// connected arrows. A flow also connects a span to the spans it
+/// `follows_from`, and to its parent if it is first entered on another thread
+/// than the one that last entered the parent.
 pub struct PerfettoLayer {
     debug_annotations: bool,
+    flows: bool,
+    async_tracks: bool,
 }
 
 impl PerfettoLayer {
@@ -92,6 +118,8 @@ impl PerfettoLayer {
     pub fn new() -> Self {
         Self {
             debug_annotations: true,
+            flows: true,
+            async_tracks: false,
         }
     }
 
@@ -102,8 +130,25 @@ impl PerfettoLayer {
     pub fn without_debug_annotations() -> Self {
         Self {
             debug_annotations: false,
+            ..Self::new()
         }
     }
+
+    /// Don't connect the slices of spans with flows.
+    #[must_use = "Builder methods return an updated layer; use the returned value or keep chaining."]
+    pub fn without_flows(mut self) -> Self {
+        self.flows = false;
+        self
+    }
+
+    /// Also emit a slice for the lifetime of each span, from its first entry
+    /// to its close, on an async track of its own. Useful for async tasks,
+    /// whose spans are entered once per poll.
+    #[must_use = "Builder methods return an updated layer; use the returned value or keep chaining."]
+    pub fn with_async_tracks(mut self) -> Self {
+        self.async_tracks = true;
+        self
+    }
 }
 
 impl Default for PerfettoLayer {
@@ -126,27 +171,36 @@ fn add_debug_args(ctx: &mut EventContext, fields: &[(&'static str, FieldValue)])
 }
 
 fn add_source_location(ctx: &mut EventContext, meta: &tracing_core::Metadata<'_>) {
-    use perfetto_sdk::protos::trace::track_event::source_location::SourceLocationFieldNumber;
-    use perfetto_sdk::protos::trace::track_event::track_event::TrackEventFieldNumber;
-    use perfetto_sdk::track_event::{TrackEventProtoField, TrackEventProtoFields};
-
-    let file = meta.file().unwrap_or("");
-    let line = meta.line().unwrap_or(0);
-
-    ctx.set_proto_fields(&TrackEventProtoFields {
-        fields: &[TrackEventProtoField::Nested(
-            TrackEventFieldNumber::SourceLocation as u32,
-            &[
-                TrackEventProtoField::Cstr(SourceLocationFieldNumber::FileName as u32, file),
-                TrackEventProtoField::VarInt(
-                    SourceLocationFieldNumber::LineNumber as u32,
-                    line as u64,
-                ),
-            ],
-        )],
+    use perfetto_sdk::track_event::TrackEventSourceLocation;
+
+    ctx.set_source_location(&TrackEventSourceLocation {
+        file_name: meta.file().unwrap_or(""),
+        function_name: meta.module_path(),
+        line_number: meta.line().unwrap_or(0),
     });
 }
 
+fn add_async_track(ctx: &mut EventContext, data: &SpanData) {
+    ctx.set_named_track_with_dynamic_name(
+        data.name(),
+        data.flow_id,
+        TrackEventTrack::process_track_uuid(),
+    );
+}
+
+// Returns the flow id of the span of `span`, and the thread that last
+// entered it.
+fn flow_and_thread<S>(
+    span: &tracing_subscriber::registry::SpanRef<'_, S>,
+) -> Option<(u64, Option<ThreadId>)>
+where
+    S: for<'a> LookupSpan<'a>,
+{
+    let extensions = span.extensions();
+    let data = extensions.get::<SpanData>()?;
+    Some((data.flow_id, data.last_thread))
+}
+
 struct FieldVisitor {
     fields: Vec<(&'static str, FieldValue)>,
 }
@@ -201,7 +255,28 @@ where
             Vec::new()
         };
 
-        span.extensions_mut().insert(SpanData { name, fields });
+        span.extensions_mut().insert(SpanData {
+            name,
+            fields,
+            flow_id: NEXT_FLOW_ID.fetch_add(1, Ordering::Relaxed),
+            incoming_flows: Vec::new(),
+            last_thread: None,
+        });
+    }
+
+    fn on_follows_from(&self, id: &span::Id, follows: &span::Id, ctx: Context<'_, S>) {
+        if !self.flows {
+            return;
+        }
+        let (Some(span), Some(follows)) = (ctx.span(id), ctx.span(follows)) else {
+            return;
+        };
+        let Some((flow_id, _)) = flow_and_thread(&follows) else {
+            return;
+        };
+        if let Some(data) = span.extensions_mut().get_mut::<SpanData>() {
+            data.incoming_flows.push(flow_id);
+        }
     }
 
     fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
@@ -219,14 +294,36 @@ where
 
     fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
         let span = ctx.span(id).expect("span not found");
-        let extensions = span.extensions();
-        let Some(data) = extensions.get::<SpanData>() else {
+        let parent = span.parent().as_ref().and_then(flow_and_thread);
+        let mut extensions = span.extensions_mut();
+        let Some(data) = extensions.get_mut::<SpanData>() else {
             return;
         };
+        let thread = thread::current().id();
+        let first_entry = data.last_thread.is_none();
+        data.last_thread = Some(thread);
+        if self.flows
+            && first_entry
+            && let Some((parent_flow_id, Some(parent_thread))) = parent
+            && parent_thread != thread
+        {
+            data.incoming_flows.push(parent_flow_id);
+        }
+        let incoming_flows = std::mem::take(&mut data.incoming_flows);
+        let data = &*data;
+
         let name_ptr = data.name.as_ptr();
         let fields = &data.fields;
         let meta = span.metadata();
         let debug_annotations = self.debug_annotations;
+        let flows = self.flows;
+        if self.async_tracks && first_entry {
+            perfetto_sdk::track_event!(
+                "tracing",
+                TrackEventType::SliceBegin(name_ptr),
+                |ctx: &mut EventContext| add_async_track(ctx, data)
+            );
+        }
         perfetto_sdk::track_event!(
             "tracing",
             TrackEventType::SliceBegin(name_ptr),
@@ -235,6 +332,12 @@ where
                 if debug_annotations && !fields.is_empty() {
                     add_debug_args(ctx, fields);
                 }
+                if flows {
+                    ctx.set_flow(&TrackEventFlow::process_scoped_flow(data.flow_id));
+                    for flow_id in &incoming_flows {
+                        ctx.set_terminating_flow(&TrackEventFlow::process_scoped_flow(*flow_id));
+                    }
+                }
             }
         );
     }
@@ -243,6 +346,26 @@ where
         perfetto_sdk::track_event!("tracing", TrackEventType::SliceEnd);
     }
 
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        if !self.async_tracks {
+            return;
+        }
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let extensions = span.extensions();
+        let Some(data) = extensions.get::<SpanData>() else {
+            return;
+        };
+        if data.last_thread.is_some() {
+            perfetto_sdk::track_event!(
+                "tracing",
+                TrackEventType::SliceEnd,
+                |ctx: &mut EventContext| add_async_track(ctx, data)
+            );
+        }
+    }
+
     fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
         let meta = event.metadata();
         let name = CString::new(meta.name()).unwrap_or_default();
@@ -356,6 +479,49 @@ mod tests {
         });
     }
 
+    #[test]
+    fn follows_from_spans() {
+        init();
+        let subscriber = tracing_subscriber::registry().with(PerfettoLayer::new());
+        tracing::subscriber::with_default(subscriber, || {
+            let cause = tracing::info_span!("cause");
+            cause.in_scope(|| {});
+            let effect = tracing::info_span!("effect");
+            effect.follows_from(&cause);
+            effect.in_scope(|| {});
+        });
+    }
+
+    #[test]
+    fn cross_thread_spans() {
+        init();
+        let dispatch = tracing::Dispatch::new(
+            tracing_subscriber::registry().with(PerfettoLayer::new().with_async_tracks()),
+        );
+        tracing::dispatcher::with_default(&dispatch, || {
+            let parent = tracing::info_span!("parent");
+            parent.in_scope(|| {});
+            let task = tracing::info_span!(parent: &parent, "task");
+            for _ in 0..2 {
+                let task = task.clone();
+                let dispatch = dispatch.clone();
+                std::thread::spawn(move || {
+                    tracing::dispatcher::with_default(&dispatch, || task.in_scope(|| {}));
+                })
+                .join()
+                .unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn without_flows() {
+        init();
+        let layer = PerfettoLayer::new().without_flows();
+        assert!(!layer.flows);
+        assert!(!layer.async_tracks);
+    }
+
     #[test]
     fn no_annotations_mode() {
         init();
@@ -143,6 +143,12 @@ class PERFETTO_EXPORT_COMPONENT ProducerEndpoint {
   // DataSourceDescriptor.will_notify_on_stop.
   virtual void NotifyDataSourceStopped(DataSourceInstanceID) = 0;
 
+  // Called in response to a Producer::SetupDataSource(), if the data source
+  // failed to set up, e.g. because it rejected its config. |error| is a human
+  // readable reason of the failure.
+  virtual void NotifyDataSourceSetupFailed(DataSourceInstanceID,
+                                           const std::string& error) = 0;
+
   // This informs the service to activate any of these triggers if any tracing
   // session was waiting for them.
   virtual void ActivateTriggers(const std::vector<std::string>&) = 0;
@@ -438,7 +444,8 @@ class PERFETTO_EXPORT_COMPONENT TracingService {
       size_t shared_memory_page_size_hint_bytes = 0,
       std::unique_ptr<SharedMemory> shm = nullptr,
       const std::string& sdk_version = {},
-      const std::string& machine_name = {}) = 0;
+      const std::string& machine_name = {},
+      bool use_shmem_emulation = false) = 0;
 
   // Connects a Consumer instance and obtains a ConsumerEndpoint, which is
   // essentially a 1:1 channel between one Consumer and the Service.
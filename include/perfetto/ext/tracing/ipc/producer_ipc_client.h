@@ -79,7 +79,9 @@ class PERFETTO_EXPORT_COMPONENT ProducerIPCClient {
       ConnectionFlags = ConnectionFlags::kDefault);
 
   // Overload of Connect() to support adopting a connected socket using
-  // ipc::Client::ConnArgs.
+  // ipc::Client::ConnArgs. If |use_shmem_emulation| is true, chunk data is
+  // committed over the socket even if the service provides a shared memory
+  // buffer, e.g. for sandboxed producers that can't map it.
   static std::unique_ptr<TracingService::ProducerEndpoint> Connect(
       ipc::Client::ConnArgs,
       Producer*,
@@ -91,7 +93,8 @@ class PERFETTO_EXPORT_COMPONENT ProducerIPCClient {
       size_t shared_memory_page_size_hint_bytes = 0,
       std::unique_ptr<SharedMemory> shm = nullptr,
       std::unique_ptr<SharedMemoryArbiter> shm_arbiter = nullptr,
-      CreateSocketAsync create_socket_async = nullptr);
+      CreateSocketAsync create_socket_async = nullptr,
+      bool use_shmem_emulation = false);
 
  protected:
   ProducerIPCClient() = delete;
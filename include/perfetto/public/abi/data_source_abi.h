@@ -50,10 +50,17 @@ typedef uint32_t PerfettoDsInstanceIndex;
 // PerfettoDsImplRegister().
 PERFETTO_SDK_EXPORT struct PerfettoDsImpl* PerfettoDsImplCreate(void);
 
-// Opaque handle used to perform operations from the OnSetup callback. Unused
-// for now.
+// Opaque handle used to perform operations from the OnSetup callback.
 struct PerfettoDsOnSetupArgs;
 
+// Reports that the data source instance failed to set up, with a
+// null-terminated `error` message. The tracing service counts the failure in
+// the trace stats of the session. Can only be called from the OnSetup
+// callback, with its `args`.
+PERFETTO_SDK_EXPORT void PerfettoDsOnSetupArgsSetError(
+    struct PerfettoDsOnSetupArgs*,
+    const char* error);
+
 // Called when a data source instance of a specific type is created. `ds_config`
 // points to a serialized perfetto.protos.DataSourceConfig message,
 // `ds_config_size` bytes long. `user_arg` is the value passed to
@@ -139,6 +146,12 @@ struct PerfettoDsAsyncFlusher;
 PERFETTO_SDK_EXPORT struct PerfettoDsAsyncFlusher*
 PerfettoDsOnFlushArgsPostpone(struct PerfettoDsOnFlushArgs*);
 
+// Returns true if the tracing service requested the flush because it started
+// cloning the tracing session of the data source instance. The data written
+// before the flush is acknowledged ends up in the clone.
+PERFETTO_SDK_EXPORT bool PerfettoDsOnFlushArgsIsClone(
+    struct PerfettoDsOnFlushArgs*);
+
 // Tells the tracing service that the flush operation is complete for a data
 // source instance (whose stop operation was previously postponed with
 // PerfettoDsOnFlushArgsPostpone).
@@ -371,6 +384,12 @@ PERFETTO_SDK_EXPORT void PerfettoDsTracerImplPacketEnd(
     struct PerfettoDsTracerImpl* tracer,
     struct PerfettoStreamWriter* writer);
 
+// Returns true if the data written to `tracer` is currently dropped, e.g.
+// because the shared memory buffer is exhausted. A packet that is still
+// being written when this returns true is lost.
+PERFETTO_SDK_EXPORT bool PerfettoDsTracerImplIsDropping(
+    struct PerfettoDsTracerImpl* tracer);
+
 // Called when a flush request is complete.
 typedef void (*PerfettoDsTracerOnFlushCb)(void* user_arg);
 
@@ -17,6 +17,7 @@
 #ifndef INCLUDE_PERFETTO_PUBLIC_ABI_PRODUCER_ABI_H_
 #define INCLUDE_PERFETTO_PUBLIC_ABI_PRODUCER_ABI_H_
 
+#include <stdbool.h>
 #include <stdint.h>
 
 #include "perfetto/public/abi/export.h"
@@ -43,6 +44,29 @@ PERFETTO_SDK_EXPORT void PerfettoProducerBackendInitArgsSetShmemSizeHintKb(
     struct PerfettoProducerBackendInitArgs*,
     uint32_t size);
 
+// Specifies the preferred size of each page in the shared memory buffer. This
+// is a trade-off between IPC overhead and fragmentation/efficiency of the
+// shared memory buffer in presence of multiple writer threads.
+// Must be one of [4, 8, 16, 32].
+PERFETTO_SDK_EXPORT void PerfettoProducerBackendInitArgsSetShmemPageSizeHintKb(
+    struct PerfettoProducerBackendInitArgs*,
+    uint32_t size);
+
+// Sets the period during which filled shared memory buffer chunks are batched
+// before the service is notified of them. If the shared memory buffer gets too
+// full while this period lasts, the service is notified immediately.
+PERFETTO_SDK_EXPORT void
+PerfettoProducerBackendInitArgsSetShmemBatchCommitsDurationMs(
+    struct PerfettoProducerBackendInitArgs*,
+    uint32_t duration_ms);
+
+// Enables direct producer-side patching of chunks that have not yet been
+// committed to the service. Ignored if the service doesn't support it.
+PERFETTO_SDK_EXPORT void
+PerfettoProducerBackendInitArgsSetShmemDirectPatchingEnabled(
+    struct PerfettoProducerBackendInitArgs*,
+    bool enabled);
+
 // Sets the machine id this process's trace data is attributed to. Only honored
 // by the in-process backend (PerfettoProducerInProcessInit); ignored by the
 // system backend. Lets separate in-process traces be recorded under distinct
@@ -51,6 +75,46 @@ PERFETTO_SDK_EXPORT void PerfettoProducerBackendInitArgsSetMachineId(
     struct PerfettoProducerBackendInitArgs*,
     uint32_t machine_id);
 
+// Sets the name of the socket used by the system backend to connect to the
+// tracing service, overriding the default (or PERFETTO_PRODUCER_SOCK_NAME).
+// Supports the same formats, e.g. "vsock://2:10001" to connect to a tracing
+// service running on the host of a virtual machine. Ignored by the in-process
+// backend.
+PERFETTO_SDK_EXPORT void PerfettoProducerBackendInitArgsSetProducerSocketName(
+    struct PerfettoProducerBackendInitArgs*,
+    const char* name);
+
+// Makes the system backend commit trace data over the producer socket instead
+// of mapping the shared memory buffer provided by the service, e.g. for
+// sandboxed processes that can't map it. The system backend also falls back to
+// this mode if mapping the buffer fails. Ignored by the in-process backend.
+PERFETTO_SDK_EXPORT void PerfettoProducerBackendInitArgsSetShmemEmulationEnabled(
+    struct PerfettoProducerBackendInitArgs*,
+    bool enabled);
+
+// Called by the system backend to create the socket connected to the tracing
+// service socket `socket_name`, instead of connecting to it directly, e.g. in
+// environments with their own socket primitives. The callback must call
+// PerfettoProducerCreateSocketDone() with `done_arg` once the socket is
+// connected, or failed to connect. Called on the internal thread of the SDK.
+typedef void (*PerfettoProducerCreateSocketCb)(const char* socket_name,
+                                               void* user_arg,
+                                               void* done_arg);
+
+// Makes the system backend create its connection to the tracing service with
+// `cb`. Only supported on POSIX platforms. Ignored by the in-process backend.
+PERFETTO_SDK_EXPORT void PerfettoProducerBackendInitArgsSetCreateSocketCb(
+    struct PerfettoProducerBackendInitArgs*,
+    PerfettoProducerCreateSocketCb cb,
+    void* user_arg);
+
+// Completes a call to a PerfettoProducerCreateSocketCb with the connected
+// socket `fd`, whose ownership is transferred to the SDK, or -1 if the socket
+// couldn't be connected, in which case the backend calls the
+// PerfettoProducerCreateSocketCb again later, with backoff.
+PERFETTO_SDK_EXPORT void PerfettoProducerCreateSocketDone(void* done_arg,
+                                                          int fd);
+
 PERFETTO_SDK_EXPORT void PerfettoProducerBackendInitArgsDestroy(
     struct PerfettoProducerBackendInitArgs*);
 
@@ -87,6 +151,60 @@ PERFETTO_SDK_EXPORT void PerfettoProducerActivateTriggers(
     const char* trigger_names[],
     uint32_t ttl_ms);
 
+// Returns how many times, since the process started, a writer had to stall
+// because the shared memory buffer was full.
+PERFETTO_SDK_EXPORT uint64_t PerfettoProducerGetStalledChunkRequests(void);
+
+// Returns how many times, since the process started, a writer dropped data
+// because the shared memory buffer was full.
+PERFETTO_SDK_EXPORT uint64_t PerfettoProducerGetDroppedChunkRequests(void);
+
+// Same as PerfettoProducerGetStalledChunkRequests(), for the writers of the
+// calling thread only. Only counted while enabled by
+// PerfettoProducerSetThreadChunkRequestStatsEnabled().
+PERFETTO_SDK_EXPORT uint64_t
+PerfettoProducerGetThreadStalledChunkRequests(void);
+
+// Same as PerfettoProducerGetDroppedChunkRequests(), for the writers of the
+// calling thread only. Only counted while enabled by
+// PerfettoProducerSetThreadChunkRequestStatsEnabled().
+PERFETTO_SDK_EXPORT uint64_t
+PerfettoProducerGetThreadDroppedChunkRequests(void);
+
+// Enables or disables counting the stalled and dropped chunk requests of each
+// thread. Disabled by default.
+PERFETTO_SDK_EXPORT void PerfettoProducerSetThreadChunkRequestStatsEnabled(
+    bool enabled);
+
+// Makes the writers of the calling thread wait for a free chunk when the
+// shared memory buffer is full, regardless of the buffer exhausted policy of
+// their data source, until `timeout_ns` from now have passed. After that they
+// drop data, until PerfettoProducerClearThreadStallTimeout() is called.
+PERFETTO_SDK_EXPORT void PerfettoProducerSetThreadStallTimeout(
+    uint64_t timeout_ns);
+
+// Restores the buffer exhausted policy of the writers of the calling thread,
+// see PerfettoProducerSetThreadStallTimeout().
+PERFETTO_SDK_EXPORT void PerfettoProducerClearThreadStallTimeout(void);
+
+// Opaque handle to a tracing service running in the current process.
+struct PerfettoService;
+
+// Starts a tracing service on a dedicated thread of the current process, like
+// the `traced` daemon, listening for producers on `producer_socket` and for
+// consumers on `consumer_socket`. The names support the same formats as
+// `PerfettoProducerBackendInitArgsSetProducerSocketName()`. A NULL name selects
+// the platform default socket.
+//
+// Returns NULL if the service couldn't be started, e.g. if a socket is already
+// in use.
+PERFETTO_SDK_EXPORT struct PerfettoService* PerfettoServiceStart(
+    const char* producer_socket,
+    const char* consumer_socket);
+
+// Stops the tracing service and destroys `service`.
+PERFETTO_SDK_EXPORT void PerfettoServiceDestroy(struct PerfettoService* service);
+
 #ifdef __cplusplus
 }
 #endif
@@ -41,6 +41,16 @@ PERFETTO_SDK_EXPORT void PerfettoTracingSessionSetup(
     void* cfg_begin,
     size_t cfg_len);
 
+// Like PerfettoTracingSessionSetup(), but the tracing service periodically
+// writes the trace into the file descriptor `fd` instead of keeping it in its
+// buffers until it is read. `write_into_file` is set in the config. The session
+// takes ownership of `fd`.
+PERFETTO_SDK_EXPORT void PerfettoTracingSessionSetupWithFd(
+    struct PerfettoTracingSessionImpl*,
+    void* cfg_begin,
+    size_t cfg_len,
+    int fd);
+
 typedef void (*PerfettoTracingSessionStopCb)(struct PerfettoTracingSessionImpl*,
                                              void* user_arg);
 
@@ -97,6 +107,46 @@ PERFETTO_SDK_EXPORT bool PerfettoTracingSessionFlushBlocking(
     struct PerfettoTracingSessionImpl*,
     uint32_t timeout_ms);
 
+// Detaches the started tracing session, which keeps running in the tracing
+// service until a session attaches to it with the same `key`; see
+// PerfettoTracingSessionAttachBlocking(). Returns false if the session isn't
+// started or another session was detached with the same key.
+PERFETTO_SDK_EXPORT bool PerfettoTracingSessionDetachBlocking(
+    struct PerfettoTracingSessionImpl*,
+    const char* key);
+
+// Attaches a session that wasn't set up to the tracing session detached with
+// the same `key`, which can then be stopped, flushed and read. Returns false if
+// there is no such session.
+PERFETTO_SDK_EXPORT bool PerfettoTracingSessionAttachBlocking(
+    struct PerfettoTracingSessionImpl*,
+    const char* key);
+
+// Clones the tracing session whose config has the `unique_session_name`, and
+// attaches a session that wasn't set up to the read-only clone, which can then
+// be read. The data sources of the cloned session are flushed first. Returns
+// false if the session couldn't be cloned, e.g. because there is no such
+// session.
+PERFETTO_SDK_EXPORT bool PerfettoTracingSessionCloneBlocking(
+    struct PerfettoTracingSessionImpl*,
+    const char* unique_session_name);
+
+// Called back with an encoded proto message queried from the tracing service.
+typedef void (*PerfettoTracingSessionQueryCb)(
+    struct PerfettoTracingSessionImpl*,
+    const void* data,
+    size_t size,
+    void* user_arg);
+
+// Queries the statistics of the tracing session and calls `cb` with the
+// encoded `perfetto.protos.TraceStats` message. Returns false, without calling
+// `cb`, if the query failed, e.g. because the session isn't set up. `user_arg`
+// is passed as is to the callback.
+PERFETTO_SDK_EXPORT bool PerfettoTracingSessionGetTraceStatsBlocking(
+    struct PerfettoTracingSessionImpl*,
+    PerfettoTracingSessionQueryCb cb,
+    void* user_arg);
+
 // Called back to read pieces of tracing data. `data` points to a chunk of trace
 // data, `size` bytes long. `has_more` is true if there is more tracing data and
 // the callback will be invoked again.
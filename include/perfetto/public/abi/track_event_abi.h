@@ -31,6 +31,24 @@ extern "C" {
 
 PERFETTO_SDK_EXPORT void PerfettoTeInit(void);
 
+// How the track event config of a data source instance enables categories.
+enum PerfettoTeCategoryFiltering {
+  // Categories are enabled by the enabled categories and tags patterns of the
+  // config, and are disabled if nothing matches them.
+  PERFETTO_TE_CATEGORY_FILTERING_C = 0,
+  // Same as the C++ TrackEvent API: disabled categories and tags take
+  // precedence over enabled tags, a single "*" wildcard is only tried last, the
+  // "slow" and "debug" tags are disabled unless the config lists tags, and
+  // categories are enabled if nothing matches them.
+  PERFETTO_TE_CATEGORY_FILTERING_CPP = 1,
+};
+
+// Sets how the configs of track event data source instances enable categories.
+// Must be called before setting up the tracing sessions. The default is
+// PERFETTO_TE_CATEGORY_FILTERING_C.
+PERFETTO_SDK_EXPORT void PerfettoTeSetCategoryFiltering(
+    enum PerfettoTeCategoryFiltering);
+
 // The attributes of a single category.
 struct PerfettoTeCategoryDescriptor {
   // The category name. Null terminated string.
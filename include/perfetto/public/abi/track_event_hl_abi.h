@@ -154,6 +154,7 @@ enum PerfettoTeHlExtraType {
   PERFETTO_TE_HL_EXTRA_TYPE_NESTED_TRACKS = 19,
   PERFETTO_TE_HL_EXTRA_TYPE_CORRELATION_ID = 20,
   PERFETTO_TE_HL_EXTRA_TYPE_CORRELATION_ID_STR = 21,
+  PERFETTO_TE_HL_EXTRA_TYPE_SOURCE_LOCATION = 22,
 };
 
 // An extra event parameter. Each type of parameter should embed this as its
@@ -312,6 +313,17 @@ union PerfettoTeHlExtraCorrelationIdUnion {
   struct PerfettoTeHlExtraCorrelationIdStr correlation_id_str;
 };
 
+// PERFETTO_TE_HL_EXTRA_TYPE_SOURCE_LOCATION
+struct PerfettoTeHlExtraSourceLocation {
+  struct PerfettoTeHlExtra header;
+  // Null terminated name of the source file that emitted the event.
+  const char* file_name;
+  // Null terminated name of the function that emitted the event, or NULL.
+  const char* function_name;
+  // Line of the source file that emitted the event.
+  uint32_t line_number;
+};
+
 // PERFETTO_TE_HL_EXTRA_TYPE_PROTO_FIELDS
 struct PerfettoTeHlExtraProtoFields {
   struct PerfettoTeHlExtra header;
@@ -325,6 +325,14 @@ struct PerfettoTeHlMacroNameAndType {
   PERFETTO_I_TE_EXTRA(PerfettoTeHlExtraCorrelationIdStr, \
                       {{PERFETTO_TE_HL_EXTRA_TYPE_CORRELATION_ID_STR}, VALUE})
 
+// Specifies the source location that emitted this event. The location is
+// interned, so it's only written once per sequence. `FILE` and `FUNCTION` are
+// `const char*`, `FUNCTION` can be NULL, and `LINE` is a `uint32_t`.
+#define PERFETTO_TE_SOURCE_LOCATION(FILE, FUNCTION, LINE)                   \
+  PERFETTO_I_TE_EXTRA(PerfettoTeHlExtraSourceLocation,                      \
+                      {{PERFETTO_TE_HL_EXTRA_TYPE_SOURCE_LOCATION}, FILE, \
+                       FUNCTION, LINE})
+
 // Flushes the shared memory buffer and makes sure that all the previous events
 // emitted by this thread are visibile in the central tracing buffer.
 #define PERFETTO_TE_FLUSH() \
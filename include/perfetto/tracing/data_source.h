@@ -29,6 +29,7 @@
 #include <functional>
 #include <memory>
 #include <mutex>
+#include <string>
 #include <type_traits>
 #include <utility>
 
@@ -111,6 +112,12 @@ class PERFETTO_EXPORT_COMPONENT DataSourceBase {
 
     // The index of this data source instance (0..kMaxDataSourceInstances - 1).
     uint32_t internal_instance_index = 0;
+
+    // If the data source instance can't be set up (e.g. because its config is
+    // invalid), it can store the reason here. The tracing service is notified
+    // and counts the failure in the TraceStats of the session. This is valid
+    // only within the scope of the OnSetup() call and can be null.
+    std::string* setup_error = nullptr;
   };
   virtual void OnSetup(const SetupArgs&);
 
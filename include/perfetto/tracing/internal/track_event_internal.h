@@ -246,6 +246,14 @@ class PERFETTO_EXPORT_COMPONENT TrackEventInternal {
                                 const protos::gen::TrackEventConfig& config,
                                 const Category& category);
 
+  // Same as IsCategoryEnabled(), for a category that isn't a group, with the
+  // `num_tags` tags in `tags`.
+  static bool IsSingleCategoryEnabled(
+      const protos::gen::TrackEventConfig& config,
+      const char* name,
+      const char* const* tags,
+      size_t num_tags);
+
   static void WriteEventName(perfetto::DynamicString event_name,
                              perfetto::EventContext& event_ctx,
                              const TrackEventTlsState&);
@@ -93,6 +93,10 @@ class PERFETTO_EXPORT_COMPONENT TraceWriterBase {
   // dropped, as multiple such packets/chunks can be dropped on entry into a
   // drop data mode.
   virtual uint64_t drop_count() const = 0;
+
+  // Returns true if the trace writer is in the mode counted by drop_count(),
+  // i.e. if the data written to it is currently lost.
+  virtual bool is_dropping_packets() const { return false; }
 };
 
 }  // namespace perfetto
@@ -87,6 +87,12 @@ struct TracingInitArgs {
   // out and record no data.
   uint32_t machine_id = 0;
 
+  // [Optional] Name of the socket used to connect to the system tracing
+  // service, overriding perfetto::GetProducerSocket(). Supports the same
+  // formats, e.g. "vsock://2:10001" for a tracing service running on the host
+  // of a virtual machine. Only honored by the system backend.
+  std::string producer_socket_name;
+
   // [Optional] Platform implementation. It allows the embedder to take control
   // of platform-specific bits like thread creation and TLS slot handling. If
   // not set it will use Platform::GetDefaultPlatform().
@@ -126,6 +132,12 @@ struct TracingInitArgs {
   // if the service supports direct patching, otherwise it will be ignored.
   bool shmem_direct_patching_enabled = false;
 
+  // [Optional] If true, the system backend commits trace data over the producer
+  // socket instead of mapping the shared memory buffer provided by the service,
+  // e.g. for sandboxed processes that can't map it. The system backend also
+  // falls back to this mode if mapping the shared memory buffer fails.
+  bool shmem_emulation_enabled = false;
+
   // [Optional] If set, the policy object is notified when certain SDK events
   // occur and may apply policy decisions, such as denying connections. The
   // embedder is responsible for ensuring the object remains alive for the
@@ -176,7 +188,9 @@ struct TracingInitArgs {
   // argument: a callback that takes an open file descriptor. The function
   // should create a socket with the name defined by
   // perfetto::GetProducerSocket(), connect to it, and return the corresponding
-  // descriptor via the callback.
+  // descriptor via the callback. If the socket can't be connected, e.g.
+  // because the service isn't up yet, the callback should be called with an
+  // invalid descriptor, in which case the function is called again later.
   // This is intended for the use-case where a process being traced is run
   // inside a sandbox and can't create sockets directly.
   // Not yet supported for consumer connections currently.
@@ -394,6 +408,30 @@ class PERFETTO_EXPORT_COMPONENT TracingSession {
   using CloneTraceCallback = std::function<void(CloneTraceCallbackArgs)>;
   virtual void CloneTrace(CloneTraceArgs args, CloneTraceCallback);
 
+  // Detaches the started tracing session from this object. The session keeps
+  // running in the tracing service, and isn't stopped when this object is
+  // destroyed, until a tracing session of the same `BackendType` (possibly in
+  // another process of the same uid) attaches to it with Attach() and the same
+  // `key`. Stop() has no effect on a detached session. The callback is invoked
+  // on an internal perfetto thread with whether the session was detached, which
+  // fails if another session was detached with the same key.
+  using DetachCallback = std::function<void(bool /*success*/)>;
+  virtual void Detach(const std::string& key, DetachCallback);
+
+  // Blocking version of Detach().
+  bool DetachBlocking(const std::string& key);
+
+  // Attaches this object to the tracing session detached with Detach() and the
+  // same `key`, instead of setting up and starting a new one. Once attached,
+  // the session can be stopped, flushed and read as if it had been started by
+  // this object. The callback is invoked on an internal perfetto thread with
+  // whether the session was attached.
+  using AttachCallback = std::function<void(bool /*success*/)>;
+  virtual void Attach(const std::string& key, AttachCallback);
+
+  // Blocking version of Attach().
+  bool AttachBlocking(const std::string& key);
+
   // This callback will be invoked when all data sources have acknowledged that
   // tracing has started. This callback will be invoked on an internal perfetto
   // thread.
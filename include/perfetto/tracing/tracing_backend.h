@@ -79,11 +79,20 @@ class PERFETTO_EXPORT_COMPONENT TracingProducerBackend {
     // backend only.
     uint32_t machine_id = 0;
 
+    // Socket name propagated from TracingInitArgs; honored by the system
+    // backend only. If empty, perfetto::GetProducerSocket() is used.
+    std::string producer_socket_name;
+
     // If true, the backend should allocate a shared memory buffer and provide
     // it to the service when connecting.
     // It's used in startup tracing.
     bool use_producer_provided_smb = false;
 
+    // If true, the producer commits chunk data over the IPC channel instead of
+    // mapping the shared memory buffer provided by the service. Propagated
+    // from TracingInitArgs; honored by the system backend only.
+    bool use_shmem_emulation = false;
+
     // If set, the producer will call this function to create and connect to a
     // socket. See the corresponding field in TracingInitArgs for more info.
     CreateSocketAsync create_socket_async = nullptr;
@@ -37,6 +37,7 @@ using google::protobuf::EnumDescriptor;
 using google::protobuf::EnumValueDescriptor;
 using google::protobuf::FieldDescriptor;
 using google::protobuf::FileDescriptor;
+using google::protobuf::OneofDescriptor;
 using google::protobuf::compiler::GeneratorContext;
 using google::protobuf::io::Printer;
 using google::protobuf::io::ZeroCopyOutputStream;
@@ -127,6 +128,16 @@ class GeneratorJob {
       for (const auto& f : SplitString(value, "|")) {
         local_files_.insert(std::string(f));
       }
+    } else if (name == "features") {
+      // Entries are of the form "path/to/file.proto:feature".
+      for (const auto& entry : SplitString(value, "|")) {
+        std::vector<std::string> file_feature = SplitString(entry, ":");
+        if (file_feature.size() != 2) {
+          Abort("Invalid features entry '" + entry + "'.");
+          continue;
+        }
+        features_[file_feature[0]] = file_feature[1];
+      }
     } else {
       Abort(std::string() + "Unknown plugin option '" + name + "'.");
     }
@@ -143,6 +154,30 @@ class GeneratorJob {
       error_ = reason;
   }
 
+  // Returns the cargo feature that the stubs of `proto_file` are gated behind,
+  // or an empty string if they are always built.
+  std::string FeatureOf(const std::string& proto_file) {
+    auto it = features_.find(proto_file);
+    return it == features_.end() ? "" : it->second;
+  }
+
+  // Returns the attribute that gates references from the current file to the
+  // stubs of `proto_file`, or an empty string if no gating is needed.
+  std::string FeatureGate(const std::string& proto_file) {
+    std::string feature = FeatureOf(proto_file);
+    if (feature.empty() || feature == FeatureOf(std::string(source_->name())))
+      return "";
+    return "#[cfg(feature = \"" + feature + "\")]";
+  }
+
+  std::string FieldFeatureGate(const FieldDescriptor* field) {
+    if (field->type() == FieldDescriptor::TYPE_MESSAGE)
+      return FeatureGate(std::string(field->message_type()->file()->name()));
+    if (field->type() == FieldDescriptor::TYPE_ENUM)
+      return FeatureGate(std::string(field->enum_type()->file()->name()));
+    return "";
+  }
+
   // Get Rust struct name corresponding to proto descriptor (simple name only).
   template <class T>
   inline std::string GetRustStructName(const T* descriptor) {
@@ -198,7 +233,7 @@ class GeneratorJob {
       case FieldDescriptor::TYPE_STRING:
         return "String";
       case FieldDescriptor::TYPE_BYTES:
-        return "String";
+        return "Bytes";
       case FieldDescriptor::TYPE_MESSAGE:
         return GetFullRustMessageName(field->message_type());
       case FieldDescriptor::TYPE_GROUP:
@@ -415,6 +450,10 @@ class GeneratorJob {
       bool is_external =
           !external_crate_.empty() && local_files_.count(imp + ".proto") == 0;
       std::string crate_prefix = is_external ? external_crate_ : "crate";
+      std::string gate = FeatureGate(imp + ".proto");
+      if (!gate.empty()) {
+        stub_rs_->Print("$gate$\n", "gate", gate);
+      }
       stub_rs_->Print("use $crate$::protos$mod$::*;\n", "crate", crate_prefix,
                       "mod", ReplaceAll(mod_path, "/", "::"));
     }
@@ -474,14 +513,28 @@ class GeneratorJob {
     std::string name = std::string(field->lowercase_name());
     std::string id = std::to_string(field->number());
 
-    if (field->type() == FieldDescriptor::TYPE_MESSAGE) {
-      std::string inner_struct = GetFullRustMessageName(field->message_type());
-      return name + ": " + inner_struct + ", msg, " + id + ",";
+    if (field->is_map()) {
+      // Map fields refer to the generated entry message type, and to the
+      // types of its key and value fields.
+      const Descriptor* entry = field->message_type();
+      const FieldDescriptor* value = entry->map_value();
+      std::string value_kind = "primitive";
+      if (value->type() == FieldDescriptor::TYPE_MESSAGE) {
+        value_kind = "msg";
+      } else if (value->type() == FieldDescriptor::TYPE_ENUM) {
+        value_kind = "enum";
+      }
+      return name + ": [" + GetFullRustMessageName(entry) + ", " +
+             FieldToRustTypeName(entry->map_key()) + ", " + value_kind + ", " +
+             FieldToRustTypeName(value) + "], map, " + id + ",";
     }
 
     std::string type = FieldToRustTypeName(field);
     std::string kind;
     switch (field->type()) {
+      case FieldDescriptor::TYPE_MESSAGE:
+        kind = "msg";
+        break;
       case FieldDescriptor::TYPE_ENUM:
         kind = "enum";
         break;
@@ -492,6 +545,13 @@ class GeneratorJob {
         kind = "primitive";
         break;
     }
+
+    // Repeated fields refer to the kind and type of their elements.
+    if (field->is_repeated()) {
+      std::string repeated_kind = field->is_packed() ? "packed" : "repeated";
+      return name + ": [" + kind + ", " + type + "], " + repeated_kind + ", " +
+             id + ",";
+    }
     return name + ": " + type + ", " + kind + ", " + id + ",";
   }
 
@@ -503,10 +563,29 @@ class GeneratorJob {
     } else {
       stub_rs_->Print("\npb_msg!($name$ {\n", "name", name);
       for (int i = 0; i < message->field_count(); ++i) {
+        std::string gate = FieldFeatureGate(message->field(i));
+        if (!gate.empty()) {
+          stub_rs_->Print("    $gate$\n", "gate", gate);
+        }
         stub_rs_->Print("    $field$\n", "field",
                         GetFieldDescriptorContent(message->field(i)));
       }
-      stub_rs_->Print("});\n");
+      stub_rs_->Print("}");
+      for (int i = 0; i < message->real_oneof_decl_count(); ++i) {
+        const OneofDescriptor* oneof = message->real_oneof_decl(i);
+        stub_rs_->Print("\noneof $name$ {\n", "name",
+                        std::string(oneof->name()));
+        for (int j = 0; j < oneof->field_count(); ++j) {
+          std::string gate = FieldFeatureGate(oneof->field(j));
+          if (!gate.empty()) {
+            stub_rs_->Print("    $gate$\n", "gate", gate);
+          }
+          stub_rs_->Print("    $field$,\n", "field",
+                          std::string(oneof->field(j)->lowercase_name()));
+        }
+        stub_rs_->Print("}");
+      }
+      stub_rs_->Print(");\n");
     }
   }
 
@@ -519,6 +598,10 @@ class GeneratorJob {
 
     stub_rs_->Print("\npb_msg_ext!($base$ {\n", "base", base_name);
     for (const FieldDescriptor* field : fields) {
+      std::string gate = FieldFeatureGate(field);
+      if (!gate.empty()) {
+        stub_rs_->Print("    $gate$\n", "gate", gate);
+      }
       stub_rs_->Print("    $field$\n", "field",
                       GetFieldDescriptorContent(field));
     }
@@ -535,6 +618,7 @@ class GeneratorJob {
   std::string path_add_prefix_;
   std::string external_crate_;
   std::set<std::string> local_files_;
+  std::map<std::string, std::string> features_;
   std::string invoker_;
   std::vector<std::string> namespaces_;
   std::string full_namespace_prefix_;
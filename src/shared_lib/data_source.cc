@@ -24,6 +24,8 @@
 #include <functional>
 #include <memory>
 #include <mutex>
+#include <optional>
+#include <string>
 #include <vector>
 
 #include "perfetto/base/compiler.h"
@@ -101,6 +103,10 @@ struct PerfettoDsImpl {
   }
 };
 
+struct PerfettoDsOnSetupArgs {
+  std::optional<std::string> error;
+};
+
 struct PerfettoDsOnStopArgs {
   struct PerfettoDsAsyncStopper* stopper = nullptr;
 };
@@ -145,9 +151,16 @@ class ShlibDataSource : public perfetto::DataSourceBase {
   void OnSetup(const SetupArgs& args) override {
     if (type_.on_setup_cb) {
       std::vector<uint8_t> serialized_config = args.config->SerializeAsArray();
+      PerfettoDsOnSetupArgs c_args;
       inst_ctx_ = type_.on_setup_cb(
           &type_, args.internal_instance_index, serialized_config.data(),
-          serialized_config.size(), type_.cb_user_arg, nullptr);
+          serialized_config.size(), type_.cb_user_arg, &c_args);
+      if (c_args.error) {
+        // Don't enable a data source instance that failed to set up.
+        if (args.setup_error)
+          *args.setup_error = std::move(*c_args.error);
+        return;
+      }
     }
     std::lock_guard<std::mutex> lock(type_.mu);
     const bool was_enabled = type_.enabled_instances.any();
@@ -437,6 +450,11 @@ void PerfettoDsImplUpdateDescriptor(struct PerfettoDsImpl* ds_impl,
   ds_impl->cpp_type.UpdateDescriptor(dsd);
 }
 
+void PerfettoDsOnSetupArgsSetError(PerfettoDsOnSetupArgs* args,
+                                   const char* error) {
+  args->error = error;
+}
+
 PerfettoDsAsyncStopper* PerfettoDsOnStopArgsPostpone(
     PerfettoDsOnStopArgs* args) {
   PerfettoDsAsyncStopper* stopper = args->stopper;
@@ -457,6 +475,13 @@ PerfettoDsAsyncFlusher* PerfettoDsOnFlushArgsPostpone(
   return reinterpret_cast<PerfettoDsAsyncFlusher*>(cb);
 }
 
+bool PerfettoDsOnFlushArgsIsClone(PerfettoDsOnFlushArgs* args) {
+  const auto* flush_args =
+      reinterpret_cast<const ShlibDataSource::FlushArgs*>(args);
+  return flush_args->flush_flags.reason() ==
+         perfetto::FlushFlags::Reason::kTraceClone;
+}
+
 void PerfettoDsFlushDone(PerfettoDsAsyncFlusher* stopper) {
   auto* cb = reinterpret_cast<std::function<void()>*>(stopper);
   (*cb)();
@@ -598,6 +623,12 @@ void PerfettoDsTracerImplPacketEnd(struct PerfettoDsTracerImpl* tracer,
   tls_inst->trace_writer->FinishTracePacket();
 }
 
+bool PerfettoDsTracerImplIsDropping(struct PerfettoDsTracerImpl* tracer) {
+  auto* tls_inst =
+      reinterpret_cast<DataSourceInstanceThreadLocalState*>(tracer);
+  return tls_inst->trace_writer->is_dropping_packets();
+}
+
 void PerfettoDsTracerImplFlush(struct PerfettoDsTracerImpl* tracer,
                                PerfettoDsTracerOnFlushCb cb,
                                void* user_arg) {
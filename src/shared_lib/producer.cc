@@ -16,11 +16,40 @@
 
 #include "perfetto/public/abi/producer_abi.h"
 
+#include <memory>
+
+#include "perfetto/base/build_config.h"
+#include "perfetto/ext/base/thread_task_runner.h"
+#include "perfetto/ext/base/waitable_event.h"
 #include "perfetto/tracing/backend_type.h"
 #include "perfetto/tracing/tracing.h"
 #include "src/shared_lib/reset_for_testing.h"
+#include "src/tracing/core/shared_memory_arbiter_impl.h"
 #include "src/tracing/internal/tracing_muxer_impl.h"
 
+#if PERFETTO_BUILDFLAG(PERFETTO_IPC)
+#include "perfetto/ext/tracing/ipc/default_socket.h"
+#include "perfetto/ext/tracing/ipc/service_ipc_host.h"
+#endif
+
+#if PERFETTO_BUILDFLAG(PERFETTO_IPC) && !PERFETTO_BUILDFLAG(PERFETTO_OS_WIN)
+namespace {
+
+// The system backend only takes a plain function pointer to create its socket,
+// so the callback that it calls is global.
+PerfettoProducerCreateSocketCb g_create_socket_cb = nullptr;
+void* g_create_socket_user_arg = nullptr;
+std::string* g_create_socket_name = nullptr;
+
+void CreateSocketAsync(perfetto::CreateSocketCallback cb) {
+  auto* done = new perfetto::CreateSocketCallback(std::move(cb));
+  g_create_socket_cb(g_create_socket_name->c_str(), g_create_socket_user_arg,
+                     done);
+}
+
+}  // namespace
+#endif
+
 namespace perfetto {
 namespace shlib {
 
@@ -32,6 +61,12 @@ void ResetForTesting() {
     perfetto::shlib::ResetTrackEventTls();
   });
   perfetto::Tracing::ResetForTesting();
+#if PERFETTO_BUILDFLAG(PERFETTO_IPC) && !PERFETTO_BUILDFLAG(PERFETTO_OS_WIN)
+  g_create_socket_cb = nullptr;
+  g_create_socket_user_arg = nullptr;
+  delete g_create_socket_name;
+  g_create_socket_name = nullptr;
+#endif
 }
 
 }  // namespace shlib
@@ -39,7 +74,14 @@ void ResetForTesting() {
 
 struct PerfettoProducerBackendInitArgs {
   uint32_t shmem_size_hint_kb = 0;
+  uint32_t shmem_page_size_hint_kb = 0;
+  uint32_t shmem_batch_commits_duration_ms = 0;
+  bool shmem_direct_patching_enabled = false;
   uint32_t machine_id = 0;
+  std::string producer_socket_name;
+  bool shmem_emulation_enabled = false;
+  PerfettoProducerCreateSocketCb create_socket_cb = nullptr;
+  void* create_socket_user_arg = nullptr;
 };
 
 struct PerfettoProducerBackendInitArgs*
@@ -53,12 +95,57 @@ void PerfettoProducerBackendInitArgsSetShmemSizeHintKb(
   backend_args->shmem_size_hint_kb = size;
 }
 
+void PerfettoProducerBackendInitArgsSetShmemPageSizeHintKb(
+    struct PerfettoProducerBackendInitArgs* backend_args,
+    uint32_t size) {
+  backend_args->shmem_page_size_hint_kb = size;
+}
+
+void PerfettoProducerBackendInitArgsSetShmemBatchCommitsDurationMs(
+    struct PerfettoProducerBackendInitArgs* backend_args,
+    uint32_t duration_ms) {
+  backend_args->shmem_batch_commits_duration_ms = duration_ms;
+}
+
+void PerfettoProducerBackendInitArgsSetShmemDirectPatchingEnabled(
+    struct PerfettoProducerBackendInitArgs* backend_args,
+    bool enabled) {
+  backend_args->shmem_direct_patching_enabled = enabled;
+}
+
 void PerfettoProducerBackendInitArgsSetMachineId(
     struct PerfettoProducerBackendInitArgs* backend_args,
     uint32_t machine_id) {
   backend_args->machine_id = machine_id;
 }
 
+void PerfettoProducerBackendInitArgsSetProducerSocketName(
+    struct PerfettoProducerBackendInitArgs* backend_args,
+    const char* name) {
+  backend_args->producer_socket_name = name ? name : "";
+}
+
+void PerfettoProducerBackendInitArgsSetShmemEmulationEnabled(
+    struct PerfettoProducerBackendInitArgs* backend_args,
+    bool enabled) {
+  backend_args->shmem_emulation_enabled = enabled;
+}
+
+void PerfettoProducerBackendInitArgsSetCreateSocketCb(
+    struct PerfettoProducerBackendInitArgs* backend_args,
+    PerfettoProducerCreateSocketCb cb,
+    void* user_arg) {
+  backend_args->create_socket_cb = cb;
+  backend_args->create_socket_user_arg = user_arg;
+}
+
+void PerfettoProducerCreateSocketDone(void* done_arg, int fd) {
+  std::unique_ptr<perfetto::CreateSocketCallback> done(
+      static_cast<perfetto::CreateSocketCallback*>(done_arg));
+  // An invalid socket makes the backend call the callback again later.
+  (*done)(static_cast<perfetto::base::SocketHandle>(fd < 0 ? -1 : fd));
+}
+
 void PerfettoProducerBackendInitArgsDestroy(
     struct PerfettoProducerBackendInitArgs* backend_args) {
   delete backend_args;
@@ -69,6 +156,11 @@ void PerfettoProducerInProcessInit(
   perfetto::TracingInitArgs args;
   args.backends = perfetto::kInProcessBackend;
   args.shmem_size_hint_kb = backend_args->shmem_size_hint_kb;
+  args.shmem_page_size_hint_kb = backend_args->shmem_page_size_hint_kb;
+  args.shmem_batch_commits_duration_ms =
+      backend_args->shmem_batch_commits_duration_ms;
+  args.shmem_direct_patching_enabled =
+      backend_args->shmem_direct_patching_enabled;
   args.machine_id = backend_args->machine_id;
   perfetto::Tracing::Initialize(args);
 }
@@ -78,6 +170,24 @@ void PerfettoProducerSystemInit(
   perfetto::TracingInitArgs args;
   args.backends = perfetto::kSystemBackend;
   args.shmem_size_hint_kb = backend_args->shmem_size_hint_kb;
+  args.producer_socket_name = backend_args->producer_socket_name;
+  args.shmem_page_size_hint_kb = backend_args->shmem_page_size_hint_kb;
+  args.shmem_batch_commits_duration_ms =
+      backend_args->shmem_batch_commits_duration_ms;
+  args.shmem_direct_patching_enabled =
+      backend_args->shmem_direct_patching_enabled;
+  args.shmem_emulation_enabled = backend_args->shmem_emulation_enabled;
+#if PERFETTO_BUILDFLAG(PERFETTO_IPC) && !PERFETTO_BUILDFLAG(PERFETTO_OS_WIN)
+  if (backend_args->create_socket_cb && !g_create_socket_cb) {
+    g_create_socket_cb = backend_args->create_socket_cb;
+    g_create_socket_user_arg = backend_args->create_socket_user_arg;
+    g_create_socket_name = new std::string(
+        backend_args->producer_socket_name.empty()
+            ? perfetto::GetProducerSocket()
+            : backend_args->producer_socket_name);
+    args.create_socket_async = &CreateSocketAsync;
+  }
+#endif
   perfetto::Tracing::Initialize(args);
 }
 
@@ -89,3 +199,85 @@ void PerfettoProducerActivateTriggers(const char* trigger_names[],
   }
   perfetto::Tracing::ActivateTriggers(triggers, ttl_ms);
 }
+
+uint64_t PerfettoProducerGetStalledChunkRequests(void) {
+  return perfetto::SharedMemoryArbiterImpl::stalled_chunk_requests();
+}
+
+uint64_t PerfettoProducerGetDroppedChunkRequests(void) {
+  return perfetto::SharedMemoryArbiterImpl::dropped_chunk_requests();
+}
+
+uint64_t PerfettoProducerGetThreadStalledChunkRequests(void) {
+  return perfetto::SharedMemoryArbiterImpl::thread_stalled_chunk_requests();
+}
+
+uint64_t PerfettoProducerGetThreadDroppedChunkRequests(void) {
+  return perfetto::SharedMemoryArbiterImpl::thread_dropped_chunk_requests();
+}
+
+void PerfettoProducerSetThreadChunkRequestStatsEnabled(bool enabled) {
+  perfetto::SharedMemoryArbiterImpl::SetThreadChunkRequestStatsEnabled(enabled);
+}
+
+void PerfettoProducerSetThreadStallTimeout(uint64_t timeout_ns) {
+  perfetto::SharedMemoryArbiterImpl::SetThreadStallTimeout(timeout_ns);
+}
+
+void PerfettoProducerClearThreadStallTimeout(void) {
+  perfetto::SharedMemoryArbiterImpl::ClearThreadStallTimeout();
+}
+
+#if PERFETTO_BUILDFLAG(PERFETTO_IPC)
+struct PerfettoService {
+  explicit PerfettoService(perfetto::base::ThreadTaskRunner runner)
+      : task_runner(std::move(runner)) {}
+
+  perfetto::base::ThreadTaskRunner task_runner;
+  // Only accessed on `task_runner`.
+  std::unique_ptr<perfetto::ServiceIPCHost> host;
+};
+
+struct PerfettoService* PerfettoServiceStart(const char* producer_socket,
+                                             const char* consumer_socket) {
+  auto service = std::make_unique<PerfettoService>(
+      perfetto::base::ThreadTaskRunner::CreateAndStart("perfetto.svc"));
+  bool started = false;
+  perfetto::base::WaitableEvent done;
+  service->task_runner.PostTask([&] {
+    service->host =
+        perfetto::ServiceIPCHost::CreateInstance(&service->task_runner);
+    started = service->host->Start(
+        producer_socket ? producer_socket : perfetto::GetProducerSocket(),
+        consumer_socket ? consumer_socket : perfetto::GetConsumerSocket());
+    if (!started) {
+      service->host.reset();
+    }
+    done.Notify();
+  });
+  done.Wait();
+  if (!started) {
+    return nullptr;
+  }
+  return service.release();
+}
+
+void PerfettoServiceDestroy(struct PerfettoService* service) {
+  perfetto::base::WaitableEvent done;
+  service->task_runner.PostTask([&] {
+    service->host.reset();
+    done.Notify();
+  });
+  done.Wait();
+  delete service;
+}
+
+#else   // PERFETTO_BUILDFLAG(PERFETTO_IPC)
+
+struct PerfettoService* PerfettoServiceStart(const char*, const char*) {
+  return nullptr;
+}
+
+void PerfettoServiceDestroy(struct PerfettoService*) {}
+
+#endif  // PERFETTO_BUILDFLAG(PERFETTO_IPC)
@@ -15,17 +15,24 @@
  */
 
 #include <atomic>
+#include <condition_variable>
 #include <cstddef>
 #include <cstdint>
+#include <cstring>
 #include <memory>
+#include <mutex>
 #include <optional>
 #include <string>
 #include <string_view>
 #include <thread>
 #include <vector>
 
+#include "perfetto/base/build_config.h"
 #include "perfetto/base/time.h"
+#include "perfetto/ext/base/file_utils.h"
 #include "perfetto/ext/base/flags.h"
+#include "perfetto/ext/base/temp_file.h"
+#include "perfetto/ext/base/utils.h"
 #include "perfetto/public/abi/atomic.h"
 #include "perfetto/public/abi/backend_type.h"
 #include "perfetto/public/abi/data_source_abi.h"
@@ -63,6 +70,12 @@
 #include "src/shared_lib/test/protos/test_messages.pzc.h"
 #include "src/shared_lib/test/utils.h"
 
+#if !PERFETTO_BUILDFLAG(PERFETTO_OS_WIN)
+#include <sys/socket.h>
+#include <sys/un.h>
+#include <unistd.h>
+#endif
+
 // Tests for the perfetto shared library.
 
 namespace {
@@ -82,8 +95,10 @@ using ::perfetto::shlib::test_utils::VarIntField;
 using ::perfetto::shlib::test_utils::WaitableEvent;
 using ::testing::_;
 using ::testing::AllOf;
+using ::testing::Contains;
 using ::testing::DoAll;
 using ::testing::ElementsAre;
+using ::testing::Gt;
 using ::testing::InSequence;
 using ::testing::IsNull;
 using ::testing::MockFunction;
@@ -93,6 +108,7 @@ using ::testing::Return;
 using ::testing::SaveArg;
 using ::testing::StrictMock;
 using ::testing::UnorderedElementsAre;
+using ::testing::WithArg;
 
 constexpr char kDataSourceName1[] = "dev.perfetto.example_data_source";
 struct PerfettoDs data_source_1 = PERFETTO_DS_INIT();
@@ -824,6 +840,57 @@ TEST_F(SharedLibDataSourceTest, Serialization) {
   EXPECT_TRUE(found_for_testing);
 }
 
+#if !PERFETTO_BUILDFLAG(PERFETTO_OS_WIN)
+TEST_F(SharedLibDataSourceTest, SetupWithFd) {
+  perfetto::base::TempFile tmp_file = perfetto::base::TempFile::Create();
+  std::vector<uint8_t> config =
+      TracingSession::Builder()
+          .set_data_source_name(kDataSourceName1)
+          .BuildProtoConfig();
+  struct PerfettoTracingSessionImpl* ts =
+      PerfettoTracingSessionCreate(PERFETTO_BACKEND_IN_PROCESS);
+  PerfettoTracingSessionSetupWithFd(ts, config.data(), config.size(),
+                                    dup(tmp_file.fd()));
+  PerfettoTracingSessionStartBlocking(ts);
+  TracingSession tracing_session = TracingSession::Adopt(ts);
+
+  PERFETTO_DS_TRACE(data_source_1, ctx) {
+    struct PerfettoDsRootTracePacket trace_packet;
+    PerfettoDsTracerPacketBegin(&ctx, &trace_packet);
+    struct perfetto_protos_TestEvent for_testing;
+    perfetto_protos_TracePacket_begin_for_testing(&trace_packet.msg,
+                                                  &for_testing);
+    perfetto_protos_TestEvent_set_cstr_str(&for_testing, "ABCDEFGH");
+    perfetto_protos_TracePacket_end_for_testing(&trace_packet.msg,
+                                                &for_testing);
+    PerfettoDsTracerPacketEnd(&ctx, &trace_packet);
+  }
+  // Stopping the session writes the rest of the trace into the file.
+  tracing_session.StopBlocking();
+
+  ASSERT_EQ(lseek(tmp_file.fd(), 0, SEEK_SET), 0);
+  std::string contents;
+  ASSERT_TRUE(perfetto::base::ReadFileDescriptor(tmp_file.fd(), &contents));
+  std::vector<uint8_t> data(contents.begin(), contents.end());
+  bool found_for_testing = false;
+  for (struct PerfettoPbDecoderField trace_field : FieldView(data)) {
+    ASSERT_THAT(trace_field, PbField(perfetto_protos_Trace_packet_field_number,
+                                     MsgField(_)));
+    IdFieldView for_testing(
+        trace_field, perfetto_protos_TracePacket_for_testing_field_number);
+    ASSERT_TRUE(for_testing.ok());
+    if (for_testing.size() == 0) {
+      continue;
+    }
+    found_for_testing = true;
+    EXPECT_THAT(FieldView(for_testing.front()),
+                ElementsAre(PbField(perfetto_protos_TestEvent_str_field_number,
+                                    StringField("ABCDEFGH"))));
+  }
+  EXPECT_TRUE(found_for_testing);
+}
+#endif  // !PERFETTO_BUILDFLAG(PERFETTO_OS_WIN)
+
 TEST_F(SharedLibDataSourceTest, Break) {
   TracingSession tracing_session1 =
       TracingSession::Builder().set_data_source_name(kDataSourceName1).Build();
@@ -907,6 +974,115 @@ TEST_F(SharedLibDataSourceTest, LifetimeCallbacks) {
   EXPECT_EQ(setup_inst, stop_inst);
 }
 
+TEST_F(SharedLibDataSourceTest, SetupError) {
+  EXPECT_CALL(ds2_callbacks_, OnSetup(_, _, _, _, kDataSource2UserArg, _))
+      .WillOnce(WithArg<5>([](struct PerfettoDsOnSetupArgs* args) -> void* {
+        PerfettoDsOnSetupArgsSetError(args, "Invalid config");
+        return nullptr;
+      }));
+
+  TracingSession tracing_session =
+      TracingSession::Builder().set_data_source_name(kDataSourceName2).Build();
+
+  bool executed = false;
+  PERFETTO_DS_TRACE(data_source_2, ctx) {
+    executed = true;
+  }
+  EXPECT_FALSE(executed);
+
+  // perfetto.protos.TraceStats.data_source_setup_failures
+  constexpr uint32_t kDataSourceSetupFailuresFieldNumber = 19;
+  std::vector<uint8_t> stats = tracing_session.GetTraceStatsBlocking();
+  EXPECT_THAT(FieldView(stats),
+              Contains(PbField(kDataSourceSetupFailuresFieldNumber,
+                               VarIntField(1))));
+}
+
+TEST_F(SharedLibDataSourceTest, GetTraceStats) {
+  TracingSession tracing_session =
+      TracingSession::Builder().set_data_source_name(kDataSourceName1).Build();
+  PERFETTO_DS_TRACE(data_source_1, ctx) {
+    struct PerfettoDsRootTracePacket trace_packet;
+    PerfettoDsTracerPacketBegin(&ctx, &trace_packet);
+    PerfettoDsTracerPacketEnd(&ctx, &trace_packet);
+  }
+  ASSERT_TRUE(tracing_session.FlushBlocking(/*timeout_ms=*/10000));
+
+  // perfetto.protos.TraceStats
+  constexpr uint32_t kBufferStatsFieldNumber = 1;
+  constexpr uint32_t kTracingSessionsFieldNumber = 6;
+  constexpr uint32_t kTotalBuffersFieldNumber = 7;
+  // perfetto.protos.TraceStats.BufferStats
+  constexpr uint32_t kBytesWrittenFieldNumber = 1;
+  std::vector<uint8_t> stats = tracing_session.GetTraceStatsBlocking();
+  EXPECT_THAT(FieldView(stats),
+              Contains(PbField(kTracingSessionsFieldNumber, VarIntField(1))));
+  EXPECT_THAT(FieldView(stats),
+              Contains(PbField(kTotalBuffersFieldNumber, VarIntField(1))));
+  EXPECT_THAT(FieldView(stats),
+              Contains(PbField(kBufferStatsFieldNumber,
+                               MsgField(Contains(PbField(
+                                   kBytesWrittenFieldNumber,
+                                   VarIntField(Gt(0U)))))));
+}
+
+TEST_F(SharedLibDataSourceTest, GetTraceStatsNotSetUp) {
+  struct PerfettoTracingSessionImpl* ts =
+      PerfettoTracingSessionCreate(PERFETTO_BACKEND_IN_PROCESS);
+  bool called = false;
+  EXPECT_FALSE(PerfettoTracingSessionGetTraceStatsBlocking(
+      ts,
+      [](struct PerfettoTracingSessionImpl*, const void*, size_t,
+         void* user_arg) { *static_cast<bool*>(user_arg) = true; },
+      &called));
+  EXPECT_FALSE(called);
+  PerfettoTracingSessionDestroy(ts);
+}
+
+TEST_F(SharedLibDataSourceTest, DetachAttach) {
+  auto write_packet = [] {
+    PERFETTO_DS_TRACE(data_source_1, ctx) {
+      struct PerfettoDsRootTracePacket trace_packet;
+      PerfettoDsTracerPacketBegin(&ctx, &trace_packet);
+      struct perfetto_protos_TestEvent for_testing;
+      perfetto_protos_TracePacket_begin_for_testing(&trace_packet.msg,
+                                                    &for_testing);
+      perfetto_protos_TracePacket_end_for_testing(&trace_packet.msg,
+                                                  &for_testing);
+      PerfettoDsTracerPacketEnd(&ctx, &trace_packet);
+    }
+  };
+  TracingSession detached_session =
+      TracingSession::Builder().set_data_source_name(kDataSourceName1).Build();
+  write_packet();
+
+  EXPECT_TRUE(
+      PerfettoTracingSessionDetachBlocking(detached_session.session(), "key"));
+  EXPECT_FALSE(
+      PerfettoTracingSessionDetachBlocking(detached_session.session(), "key"));
+
+  // The detached tracing session keeps running.
+  write_packet();
+
+  TracingSession attached_session = TracingSession::Adopt(
+      PerfettoTracingSessionCreate(PERFETTO_BACKEND_IN_PROCESS));
+  EXPECT_FALSE(PerfettoTracingSessionAttachBlocking(attached_session.session(),
+                                                    "other_key"));
+  ASSERT_TRUE(
+      PerfettoTracingSessionAttachBlocking(attached_session.session(), "key"));
+
+  attached_session.StopBlocking();
+  std::vector<uint8_t> data = attached_session.ReadBlocking();
+  size_t for_testing_count = 0;
+  for (struct PerfettoPbDecoderField trace_field : FieldView(data)) {
+    IdFieldView for_testing(
+        trace_field, perfetto_protos_TracePacket_for_testing_field_number);
+    ASSERT_TRUE(for_testing.ok());
+    for_testing_count += for_testing.size();
+  }
+  EXPECT_EQ(for_testing_count, 2u);
+}
+
 TEST_F(SharedLibDataSourceTest, StopDone) {
   TracingSession tracing_session =
       TracingSession::Builder().set_data_source_name(kDataSourceName2).Build();
@@ -1446,6 +1622,175 @@ TEST_F(SharedLibProducerTest, ActivateTriggers) {
                           StringField("trigger1"))))))))));
 }
 
+#if PERFETTO_BUILDFLAG(PERFETTO_IPC) && !PERFETTO_BUILDFLAG(PERFETTO_OS_WIN)
+// Runs a tracing service started with PerfettoServiceStart() on the sockets of
+// a temporary directory, which the system backend connects to.
+class SharedLibServiceTest : public testing::Test {
+ protected:
+  void SetUp() override {
+    producer_socket_ = tmp_dir_.path() + "/producer.sock";
+    consumer_socket_ = tmp_dir_.path() + "/consumer.sock";
+    perfetto::base::SetEnv("PERFETTO_PRODUCER_SOCK_NAME", producer_socket_);
+    perfetto::base::SetEnv("PERFETTO_CONSUMER_SOCK_NAME", consumer_socket_);
+  }
+
+  void TearDown() override {
+    perfetto::shlib::ResetForTesting();
+    data_source_1.enabled = &perfetto_atomic_false;
+    perfetto::shlib::DsImplDestroy(data_source_1.impl);
+    data_source_1.impl = nullptr;
+    if (service_) {
+      PerfettoServiceDestroy(service_);
+    }
+    unlink(producer_socket_.c_str());
+    unlink(consumer_socket_.c_str());
+    perfetto::base::UnsetEnv("PERFETTO_PRODUCER_SOCK_NAME");
+    perfetto::base::UnsetEnv("PERFETTO_CONSUMER_SOCK_NAME");
+  }
+
+  struct PerfettoService* StartService() {
+    return PerfettoServiceStart(producer_socket_.c_str(),
+                                consumer_socket_.c_str());
+  }
+
+  // Registers data_source_1, which notifies `ds_started_` when it starts.
+  void RegisterDataSource() {
+    struct PerfettoDsParams params = PerfettoDsParamsDefault();
+    params.on_start_cb = [](struct PerfettoDsImpl*, PerfettoDsInstanceIndex,
+                            void* user_arg, void*,
+                            struct PerfettoDsOnStartArgs*) {
+      static_cast<WaitableEvent*>(user_arg)->Notify();
+    };
+    params.user_arg = &ds_started_;
+    PerfettoDsRegister(&data_source_1, kDataSourceName1, params);
+  }
+
+  perfetto::base::TempDir tmp_dir_ = perfetto::base::TempDir::Create();
+  std::string producer_socket_;
+  std::string consumer_socket_;
+  struct PerfettoService* service_ = nullptr;
+  WaitableEvent ds_started_;
+};
+
+TEST_F(SharedLibServiceTest, ServiceConnectsProducerAndConsumer) {
+  service_ = StartService();
+  ASSERT_NE(service_, nullptr);
+  // The sockets are in use by `service_`.
+  EXPECT_EQ(StartService(), nullptr);
+
+  struct PerfettoProducerBackendInitArgs* backend_args =
+      PerfettoProducerBackendInitArgsCreate();
+  PerfettoProducerBackendInitArgsSetProducerSocketName(
+      backend_args, producer_socket_.c_str());
+  PerfettoProducerSystemInit(backend_args);
+  PerfettoProducerBackendInitArgsDestroy(backend_args);
+  RegisterDataSource();
+
+  TracingSession tracing_session = TracingSession::Builder()
+                                       .set_data_source_name(kDataSourceName1)
+                                       .set_backend(PERFETTO_BACKEND_SYSTEM)
+                                       .Build();
+  ds_started_.WaitForNotification();
+  PERFETTO_DS_TRACE(data_source_1, ctx) {
+    struct PerfettoDsRootTracePacket trace_packet;
+    PerfettoDsTracerPacketBegin(&ctx, &trace_packet);
+    struct perfetto_protos_TestEvent for_testing;
+    perfetto_protos_TracePacket_begin_for_testing(&trace_packet.msg,
+                                                  &for_testing);
+    perfetto_protos_TracePacket_end_for_testing(&trace_packet.msg,
+                                                &for_testing);
+    PerfettoDsTracerPacketEnd(&ctx, &trace_packet);
+  }
+  tracing_session.StopBlocking();
+
+  std::vector<uint8_t> data = tracing_session.ReadBlocking();
+  bool found_for_testing = false;
+  for (struct PerfettoPbDecoderField trace_field : FieldView(data)) {
+    IdFieldView for_testing(
+        trace_field, perfetto_protos_TracePacket_for_testing_field_number);
+    ASSERT_TRUE(for_testing.ok());
+    if (for_testing.size() != 0) {
+      found_for_testing = true;
+    }
+  }
+  EXPECT_TRUE(found_for_testing);
+}
+
+// Connects the system backend like a PerfettoProducerCreateSocketCb of a
+// sandboxed process, and counts the attempts.
+class SocketCreator {
+ public:
+  static void CreateSocket(const char* socket_name,
+                           void* user_arg,
+                           void* done_arg) {
+    auto* thiz = static_cast<SocketCreator*>(user_arg);
+    int fd = socket(AF_UNIX, SOCK_STREAM, 0);
+    struct sockaddr_un addr = {};
+    addr.sun_family = AF_UNIX;
+    strncpy(addr.sun_path, socket_name, sizeof(addr.sun_path) - 1);
+    if (connect(fd, reinterpret_cast<struct sockaddr*>(&addr), sizeof(addr))) {
+      close(fd);
+      fd = -1;
+    }
+    PerfettoProducerCreateSocketDone(done_arg, fd);
+    std::lock_guard<std::mutex> lock(thiz->mutex_);
+    thiz->attempts_++;
+    thiz->connected_ = fd >= 0;
+    thiz->cv_.notify_all();
+  }
+
+  // Waits for the first call, and returns whether it connected.
+  bool WaitForFirstAttempt() {
+    std::unique_lock<std::mutex> lock(mutex_);
+    cv_.wait(lock, [&] { return attempts_ >= 1; });
+    return connected_;
+  }
+
+  // Waits for a call that connects, and returns the number of calls.
+  int WaitForConnection() {
+    std::unique_lock<std::mutex> lock(mutex_);
+    cv_.wait(lock, [&] { return connected_; });
+    return attempts_;
+  }
+
+ private:
+  std::mutex mutex_;
+  std::condition_variable cv_;
+  int attempts_ = 0;
+  bool connected_ = false;
+};
+
+TEST_F(SharedLibServiceTest, CreateSocketCbRetriesUntilServiceStarts) {
+  SocketCreator socket_creator;
+  struct PerfettoProducerBackendInitArgs* backend_args =
+      PerfettoProducerBackendInitArgsCreate();
+  PerfettoProducerBackendInitArgsSetProducerSocketName(
+      backend_args, producer_socket_.c_str());
+  PerfettoProducerBackendInitArgsSetCreateSocketCb(
+      backend_args, &SocketCreator::CreateSocket, &socket_creator);
+  PerfettoProducerSystemInit(backend_args);
+  PerfettoProducerBackendInitArgsDestroy(backend_args);
+  RegisterDataSource();
+
+  // The service isn't up yet.
+  EXPECT_FALSE(socket_creator.WaitForFirstAttempt());
+
+  service_ = StartService();
+  ASSERT_NE(service_, nullptr);
+
+  // The backend retries with backoff until the socket is connected, and then
+  // registers the data source with the service.
+  EXPECT_GE(socket_creator.WaitForConnection(), 2);
+  TracingSession tracing_session = TracingSession::Builder()
+                                       .set_data_source_name(kDataSourceName1)
+                                       .set_backend(PERFETTO_BACKEND_SYSTEM)
+                                       .Build();
+  ds_started_.WaitForNotification();
+  tracing_session.StopBlocking();
+}
+#endif  // PERFETTO_BUILDFLAG(PERFETTO_IPC) &&
+        // !PERFETTO_BUILDFLAG(PERFETTO_OS_WIN)
+
 TEST(SharedLibNonInitializedTest, DataSourceTrace) {
   EXPECT_FALSE(PERFETTO_ATOMIC_LOAD(data_source_1.enabled));
 
@@ -2768,6 +3113,56 @@ TEST_F(SharedLibTrackEventTest, TrackEventHlCorrelationId) {
                   ElementsAre(StringField("req-5678"))))))));
 }
 
+TEST_F(SharedLibTrackEventTest, TrackEventHlSourceLocation) {
+  // Field numbers of SourceLocation, which has no public C header.
+  constexpr uint32_t kSourceLocationFileNameFieldNumber = 2;
+  constexpr uint32_t kSourceLocationLineNumberFieldNumber = 4;
+  TracingSession tracing_session = TracingSession::Builder()
+                                       .set_data_source_name("track_event")
+                                       .add_enabled_category("*")
+                                       .Build();
+
+  for (int i = 0; i < 2; i++) {
+    PERFETTO_TE(cat1, PERFETTO_TE_INSTANT("event"),
+                PERFETTO_TE_SOURCE_LOCATION("file.cc", "Function", 42));
+  }
+
+  tracing_session.StopBlocking();
+  std::vector<uint8_t> data = tracing_session.ReadBlocking();
+
+  // The location is interned once and referenced by both events.
+  EXPECT_THAT(
+      FieldView(data),
+      Contains(PbField(
+          perfetto_protos_Trace_packet_field_number,
+          MsgField(Contains(PbField(
+              perfetto_protos_TracePacket_interned_data_field_number,
+              MsgField(Contains(PbField(
+                  perfetto_protos_InternedData_source_locations_field_number,
+                  MsgField(AllOf(
+                      Contains(PbField(kSourceLocationFileNameFieldNumber,
+                                       StringField("file.cc"))),
+                      Contains(PbField(kSourceLocationLineNumberFieldNumber,
+                                       VarIntField(42))))))))))))));
+  size_t events = 0;
+  for (struct PerfettoPbDecoderField trace_field : FieldView(data)) {
+    ASSERT_THAT(trace_field, PbField(perfetto_protos_Trace_packet_field_number,
+                                     MsgField(_)));
+    IdFieldView track_event(
+        trace_field, perfetto_protos_TracePacket_track_event_field_number);
+    if (track_event.size() == 0) {
+      continue;
+    }
+    events++;
+    EXPECT_THAT(
+        track_event,
+        ElementsAre(AllFieldsWithId(
+            perfetto_protos_TrackEvent_source_location_iid_field_number,
+            ElementsAre(VarIntField(_)))));
+  }
+  EXPECT_EQ(events, 2u);
+}
+
 TEST_F(SharedLibTrackEventTest, TrackEventIsCategoryEnabled) {
   ASSERT_FALSE(PERFETTO_TE_IS_CATEGORY_ENABLED(cat1));
 
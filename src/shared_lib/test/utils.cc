@@ -121,8 +121,7 @@ std::vector<uint8_t> TracingSession::Builder::BuildProtoConfig() {
 TracingSession TracingSession::Builder::Build() {
   std::vector<uint8_t> config = BuildProtoConfig();
 
-  struct PerfettoTracingSessionImpl* ts =
-      PerfettoTracingSessionCreate(PERFETTO_BACKEND_IN_PROCESS);
+  struct PerfettoTracingSessionImpl* ts = PerfettoTracingSessionCreate(backend_);
 
   PerfettoTracingSessionSetup(ts, config.data(), config.size());
 
@@ -208,6 +207,20 @@ std::vector<uint8_t> TracingSession::ReadBlocking() {
   return data;
 }
 
+std::vector<uint8_t> TracingSession::GetTraceStatsBlocking() {
+  std::vector<uint8_t> stats;
+  PerfettoTracingSessionGetTraceStatsBlocking(
+      session_,
+      [](struct PerfettoTracingSessionImpl*, const void* data, size_t size,
+         void* user_arg) {
+        auto& dst = *static_cast<std::vector<uint8_t>*>(user_arg);
+        auto* src = static_cast<const uint8_t*>(data);
+        dst.insert(dst.end(), src, src + size);
+      },
+      &stats);
+  return stats;
+}
+
 }  // namespace test_utils
 }  // namespace shlib
 }  // namespace perfetto
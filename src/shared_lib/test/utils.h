@@ -90,6 +90,10 @@ class TracingSession {
       enable_protovm_config_ = true;
       return *this;
     }
+    Builder& set_backend(PerfettoBackendTypes backend) {
+      backend_ = backend;
+      return *this;
+    }
     std::vector<uint8_t> BuildProtoConfig();
 
     TracingSession Build();
@@ -100,6 +104,7 @@ class TracingSession {
     std::vector<std::string> disabled_categories_;
     uint32_t clear_period_ms_ = 0;
     bool enable_protovm_config_ = false;
+    PerfettoBackendTypes backend_ = PERFETTO_BACKEND_IN_PROCESS;
   };
 
   static TracingSession Adopt(struct PerfettoTracingSessionImpl*);
@@ -118,6 +123,8 @@ class TracingSession {
   // Equivalent to StopAsync() + WaitForStopped().
   void StopBlocking();
   std::vector<uint8_t> ReadBlocking();
+  // Returns the encoded perfetto.protos.TraceStats of the tracing session.
+  std::vector<uint8_t> GetTraceStatsBlocking();
 
  private:
   TracingSession() = default;
@@ -45,6 +45,18 @@ void PerfettoTracingSessionSetup(struct PerfettoTracingSessionImpl* session,
   ts->Setup(cfg);
 }
 
+void PerfettoTracingSessionSetupWithFd(
+    struct PerfettoTracingSessionImpl* session,
+    void* cfg_begin,
+    size_t cfg_len,
+    int fd) {
+  auto* ts = reinterpret_cast<perfetto::TracingSession*>(session);
+  perfetto::TraceConfig cfg;
+  cfg.ParseFromArray(cfg_begin, cfg_len);
+  cfg.set_write_into_file(true);
+  ts->Setup(cfg, fd);
+}
+
 void PerfettoTracingSessionSetStopCb(struct PerfettoTracingSessionImpl* session,
                                      PerfettoTracingSessionStopCb cb,
                                      void* user_arg) {
@@ -86,6 +98,60 @@ bool PerfettoTracingSessionFlushBlocking(
   return ts->FlushBlocking(timeout_ms);
 }
 
+bool PerfettoTracingSessionDetachBlocking(
+    struct PerfettoTracingSessionImpl* session,
+    const char* key) {
+  auto* ts = reinterpret_cast<perfetto::TracingSession*>(session);
+  return ts->DetachBlocking(key);
+}
+
+bool PerfettoTracingSessionAttachBlocking(
+    struct PerfettoTracingSessionImpl* session,
+    const char* key) {
+  auto* ts = reinterpret_cast<perfetto::TracingSession*>(session);
+  return ts->AttachBlocking(key);
+}
+
+bool PerfettoTracingSessionCloneBlocking(
+    struct PerfettoTracingSessionImpl* session,
+    const char* unique_session_name) {
+  auto* ts = reinterpret_cast<perfetto::TracingSession*>(session);
+
+  std::mutex mutex;
+  std::condition_variable cv;
+
+  bool done = false;
+  bool success = false;
+
+  perfetto::TracingSession::CloneTraceArgs args;
+  args.unique_session_name = unique_session_name;
+  ts->CloneTrace(args, [&mutex, &cv, &done, &success](
+                           perfetto::TracingSession::CloneTraceCallbackArgs
+                               result) {
+    std::unique_lock<std::mutex> lock(mutex);
+    success = result.success;
+    done = true;
+    cv.notify_one();
+  });
+
+  std::unique_lock<std::mutex> lock(mutex);
+  cv.wait(lock, [&done] { return done; });
+  return success;
+}
+
+bool PerfettoTracingSessionGetTraceStatsBlocking(
+    struct PerfettoTracingSessionImpl* session,
+    PerfettoTracingSessionQueryCb callback,
+    void* user_arg) {
+  auto* ts = reinterpret_cast<perfetto::TracingSession*>(session);
+  auto args = ts->GetTraceStatsBlocking();
+  if (!args.success)
+    return false;
+  callback(session, static_cast<const void*>(args.trace_stats_data.data()),
+           args.trace_stats_data.size(), user_arg);
+  return true;
+}
+
 void PerfettoTracingSessionStopAsync(
     struct PerfettoTracingSessionImpl* session) {
   auto* ts = reinterpret_cast<perfetto::TracingSession*>(session);
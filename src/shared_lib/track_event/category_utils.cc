@@ -15,8 +15,10 @@
  */
 
 #include "src/shared_lib/track_event/category_utils.h"
+#include <atomic>
 #include <functional>
 #include "perfetto/ext/base/string_view.h"
+#include "perfetto/tracing/internal/track_event_internal.h"
 
 namespace perfetto::shlib {
 
@@ -24,6 +26,9 @@ namespace {
 
 enum class MatchType { kExact, kPattern };
 
+std::atomic<PerfettoTeCategoryFiltering> g_category_filtering{
+    PERFETTO_TE_CATEGORY_FILTERING_C};
+
 bool NameMatchesPattern(const std::string& pattern,
                         const perfetto::base::StringView& name,
                         MatchType match_type) {
@@ -49,9 +54,7 @@ bool NameMatchesPatternList(const std::vector<std::string>& patterns,
   return false;
 }
 
-}  // namespace
-
-bool IsSingleCategoryEnabled(
+bool IsSingleCategoryEnabledC(
     const PerfettoTeCategoryDescriptor& c,
     const perfetto::protos::gen::TrackEventConfig& config) {
   auto has_matching_tag = [&](std::function<bool(const char*)> matcher) {
@@ -98,6 +101,21 @@ bool IsSingleCategoryEnabled(
   return false;
 }
 
+}  // namespace
+
+bool IsSingleCategoryEnabled(
+    const PerfettoTeCategoryDescriptor& c,
+    const perfetto::protos::gen::TrackEventConfig& config) {
+  switch (g_category_filtering.load(std::memory_order_relaxed)) {
+    case PERFETTO_TE_CATEGORY_FILTERING_CPP:
+      return perfetto::internal::TrackEventInternal::IsSingleCategoryEnabled(
+          config, c.name, c.tags, c.num_tags);
+    case PERFETTO_TE_CATEGORY_FILTERING_C:
+      break;
+  }
+  return IsSingleCategoryEnabledC(c, config);
+}
+
 void SerializeCategory(const PerfettoTeCategoryDescriptor& desc,
                        perfetto::protos::pbzero::TrackEventDescriptor* ted) {
   auto* c = ted->add_available_categories();
@@ -110,3 +128,7 @@ void SerializeCategory(const PerfettoTeCategoryDescriptor& desc,
 }
 
 }  // namespace perfetto::shlib
+
+void PerfettoTeSetCategoryFiltering(enum PerfettoTeCategoryFiltering mode) {
+  perfetto::shlib::g_category_filtering.store(mode, std::memory_order_relaxed);
+}
@@ -0,0 +1,99 @@
+/*
+ * Copyright (C) 2025 The Android Open Source Project
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *      http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ */
+
+#include "src/shared_lib/track_event/category_utils.h"
+
+#include <utility>
+
+#include "perfetto/tracing/internal/track_event_internal.h"
+#include "perfetto/tracing/track_event_category_registry.h"
+
+#include "test/gtest_and_gmock.h"
+
+namespace perfetto::shlib {
+namespace {
+
+class CategoryUtilsTest : public testing::Test {
+ protected:
+  void TearDown() override {
+    PerfettoTeSetCategoryFiltering(PERFETTO_TE_CATEGORY_FILTERING_C);
+  }
+
+  // Returns whether the category is enabled by `config_` in C and in C++ mode,
+  // and checks that the C++ mode matches the C++ TrackEvent API.
+  std::pair<bool, bool> IsEnabled(const Category& category) {
+    PerfettoTeCategoryDescriptor desc{};
+    desc.name = category.name;
+    Category::Tags tags = category.tags;
+    while (desc.num_tags < tags.size() && tags[desc.num_tags])
+      desc.num_tags++;
+    desc.tags = tags.data();
+
+    PerfettoTeSetCategoryFiltering(PERFETTO_TE_CATEGORY_FILTERING_C);
+    bool c_enabled = IsSingleCategoryEnabled(desc, config_);
+    PerfettoTeSetCategoryFiltering(PERFETTO_TE_CATEGORY_FILTERING_CPP);
+    bool cpp_enabled = IsSingleCategoryEnabled(desc, config_);
+
+    internal::TrackEventCategoryRegistry registry(0, nullptr, nullptr);
+    EXPECT_EQ(cpp_enabled, internal::TrackEventInternal::IsCategoryEnabled(
+                               registry, config_, category));
+    return {c_enabled, cpp_enabled};
+  }
+
+  protos::gen::TrackEventConfig config_;
+};
+
+TEST_F(CategoryUtilsTest, EmptyConfig) {
+  // Nothing matches: disabled in C mode, enabled in C++ mode.
+  EXPECT_EQ(IsEnabled(Category("cat")), std::make_pair(false, true));
+  // The "slow" and "debug" tags are disabled by default in C++ mode.
+  EXPECT_EQ(IsEnabled(Category("cat").SetTags("slow")),
+            std::make_pair(false, false));
+  EXPECT_EQ(IsEnabled(Category("cat").SetTags("debug")),
+            std::make_pair(false, false));
+}
+
+TEST_F(CategoryUtilsTest, CategoriesAndTags) {
+  config_.add_enabled_categories("enabled");
+  config_.add_enabled_categories("prefix*");
+  config_.add_disabled_categories("disabled");
+  config_.add_enabled_tags("enabled_tag");
+
+  EXPECT_EQ(IsEnabled(Category("enabled")), std::make_pair(true, true));
+  EXPECT_EQ(IsEnabled(Category("prefix_cat")), std::make_pair(true, true));
+  EXPECT_EQ(IsEnabled(Category("disabled")), std::make_pair(false, false));
+  EXPECT_EQ(IsEnabled(Category("other")), std::make_pair(false, true));
+  EXPECT_EQ(IsEnabled(Category("other").SetTags("enabled_tag")),
+            std::make_pair(true, true));
+  // Enabled tags take precedence over disabled categories in C mode only.
+  EXPECT_EQ(IsEnabled(Category("disabled").SetTags("enabled_tag")),
+            std::make_pair(true, false));
+  // The "slow" tag isn't disabled by default if the config lists tags.
+  EXPECT_EQ(IsEnabled(Category("other").SetTags("slow")),
+            std::make_pair(false, true));
+}
+
+TEST_F(CategoryUtilsTest, Wildcard) {
+  config_.add_enabled_categories("*");
+  config_.add_disabled_categories("disabled*");
+
+  EXPECT_EQ(IsEnabled(Category("cat")), std::make_pair(true, true));
+  // In C++ mode, the "*" wildcard is only tried after the other patterns.
+  EXPECT_EQ(IsEnabled(Category("disabled_cat")), std::make_pair(true, false));
+}
+
+}  // namespace
+}  // namespace perfetto::shlib
@@ -16,7 +16,10 @@
 
 #include "perfetto/public/abi/track_event_hl_abi.h"
 
+#include <string>
+
 #include "perfetto/tracing/internal/track_event_internal.h"
+#include "protos/perfetto/trace/track_event/source_location.pbzero.h"
 #include "src/shared_lib/track_event/ds.h"
 #include "src/shared_lib/track_event/serialization.h"
 
@@ -283,6 +286,47 @@ void WriteTrackEvent(TrackEventIncrementalState* incr,
     }
   }
 
+  for (const auto* it = extra_data; *it != nullptr; it++) {
+    const struct PerfettoTeHlExtra& extra = **it;
+    if (extra.type == PERFETTO_TE_HL_EXTRA_TYPE_SOURCE_LOCATION &&
+        type != protos::pbzero::TrackEvent::TYPE_SLICE_END) {
+      const auto& loc =
+          reinterpret_cast<const struct PerfettoTeHlExtraSourceLocation&>(
+              extra);
+      const char* function_name = loc.function_name ? loc.function_name : "";
+      if (use_interning) {
+        // Locations are interned by value: the same location can be passed
+        // with different string pointers.
+        std::string key(loc.file_name);
+        key.push_back('\0');
+        key.append(function_name);
+        key.push_back('\0');
+        key.append(reinterpret_cast<const char*>(&loc.line_number),
+                   sizeof(loc.line_number));
+        auto res = incr->iids.FindOrAssign(
+            protos::pbzero::InternedData::kSourceLocationsFieldNumber,
+            key.data(), key.size());
+        if (res.newly_assigned) {
+          auto* ser = incr->serialized_interned_data->add_source_locations();
+          ser->set_iid(res.iid);
+          ser->set_file_name(loc.file_name);
+          if (*function_name) {
+            ser->set_function_name(function_name);
+          }
+          ser->set_line_number(loc.line_number);
+        }
+        event->set_source_location_iid(res.iid);
+      } else {
+        auto* ser = event->set_source_location();
+        ser->set_file_name(loc.file_name);
+        if (*function_name) {
+          ser->set_function_name(function_name);
+        }
+        ser->set_line_number(loc.line_number);
+      }
+    }
+  }
+
   for (const auto* it = extra_data; *it != nullptr; it++) {
     const struct PerfettoTeHlExtra& extra = **it;
     if (extra.type == PERFETTO_TE_HL_EXTRA_TYPE_PROTO_FIELDS) {
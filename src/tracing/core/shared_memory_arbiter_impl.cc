@@ -17,6 +17,7 @@
 #include "src/tracing/core/shared_memory_arbiter_impl.h"
 
 #include <algorithm>
+#include <atomic>
 #include <limits>
 #include <utility>
 
@@ -42,6 +43,36 @@ constexpr size_t kMaxCommitDataRequestChunkSize =
     128 * 1024 - 512;  // This is ipc::kIPCBufferSize - 512, see
                        // |kMaxTracePacketSliceSize| in tracing_service_impl.h
 
+// Number of chunk requests, across all the arbiters of the process, that
+// stalled because the shared memory buffer was full, and that were dropped
+// because it stayed full or stalling wasn't allowed.
+std::atomic<uint64_t> g_stalled_chunk_requests{0};
+std::atomic<uint64_t> g_dropped_chunk_requests{0};
+
+// Same as above, for the chunk requests of the calling thread only. Only
+// counted if g_thread_chunk_request_stats_enabled is set.
+thread_local uint64_t g_thread_stalled_chunk_requests = 0;
+thread_local uint64_t g_thread_dropped_chunk_requests = 0;
+std::atomic<bool> g_thread_chunk_request_stats_enabled{false};
+
+// Deadline until which the chunk requests of the calling thread stall when the
+// shared memory buffer is full, regardless of their BufferExhaustedPolicy. Only
+// used if g_thread_stall_deadline_set is set.
+thread_local bool g_thread_stall_deadline_set = false;
+thread_local base::TimeNanos g_thread_stall_deadline;
+
+void CountStalledChunkRequest() {
+  g_stalled_chunk_requests.fetch_add(1, std::memory_order_relaxed);
+  if (g_thread_chunk_request_stats_enabled.load(std::memory_order_relaxed))
+    g_thread_stalled_chunk_requests++;
+}
+
+void CountDroppedChunkRequest() {
+  g_dropped_chunk_requests.fetch_add(1, std::memory_order_relaxed);
+  if (g_thread_chunk_request_stats_enabled.load(std::memory_order_relaxed))
+    g_thread_dropped_chunk_requests++;
+}
+
 // Conservative upper bound of a ChunkToPatch's serialized size, used to bound
 // how many patches are packed into one request. Over-estimating only causes an
 // extra split, never an oversized frame.
@@ -140,6 +171,14 @@ Chunk SharedMemoryArbiterImpl::GetNewChunk(
       break;
   }
 
+  // A stall timeout of the thread overrides the policy. Stalling is not
+  // supported if we were ever unbound (see below), so drop instead.
+  const bool has_deadline = g_thread_stall_deadline_set;
+  if (has_deadline) {
+    should_stall = was_always_bound_;
+    should_abort = false;
+  }
+
   for (;;) {
     // TODO(primiano): Probably this lock is not really required and this code
     // could be rewritten leveraging only the Try* atomic operations in
@@ -217,6 +256,7 @@ Chunk SharedMemoryArbiterImpl::GetNewChunk(
 
     if (!should_stall) {
       PERFETTO_DLOG("Shared memory buffer exhausted, returning invalid Chunk!");
+      CountDroppedChunkRequest();
       return Chunk();
     }
 
@@ -225,11 +265,21 @@ Chunk SharedMemoryArbiterImpl::GetNewChunk(
 
     // All chunks are taken (either kBeingWritten by us or kBeingRead by the
     // Service).
+    if (stall_count == 0)
+      CountStalledChunkRequest();
     if (stall_count++ == kLogAfterNStalls) {
       PERFETTO_DLOG("Shared memory buffer overrun! Stalling");
     }
 
-    if (stall_count == kAssertAtNStalls) {
+    base::TimeNanos remaining{};
+    if (has_deadline) {
+      remaining = g_thread_stall_deadline - base::GetWallTimeNs();
+      if (remaining.count() <= 0) {
+        PERFETTO_DLOG("Stall timeout exceeded, returning invalid Chunk!");
+        CountDroppedChunkRequest();
+        return Chunk();
+      }
+    } else if (stall_count == kAssertAtNStalls) {
       if (should_abort) {
         Stats stats = GetStats();
         PERFETTO_FATAL(
@@ -241,6 +291,7 @@ Chunk SharedMemoryArbiterImpl::GetNewChunk(
       } else {
         PERFETTO_DLOG(
             "Shared memory buffer exhausted, returning invalid Chunk!");
+        CountDroppedChunkRequest();
         return Chunk();
       }
     }
@@ -267,7 +318,16 @@ Chunk SharedMemoryArbiterImpl::GetNewChunk(
       // commits (crbug.com/919187#c28).
       FlushPendingCommitDataRequests();
     } else {
-      base::SleepMicroseconds(stall_interval_us);
+      unsigned sleep_us = stall_interval_us;
+      if (has_deadline) {
+        // Wake up at the deadline at the latest.
+        auto remaining_us =
+            std::chrono::duration_cast<std::chrono::microseconds>(remaining)
+                .count();
+        sleep_us = static_cast<unsigned>(
+            std::min<int64_t>(sleep_us, remaining_us));
+      }
+      base::SleepMicroseconds(sleep_us);
       stall_interval_us =
           std::min(kMaxStallIntervalUs, (stall_interval_us + 1) * 8);
     }
@@ -908,6 +968,47 @@ void SharedMemoryArbiterImpl::BindStartupTargetBufferImpl(
     FlushPendingCommitDataRequests(flush_callback);
 }
 
+// static
+uint64_t SharedMemoryArbiterImpl::stalled_chunk_requests() {
+  return g_stalled_chunk_requests.load(std::memory_order_relaxed);
+}
+
+// static
+uint64_t SharedMemoryArbiterImpl::dropped_chunk_requests() {
+  return g_dropped_chunk_requests.load(std::memory_order_relaxed);
+}
+
+// static
+uint64_t SharedMemoryArbiterImpl::thread_stalled_chunk_requests() {
+  return g_thread_stalled_chunk_requests;
+}
+
+// static
+uint64_t SharedMemoryArbiterImpl::thread_dropped_chunk_requests() {
+  return g_thread_dropped_chunk_requests;
+}
+
+// static
+void SharedMemoryArbiterImpl::SetThreadChunkRequestStatsEnabled(bool enabled) {
+  g_thread_chunk_request_stats_enabled.store(enabled,
+                                             std::memory_order_relaxed);
+}
+
+// static
+void SharedMemoryArbiterImpl::SetThreadStallTimeout(uint64_t timeout_ns) {
+  // Saturate instead of overflowing for very long timeouts.
+  const uint64_t kMaxTimeoutNs =
+      static_cast<uint64_t>(std::numeric_limits<int64_t>::max() / 2);
+  auto timeout = static_cast<int64_t>(std::min(timeout_ns, kMaxTimeoutNs));
+  g_thread_stall_deadline = base::GetWallTimeNs() + base::TimeNanos(timeout);
+  g_thread_stall_deadline_set = true;
+}
+
+// static
+void SharedMemoryArbiterImpl::ClearThreadStallTimeout() {
+  g_thread_stall_deadline_set = false;
+}
+
 SharedMemoryArbiterImpl::Stats SharedMemoryArbiterImpl::GetStats() {
   std::lock_guard<base::MaybeRtMutex> scoped_lock(lock_);
   Stats res;
@@ -162,6 +162,34 @@ class SharedMemoryArbiterImpl : public SharedMemoryArbiter {
     return default_page_layout;
   }
 
+  // Number of GetNewChunk() calls, across all the arbiters of the process,
+  // that stalled because the shared memory buffer was full.
+  static uint64_t stalled_chunk_requests();
+
+  // Number of GetNewChunk() calls, across all the arbiters of the process,
+  // that returned an invalid chunk because the shared memory buffer was full.
+  static uint64_t dropped_chunk_requests();
+
+  // Same as stalled_chunk_requests() and dropped_chunk_requests(), for the
+  // GetNewChunk() calls of the calling thread only. Only counted while
+  // enabled by SetThreadChunkRequestStatsEnabled().
+  static uint64_t thread_stalled_chunk_requests();
+  static uint64_t thread_dropped_chunk_requests();
+
+  // Enables or disables counting the chunk requests of each thread. Disabled
+  // by default.
+  static void SetThreadChunkRequestStatsEnabled(bool enabled);
+
+  // Makes the GetNewChunk() calls of the calling thread stall when the shared
+  // memory buffer is full, until |timeout_ns| from now have passed, and then
+  // drop data, regardless of their BufferExhaustedPolicy. Calls on an arbiter
+  // that was ever unbound drop data right away, as they can't stall.
+  static void SetThreadStallTimeout(uint64_t timeout_ns);
+
+  // Restores the BufferExhaustedPolicy of the GetNewChunk() calls of the
+  // calling thread, see SetThreadStallTimeout().
+  static void ClearThreadStallTimeout();
+
   // F is lambda with signature:
   // void(SharedMemoryABI::Chunk*, bool chunk_complete,
   //      uint16_t packet_count, uint8_t packet_flags)
@@ -17,6 +17,9 @@
 #include "src/tracing/core/shared_memory_arbiter_impl.h"
 
 #include <bitset>
+#include <thread>
+
+#include "perfetto/base/time.h"
 #include "perfetto/ext/base/utils.h"
 #include "perfetto/ext/tracing/core/basic_types.h"
 #include "perfetto/ext/tracing/core/commit_data_request.h"
@@ -577,9 +580,33 @@ TEST_P(SharedMemoryArbiterImplTest, BufferExhaustedPolicyDrop) {
 
   // SMB is exhausted, thus GetNewChunk() should return an invalid chunk. In
   // kStall mode, this would stall.
+  uint64_t dropped = SharedMemoryArbiterImpl::dropped_chunk_requests();
+  uint64_t thread_dropped =
+      SharedMemoryArbiterImpl::thread_dropped_chunk_requests();
   SharedMemoryABI::Chunk invalid_chunk =
       arbiter_->GetNewChunk({}, BufferExhaustedPolicy::kDrop);
   ASSERT_FALSE(invalid_chunk.is_valid());
+  EXPECT_GE(SharedMemoryArbiterImpl::dropped_chunk_requests(), dropped + 1);
+  // The requests of the thread are only counted once enabled.
+  EXPECT_EQ(SharedMemoryArbiterImpl::thread_dropped_chunk_requests(),
+            thread_dropped);
+
+  SharedMemoryArbiterImpl::SetThreadChunkRequestStatsEnabled(true);
+  EXPECT_FALSE(
+      arbiter_->GetNewChunk({}, BufferExhaustedPolicy::kDrop).is_valid());
+  EXPECT_GE(SharedMemoryArbiterImpl::dropped_chunk_requests(), dropped + 2);
+  EXPECT_EQ(SharedMemoryArbiterImpl::thread_dropped_chunk_requests(),
+            thread_dropped + 1);
+
+  // Requests from other threads are only counted in the process-wide total.
+  std::thread([&] {
+    EXPECT_FALSE(
+        arbiter_->GetNewChunk({}, BufferExhaustedPolicy::kDrop).is_valid());
+  }).join();
+  EXPECT_GE(SharedMemoryArbiterImpl::dropped_chunk_requests(), dropped + 3);
+  EXPECT_EQ(SharedMemoryArbiterImpl::thread_dropped_chunk_requests(),
+            thread_dropped + 1);
+  SharedMemoryArbiterImpl::SetThreadChunkRequestStatsEnabled(false);
 
   // Returning the chunk is not enough to be able to reacquire it.
   PatchList ignored;
@@ -598,6 +625,57 @@ TEST_P(SharedMemoryArbiterImplTest, BufferExhaustedPolicyDrop) {
   ASSERT_TRUE(chunks[0].is_valid());
 }
 
+// Verify that a stall timeout of the thread makes GetNewChunk() stall, even
+// with the kDrop policy, and drop data once the timeout expires.
+TEST_P(SharedMemoryArbiterImplTest, ThreadStallTimeout) {
+  SharedMemoryArbiterImpl::set_default_layout_for_testing(
+      SharedMemoryABI::PageLayout::kPageDiv1);
+  static constexpr size_t kTotChunks = kNumPages;
+  SharedMemoryABI::Chunk chunks[kTotChunks];
+  for (size_t i = 0; i < kTotChunks; i++) {
+    chunks[i] = arbiter_->GetNewChunk({}, BufferExhaustedPolicy::kDrop);
+    ASSERT_TRUE(chunks[i].is_valid());
+  }
+
+  uint64_t stalled = SharedMemoryArbiterImpl::stalled_chunk_requests();
+  uint64_t dropped = SharedMemoryArbiterImpl::dropped_chunk_requests();
+  SharedMemoryArbiterImpl::SetThreadStallTimeout(10 * 1000 * 1000);
+  auto start = base::GetWallTimeNs();
+  EXPECT_FALSE(
+      arbiter_->GetNewChunk({}, BufferExhaustedPolicy::kDrop).is_valid());
+  EXPECT_GE(base::GetWallTimeNs() - start, base::TimeMillis(10));
+  EXPECT_GE(SharedMemoryArbiterImpl::stalled_chunk_requests(), stalled + 1);
+  EXPECT_GE(SharedMemoryArbiterImpl::dropped_chunk_requests(), dropped + 1);
+
+  // The timeout covers all the requests until it's cleared, instead of each
+  // of them, so the next request doesn't stall again.
+  start = base::GetWallTimeNs();
+  EXPECT_FALSE(
+      arbiter_->GetNewChunk({}, BufferExhaustedPolicy::kDrop).is_valid());
+  EXPECT_LT(base::GetWallTimeNs() - start, base::TimeMillis(10));
+
+  // The stall recovers once a chunk is freed before the timeout.
+  SharedMemoryArbiterImpl::SetThreadStallTimeout(
+      uint64_t{60} * 1000 * 1000 * 1000);
+  PatchList ignored;
+  arbiter_->ReturnCompletedChunk(std::move(chunks[0]), 1, &ignored);
+  std::thread releaser([&] {
+    auto* abi = arbiter_->shmem_abi_for_testing();
+    SharedMemoryABI::Chunk chunk;
+    while (!chunk.is_valid())
+      chunk = abi->TryAcquireChunkForReading(0, 0);
+    abi->ReleaseChunkAsFree(std::move(chunk));
+  });
+  chunks[0] = arbiter_->GetNewChunk({}, BufferExhaustedPolicy::kDrop);
+  releaser.join();
+  EXPECT_TRUE(chunks[0].is_valid());
+  SharedMemoryArbiterImpl::ClearThreadStallTimeout();
+
+  // Without a timeout, the policy applies again.
+  EXPECT_FALSE(
+      arbiter_->GetNewChunk({}, BufferExhaustedPolicy::kDrop).is_valid());
+}
+
 TEST_P(SharedMemoryArbiterImplTest, CreateUnboundAndBind) {
   auto checkpoint_writer = task_runner_->CreateCheckpoint("writer_registered");
   auto checkpoint_flush = task_runner_->CreateCheckpoint("flush_completed");
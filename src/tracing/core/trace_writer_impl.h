@@ -71,6 +71,7 @@ class TraceWriterImpl : public TraceWriter,
     return protobuf_stream_writer_.written();
   }
   uint64_t drop_count() const override { return drop_count_; }
+  bool is_dropping_packets() const override { return drop_packets_; }
 
   bool drop_packets_for_testing() const { return drop_packets_; }
 
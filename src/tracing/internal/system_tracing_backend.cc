@@ -65,12 +65,15 @@ std::unique_ptr<ProducerEndpoint> SystemProducerTracingBackend::ConnectProducer(
         shm.get(), shmem_page_size_hint, SharedMemoryABI::ShmemMode::kDefault);
   }
 
-  ipc::Client::ConnArgs conn_args(GetProducerSocket(), true);
+  ipc::Client::ConnArgs conn_args(args.producer_socket_name.empty()
+                                      ? GetProducerSocket()
+                                      : args.producer_socket_name.c_str(),
+                                  true);
   auto endpoint = ProducerIPCClient::Connect(
       std::move(conn_args), args.producer, args.producer_name, args.task_runner,
       TracingService::ProducerSMBScrapingMode::kEnabled, shmem_size_hint,
       shmem_page_size_hint, std::move(shm), std::move(arbiter),
-      args.create_socket_async);
+      args.create_socket_async, args.use_shmem_emulation);
   PERFETTO_CHECK(endpoint);
   return endpoint;
 }
@@ -79,6 +79,8 @@ class UnsupportedProducerEndpoint : public ProducerEndpoint {
   void NotifyFlushComplete(FlushRequestID) override {}
   void NotifyDataSourceStarted(DataSourceInstanceID) override {}
   void NotifyDataSourceStopped(DataSourceInstanceID) override {}
+  void NotifyDataSourceSetupFailed(DataSourceInstanceID,
+                                   const std::string&) override {}
   void ActivateTriggers(const std::vector<std::string>&) override {}
 
   void Sync(std::function<void()> callback) override {
@@ -468,6 +468,10 @@ void TracingMuxerImpl::ConsumerImpl::OnConnect() {
     service_->CloneSession(std::move(*session_to_clone_));
     session_to_clone_ = std::nullopt;
   }
+  if (session_to_attach_) {
+    service_->Attach(*session_to_attach_);
+    session_to_attach_ = std::nullopt;
+  }
 
   if (stop_pending_)
     muxer_->StopTracingSession(session_id_);
@@ -666,9 +670,34 @@ void TracingMuxerImpl::ConsumerImpl::OnTraceStats(
   get_trace_stats_callback_ = nullptr;
 }
 
-// The callbacks below are not used.
-void TracingMuxerImpl::ConsumerImpl::OnDetach(bool) {}
-void TracingMuxerImpl::ConsumerImpl::OnAttach(bool, const TraceConfig&) {}
+void TracingMuxerImpl::ConsumerImpl::OnDetach(bool success) {
+  PERFETTO_DCHECK_THREAD(thread_checker_);
+  if (success) {
+    // The service no longer associates the session with this consumer, so
+    // treat it as stopped.
+    stopped_ = true;
+    trace_config_.reset();
+  }
+  if (!detach_callback_)
+    return;
+  muxer_->task_runner_->PostTask(
+      std::bind(std::move(detach_callback_), success));
+  detach_callback_ = nullptr;
+}
+
+void TracingMuxerImpl::ConsumerImpl::OnAttach(bool success,
+                                              const TraceConfig& trace_config) {
+  PERFETTO_DCHECK_THREAD(thread_checker_);
+  if (success) {
+    stopped_ = false;
+    trace_config_ = std::make_shared<TraceConfig>(trace_config);
+  }
+  if (!attach_callback_)
+    return;
+  muxer_->task_runner_->PostTask(
+      std::bind(std::move(attach_callback_), success));
+  attach_callback_ = nullptr;
+}
 // ----- End of TracingMuxerImpl::ConsumerImpl
 
 // ----- Begin of TracingMuxerImpl::TracingSessionImpl
@@ -732,6 +761,26 @@ void TracingMuxerImpl::TracingSessionImpl::CloneTrace(CloneTraceArgs args,
   });
 }
 
+// Can be called from any thread.
+void TracingMuxerImpl::TracingSessionImpl::Detach(const std::string& key,
+                                                  DetachCallback cb) {
+  auto* muxer = muxer_;
+  auto session_id = session_id_;
+  muxer->task_runner_->PostTask([muxer, session_id, key, cb] {
+    muxer->DetachTracingSession(session_id, key, std::move(cb));
+  });
+}
+
+// Can be called from any thread.
+void TracingMuxerImpl::TracingSessionImpl::Attach(const std::string& key,
+                                                  AttachCallback cb) {
+  auto* muxer = muxer_;
+  auto session_id = session_id_;
+  muxer->task_runner_->PostTask([muxer, session_id, key, cb] {
+    muxer->AttachTracingSession(session_id, key, std::move(cb));
+  });
+}
+
 // Can be called from any thread.
 void TracingMuxerImpl::TracingSessionImpl::ChangeTraceConfig(
     const TraceConfig& cfg) {
@@ -1016,6 +1065,8 @@ void TracingMuxerImpl::AddProducerBackend(TracingProducerBackend* backend,
       args.shmem_page_size_hint_kb * 1024;
   rb.producer_conn_args.create_socket_async = args.create_socket_async;
   rb.producer_conn_args.machine_id = args.machine_id;
+  rb.producer_conn_args.producer_socket_name = args.producer_socket_name;
+  rb.producer_conn_args.use_shmem_emulation = args.shmem_emulation_enabled;
   rb.producer->Initialize(rb.backend->ConnectProducer(rb.producer_conn_args));
 }
 
@@ -1414,15 +1465,27 @@ TracingMuxerImpl::FindDataSourceRes TracingMuxerImpl::SetupDataSourceImpl(
     // DataSource::Trace().
     static_state.valid_instances.fetch_or(1 << i, std::memory_order_release);
 
+    std::string setup_error;
     DataSourceBase::SetupArgs setup_args;
     setup_args.config = &cfg;
     setup_args.backend_type = backend.type;
     setup_args.internal_instance_index = i;
+    setup_args.setup_error = &setup_error;
 
     if (!rds.params.requires_callbacks_under_lock)
       lock.unlock();
     internal_state->data_source->OnSetup(setup_args);
 
+    if (!setup_error.empty() && instance_id) {
+      ProducerImpl* producer = backend.producer.get();
+      if (producer && producer->connected_ &&
+          producer->connection_id_.load(std::memory_order_relaxed) ==
+              backend_connection_id) {
+        producer->service_->NotifyDataSourceSetupFailed(instance_id,
+                                                        setup_error);
+      }
+    }
+
     return FindDataSourceRes(&static_state, internal_state, i,
                              rds.params.requires_callbacks_under_lock);
   }
@@ -2009,6 +2072,43 @@ void TracingMuxerImpl::CloneTracingSession(
   consumer->service_->CloneSession(std::move(consumer_args));
 }
 
+void TracingMuxerImpl::DetachTracingSession(
+    TracingSessionGlobalID session_id,
+    const std::string& key,
+    TracingSession::DetachCallback callback) {
+  PERFETTO_DCHECK_THREAD(thread_checker_);
+  auto* consumer = FindConsumer(session_id);
+  if (!consumer || !consumer->connected_ || !consumer->trace_config_ ||
+      consumer->stopped_) {
+    PERFETTO_ELOG("Must call Setup(config) and Start() first");
+    callback(false);
+    return;
+  }
+  PERFETTO_DCHECK(!consumer->detach_callback_);
+  consumer->detach_callback_ = std::move(callback);
+  consumer->service_->Detach(key);
+}
+
+void TracingMuxerImpl::AttachTracingSession(
+    TracingSessionGlobalID session_id,
+    const std::string& key,
+    TracingSession::AttachCallback callback) {
+  PERFETTO_DCHECK_THREAD(thread_checker_);
+  auto* consumer = FindConsumer(session_id);
+  if (!consumer || consumer->trace_config_) {
+    PERFETTO_ELOG("Cannot attach a tracing session that was set up");
+    callback(false);
+    return;
+  }
+  PERFETTO_DCHECK(!consumer->attach_callback_);
+  consumer->attach_callback_ = std::move(callback);
+  if (!consumer->connected_) {
+    consumer->session_to_attach_ = key;
+    return;
+  }
+  consumer->service_->Attach(key);
+}
+
 void TracingMuxerImpl::ChangeTracingSessionConfig(
     TracingSessionGlobalID session_id,
     const TraceConfig& trace_config) {
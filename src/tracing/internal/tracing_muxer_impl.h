@@ -172,6 +172,12 @@ class TracingMuxerImpl : public TracingMuxer {
   void CloneTracingSession(TracingSessionGlobalID,
                            TracingSession::CloneTraceArgs,
                            TracingSession::CloneTraceCallback);
+  void DetachTracingSession(TracingSessionGlobalID,
+                            const std::string& key,
+                            TracingSession::DetachCallback);
+  void AttachTracingSession(TracingSessionGlobalID,
+                            const std::string& key,
+                            TracingSession::AttachCallback);
   void ChangeTracingSessionConfig(TracingSessionGlobalID, const TraceConfig&);
   void StopTracingSession(TracingSessionGlobalID);
   void DestroyTracingSession(TracingSessionGlobalID);
@@ -341,6 +347,10 @@ class TracingMuxerImpl : public TracingMuxer {
     // cloning another sesison before the consumer was connected.
     std::optional<ConsumerEndpoint::CloneSessionArgs> session_to_clone_;
 
+    // Similarly we need to buffer the key of the session to attach to if the
+    // consumer wasn't connected yet.
+    std::optional<std::string> session_to_attach_;
+
     // Whether this session was already stopped. This will happen in response to
     // Stop{,Blocking}, but also if the service stops the session for us
     // automatically (e.g., when there are no data sources).
@@ -372,6 +382,10 @@ class TracingMuxerImpl : public TracingMuxer {
     // Callback for a pending call to CloneTrace().
     TracingSession::CloneTraceCallback clone_trace_callback_;
 
+    // Callbacks for pending calls to Detach() and Attach().
+    TracingSession::DetachCallback detach_callback_;
+    TracingSession::AttachCallback attach_callback_;
+
     // Callback passed to ReadTrace().
     std::function<void(TracingSession::ReadTraceCallbackArgs)>
         read_trace_callback_;
@@ -401,6 +415,8 @@ class TracingMuxerImpl : public TracingMuxer {
     void Start() override;
     void StartBlocking() override;
     void CloneTrace(CloneTraceArgs args, CloneTraceCallback) override;
+    void Detach(const std::string& key, DetachCallback) override;
+    void Attach(const std::string& key, AttachCallback) override;
     void SetOnStartCallback(std::function<void()>) override;
     void SetOnErrorCallback(std::function<void(TracingError)>) override;
     void Stop() override;
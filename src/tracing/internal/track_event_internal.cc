@@ -351,11 +351,22 @@ bool TrackEventInternal::IsCategoryEnabled(
     return result;
   }
 
+  size_t num_tags = 0;
+  while (num_tags < category.tags.size() && category.tags[num_tags])
+    num_tags++;
+  return IsSingleCategoryEnabled(config, category.name, category.tags.data(),
+                                 num_tags);
+}
+
+// static
+bool TrackEventInternal::IsSingleCategoryEnabled(
+    const protos::gen::TrackEventConfig& config,
+    const char* name,
+    const char* const* tags,
+    size_t num_tags) {
   auto has_matching_tag = [&](std::function<bool(const char*)> matcher) {
-    for (const auto& tag : category.tags) {
-      if (!tag)
-        break;
-      if (matcher(tag))
+    for (size_t i = 0; i < num_tags; i++) {
+      if (matcher(tags[i]))
         return true;
     }
     return false;
@@ -367,13 +378,12 @@ bool TrackEventInternal::IsCategoryEnabled(
       {MatchType::kExact, MatchType::kPattern, MatchType::kWildcard}};
   for (auto match_type : match_types) {
     // 1. Enabled categories.
-    if (NameMatchesPatternList(config.enabled_categories(), category.name,
-                               match_type)) {
+    if (NameMatchesPatternList(config.enabled_categories(), name, match_type)) {
       return true;
     }
 
     // 2. Disabled categories.
-    if (NameMatchesPatternList(config.disabled_categories(), category.name,
+    if (NameMatchesPatternList(config.disabled_categories(), name,
                                match_type)) {
       return false;
     }
@@ -80,13 +80,14 @@ std::unique_ptr<TracingService::ProducerEndpoint> ProducerIPCClient::Connect(
     size_t shared_memory_page_size_hint_bytes,
     std::unique_ptr<SharedMemory> shm,
     std::unique_ptr<SharedMemoryArbiter> shm_arbiter,
-    CreateSocketAsync create_socket_async) {
+    CreateSocketAsync create_socket_async,
+    bool use_shmem_emulation) {
   return std::unique_ptr<TracingService::ProducerEndpoint>(
       new ProducerIPCClientImpl(
           std::move(conn_args), producer, producer_name, task_runner,
           smb_scraping_mode, shared_memory_size_hint_bytes,
           shared_memory_page_size_hint_bytes, std::move(shm),
-          std::move(shm_arbiter), create_socket_async));
+          std::move(shm_arbiter), create_socket_async, use_shmem_emulation));
 }
 
 ProducerIPCClientImpl::ProducerIPCClientImpl(
@@ -99,7 +100,8 @@ ProducerIPCClientImpl::ProducerIPCClientImpl(
     size_t shared_memory_page_size_hint_bytes,
     std::unique_ptr<SharedMemory> shm,
     std::unique_ptr<SharedMemoryArbiter> shm_arbiter,
-    CreateSocketAsync create_socket_async)
+    CreateSocketAsync create_socket_async,
+    bool use_shmem_emulation)
     : producer_(producer),
       task_runner_(task_runner),
       receive_shmem_fd_cb_fuchsia_(
@@ -111,7 +113,8 @@ ProducerIPCClientImpl::ProducerIPCClientImpl(
       name_(producer_name),
       shared_memory_page_size_hint_bytes_(shared_memory_page_size_hint_bytes),
       shared_memory_size_hint_bytes_(shared_memory_size_hint_bytes),
-      smb_scraping_mode_(smb_scraping_mode) {
+      smb_scraping_mode_(smb_scraping_mode),
+      force_shmem_emulation_(use_shmem_emulation) {
   // Check for producer-provided SMB (used by Chrome for startup tracing).
   if (shared_memory_) {
     // We also expect a valid (unbound) arbiter. Bind it to this endpoint now.
@@ -126,21 +129,8 @@ ProducerIPCClientImpl::ProducerIPCClientImpl(
 
   if (create_socket_async) {
     PERFETTO_DCHECK(conn_args.socket_name);
-    auto weak_this = weak_factory_.GetWeakPtr();
-    create_socket_async(
-        [weak_this, task_runner = task_runner_](base::SocketHandle fd) {
-          task_runner->PostTask([weak_this, fd] {
-            base::ScopedSocketHandle handle(fd);
-            if (!weak_this) {
-              return;
-            }
-            ipc::Client::ConnArgs args(std::move(handle));
-            weak_this->ipc_channel_ = ipc::Client::CreateInstance(
-                std::move(args), weak_this->task_runner_);
-            weak_this->ipc_channel_->BindService(
-                weak_this->producer_port_->GetWeakPtr());
-          });
-        });
+    create_socket_async_ = create_socket_async;
+    CreateSocket();
   } else {
     ipc_channel_ =
         ipc::Client::CreateInstance(std::move(conn_args), task_runner);
@@ -149,6 +139,41 @@ ProducerIPCClientImpl::ProducerIPCClientImpl(
   PERFETTO_DCHECK_THREAD(thread_checker_);
 }
 
+void ProducerIPCClientImpl::CreateSocket() {
+  auto weak_this = weak_factory_.GetWeakPtr();
+  create_socket_async_(
+      [weak_this, task_runner = task_runner_](base::SocketHandle fd) {
+        task_runner->PostTask([weak_this, fd] {
+          base::ScopedSocketHandle handle(fd);
+          if (!weak_this) {
+            return;
+          }
+          if (!handle) {
+            // The socket couldn't be connected, e.g. because the service
+            // isn't up yet. Retry with the same backoff as ipc::Client.
+            weak_this->create_socket_backoff_ms_ =
+                (weak_this->create_socket_backoff_ms_ < 10000)
+                    ? weak_this->create_socket_backoff_ms_ + 1000
+                    : 30000;
+            PERFETTO_DLOG("Creating the socket failed, retrying in %u seconds",
+                          weak_this->create_socket_backoff_ms_ / 1000);
+            weak_this->task_runner_->PostDelayedTask(
+                [weak_this] {
+                  if (weak_this)
+                    weak_this->CreateSocket();
+                },
+                weak_this->create_socket_backoff_ms_);
+            return;
+          }
+          ipc::Client::ConnArgs args(std::move(handle));
+          weak_this->ipc_channel_ = ipc::Client::CreateInstance(
+              std::move(args), weak_this->task_runner_);
+          weak_this->ipc_channel_->BindService(
+              weak_this->producer_port_->GetWeakPtr());
+        });
+      });
+}
+
 ProducerIPCClientImpl::~ProducerIPCClientImpl() {
   PERFETTO_DCHECK_THREAD(thread_checker_);
 }
@@ -215,6 +240,9 @@ void ProducerIPCClientImpl::OnConnect() {
 #endif
   }
 
+  if (force_shmem_emulation_ && !shared_memory_)
+    req.set_use_shmem_emulation(true);
+
   req.set_sdk_version(base::GetVersionString());
   producer_port_->InitializeConnection(req, std::move(on_init), shm_fd);
 
@@ -277,7 +305,10 @@ void ProducerIPCClientImpl::OnConnectionInitialized(
   // commit data over the socket. This can happen when the client connects to
   // the service via a relay service:
   // client <-Unix socket-> relay service <- vsock -> tracing service.
-  use_shmem_emulation_ = use_shmem_emulation;
+  // The client can also choose to, unless it provided the SMB itself.
+  use_shmem_emulation_ =
+      use_shmem_emulation ||
+      (force_shmem_emulation_ && !is_shmem_provided_by_producer_);
   producer_->OnConnect();
 
   // Bail out if the service failed to adopt our producer-allocated SMB.
@@ -327,7 +358,7 @@ void ProducerIPCClientImpl::OnServiceRequest(
     std::unique_ptr<SharedMemory> ipc_shared_memory;
 #if PERFETTO_BUILDFLAG(PERFETTO_OS_WIN)
     const std::string& shm_key = cmd.setup_tracing().shm_key_windows();
-    if (!shm_key.empty())
+    if (!shm_key.empty() && !use_shmem_emulation_)
       ipc_shared_memory = SharedMemoryWindows::Attach(shm_key);
 #elif PERFETTO_BUILDFLAG(PERFETTO_OS_FUCHSIA)
     // On Fuchsia, the embedder is responsible for routing the shared memory
@@ -348,13 +379,22 @@ void ProducerIPCClientImpl::OnServiceRequest(
                                       /*require_seals_if_supported=*/false);
 #else
     base::ScopedFile shmem_fd = ipc_channel_->TakeReceivedFD();
-    if (shmem_fd) {
-      // TODO(primiano): handle mmap failure in case of OOM.
+    if (shmem_fd && !use_shmem_emulation_) {
       ipc_shared_memory =
           PosixSharedMemory::AttachToFd(std::move(shmem_fd),
                                         /*require_seals_if_supported=*/false);
     }
 #endif
+    if (!ipc_shared_memory && !use_shmem_emulation_ &&
+        !is_shmem_provided_by_producer_) {
+      // The SMB couldn't be mapped, e.g. because the producer is sandboxed or
+      // out of memory. The service accepts chunk data over the socket too, but
+      // unlike with InitializeConnectionRequest.use_shmem_emulation it doesn't
+      // know that it has to flush this producer to get the data of
+      // non-flushable data sources.
+      PERFETTO_ELOG("Could not map the SMB, falling back to SMB emulation.");
+      use_shmem_emulation_ = true;
+    }
     if (use_shmem_emulation_) {
       PERFETTO_CHECK(!ipc_shared_memory);
       // Need to create an emulated shmem buffer when the transport doesn't
@@ -552,6 +592,23 @@ void ProducerIPCClientImpl::NotifyDataSourceStopped(DataSourceInstanceID id) {
       req, ipc::Deferred<protos::gen::NotifyDataSourceStoppedResponse>());
 }
 
+void ProducerIPCClientImpl::NotifyDataSourceSetupFailed(
+    DataSourceInstanceID id,
+    const std::string& error) {
+  PERFETTO_DCHECK_THREAD(thread_checker_);
+  if (!connected_) {
+    PERFETTO_DLOG(
+        "Cannot NotifyDataSourceSetupFailed(), not connected to tracing "
+        "service");
+    return;
+  }
+  protos::gen::NotifyDataSourceSetupFailedRequest req;
+  req.set_data_source_id(id);
+  req.set_error(error);
+  producer_port_->NotifyDataSourceSetupFailed(
+      req, ipc::Deferred<protos::gen::NotifyDataSourceSetupFailedResponse>());
+}
+
 void ProducerIPCClientImpl::ActivateTriggers(
     const std::vector<std::string>& triggers) {
   PERFETTO_DCHECK_THREAD(thread_checker_);
@@ -60,7 +60,8 @@ class ProducerIPCClientImpl : public TracingService::ProducerEndpoint,
                         size_t shared_memory_page_size_hint_bytes,
                         std::unique_ptr<SharedMemory> shm,
                         std::unique_ptr<SharedMemoryArbiter> shm_arbiter,
-                        CreateSocketAsync create_socket_async);
+                        CreateSocketAsync create_socket_async,
+                        bool use_shmem_emulation = false);
   ~ProducerIPCClientImpl() override;
 
   // TracingService::ProducerEndpoint implementation.
@@ -75,6 +76,8 @@ class ProducerIPCClientImpl : public TracingService::ProducerEndpoint,
   void CommitData(const CommitDataRequest&, CommitDataCallback) override;
   void NotifyDataSourceStarted(DataSourceInstanceID) override;
   void NotifyDataSourceStopped(DataSourceInstanceID) override;
+  void NotifyDataSourceSetupFailed(DataSourceInstanceID,
+                                   const std::string& error) override;
   void ActivateTriggers(const std::vector<std::string>&) override;
   void Sync(std::function<void()> callback) override;
 
@@ -100,6 +103,10 @@ class ProducerIPCClientImpl : public TracingService::ProducerEndpoint,
   // processing an IPC command.
   void ScheduleDisconnect();
 
+  // Connects |ipc_channel_| to the socket created by |create_socket_async_|,
+  // retrying with backoff while it fails.
+  void CreateSocket();
+
   // Invoked soon after having established the connection with the service.
   void OnConnectionInitialized(bool connection_succeeded,
                                bool using_shmem_provided_by_producer,
@@ -120,6 +127,9 @@ class ProducerIPCClientImpl : public TracingService::ProducerEndpoint,
   // The object that owns the client socket and takes care of IPC traffic.
   std::unique_ptr<ipc::Client> ipc_channel_;
 
+  CreateSocketAsync create_socket_async_ = nullptr;
+  uint32_t create_socket_backoff_ms_ = 0;
+
   // The proxy interface for the producer port of the service. It is bound
   // to |ipc_channel_| and (de)serializes method invocations over the wire.
   std::unique_ptr<protos::gen::ProducerPortProxy> producer_port_;
@@ -143,6 +153,8 @@ class ProducerIPCClientImpl : public TracingService::ProducerEndpoint,
   bool is_shmem_provided_by_producer_ = false;
   bool direct_smb_patching_supported_ = false;
   bool use_shmem_emulation_ = false;
+  // Set if the producer asked for SMB emulation regardless of the service.
+  bool const force_shmem_emulation_;
   std::vector<std::function<void()>> pending_sync_reqs_;
   base::WeakPtrFactory<ProducerIPCClientImpl> weak_factory_{this};
   PERFETTO_THREAD_CHECKER(thread_checker_)
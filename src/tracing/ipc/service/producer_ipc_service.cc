@@ -125,7 +125,8 @@ void ProducerIPCService::InitializeConnection(
       req.shared_memory_size_hint_bytes(),
       /*in_process=*/false, smb_scraping_mode,
       req.shared_memory_page_size_hint_bytes(), std::move(shmem),
-      req.sdk_version(), client_info.machine_name());
+      req.sdk_version(), client_info.machine_name(),
+      req.use_shmem_emulation());
 
   // Could happen if the service has too many producers connected.
   if (!producer->service_endpoint) {
@@ -133,7 +134,8 @@ void ProducerIPCService::InitializeConnection(
     return;
   }
 
-  bool use_shmem_emulation = ipc::Service::use_shmem_emulation();
+  bool use_shmem_emulation =
+      ipc::Service::use_shmem_emulation() || req.use_shmem_emulation();
   bool using_producer_shmem =
       !use_shmem_emulation &&
       producer->service_endpoint->IsShmemProvidedByProducer();
@@ -343,6 +345,30 @@ void ProducerIPCService::NotifyDataSourceStopped(
   }
 }
 
+void ProducerIPCService::NotifyDataSourceSetupFailed(
+    const protos::gen::NotifyDataSourceSetupFailedRequest& request,
+    DeferredNotifyDataSourceSetupFailedResponse response) {
+  RemoteProducer* producer = GetProducerForCurrentRequest();
+  if (!producer) {
+    PERFETTO_DLOG(
+        "Producer invoked NotifyDataSourceSetupFailed() before "
+        "InitializeConnection()");
+    if (response.IsBound())
+      response.Reject();
+    return;
+  }
+  producer->service_endpoint->NotifyDataSourceSetupFailed(
+      request.data_source_id(), request.error());
+
+  // NotifyDataSourceSetupFailed shouldn't expect any meaningful response,
+  // avoid a useless IPC in that case.
+  if (response.IsBound()) {
+    response.Resolve(
+        ipc::AsyncResult<
+            protos::gen::NotifyDataSourceSetupFailedResponse>::Create());
+  }
+}
+
 void ProducerIPCService::ActivateTriggers(
     const protos::gen::ActivateTriggersRequest& proto_req,
     DeferredActivateTriggersResponse resp) {
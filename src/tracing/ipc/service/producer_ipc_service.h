@@ -64,6 +64,9 @@ class ProducerIPCService : public protos::gen::ProducerPort {
   void NotifyDataSourceStopped(
       const protos::gen::NotifyDataSourceStoppedRequest&,
       DeferredNotifyDataSourceStoppedResponse) override;
+  void NotifyDataSourceSetupFailed(
+      const protos::gen::NotifyDataSourceSetupFailedRequest&,
+      DeferredNotifyDataSourceSetupFailedResponse) override;
   void ActivateTriggers(const protos::gen::ActivateTriggersRequest&,
                         DeferredActivateTriggersResponse) override;
 
@@ -692,6 +692,13 @@ void ProducerEndpointImpl::NotifyDataSourceStopped(
   service_->NotifyDataSourceStopped(id_, data_source_id);
 }
 
+void ProducerEndpointImpl::NotifyDataSourceSetupFailed(
+    DataSourceInstanceID data_source_id,
+    const std::string& error) {
+  PERFETTO_DCHECK_THREAD(thread_checker_);
+  service_->NotifyDataSourceSetupFailed(id_, data_source_id, error);
+}
+
 void ProducerEndpointImpl::OnFreeBuffers(
     const std::vector<BufferID>& target_buffers) {
   if (allowed_target_buffers_.empty())
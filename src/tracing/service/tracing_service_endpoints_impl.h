@@ -89,6 +89,8 @@ class ProducerEndpointImpl : public TracingService::ProducerEndpoint {
   void NotifyFlushComplete(FlushRequestID) override;
   void NotifyDataSourceStarted(DataSourceInstanceID) override;
   void NotifyDataSourceStopped(DataSourceInstanceID) override;
+  void NotifyDataSourceSetupFailed(DataSourceInstanceID,
+                                   const std::string& error) override;
   SharedMemory* shared_memory() const override;
   size_t shared_buffer_page_size_kb() const override;
   void ActivateTriggers(const std::vector<std::string>&) override;
@@ -138,6 +140,8 @@ class ProducerEndpointImpl : public TracingService::ProducerEndpoint {
   size_t shmem_size_hint_bytes_ = 0;
   size_t shmem_page_size_hint_bytes_ = 0;
   bool is_shmem_provided_by_producer_ = false;
+  // Set if the producer commits chunk data over IPC instead of using the SMB.
+  bool shmem_emulation_requested_ = false;
   const std::string name_;
   const std::string machine_name_;
   std::string sdk_version_;
@@ -389,7 +389,8 @@ TracingServiceImpl::ConnectProducer(Producer* producer,
                                     size_t shared_memory_page_size_hint_bytes,
                                     std::unique_ptr<SharedMemory> shm,
                                     const std::string& sdk_version,
-                                    const std::string& machine_name) {
+                                    const std::string& machine_name,
+                                    bool use_shmem_emulation) {
   PERFETTO_DCHECK_THREAD(thread_checker_);
 
   auto uid = client_identity.uid();
@@ -437,6 +438,7 @@ TracingServiceImpl::ConnectProducer(Producer* producer,
 
   endpoint->shmem_size_hint_bytes_ = shared_memory_size_hint_bytes;
   endpoint->shmem_page_size_hint_bytes_ = shared_memory_page_size_hint_bytes;
+  endpoint->shmem_emulation_requested_ = use_shmem_emulation;
 
   // Producer::OnConnect() should run before Producer::OnTracingSetup(). The
   // latter may be posted by SetupSharedMemory() below, so post OnConnect() now.
@@ -1886,6 +1888,25 @@ void TracingServiceImpl::NotifyDataSourceStopped(
   }  // for (tracing_session)
 }
 
+void TracingServiceImpl::NotifyDataSourceSetupFailed(
+    ProducerID producer_id,
+    DataSourceInstanceID instance_id,
+    const std::string& error) {
+  PERFETTO_DCHECK_THREAD(thread_checker_);
+  for (auto& kv : tracing_sessions_) {
+    TracingSession& tracing_session = kv.second;
+    DataSourceInstance* instance =
+        tracing_session.GetDataSourceInstance(producer_id, instance_id);
+
+    if (!instance)
+      continue;
+
+    PERFETTO_ELOG("Data source \"%s\" failed to set up: %s",
+                  instance->data_source_name.c_str(), error.c_str());
+    tracing_session.data_source_setup_failures++;
+  }  // for (tracing_session)
+}
+
 void TracingServiceImpl::ActivateTriggers(
     ProducerID producer_id,
     const std::vector<std::string>& triggers) {
@@ -3531,7 +3552,9 @@ DataSourceInstance* TracingServiceImpl::SetupDataSource(
     // physical memory.
     auto shared_memory = shm_factory_->CreateSharedMemory(shm_size);
     auto shmem_mode =
-        GetShmemMode(producer->client_identity(), producer->in_process_);
+        producer->shmem_emulation_requested_
+            ? SharedMemoryABI::ShmemMode::kShmemEmulation
+            : GetShmemMode(producer->client_identity(), producer->in_process_);
     producer->SetupSharedMemory(std::move(shared_memory), page_size,
                                 /*provided_by_producer=*/false, shmem_mode);
   }
@@ -4035,6 +4058,8 @@ TraceStats TracingServiceImpl::GetTraceStats(TracingSession* tracing_session) {
   trace_stats.set_flushes_succeeded(tracing_session->flushes_succeeded);
   trace_stats.set_flushes_failed(tracing_session->flushes_failed);
   trace_stats.set_final_flush_outcome(tracing_session->final_flush_outcome);
+  trace_stats.set_data_source_setup_failures(
+      tracing_session->data_source_setup_failures);
 
   if (tracing_session->trace_filter) {
     auto* filt_stats = trace_stats.mutable_filter_stats();
@@ -4977,6 +5002,7 @@ base::Status TracingServiceImpl::FinishCloneSession(
   cloned_session->flushes_requested = src->flushes_requested;
   cloned_session->flushes_succeeded = src->flushes_succeeded;
   cloned_session->flushes_failed = src->flushes_failed;
+  cloned_session->data_source_setup_failures = src->data_source_setup_failures;
   if (src->trace_filter && !skip_trace_filter) {
     // Copy the trace filter, unless it's a clone-for-bugreport (b/317065412).
     cloned_session->trace_filter.reset(
@@ -110,6 +110,9 @@ class TracingServiceImpl : public TracingService {
   void NotifyFlushDoneForProducer(ProducerID, FlushRequestID);
   void NotifyDataSourceStarted(ProducerID, DataSourceInstanceID);
   void NotifyDataSourceStopped(ProducerID, DataSourceInstanceID);
+  void NotifyDataSourceSetupFailed(ProducerID,
+                                   DataSourceInstanceID,
+                                   const std::string& error);
   void ActivateTriggers(ProducerID, const std::vector<std::string>& triggers);
 
   // Called by ConsumerEndpointImpl.
@@ -174,7 +177,8 @@ class TracingServiceImpl : public TracingService {
       size_t shared_memory_page_size_hint_bytes = 0,
       std::unique_ptr<SharedMemory> shm = nullptr,
       const std::string& sdk_version = {},
-      const std::string& machine_name = {}) override;
+      const std::string& machine_name = {},
+      bool use_shmem_emulation = false) override;
 
   std::unique_ptr<TracingService::ConsumerEndpoint> ConnectConsumer(
       Consumer*,
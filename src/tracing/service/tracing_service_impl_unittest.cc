@@ -4060,6 +4060,52 @@ TEST_F(TracingServiceImplTest, NoFlush) {
   consumer->DisableTracing();
 }
 
+TEST_F(TracingServiceImplTest, NoFlushWithShmemEmulation) {
+  svc->SetSMBScrapingEnabled(true);
+
+  std::unique_ptr<MockConsumer> consumer = CreateMockConsumer();
+  consumer->Connect(svc.get());
+
+  // Commits its chunks over IPC, like a sandboxed producer that can't map the
+  // SMB provided by the service.
+  std::unique_ptr<MockProducer> producer_1 = CreateMockProducer();
+  producer_1->Connect(svc.get(), "mock_producer_1", /*uid=*/42, /*pid=*/1025,
+                      kDefaultMachineID, /*machine_name=*/{},
+                      /*shared_memory_size_hint_bytes=*/0,
+                      /*shared_memory_page_size_hint_bytes=*/0,
+                      /*shm=*/nullptr, /*in_process=*/false,
+                      /*use_shmem_emulation=*/true);
+  producer_1->RegisterDataSource("ds_noflush", false, false, false,
+                                 /*no_flush=*/true);
+
+  std::unique_ptr<MockProducer> producer_2 = CreateMockProducer();
+  producer_2->Connect(svc.get(), "mock_producer_2");
+  producer_2->RegisterDataSource("ds_noflush", false, false, false,
+                                 /*no_flush=*/true);
+
+  TraceConfig trace_config;
+  trace_config.add_buffers()->set_size_kb(128);
+  trace_config.add_data_sources()->mutable_config()->set_name("ds_noflush");
+
+  consumer->EnableTracing(trace_config);
+  producer_1->WaitForTracingSetup();
+  producer_1->WaitForDataSourceSetup("ds_noflush");
+  producer_1->WaitForDataSourceStart("ds_noflush");
+  producer_2->WaitForTracingSetup();
+  producer_2->WaitForDataSourceSetup("ds_noflush");
+  producer_2->WaitForDataSourceStart("ds_noflush");
+
+  // The service can't scrape the emulated SMB of producer_1, so it asks it to
+  // flush even though its data source doesn't support flushes.
+  producer_1->ExpectFlush(nullptr);
+  EXPECT_CALL(*producer_2, Flush(_, _, _, _)).Times(0);
+
+  auto flush_request = consumer->Flush();
+  ASSERT_TRUE(flush_request.WaitForReply());
+
+  consumer->DisableTracing();
+}
+
 TEST_F(TracingServiceImplTest, PeriodicClearIncrementalState) {
   std::unique_ptr<MockConsumer> consumer = CreateMockConsumer();
   consumer->Connect(svc.get());
@@ -5185,6 +5231,46 @@ TEST_F(TracingServiceImplTest, GetTraceStats) {
   consumer->WaitForTracingDisabled();
 }
 
+TEST_F(TracingServiceImplTest, DataSourceSetupFailed) {
+  std::unique_ptr<MockConsumer> consumer = CreateMockConsumer();
+  consumer->Connect(svc.get());
+
+  std::unique_ptr<MockProducer> producer = CreateMockProducer();
+  producer->Connect(svc.get(), "mock_producer");
+  producer->RegisterDataSource("data_source_1");
+  producer->RegisterDataSource("data_source_2");
+
+  TraceConfig trace_config;
+  trace_config.add_buffers()->set_size_kb(128);
+  trace_config.add_data_sources()->mutable_config()->set_name("data_source_1");
+  trace_config.add_data_sources()->mutable_config()->set_name("data_source_2");
+
+  consumer->EnableTracing(trace_config);
+  producer->WaitForTracingSetup();
+  producer->WaitForDataSourceSetup("data_source_1");
+  producer->WaitForDataSourceSetup("data_source_2");
+  producer->WaitForDataSourceStart("data_source_1");
+  producer->WaitForDataSourceStart("data_source_2");
+
+  consumer->GetTraceStats();
+  EXPECT_EQ(consumer->WaitForTraceStats(true).data_source_setup_failures(),
+            0u);
+
+  DataSourceInstanceID id = producer->GetDataSourceInstanceId("data_source_1");
+  producer->endpoint()->NotifyDataSourceSetupFailed(id, "Invalid config");
+  // Unknown instances are ignored.
+  producer->endpoint()->NotifyDataSourceSetupFailed(id + 100, "Unknown");
+
+  consumer->GetTraceStats();
+  EXPECT_EQ(consumer->WaitForTraceStats(true).data_source_setup_failures(),
+            1u);
+
+  consumer->DisableTracing();
+  producer->WaitForDataSourceStop("data_source_1");
+  producer->WaitForDataSourceStop("data_source_2");
+  consumer->WaitForTracingDisabled();
+}
+
 TEST_F(TracingServiceImplTest, TraceWriterStats) {
   std::unique_ptr<MockConsumer> consumer = CreateMockConsumer();
   consumer->Connect(svc.get());
@@ -175,6 +175,9 @@ struct TracingSession {
   uint64_t flushes_succeeded = 0;
   uint64_t flushes_failed = 0;
 
+  // Data source instances that reported a failure in their setup.
+  uint64_t data_source_setup_failures = 0;
+
   // Outcome of the final Flush() done by FlushAndDisableTracing().
   protos::gen::TraceStats_FinalFlushOutcome final_flush_outcome{};
 
@@ -7362,6 +7362,42 @@ TEST_P(PerfettoApiTest, CloneSession) {
   }
 }
 
+TEST_P(PerfettoApiTest, DetachAndAttach) {
+  perfetto::TraceConfig cfg;
+  cfg.add_buffers()->set_size_kb(1024);
+  auto* ds_cfg = cfg.add_data_sources()->mutable_config();
+  ds_cfg->set_name("track_event");
+  perfetto::protos::gen::TrackEventConfig te_cfg;
+  te_cfg.add_disabled_categories("*");
+  te_cfg.add_enabled_categories("test");
+  ds_cfg->set_track_event_config_raw(te_cfg.SerializeAsString());
+  auto* tracing_session = NewTrace(cfg);
+  tracing_session->get()->StartBlocking();
+  TRACE_EVENT_BEGIN("test", "BeforeDetach");
+  TRACE_EVENT_END("test");
+
+  EXPECT_TRUE(tracing_session->get()->DetachBlocking("detach_key"));
+  // The session is no longer owned by this object.
+  EXPECT_FALSE(tracing_session->get()->DetachBlocking("detach_key"));
+
+  // The detached session keeps recording.
+  TRACE_EVENT_BEGIN("test", "WhileDetached");
+  TRACE_EVENT_END("test");
+  perfetto::TrackEvent::Flush();
+
+  sessions_.emplace_back();
+  TestTracingSessionHandle* attached_session = &sessions_.back();
+  attached_session->session =
+      perfetto::Tracing::NewTrace(/*backend_type=*/GetParam());
+  EXPECT_FALSE(attached_session->get()->AttachBlocking("other_key"));
+  ASSERT_TRUE(attached_session->get()->AttachBlocking("detach_key"));
+
+  std::vector<char> raw_trace = StopSessionAndReturnBytes(attached_session);
+  std::string trace(raw_trace.data(), raw_trace.size());
+  EXPECT_THAT(trace, HasSubstr("BeforeDetach"));
+  EXPECT_THAT(trace, HasSubstr("WhileDetached"));
+}
+
 class PerfettoStartupTracingApiTest : public PerfettoApiTest {
  public:
   using SetupStartupTracingOpts = perfetto::Tracing::SetupStartupTracingOpts;
@@ -75,7 +75,8 @@ void MockProducer::Connect(TracingService* svc,
                            size_t shared_memory_size_hint_bytes,
                            size_t shared_memory_page_size_hint_bytes,
                            std::unique_ptr<SharedMemory> shm,
-                           bool in_process) {
+                           bool in_process,
+                           bool use_shmem_emulation) {
   producer_name_ = producer_name;
   service_endpoint_ =
       svc->ConnectProducer(this, ClientIdentity(uid, pid, machine_id),
@@ -83,7 +84,8 @@ void MockProducer::Connect(TracingService* svc,
                            /*in_process=*/in_process,
                            TracingService::ProducerSMBScrapingMode::kDefault,
                            shared_memory_page_size_hint_bytes, std::move(shm),
-                           /*sdk_version=*/{}, machine_name);
+                           /*sdk_version=*/{}, machine_name,
+                           use_shmem_emulation);
   auto checkpoint_name = "on_producer_connect_" + producer_name;
   auto on_connect = task_runner_->CreateCheckpoint(checkpoint_name);
   EXPECT_CALL(*this, OnConnect()).WillOnce(on_connect);
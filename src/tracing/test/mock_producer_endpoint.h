@@ -58,6 +58,10 @@ class MockProducerEndpoint : public TracingService::ProducerEndpoint {
               NotifyDataSourceStopped,
               (DataSourceInstanceID),
               (override));
+  MOCK_METHOD(void,
+              NotifyDataSourceSetupFailed,
+              (DataSourceInstanceID, const std::string&),
+              (override));
   MOCK_METHOD(void,
               ActivateTriggers,
               (const std::vector<std::string>&),
@@ -117,6 +117,14 @@ void ProxyProducerEndpoint::NotifyDataSourceStopped(DataSourceInstanceID id) {
   }
   backend_->NotifyDataSourceStopped(id);
 }
+void ProxyProducerEndpoint::NotifyDataSourceSetupFailed(
+    DataSourceInstanceID id,
+    const std::string& error) {
+  if (!backend_) {
+    return;
+  }
+  backend_->NotifyDataSourceSetupFailed(id, error);
+}
 void ProxyProducerEndpoint::ActivateTriggers(
     const std::vector<std::string>& triggers) {
   if (!backend_) {
@@ -52,6 +52,8 @@ class ProxyProducerEndpoint : public ProducerEndpoint {
   void NotifyFlushComplete(FlushRequestID) override;
   void NotifyDataSourceStarted(DataSourceInstanceID) override;
   void NotifyDataSourceStopped(DataSourceInstanceID) override;
+  void NotifyDataSourceSetupFailed(DataSourceInstanceID,
+                                   const std::string& error) override;
   void ActivateTriggers(const std::vector<std::string>&) override;
   void Sync(std::function<void()> callback) override;
   // End ProducerEndpoint implementation
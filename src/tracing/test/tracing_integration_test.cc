@@ -32,6 +32,7 @@
 #include "perfetto/tracing/core/trace_config.h"
 #include "src/base/test/test_task_runner.h"
 #include "src/ipc/test/test_socket.h"
+#include "src/tracing/core/in_process_shared_memory.h"
 #include "src/tracing/service/tracing_service_impl.h"
 #include "test/gtest_and_gmock.h"
 
@@ -146,8 +147,12 @@ class TracingIntegrationTest : public ::testing::Test {
 
     // Create and connect a Producer.
     producer_endpoint_ = ProducerIPCClient::Connect(
-        kProducerSock.name(), &producer_, "perfetto.mock_producer",
-        task_runner_.get(), GetProducerSMBScrapingMode());
+        ipc::Client::ConnArgs(kProducerSock.name(), /*sock_retry=*/false),
+        &producer_, "perfetto.mock_producer", task_runner_.get(),
+        GetProducerSMBScrapingMode(), /*shared_memory_size_hint_bytes=*/0,
+        /*shared_memory_page_size_hint_bytes=*/0, /*shm=*/nullptr,
+        /*shm_arbiter=*/nullptr, /*create_socket_async=*/nullptr,
+        UseShmemEmulation());
     auto on_producer_connect =
         task_runner_->CreateCheckpoint("on_producer_connect");
     EXPECT_CALL(producer_, OnConnect()).WillOnce(on_producer_connect);
@@ -198,6 +203,8 @@ class TracingIntegrationTest : public ::testing::Test {
     return TracingService::ProducerSMBScrapingMode::kDefault;
   }
 
+  virtual bool UseShmemEmulation() { return false; }
+
   std::unique_ptr<base::TestTaskRunner> task_runner_;
   std::unique_ptr<ServiceIPCHost> svc_;
   std::unique_ptr<TracingService::ProducerEndpoint> producer_endpoint_;
@@ -544,6 +551,82 @@ TEST_F(TracingIntegrationTestWithSMBScrapingProducer, ScrapeOnFlush) {
   task_runner_->RunUntilCheckpoint("on_tracing_disabled");
 }
 
+class TracingIntegrationTestWithShmemEmulation
+    : public TracingIntegrationTest {
+ public:
+  bool UseShmemEmulation() override { return true; }
+};
+
+TEST_F(TracingIntegrationTestWithShmemEmulation, CommitDataOverIPC) {
+  // Start tracing.
+  TraceConfig trace_config;
+  trace_config.add_buffers()->set_size_kb(4096 * 10);
+  auto* ds_config = trace_config.add_data_sources()->mutable_config();
+  ds_config->set_name("perfetto.test");
+  ds_config->set_target_buffer(0);
+  consumer_endpoint_->EnableTracing(trace_config);
+
+  BufferID global_buf_id = 0;
+  auto on_create_ds_instance =
+      task_runner_->CreateCheckpoint("on_create_ds_instance");
+  EXPECT_CALL(producer_, OnTracingSetup());
+  EXPECT_CALL(producer_, SetupDataSource(_, _));
+  EXPECT_CALL(producer_, StartDataSource(_, _))
+      .WillOnce([on_create_ds_instance, &global_buf_id](
+                    DataSourceInstanceID, const DataSourceConfig& cfg) {
+        global_buf_id = static_cast<BufferID>(cfg.target_buffer());
+        on_create_ds_instance();
+      });
+  task_runner_->RunUntilCheckpoint("on_create_ds_instance");
+
+  // The producer doesn't map the SMB of the service, and writes into an
+  // emulated one instead.
+  ASSERT_TRUE(producer_endpoint_->shared_memory());
+  EXPECT_EQ(producer_endpoint_->shared_memory()->size(),
+            InProcessSharedMemory::kShmemEmulationSize);
+
+  std::unique_ptr<TraceWriter> writer = producer_endpoint_->CreateTraceWriter(
+      global_buf_id, BufferExhaustedPolicy::kStall);
+  ASSERT_TRUE(writer);
+  writer->NewTracePacket()->set_for_testing()->set_str("payload1");
+  writer->NewTracePacket()->set_for_testing()->set_str("payload2");
+
+  // The chunk data is committed over the socket.
+  auto on_data_committed = task_runner_->CreateCheckpoint("on_data_committed");
+  writer->Flush(on_data_committed);
+  task_runner_->RunUntilCheckpoint("on_data_committed");
+
+  consumer_endpoint_->ReadBuffers();
+  std::vector<std::string> test_payloads;
+  auto all_packets_rx = task_runner_->CreateCheckpoint("all_packets_rx");
+  EXPECT_CALL(consumer_, OnTracePackets(_, _))
+      .WillRepeatedly([&test_payloads, all_packets_rx](
+                          std::vector<TracePacket>* packets, bool has_more) {
+        for (auto& encoded_packet : *packets) {
+          protos::gen::TracePacket packet;
+          ASSERT_TRUE(
+              packet.ParseFromString(encoded_packet.GetRawBytesForTesting()));
+          if (packet.has_for_testing()) {
+            test_payloads.push_back(packet.for_testing().str());
+          }
+        }
+        if (!has_more)
+          all_packets_rx();
+      });
+  task_runner_->RunUntilCheckpoint("all_packets_rx");
+  EXPECT_THAT(test_payloads, testing::ElementsAre("payload1", "payload2"));
+
+  // Disable tracing.
+  consumer_endpoint_->DisableTracing();
+
+  auto on_tracing_disabled =
+      task_runner_->CreateCheckpoint("on_tracing_disabled");
+  EXPECT_CALL(producer_, StopDataSource(_));
+  EXPECT_CALL(consumer_, OnTracingDisabled(_))
+      .WillOnce(InvokeWithoutArgs(on_tracing_disabled));
+  task_runner_->RunUntilCheckpoint("on_tracing_disabled");
+}
+
 // TODO(primiano): add tests to cover:
 // - unknown fields preserved end-to-end.
 // - >1 data source.
@@ -133,6 +133,38 @@ TracingSession::~TracingSession() = default;
 
 void TracingSession::CloneTrace(CloneTraceArgs, CloneTraceCallback) {}
 
+void TracingSession::Detach(const std::string&, DetachCallback callback) {
+  callback(false);
+}
+
+void TracingSession::Attach(const std::string&, AttachCallback callback) {
+  callback(false);
+}
+
+// Can be called from any thread.
+bool TracingSession::DetachBlocking(const std::string& key) {
+  std::atomic<bool> detach_result;
+  base::WaitableEvent detach_ack;
+  Detach(key, [&detach_ack, &detach_result](bool res) {
+    detach_result = res;
+    detach_ack.Notify();
+  });
+  detach_ack.Wait();
+  return detach_result;
+}
+
+// Can be called from any thread.
+bool TracingSession::AttachBlocking(const std::string& key) {
+  std::atomic<bool> attach_result;
+  base::WaitableEvent attach_ack;
+  Attach(key, [&attach_ack, &attach_result](bool res) {
+    attach_result = res;
+    attach_ack.Notify();
+  });
+  attach_ack.Wait();
+  return attach_result;
+}
+
 // Can be called from any thread.
 bool TracingSession::FlushBlocking(uint32_t timeout_ms) {
   std::atomic<bool> flush_result;